@@ -1,4 +1,4 @@
-use std::any::Any;
+use std::{any::Any, time::Duration};
 
 use actors::{
     actor::{Actor, SupervisionStrategy},
@@ -8,7 +8,6 @@ use actors::{
 };
 use async_trait::async_trait;
 
-#[derive(Clone)]
 pub struct Increment {
     pub value: i64,
 }
@@ -17,7 +16,6 @@ impl Message for Increment {
     type Response = ();
 }
 
-#[derive(Clone)]
 pub struct GetValue {}
 
 impl Message for GetValue {
@@ -61,7 +59,9 @@ impl CounterRef for ActorRef<Counter> {
     }
 
     async fn get_value(&self) -> i64 {
-        self.ask(GetValue {}).await.unwrap()
+        self.send_and_wait(GetValue {}, Duration::from_secs(1))
+            .await
+            .unwrap()
     }
 }
 
@@ -1,5 +1,5 @@
 use core::fmt;
-use std::any::Any;
+use std::{any::Any, time::Duration};
 
 use tokio::sync::oneshot;
 
@@ -29,6 +29,8 @@ where
 {
     SendError(M::Error),
     ReceiveAnswerError(oneshot::error::RecvError),
+    /// The actor did not answer within the given time.
+    Timeout(Duration),
 }
 
 impl<A, M> fmt::Debug for ActorError<A, M>
@@ -40,6 +42,7 @@ where
         match self {
             Self::SendError(why) => write!(f, "SendError: {:?}", why),
             Self::ReceiveAnswerError(why) => write!(f, "ReceiveError: {:?}", why),
+            Self::Timeout(timeout) => write!(f, "Timeout: no answer within {:?}", timeout),
         }
     }
 }
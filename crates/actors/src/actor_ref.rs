@@ -1,4 +1,6 @@
-use tokio::sync::oneshot;
+use std::time::Duration;
+
+use tokio::{sync::oneshot, time};
 
 use crate::{
     actor::{Actor, ActorError},
@@ -43,4 +45,92 @@ impl<A: Actor> ActorRef<A> {
             .await
             .map_err(|why| ActorError::ReceiveAnswerError(why))
     }
+
+    /// Like `ask`, but fails with `ActorError::Timeout`, if the message can not be
+    /// sent or is not answered within the timeout, e.g. as the actor is stuck.
+    pub async fn send_and_wait<M>(
+        &self,
+        msg: M,
+        timeout: Duration,
+    ) -> Result<M::Response, ActorError<A, BoundedMailbox<A>>>
+    where
+        M: Message,
+        A: Handler<M>,
+    {
+        time::timeout(timeout, self.ask(msg))
+            .await
+            .unwrap_or(Err(ActorError::Timeout(timeout)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::run_unsupervised;
+
+    /// A large payload, which can not be cloned.
+    struct Updates(Vec<u64>);
+
+    impl Message for Updates {
+        /// Address of the payload as seen by the handler.
+        type Response = usize;
+    }
+
+    /// Answers after the given delay.
+    struct Sleep(Duration);
+
+    impl Message for Sleep {
+        type Response = ();
+    }
+
+    struct Receiver;
+
+    impl Actor for Receiver {}
+
+    #[async_trait]
+    impl Handler<Updates> for Receiver {
+        async fn handle(&mut self, message: Updates) -> usize {
+            message.0.as_ptr() as usize
+        }
+    }
+
+    #[async_trait]
+    impl Handler<Sleep> for Receiver {
+        async fn handle(&mut self, message: Sleep) {
+            time::sleep(message.0).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn payloads_are_moved_into_the_handler() {
+        let actor_ref = run_unsupervised(Receiver);
+        let updates = Updates((0..100_000).collect());
+        let address = updates.0.as_ptr() as usize;
+        let received = actor_ref
+            .send_and_wait(updates, Duration::from_secs(5))
+            .await
+            .expect("answer");
+        assert_eq!(received, address);
+    }
+
+    #[tokio::test]
+    async fn late_answers_time_out() {
+        let actor_ref = run_unsupervised(Receiver);
+        let timeout = Duration::from_millis(10);
+        let result = actor_ref
+            .send_and_wait(Sleep(Duration::from_secs(1)), timeout)
+            .await;
+        assert!(
+            matches!(result, Err(ActorError::Timeout(after)) if after == timeout),
+            "{:?}",
+            result
+        );
+        // answers in time are received, once the actor is done sleeping.
+        actor_ref
+            .send_and_wait(Sleep(Duration::ZERO), Duration::from_secs(10))
+            .await
+            .expect("answer");
+    }
 }
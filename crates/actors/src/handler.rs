@@ -13,13 +13,17 @@ where
     async fn handle(&mut self, message: M) -> M::Response;
 }
 
-pub trait Message: Clone + Send + Sync + 'static {
-    type Response: Send + Sync + 'static;
+/// A message, which is moved into the handler of the actor. It need not be
+/// `Clone`, so large payloads are never copied.
+pub trait Message: Send + 'static {
+    type Response: Send + 'static;
 }
 
 #[async_trait]
-pub trait MessageHandler<A: Actor>: Send + Sync {
-    async fn handle(&mut self, actor: &mut A);
+pub trait MessageHandler<A: Actor>: Send {
+    /// Passes the message to the handler of the actor. Consumes the message, as
+    /// it is handled exactly once.
+    async fn handle(self: Box<Self>, actor: &mut A);
 }
 
 pub struct ActorMessage<M, A>
@@ -52,10 +56,15 @@ where
     M: Message,
     A: Handler<M>,
 {
-    async fn handle(&mut self, actor: &mut A) {
-        let result = actor.handle(self.message.clone()).await;
+    async fn handle(self: Box<Self>, actor: &mut A) {
+        let Self {
+            message,
+            respond_to,
+            ..
+        } = *self;
+        let result = actor.handle(message).await;
 
-        if let Some(respond_to) = self.respond_to.take() {
+        if let Some(respond_to) = respond_to {
             respond_to
                 .send(result)
                 .unwrap_or_else(|_| log::error!("Can not respond to message!"));
@@ -23,7 +23,7 @@ where
 
     // run actor
     tokio::spawn(async move {
-        while let Some(mut message) = rx.recv().await {
+        while let Some(message) = rx.recv().await {
             // handle message
             let result = AssertUnwindSafe(message.handle(&mut actor))
                 .catch_unwind()
@@ -54,7 +54,7 @@ pub fn run_unsupervised<A: Actor>(mut actor: A) -> ActorRef<A> {
 
     // run actor
     tokio::spawn(async move {
-        while let Some(mut message) = rx.recv().await {
+        while let Some(message) = rx.recv().await {
             message.handle(&mut actor).await;
         }
     });
@@ -0,0 +1,8 @@
+-- lease of the server instance running a collector, so that instances sharing
+-- the database neither run a collector at the same time, nor right after each
+-- other. leases expire, unless they are renewed during the run, so that a
+-- crashed instance does not block the collector.
+ALTER TABLE collectors
+    ADD COLUMN leased_until TIMESTAMPTZ,
+    -- start of the latest run, which also identifies its lease.
+    ADD COLUMN last_run_at TIMESTAMPTZ;
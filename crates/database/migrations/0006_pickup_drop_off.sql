@@ -0,0 +1,10 @@
+CREATE TYPE pickup_drop_off_type as ENUM(
+    'regular',
+    'not_available',
+    'phone_agency',
+    'coordinate_with_driver'
+);
+
+ALTER TABLE stop_times
+    ADD COLUMN pickup_type      pickup_drop_off_type NOT NULL DEFAULT 'regular',
+    ADD COLUMN drop_off_type    pickup_drop_off_type NOT NULL DEFAULT 'regular';
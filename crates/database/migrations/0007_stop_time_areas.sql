@@ -0,0 +1,9 @@
+CREATE TYPE area_kind as ENUM(
+    'location_group',
+    'location'
+);
+
+-- on-demand areas served instead of a stop (gtfs flex)
+ALTER TABLE stop_times
+    ADD COLUMN area_id      TEXT,
+    ADD COLUMN area_kind    area_kind;
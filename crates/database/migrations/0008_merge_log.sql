@@ -0,0 +1,35 @@
+---/------------------------\---
+--|          TYPES          |--
+---\------------------------/---
+
+CREATE TYPE merge_subject AS ENUM('stop');
+
+CREATE TYPE merge_status AS ENUM(
+    'auto_accepted',
+    'pending_review',
+    'confirmed',
+    'rejected'
+);
+
+---/------------------------\---
+--|          TABLES          |--
+---\------------------------/---
+
+-- decisions about merging a subject of an origin into an existing subject.
+-- rejected entries are kept, so that the merge is not proposed again.
+CREATE TABLE merge_log(
+    id              SERIAL PRIMARY KEY,
+    subject         merge_subject NOT NULL,
+    origin          slug NOT NULL REFERENCES origins(id),
+    subject_id      slug NOT NULL,
+    -- original id of the subject, by which it is recognized on later imports.
+    subject_original_id TEXT,
+    candidate_id    slug NOT NULL,
+    similarity      DOUBLE PRECISION NOT NULL,
+    status          merge_status NOT NULL,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    decided_at      TIMESTAMPTZ
+);
+
+CREATE INDEX ON merge_log(status);
+CREATE INDEX ON merge_log(subject, origin, subject_original_id, candidate_id);
@@ -0,0 +1,3 @@
+-- realtime is looked up by trip ids, which the primary key (leading with the
+-- origin) does not support.
+CREATE INDEX ON trip_updates(trip_id, trip_start_date, origin);
@@ -0,0 +1,33 @@
+-- original id mappings and stop times can not exist without the subject they
+-- belong to. Deleting a subject deletes them as well, instead of failing or
+-- leaving them behind.
+
+ALTER TABLE agencies_original_ids
+    DROP CONSTRAINT agencies_original_ids_id_origin_fkey,
+    ADD FOREIGN KEY(id, origin) REFERENCES agencies(id, origin) ON DELETE CASCADE;
+
+ALTER TABLE stops_original_ids
+    DROP CONSTRAINT stops_original_ids_id_origin_fkey,
+    ADD FOREIGN KEY(id, origin) REFERENCES stops(id, origin) ON DELETE CASCADE;
+
+ALTER TABLE shared_mobility_stations_original_ids
+    DROP CONSTRAINT shared_mobility_stations_original_ids_id_origin_fkey,
+    ADD FOREIGN KEY(id, origin) REFERENCES stops(id, origin) ON DELETE CASCADE;
+
+ALTER TABLE lines_original_ids
+    DROP CONSTRAINT lines_original_ids_id_origin_fkey,
+    ADD FOREIGN KEY(id, origin) REFERENCES lines(id, origin) ON DELETE CASCADE;
+
+ALTER TABLE trips_original_ids
+    DROP CONSTRAINT trips_original_ids_id_origin_fkey,
+    ADD FOREIGN KEY(id, origin) REFERENCES trips(id, origin) ON DELETE CASCADE;
+
+ALTER TABLE stop_times
+    DROP CONSTRAINT stop_times_trip_id_origin_fkey,
+    DROP CONSTRAINT stop_times_stop_id_origin_fkey,
+    ADD FOREIGN KEY(trip_id, origin) REFERENCES trips(id, origin) ON DELETE CASCADE,
+    ADD FOREIGN KEY(stop_id, origin) REFERENCES stops(id, origin) ON DELETE CASCADE;
+
+ALTER TABLE vehicles
+    DROP CONSTRAINT vehicles_trip_id_origin_fkey,
+    ADD FOREIGN KEY(trip_id, origin) REFERENCES trips(id, origin) ON DELETE CASCADE;
@@ -0,0 +1,8 @@
+-- last day, at which any trip calls at a stop (or one of its children).
+-- `last_service_date` is NULL, if no trip calls at the stop at all.
+-- Stops without an entry have not been evaluated yet. The summary is refreshed
+-- on schedule import.
+CREATE TABLE stop_service_summary(
+    stop_id             slug PRIMARY KEY,
+    last_service_date   DATE
+);
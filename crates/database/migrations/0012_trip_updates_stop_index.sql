@@ -0,0 +1,3 @@
+-- realtime departures are looked up by the stops within the stop time updates, so
+-- that trips which are not part of the schedule are found as well.
+CREATE INDEX ON trip_updates USING GIN (stop_time_updates jsonb_path_ops);
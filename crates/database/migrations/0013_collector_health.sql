@@ -0,0 +1,4 @@
+-- outcome of the health check, each collector runs before it is first scheduled.
+ALTER TABLE collectors
+    ADD COLUMN healthcheck_at TIMESTAMPTZ,
+    ADD COLUMN healthcheck_error TEXT;
@@ -0,0 +1,9 @@
+-- tags derived from the calendar of a service. Recomputed by the service classifier
+-- after each schedule import.
+CREATE TYPE service_tag AS ENUM('school', 'seasonal');
+
+CREATE TABLE service_tags(
+    service_id      INT NOT NULL,
+    tag             service_tag NOT NULL,
+    PRIMARY KEY(service_id, tag)
+);
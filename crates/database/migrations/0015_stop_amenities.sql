@@ -0,0 +1,3 @@
+-- structured facts about a stop, which used to be encoded in its description
+-- (e.g. the steam permission emoji of db stations).
+ALTER TABLE stops ADD COLUMN amenities TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,22 @@
+-- shapes of the feeds, which describe the path a vehicle travels along. the
+-- points of a shape are stored in 'shapes', the mapping of its original id here.
+CREATE TABLE shapes_original_ids(
+    origin          slug NOT NULL REFERENCES origins(id),
+    original_id     TEXT NOT NULL,
+    id              INT NOT NULL,
+    PRIMARY KEY(original_id, origin)
+);
+
+CREATE INDEX ON shapes_original_ids(id, origin);
+
+CREATE TYPE trip_direction as ENUM(
+    'outbound',
+    'inbound'
+);
+
+ALTER TABLE trips
+    ADD COLUMN shape_id     INT,
+    ADD COLUMN direction    trip_direction;
+
+-- representative shapes are chosen among the shapes of a line's trips
+CREATE INDEX ON trips(line_id) WHERE shape_id IS NOT NULL;
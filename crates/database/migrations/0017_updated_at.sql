@@ -0,0 +1,31 @@
+-- last modification of stops, lines and trips, e.g. for entity tags of the api.
+ALTER TABLE stops ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE lines ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE trips ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+CREATE OR REPLACE FUNCTION set_updated_at()
+RETURNS TRIGGER AS $$
+BEGIN
+    -- collectors upsert unchanged data on every import, which is no modification.
+    IF NEW IS DISTINCT FROM OLD THEN
+        NEW.updated_at := now();
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER before_update_set_stop_updated_at
+BEFORE UPDATE ON stops
+FOR EACH ROW
+EXECUTE FUNCTION set_updated_at();
+
+CREATE TRIGGER before_update_set_line_updated_at
+BEFORE UPDATE ON lines
+FOR EACH ROW
+EXECUTE FUNCTION set_updated_at();
+
+CREATE TRIGGER before_update_set_trip_updated_at
+BEFORE UPDATE ON trips
+FOR EACH ROW
+EXECUTE FUNCTION set_updated_at();
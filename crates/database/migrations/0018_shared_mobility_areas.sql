@@ -0,0 +1,11 @@
+-- pick-up areas of (virtual) shared mobility stations as GeoJSON geometry.
+ALTER TABLE shared_mobility_stations ADD COLUMN area JSONB;
+ALTER TABLE shared_mobility_stations ADD COLUMN region_id TEXT;
+ALTER TABLE shared_mobility_stations
+    ADD COLUMN is_virtual_station BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- bounding box of the area, to find stations, whose area contains a location.
+ALTER TABLE shared_mobility_stations ADD COLUMN area_min_latitude DOUBLE PRECISION;
+ALTER TABLE shared_mobility_stations ADD COLUMN area_min_longitude DOUBLE PRECISION;
+ALTER TABLE shared_mobility_stations ADD COLUMN area_max_latitude DOUBLE PRECISION;
+ALTER TABLE shared_mobility_stations ADD COLUMN area_max_longitude DOUBLE PRECISION;
@@ -0,0 +1,19 @@
+-- delays of stop times as they were announced over time, e.g. the historic
+-- delays of the db timetables api. Pruned after a retention period.
+CREATE TABLE historic_delays(
+    origin                  slug NOT NULL REFERENCES origins(id),
+    trip_id                 slug NOT NULL,
+    trip_start_date         DATE NOT NULL,
+    scheduled_stop_sequence INTEGER NOT NULL,
+    stop_id                 slug,
+    timestamp               TIMESTAMPTZ NOT NULL,
+    arrival_time            TIMESTAMPTZ,
+    departure_time          TIMESTAMPTZ,
+    cause                   TEXT,
+    source                  TEXT,
+    -- the same delay is part of every response until it is removed.
+    PRIMARY KEY(origin, trip_id, trip_start_date, scheduled_stop_sequence, timestamp)
+);
+
+CREATE INDEX ON historic_delays(trip_id, trip_start_date);
+CREATE INDEX ON historic_delays(trip_start_date);
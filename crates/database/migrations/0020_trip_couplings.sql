@@ -0,0 +1,21 @@
+CREATE TYPE coupling_kind as ENUM(
+    'coupled',
+    'split',
+    'joined'
+);
+
+-- trips, whose vehicles are coupled at a stop, e.g. the wings of the db
+-- timetables api. The coupled trip is referenced by its original id of the same
+-- origin, as it might not be stored yet.
+CREATE TABLE trip_couplings(
+    origin              slug NOT NULL REFERENCES origins(id),
+    trip_id             slug NOT NULL,
+    stop_sequence       INTEGER NOT NULL,
+    stop_id             slug,
+    coupled_original_id TEXT NOT NULL,
+    kind                coupling_kind NOT NULL,
+    PRIMARY KEY(origin, trip_id, stop_sequence, coupled_original_id),
+    FOREIGN KEY(trip_id, origin) REFERENCES trips(id, origin) ON DELETE CASCADE
+);
+
+CREATE INDEX ON trip_couplings(trip_id);
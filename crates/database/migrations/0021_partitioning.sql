@@ -0,0 +1,222 @@
+-- trip updates are partitioned by the month of the trip start date, so that
+-- expired months are dropped as a whole instead of being deleted row by row.
+-- stop times are partitioned by origin, the only column of their primary key,
+-- which does not spread the stop times of a stop over all partitions: stops are
+-- looked up by id, which is shared by the origins merged into the stop. the
+-- partitions of trip update months and of origins are created by the server.
+--
+-- the tables are large, so their rows are not copied here, but moved online by
+-- the server, see partitions.rs of the database crate:
+--  1. begin_partitioning creates an empty partitioned copy `<table>_partitioned`
+--     and mirrors all writes to the table into it.
+--  2. copy_partitioning_batch copies the existing rows in batches of pages.
+--  3. finish_partitioning replaces the table with the copy.
+-- the table stays readable and writable throughout, only the last step locks it
+-- briefly.
+
+-- pages of a table already copied, and the page, at which copying ends. rows
+-- of later pages were written after mirroring began, and are mirrored.
+CREATE TABLE partitioning_progress(
+    table_name      TEXT PRIMARY KEY,
+    next_page       BIGINT NOT NULL,
+    end_page        BIGINT NOT NULL
+);
+
+-- applies the changes of a statement to the partitioned copy of the table.
+-- TG_ARGV[0] is the copy, TG_ARGV[1] its primary key columns.
+CREATE FUNCTION mirror_to_partitioned()
+RETURNS TRIGGER AS $$
+DECLARE
+    copy TEXT := TG_ARGV[0];
+    key_columns TEXT := TG_ARGV[1];
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        EXECUTE format(
+            'DELETE FROM %I c USING old_rows o WHERE (%s) = (%s)',
+            copy,
+            regexp_replace(key_columns, '(\w+)', 'c.\1', 'g'),
+            regexp_replace(key_columns, '(\w+)', 'o.\1', 'g')
+        );
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        EXECUTE format(
+            'INSERT INTO %I SELECT * FROM new_rows ON CONFLICT DO NOTHING',
+            copy
+        );
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- creates the partitioned copy of the table with its columns, indexes and
+-- foreign keys, and a default partition. partitions are created separately, in
+-- the same transaction, so that no mirrored row lands in the default partition
+-- in between. fails, if the copy exists already.
+CREATE FUNCTION begin_partitioning(
+    table_name TEXT,
+    key_columns TEXT,
+    partition_by TEXT
+)
+RETURNS VOID AS $$
+DECLARE
+    copy TEXT := table_name || '_partitioned';
+    fk RECORD;
+BEGIN
+    EXECUTE format(
+        'CREATE TABLE %I (LIKE %I INCLUDING ALL) PARTITION BY %s',
+        copy, table_name, partition_by
+    );
+    EXECUTE format(
+        'CREATE TABLE %I PARTITION OF %I DEFAULT',
+        table_name || '_default', copy
+    );
+    FOR fk IN
+        SELECT conname, pg_get_constraintdef(oid) AS definition
+        FROM pg_constraint
+        WHERE conrelid = table_name::regclass AND contype = 'f'
+    LOOP
+        EXECUTE format(
+            'ALTER TABLE %I ADD CONSTRAINT %I %s',
+            copy, fk.conname, fk.definition
+        );
+    END LOOP;
+
+    EXECUTE format(
+        'CREATE TRIGGER after_insert_mirror_to_partitioned AFTER INSERT ON %I
+        REFERENCING NEW TABLE AS new_rows
+        FOR EACH STATEMENT EXECUTE FUNCTION mirror_to_partitioned(%L, %L)',
+        table_name, copy, key_columns
+    );
+    EXECUTE format(
+        'CREATE TRIGGER after_update_mirror_to_partitioned AFTER UPDATE ON %I
+        REFERENCING OLD TABLE AS old_rows NEW TABLE AS new_rows
+        FOR EACH STATEMENT EXECUTE FUNCTION mirror_to_partitioned(%L, %L)',
+        table_name, copy, key_columns
+    );
+    EXECUTE format(
+        'CREATE TRIGGER after_delete_mirror_to_partitioned AFTER DELETE ON %I
+        REFERENCING OLD TABLE AS old_rows
+        FOR EACH STATEMENT EXECUTE FUNCTION mirror_to_partitioned(%L, %L)',
+        table_name, copy, key_columns
+    );
+    -- creating the triggers blocks writes to the table until the transaction
+    -- ends, so that no row is written behind the end page without being mirrored.
+    INSERT INTO partitioning_progress(table_name, next_page, end_page)
+    VALUES (
+        table_name,
+        0,
+        pg_relation_size(table_name::regclass)
+            / current_setting('block_size')::BIGINT
+    );
+END;
+$$ LANGUAGE plpgsql;
+
+-- copies the rows of the next `pages` pages of the table into its partitioned
+-- copy. returns whether all pages are copied. rows are locked while they are
+-- copied, so that concurrent updates and deletions are mirrored afterwards.
+CREATE FUNCTION copy_partitioning_batch(table_name TEXT, pages BIGINT)
+RETURNS BOOLEAN AS $$
+DECLARE
+    progress partitioning_progress;
+BEGIN
+    SELECT * INTO progress
+    FROM partitioning_progress p
+    WHERE p.table_name = copy_partitioning_batch.table_name
+    FOR UPDATE;
+    IF progress.next_page >= progress.end_page THEN
+        RETURN TRUE;
+    END IF;
+    EXECUTE format(
+        'INSERT INTO %I
+        SELECT * FROM %I
+        WHERE ctid >= %L::tid AND ctid < %L::tid
+        FOR SHARE
+        ON CONFLICT DO NOTHING',
+        table_name || '_partitioned',
+        table_name,
+        format('(%s,0)', progress.next_page),
+        format('(%s,0)', progress.next_page + pages)
+    );
+    UPDATE partitioning_progress p
+    SET next_page = progress.next_page + pages
+    WHERE p.table_name = copy_partitioning_batch.table_name;
+    RETURN progress.next_page + pages >= progress.end_page;
+END;
+$$ LANGUAGE plpgsql;
+
+-- replaces the table with its partitioned copy, once all pages are copied. the
+-- triggers of the table are recreated on the copy. indexes of the copy are
+-- renamed to the names, the indexes of the table would have.
+CREATE FUNCTION finish_partitioning(table_name TEXT)
+RETURNS VOID AS $$
+DECLARE
+    copy TEXT := table_name || '_partitioned';
+    triggers TEXT[];
+    trigger_definition TEXT;
+    index RECORD;
+BEGIN
+    IF NOT copy_partitioning_batch(table_name, 0) THEN
+        RAISE EXCEPTION 'rows of % are not copied yet', table_name;
+    END IF;
+    EXECUTE format('LOCK TABLE %I IN ACCESS EXCLUSIVE MODE', table_name);
+    SELECT array_agg(pg_get_triggerdef(oid))
+    INTO triggers
+    FROM pg_trigger
+    WHERE
+        tgrelid = table_name::regclass
+        AND NOT tgisinternal
+        AND tgname NOT LIKE '%_mirror_to_partitioned';
+
+    EXECUTE format('DROP TABLE %I', table_name);
+    EXECUTE format('ALTER TABLE %I RENAME TO %I', copy, table_name);
+    FOR index IN
+        SELECT c.relname
+        FROM pg_index i JOIN pg_class c ON c.oid = i.indexrelid
+        WHERE i.indrelid = table_name::regclass
+    LOOP
+        EXECUTE format(
+            'ALTER INDEX %I RENAME TO %I',
+            index.relname,
+            regexp_replace(index.relname, '^' || copy, table_name)
+        );
+    END LOOP;
+    FOREACH trigger_definition IN ARRAY COALESCE(triggers, '{}') LOOP
+        EXECUTE trigger_definition;
+    END LOOP;
+    DELETE FROM partitioning_progress p
+    WHERE p.table_name = finish_partitioning.table_name;
+END;
+$$ LANGUAGE plpgsql;
+
+-- creates the partitions of origins of the table partitioned by origin, which
+-- have none yet. partitions are named `<table>_origin_<origin>`. rows of
+-- origins without partition are kept in the default partition, as moving them
+-- would lock the table. their partitions are not created.
+CREATE FUNCTION create_origin_partitions(parent REGCLASS)
+RETURNS VOID AS $$
+DECLARE
+    table_name TEXT := regexp_replace(
+        (SELECT relname FROM pg_class WHERE oid = parent),
+        '_partitioned$',
+        ''
+    );
+    origin TEXT;
+    partition_name TEXT;
+    has_rows BOOLEAN;
+BEGIN
+    FOR origin IN SELECT id FROM origins LOOP
+        partition_name := left(table_name || '_origin_' || origin, 63);
+        CONTINUE WHEN to_regclass(quote_ident(partition_name)) IS NOT NULL;
+        EXECUTE format(
+            'SELECT EXISTS (SELECT 1 FROM %I WHERE origin = %L)',
+            table_name || '_default', origin
+        ) INTO has_rows;
+        IF NOT has_rows THEN
+            EXECUTE format(
+                'CREATE TABLE %I PARTITION OF %s FOR VALUES IN (%L)',
+                partition_name, parent, origin
+            );
+        END IF;
+    END LOOP;
+END;
+$$ LANGUAGE plpgsql;
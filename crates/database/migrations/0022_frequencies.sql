@@ -0,0 +1,14 @@
+-- periods, in which a trip runs repeatedly (gtfs frequencies.txt). Times are
+-- seconds since midnight of the service day, like the times of stop_times.
+CREATE TABLE frequencies(
+    origin          slug NOT NULL REFERENCES origins(id),
+    trip_id         slug NOT NULL,
+    start_time      BIGINT NOT NULL,
+    end_time        BIGINT NOT NULL,
+    headway_secs    INTEGER NOT NULL CHECK (headway_secs > 0),
+    exact_times     BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY(origin, trip_id, start_time),
+    FOREIGN KEY(trip_id, origin) REFERENCES trips(id, origin) ON DELETE CASCADE
+);
+
+CREATE INDEX ON frequencies(trip_id);
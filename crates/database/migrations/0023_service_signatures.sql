@@ -0,0 +1,9 @@
+-- services stored by value, e.g. the single days of the db timetables api, are
+-- shared by all trips with the same windows and dates. The signature is the
+-- normalized calendar of the service, see Service::signature.
+CREATE TABLE service_signatures(
+    origin          slug NOT NULL REFERENCES origins(id),
+    signature       TEXT NOT NULL,
+    service_id      INT NOT NULL,
+    PRIMARY KEY(origin, signature)
+);
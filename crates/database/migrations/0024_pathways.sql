@@ -0,0 +1,47 @@
+-- levels of stations (gtfs levels.txt). ids are those of the feed.
+CREATE TABLE levels(
+    origin          slug NOT NULL REFERENCES origins(id),
+    id              TEXT NOT NULL,
+    level_index     DOUBLE PRECISION NOT NULL,
+    name            TEXT,
+    PRIMARY KEY(origin, id)
+);
+
+CREATE TYPE pathway_mode as ENUM(
+    'walkway',
+    'stairs',
+    'moving_sidewalk',
+    'escalator',
+    'elevator',
+    'fare_gate',
+    'exit_gate'
+);
+
+-- ways within stations (gtfs pathways.txt), e.g. from an entrance to a
+-- platform. ids are those of the feed. the station is the parent of the
+-- locations in the feed, as parents of stops are not imported.
+CREATE TABLE pathways(
+    origin                  slug NOT NULL REFERENCES origins(id),
+    id                      TEXT NOT NULL,
+    from_stop_id            slug NOT NULL,
+    to_stop_id              slug NOT NULL,
+    station_id              slug,
+    from_level_id           TEXT,
+    to_level_id             TEXT,
+    mode                    pathway_mode NOT NULL,
+    is_bidirectional        BOOLEAN NOT NULL,
+    length                  DOUBLE PRECISION,
+    traversal_time          INTEGER,
+    stair_count             INTEGER,
+    max_slope               DOUBLE PRECISION,
+    min_width               DOUBLE PRECISION,
+    signposted_as           TEXT,
+    reversed_signposted_as  TEXT,
+    PRIMARY KEY(origin, id),
+    FOREIGN KEY(from_stop_id, origin) REFERENCES stops(id, origin) ON DELETE CASCADE,
+    FOREIGN KEY(to_stop_id, origin) REFERENCES stops(id, origin) ON DELETE CASCADE
+);
+
+CREATE INDEX ON pathways(station_id);
+CREATE INDEX ON pathways(from_stop_id);
+CREATE INDEX ON pathways(to_stop_id);
@@ -0,0 +1,3 @@
+-- when a collector, which asked to sleep, is run next. kept across restarts.
+ALTER TABLE collectors
+    ADD COLUMN next_run_at TIMESTAMPTZ;
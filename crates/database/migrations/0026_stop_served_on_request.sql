@@ -0,0 +1,5 @@
+-- whether all trips calling at a stop (or one of its children) only stop on
+-- request. false for stops without service. refreshed with the rest of the
+-- summary on schedule import.
+ALTER TABLE stop_service_summary
+    ADD COLUMN served_only_on_request BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,5 @@
+-- finer rail kinds. 'rail' remains for rail of unknown service level. the values
+-- can only be used once this migration is committed, see 0028.
+ALTER TYPE line_type ADD VALUE IF NOT EXISTS 'long_distance_rail';
+ALTER TYPE line_type ADD VALUE IF NOT EXISTS 'regional_rail';
+ALTER TYPE line_type ADD VALUE IF NOT EXISTS 'suburban_rail';
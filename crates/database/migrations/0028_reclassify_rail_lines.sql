@@ -0,0 +1,13 @@
+-- reclassify rail lines named after their train category, e.g. 'RE83' or 'S 1'.
+-- mirrors LineType::of_rail_line_name, other rail lines stay 'rail'.
+UPDATE lines
+SET kind = CASE
+    WHEN name ~* '^\s*(ICE|ICD|IC|EC|ECE|RJ|RJX|NJ|EN|FLX|TGV|WB)\s*\d'
+        THEN 'long_distance_rail'::line_type
+    WHEN name ~* '^\s*(RE|RB|IRE|MEX)\s*\d'
+        THEN 'regional_rail'::line_type
+    WHEN name ~* '^\s*S\s*\d'
+        THEN 'suburban_rail'::line_type
+    ELSE kind
+END
+WHERE kind = 'rail';
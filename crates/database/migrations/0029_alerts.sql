@@ -0,0 +1,13 @@
+-- outcome of the recent runs of a collector, for alerts on repeated failures and
+-- collectors, which stopped delivering data.
+ALTER TABLE collectors
+    ADD COLUMN consecutive_failures INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN last_success_at TIMESTAMPTZ;
+
+-- open incidents of alert rules. an alert is sent by the instance, which opens
+-- the incident, and a recovery by the one, which closes it. survives restarts.
+CREATE TABLE alerts(
+    rule            TEXT PRIMARY KEY,
+    message         TEXT NOT NULL,
+    opened_at       TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
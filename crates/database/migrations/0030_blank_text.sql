@@ -0,0 +1,32 @@
+-- empty and whitespace-only values of optional text columns are no values. they
+-- are no longer written, but existing ones would still win when merging.
+UPDATE agencies SET phone_number = NULL WHERE btrim(phone_number) = '';
+UPDATE agencies SET email = NULL WHERE btrim(email) = '';
+UPDATE agencies SET fare_url = NULL WHERE btrim(fare_url) = '';
+
+UPDATE lines SET name = NULL WHERE btrim(name) = '';
+
+UPDATE stops SET name = NULL WHERE btrim(name) = '';
+UPDATE stops SET description = NULL WHERE btrim(description) = '';
+UPDATE stops SET address = NULL WHERE btrim(address) = '';
+UPDATE stops SET platform_code = NULL WHERE btrim(platform_code) = '';
+
+UPDATE trips SET headsign = NULL WHERE btrim(headsign) = '';
+UPDATE trips SET short_name = NULL WHERE btrim(short_name) = '';
+
+UPDATE stop_times SET stop_headsign = NULL WHERE btrim(stop_headsign) = '';
+
+UPDATE shared_mobility_stations
+SET rental_uri_android = NULL WHERE btrim(rental_uri_android) = '';
+UPDATE shared_mobility_stations
+SET rental_uri_ios = NULL WHERE btrim(rental_uri_ios) = '';
+UPDATE shared_mobility_stations
+SET rental_uri_web = NULL WHERE btrim(rental_uri_web) = '';
+
+UPDATE levels SET name = NULL WHERE btrim(name) = '';
+UPDATE pathways SET signposted_as = NULL WHERE btrim(signposted_as) = '';
+UPDATE pathways
+SET reversed_signposted_as = NULL WHERE btrim(reversed_signposted_as) = '';
+
+UPDATE historic_delays SET cause = NULL WHERE btrim(cause) = '';
+UPDATE historic_delays SET source = NULL WHERE btrim(source) = '';
@@ -0,0 +1,165 @@
+-- changes of stops, lines and trips, so that downstream mirrors can sync
+-- incrementally. changes of stop times are changes of their trip.
+CREATE TYPE change_entity AS ENUM ('stop', 'line', 'trip');
+CREATE TYPE change_operation AS ENUM ('insert', 'update', 'delete');
+
+CREATE TABLE change_log(
+    -- sequence numbers are assigned by a single flush at a time, see
+    -- change_log_pending, so that they become visible in order.
+    id              BIGSERIAL PRIMARY KEY,
+    entity          change_entity NOT NULL,
+    entity_id       TEXT NOT NULL,
+    origin          TEXT NOT NULL,
+    operation       change_operation NOT NULL,
+    changed_at      TIMESTAMPTZ NOT NULL
+);
+
+-- compaction looks up newer entries of the same entity.
+CREATE INDEX ON change_log(entity, entity_id, origin);
+-- compaction and the stop name sync look up entries by time.
+CREATE INDEX ON change_log(changed_at);
+
+-- changes of running transactions, at most one per entity and transaction. the
+-- changes of finished transactions are moved to the change log by the server,
+-- so that sequence numbers of concurrent transactions do not become visible out
+-- of order, without holding back the feed until long transactions finish.
+CREATE TABLE change_log_pending(
+    transaction_id  XID8 NOT NULL DEFAULT pg_current_xact_id(),
+    entity          change_entity NOT NULL,
+    entity_id       TEXT NOT NULL,
+    origin          TEXT NOT NULL,
+    operation       change_operation NOT NULL,
+    changed_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY(transaction_id, entity, entity_id, origin)
+);
+
+-- the operation of an entity changed repeatedly within a transaction, e.g. an
+-- entity inserted and then updated is still inserted.
+CREATE OR REPLACE FUNCTION merge_change_operations(
+    previous change_operation,
+    latest change_operation
+)
+RETURNS change_operation AS $$
+    SELECT CASE
+        WHEN previous = 'insert' AND latest = 'update' THEN previous
+        ELSE latest
+    END;
+$$ LANGUAGE sql IMMUTABLE;
+
+-- statement level triggers log all rows of a statement with a single insert, so
+-- that bulk imports are not slowed down row by row. upserts fire the insert and
+-- the update trigger. updates without any modification are not logged.
+CREATE OR REPLACE FUNCTION log_changes()
+RETURNS TRIGGER AS $$
+DECLARE
+    changed_entity change_entity := TG_ARGV[0];
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        INSERT INTO change_log_pending(entity, entity_id, origin, operation)
+        SELECT DISTINCT changed_entity, n.id, n.origin, 'insert'::change_operation
+        FROM new_rows n
+        ON CONFLICT (transaction_id, entity, entity_id, origin) DO UPDATE
+        SET operation = merge_change_operations(
+            change_log_pending.operation, EXCLUDED.operation
+        );
+    ELSIF TG_OP = 'UPDATE' THEN
+        INSERT INTO change_log_pending(entity, entity_id, origin, operation)
+        SELECT DISTINCT changed_entity, n.id, n.origin, 'update'::change_operation
+        FROM new_rows n
+        JOIN old_rows o ON o.id = n.id AND o.origin = n.origin
+        WHERE n IS DISTINCT FROM o
+        ON CONFLICT (transaction_id, entity, entity_id, origin) DO UPDATE
+        SET operation = merge_change_operations(
+            change_log_pending.operation, EXCLUDED.operation
+        );
+    ELSE
+        INSERT INTO change_log_pending(entity, entity_id, origin, operation)
+        SELECT DISTINCT changed_entity, o.id, o.origin, 'delete'::change_operation
+        FROM old_rows o
+        ON CONFLICT (transaction_id, entity, entity_id, origin) DO UPDATE
+        SET operation = merge_change_operations(
+            change_log_pending.operation, EXCLUDED.operation
+        );
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- stop times are logged as updates of their trip, once per trip and statement.
+-- stop times are replaced by only writing the modified ones, so that trips
+-- imported again without modification are not logged.
+CREATE OR REPLACE FUNCTION log_stop_time_changes()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        INSERT INTO change_log_pending(entity, entity_id, origin, operation)
+        SELECT DISTINCT
+            'trip'::change_entity, n.trip_id, n.origin, 'update'::change_operation
+        FROM new_rows n
+        ON CONFLICT (transaction_id, entity, entity_id, origin) DO NOTHING;
+    ELSIF TG_OP = 'UPDATE' THEN
+        INSERT INTO change_log_pending(entity, entity_id, origin, operation)
+        SELECT DISTINCT
+            'trip'::change_entity, n.trip_id, n.origin, 'update'::change_operation
+        FROM new_rows n
+        JOIN old_rows o
+            ON o.trip_id = n.trip_id
+            AND o.origin = n.origin
+            AND o.stop_sequence = n.stop_sequence
+        WHERE n IS DISTINCT FROM o
+        ON CONFLICT (transaction_id, entity, entity_id, origin) DO NOTHING;
+    ELSE
+        -- stop times of deleted trips are deleted along with them.
+        INSERT INTO change_log_pending(entity, entity_id, origin, operation)
+        SELECT DISTINCT
+            'trip'::change_entity, o.trip_id, o.origin, 'update'::change_operation
+        FROM old_rows o
+        WHERE EXISTS (
+            SELECT 1 FROM trips t WHERE t.id = o.trip_id AND t.origin = o.origin
+        )
+        ON CONFLICT (transaction_id, entity, entity_id, origin) DO NOTHING;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DO $$
+DECLARE
+    target RECORD;
+BEGIN
+    FOR target IN
+        SELECT * FROM (VALUES
+            ('stops', 'stop', 'log_changes(''stop'')'),
+            ('lines', 'line', 'log_changes(''line'')'),
+            ('trips', 'trip', 'log_changes(''trip'')'),
+            ('stop_times', 'stop_time', 'log_stop_time_changes()')
+        ) AS targets(table_name, name, function)
+    LOOP
+        EXECUTE format(
+            'CREATE TRIGGER after_insert_log_%s_changes AFTER INSERT ON %I
+            REFERENCING NEW TABLE AS new_rows
+            FOR EACH STATEMENT EXECUTE FUNCTION %s',
+            target.name, target.table_name, target.function
+        );
+        EXECUTE format(
+            'CREATE TRIGGER after_update_log_%s_changes AFTER UPDATE ON %I
+            REFERENCING OLD TABLE AS old_rows NEW TABLE AS new_rows
+            FOR EACH STATEMENT EXECUTE FUNCTION %s',
+            target.name, target.table_name, target.function
+        );
+        EXECUTE format(
+            'CREATE TRIGGER after_delete_log_%s_changes AFTER DELETE ON %I
+            REFERENCING OLD TABLE AS old_rows
+            FOR EACH STATEMENT EXECUTE FUNCTION %s',
+            target.name, target.table_name, target.function
+        );
+    END LOOP;
+END $$;
+
+-- existing entities, so that a mirror can start from an empty cursor.
+INSERT INTO change_log(entity, entity_id, origin, operation, changed_at)
+SELECT 'stop', id, origin, 'insert', NOW() FROM stops;
+INSERT INTO change_log(entity, entity_id, origin, operation, changed_at)
+SELECT 'line', id, origin, 'insert', NOW() FROM lines;
+INSERT INTO change_log(entity, entity_id, origin, operation, changed_at)
+SELECT 'trip', id, origin, 'insert', NOW() FROM trips;
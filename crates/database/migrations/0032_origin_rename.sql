@@ -0,0 +1,91 @@
+-- origins are renamed by updating their id, which is denormalized into every
+-- table. foreign keys containing the origin follow the update, both the ones
+-- referencing the origin and the ones referencing a subject of the origin.
+DO $$
+DECLARE
+    fk RECORD;
+BEGIN
+    FOR fk IN
+        SELECT
+            conrelid::regclass AS table_name,
+            conname,
+            pg_get_constraintdef(oid) AS definition
+        FROM pg_constraint
+        WHERE
+            contype = 'f'
+            -- constraints of partitions are altered along with their table.
+            AND conparentid = 0
+            AND confupdtype = 'a'
+            AND EXISTS (
+                SELECT 1
+                FROM unnest(conkey) AS k(attnum)
+                JOIN pg_attribute a
+                    ON a.attrelid = conrelid AND a.attnum = k.attnum
+                WHERE a.attname = 'origin'
+            )
+    LOOP
+        EXECUTE format(
+            'ALTER TABLE %s DROP CONSTRAINT %I, ADD CONSTRAINT %I %s ON UPDATE CASCADE',
+            fk.table_name, fk.conname, fk.conname, fk.definition
+        );
+    END LOOP;
+END $$;
+
+-- an update of the id or origin of an entity is logged as the deletion of the
+-- old entity and the insertion of the new one, as mirrors identify entities by
+-- both.
+CREATE OR REPLACE FUNCTION log_changes()
+RETURNS TRIGGER AS $$
+DECLARE
+    changed_entity change_entity := TG_ARGV[0];
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        INSERT INTO change_log_pending(entity, entity_id, origin, operation)
+        SELECT DISTINCT changed_entity, n.id, n.origin, 'insert'::change_operation
+        FROM new_rows n
+        ON CONFLICT (transaction_id, entity, entity_id, origin) DO UPDATE
+        SET operation = merge_change_operations(
+            change_log_pending.operation, EXCLUDED.operation
+        );
+    ELSIF TG_OP = 'UPDATE' THEN
+        INSERT INTO change_log_pending(entity, entity_id, origin, operation)
+        SELECT DISTINCT changed_entity, o.id, o.origin, 'delete'::change_operation
+        FROM old_rows o
+        WHERE NOT EXISTS (
+            SELECT 1 FROM new_rows n WHERE n.id = o.id AND n.origin = o.origin
+        )
+        ON CONFLICT (transaction_id, entity, entity_id, origin) DO UPDATE
+        SET operation = merge_change_operations(
+            change_log_pending.operation, EXCLUDED.operation
+        );
+        INSERT INTO change_log_pending(entity, entity_id, origin, operation)
+        SELECT DISTINCT changed_entity, n.id, n.origin, 'update'::change_operation
+        FROM new_rows n
+        JOIN old_rows o ON o.id = n.id AND o.origin = n.origin
+        WHERE n IS DISTINCT FROM o
+        ON CONFLICT (transaction_id, entity, entity_id, origin) DO UPDATE
+        SET operation = merge_change_operations(
+            change_log_pending.operation, EXCLUDED.operation
+        );
+        INSERT INTO change_log_pending(entity, entity_id, origin, operation)
+        SELECT DISTINCT changed_entity, n.id, n.origin, 'insert'::change_operation
+        FROM new_rows n
+        WHERE NOT EXISTS (
+            SELECT 1 FROM old_rows o WHERE o.id = n.id AND o.origin = n.origin
+        )
+        ON CONFLICT (transaction_id, entity, entity_id, origin) DO UPDATE
+        SET operation = merge_change_operations(
+            change_log_pending.operation, EXCLUDED.operation
+        );
+    ELSE
+        INSERT INTO change_log_pending(entity, entity_id, origin, operation)
+        SELECT DISTINCT changed_entity, o.id, o.origin, 'delete'::change_operation
+        FROM old_rows o
+        ON CONFLICT (transaction_id, entity, entity_id, origin) DO UPDATE
+        SET operation = merge_change_operations(
+            change_log_pending.operation, EXCLUDED.operation
+        );
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,60 @@
+-- names of the stops of the same origin, so that trips can be instantiated with
+-- stop names without looking up the stops. renamed stops are synced lazily by
+-- the server, so the names may lag behind for a bounded time.
+ALTER TABLE stop_times ADD COLUMN stop_name TEXT;
+
+CREATE OR REPLACE FUNCTION set_stop_time_name()
+RETURNS TRIGGER AS $$
+BEGIN
+    NEW.stop_name := (
+        SELECT name FROM stops WHERE id = NEW.stop_id AND origin = NEW.origin
+    );
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER before_write_set_stop_time_name
+BEFORE INSERT OR UPDATE OF stop_id, origin ON stop_times
+FOR EACH ROW
+EXECUTE FUNCTION set_stop_time_name();
+
+-- the synced names are no change of the trip.
+CREATE OR REPLACE FUNCTION log_stop_time_changes()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        INSERT INTO change_log_pending(entity, entity_id, origin, operation)
+        SELECT DISTINCT
+            'trip'::change_entity, n.trip_id, n.origin, 'update'::change_operation
+        FROM new_rows n
+        ON CONFLICT (transaction_id, entity, entity_id, origin) DO NOTHING;
+    ELSIF TG_OP = 'UPDATE' THEN
+        INSERT INTO change_log_pending(entity, entity_id, origin, operation)
+        SELECT DISTINCT
+            'trip'::change_entity, n.trip_id, n.origin, 'update'::change_operation
+        FROM new_rows n
+        JOIN old_rows o
+            ON o.trip_id = n.trip_id
+            AND o.origin = n.origin
+            AND o.stop_sequence = n.stop_sequence
+        WHERE to_jsonb(n) - 'stop_name' IS DISTINCT FROM to_jsonb(o) - 'stop_name'
+        ON CONFLICT (transaction_id, entity, entity_id, origin) DO NOTHING;
+    ELSE
+        -- stop times of deleted trips are deleted along with them.
+        INSERT INTO change_log_pending(entity, entity_id, origin, operation)
+        SELECT DISTINCT
+            'trip'::change_entity, o.trip_id, o.origin, 'update'::change_operation
+        FROM old_rows o
+        WHERE EXISTS (
+            SELECT 1 FROM trips t WHERE t.id = o.trip_id AND t.origin = o.origin
+        )
+        ON CONFLICT (transaction_id, entity, entity_id, origin) DO NOTHING;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+UPDATE stop_times st
+SET stop_name = s.name
+FROM stops s
+WHERE s.id = st.stop_id AND s.origin = st.origin;
@@ -0,0 +1,59 @@
+-- agencies a line is marketed by besides its primary agency, e.g. a
+-- Verkehrsverbund for a line operated by DB Regio.
+CREATE TABLE line_secondary_agencies(
+    origin          slug NOT NULL REFERENCES origins(id) ON UPDATE CASCADE,
+    line_id         slug NOT NULL,
+    agency_id       slug NOT NULL,
+    -- order of the attribution by the origin.
+    position        INT NOT NULL,
+    PRIMARY KEY(line_id, origin, agency_id),
+    FOREIGN KEY(line_id, origin) REFERENCES lines(id, origin)
+        ON UPDATE CASCADE ON DELETE CASCADE,
+    FOREIGN KEY(agency_id, origin) REFERENCES agencies(id, origin)
+        ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE INDEX ON line_secondary_agencies(agency_id, origin);
+
+CREATE OR REPLACE FUNCTION line_secondary_agency_ids(line_id slug, origin slug)
+RETURNS TEXT[] AS $$
+    SELECT COALESCE(array_agg(s.agency_id::TEXT ORDER BY s.position), '{}')
+    FROM line_secondary_agencies s
+    WHERE s.line_id = $1 AND s.origin = $2;
+$$ LANGUAGE sql STABLE;
+
+-- a changed attribution is a modification of the line, which updates its
+-- timestamp and thereby logs the change. lines written in the same transaction
+-- already carry the timestamp.
+CREATE OR REPLACE FUNCTION touch_lines_of_secondary_agencies()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        UPDATE lines l
+        SET updated_at = now()
+        FROM (SELECT DISTINCT line_id, origin FROM old_rows) o
+        WHERE l.id = o.line_id AND l.origin = o.origin AND l.updated_at < now();
+    ELSE
+        UPDATE lines l
+        SET updated_at = now()
+        FROM (SELECT DISTINCT line_id, origin FROM new_rows) n
+        WHERE l.id = n.line_id AND l.origin = n.origin AND l.updated_at < now();
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER after_insert_touch_lines
+AFTER INSERT ON line_secondary_agencies
+REFERENCING NEW TABLE AS new_rows
+FOR EACH STATEMENT EXECUTE FUNCTION touch_lines_of_secondary_agencies();
+
+CREATE TRIGGER after_update_touch_lines
+AFTER UPDATE ON line_secondary_agencies
+REFERENCING NEW TABLE AS new_rows
+FOR EACH STATEMENT EXECUTE FUNCTION touch_lines_of_secondary_agencies();
+
+CREATE TRIGGER after_delete_touch_lines
+AFTER DELETE ON line_secondary_agencies
+REFERENCING OLD TABLE AS old_rows
+FOR EACH STATEMENT EXECUTE FUNCTION touch_lines_of_secondary_agencies();
@@ -0,0 +1,61 @@
+-- natural order of platform codes, e.g. 2 before 10, so that platforms can be
+-- listed in order by the database. keys are computed by the application on
+-- write, see platform_sort_key of the utility crate: numbers are padded to 8
+-- digits, everything else is lowercased and whitespace is dropped.
+ALTER TABLE stops ADD COLUMN platform_sort_key TEXT;
+
+-- the normalized code and derived key are no modification of the stops.
+ALTER TABLE stops DISABLE TRIGGER USER;
+
+-- existing codes are normalized like new ones, see normalize_platform_code of
+-- the utility crate: whitespace is collapsed, prefixes like `Gleis` are removed,
+-- as are leading zeros of numbers. keys of unnormalized codes like `Gleis 1`
+-- would sort after all numeric ones.
+UPDATE stops s
+SET platform_code = n.code
+FROM (
+    SELECT
+        id,
+        origin,
+        NULLIF(
+            regexp_replace(
+                regexp_replace(
+                    btrim(regexp_replace(platform_code, '\s+', ' ', 'g')),
+                    '^((gleis|bahnsteig|bussteig|steig|platform|track)(?![[:alpha:]])|gl\.|bstg\.) ?(?=.)',
+                    '',
+                    'i'
+                ),
+                '(^|[^0-9])0+([0-9])',
+                '\1\2',
+                'g'
+            ),
+            ''
+        ) AS code
+    FROM
+        stops
+    WHERE
+        platform_code IS NOT NULL
+) n
+WHERE
+    s.id = n.id
+    AND s.origin = n.origin
+    AND s.platform_code IS DISTINCT FROM n.code;
+
+UPDATE stops s
+SET platform_sort_key = (
+    SELECT string_agg(
+        CASE
+            WHEN m.part[1] ~ '^[0-9]+$'
+                THEN lpad(m.part[1], greatest(8, length(m.part[1])), '0')
+            ELSE lower(regexp_replace(m.part[1], '\s', '', 'g'))
+        END,
+        '' ORDER BY m.position
+    )
+    FROM regexp_matches(s.platform_code, '[0-9]+|[^0-9]+', 'g')
+        WITH ORDINALITY AS m(part, position)
+)
+WHERE s.platform_code IS NOT NULL;
+
+ALTER TABLE stops ENABLE TRIGGER USER;
+
+CREATE INDEX ON stops(parent_id);
@@ -0,0 +1,7 @@
+-- origins, which do not know how riders may board or alight (e.g. the db
+-- timetables), store NULL, so that merging keeps the types of other origins.
+ALTER TABLE stop_times
+    ALTER COLUMN pickup_type DROP NOT NULL,
+    ALTER COLUMN pickup_type DROP DEFAULT,
+    ALTER COLUMN drop_off_type DROP NOT NULL,
+    ALTER COLUMN drop_off_type DROP DEFAULT;
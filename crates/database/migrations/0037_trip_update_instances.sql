@@ -0,0 +1,41 @@
+-- instances of frequency based trips share the trip and service day, so their
+-- updates are told apart by the index of the departure, see TripInstanceId.
+-- updates of other trips have the index 0.
+ALTER TABLE trip_updates ADD COLUMN instance INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE trip_updates DROP CONSTRAINT trip_updates_pkey;
+ALTER TABLE trip_updates
+    ADD PRIMARY KEY (origin, trip_id, trip_start_date, instance);
+
+-- while trip updates are moved to partitions, their partitioned copy gets the
+-- same key, and writes are mirrored into it by that key, see 0021.
+DO $$
+BEGIN
+    IF to_regclass('trip_updates_partitioned') IS NULL THEN
+        RETURN;
+    END IF;
+    ALTER TABLE trip_updates_partitioned
+        ADD COLUMN instance INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE trip_updates_partitioned
+        DROP CONSTRAINT trip_updates_partitioned_pkey;
+    ALTER TABLE trip_updates_partitioned
+        ADD PRIMARY KEY (origin, trip_id, trip_start_date, instance);
+
+    DROP TRIGGER after_insert_mirror_to_partitioned ON trip_updates;
+    DROP TRIGGER after_update_mirror_to_partitioned ON trip_updates;
+    DROP TRIGGER after_delete_mirror_to_partitioned ON trip_updates;
+    CREATE TRIGGER after_insert_mirror_to_partitioned AFTER INSERT ON trip_updates
+    REFERENCING NEW TABLE AS new_rows
+    FOR EACH STATEMENT EXECUTE FUNCTION mirror_to_partitioned(
+        'trip_updates_partitioned', 'origin, trip_id, trip_start_date, instance'
+    );
+    CREATE TRIGGER after_update_mirror_to_partitioned AFTER UPDATE ON trip_updates
+    REFERENCING OLD TABLE AS old_rows NEW TABLE AS new_rows
+    FOR EACH STATEMENT EXECUTE FUNCTION mirror_to_partitioned(
+        'trip_updates_partitioned', 'origin, trip_id, trip_start_date, instance'
+    );
+    CREATE TRIGGER after_delete_mirror_to_partitioned AFTER DELETE ON trip_updates
+    REFERENCING OLD TABLE AS old_rows
+    FOR EACH STATEMENT EXECUTE FUNCTION mirror_to_partitioned(
+        'trip_updates_partitioned', 'origin, trip_id, trip_start_date, instance'
+    );
+END $$;
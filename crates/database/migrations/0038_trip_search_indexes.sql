@@ -0,0 +1,16 @@
+-- the trip search matches substrings, which only trigram indexes support. like
+-- the extension itself, these indexes are optional, see Capability::PgTrgm.
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'pg_trgm')
+        OR to_regprocedure('similarity(text, text)') IS NULL THEN
+        RETURN;
+    END IF;
+    CREATE INDEX idx_trip_headsign_trgm ON trips USING GIN (headsign gin_trgm_ops);
+    CREATE INDEX idx_trip_short_name_trgm
+        ON trips USING GIN (short_name gin_trgm_ops);
+    CREATE INDEX idx_line_name_trgm ON lines USING GIN (name gin_trgm_ops);
+END $$;
+
+-- trips of the lines, whose name matches a word of the pattern.
+CREATE INDEX ON trips(line_id, origin);
@@ -0,0 +1,17 @@
+-- delay until the next run, which a collector asked for after its last
+-- successful run. the collector is due at the last success plus the interval.
+ALTER TABLE collectors ADD COLUMN run_interval INTERVAL;
+
+-- timestamp of the latest trip update of each origin, which is kept up to date
+-- by the writes of trip updates, so that the alert rules do not scan them.
+CREATE TABLE latest_trip_updates(
+    origin          slug PRIMARY KEY REFERENCES origins(id)
+                        ON UPDATE CASCADE ON DELETE CASCADE,
+    timestamp       TIMESTAMPTZ NOT NULL
+);
+
+INSERT INTO latest_trip_updates(origin, timestamp)
+SELECT origin, MAX(timestamp)
+FROM trip_updates
+WHERE trip_start_date >= CURRENT_DATE - 1 AND timestamp IS NOT NULL
+GROUP BY origin;
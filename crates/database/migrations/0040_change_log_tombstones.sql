@@ -0,0 +1,9 @@
+-- deletions older than the retention are compacted as well, once they are the
+-- latest change of their entity, so that the change log does not grow with
+-- every entity ever deleted. mirrors, which synced up to a position before the
+-- latest compacted deletion, may have missed it, so that they have to sync from
+-- scratch. the single row holds the sequence of that deletion.
+CREATE TABLE change_log_horizon(
+    id                          BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    compacted_deletions_through BIGINT NOT NULL
+);
@@ -0,0 +1,8 @@
+-- former ids of renamed origins. clients registering an origin by its former
+-- name, e.g. collectors configured before the rename, get the renamed origin
+-- instead of creating the former one anew.
+CREATE TABLE origin_aliases(
+    alias           slug PRIMARY KEY,
+    origin          slug NOT NULL REFERENCES origins(id)
+                        ON UPDATE CASCADE ON DELETE CASCADE
+);
@@ -0,0 +1,18 @@
+-- stop names are set by the statements writing stop times, as a trigger for each
+-- row slows down writing the stop times of whole feeds.
+DROP TRIGGER before_write_set_stop_time_name ON stop_times;
+DROP FUNCTION set_stop_time_name();
+
+-- position in the change log, up to which the stop names of stop times are
+-- synced with their stops. compaction keeps the latest change of every stop,
+-- which is not deleted, so that no renamed stop is missed behind it.
+CREATE TABLE stop_name_sync(
+    id              BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    synced_through  BIGINT NOT NULL
+);
+
+-- stops changed during the last day may not be synced yet, see 0033.
+INSERT INTO stop_name_sync(synced_through)
+SELECT COALESCE(MAX(id), 0)
+FROM change_log
+WHERE changed_at < now() - INTERVAL '1 day';
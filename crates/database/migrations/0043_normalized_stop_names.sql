@@ -0,0 +1,13 @@
+-- names of stops as compared by `Stop::same_subject_as`: lower case, without
+-- anything but letters and digits, and with common words abbreviated.
+CREATE FUNCTION normalized_stop_name(name TEXT)
+RETURNS TEXT AS $$
+    SELECT
+        replace(replace(
+        replace(replace(
+        replace(replace(
+            regexp_replace(lower(name), '[^[:alnum:]]', '', 'g'),
+            'hauptbahnhof', 'hbf'), 'central station', 'hbf'),
+            'bahnhof', 'bf'), 'bhf', 'bf'),
+            'straße', 'str'), 'street', 'str');
+$$ LANGUAGE SQL IMMUTABLE STRICT PARALLEL SAFE;
@@ -0,0 +1,34 @@
+use public_transport::capability::{Capabilities, Capability};
+use sqlx::PgPool;
+
+/// Detects the capabilities of the database. An extension only counts, if it is
+/// installed and its functions are visible to the role, e.g. are not hidden in a
+/// schema outside of its search path.
+pub(crate) async fn probe(pool: &PgPool) -> Result<Capabilities, sqlx::Error> {
+    let mut capabilities = Capabilities::none();
+    for capability in Capability::ALL {
+        let (available,): (bool,) = sqlx::query_as(
+            "
+            SELECT
+                EXISTS (SELECT 1 FROM pg_extension WHERE extname = $1)
+                AND to_regprocedure($2) IS NOT NULL;
+            ",
+        )
+        .bind(capability.extension())
+        .bind(probe_function(capability))
+        .fetch_one(pool)
+        .await?;
+        if available {
+            capabilities = capabilities.with(capability);
+        }
+    }
+    Ok(capabilities)
+}
+
+/// Function provided by the extension of the capability.
+fn probe_function(capability: Capability) -> &'static str {
+    match capability {
+        Capability::PgTrgm => "similarity(text, text)",
+        Capability::PostGis => "postgis_version()",
+    }
+}
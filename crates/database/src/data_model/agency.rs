@@ -3,11 +3,11 @@ use model::origin::OriginalIdMapping;
 use model::{agency::Agency, origin::Origin, DatabaseEntry, WithId, WithOrigin};
 use public_transport::database::{AgencyRepo, Repo, Result, SubjectRepo};
 use sqlx::prelude::FromRow;
-use utility::id::Id;
+use utility::{id::Id, normalize::non_blank};
 
 use crate::queries::agency::{
-    exists, exists_with_origin, get, get_all, get_by_name, id_by_original_id, insert, put,
-    put_original_id, update,
+    exists, exists_with_origin, get, get_all, get_by_name, get_many, id_by_original_id, insert,
+    put, put_original_id, update,
 };
 use crate::PgDatabaseAutocommit;
 use crate::PgDatabaseTransaction;
@@ -34,9 +34,9 @@ impl AgencyRow {
             origin: agency.origin.to_string(),
             name: agency.content.name,
             website: agency.content.website,
-            phone_number: agency.content.phone_number,
-            email: agency.content.email,
-            fare_url: agency.content.fare_url,
+            phone_number: non_blank(agency.content.phone_number),
+            email: non_blank(agency.content.email),
+            fare_url: non_blank(agency.content.fare_url),
         })
     }
 }
@@ -56,9 +56,9 @@ impl DatabaseRow for AgencyRow {
         Agency {
             name: self.name,
             website: self.website,
-            phone_number: self.phone_number,
-            email: self.email,
-            fare_url: self.fare_url,
+            phone_number: non_blank(self.phone_number),
+            email: non_blank(self.email),
+            fare_url: non_blank(self.fare_url),
         }
     }
 
@@ -68,9 +68,9 @@ impl DatabaseRow for AgencyRow {
             origin: agency.origin.to_string(),
             name: agency.content.name,
             website: agency.content.website,
-            phone_number: agency.content.phone_number,
-            email: agency.content.email,
-            fare_url: agency.content.fare_url,
+            phone_number: non_blank(agency.content.phone_number),
+            email: non_blank(agency.content.email),
+            fare_url: non_blank(agency.content.fare_url),
         }
     }
 }
@@ -87,6 +87,10 @@ impl Repo<Agency> for PgDatabaseAutocommit {
         get_all(&self.pool).await
     }
 
+    async fn get_many(&mut self, ids: &[Id<Agency>]) -> Result<Vec<DatabaseEntry<Agency>>> {
+        get_many(&self.pool, ids).await
+    }
+
     async fn insert(&mut self, element: WithOrigin<Agency>) -> Result<WithOrigin<WithId<Agency>>> {
         insert(&self.pool, element).await
     }
@@ -124,6 +128,10 @@ impl<'a> Repo<Agency> for PgDatabaseTransaction<'a> {
         get_all(&mut *self.tx).await
     }
 
+    async fn get_many(&mut self, ids: &[Id<Agency>]) -> Result<Vec<DatabaseEntry<Agency>>> {
+        get_many(&mut *self.tx, ids).await
+    }
+
     async fn insert(&mut self, element: WithOrigin<Agency>) -> Result<WithOrigin<WithId<Agency>>> {
         insert(&mut *self.tx, element).await
     }
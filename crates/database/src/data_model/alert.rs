@@ -0,0 +1,59 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use public_transport::{
+    database::{AlertRepo, Result},
+    notification::Alert,
+};
+use sqlx::prelude::FromRow;
+
+use crate::{
+    queries::alert::{close, get_all, open},
+    PgDatabaseAutocommit, PgDatabaseTransaction,
+};
+
+#[derive(Debug, Clone, FromRow)]
+pub struct AlertRow {
+    pub rule: String,
+    pub message: String,
+    pub opened_at: DateTime<Local>,
+}
+
+impl AlertRow {
+    pub fn to_model(self) -> Alert {
+        Alert {
+            rule: self.rule,
+            message: self.message,
+            opened_at: self.opened_at,
+        }
+    }
+}
+
+#[async_trait]
+impl AlertRepo for PgDatabaseAutocommit {
+    async fn open_alert(&mut self, alert: &Alert) -> Result<bool> {
+        open(&self.pool, alert).await
+    }
+
+    async fn close_alert(&mut self, rule: &str) -> Result<Option<Alert>> {
+        close(&self.pool, rule).await
+    }
+
+    async fn open_alerts(&mut self) -> Result<Vec<Alert>> {
+        get_all(&self.pool).await
+    }
+}
+
+#[async_trait]
+impl<'a> AlertRepo for PgDatabaseTransaction<'a> {
+    async fn open_alert(&mut self, alert: &Alert) -> Result<bool> {
+        open(&mut *self.tx, alert).await
+    }
+
+    async fn close_alert(&mut self, rule: &str) -> Result<Option<Alert>> {
+        close(&mut *self.tx, rule).await
+    }
+
+    async fn open_alerts(&mut self) -> Result<Vec<Alert>> {
+        get_all(&mut *self.tx).await
+    }
+}
@@ -5,12 +5,14 @@ use model::{
 };
 use public_transport::database::{self, ServiceRepo, SubjectRepo};
 use sqlx::prelude::FromRow;
+use std::collections::HashMap;
 use utility::id::Id;
 
 use crate::{
     queries::service::{
-        get_calendar_dates, get_calendar_windows, id_by_original_id,
-        put_calendar_date, put_calendar_window, put_original_id,
+        claim_signature, get_calendar_dates, get_calendar_windows,
+        get_ids_of_origin, get_tags, id_by_original_id, put_calendar_date,
+        put_calendar_window, put_original_id, set_tags,
     },
     PgDatabaseAutocommit, PgDatabaseTransaction,
 };
@@ -40,6 +42,22 @@ impl From<model::calendar::ServiceAvailability> for ServiceAvailability {
     }
 }
 
+#[derive(Debug, Clone, sqlx::Type)]
+#[sqlx(type_name = "service_tag", rename_all = "snake_case")]
+pub enum ServiceTag {
+    School,
+    Seasonal,
+}
+
+impl From<ServiceTag> for model::calendar::ServiceTag {
+    fn from(value: ServiceTag) -> Self {
+        match value {
+            ServiceTag::School => Self::School,
+            ServiceTag::Seasonal => Self::Seasonal,
+        }
+    }
+}
+
 #[derive(Debug, Clone, FromRow)]
 pub struct CalendarWindowRow {
     pub service_id: Option<i32>,
@@ -160,6 +178,37 @@ impl ServiceRepo for PgDatabaseAutocommit {
     ) -> database::Result<Vec<CalendarDate>> {
         get_calendar_dates(&self.pool, service_id).await
     }
+
+    async fn get_service_ids(
+        &mut self,
+        origin: &Id<Origin>,
+    ) -> database::Result<Vec<Id<Service>>> {
+        get_ids_of_origin(&self.pool, origin).await
+    }
+
+    async fn set_service_tags(
+        &mut self,
+        service_ids: &[Id<Service>],
+        tags: &[(Id<Service>, model::calendar::ServiceTag)],
+    ) -> database::Result<()> {
+        set_tags(&self.pool, service_ids, tags).await
+    }
+
+    async fn get_service_tags(
+        &mut self,
+        service_ids: &[Id<Service>],
+    ) -> database::Result<HashMap<Id<Service>, Vec<model::calendar::ServiceTag>>>
+    {
+        get_tags(&self.pool, service_ids).await
+    }
+
+    async fn claim_service_signature(
+        &mut self,
+        origin: &Id<Origin>,
+        signature: &str,
+    ) -> database::Result<(Id<Service>, bool)> {
+        claim_signature(&self.pool, origin, signature).await
+    }
 }
 
 #[async_trait]
@@ -193,4 +242,35 @@ impl<'a> ServiceRepo for PgDatabaseTransaction<'a> {
     ) -> database::Result<Vec<CalendarDate>> {
         get_calendar_dates(&mut *self.tx, service_id).await
     }
+
+    async fn get_service_ids(
+        &mut self,
+        origin: &Id<Origin>,
+    ) -> database::Result<Vec<Id<Service>>> {
+        get_ids_of_origin(&mut *self.tx, origin).await
+    }
+
+    async fn set_service_tags(
+        &mut self,
+        service_ids: &[Id<Service>],
+        tags: &[(Id<Service>, model::calendar::ServiceTag)],
+    ) -> database::Result<()> {
+        set_tags(&mut *self.tx, service_ids, tags).await
+    }
+
+    async fn get_service_tags(
+        &mut self,
+        service_ids: &[Id<Service>],
+    ) -> database::Result<HashMap<Id<Service>, Vec<model::calendar::ServiceTag>>>
+    {
+        get_tags(&mut *self.tx, service_ids).await
+    }
+
+    async fn claim_service_signature(
+        &mut self,
+        origin: &Id<Origin>,
+        signature: &str,
+    ) -> database::Result<(Id<Service>, bool)> {
+        claim_signature(&mut *self.tx, origin, signature).await
+    }
 }
@@ -0,0 +1,124 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use model::change::{Change, ChangeOperation, ChangePosition, ChangedEntity};
+use public_transport::database::{ChangeLogRepo, Result};
+use sqlx::prelude::FromRow;
+use utility::id::Id;
+
+use crate::{
+    queries::change_log::{compact, flush, get_after, get_horizon, try_lock_flush},
+    PgDatabaseAutocommit, PgDatabaseTransaction,
+};
+
+#[derive(Debug, Clone, Copy, sqlx::Type)]
+#[sqlx(type_name = "change_entity", rename_all = "snake_case")]
+pub enum RowChangedEntity {
+    Stop,
+    Line,
+    Trip,
+}
+
+impl RowChangedEntity {
+    pub fn to_model(self) -> ChangedEntity {
+        match self {
+            Self::Stop => ChangedEntity::Stop,
+            Self::Line => ChangedEntity::Line,
+            Self::Trip => ChangedEntity::Trip,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, sqlx::Type)]
+#[sqlx(type_name = "change_operation", rename_all = "snake_case")]
+pub enum RowChangeOperation {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl RowChangeOperation {
+    pub fn to_model(self) -> ChangeOperation {
+        match self {
+            Self::Insert => ChangeOperation::Insert,
+            Self::Update => ChangeOperation::Update,
+            Self::Delete => ChangeOperation::Delete,
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct ChangeRow {
+    pub id: i64,
+    pub entity: RowChangedEntity,
+    pub entity_id: String,
+    pub origin: String,
+    pub operation: RowChangeOperation,
+    pub changed_at: DateTime<Local>,
+}
+
+impl ChangeRow {
+    pub fn to_model(self) -> Change {
+        Change {
+            position: ChangePosition { sequence: self.id },
+            entity: self.entity.to_model(),
+            id: self.entity_id,
+            origin: Id::new(self.origin),
+            operation: self.operation.to_model(),
+            changed_at: self.changed_at,
+        }
+    }
+}
+
+#[async_trait]
+impl ChangeLogRepo for PgDatabaseAutocommit {
+    async fn changes_after(
+        &mut self,
+        after: Option<ChangePosition>,
+        limit: usize,
+    ) -> Result<Vec<Change>> {
+        get_after(&self.pool, after, limit).await
+    }
+
+    async fn try_lock_change_flush(&mut self) -> Result<bool> {
+        try_lock_flush(&self.pool).await
+    }
+
+    async fn flush_changes(&mut self, limit: usize) -> Result<u64> {
+        flush(&self.pool, limit).await
+    }
+
+    async fn compact_changes(&mut self, before: DateTime<Local>) -> Result<u64> {
+        compact(&self.pool, before).await
+    }
+
+    async fn change_log_horizon(&mut self) -> Result<Option<ChangePosition>> {
+        get_horizon(&self.pool).await
+    }
+}
+
+#[async_trait]
+impl<'a> ChangeLogRepo for PgDatabaseTransaction<'a> {
+    async fn changes_after(
+        &mut self,
+        after: Option<ChangePosition>,
+        limit: usize,
+    ) -> Result<Vec<Change>> {
+        get_after(&mut *self.tx, after, limit).await
+    }
+
+    async fn try_lock_change_flush(&mut self) -> Result<bool> {
+        try_lock_flush(&mut *self.tx).await
+    }
+
+    async fn flush_changes(&mut self, limit: usize) -> Result<u64> {
+        flush(&mut *self.tx, limit).await
+    }
+
+    async fn compact_changes(&mut self, before: DateTime<Local>) -> Result<u64> {
+        compact(&mut *self.tx, before).await
+    }
+
+    async fn change_log_horizon(&mut self) -> Result<Option<ChangePosition>> {
+        get_horizon(&mut *self.tx).await
+    }
+}
@@ -1,14 +1,18 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Duration, Local};
 use model::WithId;
 use public_transport::{
-    collector::{Collector, CollectorInstance},
+    collector::{Collector, CollectorHealth, CollectorInstance, CollectorLease},
     database::{CollectorRepo, Result},
 };
 use sqlx::{prelude::FromRow, types::Json};
 use utility::id::Id;
 
 use crate::{
-    queries::collector::{get, get_all, set_state},
+    queries::collector::{
+        get, get_all, get_health, record_run, release_lease, renew_lease, set_health,
+        set_next_run, set_state, try_lease,
+    },
     PgDatabaseAutocommit, PgDatabaseTransaction,
 };
 
@@ -19,6 +23,41 @@ pub struct CollectorRow<C: Collector> {
     pub kind: String,
     pub is_active: bool,
     pub state: Json<C::State>,
+    pub next_run_at: Option<DateTime<Local>>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct CollectorHealthRow {
+    pub id: i32,
+    pub origin: String,
+    pub kind: String,
+    pub is_active: bool,
+    pub healthcheck_at: Option<DateTime<Local>>,
+    pub healthcheck_error: Option<String>,
+    pub consecutive_failures: i32,
+    pub last_success_at: Option<DateTime<Local>>,
+    pub next_run_at: Option<DateTime<Local>>,
+    pub due_at: Option<DateTime<Local>>,
+}
+
+impl CollectorHealthRow {
+    pub fn to_model(self) -> CollectorHealth {
+        CollectorHealth {
+            id: self.id,
+            origin: Id::new(self.origin),
+            kind: self.kind,
+            is_active: self.is_active,
+            healthy: self
+                .healthcheck_at
+                .map(|_| self.healthcheck_error.is_none()),
+            checked_at: self.healthcheck_at,
+            error: self.healthcheck_error,
+            consecutive_failures: self.consecutive_failures,
+            last_success_at: self.last_success_at,
+            next_run_at: self.next_run_at,
+            due_at: self.due_at,
+        }
+    }
 }
 
 #[async_trait]
@@ -50,6 +89,79 @@ impl CollectorRepo for PgDatabaseAutocommit {
     {
         set_state(&self.pool, id, state).await
     }
+
+    async fn try_lease_collector<C>(
+        &mut self,
+        id: &Id<CollectorInstance<C>>,
+        duration: Duration,
+        spacing: Duration,
+    ) -> Result<CollectorLease>
+    where
+        C: Collector + 'static,
+    {
+        try_lease(&self.pool, id, duration, spacing).await
+    }
+
+    async fn renew_collector_lease<C>(
+        &mut self,
+        id: &Id<CollectorInstance<C>>,
+        started_at: DateTime<Local>,
+        duration: Duration,
+    ) -> Result<bool>
+    where
+        C: Collector + 'static,
+    {
+        renew_lease(&self.pool, id, started_at, duration).await
+    }
+
+    async fn release_collector_lease<C>(
+        &mut self,
+        id: &Id<CollectorInstance<C>>,
+        started_at: DateTime<Local>,
+    ) -> Result<()>
+    where
+        C: Collector + 'static,
+    {
+        release_lease(&self.pool, id, started_at).await
+    }
+
+    async fn set_collector_health<C>(
+        &mut self,
+        id: &Id<CollectorInstance<C>>,
+        error: Option<String>,
+    ) -> Result<()>
+    where
+        C: Collector + 'static,
+    {
+        set_health(&self.pool, id, error).await
+    }
+
+    async fn set_collector_next_run<C>(
+        &mut self,
+        id: &Id<CollectorInstance<C>>,
+        next_run_at: Option<DateTime<Local>>,
+    ) -> Result<()>
+    where
+        C: Collector + 'static,
+    {
+        set_next_run(&self.pool, id, next_run_at).await
+    }
+
+    async fn record_collector_run<C>(
+        &mut self,
+        id: &Id<CollectorInstance<C>>,
+        succeeded: bool,
+        run_interval: Option<Duration>,
+    ) -> Result<()>
+    where
+        C: Collector + 'static,
+    {
+        record_run(&self.pool, id, succeeded, run_interval).await
+    }
+
+    async fn collector_health(&mut self) -> Result<Vec<CollectorHealth>> {
+        get_health(&self.pool).await
+    }
 }
 
 #[async_trait]
@@ -81,4 +193,77 @@ impl<'a> CollectorRepo for PgDatabaseTransaction<'a> {
     {
         set_state(&mut *self.tx, id, state).await
     }
+
+    async fn try_lease_collector<C>(
+        &mut self,
+        id: &Id<CollectorInstance<C>>,
+        duration: Duration,
+        spacing: Duration,
+    ) -> Result<CollectorLease>
+    where
+        C: Collector + 'static,
+    {
+        try_lease(&mut *self.tx, id, duration, spacing).await
+    }
+
+    async fn renew_collector_lease<C>(
+        &mut self,
+        id: &Id<CollectorInstance<C>>,
+        started_at: DateTime<Local>,
+        duration: Duration,
+    ) -> Result<bool>
+    where
+        C: Collector + 'static,
+    {
+        renew_lease(&mut *self.tx, id, started_at, duration).await
+    }
+
+    async fn release_collector_lease<C>(
+        &mut self,
+        id: &Id<CollectorInstance<C>>,
+        started_at: DateTime<Local>,
+    ) -> Result<()>
+    where
+        C: Collector + 'static,
+    {
+        release_lease(&mut *self.tx, id, started_at).await
+    }
+
+    async fn set_collector_health<C>(
+        &mut self,
+        id: &Id<CollectorInstance<C>>,
+        error: Option<String>,
+    ) -> Result<()>
+    where
+        C: Collector + 'static,
+    {
+        set_health(&mut *self.tx, id, error).await
+    }
+
+    async fn set_collector_next_run<C>(
+        &mut self,
+        id: &Id<CollectorInstance<C>>,
+        next_run_at: Option<DateTime<Local>>,
+    ) -> Result<()>
+    where
+        C: Collector + 'static,
+    {
+        set_next_run(&mut *self.tx, id, next_run_at).await
+    }
+
+    async fn record_collector_run<C>(
+        &mut self,
+        id: &Id<CollectorInstance<C>>,
+        succeeded: bool,
+        run_interval: Option<Duration>,
+    ) -> Result<()>
+    where
+        C: Collector + 'static,
+    {
+        record_run(&mut *self.tx, id, succeeded, run_interval).await
+    }
+
+    async fn collector_health(&mut self) -> Result<Vec<CollectorHealth>> {
+        get_health(&mut *self.tx).await
+    }
 }
@@ -0,0 +1,53 @@
+use async_trait::async_trait;
+use model::origin::Origin;
+use public_transport::database::{
+    IntegrityRepo, InvalidCoordinates, Orphans, Result,
+};
+use utility::id::Id;
+
+use crate::{
+    queries::integrity::{clear_invalid_coordinates, invalid_coordinates, orphans},
+    PgDatabaseAutocommit, PgDatabaseTransaction,
+};
+
+#[async_trait]
+impl IntegrityRepo for PgDatabaseAutocommit {
+    async fn orphans(&mut self) -> Result<Vec<Orphans>> {
+        orphans(&self.pool).await
+    }
+
+    async fn invalid_coordinates(
+        &mut self,
+        null_island_origins: &[Id<Origin>],
+    ) -> Result<Vec<InvalidCoordinates>> {
+        invalid_coordinates(&self.pool, null_island_origins).await
+    }
+
+    async fn clear_invalid_coordinates(
+        &mut self,
+        null_island_origins: &[Id<Origin>],
+    ) -> Result<u64> {
+        clear_invalid_coordinates(&self.pool, null_island_origins).await
+    }
+}
+
+#[async_trait]
+impl<'a> IntegrityRepo for PgDatabaseTransaction<'a> {
+    async fn orphans(&mut self) -> Result<Vec<Orphans>> {
+        orphans(&mut *self.tx).await
+    }
+
+    async fn invalid_coordinates(
+        &mut self,
+        null_island_origins: &[Id<Origin>],
+    ) -> Result<Vec<InvalidCoordinates>> {
+        invalid_coordinates(&mut *self.tx, null_island_origins).await
+    }
+
+    async fn clear_invalid_coordinates(
+        &mut self,
+        null_island_origins: &[Id<Origin>],
+    ) -> Result<u64> {
+        clear_invalid_coordinates(&mut *self.tx, null_island_origins).await
+    }
+}
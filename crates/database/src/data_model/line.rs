@@ -1,11 +1,13 @@
 use crate::{
     queries::line::{
         exists, exists_with_origin, get, get_all, get_by_name_and_agency, get_by_stop_id,
-        id_by_original_id, insert, put, put_original_id, update,
+        get_by_stop_ids, get_departure_span, get_many, id_by_original_id, insert, put,
+        put_original_id, search, update,
     },
     PgDatabaseTransaction,
 };
 use async_trait::async_trait;
+use chrono::{DateTime, Duration, Local, NaiveDate};
 use model::{
     agency::Agency,
     line::{Line, LineType},
@@ -15,7 +17,11 @@ use model::{
 };
 use public_transport::database::{LineRepo, Repo, Result, SubjectRepo};
 use sqlx::prelude::FromRow;
-use utility::id::{Id, IdWrapper};
+use std::collections::HashMap;
+use utility::{
+    id::{Id, IdWrapper},
+    normalize::non_blank,
+};
 
 use crate::PgDatabaseAutocommit;
 
@@ -34,6 +40,9 @@ pub enum RowLineType {
     Funicular,
     Trolleybus,
     Monorail,
+    LongDistanceRail,
+    RegionalRail,
+    SuburbanRail,
 }
 
 impl RowLineType {
@@ -49,6 +58,9 @@ impl RowLineType {
             Self::Funicular => LineType::Funicular,
             Self::Trolleybus => LineType::Trolleybus,
             Self::Monorail => LineType::Monorail,
+            Self::LongDistanceRail => LineType::LongDistanceRail,
+            Self::RegionalRail => LineType::RegionalRail,
+            Self::SuburbanRail => LineType::SuburbanRail,
         }
     }
 
@@ -64,6 +76,9 @@ impl RowLineType {
             LineType::Funicular => Self::Funicular,
             LineType::Trolleybus => Self::Trolleybus,
             LineType::Monorail => Self::Monorail,
+            LineType::LongDistanceRail => Self::LongDistanceRail,
+            LineType::RegionalRail => Self::RegionalRail,
+            LineType::SuburbanRail => Self::SuburbanRail,
         }
     }
 }
@@ -75,6 +90,16 @@ pub struct LineRow {
     pub name: Option<String>,
     pub kind: RowLineType,
     pub agency_id: Option<String>,
+    pub secondary_agency_ids: Vec<String>,
+    pub updated_at: Option<DateTime<Local>>,
+}
+
+/// A line and one of the stops it serves.
+#[derive(Debug, Clone, FromRow)]
+pub struct StopLineRow {
+    pub stop_id: String,
+    #[sqlx(flatten)]
+    pub line: LineRow,
 }
 
 impl DatabaseRow for LineRow {
@@ -90,9 +115,15 @@ impl DatabaseRow for LineRow {
 
     fn to_model(self) -> Self::Model {
         Line {
-            name: self.name,
+            name: non_blank(self.name),
             kind: self.kind.to_line_type(),
             agency_id: self.agency_id.map(|inner| Id::new(inner)),
+            secondary_agency_ids: self
+                .secondary_agency_ids
+                .into_iter()
+                .map(Id::new)
+                .collect(),
+            updated_at: self.updated_at,
         }
     }
 
@@ -100,9 +131,11 @@ impl DatabaseRow for LineRow {
         Self {
             id: "".to_owned(),
             origin: line.origin.raw(),
-            name: line.content.name,
+            name: non_blank(line.content.name),
             kind: RowLineType::from_line_type(line.content.kind),
             agency_id: line.content.agency_id.raw(),
+            secondary_agency_ids: line.content.secondary_agency_ids.raw(),
+            updated_at: None,
         }
     }
 }
@@ -119,6 +152,10 @@ impl Repo<Line> for PgDatabaseAutocommit {
         get_all(&self.pool).await
     }
 
+    async fn get_many(&mut self, ids: &[Id<Line>]) -> Result<Vec<DatabaseEntry<Line>>> {
+        get_many(&self.pool, ids).await
+    }
+
     async fn insert(&mut self, element: WithOrigin<Line>) -> Result<WithOrigin<WithId<Line>>> {
         insert(&self.pool, element).await
     }
@@ -153,6 +190,10 @@ impl<'a> Repo<Line> for PgDatabaseTransaction<'a> {
         get_all(&mut *self.tx).await
     }
 
+    async fn get_many(&mut self, ids: &[Id<Line>]) -> Result<Vec<DatabaseEntry<Line>>> {
+        get_many(&mut *self.tx, ids).await
+    }
+
     async fn insert(&mut self, element: WithOrigin<Line>) -> Result<WithOrigin<WithId<Line>>> {
         insert(&mut *self.tx, element).await
     }
@@ -235,6 +276,29 @@ impl LineRepo for PgDatabaseAutocommit {
         // TODO: make underlying function take stop_id by ref.
         get_by_stop_id(&self.pool, stop_id.clone()).await
     }
+
+    async fn get_by_stop_ids(
+        &mut self,
+        stop_ids: &[&Id<Stop>],
+    ) -> Result<HashMap<Id<Stop>, Vec<DatabaseEntry<Line>>>> {
+        get_by_stop_ids(&self.pool, stop_ids).await
+    }
+
+    async fn search_by_name<S: Into<String> + Send>(
+        &mut self,
+        pattern: S,
+        limit: usize,
+    ) -> Result<Vec<DatabaseEntry<Line>>> {
+        search(&self.pool, pattern, limit).await
+    }
+
+    async fn get_departure_span(
+        &mut self,
+        id: &Id<Line>,
+        service_day: NaiveDate,
+    ) -> Result<Option<(Duration, Duration)>> {
+        get_departure_span(&self.pool, id, service_day).await
+    }
 }
 
 #[async_trait]
@@ -251,4 +315,27 @@ impl<'a> LineRepo for PgDatabaseTransaction<'a> {
         // TODO: make underlying function take stop_id by ref.
         get_by_stop_id(&mut *self.tx, stop_id.clone()).await
     }
+
+    async fn get_by_stop_ids(
+        &mut self,
+        stop_ids: &[&Id<Stop>],
+    ) -> Result<HashMap<Id<Stop>, Vec<DatabaseEntry<Line>>>> {
+        get_by_stop_ids(&mut *self.tx, stop_ids).await
+    }
+
+    async fn search_by_name<S: Into<String> + Send>(
+        &mut self,
+        pattern: S,
+        limit: usize,
+    ) -> Result<Vec<DatabaseEntry<Line>>> {
+        search(&mut *self.tx, pattern, limit).await
+    }
+
+    async fn get_departure_span(
+        &mut self,
+        id: &Id<Line>,
+        service_day: NaiveDate,
+    ) -> Result<Option<(Duration, Duration)>> {
+        get_departure_span(&mut *self.tx, id, service_day).await
+    }
 }
@@ -0,0 +1,181 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use model::{
+    merge::{MergeLogEntry, MergeStatus, MergeSubject},
+    origin::Origin,
+    WithId,
+};
+use public_transport::database::{MergeLogRepo, Result};
+use sqlx::prelude::FromRow;
+use utility::id::Id;
+
+use crate::{
+    queries::merge_log::{get, get_all, insert, is_rejected, set_status},
+    PgDatabaseAutocommit, PgDatabaseTransaction,
+};
+
+#[derive(Debug, Clone, sqlx::Type)]
+#[sqlx(type_name = "merge_subject", rename_all = "snake_case")]
+pub enum RowMergeSubject {
+    Stop,
+}
+
+impl RowMergeSubject {
+    pub fn to_model(self) -> MergeSubject {
+        match self {
+            Self::Stop => MergeSubject::Stop,
+        }
+    }
+
+    pub fn from_model(subject: MergeSubject) -> Self {
+        match subject {
+            MergeSubject::Stop => Self::Stop,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::Type)]
+#[sqlx(type_name = "merge_status", rename_all = "snake_case")]
+pub enum RowMergeStatus {
+    AutoAccepted,
+    PendingReview,
+    Confirmed,
+    Rejected,
+}
+
+impl RowMergeStatus {
+    pub fn to_model(self) -> MergeStatus {
+        match self {
+            Self::AutoAccepted => MergeStatus::AutoAccepted,
+            Self::PendingReview => MergeStatus::PendingReview,
+            Self::Confirmed => MergeStatus::Confirmed,
+            Self::Rejected => MergeStatus::Rejected,
+        }
+    }
+
+    pub fn from_model(status: MergeStatus) -> Self {
+        match status {
+            MergeStatus::AutoAccepted => Self::AutoAccepted,
+            MergeStatus::PendingReview => Self::PendingReview,
+            MergeStatus::Confirmed => Self::Confirmed,
+            MergeStatus::Rejected => Self::Rejected,
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct MergeLogRow {
+    pub id: i32,
+    pub subject: RowMergeSubject,
+    pub origin: String,
+    pub subject_id: String,
+    pub subject_original_id: Option<String>,
+    pub candidate_id: String,
+    pub similarity: f64,
+    pub status: RowMergeStatus,
+    pub created_at: DateTime<Local>,
+    pub decided_at: Option<DateTime<Local>>,
+}
+
+impl MergeLogRow {
+    pub fn to_model(self) -> WithId<MergeLogEntry> {
+        WithId::new(
+            Id::new(self.id),
+            MergeLogEntry {
+                subject: self.subject.to_model(),
+                origin: Id::<Origin>::new(self.origin),
+                subject_id: self.subject_id,
+                subject_original_id: self.subject_original_id,
+                candidate_id: self.candidate_id,
+                similarity: self.similarity,
+                status: self.status.to_model(),
+                created_at: self.created_at,
+                decided_at: self.decided_at,
+            },
+        )
+    }
+}
+
+#[async_trait]
+impl MergeLogRepo for PgDatabaseAutocommit {
+    async fn log_merge(
+        &mut self,
+        entry: MergeLogEntry,
+    ) -> Result<WithId<MergeLogEntry>> {
+        insert(&self.pool, entry).await
+    }
+
+    async fn get_merge_log_entry(
+        &mut self,
+        id: &Id<MergeLogEntry>,
+    ) -> Result<MergeLogEntry> {
+        get(&self.pool, id).await
+    }
+
+    async fn get_merge_log(
+        &mut self,
+        status: Option<MergeStatus>,
+    ) -> Result<Vec<WithId<MergeLogEntry>>> {
+        get_all(&self.pool, status).await
+    }
+
+    async fn decide_merge(
+        &mut self,
+        id: &Id<MergeLogEntry>,
+        status: MergeStatus,
+    ) -> Result<Option<MergeLogEntry>> {
+        set_status(&self.pool, id, status).await
+    }
+
+    async fn is_merge_rejected(
+        &mut self,
+        subject: MergeSubject,
+        origin: &Id<Origin>,
+        original_id: Option<&str>,
+        candidate_id: &str,
+    ) -> Result<bool> {
+        is_rejected(&self.pool, subject, origin, original_id, candidate_id).await
+    }
+}
+
+#[async_trait]
+impl<'a> MergeLogRepo for PgDatabaseTransaction<'a> {
+    async fn log_merge(
+        &mut self,
+        entry: MergeLogEntry,
+    ) -> Result<WithId<MergeLogEntry>> {
+        insert(&mut *self.tx, entry).await
+    }
+
+    async fn get_merge_log_entry(
+        &mut self,
+        id: &Id<MergeLogEntry>,
+    ) -> Result<MergeLogEntry> {
+        get(&mut *self.tx, id).await
+    }
+
+    async fn get_merge_log(
+        &mut self,
+        status: Option<MergeStatus>,
+    ) -> Result<Vec<WithId<MergeLogEntry>>> {
+        get_all(&mut *self.tx, status).await
+    }
+
+    async fn decide_merge(
+        &mut self,
+        id: &Id<MergeLogEntry>,
+        status: MergeStatus,
+    ) -> Result<Option<MergeLogEntry>> {
+        set_status(&mut *self.tx, id, status).await
+    }
+
+    async fn is_merge_rejected(
+        &mut self,
+        subject: MergeSubject,
+        origin: &Id<Origin>,
+        original_id: Option<&str>,
+        candidate_id: &str,
+    ) -> Result<bool> {
+        is_rejected(&mut *self.tx, subject, origin, original_id, candidate_id).await
+    }
+}
@@ -5,12 +5,17 @@ use serde::Serialize;
 use utility::id::{HasId, Id};
 
 pub mod agency;
+pub mod alert;
+pub mod change_log;
 pub mod calendar;
 pub mod calendar_exception;
 pub mod collector;
+pub mod integrity;
 pub mod line;
 pub mod location;
+pub mod merge_log;
 pub mod origin;
+pub mod pathway;
 pub mod shared_mobility;
 pub mod stop;
 pub mod trip;
@@ -0,0 +1,239 @@
+use async_trait::async_trait;
+use chrono::Duration;
+use model::{
+    origin::Origin,
+    pathway::{Level, Pathway, PathwayMode},
+    stop::Stop,
+    WithId, WithOrigin,
+};
+use public_transport::database::{PathwayRepo, Result};
+use sqlx::prelude::FromRow;
+use utility::{
+    id::{Id, IdWrapper},
+    normalize::non_blank,
+};
+
+use super::DatabaseRow;
+use crate::{
+    queries::pathway::{
+        get_by_station, get_levels_by_station, put_level, put_pathway,
+    },
+    PgDatabaseAutocommit, PgDatabaseTransaction,
+};
+
+#[derive(Debug, Clone, FromRow)]
+pub struct LevelRow {
+    pub origin: String,
+    pub id: String,
+    pub level_index: f64,
+    pub name: Option<String>,
+}
+
+impl DatabaseRow for LevelRow {
+    type Model = Level;
+
+    fn get_id(&self) -> Id<Self::Model> {
+        Id::new(self.id.clone())
+    }
+
+    fn get_origin(&self) -> Id<Origin> {
+        Id::new(self.origin.clone())
+    }
+
+    fn to_model(self) -> Level {
+        Level {
+            index: self.level_index,
+            name: non_blank(self.name),
+        }
+    }
+
+    fn from_model(level: WithOrigin<Level>) -> Self {
+        Self {
+            origin: level.origin.raw(),
+            id: "".to_owned(),
+            level_index: level.content.index,
+            name: non_blank(level.content.name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, sqlx::Type)]
+#[sqlx(type_name = "pathway_mode", rename_all = "snake_case")]
+pub enum RowPathwayMode {
+    Walkway,
+    Stairs,
+    MovingSidewalk,
+    Escalator,
+    Elevator,
+    FareGate,
+    ExitGate,
+}
+
+impl RowPathwayMode {
+    pub fn to_model(self) -> PathwayMode {
+        match self {
+            Self::Walkway => PathwayMode::Walkway,
+            Self::Stairs => PathwayMode::Stairs,
+            Self::MovingSidewalk => PathwayMode::MovingSidewalk,
+            Self::Escalator => PathwayMode::Escalator,
+            Self::Elevator => PathwayMode::Elevator,
+            Self::FareGate => PathwayMode::FareGate,
+            Self::ExitGate => PathwayMode::ExitGate,
+        }
+    }
+
+    pub fn from_model(mode: PathwayMode) -> Self {
+        match mode {
+            PathwayMode::Walkway => Self::Walkway,
+            PathwayMode::Stairs => Self::Stairs,
+            PathwayMode::MovingSidewalk => Self::MovingSidewalk,
+            PathwayMode::Escalator => Self::Escalator,
+            PathwayMode::Elevator => Self::Elevator,
+            PathwayMode::FareGate => Self::FareGate,
+            PathwayMode::ExitGate => Self::ExitGate,
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct PathwayRow {
+    pub origin: String,
+    pub id: String,
+    pub from_stop_id: String,
+    pub to_stop_id: String,
+    pub station_id: Option<String>,
+    pub from_level_id: Option<String>,
+    pub to_level_id: Option<String>,
+    pub mode: RowPathwayMode,
+    pub is_bidirectional: bool,
+    pub length: Option<f64>,
+    pub traversal_time: Option<i32>,
+    pub stair_count: Option<i32>,
+    pub max_slope: Option<f64>,
+    pub min_width: Option<f64>,
+    pub signposted_as: Option<String>,
+    pub reversed_signposted_as: Option<String>,
+}
+
+impl DatabaseRow for PathwayRow {
+    type Model = Pathway;
+
+    fn get_id(&self) -> Id<Self::Model> {
+        Id::new(self.id.clone())
+    }
+
+    fn get_origin(&self) -> Id<Origin> {
+        Id::new(self.origin.clone())
+    }
+
+    fn to_model(self) -> Pathway {
+        Pathway {
+            from_stop_id: Id::new(self.from_stop_id),
+            to_stop_id: Id::new(self.to_stop_id),
+            station_id: self.station_id.map(Id::new),
+            from_level_id: self.from_level_id.map(Id::new),
+            to_level_id: self.to_level_id.map(Id::new),
+            mode: self.mode.to_model(),
+            is_bidirectional: self.is_bidirectional,
+            length: self.length,
+            traversal_time: self
+                .traversal_time
+                .map(|seconds| Duration::seconds(seconds as i64)),
+            stair_count: self.stair_count,
+            max_slope: self.max_slope,
+            min_width: self.min_width,
+            signposted_as: non_blank(self.signposted_as),
+            reversed_signposted_as: non_blank(self.reversed_signposted_as),
+        }
+    }
+
+    fn from_model(pathway: WithOrigin<Pathway>) -> Self {
+        let content = pathway.content;
+        Self {
+            origin: pathway.origin.raw(),
+            id: "".to_owned(),
+            from_stop_id: content.from_stop_id.raw(),
+            to_stop_id: content.to_stop_id.raw(),
+            station_id: content.station_id.raw(),
+            from_level_id: content.from_level_id.raw(),
+            to_level_id: content.to_level_id.raw(),
+            mode: RowPathwayMode::from_model(content.mode),
+            is_bidirectional: content.is_bidirectional,
+            length: content.length,
+            traversal_time: content
+                .traversal_time
+                .map(|time| time.num_seconds() as i32),
+            stair_count: content.stair_count,
+            max_slope: content.max_slope,
+            min_width: content.min_width,
+            signposted_as: non_blank(content.signposted_as),
+            reversed_signposted_as: non_blank(content.reversed_signposted_as),
+        }
+    }
+}
+
+#[async_trait]
+impl PathwayRepo for PgDatabaseAutocommit {
+    async fn put_level(
+        &mut self,
+        origin: &Id<Origin>,
+        level: &WithId<Level>,
+    ) -> Result<()> {
+        put_level(&self.pool, origin, level).await
+    }
+
+    async fn put_pathway(
+        &mut self,
+        origin: &Id<Origin>,
+        pathway: &WithId<Pathway>,
+    ) -> Result<()> {
+        put_pathway(&self.pool, origin, pathway).await
+    }
+
+    async fn get_station_pathways(
+        &mut self,
+        station_id: &Id<Stop>,
+    ) -> Result<Vec<WithOrigin<WithId<Pathway>>>> {
+        get_by_station(&self.pool, station_id).await
+    }
+
+    async fn get_station_levels(
+        &mut self,
+        station_id: &Id<Stop>,
+    ) -> Result<Vec<WithOrigin<WithId<Level>>>> {
+        get_levels_by_station(&self.pool, station_id).await
+    }
+}
+
+#[async_trait]
+impl<'a> PathwayRepo for PgDatabaseTransaction<'a> {
+    async fn put_level(
+        &mut self,
+        origin: &Id<Origin>,
+        level: &WithId<Level>,
+    ) -> Result<()> {
+        put_level(&mut *self.tx, origin, level).await
+    }
+
+    async fn put_pathway(
+        &mut self,
+        origin: &Id<Origin>,
+        pathway: &WithId<Pathway>,
+    ) -> Result<()> {
+        put_pathway(&mut *self.tx, origin, pathway).await
+    }
+
+    async fn get_station_pathways(
+        &mut self,
+        station_id: &Id<Stop>,
+    ) -> Result<Vec<WithOrigin<WithId<Pathway>>>> {
+        get_by_station(&mut *self.tx, station_id).await
+    }
+
+    async fn get_station_levels(
+        &mut self,
+        station_id: &Id<Stop>,
+    ) -> Result<Vec<WithOrigin<WithId<Level>>>> {
+        get_levels_by_station(&mut *self.tx, station_id).await
+    }
+}
@@ -1,5 +1,22 @@
-use model::shape::ShapePoint;
+use async_trait::async_trait;
+use model::{
+    line::Line,
+    origin::{Origin, OriginalIdMapping},
+    shape::{LineShape, Shape, ShapePoint},
+};
+use public_transport::database::{self, ShapeRepo, SubjectRepo};
 use sqlx::prelude::FromRow;
+use utility::id::Id;
+
+use crate::{
+    queries::shape::{
+        create_id, delete_points, get_by_line, id_by_original_id, put_original_id,
+        put_points,
+    },
+    PgDatabaseAutocommit, PgDatabaseTransaction,
+};
+
+use super::trip::RowTripDirection;
 
 #[derive(Debug, Clone, FromRow)]
 pub struct ShapePointRow {
@@ -19,3 +36,112 @@ impl ShapePointRow {
         }
     }
 }
+
+/// A point of a shape used by trips of a line in the given direction.
+#[derive(Debug, Clone, FromRow)]
+pub struct LineShapePointRow {
+    pub direction: Option<RowTripDirection>,
+    pub id: i32,
+    pub sequence: i32,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub distance: Option<f64>,
+}
+
+#[async_trait]
+impl SubjectRepo<Shape> for PgDatabaseAutocommit {
+    async fn id_by_original_id(
+        &mut self,
+        origin: Id<Origin>,
+        original_id: String,
+    ) -> database::Result<Option<Id<Shape>>> {
+        id_by_original_id(&self.pool, origin, original_id).await
+    }
+
+    async fn put_original_id(
+        &mut self,
+        origin: Id<Origin>,
+        original_id: String,
+        id: Id<Shape>,
+    ) -> database::Result<OriginalIdMapping<Shape>> {
+        put_original_id(&self.pool, origin, original_id, id).await
+    }
+}
+
+#[async_trait]
+impl ShapeRepo for PgDatabaseAutocommit {
+    async fn create_shape_id(&mut self) -> database::Result<Id<Shape>> {
+        create_id(&self.pool).await
+    }
+
+    async fn delete_shape_points(
+        &mut self,
+        shape_id: &Id<Shape>,
+    ) -> database::Result<()> {
+        delete_points(&self.pool, shape_id).await
+    }
+
+    async fn put_shape_points(
+        &mut self,
+        shape_id: &Id<Shape>,
+        points: Vec<(i32, ShapePoint)>,
+    ) -> database::Result<()> {
+        put_points(&self.pool, shape_id, points).await
+    }
+
+    async fn get_line_shapes(
+        &mut self,
+        line_id: &Id<Line>,
+    ) -> database::Result<Vec<LineShape>> {
+        get_by_line(&self.pool, line_id).await
+    }
+}
+
+#[async_trait]
+impl<'a> SubjectRepo<Shape> for PgDatabaseTransaction<'a> {
+    async fn id_by_original_id(
+        &mut self,
+        origin: Id<Origin>,
+        original_id: String,
+    ) -> database::Result<Option<Id<Shape>>> {
+        id_by_original_id(&mut *self.tx, origin, original_id).await
+    }
+
+    async fn put_original_id(
+        &mut self,
+        origin: Id<Origin>,
+        original_id: String,
+        id: Id<Shape>,
+    ) -> database::Result<OriginalIdMapping<Shape>> {
+        put_original_id(&mut *self.tx, origin, original_id, id).await
+    }
+}
+
+#[async_trait]
+impl<'a> ShapeRepo for PgDatabaseTransaction<'a> {
+    async fn create_shape_id(&mut self) -> database::Result<Id<Shape>> {
+        create_id(&mut *self.tx).await
+    }
+
+    async fn delete_shape_points(
+        &mut self,
+        shape_id: &Id<Shape>,
+    ) -> database::Result<()> {
+        delete_points(&mut *self.tx, shape_id).await
+    }
+
+    async fn put_shape_points(
+        &mut self,
+        shape_id: &Id<Shape>,
+        points: Vec<(i32, ShapePoint)>,
+    ) -> database::Result<()> {
+        put_points(&mut *self.tx, shape_id, points).await
+    }
+
+    async fn get_line_shapes(
+        &mut self,
+        line_id: &Id<Line>,
+    ) -> database::Result<Vec<LineShape>> {
+        get_by_line(&mut *self.tx, line_id).await
+    }
+}
@@ -6,11 +6,12 @@ use model::{
 };
 use public_transport::database::{Result, SharedMobilityStationRepo, SubjectRepo};
 use sqlx::{prelude::FromRow, types::Json};
-use utility::id::Id;
+use utility::{geo::GeoPolygon, id::Id, normalize::non_blank};
 
 use crate::{
     queries::shared_mobility::{
         get_nearby, id_by_original_id, put_all, put_original_id, update_status,
+        update_statuses,
     },
     PgDatabaseAutocommit, PgDatabaseTransaction,
 };
@@ -29,6 +30,9 @@ pub struct SharedMobilityStationRow {
     pub rental_uri_ios: Option<String>,
     pub rental_uri_web: Option<String>,
     pub status: Option<Json<Status>>,
+    pub area: Option<Json<GeoPolygon>>,
+    pub region_id: Option<String>,
+    pub is_virtual_station: bool,
 }
 
 impl DatabaseRow for SharedMobilityStationRow {
@@ -49,11 +53,14 @@ impl DatabaseRow for SharedMobilityStationRow {
             longitude: self.longitude,
             capacity: self.capacity as u32,
             rental_uris: RentalUris {
-                android: self.rental_uri_android,
-                ios: self.rental_uri_ios,
-                web: self.rental_uri_web,
+                android: non_blank(self.rental_uri_android),
+                ios: non_blank(self.rental_uri_ios),
+                web: non_blank(self.rental_uri_web),
             },
             status: self.status.map(|s| s.0),
+            area: self.area.map(|area| area.0),
+            region_id: self.region_id,
+            is_virtual_station: self.is_virtual_station,
         }
     }
 
@@ -91,6 +98,14 @@ impl SharedMobilityStationRepo for PgDatabaseAutocommit {
     ) -> Result<()> {
         update_status(&self.pool, origin, id, status).await
     }
+
+    async fn update_shared_mobility_station_statuses(
+        &mut self,
+        origin: &Id<Origin>,
+        statuses: &[(Id<SharedMobilityStation>, Option<Status>)],
+    ) -> Result<Vec<Id<SharedMobilityStation>>> {
+        update_statuses(&self.pool, origin, statuses).await
+    }
 }
 
 #[async_trait]
@@ -120,6 +135,14 @@ impl<'a> SharedMobilityStationRepo for PgDatabaseTransaction<'a> {
     ) -> Result<()> {
         update_status(&mut *self.tx, origin, id, status).await
     }
+
+    async fn update_shared_mobility_station_statuses(
+        &mut self,
+        origin: &Id<Origin>,
+        statuses: &[(Id<SharedMobilityStation>, Option<Status>)],
+    ) -> Result<Vec<Id<SharedMobilityStation>>> {
+        update_statuses(&mut *self.tx, origin, statuses).await
+    }
 }
 
 // Subject Repo
@@ -1,21 +1,32 @@
 use super::DatabaseRow;
 use crate::{
     queries::stop::{
-        exists, exists_with_origin, get, get_all, get_by_name, get_nearby,
-        id_by_original_id, insert, merge_candidates, put, put_original_id, search,
-        update,
+        autocomplete, backfill_centroids, exists, exists_with_origin, get, get_all,
+        get_by_name, get_many, get_nearby, get_page_after, get_page_by_agency_after,
+        get_platforms, get_service_summaries, id_by_original_id, insert,
+        merge_candidates, put, put_original_id, redirect, refresh_service_summary,
+        search, update,
     },
     PgDatabaseAutocommit, PgDatabaseTransaction,
 };
 use async_trait::async_trait;
+use chrono::{DateTime, Local};
 use model::{
+    agency::Agency,
     origin::{Origin, OriginalIdMapping},
-    stop::{Location, Stop},
+    stop::{Location, ServiceSummary, Stop, StopAmenity},
     DatabaseEntry, WithId, WithOrigin,
 };
-use public_transport::database::{MergableRepo, Repo, Result, StopRepo, SubjectRepo};
+use public_transport::{
+    capability::{self, Capabilities},
+    database::{DatabaseError, MergableRepo, Repo, Result, StopRepo, SubjectRepo},
+};
 use sqlx::prelude::FromRow;
-use utility::id::{Id, IdWrapper};
+use std::collections::HashMap;
+use utility::{
+    id::{Id, IdWrapper},
+    normalize::{self, non_blank, non_blank_ref},
+};
 
 #[derive(Debug, Clone, FromRow)]
 pub struct StopRow {
@@ -28,6 +39,21 @@ pub struct StopRow {
     pub longitude: Option<f64>,
     pub address: Option<String>,
     pub platform_code: Option<String>,
+    pub amenities: Vec<String>,
+    pub updated_at: Option<DateTime<Local>>,
+}
+
+/// Stored natural sort key of the platform code of a stop.
+pub(crate) fn platform_sort_key(platform_code: &Option<String>) -> Option<String> {
+    non_blank_ref(platform_code).map(normalize::platform_sort_key)
+}
+
+/// Stored representation of the amenities of a stop.
+pub(crate) fn amenity_names(amenities: &[StopAmenity]) -> Vec<String> {
+    amenities
+        .iter()
+        .map(|amenity| amenity.to_string())
+        .collect()
 }
 
 impl DatabaseRow for StopRow {
@@ -43,18 +69,25 @@ impl DatabaseRow for StopRow {
 
     fn to_model(self) -> Self::Model {
         Stop {
-            name: self.name,
-            description: self.description,
+            name: non_blank(self.name),
+            description: non_blank(self.description),
             parent_id: self.parent_id.map(|id| Id::new(id)),
             location: match (self.latitude, self.longitude) {
                 (Some(lat), Some(long)) => Some(Location {
                     latitude: lat,
                     longitude: long,
-                    address: self.address,
+                    address: non_blank(self.address),
                 }),
                 _ => None,
             },
-            platform_code: self.platform_code,
+            platform_code: non_blank(self.platform_code),
+            // unknown amenities were written by a newer version, ignore them.
+            amenities: self
+                .amenities
+                .iter()
+                .filter_map(|amenity| amenity.parse().ok())
+                .collect(),
+            updated_at: self.updated_at,
         }
     }
 
@@ -62,8 +95,8 @@ impl DatabaseRow for StopRow {
         Self {
             id: "".to_owned(),
             origin: stop.origin.raw(),
-            name: stop.content.name,
-            description: stop.content.description,
+            name: non_blank(stop.content.name),
+            description: non_blank(stop.content.description),
             parent_id: stop.content.parent_id.raw(),
             latitude: stop
                 .content
@@ -75,8 +108,12 @@ impl DatabaseRow for StopRow {
                 .location
                 .as_ref()
                 .map(|location| location.longitude),
-            address: stop.content.location.and_then(|location| location.address),
-            platform_code: stop.content.platform_code,
+            address: non_blank(
+                stop.content.location.and_then(|location| location.address),
+            ),
+            platform_code: non_blank(stop.content.platform_code),
+            amenities: amenity_names(&stop.content.amenities),
+            updated_at: None,
         }
     }
 }
@@ -93,6 +130,13 @@ impl Repo<Stop> for PgDatabaseAutocommit {
         get_all(&self.pool).await
     }
 
+    async fn get_many(
+        &mut self,
+        ids: &[Id<Stop>],
+    ) -> Result<Vec<DatabaseEntry<Stop>>> {
+        get_many(&self.pool, ids).await
+    }
+
     async fn insert(
         &mut self,
         element: WithOrigin<Stop>,
@@ -137,6 +181,13 @@ impl<'a> Repo<Stop> for PgDatabaseTransaction<'a> {
         get_all(&mut *self.tx).await
     }
 
+    async fn get_many(
+        &mut self,
+        ids: &[Id<Stop>],
+    ) -> Result<Vec<DatabaseEntry<Stop>>> {
+        get_many(&mut *self.tx, ids).await
+    }
+
     async fn insert(
         &mut self,
         element: WithOrigin<Stop>,
@@ -237,7 +288,79 @@ impl StopRepo for PgDatabaseAutocommit {
         &mut self,
         pattern: S,
     ) -> Result<Vec<DatabaseEntry<Stop>>> {
-        search(&self.pool, pattern).await
+        search(&self.pool, pattern, self.capabilities.search_mode()).await
+    }
+
+    async fn autocomplete<S: Into<String> + Send>(
+        &mut self,
+        pattern: S,
+        limit: usize,
+        origins: &[Id<Origin>],
+    ) -> Result<Vec<DatabaseEntry<Stop>>> {
+        let mode = self.capabilities.search_mode();
+        autocomplete(&self.pool, pattern, limit, origins, mode).await
+    }
+
+    async fn get_page_after(
+        &mut self,
+        after: Option<Id<Stop>>,
+        limit: usize,
+        origins: &[Id<Origin>],
+    ) -> Result<Vec<DatabaseEntry<Stop>>> {
+        get_page_after(&self.pool, after, limit, origins).await
+    }
+
+    async fn get_page_by_agency_after(
+        &mut self,
+        agency_id: &Id<Agency>,
+        include_secondary: bool,
+        after: Option<Id<Stop>>,
+        limit: Option<usize>,
+        origins: &[Id<Origin>],
+    ) -> Result<Vec<DatabaseEntry<Stop>>> {
+        get_page_by_agency_after(
+            &self.pool,
+            agency_id,
+            include_secondary,
+            after,
+            limit,
+            origins,
+        )
+        .await
+    }
+
+    async fn get_platforms(
+        &mut self,
+        parent_id: &Id<Stop>,
+    ) -> Result<Vec<DatabaseEntry<Stop>>> {
+        get_platforms(&self.pool, parent_id).await
+    }
+
+    async fn redirect_stop(
+        &mut self,
+        origin: &Id<Origin>,
+        from: &Id<Stop>,
+        to: &Id<Stop>,
+    ) -> Result<()> {
+        redirect(&self.pool, origin, from, to).await
+    }
+
+    async fn backfill_centroids(&mut self, origin: &Id<Origin>) -> Result<u64> {
+        backfill_centroids(&self.pool, origin).await
+    }
+
+    async fn refresh_service_summary(
+        &mut self,
+        ids: Option<&[&Id<Stop>]>,
+    ) -> Result<u64> {
+        refresh_service_summary(&self.pool, ids).await
+    }
+
+    async fn get_service_summaries(
+        &mut self,
+        ids: &[&Id<Stop>],
+    ) -> Result<HashMap<Id<Stop>, ServiceSummary>> {
+        get_service_summaries(&self.pool, ids).await
     }
 }
 
@@ -263,12 +386,96 @@ impl<'a> StopRepo for PgDatabaseTransaction<'a> {
         &mut self,
         pattern: S,
     ) -> Result<Vec<DatabaseEntry<Stop>>> {
-        search(&mut *self.tx, pattern).await
+        search(&mut *self.tx, pattern, self.capabilities.search_mode()).await
+    }
+
+    async fn autocomplete<S: Into<String> + Send>(
+        &mut self,
+        pattern: S,
+        limit: usize,
+        origins: &[Id<Origin>],
+    ) -> Result<Vec<DatabaseEntry<Stop>>> {
+        let mode = self.capabilities.search_mode();
+        autocomplete(&mut *self.tx, pattern, limit, origins, mode).await
+    }
+
+    async fn get_page_after(
+        &mut self,
+        after: Option<Id<Stop>>,
+        limit: usize,
+        origins: &[Id<Origin>],
+    ) -> Result<Vec<DatabaseEntry<Stop>>> {
+        get_page_after(&mut *self.tx, after, limit, origins).await
+    }
+
+    async fn get_page_by_agency_after(
+        &mut self,
+        agency_id: &Id<Agency>,
+        include_secondary: bool,
+        after: Option<Id<Stop>>,
+        limit: Option<usize>,
+        origins: &[Id<Origin>],
+    ) -> Result<Vec<DatabaseEntry<Stop>>> {
+        get_page_by_agency_after(
+            &mut *self.tx,
+            agency_id,
+            include_secondary,
+            after,
+            limit,
+            origins,
+        )
+        .await
+    }
+
+    async fn get_platforms(
+        &mut self,
+        parent_id: &Id<Stop>,
+    ) -> Result<Vec<DatabaseEntry<Stop>>> {
+        get_platforms(&mut *self.tx, parent_id).await
+    }
+
+    async fn redirect_stop(
+        &mut self,
+        origin: &Id<Origin>,
+        from: &Id<Stop>,
+        to: &Id<Stop>,
+    ) -> Result<()> {
+        redirect(&mut *self.tx, origin, from, to).await
+    }
+
+    async fn backfill_centroids(&mut self, origin: &Id<Origin>) -> Result<u64> {
+        backfill_centroids(&mut *self.tx, origin).await
+    }
+
+    async fn refresh_service_summary(
+        &mut self,
+        ids: Option<&[&Id<Stop>]>,
+    ) -> Result<u64> {
+        refresh_service_summary(&mut *self.tx, ids).await
+    }
+
+    async fn get_service_summaries(
+        &mut self,
+        ids: &[&Id<Stop>],
+    ) -> Result<HashMap<Id<Stop>, ServiceSummary>> {
+        get_service_summaries(&mut *self.tx, ids).await
     }
 }
 
 // Mergable Repo
 
+/// Merge candidates are ranked by the trigram similarity of their names. Fails
+/// with the missing extension, instead of the error of the query.
+fn require_stop_merging(capabilities: Capabilities) -> Result<()> {
+    capability::check(
+        "finding merge candidates of stops",
+        &[capability::STOP_MERGING],
+        capabilities,
+    )
+    .map(|_| ())
+    .map_err(|why| DatabaseError::Other(why.into()))
+}
+
 #[async_trait]
 impl<'a> MergableRepo<Stop> for PgDatabaseTransaction<'a> {
     async fn merge_candidates(
@@ -276,7 +483,14 @@ impl<'a> MergableRepo<Stop> for PgDatabaseTransaction<'a> {
         element: &Stop,
         excluded_origin: &Id<Origin>,
     ) -> Result<Vec<WithOrigin<WithId<Stop>>>> {
-        merge_candidates(&mut *self.tx, element, excluded_origin).await
+        require_stop_merging(self.capabilities)?;
+        merge_candidates(
+            &mut *self.tx,
+            element,
+            excluded_origin,
+            self.merge_candidate_limit,
+        )
+        .await
     }
 }
 
@@ -287,6 +501,13 @@ impl MergableRepo<Stop> for PgDatabaseAutocommit {
         element: &Stop,
         excluded_origin: &Id<Origin>,
     ) -> Result<Vec<WithOrigin<WithId<Stop>>>> {
-        merge_candidates(&self.pool, element, excluded_origin).await
+        require_stop_merging(self.capabilities)?;
+        merge_candidates(
+            &self.pool,
+            element,
+            excluded_origin,
+            self.merge_candidate_limit,
+        )
+        .await
     }
 }
@@ -1,20 +1,29 @@
 use async_trait::async_trait;
-use chrono::{DateTime, Duration, Local};
+use chrono::{DateTime, Duration, Local, NaiveDate};
 use model::{
     origin::{Origin, OriginalIdMapping},
     stop::Stop,
-    trip::{StopTime, Trip},
+    trip::{
+        AreaKind, AreaReference, CouplingKind, Frequency, PickupDropOffType,
+        StopTime, Trip, TripCoupling, TripDirection,
+    },
+    trip_instance::WindowMode,
     DatabaseEntry, WithId, WithOrigin,
 };
 use public_transport::database::{Repo, Result, SubjectRepo, TripRepo};
 use sqlx::prelude::FromRow;
-use utility::id::{Id, IdWrapper};
+use utility::{
+    id::{Id, IdWrapper},
+    normalize::non_blank,
+};
 
 use crate::{
     queries::trip::{
-        delete_stop_times, exists, exists_with_origin, get, get_all,
-        get_all_via_stop, get_stop_times, id_by_original_id, insert, put,
-        put_original_id, put_stop_time, update,
+        delete, delete_stop_times, exists, exists_with_origin, get, get_all,
+        get_all_via_stop, get_frequencies, get_many, get_page_after, get_stop_times,
+        get_trip_couplings, id_by_original_id, insert, put, put_frequency,
+        put_original_id, put_stop_time, put_stop_times, put_trip_couplings,
+        search_text, sync_stop_names, update,
     },
     PgDatabaseAutocommit, PgDatabaseTransaction,
 };
@@ -29,6 +38,9 @@ pub struct TripRow {
     pub service_id: Option<i32>,
     pub headsign: Option<String>,
     pub short_name: Option<String>,
+    pub direction: Option<RowTripDirection>,
+    pub shape_id: Option<i32>,
+    pub updated_at: Option<DateTime<Local>>,
 }
 
 impl DatabaseRow for TripRow {
@@ -46,9 +58,13 @@ impl DatabaseRow for TripRow {
         Trip {
             line_id: Id::new(self.line_id),
             service_id: self.service_id.map(Id::new),
-            headsign: self.headsign,
-            short_name: self.short_name,
+            headsign: non_blank(self.headsign),
+            short_name: non_blank(self.short_name),
+            direction: self.direction.map(RowTripDirection::to_model),
+            shape_id: self.shape_id.map(Id::new),
             stops: vec![],
+            frequencies: vec![],
+            updated_at: self.updated_at,
         }
     }
 
@@ -58,8 +74,34 @@ impl DatabaseRow for TripRow {
             origin: trip.origin.raw(),
             line_id: trip.content.line_id.raw(),
             service_id: trip.content.service_id.raw(),
-            headsign: trip.content.headsign,
-            short_name: trip.content.short_name,
+            headsign: non_blank(trip.content.headsign),
+            short_name: non_blank(trip.content.short_name),
+            direction: trip.content.direction.map(RowTripDirection::from_model),
+            shape_id: trip.content.shape_id.raw(),
+            updated_at: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::Type)]
+#[sqlx(type_name = "trip_direction", rename_all = "snake_case")]
+pub enum RowTripDirection {
+    Outbound,
+    Inbound,
+}
+
+impl RowTripDirection {
+    pub fn to_model(self) -> TripDirection {
+        match self {
+            Self::Outbound => TripDirection::Outbound,
+            Self::Inbound => TripDirection::Inbound,
+        }
+    }
+
+    pub fn from_model(direction: TripDirection) -> Self {
+        match direction {
+            TripDirection::Outbound => Self::Outbound,
+            TripDirection::Inbound => Self::Inbound,
         }
     }
 }
@@ -73,6 +115,83 @@ pub struct StopTimeRow {
     pub arrival_time: Option<i64>,
     pub departure_time: Option<i64>,
     pub stop_headsign: Option<String>,
+    pub pickup_type: Option<RowPickupDropOffType>,
+    pub drop_off_type: Option<RowPickupDropOffType>,
+    pub area_id: Option<String>,
+    pub area_kind: Option<RowAreaKind>,
+    /// Set by the database on write, see migration `0033_stop_time_names`.
+    #[sqlx(default)]
+    pub stop_name: Option<String>,
+}
+
+#[derive(Debug, Clone, sqlx::Type)]
+#[sqlx(type_name = "area_kind", rename_all = "snake_case")]
+pub enum RowAreaKind {
+    LocationGroup,
+    Location,
+}
+
+impl RowAreaKind {
+    pub fn to_model(self) -> AreaKind {
+        match self {
+            Self::LocationGroup => AreaKind::LocationGroup,
+            Self::Location => AreaKind::Location,
+        }
+    }
+
+    pub fn from_model(kind: AreaKind) -> Self {
+        match kind {
+            AreaKind::LocationGroup => Self::LocationGroup,
+            AreaKind::Location => Self::Location,
+        }
+    }
+
+    /// Name of the variant in the database, to bind arrays of kinds as text.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::LocationGroup => "location_group",
+            Self::Location => "location",
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::Type)]
+#[sqlx(type_name = "pickup_drop_off_type", rename_all = "snake_case")]
+pub enum RowPickupDropOffType {
+    Regular,
+    NotAvailable,
+    PhoneAgency,
+    CoordinateWithDriver,
+}
+
+impl RowPickupDropOffType {
+    pub fn to_model(self) -> PickupDropOffType {
+        match self {
+            Self::Regular => PickupDropOffType::Regular,
+            Self::NotAvailable => PickupDropOffType::NotAvailable,
+            Self::PhoneAgency => PickupDropOffType::PhoneAgency,
+            Self::CoordinateWithDriver => PickupDropOffType::CoordinateWithDriver,
+        }
+    }
+
+    pub fn from_model(kind: PickupDropOffType) -> Self {
+        match kind {
+            PickupDropOffType::Regular => Self::Regular,
+            PickupDropOffType::NotAvailable => Self::NotAvailable,
+            PickupDropOffType::PhoneAgency => Self::PhoneAgency,
+            PickupDropOffType::CoordinateWithDriver => Self::CoordinateWithDriver,
+        }
+    }
+
+    /// Name of the variant in the database, to bind arrays of types as text.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Regular => "regular",
+            Self::NotAvailable => "not_available",
+            Self::PhoneAgency => "phone_agency",
+            Self::CoordinateWithDriver => "coordinate_with_driver",
+        }
+    }
 }
 
 impl StopTimeRow {
@@ -82,7 +201,17 @@ impl StopTimeRow {
             stop_id: self.stop_id.map(Id::new),
             arrival_time: self.arrival_time.map(Duration::seconds),
             departure_time: self.departure_time.map(Duration::seconds),
-            stop_headsign: self.stop_headsign,
+            stop_headsign: non_blank(self.stop_headsign),
+            pickup_type: self.pickup_type.map(RowPickupDropOffType::to_model),
+            drop_off_type: self.drop_off_type.map(RowPickupDropOffType::to_model),
+            area_reference: match (self.area_id, self.area_kind) {
+                (Some(id), Some(kind)) => Some(AreaReference {
+                    id,
+                    kind: kind.to_model(),
+                }),
+                _ => None,
+            },
+            stop_name: self.stop_name,
         }
     }
 
@@ -100,11 +229,116 @@ impl StopTimeRow {
                 .content
                 .departure_time
                 .map(|time| time.num_seconds()),
-            stop_headsign: stop_time.content.stop_headsign,
+            stop_headsign: non_blank(stop_time.content.stop_headsign),
+            pickup_type: stop_time
+                .content
+                .pickup_type
+                .map(RowPickupDropOffType::from_model),
+            drop_off_type: stop_time
+                .content
+                .drop_off_type
+                .map(RowPickupDropOffType::from_model),
+            area_id: stop_time
+                .content
+                .area_reference
+                .as_ref()
+                .map(|area| area.id.clone()),
+            area_kind: stop_time
+                .content
+                .area_reference
+                .map(|area| RowAreaKind::from_model(area.kind)),
+            stop_name: None,
         }
     }
 }
 
+#[derive(Debug, Clone, FromRow)]
+pub struct FrequencyRow {
+    pub origin: String,
+    pub trip_id: String,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub headway_secs: i32,
+    pub exact_times: bool,
+}
+
+impl FrequencyRow {
+    pub fn to_model(self) -> (Id<Trip>, WithOrigin<Frequency>) {
+        let frequency = Frequency {
+            start_time: Duration::seconds(self.start_time),
+            end_time: Duration::seconds(self.end_time),
+            headway: Duration::seconds(self.headway_secs as i64),
+            exact_times: self.exact_times,
+        };
+        (
+            Id::new(self.trip_id),
+            WithOrigin::new(Id::new(self.origin), frequency),
+        )
+    }
+}
+
+#[derive(Debug, Clone, sqlx::Type)]
+#[sqlx(type_name = "coupling_kind", rename_all = "snake_case")]
+pub enum RowCouplingKind {
+    Coupled,
+    Split,
+    Joined,
+}
+
+impl RowCouplingKind {
+    pub fn to_model(self) -> CouplingKind {
+        match self {
+            Self::Coupled => CouplingKind::Coupled,
+            Self::Split => CouplingKind::Split,
+            Self::Joined => CouplingKind::Joined,
+        }
+    }
+
+    pub fn from_model(kind: CouplingKind) -> Self {
+        match kind {
+            CouplingKind::Coupled => Self::Coupled,
+            CouplingKind::Split => Self::Split,
+            CouplingKind::Joined => Self::Joined,
+        }
+    }
+
+    /// Name of the variant in the database, to bind arrays of kinds as text.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Coupled => "coupled",
+            Self::Split => "split",
+            Self::Joined => "joined",
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct TripCouplingRow {
+    pub origin: String,
+    pub trip_id: String,
+    pub stop_sequence: i32,
+    pub stop_id: Option<String>,
+    pub coupled_trip_id: String,
+    pub coupled_headsign: Option<String>,
+    pub kind: RowCouplingKind,
+}
+
+impl TripCouplingRow {
+    pub fn to_model(self) -> WithOrigin<TripCoupling> {
+        WithOrigin::new(
+            Id::new(self.origin),
+            TripCoupling {
+                trip_id: Id::new(self.trip_id),
+                stop_sequence: self.stop_sequence,
+                stop_id: self.stop_id.map(Id::new),
+                coupled_trip_id: Id::new(self.coupled_trip_id),
+                coupled_headsign: non_blank(self.coupled_headsign),
+                kind: self.kind.to_model(),
+            },
+        )
+    }
+}
+
 // Repo
 
 #[async_trait]
@@ -117,6 +351,13 @@ impl Repo<Trip> for PgDatabaseAutocommit {
         get_all(&self.pool).await
     }
 
+    async fn get_many(
+        &mut self,
+        ids: &[Id<Trip>],
+    ) -> Result<Vec<DatabaseEntry<Trip>>> {
+        get_many(&self.pool, ids).await
+    }
+
     async fn insert(
         &mut self,
         element: WithOrigin<Trip>,
@@ -161,6 +402,13 @@ impl<'a> Repo<Trip> for PgDatabaseTransaction<'a> {
         get_all(&mut *self.tx).await
     }
 
+    async fn get_many(
+        &mut self,
+        ids: &[Id<Trip>],
+    ) -> Result<Vec<DatabaseEntry<Trip>>> {
+        get_many(&mut *self.tx, ids).await
+    }
+
     async fn insert(
         &mut self,
         element: WithOrigin<Trip>,
@@ -249,6 +497,16 @@ impl TripRepo for PgDatabaseAutocommit {
         put_stop_time(&self.pool, trip_id, stop_time).await
     }
 
+    async fn put_stop_times(
+        &mut self,
+        trip_id: &Id<Trip>,
+        origin: &Id<Origin>,
+        stop_times: &[StopTime],
+        replace: bool,
+    ) -> Result<u64> {
+        put_stop_times(&self.pool, trip_id, origin, stop_times, replace).await
+    }
+
     async fn get_stop_times(
         &mut self,
         trip_id: Id<Trip>,
@@ -261,17 +519,90 @@ impl TripRepo for PgDatabaseAutocommit {
         &mut self,
         trip_id: Id<Trip>,
         origin: Id<Origin>,
-    ) -> Result<()> {
+    ) -> Result<Vec<StopTime>> {
         delete_stop_times(&self.pool, trip_id, origin).await
     }
 
+    async fn sync_stop_names(&mut self) -> Result<u64> {
+        sync_stop_names(&self.pool).await
+    }
+
+    async fn delete_trip(
+        &mut self,
+        id: &Id<Trip>,
+        origin: &Id<Origin>,
+    ) -> Result<()> {
+        delete(&self.pool, id, origin).await
+    }
+
     async fn get_all_via_stop(
         &mut self,
         stops: &[&Id<Stop>],
         start: DateTime<Local>,
         end: DateTime<Local>,
+        mode: WindowMode,
     ) -> Result<Vec<DatabaseEntry<Trip>>> {
-        get_all_via_stop(&self.pool, stops, start, end).await
+        get_all_via_stop(&self.pool, stops, start, end, mode).await
+    }
+
+    async fn get_page_after(
+        &mut self,
+        after: Option<Id<Trip>>,
+        limit: usize,
+        origins: &[Id<Origin>],
+    ) -> Result<Vec<DatabaseEntry<Trip>>> {
+        get_page_after(&self.pool, after, limit, origins).await
+    }
+
+    async fn search_text<S: Into<String> + Send>(
+        &mut self,
+        pattern: S,
+        service_day: NaiveDate,
+        limit: usize,
+    ) -> Result<Vec<DatabaseEntry<Trip>>> {
+        search_text(&self.pool, pattern, service_day, limit).await
+    }
+
+    async fn put_trip_couplings(
+        &mut self,
+        trip_id: &Id<Trip>,
+        origin: &Id<Origin>,
+        stop_sequence: i32,
+        stop_id: Option<&Id<Stop>>,
+        couplings: &[(String, CouplingKind)],
+    ) -> Result<()> {
+        put_trip_couplings(
+            &self.pool,
+            trip_id,
+            origin,
+            stop_sequence,
+            stop_id,
+            couplings,
+        )
+        .await
+    }
+
+    async fn get_trip_couplings(
+        &mut self,
+        trip_ids: &[Id<Trip>],
+    ) -> Result<Vec<WithOrigin<TripCoupling>>> {
+        get_trip_couplings(&self.pool, trip_ids).await
+    }
+
+    async fn put_frequency(
+        &mut self,
+        trip_id: &Id<Trip>,
+        origin: &Id<Origin>,
+        frequency: &Frequency,
+    ) -> Result<()> {
+        put_frequency(&self.pool, trip_id, origin, frequency).await
+    }
+
+    async fn get_frequencies(
+        &mut self,
+        trip_ids: &[Id<Trip>],
+    ) -> Result<Vec<(Id<Trip>, WithOrigin<Frequency>)>> {
+        get_frequencies(&self.pool, trip_ids).await
     }
 }
 
@@ -285,6 +616,16 @@ impl<'a> TripRepo for PgDatabaseTransaction<'a> {
         put_stop_time(&mut *self.tx, trip_id, stop_time).await
     }
 
+    async fn put_stop_times(
+        &mut self,
+        trip_id: &Id<Trip>,
+        origin: &Id<Origin>,
+        stop_times: &[StopTime],
+        replace: bool,
+    ) -> Result<u64> {
+        put_stop_times(&mut *self.tx, trip_id, origin, stop_times, replace).await
+    }
+
     async fn get_stop_times(
         &mut self,
         trip_id: Id<Trip>,
@@ -297,16 +638,89 @@ impl<'a> TripRepo for PgDatabaseTransaction<'a> {
         &mut self,
         trip_id: Id<Trip>,
         origin: Id<Origin>,
-    ) -> Result<()> {
+    ) -> Result<Vec<StopTime>> {
         delete_stop_times(&mut *self.tx, trip_id, origin).await
     }
 
+    async fn sync_stop_names(&mut self) -> Result<u64> {
+        sync_stop_names(&mut *self.tx).await
+    }
+
+    async fn delete_trip(
+        &mut self,
+        id: &Id<Trip>,
+        origin: &Id<Origin>,
+    ) -> Result<()> {
+        delete(&mut *self.tx, id, origin).await
+    }
+
     async fn get_all_via_stop(
         &mut self,
         stops: &[&Id<Stop>],
         start: DateTime<Local>,
         end: DateTime<Local>,
+        mode: WindowMode,
+    ) -> Result<Vec<DatabaseEntry<Trip>>> {
+        get_all_via_stop(&mut *self.tx, stops, start, end, mode).await
+    }
+
+    async fn get_page_after(
+        &mut self,
+        after: Option<Id<Trip>>,
+        limit: usize,
+        origins: &[Id<Origin>],
+    ) -> Result<Vec<DatabaseEntry<Trip>>> {
+        get_page_after(&mut *self.tx, after, limit, origins).await
+    }
+
+    async fn search_text<S: Into<String> + Send>(
+        &mut self,
+        pattern: S,
+        service_day: NaiveDate,
+        limit: usize,
     ) -> Result<Vec<DatabaseEntry<Trip>>> {
-        get_all_via_stop(&mut *self.tx, stops, start, end).await
+        search_text(&mut *self.tx, pattern, service_day, limit).await
+    }
+
+    async fn put_trip_couplings(
+        &mut self,
+        trip_id: &Id<Trip>,
+        origin: &Id<Origin>,
+        stop_sequence: i32,
+        stop_id: Option<&Id<Stop>>,
+        couplings: &[(String, CouplingKind)],
+    ) -> Result<()> {
+        put_trip_couplings(
+            &mut *self.tx,
+            trip_id,
+            origin,
+            stop_sequence,
+            stop_id,
+            couplings,
+        )
+        .await
+    }
+
+    async fn get_trip_couplings(
+        &mut self,
+        trip_ids: &[Id<Trip>],
+    ) -> Result<Vec<WithOrigin<TripCoupling>>> {
+        get_trip_couplings(&mut *self.tx, trip_ids).await
+    }
+
+    async fn put_frequency(
+        &mut self,
+        trip_id: &Id<Trip>,
+        origin: &Id<Origin>,
+        frequency: &Frequency,
+    ) -> Result<()> {
+        put_frequency(&mut *self.tx, trip_id, origin, frequency).await
+    }
+
+    async fn get_frequencies(
+        &mut self,
+        trip_ids: &[Id<Trip>],
+    ) -> Result<Vec<(Id<Trip>, WithOrigin<Frequency>)>> {
+        get_frequencies(&mut *self.tx, trip_ids).await
     }
 }
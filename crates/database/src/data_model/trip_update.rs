@@ -1,16 +1,19 @@
 use async_trait::async_trait;
 use chrono::{DateTime, Local, NaiveDate};
 use model::origin::Origin;
+use model::stop::Stop;
 use model::trip::Trip;
-use model::trip_update::{StopTimeUpdate, TripUpdate, TripUpdateId};
+use model::trip_update::{HistoricDelay, StopTimeUpdate, TripUpdate, TripUpdateId};
 use model::{DatabaseEntry, DateTimeRange, WithId, WithOrigin};
 use public_transport::database::{RealtimeRepo, Result};
 use sqlx::prelude::FromRow;
 use sqlx::types::Json;
-use utility::id::Id;
+use utility::{id::Id, normalize::non_blank};
 
 use crate::queries::trip_update::{
-    get, get_for_trips_in_range, get_timestamp, put_all,
+    delete_historic_delays_before, get, get_for_stop_in_range,
+    get_for_trip_instances, get_historic_delays, get_timestamp, latest_timestamps,
+    put_all, put_historic_delays, put_latest_timestamp, TRIP_IDS_PER_QUERY,
 };
 use crate::{PgDatabaseAutocommit, PgDatabaseTransaction};
 
@@ -55,6 +58,7 @@ pub struct TripUpdateRow {
     pub origin: String,
     pub trip_id: String,
     pub trip_start_date: NaiveDate,
+    pub instance: i32,
     pub status: TripStatus,
     pub stop_time_updates: Json<Vec<StopTimeUpdate>>,
     pub timestamp: Option<DateTime<Local>>,
@@ -64,9 +68,10 @@ impl DatabaseRow for TripUpdateRow {
     type Model = TripUpdate;
 
     fn get_id(&self) -> Id<Self::Model> {
-        Id::new(TripUpdateId::new(
+        Id::new(TripUpdateId::of_instance(
             Id::new(self.trip_id.clone()),
             self.trip_start_date.clone(),
+            self.instance as u32,
         ))
     }
 
@@ -87,6 +92,44 @@ impl DatabaseRow for TripUpdateRow {
     }
 }
 
+#[derive(Debug, Clone, FromRow)]
+pub struct HistoricDelayRow {
+    pub origin: String,
+    pub scheduled_stop_sequence: i32,
+    pub stop_id: Option<String>,
+    pub timestamp: DateTime<Local>,
+    pub arrival_time: Option<DateTime<Local>>,
+    pub departure_time: Option<DateTime<Local>>,
+    pub cause: Option<String>,
+    pub source: Option<String>,
+}
+
+impl HistoricDelayRow {
+    pub fn to_model(self) -> WithOrigin<HistoricDelay> {
+        WithOrigin::new(
+            Id::new(self.origin),
+            HistoricDelay {
+                scheduled_stop_sequence: self.scheduled_stop_sequence,
+                stop_id: self.stop_id.map(Id::new),
+                timestamp: self.timestamp,
+                arrival_time: self.arrival_time,
+                departure_time: self.departure_time,
+                cause: non_blank(self.cause),
+                source: non_blank(self.source),
+            },
+        )
+    }
+}
+
+/// Timestamp of the latest of the updates, which the alert rules check the
+/// freshness of realtime data by.
+fn latest_timestamp(updates: &[WithId<TripUpdate>]) -> Option<DateTime<Local>> {
+    updates
+        .iter()
+        .filter_map(|update| update.content.timestamp)
+        .max()
+}
+
 #[async_trait]
 impl RealtimeRepo for PgDatabaseAutocommit {
     async fn put_trip_updates(
@@ -94,32 +137,79 @@ impl RealtimeRepo for PgDatabaseAutocommit {
         origin: &Id<Origin>,
         updates: &[WithId<TripUpdate>],
     ) -> Result<WithOrigin<Vec<WithId<TripUpdate>>>> {
-        put_all(&self.pool, origin, updates).await
+        let stored = put_all(&self.pool, origin, updates).await?;
+        if let Some(timestamp) = latest_timestamp(updates) {
+            put_latest_timestamp(&self.pool, origin, timestamp).await?;
+        }
+        Ok(stored)
     }
 
     async fn get_realtime_for_trip(
         &mut self,
         trip_id: &Id<Trip>,
         trip_start_date: NaiveDate,
+        instance: u32,
     ) -> Result<DatabaseEntry<TripUpdate>> {
-        get(&self.pool, trip_id, trip_start_date).await
+        get(&self.pool, trip_id, trip_start_date, instance).await
     }
 
     async fn get_timestamp(
         &mut self,
         origin: &Id<Origin>,
-        trip_id: &Id<Trip>,
-        trip_start_date: NaiveDate,
+        id: &TripUpdateId,
     ) -> Result<Option<DateTime<Local>>> {
-        get_timestamp(&self.pool, origin, trip_id, trip_start_date).await
+        get_timestamp(&self.pool, origin, id).await
+    }
+
+    async fn get_realtime_for_trip_instances(
+        &mut self,
+        ids: &[Id<TripUpdate>],
+    ) -> Result<Vec<DatabaseEntry<TripUpdate>>> {
+        let mut result = Vec::new();
+        for chunk in ids.chunks(TRIP_IDS_PER_QUERY) {
+            result.extend(get_for_trip_instances(&self.pool, chunk).await?);
+        }
+        Ok(result)
     }
 
-    async fn get_realtime_for_trips_in_range<'c>(
+    async fn get_realtime_updates_for_stop(
         &mut self,
-        trip_ids: &[Id<Trip>],
+        stop_id: &Id<Stop>,
         range: DateTimeRange<Local>,
     ) -> Result<Vec<DatabaseEntry<TripUpdate>>> {
-        get_for_trips_in_range(&self.pool, trip_ids, range).await
+        get_for_stop_in_range(&self.pool, stop_id, range).await
+    }
+
+    async fn put_historic_delays(
+        &mut self,
+        origin: &Id<Origin>,
+        trip_id: &Id<Trip>,
+        trip_start_date: NaiveDate,
+        delays: &[HistoricDelay],
+    ) -> Result<()> {
+        put_historic_delays(&self.pool, origin, trip_id, trip_start_date, delays)
+            .await
+    }
+
+    async fn get_historic_delays(
+        &mut self,
+        trip_id: &Id<Trip>,
+        trip_start_date: NaiveDate,
+    ) -> Result<Vec<WithOrigin<HistoricDelay>>> {
+        get_historic_delays(&self.pool, trip_id, trip_start_date).await
+    }
+
+    async fn delete_historic_delays_before(
+        &mut self,
+        date: NaiveDate,
+    ) -> Result<u64> {
+        delete_historic_delays_before(&self.pool, date).await
+    }
+
+    async fn latest_update_timestamps(
+        &mut self,
+    ) -> Result<Vec<(Id<Origin>, DateTime<Local>)>> {
+        latest_timestamps(&self.pool).await
     }
 }
 
@@ -130,31 +220,78 @@ impl<'a> RealtimeRepo for PgDatabaseTransaction<'a> {
         origin: &Id<Origin>,
         updates: &[WithId<TripUpdate>],
     ) -> Result<WithOrigin<Vec<WithId<TripUpdate>>>> {
-        put_all(&mut *self.tx, origin, updates).await
+        let stored = put_all(&mut *self.tx, origin, updates).await?;
+        if let Some(timestamp) = latest_timestamp(updates) {
+            put_latest_timestamp(&mut *self.tx, origin, timestamp).await?;
+        }
+        Ok(stored)
     }
 
     async fn get_realtime_for_trip(
         &mut self,
         trip_id: &Id<Trip>,
         trip_start_date: NaiveDate,
+        instance: u32,
     ) -> Result<DatabaseEntry<TripUpdate>> {
-        get(&mut *self.tx, trip_id, trip_start_date).await
+        get(&mut *self.tx, trip_id, trip_start_date, instance).await
     }
 
     async fn get_timestamp(
         &mut self,
         origin: &Id<Origin>,
-        trip_id: &Id<Trip>,
-        trip_start_date: NaiveDate,
+        id: &TripUpdateId,
     ) -> Result<Option<DateTime<Local>>> {
-        get_timestamp(&mut *self.tx, origin, trip_id, trip_start_date).await
+        get_timestamp(&mut *self.tx, origin, id).await
     }
 
-    async fn get_realtime_for_trips_in_range<'c>(
+    async fn get_realtime_for_trip_instances(
         &mut self,
-        trip_ids: &[Id<Trip>],
+        ids: &[Id<TripUpdate>],
+    ) -> Result<Vec<DatabaseEntry<TripUpdate>>> {
+        let mut result = Vec::new();
+        for chunk in ids.chunks(TRIP_IDS_PER_QUERY) {
+            result.extend(get_for_trip_instances(&mut *self.tx, chunk).await?);
+        }
+        Ok(result)
+    }
+
+    async fn get_realtime_updates_for_stop(
+        &mut self,
+        stop_id: &Id<Stop>,
         range: DateTimeRange<Local>,
     ) -> Result<Vec<DatabaseEntry<TripUpdate>>> {
-        get_for_trips_in_range(&mut *self.tx, trip_ids, range).await
+        get_for_stop_in_range(&mut *self.tx, stop_id, range).await
+    }
+
+    async fn put_historic_delays(
+        &mut self,
+        origin: &Id<Origin>,
+        trip_id: &Id<Trip>,
+        trip_start_date: NaiveDate,
+        delays: &[HistoricDelay],
+    ) -> Result<()> {
+        put_historic_delays(&mut *self.tx, origin, trip_id, trip_start_date, delays)
+            .await
+    }
+
+    async fn get_historic_delays(
+        &mut self,
+        trip_id: &Id<Trip>,
+        trip_start_date: NaiveDate,
+    ) -> Result<Vec<WithOrigin<HistoricDelay>>> {
+        get_historic_delays(&mut *self.tx, trip_id, trip_start_date).await
+    }
+
+    async fn delete_historic_delays_before(
+        &mut self,
+        date: NaiveDate,
+    ) -> Result<u64> {
+        delete_historic_delays_before(&mut *self.tx, date).await
+    }
+
+    async fn latest_update_timestamps(
+        &mut self,
+    ) -> Result<Vec<(Id<Origin>, DateTime<Local>)>> {
+        latest_timestamps(&mut *self.tx).await
     }
 }
@@ -1,16 +1,32 @@
-use std::{env, error::Error, future::Future};
+use std::{env, error::Error, future::Future, time::Duration};
 
 use async_trait::async_trait;
 use model::{origin::Origin, WithId};
-use public_transport::database::{
-    Database, DatabaseAutocommit, DatabaseError, DatabaseOperations,
-    DatabaseTransaction,
+use public_transport::{
+    capability::Capabilities,
+    database::{
+        Database, DatabaseAutocommit, DatabaseOperations, DatabaseTransaction,
+    },
 };
 use queries::convert_error;
-use sqlx::Transaction;
+use sqlx::{postgres::PgPoolOptions, Connection, PgConnection, Transaction};
+use utility::id::Id;
 
+mod capabilities;
 pub mod data_model;
+mod migrations;
+mod partitions;
 pub mod queries;
+mod replica;
+
+pub use migrations::{
+    MissingExtensionError, PendingMigration, PendingMigrationsError,
+};
+use replica::Replica;
+pub use replica::{ReplicaStatus, MAX_REPLICA_LAG};
+
+/// Merge candidates of a stop, if `DATABASE_MERGE_CANDIDATE_LIMIT` is not set.
+pub const DEFAULT_MERGE_CANDIDATE_LIMIT: usize = 50;
 
 pub struct DatabaseConnectionInfo {
     pub username: String,
@@ -18,6 +34,21 @@ pub struct DatabaseConnectionInfo {
     pub hostname: String,
     pub port: u16,
     pub database: String,
+    /// Url of a read replica, used for read only operations.
+    pub read_url: Option<String>,
+    /// Months, for which trip updates are kept in addition to the current one.
+    /// `None` keeps them forever.
+    pub trip_update_retention_months: Option<u32>,
+    /// Whether pending migrations are applied on connect. Otherwise they are
+    /// left to a separate migration step.
+    pub auto_migrate: bool,
+    /// Limit of the duration of each statement, set on every connection of the
+    /// pools. Statements of transactions are further limited to the deadline of
+    /// the request, they are performed for. `None` does not limit them.
+    pub statement_timeout: Option<Duration>,
+    /// Maximum number of merge candidates of a stop, the most similar ones
+    /// first. `None` does not limit them.
+    pub merge_candidate_limit: Option<usize>,
 }
 
 impl DatabaseConnectionInfo {
@@ -27,16 +58,37 @@ impl DatabaseConnectionInfo {
         let hostname = env::var("DATABASE_HOST").ok()?;
         let port: u16 = env::var("DATABASE_PORT").ok()?.parse().ok()?;
         let database = env::var("DATABASE_NAME").ok()?;
+        let read_url = env::var("DATABASE_READ_URL").ok();
+        let trip_update_retention_months =
+            env::var("DATABASE_TRIP_UPDATE_RETENTION_MONTHS")
+                .ok()
+                .and_then(|months| months.parse().ok());
+        let auto_migrate = env::var("DATABASE_AUTO_MIGRATE")
+            .map_or(true, |value| value != "false" && value != "0");
+        let statement_timeout = env::var("DATABASE_STATEMENT_TIMEOUT_SECONDS")
+            .ok()
+            .and_then(|seconds| seconds.parse().ok())
+            .map(Duration::from_secs);
+        let merge_candidate_limit = env::var("DATABASE_MERGE_CANDIDATE_LIMIT")
+            .ok()
+            .map_or(Some(DEFAULT_MERGE_CANDIDATE_LIMIT), |limit| {
+                limit.parse().ok().filter(|limit| *limit > 0)
+            });
         Some(Self {
             username,
             password,
             hostname,
             port,
             database,
+            read_url,
+            trip_update_retention_months,
+            auto_migrate,
+            statement_timeout,
+            merge_candidate_limit,
         })
     }
 
-    pub(self) fn postgres_url(self) -> String {
+    pub(self) fn postgres_url(&self) -> String {
         format!(
             "postgres://{}:{}@{}:{}/{}",
             self.username, self.password, self.hostname, self.port, self.database
@@ -44,27 +96,53 @@ impl DatabaseConnectionInfo {
     }
 }
 
+/// Options of the pools, which limit the duration of statements on each of their
+/// connections. Unlike the deadline of a request, which is set per transaction,
+/// this also applies to statements outside of transactions.
+fn pool_options(statement_timeout: Option<Duration>) -> PgPoolOptions {
+    PgPoolOptions::new().after_connect(move |connection, _| {
+        Box::pin(async move {
+            if let Some(timeout) = statement_timeout {
+                // a timeout of zero would disable it.
+                let millis = timeout.as_millis().max(1);
+                // `SET` does not support bind parameters.
+                sqlx::query(&format!("SET statement_timeout = {}", millis))
+                    .execute(connection)
+                    .await?;
+            }
+            Ok(())
+        })
+    })
+}
+
 #[derive(Clone)]
 pub struct PgDatabase {
     connection: sqlx::PgPool,
+    replica: Option<Replica>,
+    /// Probed once on connect, as extensions are rarely installed at runtime.
+    capabilities: Capabilities,
+    /// Set on every connection, see [`DatabaseConnectionInfo::statement_timeout`].
+    statement_timeout: Option<Duration>,
+    merge_candidate_limit: Option<usize>,
 }
 
 pub struct PgDatabaseTransaction<'a> {
     tx: Transaction<'a, sqlx::Postgres>,
+    capabilities: Capabilities,
+    merge_candidate_limit: Option<usize>,
 }
 
 #[async_trait]
 impl<'a> DatabaseTransaction for PgDatabaseTransaction<'a> {
     async fn commit(self) -> public_transport::database::Result<()> {
-        self.tx.commit().await.map_err(|why| match why {
-            sqlx::Error::RowNotFound => DatabaseError::NotFound,
-            _ => DatabaseError::Other(Box::new(why)),
-        })
+        self.tx.commit().await.map_err(convert_error)
     }
 }
 
 pub struct PgDatabaseAutocommit {
     pool: sqlx::PgPool,
+    capabilities: Capabilities,
+    merge_candidate_limit: Option<usize>,
 }
 
 impl DatabaseAutocommit for PgDatabaseAutocommit {}
@@ -74,11 +152,68 @@ impl PgDatabase {
         database_connection_info: DatabaseConnectionInfo,
     ) -> Result<Self, Box<dyn Error>> {
         let url = database_connection_info.postgres_url();
-        let pool = sqlx::postgres::PgPool::connect(&url).await?;
+        let statement_timeout = database_connection_info.statement_timeout;
+        let pool = pool_options(statement_timeout).connect(&url).await?;
+
+        if database_connection_info.auto_migrate {
+            // fails fast on missing extensions, instead of inside a migration.
+            let pending = migrations::pending(&pool).await?;
+            migrations::check_extensions(&pool, &pending).await?;
+            // migrations may take longer than the statement timeout of the pool.
+            let mut connection = PgConnection::connect(&url).await?;
+            migrations::MIGRATOR.run(&mut connection).await?;
+            connection.close().await?;
+        } else {
+            // nothing else may touch the schema before it is migrated.
+            let pending = migrations::pending(&pool).await?;
+            if !pending.is_empty() {
+                return Err(Box::new(PendingMigrationsError(pending)));
+            }
+        }
+        let capabilities = capabilities::probe(&pool).await?;
+        partitions::spawn_maintenance(
+            pool.clone(),
+            database_connection_info.trip_update_retention_months,
+        );
 
-        sqlx::migrate!("./migrations").run(&pool).await?;
+        let replica = match &database_connection_info.read_url {
+            Some(read_url) => Some(
+                Replica::connect(pool_options(statement_timeout), read_url).await?,
+            ),
+            None => None,
+        };
 
-        Ok(Self { connection: pool })
+        Ok(Self {
+            connection: pool,
+            replica,
+            capabilities,
+            statement_timeout,
+            merge_candidate_limit: database_connection_info.merge_candidate_limit,
+        })
+    }
+
+    /// Maintains the partitions of trip updates and stop times as of the given
+    /// day right away, which otherwise happens periodically in the background.
+    /// Returns `false`, if another process maintains them right now.
+    pub async fn maintain_partitions(
+        &self,
+        today: chrono::NaiveDate,
+        retention_months: Option<u32>,
+    ) -> Result<bool, sqlx::Error> {
+        partitions::maintain(&self.connection, today, retention_months).await
+    }
+
+    /// Status of the read replica, or `None` if no read replica is configured.
+    pub fn replica_status(&self) -> Option<ReplicaStatus> {
+        self.replica.as_ref().map(Replica::status)
+    }
+
+    /// Whether the primary database is reachable.
+    pub async fn is_reachable(&self) -> bool {
+        sqlx::query("SELECT 1")
+            .execute(&self.connection)
+            .await
+            .is_ok()
     }
 }
 
@@ -92,19 +227,39 @@ impl Database for PgDatabase {
     fn auto(&self) -> Self::Autocommit {
         PgDatabaseAutocommit {
             pool: self.connection.clone(),
+            capabilities: self.capabilities,
+            merge_candidate_limit: self.merge_candidate_limit,
+        }
+    }
+
+    /// Uses the read replica, unless it lags too far behind.
+    fn read(&self) -> Self::Autocommit {
+        match &self.replica {
+            Some(replica) if replica.status().serves_reads => PgDatabaseAutocommit {
+                pool: replica.pool.clone(),
+                capabilities: self.capabilities,
+                merge_candidate_limit: self.merge_candidate_limit,
+            },
+            _ => self.auto(),
         }
     }
 
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
     async fn transaction(
         &self,
     ) -> public_transport::database::Result<Self::Transaction> {
-        let tx: Transaction<'_, sqlx::Postgres> = self
-            .connection
-            .begin()
-            .await
-            .map_err(|why| convert_error(why))?;
+        let mut tx: Transaction<'_, sqlx::Postgres> =
+            self.connection.begin().await.map_err(convert_error)?;
+        queries::apply_deadline(&mut tx, self.statement_timeout).await?;
 
-        Ok(PgDatabaseTransaction { tx })
+        Ok(PgDatabaseTransaction {
+            tx,
+            capabilities: self.capabilities,
+            merge_candidate_limit: self.merge_candidate_limit,
+        })
     }
 
     async fn perform_transaction<T, F, Fut>(
@@ -116,14 +271,16 @@ impl Database for PgDatabase {
         F: Send + FnOnce(&mut Self::Transaction) -> Fut + Send,
         Fut: Future<Output = public_transport::database::Result<T>> + Send,
     {
-        let tx: Transaction<'_, sqlx::Postgres> = self
-            .connection
-            .begin()
-            .await
-            .map_err(|why| convert_error(why))?;
+        let mut tx: Transaction<'_, sqlx::Postgres> =
+            self.connection.begin().await.map_err(convert_error)?;
+        queries::apply_deadline(&mut tx, self.statement_timeout).await?;
 
         // run operations
-        let mut tx = PgDatabaseTransaction { tx };
+        let mut tx = PgDatabaseTransaction {
+            tx,
+            capabilities: self.capabilities,
+            merge_candidate_limit: self.merge_candidate_limit,
+        };
         let result = action(&mut tx).await;
 
         tx.commit().await?;
@@ -146,6 +303,21 @@ impl DatabaseOperations for PgDatabaseAutocommit {
     ) -> public_transport::database::Result<WithId<Origin>> {
         queries::origin::put(&self.pool, origin).await
     }
+
+    async fn rename_origin(
+        &mut self,
+        id: &Id<Origin>,
+        origin: WithId<Origin>,
+    ) -> public_transport::database::Result<WithId<Origin>> {
+        queries::origin::rename(&self.pool, id, origin).await
+    }
+
+    async fn origin_by_alias(
+        &mut self,
+        alias: &Id<Origin>,
+    ) -> public_transport::database::Result<Option<Id<Origin>>> {
+        queries::origin::get_by_alias(&self.pool, alias).await
+    }
 }
 
 #[async_trait]
@@ -162,4 +334,19 @@ impl<'a> DatabaseOperations for PgDatabaseTransaction<'a> {
     ) -> public_transport::database::Result<WithId<Origin>> {
         queries::origin::put(&mut *self.tx, origin).await
     }
+
+    async fn rename_origin(
+        &mut self,
+        id: &Id<Origin>,
+        origin: WithId<Origin>,
+    ) -> public_transport::database::Result<WithId<Origin>> {
+        queries::origin::rename(&mut *self.tx, id, origin).await
+    }
+
+    async fn origin_by_alias(
+        &mut self,
+        alias: &Id<Origin>,
+    ) -> public_transport::database::Result<Option<Id<Origin>>> {
+        queries::origin::get_by_alias(&mut *self.tx, alias).await
+    }
 }
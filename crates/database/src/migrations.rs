@@ -0,0 +1,143 @@
+use std::{error::Error, fmt};
+
+use public_transport::capability::Capability;
+use sqlx::{migrate::Migrator, PgPool};
+
+use crate::capabilities;
+
+/// Migrations embedded at compile time.
+pub(crate) static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Capabilities, which migrations create objects of unconditionally, by version
+/// of the migration. Later migrations skip such objects, if the capability is
+/// missing, see 0038.
+const REQUIRED_CAPABILITIES: &[(i64, Capability)] =
+    &[(1, Capability::PgTrgm), (3, Capability::PgTrgm)];
+
+#[derive(Debug, Clone)]
+pub struct PendingMigration {
+    pub version: i64,
+    pub description: String,
+}
+
+/// The database misses migrations, while applying them is left to a separate
+/// migration step. Using it anyway would fail on the first query of a missing
+/// table or column, or worse, write data of the wrong shape.
+#[derive(Debug)]
+pub struct PendingMigrationsError(pub Vec<PendingMigration>);
+
+impl fmt::Display for PendingMigrationsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let migrations = self
+            .0
+            .iter()
+            .map(|migration| {
+                format!("{} ({})", migration.version, migration.description)
+            })
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "pending migrations {} were not applied, as DATABASE_AUTO_MIGRATE is disabled",
+            migrations.join(", ")
+        )
+    }
+}
+
+impl Error for PendingMigrationsError {}
+
+/// Pending migrations need an extension, which is neither visible to the role,
+/// e.g. as it is installed in a schema outside of its search path, nor can be
+/// created by the first migration. Migrating anyway would fail on the first object
+/// of the extension with a cryptic error.
+#[derive(Debug)]
+pub struct MissingExtensionError {
+    pub capability: Capability,
+    pub migrations: Vec<PendingMigration>,
+}
+
+impl fmt::Display for MissingExtensionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let migrations = self
+            .migrations
+            .iter()
+            .map(|migration| {
+                format!("{} ({})", migration.version, migration.description)
+            })
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "CREATE EXTENSION {} required by pending migrations {}, but it is \
+             neither visible in the search path of the role nor available to be \
+             created",
+            self.capability.extension(),
+            migrations.join(", ")
+        )
+    }
+}
+
+impl Error for MissingExtensionError {}
+
+/// Checks, that the pending migrations find the extensions they need. Extensions,
+/// which are not installed yet, but available, are created by the first migration.
+pub(crate) async fn check_extensions(
+    pool: &PgPool,
+    pending: &[PendingMigration],
+) -> Result<(), Box<dyn Error>> {
+    let capabilities = capabilities::probe(pool).await?;
+    for capability in Capability::ALL {
+        let migrations = pending
+            .iter()
+            .filter(|migration| {
+                REQUIRED_CAPABILITIES.contains(&(migration.version, capability))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if migrations.is_empty() || capabilities.has(capability) {
+            continue;
+        }
+        let (creatable,): (bool,) = sqlx::query_as(
+            "
+            SELECT
+                NOT EXISTS (SELECT 1 FROM pg_extension WHERE extname = $1)
+                AND EXISTS (SELECT 1 FROM pg_available_extensions WHERE name = $1);
+            ",
+        )
+        .bind(capability.extension())
+        .fetch_one(pool)
+        .await?;
+        if !creatable {
+            return Err(Box::new(MissingExtensionError {
+                capability,
+                migrations,
+            }));
+        }
+    }
+    Ok(())
+}
+
+/// Migrations, which have not been applied successfully yet. Nothing is written,
+/// not even the table sqlx keeps track of applied migrations in.
+pub(crate) async fn pending(
+    pool: &PgPool,
+) -> Result<Vec<PendingMigration>, sqlx::Error> {
+    let is_tracked: bool =
+        sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+            .fetch_one(pool)
+            .await?;
+    let applied: Vec<i64> = if is_tracked {
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(pool)
+            .await?
+    } else {
+        vec![]
+    };
+    Ok(MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .filter(|migration| !applied.contains(&migration.version))
+        .map(|migration| PendingMigration {
+            version: migration.version,
+            description: migration.description.to_string(),
+        })
+        .collect())
+}
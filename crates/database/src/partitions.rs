@@ -0,0 +1,396 @@
+//! Maintenance of the partitions of `trip_updates` and `stop_times`, see migration
+//! 0021. Both tables are moved to their partitioned layout online. Monthly
+//! partitions of trip updates are created ahead of time, so that new trip updates
+//! never end up in the default partition, and dropped as a whole once they exceed
+//! the retention. Trip updates, which are older than the move, stay in the
+//! default partition until they expire. Those of later months are moved into the
+//! partition of their month, once it is created. Stop times are partitioned by
+//! origin.
+//!
+//! Only one process maintains the partitions at a time, the one holding the
+//! advisory lock [`LEADER_LOCK`].
+
+use std::time::Duration;
+
+use chrono::{Datelike, Local, Months, NaiveDate};
+use sqlx::{Connection, PgConnection, PgPool};
+
+/// Months after the current one, for which partitions exist.
+const MONTHS_AHEAD: u32 = 3;
+
+/// Interval, in which partitions are maintained.
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+const PARTITION_PREFIX: &str = "trip_updates_p";
+
+/// Advisory lock of the process maintaining the partitions.
+const LEADER_LOCK: &str = "partition_maintenance";
+
+/// Pages of a table copied per transaction, while it is moved to its partitioned
+/// layout.
+const COPY_BATCH_PAGES: i64 = 1000;
+
+/// How long maintenance waits for a lock, e.g. to replace a table by its
+/// partitioned copy. Queries of the table queue behind the waiting maintenance,
+/// so it rather gives up and tries again.
+const LOCK_TIMEOUT: &str = "1s";
+
+/// Attempts to replace a table by its partitioned copy in one maintenance.
+const FINISH_ATTEMPTS: u32 = 10;
+
+/// Error code of Postgres, if a lock is not acquired in time.
+const LOCK_NOT_AVAILABLE: &str = "55P03";
+
+#[derive(Debug, Clone, Copy)]
+enum PartitionKey {
+    /// Range partitions by month of `trip_start_date`.
+    Month,
+    /// List partitions by `origin`.
+    Origin,
+}
+
+/// Partitioned layout of a table.
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    table: &'static str,
+    key_columns: &'static str,
+    partition_by: &'static str,
+    key: PartitionKey,
+}
+
+const LAYOUTS: [Layout; 2] = [
+    Layout {
+        table: "trip_updates",
+        key_columns: "origin, trip_id, trip_start_date, instance",
+        partition_by: "RANGE (trip_start_date)",
+        key: PartitionKey::Month,
+    },
+    Layout {
+        table: "stop_times",
+        key_columns: "origin, trip_id, stop_sequence",
+        partition_by: "LIST (origin)",
+        key: PartitionKey::Origin,
+    },
+];
+
+/// How far a table is moved to its partitioned layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Progress {
+    Unpartitioned,
+    /// The partitioned copy exists, and writes are mirrored into it.
+    Copying,
+    Partitioned,
+}
+
+/// Maintains the partitions now and periodically in the background. With a
+/// retention of `None`, no trip updates are dropped.
+pub(crate) fn spawn_maintenance(pool: PgPool, retention_months: Option<u32>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(MAINTENANCE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let today = Local::now().date_naive();
+            if let Err(why) = maintain(&pool, today, retention_months).await {
+                log::error!("maintaining partitions failed: {:?}", why);
+            }
+        }
+    });
+}
+
+/// Maintains the partitions, unless another process does right now. Returns
+/// whether they were maintained.
+pub(crate) async fn maintain(
+    pool: &PgPool,
+    today: NaiveDate,
+    retention_months: Option<u32>,
+) -> Result<bool, sqlx::Error> {
+    // a connection of its own, so that the lock and settings of the session end
+    // with it, instead of being returned to the pool.
+    let mut conn = PgConnection::connect_with(&pool.connect_options()).await?;
+    let is_leader: bool =
+        sqlx::query_scalar("SELECT pg_try_advisory_lock(hashtext($1));")
+            .bind(LEADER_LOCK)
+            .fetch_one(&mut conn)
+            .await?;
+    let result = if is_leader {
+        maintain_as_leader(&mut conn, today, retention_months).await
+    } else {
+        Ok(())
+    };
+    conn.close().await?;
+    result.map(|_| is_leader)
+}
+
+async fn maintain_as_leader(
+    conn: &mut PgConnection,
+    today: NaiveDate,
+    retention_months: Option<u32>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&format!("SET lock_timeout = '{}';", LOCK_TIMEOUT))
+        .execute(&mut *conn)
+        .await?;
+    for layout in LAYOUTS {
+        move_to_partitions(conn, layout, today).await?;
+    }
+    if let Some(table) = partitioned(conn, "stop_times").await? {
+        let mut tx = conn.begin().await?;
+        create_origin_partitions(&mut tx, &table).await?;
+        tx.commit().await?;
+    }
+    let Some(table) = partitioned(conn, "trip_updates").await? else {
+        return Ok(());
+    };
+    let this_month = first_of_month(today);
+    for ahead in 0..=MONTHS_AHEAD {
+        create_partition(conn, &table, this_month + Months::new(ahead)).await?;
+    }
+    let Some(retention_months) = retention_months else {
+        return Ok(());
+    };
+    // whole months are kept, the current one does not count.
+    let expired_before = this_month - Months::new(retention_months);
+    for (name, month) in partitions(conn, &table).await? {
+        if month + Months::new(1) <= expired_before {
+            sqlx::query(&format!("DROP TABLE {};", name))
+                .execute(&mut *conn)
+                .await?;
+            log::info!("dropped expired partition {}.", name);
+        }
+    }
+    sqlx::query("DELETE FROM trip_updates_default WHERE trip_start_date < $1;")
+        .bind(expired_before)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Moves the table to its partitioned layout, continuing where the last
+/// maintenance stopped. Leaves the table being copied, if it is not replaced by
+/// its copy in time, so that the next maintenance tries again.
+async fn move_to_partitions(
+    conn: &mut PgConnection,
+    layout: Layout,
+    today: NaiveDate,
+) -> Result<(), sqlx::Error> {
+    match progress(conn, layout.table).await? {
+        Progress::Partitioned => return Ok(()),
+        Progress::Copying => {}
+        Progress::Unpartitioned => {
+            let mut tx = conn.begin().await?;
+            sqlx::query("SELECT begin_partitioning($1, $2, $3);")
+                .bind(layout.table)
+                .bind(layout.key_columns)
+                .bind(layout.partition_by)
+                .execute(&mut *tx)
+                .await?;
+            let copy = format!("{}_partitioned", layout.table);
+            match layout.key {
+                PartitionKey::Month => {
+                    let this_month = first_of_month(today);
+                    for ahead in 0..=MONTHS_AHEAD {
+                        create_partition(
+                            &mut tx,
+                            &copy,
+                            this_month + Months::new(ahead),
+                        )
+                        .await?;
+                    }
+                }
+                PartitionKey::Origin => {
+                    create_origin_partitions(&mut tx, &copy).await?;
+                }
+            }
+            tx.commit().await?;
+            log::info!("started moving {} to partitions.", layout.table);
+        }
+    }
+    loop {
+        let is_copied: bool =
+            sqlx::query_scalar("SELECT copy_partitioning_batch($1, $2);")
+                .bind(layout.table)
+                .bind(COPY_BATCH_PAGES)
+                .fetch_one(&mut *conn)
+                .await?;
+        if is_copied {
+            break;
+        }
+    }
+    for attempt in 1..=FINISH_ATTEMPTS {
+        let result = sqlx::query("SELECT finish_partitioning($1);")
+            .bind(layout.table)
+            .execute(&mut *conn)
+            .await;
+        match result {
+            Ok(_) => {
+                log::info!("moved {} to partitions.", layout.table);
+                return Ok(());
+            }
+            Err(sqlx::Error::Database(why))
+                if why.code().as_deref() == Some(LOCK_NOT_AVAILABLE) =>
+            {
+                log::warn!(
+                    "{} is in use, retrying to replace it ({}/{}).",
+                    layout.table,
+                    attempt,
+                    FINISH_ATTEMPTS
+                );
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Err(why) => return Err(why),
+        }
+    }
+    Ok(())
+}
+
+/// Creates the partitions of the origins, which have none yet. Must be called in a
+/// transaction. The origins are locked first, as renaming an origin locks them
+/// before the tables referencing them, while a new partition locks its table
+/// before the origins it references, so that both would deadlock otherwise.
+async fn create_origin_partitions(
+    conn: &mut PgConnection,
+    table: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("LOCK TABLE origins IN SHARE ROW EXCLUSIVE MODE;")
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("SELECT create_origin_partitions($1::regclass);")
+        .bind(table)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+async fn progress(
+    conn: &mut PgConnection,
+    table: &str,
+) -> Result<Progress, sqlx::Error> {
+    let (is_partitioned, is_copying): (bool, bool) = sqlx::query_as(
+        "
+        SELECT
+            EXISTS (
+                SELECT 1 FROM pg_class
+                WHERE oid = to_regclass($1) AND relkind = 'p'
+            ),
+            to_regclass($1 || '_partitioned') IS NOT NULL;
+        ",
+    )
+    .bind(table)
+    .fetch_one(conn)
+    .await?;
+    Ok(if is_partitioned {
+        Progress::Partitioned
+    } else if is_copying {
+        Progress::Copying
+    } else {
+        Progress::Unpartitioned
+    })
+}
+
+/// The partitioned table, i.e. the table itself or its copy, while it is moved.
+async fn partitioned(
+    conn: &mut PgConnection,
+    table: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    Ok(match progress(conn, table).await? {
+        Progress::Partitioned => Some(table.to_owned()),
+        Progress::Copying => Some(format!("{}_partitioned", table)),
+        Progress::Unpartitioned => None,
+    })
+}
+
+/// Creates the partition of the month, unless it exists. Trip updates of the
+/// month, which the default partition holds, e.g. because they were written while
+/// the table was moved to partitions, are moved into it.
+async fn create_partition(
+    conn: &mut PgConnection,
+    table: &str,
+    month: NaiveDate,
+) -> Result<(), sqlx::Error> {
+    let name = partition_name(month);
+    let exists: bool = sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL;")
+        .bind(&name)
+        .fetch_one(&mut *conn)
+        .await?;
+    if exists {
+        return Ok(());
+    }
+    let next_month = month + Months::new(1);
+    let mut tx = conn.begin().await?;
+    sqlx::query(&format!(
+        "CREATE TABLE {} (LIKE {} INCLUDING DEFAULTS INCLUDING CONSTRAINTS);",
+        name, table
+    ))
+    .execute(&mut *tx)
+    .await?;
+    // rows of the month can only be in the default partition, as long as the
+    // partition of the month does not exist.
+    let moved = sqlx::query(&format!(
+        "
+        WITH moved AS (
+            DELETE FROM {} WHERE trip_start_date >= $1 AND trip_start_date < $2
+            RETURNING *
+        )
+        INSERT INTO {} SELECT * FROM moved;
+        ",
+        table, name
+    ))
+    .bind(month)
+    .bind(next_month)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    sqlx::query(&format!(
+        "ALTER TABLE {} ATTACH PARTITION {} FOR VALUES FROM ('{}') TO ('{}');",
+        table, name, month, next_month,
+    ))
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    if moved > 0 {
+        log::info!(
+            "moved {} trip updates from the default partition to {}.",
+            moved,
+            name
+        );
+    }
+    Ok(())
+}
+
+/// Monthly partitions by name and first day of their month.
+async fn partitions(
+    conn: &mut PgConnection,
+    table: &str,
+) -> Result<Vec<(String, NaiveDate)>, sqlx::Error> {
+    let names: Vec<String> = sqlx::query_scalar(
+        "
+        SELECT
+            c.relname::text
+        FROM
+            pg_inherits i
+            JOIN pg_class c ON c.oid = i.inhrelid
+        WHERE
+            i.inhparent = $1::regclass;
+        ",
+    )
+    .bind(table)
+    .fetch_all(conn)
+    .await?;
+    Ok(names
+        .into_iter()
+        .filter_map(|name| {
+            let month = name.strip_prefix(PARTITION_PREFIX)?;
+            let month =
+                NaiveDate::parse_from_str(&format!("{}_01", month), "%Y_%m_%d")
+                    .ok()?;
+            Some((name, month))
+        })
+        .collect())
+}
+
+fn partition_name(month: NaiveDate) -> String {
+    format!("{}{}", PARTITION_PREFIX, month.format("%Y_%m"))
+}
+
+fn first_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap_or(date)
+}
@@ -4,7 +4,7 @@ use model::{
     DatabaseEntry, WithId, WithOrigin,
 };
 use public_transport::database::Result;
-use utility::{id::Id, let_also::LetAlso};
+use utility::{id::Id, let_also::LetAlso, normalize::non_blank_ref};
 
 use crate::data_model::{
     agency::AgencyRow, with_origin_and_id, with_origins, with_origins_and_ids,
@@ -44,7 +44,8 @@ where
     sqlx::query_as(
         "
         SELECT id, origin, name, website, phone_number, email, fare_url
-        FROM agencies;
+        FROM agencies
+        ORDER BY id, origin;
         ",
     )
     .fetch_all(executor)
@@ -55,6 +56,29 @@ where
     })
 }
 
+pub async fn get_many<'c, E>(
+    executor: E,
+    ids: &[Id<Agency>],
+) -> Result<Vec<DatabaseEntry<Agency>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        SELECT id, origin, name, website, phone_number, email, fare_url
+        FROM agencies
+        WHERE id = ANY($1);
+        ",
+    )
+    .bind(ids.iter().map(|id| id.raw()).collect::<Vec<_>>())
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .let_owned(|agencies: Vec<AgencyRow>| {
+        Ok(DatabaseEntry::gather_many(with_origins_and_ids(agencies)))
+    })
+}
+
 pub async fn insert<'c, E>(
     executor: E,
     agency: WithOrigin<Agency>,
@@ -79,9 +103,9 @@ where
     .bind(agency.origin.raw())
     .bind(&agency.content.name)
     .bind(&agency.content.website)
-    .bind(&agency.content.phone_number)
-    .bind(&agency.content.email)
-    .bind(&agency.content.fare_url)
+    .bind(non_blank_ref(&agency.content.phone_number))
+    .bind(non_blank_ref(&agency.content.email))
+    .bind(non_blank_ref(&agency.content.fare_url))
     .fetch_one(executor)
     .await
     .map(|row: AgencyRow| with_origin_and_id(row))
@@ -121,9 +145,9 @@ where
     .bind(agency.origin.raw())
     .bind(&agency.content.content.name)
     .bind(&agency.content.content.website)
-    .bind(&agency.content.content.phone_number)
-    .bind(&agency.content.content.email)
-    .bind(&agency.content.content.fare_url)
+    .bind(non_blank_ref(&agency.content.content.phone_number))
+    .bind(non_blank_ref(&agency.content.content.email))
+    .bind(non_blank_ref(&agency.content.content.fare_url))
     .fetch_one(executor)
     .await
     .map_err(|why| convert_error(why))
@@ -151,9 +175,9 @@ where
     )
     .bind(&agency.content.content.name)
     .bind(&agency.content.content.website)
-    .bind(&agency.content.content.phone_number)
-    .bind(&agency.content.content.email)
-    .bind(&agency.content.content.fare_url)
+    .bind(non_blank_ref(&agency.content.content.phone_number))
+    .bind(non_blank_ref(&agency.content.content.email))
+    .bind(non_blank_ref(&agency.content.content.fare_url))
     .bind(agency.origin.raw())
     .bind(agency.content.id.raw())
     .fetch_one(executor)
@@ -0,0 +1,69 @@
+use public_transport::{database::Result, notification::Alert};
+use sqlx::{Executor, Postgres};
+
+use crate::data_model::alert::AlertRow;
+
+use super::convert_error;
+
+/// Inserts the incident, unless one of its rule is open already, e.g. opened by
+/// another instance. Returns whether it was inserted.
+pub async fn open<'c, E>(executor: E, alert: &Alert) -> Result<bool>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_scalar(
+        "
+        INSERT INTO alerts(rule, message, opened_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (rule) DO NOTHING
+        RETURNING rule;
+        ",
+    )
+    .bind(&alert.rule)
+    .bind(&alert.message)
+    .bind(alert.opened_at)
+    .fetch_optional(executor)
+    .await
+    .map_err(convert_error)
+    .map(|rule: Option<String>| rule.is_some())
+}
+
+pub async fn close<'c, E>(executor: E, rule: &str) -> Result<Option<Alert>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        DELETE FROM alerts
+        WHERE
+            rule = $1
+        RETURNING
+            rule, message, opened_at;
+        ",
+    )
+    .bind(rule)
+    .fetch_optional(executor)
+    .await
+    .map_err(convert_error)
+    .map(|row: Option<AlertRow>| row.map(AlertRow::to_model))
+}
+
+pub async fn get_all<'c, E>(executor: E) -> Result<Vec<Alert>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        SELECT
+            rule, message, opened_at
+        FROM
+            alerts
+        ORDER BY
+            opened_at, rule;
+        ",
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)
+    .map(|rows: Vec<AlertRow>| rows.into_iter().map(AlertRow::to_model).collect())
+}
@@ -0,0 +1,163 @@
+use chrono::{DateTime, Local};
+use model::change::{Change, ChangePosition};
+use public_transport::database::Result;
+use sqlx::{Executor, Postgres};
+
+use crate::data_model::change_log::ChangeRow;
+
+use super::convert_error;
+
+pub async fn get_after<'c, E>(
+    executor: E,
+    after: Option<ChangePosition>,
+    limit: usize,
+) -> Result<Vec<Change>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        SELECT
+            id, entity, entity_id, origin, operation, changed_at
+        FROM
+            change_log
+        WHERE
+            $1::bigint IS NULL OR id > $1
+        ORDER BY
+            id
+        LIMIT $2;
+        ",
+    )
+    .bind(after.map(|position| position.sequence))
+    .bind(limit as i64)
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)
+    .map(|rows: Vec<ChangeRow>| rows.into_iter().map(ChangeRow::to_model).collect())
+}
+
+/// Tries to acquire the transaction scoped advisory lock for flushing pending
+/// changes, so that changes are only flushed by one transaction at a time.
+pub async fn try_lock_flush<'c, E>(executor: E) -> Result<bool>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_scalar(
+        "SELECT pg_try_advisory_xact_lock(hashtext('change_log_flush'));",
+    )
+    .fetch_one(executor)
+    .await
+    .map_err(convert_error)
+}
+
+/// Moves up to `limit` changes of finished transactions from the pending changes
+/// to the change log, oldest transaction first. Changes of running transactions
+/// are not visible yet, so that they are flushed later on.
+pub async fn flush<'c, E>(executor: E, limit: usize) -> Result<u64>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query(
+        "
+        WITH flushed AS (
+            DELETE FROM change_log_pending
+            WHERE (transaction_id, entity, entity_id, origin) IN (
+                SELECT transaction_id, entity, entity_id, origin
+                FROM change_log_pending
+                ORDER BY transaction_id
+                LIMIT $1
+            )
+            RETURNING *
+        )
+        INSERT INTO change_log(entity, entity_id, origin, operation, changed_at)
+        SELECT entity, entity_id, origin, operation, changed_at
+        FROM flushed
+        ORDER BY transaction_id, changed_at, entity, entity_id, origin;
+        ",
+    )
+    .bind(limit as i64)
+    .execute(executor)
+    .await
+    .map(|result| result.rows_affected())
+    .map_err(convert_error)
+}
+
+/// Deletes changes before the given time, which are followed by a newer change of
+/// the same entity, and deletions, which are not. Moves the horizon of the change
+/// log past the compacted deletions.
+pub async fn compact<'c, E>(executor: E, before: DateTime<Local>) -> Result<u64>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_scalar(
+        "
+        WITH superseded AS (
+            DELETE FROM change_log c
+            WHERE
+                c.changed_at < $1
+                AND EXISTS (
+                    SELECT 1
+                    FROM change_log n
+                    WHERE
+                        n.entity = c.entity
+                        AND n.entity_id = c.entity_id
+                        AND n.origin = c.origin
+                        AND n.id > c.id
+                )
+            RETURNING c.id
+        ),
+        deletions AS (
+            DELETE FROM change_log c
+            WHERE
+                c.changed_at < $1
+                AND c.operation = 'delete'
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM change_log n
+                    WHERE
+                        n.entity = c.entity
+                        AND n.entity_id = c.entity_id
+                        AND n.origin = c.origin
+                        AND n.id > c.id
+                )
+            RETURNING c.id
+        ),
+        horizon AS (
+            INSERT INTO change_log_horizon(compacted_deletions_through)
+            SELECT MAX(id) FROM deletions HAVING COUNT(*) > 0
+            ON CONFLICT (id) DO UPDATE
+            SET
+                compacted_deletions_through = GREATEST(
+                    change_log_horizon.compacted_deletions_through,
+                    EXCLUDED.compacted_deletions_through
+                )
+        )
+        SELECT
+            (SELECT COUNT(*) FROM superseded) + (SELECT COUNT(*) FROM deletions);
+        ",
+    )
+    .bind(before)
+    .fetch_one(executor)
+    .await
+    .map(|count: i64| count as u64)
+    .map_err(convert_error)
+}
+
+/// Position of the latest compacted deletion, if any.
+pub async fn get_horizon<'c, E>(executor: E) -> Result<Option<ChangePosition>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_scalar(
+        "
+        SELECT
+            compacted_deletions_through
+        FROM
+            change_log_horizon;
+        ",
+    )
+    .fetch_optional(executor)
+    .await
+    .map(|sequence| sequence.map(|sequence| ChangePosition { sequence }))
+    .map_err(convert_error)
+}
@@ -1,11 +1,14 @@
+use chrono::{DateTime, Duration, Local};
 use model::WithId;
-use public_transport::collector::{Collector, CollectorInstance};
+use public_transport::collector::{
+    Collector, CollectorHealth, CollectorInstance, CollectorLease,
+};
 use public_transport::database::Result;
 use sqlx::types::Json;
 use sqlx::{Executor, Postgres};
 use utility::{id::Id, let_also::LetAlso};
 
-use crate::data_model::collector::CollectorRow;
+use crate::data_model::collector::{CollectorHealthRow, CollectorRow};
 
 use super::convert_error;
 
@@ -20,11 +23,13 @@ where
     sqlx::query_as(
         "
         SELECT
-            id, origin, kind, is_active, state
+            id, origin, kind, is_active, state, next_run_at
         FROM
             collectors
         WHERE
-            kind = $1;
+            kind = $1
+        ORDER BY
+            id;
         ",
     )
     .bind(C::unique_id())
@@ -39,6 +44,7 @@ where
                 origin: Id::new(row.origin),
                 is_active: row.is_active,
                 state: row.state.0,
+                next_run_at: row.next_run_at,
             },
         )
     })
@@ -57,7 +63,7 @@ where
     sqlx::query_as(
         "
         SELECT
-            id, origin, kind, is_active, state
+            id, origin, kind, is_active, state, next_run_at
         FROM
             collectors
         WHERE
@@ -73,6 +79,7 @@ where
         origin: Id::new(row.origin),
         is_active: row.is_active,
         state: row.state.0,
+        next_run_at: row.next_run_at,
     })
 }
 
@@ -104,3 +111,246 @@ where
     .map_err(convert_error)
     .map(|row: CollectorRow<C>| row.state.0)
 }
+
+/// Tries to lease the given collector instance for a run. Fails, if it is leased
+/// by someone else, asked to sleep until later, or was run less than `spacing`
+/// ago. Returns the start of the run, which identifies the lease, or why it
+/// failed.
+pub async fn try_lease<'c, E, C>(
+    executor: E,
+    id: &Id<CollectorInstance<C>>,
+    duration: Duration,
+    spacing: Duration,
+) -> Result<CollectorLease>
+where
+    E: Executor<'c, Database = Postgres>,
+    C: Collector + 'static,
+{
+    // the subquery sees the lease as it was before the update, as all parts of the
+    // statement share one snapshot.
+    let (started_at, is_held): (Option<DateTime<Local>>, bool) = sqlx::query_as(
+        "
+        WITH leased AS (
+            UPDATE
+                collectors
+            SET
+                leased_until = NOW() + make_interval(secs => $1),
+                last_run_at = NOW()
+            WHERE
+                id = $3 AND kind = $4
+                AND (leased_until IS NULL OR leased_until < NOW())
+                AND (next_run_at IS NULL OR next_run_at <= NOW())
+                AND (
+                    last_run_at IS NULL
+                    OR last_run_at <= NOW() - make_interval(secs => $2)
+                )
+            RETURNING
+                last_run_at
+        )
+        SELECT
+            (SELECT last_run_at FROM leased),
+            EXISTS (
+                SELECT 1 FROM collectors
+                WHERE id = $3 AND kind = $4 AND leased_until >= NOW()
+            );
+        ",
+    )
+    .bind(duration.num_milliseconds() as f64 / 1000.0)
+    .bind(spacing.num_milliseconds() as f64 / 1000.0)
+    .bind(id.raw())
+    .bind(C::unique_id())
+    .fetch_one(executor)
+    .await
+    .map_err(convert_error)?;
+    Ok(match started_at {
+        Some(started_at) => CollectorLease::Acquired(started_at),
+        None if is_held => CollectorLease::Held,
+        None => CollectorLease::NotDue,
+    })
+}
+
+/// Extends the lease of the run started at `started_at`. Returns `false`, if the
+/// lease expired and was taken by someone else.
+pub async fn renew_lease<'c, E, C>(
+    executor: E,
+    id: &Id<CollectorInstance<C>>,
+    started_at: DateTime<Local>,
+    duration: Duration,
+) -> Result<bool>
+where
+    E: Executor<'c, Database = Postgres>,
+    C: Collector + 'static,
+{
+    sqlx::query(
+        "
+        UPDATE
+            collectors
+        SET
+            leased_until = NOW() + make_interval(secs => $1)
+        WHERE
+            id = $2 AND kind = $3 AND last_run_at = $4;
+        ",
+    )
+    .bind(duration.num_milliseconds() as f64 / 1000.0)
+    .bind(id.raw())
+    .bind(C::unique_id())
+    .bind(started_at)
+    .execute(executor)
+    .await
+    .map(|result| result.rows_affected() > 0)
+    .map_err(convert_error)
+}
+
+/// Releases the lease of the run started at `started_at`, if it is still held.
+pub async fn release_lease<'c, E, C>(
+    executor: E,
+    id: &Id<CollectorInstance<C>>,
+    started_at: DateTime<Local>,
+) -> Result<()>
+where
+    E: Executor<'c, Database = Postgres>,
+    C: Collector + 'static,
+{
+    sqlx::query(
+        "
+        UPDATE
+            collectors
+        SET
+            leased_until = NULL
+        WHERE
+            id = $1 AND kind = $2 AND last_run_at = $3;
+        ",
+    )
+    .bind(id.raw())
+    .bind(C::unique_id())
+    .bind(started_at)
+    .execute(executor)
+    .await
+    .map(|_| ())
+    .map_err(convert_error)
+}
+
+pub async fn set_health<'c, E, C>(
+    executor: E,
+    id: &Id<CollectorInstance<C>>,
+    error: Option<String>,
+) -> Result<()>
+where
+    E: Executor<'c, Database = Postgres>,
+    C: Collector + 'static,
+{
+    sqlx::query(
+        "
+        UPDATE
+            collectors
+        SET
+            healthcheck_at = NOW(),
+            healthcheck_error = $1
+        WHERE
+            id = $2 AND kind = $3;
+        ",
+    )
+    .bind(error)
+    .bind(id.raw())
+    .bind(C::unique_id())
+    .execute(executor)
+    .await
+    .map(|_| ())
+    .map_err(convert_error)
+}
+
+pub async fn set_next_run<'c, E, C>(
+    executor: E,
+    id: &Id<CollectorInstance<C>>,
+    next_run_at: Option<DateTime<Local>>,
+) -> Result<()>
+where
+    E: Executor<'c, Database = Postgres>,
+    C: Collector + 'static,
+{
+    sqlx::query(
+        "
+        UPDATE
+            collectors
+        SET
+            next_run_at = $1
+        WHERE
+            id = $2 AND kind = $3;
+        ",
+    )
+    .bind(next_run_at)
+    .bind(id.raw())
+    .bind(C::unique_id())
+    .execute(executor)
+    .await
+    .map(|_| ())
+    .map_err(convert_error)
+}
+
+/// Counts consecutive failed runs, which are reset by a successful one. The
+/// interval until the next run is kept of successful runs only.
+pub async fn record_run<'c, E, C>(
+    executor: E,
+    id: &Id<CollectorInstance<C>>,
+    succeeded: bool,
+    run_interval: Option<Duration>,
+) -> Result<()>
+where
+    E: Executor<'c, Database = Postgres>,
+    C: Collector + 'static,
+{
+    sqlx::query(
+        "
+        UPDATE
+            collectors
+        SET
+            consecutive_failures = CASE
+                WHEN $1 THEN 0
+                ELSE consecutive_failures + 1
+            END,
+            last_success_at = CASE
+                WHEN $1 THEN NOW()
+                ELSE last_success_at
+            END,
+            run_interval = CASE
+                WHEN $1 THEN make_interval(secs => $4)
+                ELSE run_interval
+            END
+        WHERE
+            id = $2 AND kind = $3;
+        ",
+    )
+    .bind(succeeded)
+    .bind(id.raw())
+    .bind(C::unique_id())
+    .bind(run_interval.map(|interval| interval.num_milliseconds() as f64 / 1000.0))
+    .execute(executor)
+    .await
+    .map(|_| ())
+    .map_err(convert_error)
+}
+
+pub async fn get_health<'c, E>(executor: E) -> Result<Vec<CollectorHealth>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        SELECT
+            id, origin, kind, is_active, healthcheck_at, healthcheck_error,
+            consecutive_failures, last_success_at, next_run_at,
+            last_success_at + COALESCE(run_interval, INTERVAL '0') AS due_at
+        FROM
+            collectors
+        ORDER BY
+            id;
+        ",
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .into_iter()
+    .map(CollectorHealthRow::to_model)
+    .collect::<Vec<_>>()
+    .let_owned(Ok)
+}
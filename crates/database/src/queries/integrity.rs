@@ -0,0 +1,137 @@
+use model::origin::Origin;
+use public_transport::database::{InvalidCoordinates, Orphans, Result};
+use sqlx::{Executor, Postgres};
+use utility::{
+    id::{Id, IdWrapper as _},
+    let_also::LetAlso,
+};
+
+use super::convert_error;
+
+pub async fn orphans<'c, E>(executor: E) -> Result<Vec<Orphans>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    // services, realtime updates and the trips they belong to are not connected by
+    // foreign keys. Everything else is enforced by the schema.
+    sqlx::query_as(
+        "
+        WITH services AS (
+            SELECT service_id FROM calendar_windows
+            UNION
+            SELECT service_id FROM calendar_dates
+        ), orphans(table_name, missing, count) AS (
+            SELECT
+                'services_original_ids', 'service', COUNT(*)
+            FROM
+                services_original_ids o
+            WHERE
+                NOT EXISTS (SELECT 1 FROM services s WHERE s.service_id = o.id)
+            UNION ALL
+            SELECT
+                'trips', 'service', COUNT(*)
+            FROM
+                trips t
+            WHERE
+                t.service_id IS NOT NULL
+                AND NOT EXISTS (SELECT 1 FROM services s WHERE s.service_id = t.service_id)
+            UNION ALL
+            SELECT
+                'trip_updates', 'trip', COUNT(*)
+            FROM
+                trip_updates u
+            WHERE
+                NOT EXISTS (SELECT 1 FROM trips t WHERE t.id = u.trip_id)
+        )
+        SELECT table_name, missing, count FROM orphans WHERE count > 0;
+        ",
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .into_iter()
+    .map(|(table, missing, count): (String, String, i64)| Orphans {
+        table,
+        missing,
+        count,
+    })
+    .collect::<Vec<_>>()
+    .let_owned(Ok)
+}
+
+/// Condition matching rows with invalid coordinates. `$1` are the origins, whose
+/// rows may be located at exactly (0, 0).
+const INVALID_COORDINATES: &str = "
+    (
+        latitude NOT BETWEEN -90 AND 90
+        OR longitude NOT BETWEEN -180 AND 180
+        OR (latitude = 0 AND longitude = 0 AND origin <> ALL($1::text[]))
+    )
+";
+
+pub async fn invalid_coordinates<'c, E>(
+    executor: E,
+    null_island_origins: &[Id<Origin>],
+) -> Result<Vec<InvalidCoordinates>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(&format!(
+        "
+        WITH invalid(table_name, count) AS (
+            SELECT 'stops', COUNT(*) FROM stops WHERE {INVALID_COORDINATES}
+            UNION ALL
+            SELECT
+                'shared_mobility_stations', COUNT(*)
+            FROM
+                shared_mobility_stations
+            WHERE
+                {INVALID_COORDINATES}
+        )
+        SELECT table_name, count FROM invalid WHERE count > 0;
+        "
+    ))
+    .bind(null_island_origins.raw_ref::<str>())
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .into_iter()
+    .map(|(table, count): (String, i64)| InvalidCoordinates { table, count })
+    .collect::<Vec<_>>()
+    .let_owned(Ok)
+}
+
+pub async fn clear_invalid_coordinates<'c, E>(
+    executor: E,
+    null_island_origins: &[Id<Origin>],
+) -> Result<u64>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_scalar(&format!(
+        "
+        WITH stops AS (
+            UPDATE
+                stops
+            SET
+                latitude = NULL,
+                longitude = NULL
+            WHERE
+                {INVALID_COORDINATES}
+            RETURNING 1
+        ), stations AS (
+            DELETE FROM
+                shared_mobility_stations
+            WHERE
+                {INVALID_COORDINATES}
+            RETURNING 1
+        )
+        SELECT (SELECT COUNT(*) FROM stops) + (SELECT COUNT(*) FROM stations);
+        "
+    ))
+    .bind(null_island_origins.raw_ref::<str>())
+    .fetch_one(executor)
+    .await
+    .map(|count: i64| count as u64)
+    .map_err(convert_error)
+}
@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate};
 use model::{
     agency::Agency,
     line::Line,
@@ -9,15 +12,16 @@ use public_transport::database::Result;
 use utility::{
     id::{Id, IdWrapper},
     let_also::LetAlso,
+    normalize::non_blank,
 };
 
 use crate::data_model::{
-    line::{LineRow, RowLineType},
+    line::{LineRow, RowLineType, StopLineRow},
     with_origin_and_id, with_origins, with_origins_and_ids,
 };
 use sqlx::{Executor, Postgres};
 
-use super::convert_error;
+use super::{convert_error, escape_like};
 
 // Repo
 
@@ -27,7 +31,9 @@ where
 {
     sqlx::query_as(
         "
-        SELECT id, origin, name, kind, agency_id
+        SELECT
+            id, origin, name, kind, agency_id, updated_at,
+            line_secondary_agency_ids(id, origin) AS secondary_agency_ids
         FROM lines
         WHERE id = $1;
         ",
@@ -47,8 +53,11 @@ where
 {
     sqlx::query_as(
         "
-        SELECT id, origin, name, kind, agency_id
-        FROM lines;
+        SELECT
+            id, origin, name, kind, agency_id, updated_at,
+            line_secondary_agency_ids(id, origin) AS secondary_agency_ids
+        FROM lines
+        ORDER BY id, origin;
         ",
     )
     .fetch_all(executor)
@@ -59,6 +68,31 @@ where
     })
 }
 
+pub async fn get_many<'c, E>(
+    executor: E,
+    ids: &[Id<Line>],
+) -> Result<Vec<DatabaseEntry<Line>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        SELECT
+            id, origin, name, kind, agency_id, updated_at,
+            line_secondary_agency_ids(id, origin) AS secondary_agency_ids
+        FROM lines
+        WHERE id = ANY($1);
+        ",
+    )
+    .bind(ids.iter().map(|id| id.raw()).collect::<Vec<_>>())
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .let_owned(|lines: Vec<LineRow>| {
+        Ok(DatabaseEntry::gather_many(with_origins_and_ids(lines)))
+    })
+}
+
 pub async fn insert<'c, E>(
     executor: E,
     line: WithOrigin<Line>,
@@ -68,20 +102,29 @@ where
 {
     sqlx::query_as(
         "
-        INSERT INTO lines(
-            origin,
-            name,
-            kind,
-            agency_id
+        WITH line AS (
+            INSERT INTO lines(
+                origin,
+                name,
+                kind,
+                agency_id
+            )
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+        ), secondary AS (
+            INSERT INTO line_secondary_agencies(origin, line_id, agency_id, position)
+            SELECT line.origin, line.id, s.agency_id, s.position
+            FROM line, UNNEST($5::TEXT[]) WITH ORDINALITY AS s(agency_id, position)
         )
-        VALUES ($1, $2, $3, $4)
-        RETURNING *;
+        SELECT line.*, $5::TEXT[] AS secondary_agency_ids
+        FROM line;
         ",
     )
     .bind(line.origin.raw())
-    .bind(line.content.name)
+    .bind(non_blank(line.content.name))
     .bind(RowLineType::from_line_type(line.content.kind))
     .bind(line.content.agency_id.raw())
+    .bind(line.content.secondary_agency_ids.raw())
     .fetch_one(executor)
     .await
     .map(|row: LineRow| with_origin_and_id(row))
@@ -97,27 +140,45 @@ where
 {
     sqlx::query_as(
         "
-        INSERT INTO lines(
-            id,
-            origin,
-            name,
-            kind,
-            agency_id
+        WITH line AS (
+            INSERT INTO lines(
+                id,
+                origin,
+                name,
+                kind,
+                agency_id
+            )
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (id, origin)
+            DO UPDATE SET
+                name = EXCLUDED.name,
+                kind = EXCLUDED.kind,
+                agency_id = EXCLUDED.agency_id
+            RETURNING *
+        ), deleted AS (
+            DELETE FROM line_secondary_agencies s
+            USING line
+            WHERE s.line_id = line.id
+                AND s.origin = line.origin
+                AND s.agency_id <> ALL($6::TEXT[])
+        ), secondary AS (
+            INSERT INTO line_secondary_agencies(origin, line_id, agency_id, position)
+            SELECT line.origin, line.id, s.agency_id, s.position
+            FROM line, UNNEST($6::TEXT[]) WITH ORDINALITY AS s(agency_id, position)
+            ON CONFLICT (line_id, origin, agency_id)
+            DO UPDATE SET position = EXCLUDED.position
+            WHERE line_secondary_agencies.position <> EXCLUDED.position
         )
-        VALUES ($1, $2, $3, $4, $5)
-        ON CONFLICT (id, origin)
-        DO UPDATE SET
-            name = EXCLUDED.name,
-            kind = EXCLUDED.kind,
-            agency_id = EXCLUDED.agency_id
-        RETURNING *;
+        SELECT line.*, $6::TEXT[] AS secondary_agency_ids
+        FROM line;
         ",
     )
     .bind(line.content.id.raw())
     .bind(line.origin.raw())
-    .bind(line.content.content.name)
+    .bind(non_blank(line.content.content.name))
     .bind(RowLineType::from_line_type(line.content.content.kind))
     .bind(line.content.content.agency_id.raw())
+    .bind(line.content.content.secondary_agency_ids.raw())
     .fetch_one(executor)
     .await
     .map_err(|why| convert_error(why))
@@ -133,19 +194,37 @@ where
 {
     sqlx::query_as(
         "
-        UPDATE lines
-        SET name = $1,
-            kind = $2,
-            agency_id = $3
-        WHERE origin = $4 AND id = $5
-        RETURNING *;
+        WITH line AS (
+            UPDATE lines
+            SET name = $1,
+                kind = $2,
+                agency_id = $3
+            WHERE origin = $4 AND id = $5
+            RETURNING *
+        ), deleted AS (
+            DELETE FROM line_secondary_agencies s
+            USING line
+            WHERE s.line_id = line.id
+                AND s.origin = line.origin
+                AND s.agency_id <> ALL($6::TEXT[])
+        ), secondary AS (
+            INSERT INTO line_secondary_agencies(origin, line_id, agency_id, position)
+            SELECT line.origin, line.id, s.agency_id, s.position
+            FROM line, UNNEST($6::TEXT[]) WITH ORDINALITY AS s(agency_id, position)
+            ON CONFLICT (line_id, origin, agency_id)
+            DO UPDATE SET position = EXCLUDED.position
+            WHERE line_secondary_agencies.position <> EXCLUDED.position
+        )
+        SELECT line.*, $6::TEXT[] AS secondary_agency_ids
+        FROM line;
         ",
     )
-    .bind(line.content.content.name)
+    .bind(non_blank(line.content.content.name))
     .bind(RowLineType::from_line_type(line.content.content.kind))
     .bind(line.content.content.agency_id.raw())
     .bind(line.origin.raw())
     .bind(line.content.id.raw())
+    .bind(line.content.content.secondary_agency_ids.raw())
     .fetch_one(executor)
     .await
     .map_err(convert_error)
@@ -228,7 +307,9 @@ where
 {
     sqlx::query_as(
         "
-        SELECT id, origin, name, kind, agency_id
+        SELECT
+            id, origin, name, kind, agency_id, updated_at,
+            line_secondary_agency_ids(id, origin) AS secondary_agency_ids
         FROM lines
         WHERE name = $1 AND agency_id = $2;
         ",
@@ -254,7 +335,8 @@ where
     sqlx::query_as(
         "
         SELECT DISTINCT
-            l.id, l.origin, l.name, l.kind, l.agency_id
+            l.id, l.origin, l.name, l.kind, l.agency_id, l.updated_at,
+            line_secondary_agency_ids(l.id, l.origin) AS secondary_agency_ids
         FROM
             lines l
             JOIN trips t ON l.id = t.line_id
@@ -272,6 +354,109 @@ where
     })
 }
 
+/// Lines at each of the given stops. Stops without lines are omitted.
+pub async fn get_by_stop_ids<'c, E>(
+    executor: E,
+    stop_ids: &[&Id<Stop>],
+) -> Result<HashMap<Id<Stop>, Vec<DatabaseEntry<Line>>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        SELECT DISTINCT
+            st.stop_id, l.id, l.origin, l.name, l.kind, l.agency_id, l.updated_at,
+            line_secondary_agency_ids(l.id, l.origin) AS secondary_agency_ids
+        FROM
+            lines l
+            JOIN trips t ON l.id = t.line_id
+            JOIN stop_times st ON t.id = st.trip_id
+        WHERE
+            st.stop_id = ANY($1);
+        ",
+    )
+    .bind(stop_ids.raw_ref::<str>())
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .into_iter()
+    .fold(
+        HashMap::new(),
+        |mut lines: HashMap<String, Vec<LineRow>>, row: StopLineRow| {
+            lines.entry(row.stop_id).or_default().push(row.line);
+            lines
+        },
+    )
+    .into_iter()
+    .map(|(stop_id, lines)| {
+        (
+            Id::new(stop_id),
+            DatabaseEntry::gather_many(with_origins_and_ids(lines)),
+        )
+    })
+    .collect::<HashMap<_, _>>()
+    .let_owned(Ok)
+}
+
+/// At most `limit` lines, whose names contain the pattern. Names equal to the
+/// pattern, regardless of spaces and case, come first, then prefix matches.
+pub async fn search<'c, E, S>(
+    executor: E,
+    pattern: S,
+    limit: usize,
+) -> Result<Vec<DatabaseEntry<Line>>>
+where
+    E: Executor<'c, Database = Postgres>,
+    S: Into<String> + Send,
+{
+    let pattern: String = pattern.into();
+    let escaped = escape_like(&pattern);
+    let prefix_pattern = format!("{}%", escaped);
+    let prefix_postfix_pattern = format!("%{}%", escaped);
+    sqlx::query_as(
+        "
+        WITH matches AS (
+            SELECT
+                id,
+                MIN(CASE
+                    WHEN lower(replace(name, ' ', ''))
+                        = lower(replace($1, ' ', '')) THEN 1
+                    WHEN name ILIKE $2 THEN 2
+                    ELSE 3
+                END) AS rank
+            FROM
+                lines
+            WHERE
+                name ILIKE $3
+                OR lower(replace(name, ' ', '')) = lower(replace($1, ' ', ''))
+            GROUP BY
+                id
+            ORDER BY
+                rank, id
+            LIMIT $4
+        )
+        SELECT
+            l.id, l.origin, l.name, l.kind, l.agency_id, l.updated_at,
+            line_secondary_agency_ids(l.id, l.origin) AS secondary_agency_ids
+        FROM
+            matches m
+            JOIN lines l ON l.id = m.id
+        ORDER BY
+            m.rank, m.id;
+        ",
+    )
+    .bind(pattern)
+    .bind(prefix_pattern)
+    .bind(prefix_postfix_pattern)
+    .bind(limit as i64)
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .let_owned(|lines: Vec<LineRow>| {
+        Ok(DatabaseEntry::gather_many(with_origins_and_ids(lines)))
+    })
+}
+
 pub async fn merge_candidates<'c, E>(
     executor: E,
     line: &Line,
@@ -286,7 +471,8 @@ where
     sqlx::query_as(
         "
         SELECT
-            id, origin, name, kind, agency_id
+            id, origin, name, kind, agency_id, updated_at,
+            line_secondary_agency_ids(id, origin) AS secondary_agency_ids
         FROM
             lines
         WHERE
@@ -305,3 +491,73 @@ where
     .map_err(convert_error)?
     .let_owned(|agencies: Vec<LineRow>| Ok(with_origins_and_ids(agencies)))
 }
+
+pub async fn get_departure_span<'c, E>(
+    executor: E,
+    id: &Id<Line>,
+    service_day: NaiveDate,
+) -> Result<Option<(Duration, Duration)>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        SELECT
+            MIN(st.departure_time), MAX(st.departure_time)
+        FROM
+            trips t
+            JOIN stop_times st ON st.trip_id = t.id AND st.origin = t.origin
+        WHERE
+            t.line_id = $1
+            AND st.departure_time IS NOT NULL
+            -- passengers can not depart from the last stop of a trip
+            AND EXISTS (
+                SELECT 1 FROM stop_times later
+                WHERE later.origin = st.origin
+                  AND later.trip_id = st.trip_id
+                  AND later.stop_sequence > st.stop_sequence
+            )
+            AND (
+                EXISTS (
+                    SELECT 1 FROM calendar_dates cd
+                    WHERE cd.service_id = t.service_id
+                      AND cd.date = $2
+                      AND cd.exception_type = 'added'
+                )
+                OR (
+                    EXISTS (
+                        SELECT 1 FROM calendar_windows c
+                        WHERE c.service_id = t.service_id
+                          AND $2 BETWEEN c.start_date AND c.end_date
+                          AND (CASE EXTRACT(ISODOW FROM $2::date)
+                                   WHEN 1 THEN c.monday
+                                   WHEN 2 THEN c.tuesday
+                                   WHEN 3 THEN c.wednesday
+                                   WHEN 4 THEN c.thursday
+                                   WHEN 5 THEN c.friday
+                                   WHEN 6 THEN c.saturday
+                                   ELSE c.sunday
+                               END) = 'available'
+                    )
+                    AND NOT EXISTS (
+                        SELECT 1 FROM calendar_dates cd
+                        WHERE cd.service_id = t.service_id
+                          AND cd.date = $2
+                          AND cd.exception_type = 'removed'
+                    )
+                )
+            );
+        ",
+    )
+    .bind(id.raw())
+    .bind(service_day)
+    .fetch_one(executor)
+    .await
+    .map_err(convert_error)
+    .map(|span: (Option<i64>, Option<i64>)| match span {
+        (Some(first), Some(last)) => {
+            Some((Duration::seconds(first), Duration::seconds(last)))
+        }
+        _ => None,
+    })
+}
@@ -0,0 +1,168 @@
+use model::{
+    merge::{MergeLogEntry, MergeStatus, MergeSubject},
+    origin::Origin,
+    WithId,
+};
+use public_transport::database::Result;
+use sqlx::{Executor, Postgres};
+use utility::id::Id;
+
+use crate::data_model::merge_log::{MergeLogRow, RowMergeStatus, RowMergeSubject};
+
+use super::convert_error;
+
+pub async fn insert<'c, E>(
+    executor: E,
+    entry: MergeLogEntry,
+) -> Result<WithId<MergeLogEntry>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        INSERT INTO merge_log(
+            subject,
+            origin,
+            subject_id,
+            subject_original_id,
+            candidate_id,
+            similarity,
+            status,
+            created_at,
+            decided_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING
+            id, subject, origin, subject_id, subject_original_id, candidate_id,
+            similarity, status, created_at, decided_at;
+        ",
+    )
+    .bind(RowMergeSubject::from_model(entry.subject))
+    .bind(entry.origin.raw())
+    .bind(entry.subject_id)
+    .bind(entry.subject_original_id)
+    .bind(entry.candidate_id)
+    .bind(entry.similarity)
+    .bind(RowMergeStatus::from_model(entry.status))
+    .bind(entry.created_at)
+    .bind(entry.decided_at)
+    .fetch_one(executor)
+    .await
+    .map_err(convert_error)
+    .map(|row: MergeLogRow| row.to_model())
+}
+
+pub async fn get<'c, E>(executor: E, id: &Id<MergeLogEntry>) -> Result<MergeLogEntry>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        SELECT
+            id, subject, origin, subject_id, subject_original_id, candidate_id,
+            similarity, status, created_at, decided_at
+        FROM
+            merge_log
+        WHERE
+            id = $1;
+        ",
+    )
+    .bind(id.raw())
+    .fetch_one(executor)
+    .await
+    .map_err(convert_error)
+    .map(|row: MergeLogRow| row.to_model().content)
+}
+
+pub async fn get_all<'c, E>(
+    executor: E,
+    status: Option<MergeStatus>,
+) -> Result<Vec<WithId<MergeLogEntry>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        SELECT
+            id, subject, origin, subject_id, subject_original_id, candidate_id,
+            similarity, status, created_at, decided_at
+        FROM
+            merge_log
+        WHERE
+            $1::merge_status IS NULL OR status = $1
+        ORDER BY
+            created_at, id;
+        ",
+    )
+    .bind(status.map(RowMergeStatus::from_model))
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)
+    .map(|rows: Vec<MergeLogRow>| {
+        rows.into_iter().map(MergeLogRow::to_model).collect()
+    })
+}
+
+/// Decides the entry, if it is still pending review. `None` otherwise, so that
+/// concurrent decisions of the same entry do not both succeed.
+pub async fn set_status<'c, E>(
+    executor: E,
+    id: &Id<MergeLogEntry>,
+    status: MergeStatus,
+) -> Result<Option<MergeLogEntry>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        UPDATE merge_log
+        SET
+            status = $2,
+            decided_at = NOW()
+        WHERE
+            id = $1
+            AND status = 'pending_review'
+        RETURNING
+            id, subject, origin, subject_id, subject_original_id, candidate_id,
+            similarity, status, created_at, decided_at;
+        ",
+    )
+    .bind(id.raw())
+    .bind(RowMergeStatus::from_model(status))
+    .fetch_optional(executor)
+    .await
+    .map_err(convert_error)
+    .map(|row: Option<MergeLogRow>| row.map(|row| row.to_model().content))
+}
+
+pub async fn is_rejected<'c, E>(
+    executor: E,
+    subject: MergeSubject,
+    origin: &Id<Origin>,
+    original_id: Option<&str>,
+    candidate_id: &str,
+) -> Result<bool>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_scalar(
+        "
+        SELECT EXISTS(
+            SELECT 1 FROM merge_log
+            WHERE
+                subject = $1
+                AND origin = $2
+                AND subject_original_id IS NOT DISTINCT FROM $3
+                AND candidate_id = $4
+                AND status = 'rejected'
+        );
+        ",
+    )
+    .bind(RowMergeSubject::from_model(subject))
+    .bind(origin.raw())
+    .bind(original_id)
+    .bind(candidate_id)
+    .fetch_one(executor)
+    .await
+    .map_err(convert_error)
+}
@@ -1,16 +1,21 @@
-use std::{fmt::Write as _, future::Future};
+use std::{fmt::Write as _, time::Duration};
 
 use public_transport::database::DatabaseError;
 use sqlx::{
     postgres::{PgArguments, PgQueryResult, PgRow},
     query::{Query, QueryAs},
-    Error, Executor, FromRow, Postgres,
+    Acquire, Error, Executor, FromRow, Postgres,
 };
 
 pub mod agency;
+pub mod alert;
+pub mod change_log;
 pub mod collector;
+pub mod integrity;
 pub mod line;
+pub mod merge_log;
 pub mod origin;
+pub mod pathway;
 pub mod service;
 pub mod shape;
 pub mod shared_mobility;
@@ -20,13 +25,61 @@ pub mod trip_update;
 
 // TODO: replace `RETURNING *` to explicitly specify column names in all queries.
 
+/// SQLSTATE of statements, which were cancelled, e.g. by `statement_timeout`.
+const QUERY_CANCELED: &str = "57014";
+
 pub(crate) fn convert_error(why: sqlx::Error) -> DatabaseError {
     match why {
         sqlx::Error::RowNotFound => DatabaseError::NotFound,
+        sqlx::Error::Database(ref error)
+            if error.code().as_deref() == Some(QUERY_CANCELED) =>
+        {
+            DatabaseError::Timeout
+        }
         _ => DatabaseError::Other(Box::new(why)),
     }
 }
 
+/// Escapes the wildcards of `LIKE` patterns, so that the text only matches
+/// itself. Uses the default escape character, the backslash.
+pub(crate) fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        if matches!(character, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(character);
+    }
+    escaped
+}
+
+/// Limits the statements of the transaction to the time left until the deadline
+/// of the current request, so that the database cancels them, instead of
+/// finishing work nobody waits for. Fails right away, if the deadline has passed.
+/// Never extends the `statement_timeout` of the connection.
+pub(crate) async fn apply_deadline(
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    statement_timeout: Option<Duration>,
+) -> Result<(), DatabaseError> {
+    let Some(remaining) = public_transport::deadline::remaining() else {
+        return Ok(());
+    };
+    if statement_timeout.is_some_and(|timeout| timeout <= remaining) {
+        return Ok(());
+    }
+    // a timeout of zero would disable it.
+    let millis = remaining.as_millis();
+    if millis == 0 {
+        return Err(DatabaseError::Timeout);
+    }
+    // `SET` does not support bind parameters.
+    sqlx::query(&format!("SET LOCAL statement_timeout = {}", millis))
+        .execute(&mut **tx)
+        .await
+        .map_err(convert_error)?;
+    Ok(())
+}
+
 // bulk insert
 
 pub async fn insert_all_returning<'c, E, T, B, O>(
@@ -144,20 +197,61 @@ where
 
 // sql framework
 
-const MAX_CHUNK_SIZE: usize = 100;
+/// Rows inserted by a single statement of [`BoundMultiRowInsert::execute`].
+pub const MAX_CHUNK_SIZE: usize = 100;
 
 pub struct InsertInto<'a> {
     table: &'a str,
     columns: &'a [&'a str],
+    conflict_set: &'a [&'a str],
 }
 
 impl<'a> InsertInto<'a> {
+    pub fn new(table: &'a str, columns: &'a [&'a str]) -> Self {
+        Self {
+            table,
+            columns,
+            conflict_set: &[],
+        }
+    }
+
+    /// Updates the other columns of rows conflicting in the given columns, like
+    /// [`insert_all_returning`]. Conflicting rows are skipped, if there are no
+    /// other columns.
+    pub fn on_conflict(self, conflict_set: &'a [&'a str]) -> Self {
+        Self {
+            conflict_set,
+            ..self
+        }
+    }
+
     pub fn values<V>(self, values: &'a [&'a V]) -> MultiRowInsert<'a, V> {
         MultiRowInsert {
             insert: self,
             values,
         }
     }
+
+    fn conflict_clause(&self) -> String {
+        if self.conflict_set.is_empty() {
+            return String::new();
+        }
+        let updates = self
+            .columns
+            .iter()
+            .filter(|column| !self.conflict_set.contains(column))
+            .map(|column| format!("{} = EXCLUDED.{}", column, column))
+            .collect::<Vec<_>>();
+        if updates.is_empty() {
+            format!(" ON CONFLICT ({}) DO NOTHING", self.conflict_set.join(", "))
+        } else {
+            format!(
+                " ON CONFLICT ({}) DO UPDATE SET {}",
+                self.conflict_set.join(", "),
+                updates.join(", ")
+            )
+        }
+    }
 }
 
 pub struct MultiRowInsert<'a, V> {
@@ -189,14 +283,19 @@ impl<'a, V, F> BoundMultiRowInsert<'a, V, F>
 where
     F: FnMut(PgArguments, &V) -> PgArguments,
 {
-    pub async fn execute<'c, X, R>(
-        mut self,
-        mut execute_callback: X,
-    ) -> Result<u64, Error>
+    /// Inserts the values in chunks of at most `MAX_CHUNK_SIZE` rows, each executed
+    /// on the same connection acquired from the executor. Gives the number of
+    /// affected rows of all chunks. No statement is executed without values.
+    pub async fn execute<'c, A>(mut self, executor: A) -> Result<u64, Error>
     where
-        X: FnMut(Query<Postgres, PgArguments>) -> R,
-        R: Future<Output = Result<u64, Error>> + Send,
+        A: Acquire<'c, Database = Postgres>,
     {
+        if self.insert.values.is_empty() {
+            return Ok(0);
+        }
+        let mut connection = executor.acquire().await?;
+        let conflict_clause = self.insert.insert.conflict_clause();
+        let mut rows_affected = 0;
         for chunk in self.insert.values.chunks(MAX_CHUNK_SIZE) {
             let mut query = format!(
                 "INSERT INTO {} ({}) VALUES ",
@@ -222,10 +321,14 @@ where
                 }
                 query.push(')');
             }
+            query.push_str(&conflict_clause);
+            query.push(';');
 
-            let stmt = sqlx::query_with(&query, args);
-            execute_callback(stmt).await?;
+            rows_affected += sqlx::query_with(&query, args)
+                .execute(&mut *connection)
+                .await?
+                .rows_affected();
         }
-        todo!()
+        Ok(rows_affected)
     }
 }
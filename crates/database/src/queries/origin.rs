@@ -26,7 +26,7 @@ where
         sqlx::query_as("SELECT * FROM origins ORDER BY priority ASC;")
             .fetch_all(executor)
             .await
-            .map_err(|why| DatabaseError::Other(Box::new(why)))?;
+            .map_err(convert_error)?;
     results
         .into_iter()
         .map(|row| {
@@ -81,6 +81,88 @@ where
     })
 }
 
+/// Updates the id of the origin, which the foreign keys of all rows referencing
+/// it follow, see migration `0032_origin_rename`. The former id becomes an alias
+/// of the origin, and the new id stops being an alias of any origin.
+pub async fn rename<'c, E>(
+    executor: E,
+    id: &Id<Origin>,
+    origin: WithId<Origin>,
+) -> public_transport::database::Result<WithId<Origin>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        WITH renamed AS (
+            UPDATE origins
+            SET
+                id = $2,
+                name = $3
+            WHERE
+                id = $1
+            RETURNING *
+        ),
+        reclaimed AS (
+            DELETE FROM origin_aliases
+            WHERE
+                alias = $2
+        ),
+        aliased AS (
+            INSERT INTO origin_aliases(alias, origin)
+            SELECT $1, id
+            FROM renamed
+            WHERE id <> $1
+            ON CONFLICT (alias) DO UPDATE
+            SET
+                origin = EXCLUDED.origin
+        )
+        SELECT * FROM renamed;
+        ",
+    )
+    .bind(id.raw_ref::<str>())
+    .bind(origin.id.raw())
+    .bind(origin.content.name)
+    .fetch_optional(executor)
+    .await
+    .map_err(convert_error)?
+    .map(|row: OriginRow| {
+        WithId::new(
+            Id::new(row.id),
+            Origin {
+                name: row.name,
+                priority: row.priority,
+            },
+        )
+    })
+    .ok_or(DatabaseError::NotFound)
+}
+
+/// Id of the origin, which was renamed from the given id, if any.
+pub async fn get_by_alias<'c, E>(
+    executor: E,
+    alias: &Id<Origin>,
+) -> public_transport::database::Result<Option<Id<Origin>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_scalar(
+        "
+        SELECT
+            origin
+        FROM
+            origin_aliases
+        WHERE
+            alias = $1;
+        ",
+    )
+    .bind(alias.raw_ref::<str>())
+    .fetch_optional(executor)
+    .await
+    .map_err(convert_error)
+    .map(|origin: Option<String>| origin.map(Id::new))
+}
+
 // id mapping
 
 pub(crate) async fn id_by_original_id<'c, E, S>(
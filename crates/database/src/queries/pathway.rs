@@ -0,0 +1,178 @@
+use model::{
+    origin::Origin,
+    pathway::{Level, Pathway},
+    stop::Stop,
+    WithId, WithOrigin,
+};
+use public_transport::database::Result;
+use utility::{id::Id, let_also::LetAlso, normalize::non_blank_ref};
+
+use crate::data_model::{
+    pathway::{LevelRow, PathwayRow, RowPathwayMode},
+    with_origins_and_ids,
+};
+use sqlx::{Executor, Postgres};
+
+use super::convert_error;
+
+pub async fn put_level<'c, E>(
+    executor: E,
+    origin: &Id<Origin>,
+    level: &WithId<Level>,
+) -> Result<()>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query(
+        "
+        INSERT INTO levels(origin, id, level_index, name)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (origin, id)
+        DO UPDATE SET
+            level_index = EXCLUDED.level_index,
+            name = EXCLUDED.name;
+        ",
+    )
+    .bind(origin.raw_ref::<str>())
+    .bind(level.id.raw_ref::<str>())
+    .bind(level.content.index)
+    .bind(non_blank_ref(&level.content.name))
+    .execute(executor)
+    .await
+    .map(|_| ())
+    .map_err(convert_error)
+}
+
+pub async fn put_pathway<'c, E>(
+    executor: E,
+    origin: &Id<Origin>,
+    pathway: &WithId<Pathway>,
+) -> Result<()>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let content = &pathway.content;
+    sqlx::query(
+        "
+        INSERT INTO pathways(
+            origin, id, from_stop_id, to_stop_id, station_id, from_level_id,
+            to_level_id, mode, is_bidirectional, length, traversal_time,
+            stair_count, max_slope, min_width, signposted_as, reversed_signposted_as
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+        ON CONFLICT (origin, id)
+        DO UPDATE SET
+            from_stop_id = EXCLUDED.from_stop_id,
+            to_stop_id = EXCLUDED.to_stop_id,
+            station_id = EXCLUDED.station_id,
+            from_level_id = EXCLUDED.from_level_id,
+            to_level_id = EXCLUDED.to_level_id,
+            mode = EXCLUDED.mode,
+            is_bidirectional = EXCLUDED.is_bidirectional,
+            length = EXCLUDED.length,
+            traversal_time = EXCLUDED.traversal_time,
+            stair_count = EXCLUDED.stair_count,
+            max_slope = EXCLUDED.max_slope,
+            min_width = EXCLUDED.min_width,
+            signposted_as = EXCLUDED.signposted_as,
+            reversed_signposted_as = EXCLUDED.reversed_signposted_as;
+        ",
+    )
+    .bind(origin.raw_ref::<str>())
+    .bind(pathway.id.raw_ref::<str>())
+    .bind(content.from_stop_id.raw_ref::<str>())
+    .bind(content.to_stop_id.raw_ref::<str>())
+    .bind(content.station_id.as_ref().map(|id| id.raw_ref::<str>()))
+    .bind(content.from_level_id.as_ref().map(|id| id.raw_ref::<str>()))
+    .bind(content.to_level_id.as_ref().map(|id| id.raw_ref::<str>()))
+    .bind(RowPathwayMode::from_model(content.mode))
+    .bind(content.is_bidirectional)
+    .bind(content.length)
+    .bind(content.traversal_time.map(|time| time.num_seconds() as i32))
+    .bind(content.stair_count)
+    .bind(content.max_slope)
+    .bind(content.min_width)
+    .bind(non_blank_ref(&content.signposted_as))
+    .bind(non_blank_ref(&content.reversed_signposted_as))
+    .execute(executor)
+    .await
+    .map(|_| ())
+    .map_err(convert_error)
+}
+
+pub async fn get_by_station<'c, E>(
+    executor: E,
+    station_id: &Id<Stop>,
+) -> Result<Vec<WithOrigin<WithId<Pathway>>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        WITH children AS (
+            SELECT id FROM stops WHERE parent_id = $1
+        )
+        SELECT
+            *
+        FROM
+            pathways
+        WHERE
+            station_id = $1
+            OR from_stop_id = $1
+            OR to_stop_id = $1
+            OR from_stop_id IN (SELECT id FROM children)
+            OR to_stop_id IN (SELECT id FROM children)
+        ORDER BY
+            origin, id;
+        ",
+    )
+    .bind(station_id.raw_ref::<str>())
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .let_owned(|rows: Vec<PathwayRow>| Ok(with_origins_and_ids(rows)))
+}
+
+pub async fn get_levels_by_station<'c, E>(
+    executor: E,
+    station_id: &Id<Stop>,
+) -> Result<Vec<WithOrigin<WithId<Level>>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        WITH children AS (
+            SELECT id FROM stops WHERE parent_id = $1
+        ), station_pathways AS (
+            SELECT
+                origin, from_level_id, to_level_id
+            FROM
+                pathways
+            WHERE
+                station_id = $1
+                OR from_stop_id = $1
+                OR to_stop_id = $1
+                OR from_stop_id IN (SELECT id FROM children)
+                OR to_stop_id IN (SELECT id FROM children)
+        )
+        SELECT
+            levels.*
+        FROM
+            levels
+        WHERE
+            (origin, id) IN (
+                SELECT origin, from_level_id FROM station_pathways
+                UNION
+                SELECT origin, to_level_id FROM station_pathways
+            )
+        ORDER BY
+            level_index, origin, id;
+        ",
+    )
+    .bind(station_id.raw_ref::<str>())
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .let_owned(|rows: Vec<LevelRow>| Ok(with_origins_and_ids(rows)))
+}
@@ -1,5 +1,7 @@
+use std::collections::HashMap;
+
 use model::{
-    calendar::{CalendarDate, CalendarWindow, Service},
+    calendar::{CalendarDate, CalendarWindow, Service, ServiceTag},
     origin::{Origin, OriginalIdMapping},
 };
 use public_transport::database::Result;
@@ -9,7 +11,7 @@ use utility::{
 };
 
 use crate::data_model::{
-    calendar::{CalendarWindowRow, ServiceAvailability},
+    calendar::{self, CalendarWindowRow, ServiceAvailability},
     calendar_exception::{CalendarDateRow, ServiceExceptionType},
     origin::OriginalIdMappingRow,
 };
@@ -140,6 +142,141 @@ where
     })
 }
 
+/// Ids of all services, the origin has an original id for.
+pub async fn get_ids_of_origin<'c, E>(
+    executor: E,
+    origin: &Id<Origin>,
+) -> Result<Vec<Id<Service>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_scalar(
+        "
+        SELECT DISTINCT
+            id
+        FROM
+            services_original_ids
+        WHERE
+            origin = $1;
+        ",
+    )
+    .bind(origin.raw_ref::<str>())
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .into_iter()
+    .map(|id: i32| Id::new(id))
+    .collect::<Vec<_>>()
+    .let_owned(Ok)
+}
+
+/// Replaces the tags of the given services. Tags, that did not change, are kept.
+pub async fn set_tags<'c, E>(
+    executor: E,
+    service_ids: &[Id<Service>],
+    tags: &[(Id<Service>, ServiceTag)],
+) -> Result<()>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query(
+        "
+        WITH new_tags(service_id, tag) AS (
+            SELECT * FROM UNNEST($2::int[], $3::text[]::service_tag[])
+        ), deleted AS (
+            DELETE FROM
+                service_tags t
+            WHERE
+                t.service_id = ANY($1::int[])
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM new_tags n
+                    WHERE n.service_id = t.service_id AND n.tag = t.tag
+                )
+        )
+        INSERT INTO service_tags(service_id, tag)
+        SELECT service_id, tag FROM new_tags
+        ON CONFLICT DO NOTHING;
+        ",
+    )
+    .bind(service_ids.iter().map(|id| id.raw()).collect::<Vec<_>>())
+    .bind(tags.iter().map(|(id, _)| id.raw()).collect::<Vec<_>>())
+    .bind(
+        tags.iter()
+            .map(|(_, tag)| tag.to_string())
+            .collect::<Vec<_>>(),
+    )
+    .execute(executor)
+    .await
+    .map(|_| ())
+    .map_err(convert_error)
+}
+
+pub async fn get_tags<'c, E>(
+    executor: E,
+    service_ids: &[Id<Service>],
+) -> Result<HashMap<Id<Service>, Vec<ServiceTag>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        SELECT
+            service_id, tag
+        FROM
+            service_tags
+        WHERE
+            service_id = ANY($1::int[])
+        ORDER BY
+            service_id, tag;
+        ",
+    )
+    .bind(service_ids.iter().map(|id| id.raw()).collect::<Vec<_>>())
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .into_iter()
+    .fold(
+        HashMap::new(),
+        |mut tags: HashMap<Id<Service>, Vec<ServiceTag>>,
+         (id, tag): (i32, calendar::ServiceTag)| {
+            tags.entry(Id::new(id)).or_default().push(tag.into());
+            tags
+        },
+    )
+    .let_owned(Ok)
+}
+
+/// Id of the service of the origin with the given signature. A new id is
+/// reserved for the signature, if there is none yet, which is indicated by
+/// `true`. Concurrent calls for the same signature wait for each other.
+pub async fn claim_signature<'c, E>(
+    executor: E,
+    origin: &Id<Origin>,
+    signature: &str,
+) -> Result<(Id<Service>, bool)>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        INSERT INTO service_signatures(origin, signature, service_id)
+        VALUES ($1, $2, nextval('service_id_seq'))
+        ON CONFLICT (origin, signature)
+        DO UPDATE SET
+            signature = EXCLUDED.signature
+        RETURNING
+            service_id, xmax = 0 AS created;
+        ",
+    )
+    .bind(origin.raw_ref::<str>())
+    .bind(signature)
+    .fetch_one(executor)
+    .await
+    .map_err(convert_error)
+    .map(|(id, created): (i32, bool)| (Id::new(id), created))
+}
+
 pub async fn put_calendar_window<'c, E>(
     executor: E,
     service_id: Option<Id<Service>>,
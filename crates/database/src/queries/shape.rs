@@ -1,12 +1,17 @@
 use model::{
-    origin::Origin,
-    shape::{Shape, ShapePoint},
+    line::Line,
+    origin::{Origin, OriginalIdMapping},
+    shape::{LineShape, Shape, ShapePoint},
     trip::{StopTime, Trip},
 };
 use public_transport::database::Result;
 use utility::{id::Id, let_also::LetAlso};
 
-use crate::data_model::{shape::ShapePointRow, trip::StopTimeRow};
+use crate::data_model::{
+    origin::OriginalIdMappingRow,
+    shape::{LineShapePointRow, ShapePointRow},
+    trip::{RowTripDirection, StopTimeRow},
+};
 use sqlx::{Executor, Postgres};
 
 use super::convert_error;
@@ -103,3 +108,201 @@ where
     .map_err(convert_error)?;
     Ok(())
 }
+
+// Subject Repo
+
+pub async fn id_by_original_id<'c, E>(
+    executor: E,
+    origin: Id<Origin>,
+    original_id: String,
+) -> Result<Option<Id<Shape>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_scalar(
+        "
+        SELECT
+            id
+        FROM
+            shapes_original_ids
+        WHERE
+            origin = $1 AND original_id = $2;
+        ",
+    )
+    .bind(origin.raw())
+    .bind(original_id)
+    .fetch_optional(executor)
+    .await
+    .map_err(convert_error)?
+    .map(|id: i32| Id::new(id))
+    .let_owned(Ok)
+}
+
+pub async fn put_original_id<'c, E>(
+    executor: E,
+    origin: Id<Origin>,
+    original_id: String,
+    id: Id<Shape>,
+) -> Result<OriginalIdMapping<Shape>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        INSERT INTO shapes_original_ids(
+            origin,
+            original_id,
+            id
+        )
+        VALUES ($1, $2, $3)
+        ON CONFLICT (origin, original_id)
+        DO UPDATE SET
+            id = EXCLUDED.id
+        RETURNING *;
+        ",
+    )
+    .bind(origin.raw())
+    .bind(original_id)
+    .bind(id.raw())
+    .fetch_one(executor)
+    .await
+    .map_err(convert_error)
+    .map(|row: OriginalIdMappingRow<i32>| row.to_model())
+}
+
+// Shape Repo
+
+pub async fn create_id<'c, E>(executor: E) -> Result<Id<Shape>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_scalar("SELECT nextval('shape_id_seq')::INT;")
+        .fetch_one(executor)
+        .await
+        .map_err(convert_error)
+        .map(|id: i32| Id::new(id))
+}
+
+pub async fn delete_points<'c, E>(executor: E, shape_id: &Id<Shape>) -> Result<()>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query("DELETE FROM shapes WHERE id = $1;")
+        .bind(shape_id.raw())
+        .execute(executor)
+        .await
+        .map_err(convert_error)?;
+    Ok(())
+}
+
+pub async fn put_points<'c, E>(
+    executor: E,
+    shape_id: &Id<Shape>,
+    points: Vec<(i32, ShapePoint)>,
+) -> Result<()>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let (sequences, points): (Vec<_>, Vec<_>) = points.into_iter().unzip();
+    sqlx::query(
+        "
+        INSERT INTO shapes(
+            id,
+            sequence,
+            latitude,
+            longitude,
+            distance
+        )
+        SELECT
+            $1, *
+        FROM
+            UNNEST($2::int[], $3::float8[], $4::float8[], $5::float8[])
+        ON CONFLICT (id, sequence)
+        DO UPDATE SET
+            latitude = EXCLUDED.latitude,
+            longitude = EXCLUDED.longitude,
+            distance = EXCLUDED.distance;
+        ",
+    )
+    .bind(shape_id.raw())
+    .bind(sequences)
+    .bind(
+        points
+            .iter()
+            .map(|point| point.latitude)
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        points
+            .iter()
+            .map(|point| point.longitude)
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        points
+            .iter()
+            .map(|point| point.distance)
+            .collect::<Vec<_>>(),
+    )
+    .execute(executor)
+    .await
+    .map_err(convert_error)?;
+    Ok(())
+}
+
+pub async fn get_by_line<'c, E>(
+    executor: E,
+    line_id: &Id<Line>,
+) -> Result<Vec<LineShape>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let rows: Vec<LineShapePointRow> = sqlx::query_as(
+        "
+        WITH line_shapes AS (
+            SELECT DISTINCT
+                direction, shape_id
+            FROM
+                trips
+            WHERE
+                line_id = $1 AND shape_id IS NOT NULL
+        )
+        SELECT
+            l.direction, s.id, s.sequence, s.latitude, s.longitude, s.distance
+        FROM
+            line_shapes l
+            JOIN shapes s ON s.id = l.shape_id
+        ORDER BY
+            l.direction, s.id, s.sequence;
+        ",
+    )
+    .bind(line_id.raw())
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?;
+    // rows are ordered, so the points of a shape are consecutive.
+    let mut shapes: Vec<LineShape> = vec![];
+    for row in rows {
+        let direction = row.direction.map(RowTripDirection::to_model);
+        let point = ShapePoint {
+            latitude: row.latitude,
+            longitude: row.longitude,
+            distance: row.distance,
+        };
+        match shapes.last_mut() {
+            Some(shape)
+                if shape.shape_id.raw() == row.id && shape.direction == direction =>
+            {
+                shape.shape.points.push(point)
+            }
+            _ => shapes.push(LineShape {
+                direction,
+                shape_id: Id::new(row.id),
+                shape: Shape {
+                    points: vec![point],
+                },
+            }),
+        }
+    }
+    Ok(shapes)
+}
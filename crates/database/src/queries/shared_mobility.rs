@@ -9,6 +9,7 @@ use utility::{
     geo::{self, EARTH_RADIUS_KM},
     id::Id,
     let_also::LetAlso,
+    normalize::non_blank,
 };
 
 use crate::data_model::{
@@ -29,7 +30,7 @@ where
         SELECT
             id, origin, name, latitude, longitude, capacity,
             rentail_uri_android, rentail_uri_ios, rental_uri_web,
-            status
+            status, area, region_id, is_virtual_station
         FROM
             shared_mobility_stations
         WHERE
@@ -76,12 +77,17 @@ where
         SELECT
             id, origin, name, latitude, longitude, capacity,
             rental_uri_android, rental_uri_ios, rental_uri_web,
-            status
+            status, area, region_id, is_virtual_station
         FROM
             shared_mobility_stations
         WHERE
             id IN (
                 SELECT id FROM distance_calc WHERE distance < $8
+            )
+            -- the location may be within the area of a station further away.
+            OR (
+                $2 BETWEEN area_min_latitude AND area_max_latitude
+                AND $3 BETWEEN area_min_longitude AND area_max_longitude
             );
         ",
     )
@@ -127,6 +133,48 @@ where
     Ok(())
 }
 
+/// Sets the statuses of many stations of the origin at once. Gives the ids of
+/// the statuses without a station.
+pub async fn update_statuses<'c, E>(
+    executor: E,
+    origin: &Id<Origin>,
+    statuses: &[(Id<SharedMobilityStation>, Option<Status>)],
+) -> Result<Vec<Id<SharedMobilityStation>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let (ids, statuses): (Vec<&str>, Vec<Option<Json<&Status>>>) = statuses
+        .iter()
+        .map(|(id, status)| (id.raw_ref::<str>(), status.as_ref().map(Json)))
+        .unzip();
+    sqlx::query_as(
+        "
+        WITH input AS (
+            SELECT * FROM UNNEST($2::TEXT[], $3::JSONB[]) AS input(id, status)
+        ), updated AS (
+            UPDATE shared_mobility_stations s
+            SET status = input.status
+            FROM input
+            WHERE s.id = input.id AND s.origin = $1
+            RETURNING s.id
+        )
+        SELECT input.id
+        FROM input
+        WHERE NOT EXISTS (SELECT 1 FROM updated WHERE updated.id = input.id);
+        ",
+    )
+    .bind(origin.raw_ref::<str>())
+    .bind(ids)
+    .bind(statuses)
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .into_iter()
+    .map(|(id,): (String,)| Id::new(id))
+    .collect::<Vec<_>>()
+    .let_owned(Ok)
+}
+
 pub async fn put_all<'c, E>(
     executor: E,
     origin: &Id<Origin>,
@@ -149,9 +197,21 @@ where
             "rental_uri_ios",
             "rental_uri_web",
             "status",
+            "area",
+            "region_id",
+            "is_virtual_station",
+            "area_min_latitude",
+            "area_min_longitude",
+            "area_max_latitude",
+            "area_max_longitude",
         ],
         stations,
         |query, station| {
+            let bounds = station
+                .content
+                .area
+                .as_ref()
+                .and_then(|area| area.bounding_box());
             query
                 .bind(station.id.raw())
                 .bind(origin.raw())
@@ -159,10 +219,17 @@ where
                 .bind(station.content.latitude)
                 .bind(station.content.longitude)
                 .bind(station.content.capacity as i32)
-                .bind(station.content.rental_uris.android.clone())
-                .bind(station.content.rental_uris.ios.clone())
-                .bind(station.content.rental_uris.web.clone())
+                .bind(non_blank(station.content.rental_uris.android.clone()))
+                .bind(non_blank(station.content.rental_uris.ios.clone()))
+                .bind(non_blank(station.content.rental_uris.web.clone()))
                 .bind(station.content.status.clone().map(|s| Json(s)))
+                .bind(station.content.area.clone().map(Json))
+                .bind(station.content.region_id.clone())
+                .bind(station.content.is_virtual_station)
+                .bind(bounds.map(|((min_lat, _), _)| min_lat))
+                .bind(bounds.map(|((_, min_lon), _)| min_lon))
+                .bind(bounds.map(|(_, (max_lat, _))| max_lat))
+                .bind(bounds.map(|(_, (_, max_lon))| max_lon))
         },
         &["id", "origin"],
     )
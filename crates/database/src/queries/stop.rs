@@ -1,21 +1,27 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
 use model::{
+    agency::Agency,
     origin::{Origin, OriginalIdMapping},
-    stop::Stop,
+    stop::{ServiceSummary, Stop},
     DatabaseEntry, WithId, WithOrigin,
 };
-use public_transport::database::Result;
+use public_transport::{capability::SearchMode, database::Result};
 use utility::{
     geo::{self, EARTH_RADIUS_KM},
     id::{Id, IdWrapper},
     let_also::LetAlso,
+    normalize::{non_blank, non_blank_ref},
 };
 
 use crate::data_model::{
-    stop::StopRow, with_origin_and_id, with_origins, with_origins_and_ids,
+    stop::{amenity_names, platform_sort_key, StopRow},
+    with_origin_and_id, with_origins, with_origins_and_ids,
 };
 use sqlx::{Executor, Postgres};
 
-use super::convert_error;
+use super::{convert_error, escape_like};
 
 // Repo
 
@@ -27,7 +33,8 @@ where
         "
         SELECT
             id, origin, name, description, parent_id,
-            latitude, longitude, address, platform_code
+            latitude, longitude, address, platform_code, amenities,
+            updated_at
         FROM
             stops
         WHERE id = $1;
@@ -50,9 +57,12 @@ where
         "
         SELECT
             id, origin, name, description, parent_id,
-            latitude, longitude, address, platform_code
+            latitude, longitude, address, platform_code, amenities,
+            updated_at
         FROM
-            stops;
+            stops
+        ORDER BY
+            id, origin;
         ",
     )
     .fetch_all(executor)
@@ -63,6 +73,451 @@ where
     })
 }
 
+pub async fn get_many<'c, E>(
+    executor: E,
+    ids: &[Id<Stop>],
+) -> Result<Vec<DatabaseEntry<Stop>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        SELECT
+            id, origin, name, description, parent_id,
+            latitude, longitude, address, platform_code, amenities,
+            updated_at
+        FROM
+            stops
+        WHERE id = ANY($1);
+        ",
+    )
+    .bind(ids.iter().map(|id| id.raw()).collect::<Vec<_>>())
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .let_owned(|stops: Vec<StopRow>| {
+        Ok(DatabaseEntry::gather_many(with_origins_and_ids(stops)))
+    })
+}
+
+pub async fn get_page_after<'c, E>(
+    executor: E,
+    after: Option<Id<Stop>>,
+    limit: usize,
+    origins: &[Id<Origin>],
+) -> Result<Vec<DatabaseEntry<Stop>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        WITH page AS (
+            SELECT DISTINCT
+                id
+            FROM
+                stops
+            WHERE
+                origin = ANY($3)
+                AND ($1::text IS NULL OR id > $1)
+            ORDER BY
+                id
+            LIMIT $2
+        )
+        SELECT
+            s.id, s.origin, s.name, s.description, s.parent_id,
+            s.latitude, s.longitude, s.address, s.platform_code, s.amenities,
+            s.updated_at
+        FROM
+            page p
+            JOIN stops s ON s.id = p.id
+        WHERE
+            s.origin = ANY($3)
+        ORDER BY
+            p.id;
+        ",
+    )
+    .bind(after.map(|id| id.raw()))
+    .bind(limit as i64)
+    .bind(
+        origins
+            .iter()
+            .map(|origin| origin.raw())
+            .collect::<Vec<_>>(),
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .let_owned(|stops: Vec<StopRow>| {
+        Ok(DatabaseEntry::gather_many(with_origins_and_ids(stops)))
+    })
+}
+
+/// Like [`get_page_after`], but only stops, at which trips of the agency's lines
+/// call. All of them, if `limit` is `None`.
+pub async fn get_page_by_agency_after<'c, E>(
+    executor: E,
+    agency_id: &Id<Agency>,
+    include_secondary: bool,
+    after: Option<Id<Stop>>,
+    limit: Option<usize>,
+    origins: &[Id<Origin>],
+) -> Result<Vec<DatabaseEntry<Stop>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        WITH served AS (
+            SELECT DISTINCT
+                st.stop_id AS id
+            FROM
+                lines l
+                JOIN trips t ON t.line_id = l.id
+                JOIN stop_times st ON st.trip_id = t.id
+            WHERE
+                l.agency_id = $1
+                OR $4 AND EXISTS (
+                    SELECT 1 FROM line_secondary_agencies sa
+                    WHERE sa.line_id = l.id
+                        AND sa.origin = l.origin
+                        AND sa.agency_id = $1
+                )
+        ),
+        page AS (
+            SELECT DISTINCT
+                s.id
+            FROM
+                stops s
+                JOIN served ON served.id = s.id
+            WHERE
+                s.origin = ANY($5)
+                AND ($2::text IS NULL OR s.id > $2)
+            ORDER BY
+                s.id
+            LIMIT $3
+        )
+        SELECT
+            s.id, s.origin, s.name, s.description, s.parent_id,
+            s.latitude, s.longitude, s.address, s.platform_code, s.amenities,
+            s.updated_at
+        FROM
+            page p
+            JOIN stops s ON s.id = p.id
+        WHERE
+            s.origin = ANY($5)
+        ORDER BY
+            p.id;
+        ",
+    )
+    .bind(agency_id.raw_ref::<str>())
+    .bind(after.map(|id| id.raw()))
+    .bind(limit.map(|limit| limit as i64))
+    .bind(include_secondary)
+    .bind(
+        origins
+            .iter()
+            .map(|origin| origin.raw())
+            .collect::<Vec<_>>(),
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .let_owned(|stops: Vec<StopRow>| {
+        Ok(DatabaseEntry::gather_many(with_origins_and_ids(stops)))
+    })
+}
+
+/// Stops, whose parent is the given stop, in natural order of their platform
+/// codes across all origins. Stops without platform code come last.
+pub async fn get_platforms<'c, E>(
+    executor: E,
+    parent_id: &Id<Stop>,
+) -> Result<Vec<DatabaseEntry<Stop>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        WITH platforms AS (
+            SELECT
+                id, MIN(platform_sort_key COLLATE \"C\") AS sort_key
+            FROM
+                stops
+            WHERE
+                parent_id = $1
+            GROUP BY
+                id
+        )
+        SELECT
+            s.id, s.origin, s.name, s.description, s.parent_id,
+            s.latitude, s.longitude, s.address, s.platform_code, s.amenities,
+            s.updated_at
+        FROM
+            platforms p
+            JOIN stops s ON s.id = p.id
+        ORDER BY
+            p.sort_key NULLS LAST, p.id;
+        ",
+    )
+    .bind(parent_id.raw_ref::<str>())
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .let_owned(|stops: Vec<StopRow>| {
+        Ok(DatabaseEntry::gather_many(with_origins_and_ids(stops)))
+    })
+}
+
+pub async fn redirect<'c, E>(
+    executor: E,
+    origin: &Id<Origin>,
+    from: &Id<Stop>,
+    to: &Id<Stop>,
+) -> Result<()>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query(
+        "
+        WITH moved AS (
+            INSERT INTO stops(
+                id, origin, name, description, parent_id,
+                latitude, longitude, address, platform_code, amenities,
+                platform_sort_key
+            )
+            SELECT
+                $3, origin, name, description, parent_id,
+                latitude, longitude, address, platform_code, amenities,
+                platform_sort_key
+            FROM
+                stops
+            WHERE
+                id = $2 AND origin = $1
+            ON CONFLICT (id, origin) DO UPDATE SET
+                name = COALESCE(stops.name, EXCLUDED.name),
+                description = COALESCE(stops.description, EXCLUDED.description),
+                parent_id = COALESCE(stops.parent_id, EXCLUDED.parent_id),
+                latitude = CASE
+                    WHEN stops.latitude IS NULL THEN EXCLUDED.latitude
+                    ELSE stops.latitude
+                END,
+                longitude = CASE
+                    WHEN stops.latitude IS NULL THEN EXCLUDED.longitude
+                    ELSE stops.longitude
+                END,
+                address = COALESCE(stops.address, EXCLUDED.address),
+                platform_code = COALESCE(stops.platform_code, EXCLUDED.platform_code),
+                amenities = ARRAY(
+                    SELECT DISTINCT unnest(stops.amenities || EXCLUDED.amenities)
+                    ORDER BY 1
+                ),
+                platform_sort_key = CASE
+                    WHEN stops.platform_code IS NULL THEN EXCLUDED.platform_sort_key
+                    ELSE stops.platform_sort_key
+                END
+            RETURNING name
+        ), children AS (
+            UPDATE stops SET parent_id = $3 WHERE parent_id = $2 AND origin = $1
+        ), original_ids AS (
+            UPDATE stops_original_ids SET id = $3 WHERE id = $2 AND origin = $1
+        ), shared_mobility_original_ids AS (
+            UPDATE shared_mobility_stations_original_ids
+            SET id = $3 WHERE id = $2 AND origin = $1
+        ), stop_times AS (
+            UPDATE stop_times SET stop_id = $3, stop_name = (SELECT name FROM moved)
+            WHERE stop_id = $2 AND origin = $1
+        ), redirected_pathways AS (
+            UPDATE pathways SET
+                from_stop_id = CASE WHEN from_stop_id = $2 THEN $3 ELSE from_stop_id END,
+                to_stop_id = CASE WHEN to_stop_id = $2 THEN $3 ELSE to_stop_id END,
+                station_id = CASE WHEN station_id = $2 THEN $3 ELSE station_id END
+            WHERE
+                origin = $1 AND $2 IN (from_stop_id, to_stop_id, station_id)
+        )
+        DELETE FROM stops WHERE id = $2 AND origin = $1;
+        ",
+    )
+    .bind(origin.raw())
+    .bind(from.raw())
+    .bind(to.raw())
+    .execute(executor)
+    .await
+    .map_err(convert_error)
+    .map(|_| ())
+}
+
+pub async fn backfill_centroids<'c, E>(
+    executor: E,
+    origin: &Id<Origin>,
+) -> Result<u64>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query(
+        "
+        UPDATE stops AS parent
+        SET
+            latitude = children.latitude,
+            longitude = children.longitude
+        FROM (
+            SELECT
+                parent_id, origin,
+                AVG(latitude) AS latitude,
+                AVG(longitude) AS longitude
+            FROM
+                stops
+            WHERE
+                origin = $1
+                AND parent_id IS NOT NULL
+                AND latitude IS NOT NULL
+                AND longitude IS NOT NULL
+            GROUP BY
+                parent_id, origin
+        ) AS children
+        WHERE
+            parent.id = children.parent_id
+            AND parent.origin = children.origin
+            AND (parent.latitude IS NULL OR parent.longitude IS NULL);
+        ",
+    )
+    .bind(origin.raw())
+    .execute(executor)
+    .await
+    .map_err(convert_error)
+    .map(|result| result.rows_affected())
+}
+
+/// Recomputes the service summary of the given stops, or of all stops if `None`.
+pub async fn refresh_service_summary<'c, E>(
+    executor: E,
+    ids: Option<&[&Id<Stop>]>,
+) -> Result<u64>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    // the last day of a service is approximated by the end of its last calendar
+    // window or its last added date, whichever is later.
+    sqlx::query(
+        "
+        WITH service_ends AS (
+            SELECT
+                service_id, MAX(date) AS last_date
+            FROM (
+                SELECT service_id, end_date AS date FROM calendar_windows
+                UNION ALL
+                SELECT service_id, date FROM calendar_dates
+                WHERE exception_type = 'added'
+            ) AS dates
+            GROUP BY
+                service_id
+        ), served AS (
+            SELECT
+                st.stop_id,
+                st.origin,
+                se.last_date,
+                -- neither boarding nor alighting is regular, but one of them can
+                -- be arranged. unknown types count as regular. see
+                -- `StopTime::is_on_request`.
+                (
+                    COALESCE(st.pickup_type, 'regular') <> 'regular'
+                    AND COALESCE(st.drop_off_type, 'regular') <> 'regular'
+                    AND (
+                        st.pickup_type IN ('phone_agency', 'coordinate_with_driver')
+                        OR st.drop_off_type IN ('phone_agency', 'coordinate_with_driver')
+                    )
+                ) AS on_request
+            FROM
+                stop_times st
+                JOIN trips t ON t.id = st.trip_id AND t.origin = st.origin
+                JOIN service_ends se ON se.service_id = t.service_id
+        ), served_with_parents AS (
+            SELECT stop_id, last_date, on_request FROM served
+            UNION ALL
+            SELECT
+                child.parent_id, served.last_date, served.on_request
+            FROM
+                served
+                JOIN stops child
+                    ON child.id = served.stop_id AND child.origin = served.origin
+            WHERE
+                child.parent_id IS NOT NULL
+        ), summary AS (
+            SELECT
+                s.id AS stop_id,
+                MAX(served.last_date) AS last_service_date,
+                -- trips of expired services no longer call at the stop.
+                COALESCE(
+                    BOOL_AND(served.on_request)
+                        FILTER (WHERE served.last_date >= CURRENT_DATE),
+                    FALSE
+                ) AS served_only_on_request
+            FROM
+                (SELECT DISTINCT id FROM stops) AS s
+                LEFT JOIN served_with_parents served ON served.stop_id = s.id
+            WHERE
+                $1::text[] IS NULL OR s.id = ANY($1::text[])
+            GROUP BY
+                s.id
+        )
+        INSERT INTO stop_service_summary(
+            stop_id, last_service_date, served_only_on_request
+        )
+        SELECT stop_id, last_service_date, served_only_on_request FROM summary
+        ON CONFLICT (stop_id) DO UPDATE
+        SET
+            last_service_date = EXCLUDED.last_service_date,
+            served_only_on_request = EXCLUDED.served_only_on_request;
+        ",
+    )
+    .bind(ids.as_ref().map(|ids| ids.raw_ref::<str>()))
+    .execute(executor)
+    .await
+    .map_err(convert_error)
+    .map(|result| result.rows_affected())
+}
+
+/// Returns the service summary of each given stop, which has one.
+pub async fn get_service_summaries<'c, E>(
+    executor: E,
+    ids: &[&Id<Stop>],
+) -> Result<HashMap<Id<Stop>, ServiceSummary>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        SELECT
+            stop_id, last_service_date, served_only_on_request
+        FROM
+            stop_service_summary
+        WHERE
+            stop_id = ANY($1::text[]);
+        ",
+    )
+    .bind(ids.raw_ref::<str>())
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .into_iter()
+    .map(
+        |(id, last_service_date, served_only_on_request): (
+            String,
+            Option<NaiveDate>,
+            bool,
+        )| {
+            (
+                Id::new(id),
+                ServiceSummary {
+                    last_service_date,
+                    served_only_on_request,
+                },
+            )
+        },
+    )
+    .collect::<HashMap<_, _>>()
+    .let_owned(Ok)
+}
+
 pub async fn insert<'c, E>(
     executor: E,
     stop: WithOrigin<Stop>,
@@ -80,20 +535,24 @@ where
             latitude,
             longitude,
             address,
-            platform_code
+            platform_code,
+            amenities,
+            platform_sort_key
         )
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
         RETURNING *;
         ",
     )
     .bind(stop.origin.raw())
-    .bind(&stop.content.name)
-    .bind(&stop.content.description)
+    .bind(non_blank_ref(&stop.content.name))
+    .bind(non_blank_ref(&stop.content.description))
     .bind(stop.content.parent_id.clone().raw())
     .bind(stop.content.latitude())
     .bind(stop.content.longitude())
-    .bind(stop.content.address())
-    .bind(stop.content.platform_code)
+    .bind(non_blank(stop.content.address()))
+    .bind(non_blank_ref(&stop.content.platform_code))
+    .bind(amenity_names(&stop.content.amenities))
+    .bind(platform_sort_key(&stop.content.platform_code))
     .fetch_one(executor)
     .await
     .map(|row: StopRow| with_origin_and_id(row))
@@ -118,9 +577,11 @@ where
             latitude,
             longitude,
             address,
-            platform_code
+            platform_code,
+            amenities,
+            platform_sort_key
         )
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $7, $8)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
         ON CONFLICT (id, origin)
         DO UPDATE SET
             name = EXCLUDED.name,
@@ -129,19 +590,23 @@ where
             latitude = EXCLUDED.latitude,
             longitude = EXCLUDED.longitude,
             address = EXCLUDED.address,
-            platform_code = EXCLUDED.platform_code
+            platform_code = EXCLUDED.platform_code,
+            amenities = EXCLUDED.amenities,
+            platform_sort_key = EXCLUDED.platform_sort_key
         RETURNING *;
         ",
     )
     .bind(stop.content.id.raw())
     .bind(stop.origin.raw())
-    .bind(&stop.content.content.name)
-    .bind(&stop.content.content.description)
+    .bind(non_blank_ref(&stop.content.content.name))
+    .bind(non_blank_ref(&stop.content.content.description))
     .bind(stop.content.content.parent_id.clone().raw())
     .bind(stop.content.content.latitude())
     .bind(stop.content.content.longitude())
-    .bind(stop.content.content.address())
-    .bind(stop.content.content.platform_code)
+    .bind(non_blank(stop.content.content.address()))
+    .bind(non_blank_ref(&stop.content.content.platform_code))
+    .bind(amenity_names(&stop.content.content.amenities))
+    .bind(platform_sort_key(&stop.content.content.platform_code))
     .fetch_one(executor)
     .await
     .map_err(|why| convert_error(why))
@@ -163,6 +628,7 @@ where
             "longitude",
             "address",
             "platform_code",
+            "amenities",
         ],
         stops,
         |query, stop| {
@@ -193,20 +659,24 @@ where
             latitude = $4,
             longitude = $5,
             address = $6,
-            platform_code = $7
-        WHERE origin = $8 AND id = $9
+            platform_code = $7,
+            amenities = $8,
+            platform_sort_key = $11
+        WHERE origin = $9 AND id = $10
         RETURNING *;
         ",
     )
-    .bind(&stop.content.content.name)
-    .bind(&stop.content.content.description)
+    .bind(non_blank_ref(&stop.content.content.name))
+    .bind(non_blank_ref(&stop.content.content.description))
     .bind(stop.content.content.parent_id.clone().raw())
     .bind(stop.content.content.latitude())
     .bind(stop.content.content.longitude())
-    .bind(stop.content.content.address())
-    .bind(stop.content.content.platform_code)
+    .bind(non_blank(stop.content.content.address()))
+    .bind(non_blank_ref(&stop.content.content.platform_code))
+    .bind(amenity_names(&stop.content.content.amenities))
     .bind(stop.origin.raw())
     .bind(stop.content.id.raw())
+    .bind(platform_sort_key(&stop.content.content.platform_code))
     .fetch_one(executor)
     .await
     .map_err(convert_error)
@@ -308,7 +778,8 @@ where
         )
         SELECT
             id, origin, name, description, parent_id,
-            latitude, longitude, address, platform_code
+            latitude, longitude, address, platform_code, amenities,
+            updated_at
         FROM
             stops
         WHERE
@@ -345,7 +816,8 @@ where
         "
         SELECT
             id, origin, name, description, parent_id,
-            latitude, longitude, address, platform_code
+            latitude, longitude, address, platform_code, amenities,
+            updated_at
         FROM
             stops
         WHERE name ILIKE $1;
@@ -363,24 +835,43 @@ where
 pub async fn search<'c, E, S>(
     executor: E,
     pattern: S,
+    mode: SearchMode,
 ) -> Result<Vec<DatabaseEntry<Stop>>>
 where
     E: Executor<'c, Database = Postgres>,
     S: Into<String> + Send,
 {
-    let pattern: String = pattern.into().replace('%', "");
-    let prefix_pattern = format!("{}%", pattern);
-    let prefix_postfix_pattern = format!("%{}%", pattern);
-    sqlx::query_as(
+    let pattern: String = pattern.into();
+    let escaped = escape_like(&pattern);
+    let prefix_pattern = format!("{}%", escaped);
+    let prefix_postfix_pattern = format!("%{}%", escaped);
+    // the trigram operators do not even parse without pg_trgm.
+    let (filter, ranking) = match mode {
+        SearchMode::Trigram => {
+            ("name % $1 OR name ILIKE $3", "similarity(name, $1) DESC")
+        }
+        SearchMode::Substring => ("name ILIKE $3", "name ASC"),
+    };
+    sqlx::query_as(&format!(
         "
         SELECT
             id, origin, name, description, parent_id,
-            latitude, longitude, address, platform_code
+            latitude, longitude, address, platform_code, amenities,
+            updated_at
         FROM
             stops
+            LEFT JOIN stop_service_summary summary ON summary.stop_id = stops.id
         WHERE
-            name % $1 OR name ILIKE $3
+            {}
         ORDER BY
+            -- stops without future service last, unless not evaluated yet
+            (
+                summary.stop_id IS NOT NULL
+                AND (
+                    summary.last_service_date IS NULL
+                    OR summary.last_service_date < CURRENT_DATE
+                )
+            ) ASC,
             -- exact matches first
             CASE
                 WHEN name = $1 THEN 1
@@ -388,11 +879,12 @@ where
                 WHEN name ILIKE $3 THEN 3
                 ELSE 4
             END ASC,
-            -- then sort by similarity
-            similarity(name, $1) DESC
+            -- then sort by similarity, or by name without pg_trgm
+            {}
         LIMIT 50; -- TODO: maybe insert a parameter for this.
         ",
-    )
+        filter, ranking
+    ))
     .bind(pattern)
     .bind(prefix_pattern)
     .bind(prefix_postfix_pattern)
@@ -404,10 +896,95 @@ where
     })
 }
 
+/// Lightweight variant of [`search`] for completing stop names while typing.
+/// Prefix matches come first, then fuzzy matches by similarity, or substring
+/// matches without pg_trgm. At most `limit` stops, each with the data of the given
+/// origins only.
+pub async fn autocomplete<'c, E, S>(
+    executor: E,
+    pattern: S,
+    limit: usize,
+    origins: &[Id<Origin>],
+    mode: SearchMode,
+) -> Result<Vec<DatabaseEntry<Stop>>>
+where
+    E: Executor<'c, Database = Postgres>,
+    S: Into<String> + Send,
+{
+    let pattern: String = pattern.into();
+    let escaped = escape_like(&pattern);
+    let prefix_pattern = format!("{}%", escaped);
+    // lower scores rank first.
+    let (filter, score) = match mode {
+        SearchMode::Trigram => {
+            ("name ILIKE $2 OR name % $1", "-similarity(name, $1)")
+        }
+        SearchMode::Substring => ("name ILIKE '%' || $2", "LENGTH(name)"),
+    };
+    sqlx::query_as(&format!(
+        "
+        WITH matches AS (
+            SELECT DISTINCT ON (id)
+                id, name ILIKE $2 AS is_prefix, {} AS score, name
+            FROM
+                stops
+            WHERE
+                ({})
+                AND latitude IS NOT NULL
+                AND longitude IS NOT NULL
+                AND origin = ANY($4)
+            ORDER BY
+                id, is_prefix DESC, score ASC, name ASC
+        ),
+        page AS (
+            SELECT
+                id, is_prefix, score, name
+            FROM
+                matches
+            ORDER BY
+                is_prefix DESC, score ASC, name ASC, id ASC
+            LIMIT $3
+        )
+        SELECT
+            s.id, s.origin, s.name, s.description, s.parent_id,
+            s.latitude, s.longitude, s.address, s.platform_code, s.amenities,
+            s.updated_at
+        FROM
+            page p
+            JOIN stops s ON s.id = p.id
+        WHERE
+            s.origin = ANY($4)
+        ORDER BY
+            p.is_prefix DESC, p.score ASC, p.name ASC, p.id ASC;
+        ",
+        score, filter
+    ))
+    .bind(pattern)
+    .bind(prefix_pattern)
+    .bind(limit as i64)
+    .bind(
+        origins
+            .iter()
+            .map(|origin| origin.raw())
+            .collect::<Vec<_>>(),
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .let_owned(|stops: Vec<StopRow>| {
+        Ok(DatabaseEntry::gather_many(with_origins_and_ids(stops)))
+    })
+}
+
+/// Stops of other origins, which may be the same as the given one, at most
+/// `limit` of them. They are ranked like in `Stop::same_subject_as`, so that the
+/// most similar ones are returned. Stops, which are too far away or known to be
+/// distinct by their platform or parent, are left out.
 pub async fn merge_candidates<'c, E>(
     executor: E,
     stop: &Stop,
     excluded_origin: &Id<Origin>,
+    limit: Option<usize>,
 ) -> Result<Vec<WithOrigin<WithId<Stop>>>>
 where
     E: Executor<'c, Database = Postgres>,
@@ -423,11 +1000,13 @@ where
     let ((min_lat, min_lon), (max_lat, max_lon)) =
         geo::calculate_bounding_box(lat, lon, rad);
 
+    // common names match lots of stops in dense areas, so only the most
+    // promising candidates are returned.
     sqlx::query_as(
         "
         WITH distance_calc AS (
             SELECT
-                id,
+                id, origin,
                 ($1 * ACOS(
                     COS(RADIANS($2)) * COS(RADIANS(latitude)) *
                     COS(RADIANS(longitude) - RADIANS($3)) +
@@ -436,24 +1015,46 @@ where
             FROM
                 stops
             WHERE
-                latitude BETWEEN $4 AND $5
+                ABS($8 - 0.0) > 0.00001
+                AND latitude BETWEEN $4 AND $5
                 AND longitude BETWEEN $6 AND $7
         )
         SELECT
-            id, origin, name, description, parent_id,
-            latitude, longitude, address, platform_code
+            stops.id, stops.origin, name, description, parent_id,
+            latitude, longitude, address, platform_code, amenities,
+            updated_at
         FROM
             stops
+            LEFT JOIN distance_calc d
+                ON d.id = stops.id
+                AND d.origin = stops.origin
+                AND d.distance < $8
         WHERE
-            ((name != '' AND name % $9)
-                OR (ABS($8 - 0.0) > 0.00001 AND id IN (
-                    SELECT id FROM distance_calc WHERE distance < $8
-                )))
+            ((name != '' AND name % $9) OR d.id IS NOT NULL)
             AND NOT EXISTS (
                 SELECT 1 FROM stops s2
                 WHERE s2.id = stops.id
                 AND s2.origin = $10
-            );
+            )
+            AND (
+                ABS($8 - 0.0) <= 0.00001
+                OR latitude IS NULL
+                OR longitude IS NULL
+                OR d.id IS NOT NULL
+            )
+            AND ($14::text IS NULL OR platform_code IS NULL OR platform_code = $14)
+            AND ($15::text IS NULL OR parent_id IS NULL OR parent_id = $15)
+        ORDER BY
+            $11 * COALESCE(1.0 - d.distance / $8, 0.0)
+                + $12 * COALESCE(
+                    similarity(normalized_stop_name(name), normalized_stop_name($9)),
+                    0.0
+                )
+                + $16 * COALESCE((platform_code = $14)::int, 0)
+                + $17 * COALESCE((parent_id = $15)::int, 0) DESC,
+            stops.id,
+            stops.origin
+        LIMIT $13;
         ",
     )
     .bind(EARTH_RADIUS_KM)
@@ -466,6 +1067,13 @@ where
     .bind(rad)
     .bind(stop.name.clone().unwrap_or("".to_owned()))
     .bind(excluded_origin.raw_ref::<str>())
+    .bind(model::stop::GEO_WEIGHT)
+    .bind(model::stop::NAME_WEIGHT)
+    .bind(limit.map(|limit| limit.min(i64::MAX as usize) as i64))
+    .bind(stop.platform_code.as_deref())
+    .bind(stop.parent_id.as_ref().map(|id| id.raw_ref::<str>()))
+    .bind(model::stop::PLATFORM_WEIGHT)
+    .bind(model::stop::PARENT_WEIGHT)
     .fetch_all(executor)
     .await
     .map_err(convert_error)?
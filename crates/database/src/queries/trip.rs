@@ -1,23 +1,28 @@
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime};
 use model::{
     origin::{Origin, OriginalIdMapping},
     stop::Stop,
-    trip::{StopTime, Trip},
+    trip::{CouplingKind, Frequency, StopTime, Trip, TripCoupling},
+    trip_instance::WindowMode,
     DatabaseEntry, WithId, WithOrigin,
 };
 use public_transport::database::Result;
 use utility::{
     id::{Id, IdWrapper},
     let_also::LetAlso,
+    normalize::non_blank,
 };
 
 use crate::data_model::{
-    trip::{StopTimeRow, TripRow},
+    trip::{
+        FrequencyRow, RowAreaKind, RowCouplingKind, RowPickupDropOffType,
+        RowTripDirection, StopTimeRow, TripCouplingRow, TripRow,
+    },
     with_origin_and_id, with_origins, with_origins_and_ids,
 };
 use sqlx::{Executor, Postgres};
 
-use super::convert_error;
+use super::{convert_error, escape_like};
 
 // Repo
 
@@ -28,7 +33,8 @@ where
     sqlx::query_as(
         "
         SELECT
-            id, origin, line_id, service_id, headsign, short_name
+            id, origin, line_id, service_id, headsign, short_name, direction,
+            shape_id, updated_at
         FROM
             trips
         WHERE
@@ -51,9 +57,12 @@ where
     sqlx::query_as(
         "
         SELECT
-            id, origin, line_id, service_id, headsign, short_name
+            id, origin, line_id, service_id, headsign, short_name, direction,
+            shape_id, updated_at
         FROM
-            trips;
+            trips
+        ORDER BY
+            id, origin;
         ",
     )
     .fetch_all(executor)
@@ -64,6 +73,194 @@ where
     })
 }
 
+pub async fn get_many<'c, E>(
+    executor: E,
+    ids: &[Id<Trip>],
+) -> Result<Vec<DatabaseEntry<Trip>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        SELECT
+            id, origin, line_id, service_id, headsign, short_name, direction,
+            shape_id, updated_at
+        FROM
+            trips
+        WHERE
+            id = ANY($1);
+        ",
+    )
+    .bind(ids.iter().map(|id| id.raw()).collect::<Vec<_>>())
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .let_owned(|trips: Vec<TripRow>| {
+        Ok(DatabaseEntry::gather_many(with_origins_and_ids(trips)))
+    })
+}
+
+pub async fn get_page_after<'c, E>(
+    executor: E,
+    after: Option<Id<Trip>>,
+    limit: usize,
+    origins: &[Id<Origin>],
+) -> Result<Vec<DatabaseEntry<Trip>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        WITH page AS (
+            SELECT DISTINCT
+                id
+            FROM
+                trips
+            WHERE
+                origin = ANY($3)
+                AND ($1::text IS NULL OR id > $1)
+            ORDER BY
+                id
+            LIMIT $2
+        )
+        SELECT
+            t.id, t.origin, t.line_id, t.service_id, t.headsign, t.short_name,
+            t.direction, t.shape_id, t.updated_at
+        FROM
+            page p
+            JOIN trips t ON t.id = p.id
+        WHERE
+            t.origin = ANY($3)
+        ORDER BY
+            p.id;
+        ",
+    )
+    .bind(after.map(|id| id.raw()))
+    .bind(limit as i64)
+    .bind(
+        origins
+            .iter()
+            .map(|origin| origin.raw())
+            .collect::<Vec<_>>(),
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .let_owned(|trips: Vec<TripRow>| {
+        Ok(DatabaseEntry::gather_many(with_origins_and_ids(trips)))
+    })
+}
+
+/// At most `limit` trips, whose headsign, short name or line name followed by
+/// the short name, e.g. `RE 7 21034`, contain the pattern, and which operate on
+/// the given service day or the next one. Trips, whose line name or short name
+/// equals the pattern, come first.
+pub async fn search_text<'c, E, S>(
+    executor: E,
+    pattern: S,
+    service_day: NaiveDate,
+    limit: usize,
+) -> Result<Vec<DatabaseEntry<Trip>>>
+where
+    E: Executor<'c, Database = Postgres>,
+    S: Into<String> + Send,
+{
+    let pattern: String = pattern.into();
+    let prefix_postfix_pattern = format!("%{}%", escape_like(&pattern));
+    // a pattern matching the line name followed by the short name contains a
+    // word of the line name, e.g. `7` of `RE 7` in `7 21034`, so that only trips
+    // of these lines are compared, instead of all trips.
+    let word_patterns = pattern
+        .split_whitespace()
+        .map(|word| format!("%{}%", escape_like(word)))
+        .collect::<Vec<_>>();
+    sqlx::query_as(
+        "
+        WITH candidates AS (
+            SELECT id FROM trips WHERE headsign ILIKE $2 OR short_name ILIKE $2
+            UNION
+            SELECT
+                t.id
+            FROM
+                lines l
+                JOIN trips t ON t.line_id = l.id AND t.origin = l.origin
+            WHERE
+                l.name ILIKE ANY($5)
+                AND concat_ws(' ', l.name, t.short_name) ILIKE $2
+        ),
+        matches AS (
+            SELECT
+                t.id,
+                MIN(CASE
+                    WHEN lower(l.name) = lower($1)
+                        OR lower(t.short_name) = lower($1) THEN 1
+                    ELSE 2
+                END) AS rank
+            FROM
+                candidates
+                JOIN trips t ON t.id = candidates.id
+                JOIN lines l ON l.id = t.line_id AND l.origin = t.origin
+                CROSS JOIN (VALUES ($3::date), ($3::date + 1)) AS d(day)
+            WHERE
+                (
+                    EXISTS (
+                        SELECT 1 FROM calendar_dates cd
+                        WHERE cd.service_id = t.service_id
+                          AND cd.date = d.day
+                          AND cd.exception_type = 'added'
+                    )
+                    OR (
+                        EXISTS (
+                            SELECT 1 FROM calendar_windows c
+                            WHERE c.service_id = t.service_id
+                              AND d.day BETWEEN c.start_date AND c.end_date
+                              AND (CASE EXTRACT(ISODOW FROM d.day)
+                                       WHEN 1 THEN c.monday
+                                       WHEN 2 THEN c.tuesday
+                                       WHEN 3 THEN c.wednesday
+                                       WHEN 4 THEN c.thursday
+                                       WHEN 5 THEN c.friday
+                                       WHEN 6 THEN c.saturday
+                                       ELSE c.sunday
+                                   END) = 'available'
+                        )
+                        AND NOT EXISTS (
+                            SELECT 1 FROM calendar_dates cd
+                            WHERE cd.service_id = t.service_id
+                              AND cd.date = d.day
+                              AND cd.exception_type = 'removed'
+                        )
+                    )
+                )
+            GROUP BY
+                t.id
+            ORDER BY
+                rank, t.id
+            LIMIT $4
+        )
+        SELECT
+            t.id, t.origin, t.line_id, t.service_id, t.headsign, t.short_name,
+            t.direction, t.shape_id, t.updated_at
+        FROM
+            matches m
+            JOIN trips t ON t.id = m.id
+        ORDER BY
+            m.rank, m.id;
+        ",
+    )
+    .bind(pattern)
+    .bind(prefix_postfix_pattern)
+    .bind(service_day)
+    .bind(limit as i64)
+    .bind(word_patterns)
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .let_owned(|trips: Vec<TripRow>| {
+        Ok(DatabaseEntry::gather_many(with_origins_and_ids(trips)))
+    })
+}
+
 pub async fn insert<'c, E>(
     executor: E,
     line: WithOrigin<Trip>,
@@ -78,17 +275,21 @@ where
             line_id,
             service_id,
             headsign,
-            short_name
+            short_name,
+            direction,
+            shape_id
         )
-        VALUES ($1, $2, $3, $4, $5)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
         RETURNING *;
         ",
     )
     .bind(line.origin.raw())
     .bind(line.content.line_id.raw())
     .bind(line.content.service_id.raw())
-    .bind(line.content.headsign)
-    .bind(line.content.short_name)
+    .bind(non_blank(line.content.headsign))
+    .bind(non_blank(line.content.short_name))
+    .bind(line.content.direction.map(RowTripDirection::from_model))
+    .bind(line.content.shape_id.raw())
     .fetch_one(executor)
     .await
     .map(|row: TripRow| with_origin_and_id(row))
@@ -110,15 +311,19 @@ where
             line_id,
             service_id,
             headsign,
-            short_name
+            short_name,
+            direction,
+            shape_id
         )
-        VALUES ($1, $2, $3, $4, $5, $6)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
         ON CONFLICT (id, origin)
         DO UPDATE SET
             line_id = EXCLUDED.line_id,
             service_id = EXCLUDED.service_id,
             headsign = EXCLUDED.headsign,
-            short_name = EXCLUDED.short_name
+            short_name = EXCLUDED.short_name,
+            direction = EXCLUDED.direction,
+            shape_id = EXCLUDED.shape_id
         RETURNING *;
         ",
     )
@@ -126,8 +331,15 @@ where
     .bind(line.origin.raw())
     .bind(line.content.content.line_id.raw())
     .bind(line.content.content.service_id.raw())
-    .bind(line.content.content.headsign)
-    .bind(line.content.content.short_name)
+    .bind(non_blank(line.content.content.headsign))
+    .bind(non_blank(line.content.content.short_name))
+    .bind(
+        line.content
+            .content
+            .direction
+            .map(RowTripDirection::from_model),
+    )
+    .bind(line.content.content.shape_id.raw())
     .fetch_one(executor)
     .await
     .map_err(|why| convert_error(why))
@@ -226,15 +438,28 @@ where
             stop_id,
             arrival_time,
             departure_time,
-            stop_headsign
+            stop_headsign,
+            pickup_type,
+            drop_off_type,
+            area_id,
+            area_kind,
+            stop_name
+        )
+        VALUES (
+            $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11,
+            (SELECT name FROM stops WHERE id = $4 AND origin = $1)
         )
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
         ON CONFLICT (origin, trip_id, stop_sequence)
         DO UPDATE SET
             stop_id = EXCLUDED.stop_id,
             arrival_time = EXCLUDED.arrival_time,
             departure_time = EXCLUDED.departure_time,
-            stop_headsign = EXCLUDED.stop_headsign
+            stop_headsign = EXCLUDED.stop_headsign,
+            pickup_type = EXCLUDED.pickup_type,
+            drop_off_type = EXCLUDED.drop_off_type,
+            area_id = EXCLUDED.area_id,
+            area_kind = EXCLUDED.area_kind,
+            stop_name = EXCLUDED.stop_name
         RETURNING *;
         ",
     )
@@ -254,7 +479,32 @@ where
             .departure_time
             .map(|time| time.num_seconds()),
     )
-    .bind(stop_time.content.stop_headsign)
+    .bind(non_blank(stop_time.content.stop_headsign))
+    .bind(
+        stop_time
+            .content
+            .pickup_type
+            .map(RowPickupDropOffType::from_model),
+    )
+    .bind(
+        stop_time
+            .content
+            .drop_off_type
+            .map(RowPickupDropOffType::from_model),
+    )
+    .bind(
+        stop_time
+            .content
+            .area_reference
+            .as_ref()
+            .map(|area| area.id.clone()),
+    )
+    .bind(
+        stop_time
+            .content
+            .area_reference
+            .map(|area| RowAreaKind::from_model(area.kind)),
+    )
     .fetch_one(executor)
     .await
     .map_err(|why| convert_error(why))
@@ -263,6 +513,161 @@ where
     })
 }
 
+/// Upserts the stop times of the trip in a single statement. Stop times, that
+/// did not change, are not written, so that trips imported again without any
+/// modification are not logged as changed. If `replace` is set, other stop times
+/// of the origin are deleted. Returns the number of written stop times.
+pub async fn put_stop_times<'c, E>(
+    executor: E,
+    trip_id: &Id<Trip>,
+    origin: &Id<Origin>,
+    stop_times: &[StopTime],
+    replace: bool,
+) -> Result<u64>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query(
+        "
+        WITH new_stop_times AS (
+            SELECT * FROM UNNEST(
+                $3::int[],
+                $4::text[],
+                $5::bigint[],
+                $6::bigint[],
+                $7::text[],
+                $8::text[]::pickup_drop_off_type[],
+                $9::text[]::pickup_drop_off_type[],
+                $10::text[],
+                $11::text[]::area_kind[]
+            ) AS s(
+                stop_sequence, stop_id, arrival_time, departure_time,
+                stop_headsign, pickup_type, drop_off_type, area_id, area_kind
+            )
+        ), deleted AS (
+            DELETE FROM
+                stop_times st
+            WHERE
+                $12
+                AND st.origin = $1
+                AND st.trip_id = $2
+                AND st.stop_sequence <> ALL($3::int[])
+        )
+        INSERT INTO stop_times AS st(
+            origin, trip_id, stop_sequence, stop_id, arrival_time, departure_time,
+            stop_headsign, pickup_type, drop_off_type, area_id, area_kind,
+            stop_name
+        )
+        SELECT
+            $1, $2, n.stop_sequence, n.stop_id, n.arrival_time, n.departure_time,
+            n.stop_headsign, n.pickup_type, n.drop_off_type, n.area_id, n.area_kind,
+            s.name
+        FROM
+            new_stop_times n
+            LEFT JOIN stops s ON s.id = n.stop_id AND s.origin = $1
+        ON CONFLICT (origin, trip_id, stop_sequence)
+        DO UPDATE SET
+            stop_id = EXCLUDED.stop_id,
+            arrival_time = EXCLUDED.arrival_time,
+            departure_time = EXCLUDED.departure_time,
+            stop_headsign = EXCLUDED.stop_headsign,
+            pickup_type = EXCLUDED.pickup_type,
+            drop_off_type = EXCLUDED.drop_off_type,
+            area_id = EXCLUDED.area_id,
+            area_kind = EXCLUDED.area_kind,
+            stop_name = EXCLUDED.stop_name
+        WHERE
+            (
+                st.stop_id, st.arrival_time, st.departure_time, st.stop_headsign,
+                st.pickup_type, st.drop_off_type, st.area_id, st.area_kind
+            ) IS DISTINCT FROM (
+                EXCLUDED.stop_id, EXCLUDED.arrival_time, EXCLUDED.departure_time,
+                EXCLUDED.stop_headsign, EXCLUDED.pickup_type,
+                EXCLUDED.drop_off_type, EXCLUDED.area_id, EXCLUDED.area_kind
+            );
+        ",
+    )
+    .bind(origin.raw_ref::<str>())
+    .bind(trip_id.raw_ref::<str>())
+    .bind(
+        stop_times
+            .iter()
+            .map(|stop_time| stop_time.stop_sequence)
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        stop_times
+            .iter()
+            .map(|stop_time| stop_time.stop_id.as_ref().map(|id| id.raw()))
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        stop_times
+            .iter()
+            .map(|stop_time| stop_time.arrival_time.map(|time| time.num_seconds()))
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        stop_times
+            .iter()
+            .map(|stop_time| stop_time.departure_time.map(|time| time.num_seconds()))
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        stop_times
+            .iter()
+            .map(|stop_time| non_blank(stop_time.stop_headsign.clone()))
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        stop_times
+            .iter()
+            .map(|stop_time| {
+                stop_time
+                    .pickup_type
+                    .map(|kind| RowPickupDropOffType::from_model(kind).as_str())
+            })
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        stop_times
+            .iter()
+            .map(|stop_time| {
+                stop_time
+                    .drop_off_type
+                    .map(|kind| RowPickupDropOffType::from_model(kind).as_str())
+            })
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        stop_times
+            .iter()
+            .map(|stop_time| {
+                stop_time
+                    .area_reference
+                    .as_ref()
+                    .map(|area| area.id.clone())
+            })
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        stop_times
+            .iter()
+            .map(|stop_time| {
+                stop_time
+                    .area_reference
+                    .as_ref()
+                    .map(|area| RowAreaKind::from_model(area.kind).as_str())
+            })
+            .collect::<Vec<_>>(),
+    )
+    .bind(replace)
+    .execute(executor)
+    .await
+    .map(|result| result.rows_affected())
+    .map_err(convert_error)
+}
+
 pub async fn get_stop_times<'c, E>(
     executor: E,
     trip_id: Id<Trip>,
@@ -274,7 +679,8 @@ where
     sqlx::query_as(
         "
         SELECT
-            origin, trip_id, stop_sequence, stop_id, arrival_time, departure_time, stop_headsign
+            origin, trip_id, stop_sequence, stop_id, arrival_time, departure_time, stop_headsign,
+            pickup_type, drop_off_type, area_id, area_kind, stop_name
         FROM
             stop_times
         WHERE
@@ -292,24 +698,171 @@ where
     .let_owned(|result| Ok(result))
 }
 
+/// Sets the stop names of stop times of the stops changed since the last sync
+/// according to the change log, and moves the position of the sync to the latest
+/// change. Concurrent syncs wait for each other.
+pub async fn sync_stop_names<'c, E>(executor: E) -> Result<u64>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_scalar(
+        "
+        WITH sync AS (
+            SELECT synced_through FROM stop_name_sync FOR UPDATE
+        ),
+        changed AS (
+            SELECT DISTINCT c.entity_id, c.origin
+            FROM change_log c, sync
+            WHERE c.id > sync.synced_through AND c.entity = 'stop'
+        ),
+        synced AS (
+            UPDATE stop_times st
+            SET
+                stop_name = s.name
+            FROM
+                stops s
+            WHERE
+                (s.id, s.origin) IN (SELECT entity_id, origin FROM changed)
+                AND s.id = st.stop_id
+                AND s.origin = st.origin
+                AND st.stop_name IS DISTINCT FROM s.name
+            RETURNING 1
+        ),
+        advanced AS (
+            UPDATE stop_name_sync
+            SET
+                synced_through = latest.id
+            FROM
+                (SELECT MAX(id) AS id FROM change_log) latest
+            WHERE
+                latest.id > stop_name_sync.synced_through
+        )
+        SELECT COUNT(*) FROM synced;
+        ",
+    )
+    .fetch_one(executor)
+    .await
+    .map(|count: i64| count as u64)
+    .map_err(convert_error)
+}
+
+pub async fn put_frequency<'c, E>(
+    executor: E,
+    trip_id: &Id<Trip>,
+    origin: &Id<Origin>,
+    frequency: &Frequency,
+) -> Result<()>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query(
+        "
+        INSERT INTO frequencies(
+            origin, trip_id, start_time, end_time, headway_secs, exact_times
+        )
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (origin, trip_id, start_time)
+        DO UPDATE SET
+            end_time = EXCLUDED.end_time,
+            headway_secs = EXCLUDED.headway_secs,
+            exact_times = EXCLUDED.exact_times;
+        ",
+    )
+    .bind(origin.raw_ref::<str>())
+    .bind(trip_id.raw_ref::<str>())
+    .bind(frequency.start_time.num_seconds())
+    .bind(frequency.end_time.num_seconds())
+    .bind(frequency.headway.num_seconds() as i32)
+    .bind(frequency.exact_times)
+    .execute(executor)
+    .await
+    .map(|_| ())
+    .map_err(convert_error)
+}
+
+/// Periods of the given trips of all origins, ordered by trip and start time.
+pub async fn get_frequencies<'c, E>(
+    executor: E,
+    trip_ids: &[Id<Trip>],
+) -> Result<Vec<(Id<Trip>, WithOrigin<Frequency>)>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        SELECT
+            origin, trip_id, start_time, end_time, headway_secs, exact_times
+        FROM
+            frequencies
+        WHERE
+            trip_id = ANY($1)
+        ORDER BY
+            trip_id, start_time;
+        ",
+    )
+    .bind(trip_ids.raw_ref::<str>())
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .let_owned(|rows: Vec<FrequencyRow>| {
+        Ok(rows.into_iter().map(FrequencyRow::to_model).collect())
+    })
+}
+
+/// Deletes only stop times of the given origin, as other origins may provide
+/// stop times for the same trip.
 pub async fn delete_stop_times<'c, E>(
     executor: E,
     trip_id: Id<Trip>,
     origin: Id<Origin>,
-) -> Result<()>
+) -> Result<Vec<StopTime>>
 where
     E: Executor<'c, Database = Postgres>,
 {
-    sqlx::query(
+    sqlx::query_as(
         "
         DELETE FROM
             stop_times
         WHERE
-            trip_id = $1 AND origin = $2;
+            trip_id = $1 AND origin = $2
+        RETURNING
+            origin, trip_id, stop_sequence, stop_id, arrival_time, departure_time, stop_headsign,
+            pickup_type, drop_off_type, area_id, area_kind, stop_name;
         ",
     )
     .bind(trip_id.raw())
     .bind(origin.raw())
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .into_iter()
+    .map(|stop_time: StopTimeRow| stop_time.to_model())
+    .collect::<Vec<_>>()
+    .let_owned(|mut result| {
+        result.sort_by_key(|stop_time| stop_time.stop_sequence);
+        Ok(result)
+    })
+}
+
+pub async fn delete<'c, E>(
+    executor: E,
+    id: &Id<Trip>,
+    origin: &Id<Origin>,
+) -> Result<()>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    // stop times and original ids are deleted by the database (`ON DELETE CASCADE`).
+    sqlx::query(
+        "
+        DELETE FROM
+            trips
+        WHERE
+            id = $1 AND origin = $2;
+        ",
+    )
+    .bind(id.raw())
+    .bind(origin.raw())
     .execute(executor)
     .await
     .map_err(convert_error)?;
@@ -321,21 +874,45 @@ pub async fn get_all_via_stop<'c, E>(
     stops: &[&Id<Stop>],
     start: DateTime<Local>,
     end: DateTime<Local>,
+    mode: WindowMode,
 ) -> Result<Vec<DatabaseEntry<Trip>>>
 where
     E: Executor<'c, Database = Postgres>,
 {
+    let first_day = start.date_naive() - Duration::days(1);
+    let last_day = end.date_naive();
+    // Stop times are stored relative to the start of the service day. Bound them
+    // conservatively, such that every service day between `first_day` and
+    // `last_day` is covered. One hour is added to both sides to account for
+    // daylight saving time changes.
+    let min_time = (start.naive_local() - last_day.and_time(NaiveTime::MIN))
+        .num_seconds()
+        - 3600;
+    let max_time =
+        (end.naive_local() - first_day.and_time(NaiveTime::MIN)).num_seconds() + 3600;
     // TODO: diese query optimieren!
     sqlx::query_as(
         "
         SELECT DISTINCT
-            t.id, t.origin, t.line_id, t.service_id, t.headsign, t.short_name
+            t.id, t.origin, t.line_id, t.service_id, t.headsign, t.short_name,
+            t.direction, t.shape_id, t.updated_at
         FROM
             trips t
             JOIN stop_times st ON t.id = st.trip_id
             JOIN stops s ON st.stop_id = s.id
             LEFT JOIN calendar_windows c ON t.service_id = c.service_id
         WHERE s.id = ANY($1)
+          AND ((CASE WHEN $4
+                    THEN COALESCE(st.arrival_time, st.departure_time)
+                    ELSE COALESCE(st.departure_time, st.arrival_time)
+                END) BETWEEN $5 AND $6
+               -- stop times of frequency based trips are shifted by each
+               -- departure, which can not reach a stop before it starts.
+               OR EXISTS (
+                   SELECT 1 FROM frequencies f
+                   WHERE f.trip_id = t.id
+                     AND f.origin = t.origin
+                     AND f.start_time <= $6))
           AND ((c.start_date <= $2::date AND c.end_date >= $3::date)
                OR EXISTS (
                    SELECT 1 FROM calendar_dates cd
@@ -345,8 +922,11 @@ where
         ",
     )
     .bind(stops.raw_ref::<str>())
-    .bind(start.date_naive())
-    .bind(end.date_naive())
+    .bind(first_day)
+    .bind(last_day)
+    .bind(mode == WindowMode::ArriveBetween)
+    .bind(min_time)
+    .bind(max_time)
     .fetch_all(executor)
     .await
     .map_err(|why| convert_error(why))?
@@ -388,3 +968,108 @@ where
     .map_err(convert_error)?
     .let_owned(|stops: Vec<TripRow>| Ok(with_origins_and_ids(stops)))
 }
+
+// Couplings
+
+/// Replaces the couplings of the trip at the stop. Couplings, that did not
+/// change, are kept.
+pub async fn put_trip_couplings<'c, E>(
+    executor: E,
+    trip_id: &Id<Trip>,
+    origin: &Id<Origin>,
+    stop_sequence: i32,
+    stop_id: Option<&Id<Stop>>,
+    couplings: &[(String, CouplingKind)],
+) -> Result<()>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query(
+        "
+        WITH new_couplings(coupled_original_id, kind) AS (
+            SELECT * FROM UNNEST($5::text[], $6::text[]::coupling_kind[])
+        ), deleted AS (
+            DELETE FROM
+                trip_couplings c
+            WHERE
+                c.origin = $1
+                AND c.trip_id = $2
+                AND c.stop_sequence = $3
+                AND c.coupled_original_id <> ALL($5::text[])
+        )
+        INSERT INTO trip_couplings(
+            origin, trip_id, stop_sequence, stop_id, coupled_original_id, kind
+        )
+        SELECT $1, $2, $3, $4, coupled_original_id, kind FROM new_couplings
+        ON CONFLICT (origin, trip_id, stop_sequence, coupled_original_id)
+        DO UPDATE SET
+            stop_id = EXCLUDED.stop_id,
+            kind = EXCLUDED.kind;
+        ",
+    )
+    .bind(origin.raw_ref::<str>())
+    .bind(trip_id.raw_ref::<str>())
+    .bind(stop_sequence)
+    .bind(stop_id.map(|id| id.raw_ref::<str>()))
+    .bind(
+        couplings
+            .iter()
+            .map(|(original_id, _)| original_id.clone())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        couplings
+            .iter()
+            .map(|(_, kind)| RowCouplingKind::from_model(*kind).as_str())
+            .collect::<Vec<_>>(),
+    )
+    .execute(executor)
+    .await
+    .map(|_| ())
+    .map_err(convert_error)
+}
+
+/// The coupled trip is resolved by its original id. Its headsign falls back to
+/// the name of its last stop, as not all origins provide headsigns.
+pub async fn get_trip_couplings<'c, E>(
+    executor: E,
+    trip_ids: &[Id<Trip>],
+) -> Result<Vec<WithOrigin<TripCoupling>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        SELECT
+            c.origin, c.trip_id, c.stop_sequence, c.stop_id, c.kind,
+            o.id AS coupled_trip_id,
+            COALESCE(
+                t.headsign,
+                (
+                    SELECT s.name
+                    FROM stop_times st
+                    JOIN stops s ON s.id = st.stop_id AND s.origin = st.origin
+                    WHERE st.trip_id = o.id AND st.origin = o.origin
+                    ORDER BY st.stop_sequence DESC
+                    LIMIT 1
+                )
+            ) AS coupled_headsign
+        FROM
+            trip_couplings c
+            JOIN trips_original_ids o
+                ON o.origin = c.origin AND o.original_id = c.coupled_original_id
+            LEFT JOIN trips t ON t.id = o.id AND t.origin = o.origin
+        WHERE
+            c.trip_id = ANY($1)
+        ORDER BY
+            c.trip_id, c.stop_sequence, o.id, c.origin;
+        ",
+    )
+    .bind(trip_ids.raw_ref::<str>())
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .let_owned(|rows: Vec<TripCouplingRow>| {
+        Ok(rows.into_iter().map(TripCouplingRow::to_model).collect())
+    })
+}
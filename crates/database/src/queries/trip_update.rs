@@ -1,19 +1,19 @@
+use std::collections::HashSet;
+
 use chrono::{DateTime, Duration, Local, NaiveDate};
 use model::{
     origin::Origin,
+    stop::Stop,
     trip::Trip,
-    trip_update::{TripUpdate, TripUpdateId},
+    trip_update::{HistoricDelay, TripUpdate, TripUpdateId},
     DatabaseEntry, DateTimeRange, WithId, WithOrigin,
 };
 use public_transport::database::Result;
 use sqlx::{types::Json, Executor, Postgres};
-use utility::{
-    id::{Id, IdWrapper as _},
-    let_also::LetAlso,
-};
+use utility::{id::Id, let_also::LetAlso, normalize::non_blank};
 
 use crate::data_model::{
-    trip_update::{TripStatus, TripUpdateRow},
+    trip_update::{HistoricDelayRow, TripStatus, TripUpdateRow},
     with_origins, with_origins_and_ids, DatabaseRow as _,
 };
 
@@ -23,6 +23,7 @@ pub async fn get<'c, E>(
     executor: E,
     trip_id: &Id<Trip>,
     trip_start_date: NaiveDate,
+    instance: u32,
 ) -> Result<DatabaseEntry<TripUpdate>>
 where
     E: Executor<'c, Database = Postgres>,
@@ -30,50 +31,114 @@ where
     sqlx::query_as(
         "
         SELECT
-            origin, trip_id, trip_start_date, status, stop_time_updates, timestamp
+            origin, trip_id, trip_start_date, instance, status, stop_time_updates,
+            timestamp
         FROM
             trip_updates
         WHERE
-            trip_id = $1;
+            trip_id = $1
+            AND trip_start_date = $2
+            AND instance = $3;
         ",
     )
     .bind(trip_id.raw_ref::<str>())
+    .bind(trip_start_date)
+    .bind(instance as i32)
     .fetch_all(executor)
     .await
-    .map_err(|why| convert_error(why))?
+    .map_err(convert_error)?
     .let_owned(|updates: Vec<TripUpdateRow>| {
         Ok(DatabaseEntry::gather(
-            Id::new(TripUpdateId::new(trip_id.clone(), trip_start_date)),
+            Id::new(TripUpdateId::of_instance(
+                trip_id.clone(),
+                trip_start_date,
+                instance,
+            )),
             with_origins(updates),
         ))
     })
 }
 
-pub async fn get_for_trips_in_range<'c, E>(
+/// Maximum number of trip ids sent within a single query.
+pub const TRIP_IDS_PER_QUERY: usize = 2000;
+
+/// Callers should split `ids` into chunks of at most `TRIP_IDS_PER_QUERY`.
+pub async fn get_for_trip_instances<'c, E>(
     executor: E,
-    trip_ids: &[Id<Trip>],
-    range: DateTimeRange<Local>,
+    ids: &[Id<TripUpdate>],
 ) -> Result<Vec<DatabaseEntry<TripUpdate>>>
 where
     E: Executor<'c, Database = Postgres>,
 {
+    let mut trip_ids = Vec::with_capacity(ids.len());
+    let mut trip_start_dates = Vec::with_capacity(ids.len());
+    let mut instances = Vec::with_capacity(ids.len());
+    for id in ids {
+        let id = id.raw();
+        trip_ids.push(id.trip_id.raw());
+        trip_start_dates.push(id.trip_start_date);
+        instances.push(id.instance as i32);
+    }
     sqlx::query_as(
         "
         SELECT
-            origin, trip_id, trip_start_date, status, stop_time_updates, timestamp
+            origin, trip_id, trip_start_date, instance, status, stop_time_updates,
+            timestamp
         FROM
             trip_updates
         WHERE
-            trip_id = ANY($1)
-            AND (trip_start_date BETWEEN $2::date AND $3::date);
+            (trip_id, trip_start_date, instance) IN (
+                SELECT * FROM UNNEST($1::text[], $2::date[], $3::integer[])
+            )
+            -- allows skipping partitions of other months.
+            AND trip_start_date = ANY($2::date[]);
+        ",
+    )
+    .bind(trip_ids)
+    .bind(trip_start_dates)
+    .bind(instances)
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .let_owned(|updates: Vec<TripUpdateRow>| {
+        Ok(DatabaseEntry::gather_many(with_origins_and_ids(updates)))
+    })
+}
+
+/// Updates for trips starting in the specified range, with at least one stop time
+/// update at the specified stop or one of its children, e.g., its platforms.
+pub async fn get_for_stop_in_range<'c, E>(
+    executor: E,
+    stop_id: &Id<Stop>,
+    range: DateTimeRange<Local>,
+) -> Result<Vec<DatabaseEntry<TripUpdate>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        WITH stop_ids AS (
+            SELECT $1::text AS id
+            UNION
+            SELECT id FROM stops WHERE parent_id = $1
+        )
+        SELECT DISTINCT ON (origin, trip_id, trip_start_date, instance)
+            origin, trip_id, trip_start_date, instance, status, stop_time_updates,
+            timestamp
+        FROM
+            stop_ids
+            JOIN trip_updates ON stop_time_updates
+                @> jsonb_build_array(jsonb_build_object('stopId', stop_ids.id))
+        WHERE
+            trip_start_date BETWEEN $2::date AND $3::date;
         ",
     )
-    .bind(trip_ids.raw_ref::<str>())
+    .bind(stop_id.raw_ref::<str>())
     .bind(range.first - Duration::days(1))
     .bind(range.last)
     .fetch_all(executor)
     .await
-    .map_err(|why| convert_error(why))?
+    .map_err(convert_error)?
     .let_owned(|updates: Vec<TripUpdateRow>| {
         Ok(DatabaseEntry::gather_many(with_origins_and_ids(updates)))
     })
@@ -82,8 +147,7 @@ where
 pub async fn get_timestamp<'c, E>(
     executor: E,
     origin: &Id<Origin>,
-    trip_id: &Id<Trip>,
-    trip_start_date: NaiveDate,
+    id: &TripUpdateId,
 ) -> Result<Option<DateTime<Local>>>
 where
     E: Executor<'c, Database = Postgres>,
@@ -98,15 +162,72 @@ where
             origin = $1
             AND trip_id = $2
             AND trip_start_date = $3
+            AND instance = $4
         ",
     )
     .bind(origin.raw_ref::<str>())
-    .bind(trip_id.raw_ref::<str>())
-    .bind(trip_start_date)
+    .bind(id.trip_id.raw_ref::<str>())
+    .bind(id.trip_start_date)
+    .bind(id.instance as i32)
     .fetch_optional(executor)
     .await
     .map(|result: Option<Option<DateTime<Local>>>| result.and_then(|x| x))
-    .map_err(|why| convert_error(why))
+    .map_err(convert_error)
+}
+
+/// Timestamp of the latest update of each origin with updates since yesterday.
+pub async fn latest_timestamps<'c, E>(
+    executor: E,
+) -> Result<Vec<(Id<Origin>, DateTime<Local>)>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        SELECT
+            origin, timestamp
+        FROM
+            latest_trip_updates
+        WHERE
+            timestamp >= CURRENT_DATE - 1;
+        ",
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .into_iter()
+    .map(|(origin, timestamp): (String, DateTime<Local>)| {
+        (Id::new(origin), timestamp)
+    })
+    .collect::<Vec<_>>()
+    .let_owned(Ok)
+}
+
+/// Keeps the timestamp of the latest update of the origin, unless it is older
+/// than the stored one.
+pub async fn put_latest_timestamp<'c, E>(
+    executor: E,
+    origin: &Id<Origin>,
+    timestamp: DateTime<Local>,
+) -> Result<()>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query(
+        "
+        INSERT INTO latest_trip_updates(origin, timestamp)
+        VALUES ($1, $2)
+        ON CONFLICT (origin) DO UPDATE
+        SET
+            timestamp = GREATEST(latest_trip_updates.timestamp, EXCLUDED.timestamp);
+        ",
+    )
+    .bind(origin.raw())
+    .bind(timestamp)
+    .execute(executor)
+    .await
+    .map(|_| ())
+    .map_err(convert_error)
 }
 
 pub async fn put_all<'c, E>(
@@ -124,6 +245,7 @@ where
             "origin",
             "trip_id",
             "trip_start_date",
+            "instance",
             "status",
             "stop_time_updates",
             "timestamp",
@@ -134,11 +256,12 @@ where
                 .bind(origin.raw())
                 .bind(update.id.raw().trip_id.raw())
                 .bind(update.id.raw().trip_start_date)
+                .bind(update.id.raw().instance as i32)
                 .bind(TripStatus::from(update.content.status.clone()))
                 .bind(Json(update.content.stops.clone()))
                 .bind(update.content.timestamp.clone())
         },
-        &["origin", "trip_id", "trip_start_date"],
+        &["origin", "trip_id", "trip_start_date", "instance"],
     )
     .await
     .map(|results: Vec<TripUpdateRow>| {
@@ -146,17 +269,139 @@ where
             origin.clone(),
             results
                 .into_iter()
-                .map(|update| {
-                    WithId::new(
-                        Id::new(TripUpdateId::new(
-                            Id::new(update.trip_id.clone()),
-                            update.trip_start_date,
-                        )),
-                        update.to_model(),
-                    )
-                })
+                .map(|update| WithId::new(update.get_id(), update.to_model()))
                 .collect(),
         )
     })
     .map_err(convert_error)
 }
+
+// Historic delays
+
+/// Delays, which are already stored, are updated.
+///
+/// ## Warning
+///
+/// Every delay has to be unique by its stop sequence and timestamp.
+pub async fn put_historic_delays<'c, E>(
+    executor: E,
+    origin: &Id<Origin>,
+    trip_id: &Id<Trip>,
+    trip_start_date: NaiveDate,
+    delays: &[HistoricDelay],
+) -> Result<()>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    if delays.is_empty() {
+        return Ok(());
+    }
+    let delays = last_of_each_announcement(delays);
+    super::insert_all_returning(
+        executor,
+        "historic_delays",
+        &[
+            "origin",
+            "trip_id",
+            "trip_start_date",
+            "scheduled_stop_sequence",
+            "stop_id",
+            "timestamp",
+            "arrival_time",
+            "departure_time",
+            "cause",
+            "source",
+        ],
+        &delays,
+        |query, delay| {
+            query
+                .bind(origin.raw())
+                .bind(trip_id.raw())
+                .bind(trip_start_date)
+                .bind(delay.scheduled_stop_sequence)
+                .bind(delay.stop_id.as_ref().map(|id| id.raw()))
+                .bind(delay.timestamp)
+                .bind(delay.arrival_time)
+                .bind(delay.departure_time)
+                .bind(non_blank(delay.cause.clone()))
+                .bind(non_blank(delay.source.clone()))
+        },
+        &[
+            "origin",
+            "trip_id",
+            "trip_start_date",
+            "scheduled_stop_sequence",
+            "timestamp",
+        ],
+    )
+    .await
+    .map(|_: Vec<HistoricDelayRow>| ())
+    .map_err(convert_error)
+}
+
+/// The last of the delays announced for the same stop at the same time. A
+/// statement must not upsert a row twice and timestamps are stored with
+/// microsecond precision, so delays a few nanoseconds apart are one row.
+fn last_of_each_announcement(delays: &[HistoricDelay]) -> Vec<&HistoricDelay> {
+    let mut seen = HashSet::new();
+    let mut unique = delays
+        .iter()
+        .rev()
+        .filter(|delay| {
+            seen.insert((
+                delay.scheduled_stop_sequence,
+                delay.timestamp.timestamp_micros(),
+            ))
+        })
+        .collect::<Vec<_>>();
+    unique.reverse();
+    unique
+}
+
+/// Delays of all origins ordered by stop sequence and time of announcement.
+pub async fn get_historic_delays<'c, E>(
+    executor: E,
+    trip_id: &Id<Trip>,
+    trip_start_date: NaiveDate,
+) -> Result<Vec<WithOrigin<HistoricDelay>>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as(
+        "
+        SELECT
+            origin, scheduled_stop_sequence, stop_id, timestamp,
+            arrival_time, departure_time, cause, source
+        FROM
+            historic_delays
+        WHERE
+            trip_id = $1
+            AND trip_start_date = $2
+        ORDER BY
+            scheduled_stop_sequence, timestamp;
+        ",
+    )
+    .bind(trip_id.raw_ref::<str>())
+    .bind(trip_start_date)
+    .fetch_all(executor)
+    .await
+    .map_err(convert_error)?
+    .let_owned(|rows: Vec<HistoricDelayRow>| {
+        Ok(rows.into_iter().map(HistoricDelayRow::to_model).collect())
+    })
+}
+
+pub async fn delete_historic_delays_before<'c, E>(
+    executor: E,
+    date: NaiveDate,
+) -> Result<u64>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query("DELETE FROM historic_delays WHERE trip_start_date < $1;")
+        .bind(date)
+        .execute(executor)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(convert_error)
+}
@@ -0,0 +1,95 @@
+use std::{
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use sqlx::{postgres::PgPoolOptions, PgPool};
+
+/// Replication lag, from which reads are served by the primary instead.
+pub const MAX_REPLICA_LAG: Duration = Duration::from_secs(30);
+
+/// Interval, in which the replication lag is checked.
+const LAG_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy)]
+pub struct ReplicaStatus {
+    /// Replication lag of the last check, or `None` if the check failed.
+    pub lag: Option<Duration>,
+    pub serves_reads: bool,
+}
+
+#[derive(Clone)]
+pub(crate) struct Replica {
+    pub(crate) pool: PgPool,
+    /// Replication lag of the last check in milliseconds, negative if the check
+    /// failed.
+    lag_millis: Arc<AtomicI64>,
+}
+
+impl Replica {
+    /// Connects to the replica and periodically checks its replication lag in the
+    /// background.
+    pub(crate) async fn connect(
+        options: PgPoolOptions,
+        url: &str,
+    ) -> Result<Self, sqlx::Error> {
+        let replica = Self {
+            pool: options.connect(url).await?,
+            lag_millis: Arc::new(AtomicI64::new(-1)),
+        };
+        replica.check_lag().await;
+        let monitor = replica.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(LAG_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                monitor.check_lag().await;
+            }
+        });
+        Ok(replica)
+    }
+
+    async fn check_lag(&self) {
+        let lag_millis = match measure_lag(&self.pool).await {
+            Ok(Some(lag)) => lag.as_millis() as i64,
+            Ok(None) => -1,
+            Err(why) => {
+                log::error!("checking replication lag failed: {:?}", why);
+                -1
+            }
+        };
+        self.lag_millis.store(lag_millis, Ordering::Relaxed);
+    }
+
+    pub(crate) fn status(&self) -> ReplicaStatus {
+        let lag_millis = self.lag_millis.load(Ordering::Relaxed);
+        let lag = (lag_millis >= 0).then(|| Duration::from_millis(lag_millis as u64));
+        ReplicaStatus {
+            lag,
+            serves_reads: matches!(lag, Some(lag) if lag <= MAX_REPLICA_LAG),
+        }
+    }
+}
+
+/// Returns `None` if the lag is unknown, e.g. because no transaction was replayed
+/// yet.
+async fn measure_lag(pool: &PgPool) -> Result<Option<Duration>, sqlx::Error> {
+    // a replica, which replayed everything it received, is up to date, even if
+    // the last replayed transaction is old. on a primary, there is no lag at all.
+    let lag: Option<f64> = sqlx::query_scalar(
+        "
+        SELECT
+            CASE
+                WHEN NOT pg_is_in_recovery() THEN 0
+                WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0
+                ELSE EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp())
+            END::float8;
+        ",
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(lag.map(|lag| Duration::from_secs_f64(lag.max(0.0))))
+}
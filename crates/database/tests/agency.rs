@@ -0,0 +1,196 @@
+//! Stops and coverage of an agency with two lines sharing stops. They are read by
+//! the client, so the entries are committed, their ids are specific to this test.
+//! The stops lie in Eckernförde, away from the stops of other tests.
+
+mod common;
+
+use model::{
+    agency::Agency,
+    fixtures::StopBuilder,
+    line::{Line, LineType},
+    stop::Stop,
+    trip::{StopTime, Trip},
+    WithId, WithOrigin,
+};
+use public_transport::{
+    database::{Database, DatabaseTransaction, Repo, TripRepo},
+    server::Server,
+};
+use serde::Serialize;
+use utility::id::{HasId, Id};
+
+const ORIGIN: &str = "test-agency-coverage";
+
+fn id<T: HasId<IdType = String>>(name: &str) -> Id<T> {
+    Id::new(format!("{}-{}", ORIGIN, name))
+}
+
+fn with_id<T>(name: &str, content: T) -> WithOrigin<WithId<T>>
+where
+    T: Serialize + HasId<IdType = String>,
+{
+    WithOrigin::new(Id::new(ORIGIN.to_owned()), WithId::new(id(name), content))
+}
+
+fn line(name: &str) -> Line {
+    Line {
+        name: Some(name.to_owned()),
+        kind: LineType::Bus,
+        agency_id: Some(id("kvg")),
+        secondary_agency_ids: vec![],
+        updated_at: None,
+    }
+}
+
+fn trip(line: &str) -> Trip {
+    Trip {
+        line_id: id(line),
+        service_id: None,
+        headsign: None,
+        short_name: None,
+        direction: None,
+        shape_id: None,
+        stops: vec![],
+        frequencies: vec![],
+        updated_at: None,
+    }
+}
+
+fn stop_times(stops: &[&str]) -> Vec<StopTime> {
+    stops
+        .iter()
+        .enumerate()
+        .map(|(sequence, stop)| StopTime {
+            stop_sequence: sequence as i32,
+            stop_id: Some(id(stop)),
+            arrival_time: None,
+            departure_time: None,
+            stop_headsign: None,
+            pickup_type: None,
+            drop_off_type: None,
+            area_reference: None,
+            stop_name: None,
+        })
+        .collect()
+}
+
+/// Calls of the trip of the extension, replacing those of previous runs.
+async fn put_extension<D: Database>(database: &D, stops: &[&str]) {
+    let mut tx = database.transaction().await.expect("transaction begins");
+    tx.put_stop_times(
+        &id("extension"),
+        &Id::new(ORIGIN.to_owned()),
+        &stop_times(stops),
+        true,
+    )
+    .await
+    .expect("stop times are stored");
+    tx.commit().await.expect("transaction is committed");
+}
+
+#[tokio::test]
+async fn stops_of_lines_sharing_stops_are_listed_once() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let origins = [Id::new(ORIGIN.to_owned())];
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    tx.put(with_id(
+        "kvg",
+        Agency {
+            name: "Kieler Verkehrsgesellschaft".to_owned(),
+            website: String::new(),
+            phone_number: None,
+            email: None,
+            fare_url: None,
+        },
+    ))
+    .await
+    .expect("agency is stored");
+    // the market is served by both lines, the harbour only by the extension later
+    // on, which moves it into the hull instead of the market.
+    for (name, latitude, longitude) in [
+        ("station", 54.4700, 9.8350),
+        ("market", 54.4780, 9.8422),
+        ("school", 54.4936, 9.8256),
+        ("harbour", 54.4750, 9.8632),
+    ] {
+        let stop = StopBuilder::new(name).at(latitude, longitude).build();
+        tx.put(with_id(name, stop)).await.expect("stop is stored");
+    }
+    for name in ["11", "12"] {
+        tx.put(with_id(name, line(name)))
+            .await
+            .expect("line is stored");
+    }
+    for (name, line, stops) in [
+        ("11-0", "11", vec!["station", "market"]),
+        ("12-0", "12", vec!["market", "school"]),
+        ("12-1", "12", vec!["school", "market"]),
+        ("extension", "12", vec![]),
+    ] {
+        tx.put(with_id(name, trip(line)))
+            .await
+            .expect("trip is stored");
+        tx.put_stop_times(&id(name), &origins[0], &stop_times(&stops), true)
+            .await
+            .expect("stop times are stored");
+    }
+    tx.commit().await.expect("transaction is committed");
+
+    let client = Server::new(database.clone()).client(ORIGIN);
+    let agency_id = id("kvg");
+    let mut pages = vec![];
+    let mut after = None;
+    loop {
+        let (stops, next) = client
+            .get_agency_stops_page(&agency_id, false, after, 2, &origins)
+            .await
+            .expect("stops are read");
+        pages.push(
+            stops
+                .into_iter()
+                .map(|stop| stop.id.raw())
+                .collect::<Vec<_>>(),
+        );
+        after = next;
+        if after.is_none() {
+            break;
+        }
+    }
+    let stop = |name| id::<Stop>(name).raw();
+    assert_eq!(
+        pages,
+        [vec![stop("market"), stop("school")], vec![stop("station")]]
+    );
+
+    // coverages are cached until the next schedule import.
+    client.schedule_imported();
+    let coverage = client
+        .get_agency_coverage(&agency_id, false, &origins)
+        .await
+        .expect("coverage is computed");
+    assert_eq!(coverage.stops.len(), 3);
+    assert_eq!(
+        coverage.hull,
+        [(54.4936, 9.8256), (54.4700, 9.8350), (54.4780, 9.8422)]
+    );
+    put_extension(&database, &["market", "harbour"]).await;
+    let cached = client
+        .get_agency_coverage(&agency_id, false, &origins)
+        .await
+        .expect("coverage is read");
+    assert_eq!(cached.hull, coverage.hull);
+    client.schedule_imported();
+    let coverage = client
+        .get_agency_coverage(&agency_id, false, &origins)
+        .await
+        .expect("coverage is computed");
+    assert_eq!(coverage.stops.len(), 4);
+    assert_eq!(
+        coverage.hull,
+        [(54.4936, 9.8256), (54.4700, 9.8350), (54.4750, 9.8632)]
+    );
+    // the next run starts without the extension.
+    put_extension(&database, &[]).await;
+}
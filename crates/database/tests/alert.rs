@@ -0,0 +1,180 @@
+//! Alerts of a collector, which fails repeatedly and stops running successfully,
+//! posted to a mock webhook. The collector is committed, as alerts are evaluated
+//! over all collectors, and deleted along with its alerts at the end.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use chrono::Duration;
+use public_transport::notification::{self, AlertConfig, Notifier};
+use serde_json::Value;
+use sqlx::PgPool;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+/// Accepts every notification. Gives the url and the bodies posted so far.
+async fn mock_webhook() -> (String, Arc<Mutex<Vec<Value>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let notifications = Arc::new(Mutex::new(vec![]));
+    let received = notifications.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![];
+            let mut buffer = [0; 4096];
+            let (head, length) = loop {
+                let read = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                let head = String::from_utf8_lossy(&request);
+                if let Some(end) = head.find("\r\n\r\n") {
+                    let length = head
+                        .lines()
+                        .find_map(|line| {
+                            let (name, value) = line.split_once(':')?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse::<usize>().ok())?
+                        })
+                        .unwrap_or_default();
+                    break (end + 4, length);
+                }
+            };
+            while request.len() < head + length {
+                let read = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+            }
+            let body = serde_json::from_slice(&request[head..head + length])
+                .expect("notification is json");
+            received.lock().unwrap().push(body);
+            let response =
+                "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+            stream.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+    (url, notifications)
+}
+
+/// Kinds of the notifications posted so far about the rules, in order. Other
+/// notifications are of collectors and origins of other tests.
+fn notified(
+    notifications: &Mutex<Vec<Value>>,
+    rules: &[String],
+) -> Vec<(String, String)> {
+    notifications
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|notification| {
+            rules.iter().any(|rule| notification["rule"] == **rule)
+        })
+        .map(|notification| {
+            (
+                notification["kind"].as_str().unwrap_or_default().to_owned(),
+                notification["rule"].as_str().unwrap_or_default().to_owned(),
+            )
+        })
+        .collect()
+}
+
+async fn delete(pool: &PgPool, id: i32, rules: &[String]) {
+    sqlx::query("DELETE FROM alerts WHERE rule = ANY($1)")
+        .bind(rules)
+        .execute(pool)
+        .await
+        .expect("alerts are deleted");
+    sqlx::query("DELETE FROM collectors WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await
+        .expect("collector is deleted");
+}
+
+#[tokio::test]
+async fn alerts_and_recoveries_are_sent_once_per_incident() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let pool = common::pool().await;
+    let (url, notifications) = mock_webhook().await;
+    let notifier = Notifier::new(AlertConfig {
+        webhooks: vec![url],
+        max_consecutive_failures: 5,
+        max_collector_silence: Duration::hours(1),
+        // realtime data of other tests is never alerted.
+        max_realtime_age: Duration::days(36500),
+    });
+
+    // the collector failed five times and last succeeded three hours ago.
+    let (id,): (i32,) = sqlx::query_as(
+        "
+        INSERT INTO collectors(
+            origin, kind, is_active, state, consecutive_failures, last_success_at,
+            run_interval
+        )
+        VALUES (
+            'migration', 'Alert Test', true, 'null', 5, NOW() - INTERVAL '3 hours',
+            INTERVAL '1 hour'
+        )
+        RETURNING id;
+        ",
+    )
+    .fetch_one(&pool)
+    .await
+    .expect("collector is inserted");
+    let failures = format!("collector-failures:{}", id);
+    let silent = format!("collector-silent:{}", id);
+    let rules = [failures.clone(), silent.clone()];
+    let alert = |rule: &String| ("alert".to_owned(), rule.clone());
+    let recovery = |rule: &String| ("recovery".to_owned(), rule.clone());
+
+    for _ in 0..2 {
+        notification::evaluate(&database, &notifier)
+            .await
+            .expect("rules are evaluated");
+    }
+    assert_eq!(
+        notified(&notifications, &rules),
+        [alert(&failures), alert(&silent)],
+        "alerts of the open incidents"
+    );
+
+    // a successful run recovers both incidents.
+    sqlx::query(
+        "
+        UPDATE collectors SET consecutive_failures = 0, last_success_at = NOW()
+        WHERE id = $1;
+        ",
+    )
+    .bind(id)
+    .execute(&pool)
+    .await
+    .expect("run is recorded");
+    for _ in 0..2 {
+        notification::evaluate(&database, &notifier)
+            .await
+            .expect("rules are evaluated");
+    }
+    assert_eq!(
+        notified(&notifications, &rules),
+        [
+            alert(&failures),
+            alert(&silent),
+            recovery(&failures),
+            recovery(&silent)
+        ],
+        "recoveries of the closed incidents"
+    );
+    let recovered = notifications
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|notification| notification["rule"] == *failures)
+        .map(|notification| notification["closedAt"].is_string())
+        .collect::<Vec<_>>();
+    assert_eq!(recovered, [false, true], "recoveries are closed");
+
+    delete(&pool, id, &rules).await;
+}
@@ -0,0 +1,224 @@
+//! Capabilities of databases, whose extensions are hidden from the role. Each test
+//! creates a database of its own, in which pg_trgm is installed in a schema outside
+//! of the default search path, so that the test database is left untouched.
+
+mod common;
+
+use database::{DatabaseConnectionInfo, MissingExtensionError, PgDatabase};
+use model::{
+    stop::{Location, Stop},
+    WithId, WithOrigin,
+};
+use public_transport::{
+    capability::{self, Capability, SearchMode},
+    database::{Database, DatabaseTransaction, MergableRepo, Repo, StopRepo},
+};
+use sqlx::PgPool;
+use url::Url;
+use utility::id::Id;
+
+const ORIGIN: &str = "test-capabilities";
+/// Role, whose search path does not include the schema of the extensions.
+const LIMITED_ROLE: &str = "test_capabilities_limited";
+
+/// Connection info of the database of the given name on the server of the test
+/// database.
+fn connection_info(database: &str) -> Option<DatabaseConnectionInfo> {
+    let mut connection_info = common::connection_info()?;
+    connection_info.database = database.to_owned();
+    Some(connection_info)
+}
+
+/// Plain connection to the database of the given name as the role of the test
+/// database.
+async fn pool(database: &str) -> PgPool {
+    let mut url = Url::parse(&common::url().expect("TEST_DATABASE_URL is set"))
+        .expect("TEST_DATABASE_URL is a postgres url");
+    url.set_path(database);
+    PgPool::connect(url.as_str())
+        .await
+        .expect("database is reachable")
+}
+
+async fn execute(pool: &PgPool, statement: &str) {
+    sqlx::query(statement)
+        .execute(pool)
+        .await
+        .unwrap_or_else(|why| panic!("{}: {:?}", statement, why));
+}
+
+/// Creates the database of the given name anew, with pg_trgm installed in the
+/// schema `extensions`.
+async fn create_database_with_hidden_trgm(database: &str) {
+    let pool = common::pool().await;
+    execute(
+        &pool,
+        &format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", database),
+    )
+    .await;
+    execute(&pool, &format!("CREATE DATABASE {}", database)).await;
+    let pool = self::pool(database).await;
+    execute(&pool, "CREATE SCHEMA extensions").await;
+    execute(&pool, "CREATE EXTENSION pg_trgm SCHEMA extensions").await;
+}
+
+fn stop(name: &str) -> Stop {
+    Stop {
+        name: Some(name.to_owned()),
+        description: None,
+        parent_id: None,
+        location: Some(Location {
+            latitude: 54.3147,
+            longitude: 10.1322,
+            address: None,
+        }),
+        platform_code: None,
+        amenities: vec![],
+        updated_at: None,
+    }
+}
+
+#[tokio::test]
+async fn migrations_fail_fast_if_an_extension_is_hidden_from_the_role() {
+    const DATABASE: &str = "test_capabilities_hidden";
+    let Some(connection_info) = connection_info(DATABASE) else {
+        return;
+    };
+    create_database_with_hidden_trgm(DATABASE).await;
+
+    let Err(why) = PgDatabase::connect(connection_info).await else {
+        panic!("database is migrated, although pg_trgm is hidden");
+    };
+    let why = why
+        .downcast_ref::<MissingExtensionError>()
+        .unwrap_or_else(|| panic!("extension is reported missing: {}", why));
+    assert_eq!(why.capability, Capability::PgTrgm);
+    assert_eq!(
+        why.migrations
+            .iter()
+            .map(|migration| migration.version)
+            .collect::<Vec<_>>(),
+        [1, 3]
+    );
+    assert!(
+        why.to_string()
+            .starts_with("CREATE EXTENSION pg_trgm required"),
+        "{}",
+        why
+    );
+
+    let is_migrated: bool = sqlx::query_scalar(
+        "SELECT to_regclass('public._sqlx_migrations') IS NOT NULL",
+    )
+    .fetch_one(&pool(DATABASE).await)
+    .await
+    .expect("migrations are looked up");
+    assert!(!is_migrated, "nothing is migrated");
+}
+
+#[tokio::test]
+async fn search_falls_back_to_substrings_for_a_role_without_extensions() {
+    const DATABASE: &str = "test_capabilities_limited";
+    let Some(connection_info) = connection_info(DATABASE) else {
+        return;
+    };
+    create_database_with_hidden_trgm(DATABASE).await;
+    let pool = pool(DATABASE).await;
+    execute(
+        &pool,
+        &format!(
+            "ALTER DATABASE {} SET search_path = \"$user\", public, extensions",
+            DATABASE
+        ),
+    )
+    .await;
+
+    // migrated by a role, which finds the extension.
+    let database = PgDatabase::connect(connection_info)
+        .await
+        .expect("database is migrated");
+    assert!(database.capabilities().has(Capability::PgTrgm));
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    tx.put(WithOrigin::new(
+        Id::new(ORIGIN.to_owned()),
+        WithId::new(
+            Id::new("test-capabilities-stop".to_owned()),
+            stop("Kiel Hauptbahnhof"),
+        ),
+    ))
+    .await
+    .expect("stop is stored");
+    tx.commit().await.expect("transaction is committed");
+
+    execute(
+        &pool,
+        &format!(
+            "
+            DO $$ BEGIN
+                CREATE ROLE {} LOGIN;
+            EXCEPTION WHEN duplicate_object THEN NULL;
+            END $$
+            ",
+            LIMITED_ROLE
+        ),
+    )
+    .await;
+    execute(
+        &pool,
+        &format!(
+            "ALTER ROLE {} IN DATABASE {} SET search_path = \"$user\", public",
+            LIMITED_ROLE, DATABASE
+        ),
+    )
+    .await;
+    execute(
+        &pool,
+        &format!(
+            "GRANT SELECT ON ALL TABLES IN SCHEMA public TO {}",
+            LIMITED_ROLE
+        ),
+    )
+    .await;
+
+    let mut connection_info = self::connection_info(DATABASE).expect("configured");
+    connection_info.username = LIMITED_ROLE.to_owned();
+    connection_info.password = String::new();
+    connection_info.auto_migrate = false;
+    let limited = PgDatabase::connect(connection_info)
+        .await
+        .expect("migrated database is reachable by the limited role");
+    let capabilities = limited.capabilities();
+    assert!(!capabilities.has(Capability::PgTrgm));
+    assert_eq!(capabilities.search_mode(), SearchMode::Substring);
+
+    let found = limited
+        .auto()
+        .search("hauptbahn")
+        .await
+        .expect("stops are searched without pg_trgm");
+    assert!(found
+        .iter()
+        .flat_map(|entry| &entry.source_data)
+        .any(|source| source.content.name.as_deref() == Some("Kiel Hauptbahnhof")));
+
+    let why = limited
+        .auto()
+        .merge_candidates(
+            &stop("Kiel Hbf"),
+            &Id::new("test-capabilities-other".to_owned()),
+        )
+        .await
+        .expect_err("merge candidates need pg_trgm");
+    assert!(
+        format!("{:?}", why)
+            .contains("CREATE EXTENSION pg_trgm required for merging stops"),
+        "{:?}",
+        why
+    );
+    assert!(capability::check(
+        "collector 'GTFS Schedule'",
+        &[capability::STOP_MERGING],
+        capabilities
+    )
+    .is_err());
+}
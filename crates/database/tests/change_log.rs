@@ -0,0 +1,361 @@
+mod common;
+
+use chrono::{Duration, Local};
+use model::{
+    change::{ChangeOperation, ChangedEntity},
+    fixtures::{stop_time, StopBuilder, TripBuilder},
+    line::{Line, LineType},
+    trip::StopTime,
+    WithId, WithOrigin,
+};
+use public_transport::database::{
+    ChangeLogRepo, Database, DatabaseOperations, DatabaseTransaction, Repo, TripRepo,
+};
+use serde::Serialize;
+use utility::id::{HasId, Id};
+
+const ORIGIN: &str = "test-change-log";
+
+type LoggedChange = (ChangedEntity, String, ChangeOperation);
+
+fn with_id<T>(id: &str, content: T) -> WithOrigin<WithId<T>>
+where
+    T: Serialize + HasId<IdType = String>,
+{
+    WithOrigin::new(
+        Id::new(ORIGIN.to_owned()),
+        WithId::new(Id::new(id.to_owned()), content),
+    )
+}
+
+/// Imports two stops, a line and a trip along them, like a schedule import.
+async fn import<O>(operations: &mut O, stop_times: &[StopTime])
+where
+    O: DatabaseOperations + Send,
+{
+    for (id, name) in [
+        ("test-change-log-1", "Kiel Hbf"),
+        ("test-change-log-2", "Raisdorf"),
+    ] {
+        operations
+            .put(with_id(id, StopBuilder::new(name).build()))
+            .await
+            .expect("stop is stored");
+    }
+    operations
+        .put(with_id(
+            "test-change-log-line",
+            Line {
+                name: Some("300".to_owned()),
+                kind: LineType::Bus,
+                agency_id: None,
+                secondary_agency_ids: vec![],
+                updated_at: None,
+            },
+        ))
+        .await
+        .expect("line is stored");
+    operations
+        .put(with_id(
+            "test-change-log-trip",
+            TripBuilder::new("test-change-log-line", 0, 0)
+                .headsign("Raisdorf")
+                .build(),
+        ))
+        .await
+        .expect("trip is stored");
+    operations
+        .put_stop_times(
+            &Id::new("test-change-log-trip".to_owned()),
+            &Id::new(ORIGIN.to_owned()),
+            stop_times,
+            true,
+        )
+        .await
+        .expect("stop times are stored");
+}
+
+fn stop_times() -> Vec<StopTime> {
+    vec![
+        stop_time(1, "test-change-log-1", 0),
+        stop_time(2, "test-change-log-2", 12),
+    ]
+}
+
+/// Flushes the pending changes and returns the changes of the test origin
+/// logged since the last call.
+async fn flushed_changes<O>(
+    operations: &mut O,
+    after: &mut Option<i64>,
+) -> Vec<LoggedChange>
+where
+    O: DatabaseOperations + Send,
+{
+    operations
+        .flush_changes(100_000)
+        .await
+        .expect("changes are flushed");
+    let changes = operations
+        .changes_after(
+            after.map(|sequence| model::change::ChangePosition { sequence }),
+            100_000,
+        )
+        .await
+        .expect("changes are read");
+    if let Some(last) = changes.last() {
+        *after = Some(last.position.sequence);
+    }
+    changes
+        .into_iter()
+        .filter(|change| change.origin.raw_ref::<str>() == ORIGIN)
+        .map(|change| (change.entity, change.id, change.operation))
+        .collect()
+}
+
+#[tokio::test]
+async fn logs_exactly_the_touched_entities() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    let mut after = None;
+    flushed_changes(&mut tx, &mut after).await;
+
+    import(&mut tx, &stop_times()).await;
+    let mut changes = flushed_changes(&mut tx, &mut after).await;
+    changes.sort_by(|a, b| a.1.cmp(&b.1));
+    assert_eq!(
+        changes,
+        vec![
+            (
+                ChangedEntity::Stop,
+                "test-change-log-1".to_owned(),
+                ChangeOperation::Insert
+            ),
+            (
+                ChangedEntity::Stop,
+                "test-change-log-2".to_owned(),
+                ChangeOperation::Insert
+            ),
+            (
+                ChangedEntity::Line,
+                "test-change-log-line".to_owned(),
+                ChangeOperation::Insert
+            ),
+            (
+                ChangedEntity::Trip,
+                "test-change-log-trip".to_owned(),
+                ChangeOperation::Insert
+            ),
+        ]
+    );
+
+    // an import without any modification is not logged.
+    import(&mut tx, &stop_times()).await;
+    assert_eq!(flushed_changes(&mut tx, &mut after).await, vec![]);
+
+    // a modified stop time is a change of its trip only.
+    let mut modified = stop_times();
+    modified[1].arrival_time = Some(Duration::minutes(13));
+    import(&mut tx, &modified).await;
+    assert_eq!(
+        flushed_changes(&mut tx, &mut after).await,
+        vec![(
+            ChangedEntity::Trip,
+            "test-change-log-trip".to_owned(),
+            ChangeOperation::Update
+        )]
+    );
+
+    // as is a removed one.
+    import(&mut tx, &modified[..1]).await;
+    assert_eq!(
+        flushed_changes(&mut tx, &mut after).await,
+        vec![(
+            ChangedEntity::Trip,
+            "test-change-log-trip".to_owned(),
+            ChangeOperation::Update
+        )]
+    );
+}
+
+#[tokio::test]
+async fn logs_an_entity_once_per_transaction() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    let mut after = None;
+    flushed_changes(&mut tx, &mut after).await;
+
+    tx.put(with_id(
+        "test-change-log-1",
+        StopBuilder::new("Kiel Hbf").build(),
+    ))
+    .await
+    .expect("stop is inserted");
+    tx.put(with_id(
+        "test-change-log-1",
+        StopBuilder::new("Kiel Hauptbahnhof").build(),
+    ))
+    .await
+    .expect("stop is updated");
+    tx.put(with_id(
+        "test-change-log-1",
+        StopBuilder::new("Kiel").build(),
+    ))
+    .await
+    .expect("stop is updated");
+    assert_eq!(
+        flushed_changes(&mut tx, &mut after).await,
+        vec![(
+            ChangedEntity::Stop,
+            "test-change-log-1".to_owned(),
+            ChangeOperation::Insert
+        )]
+    );
+}
+
+#[tokio::test]
+async fn compaction_keeps_the_latest_change_of_each_entity() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    let mut after = None;
+    flushed_changes(&mut tx, &mut after).await;
+    let start = after;
+
+    import(&mut tx, &stop_times()).await;
+    flushed_changes(&mut tx, &mut after).await;
+    let mut modified = stop_times();
+    modified[0].departure_time = Some(Duration::minutes(1));
+    import(&mut tx, &modified).await;
+    flushed_changes(&mut tx, &mut after).await;
+
+    tx.compact_changes(Local::now() + Duration::days(1))
+        .await
+        .expect("change log is compacted");
+    let mut after = start;
+    let changes = flushed_changes(&mut tx, &mut after).await;
+    let trip_changes = changes
+        .iter()
+        .filter(|(entity, _, _)| *entity == ChangedEntity::Trip)
+        .collect::<Vec<_>>();
+    assert_eq!(changes.len(), 4);
+    assert_eq!(
+        trip_changes,
+        vec![&(
+            ChangedEntity::Trip,
+            "test-change-log-trip".to_owned(),
+            ChangeOperation::Update
+        )]
+    );
+}
+
+#[tokio::test]
+async fn compaction_drops_deletions_behind_the_horizon() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    let mut after = None;
+    flushed_changes(&mut tx, &mut after).await;
+    let start = after;
+
+    import(&mut tx, &stop_times()).await;
+    flushed_changes(&mut tx, &mut after).await;
+    tx.delete_trip(
+        &Id::new("test-change-log-trip".to_owned()),
+        &Id::new(ORIGIN.to_owned()),
+    )
+    .await
+    .expect("trip is deleted");
+    assert_eq!(
+        flushed_changes(&mut tx, &mut after).await,
+        vec![(
+            ChangedEntity::Trip,
+            "test-change-log-trip".to_owned(),
+            ChangeOperation::Delete
+        )]
+    );
+    let deleted_at = after.expect("deletion is logged");
+
+    tx.compact_changes(Local::now() - Duration::days(1))
+        .await
+        .expect("change log is compacted");
+    let horizon = tx.change_log_horizon().await.expect("horizon is read");
+    assert!(
+        horizon.is_none_or(|horizon| horizon.sequence < deleted_at),
+        "deletions within the retention are kept"
+    );
+
+    tx.compact_changes(Local::now() + Duration::days(1))
+        .await
+        .expect("change log is compacted");
+    let mut after = start;
+    let changes = flushed_changes(&mut tx, &mut after).await;
+    assert_eq!(changes.len(), 3, "the stops and the line are kept");
+    assert!(changes
+        .iter()
+        .all(|(entity, _, _)| *entity != ChangedEntity::Trip));
+    let horizon = tx.change_log_horizon().await.expect("horizon is read");
+    assert_eq!(horizon.map(|horizon| horizon.sequence), Some(deleted_at));
+}
+
+/// Changes of finished transactions are served, while an older transaction is
+/// still running.
+#[tokio::test]
+async fn running_transactions_do_not_hold_back_the_feed() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let origin = "test-change-log-running";
+    let mut running = common::transaction(&database, ORIGIN).await;
+    running
+        .put(with_id(
+            "test-change-log-1",
+            StopBuilder::new("Kiel Hbf").build(),
+        ))
+        .await
+        .expect("stop is inserted");
+
+    // committed, so that the name differs from previous runs.
+    let name = format!("Raisdorf {}", Local::now().timestamp_micros());
+    let mut finished = common::transaction(&database, origin).await;
+    finished
+        .put(WithOrigin::new(
+            Id::new(origin.to_owned()),
+            WithId::new(
+                Id::new("test-change-log-running-1".to_owned()),
+                StopBuilder::new(&name).build(),
+            ),
+        ))
+        .await
+        .expect("stop is stored");
+    finished.commit().await.expect("transaction commits");
+
+    let mut flush = database.transaction().await.expect("transaction begins");
+    assert!(flush
+        .try_lock_change_flush()
+        .await
+        .expect("lock is acquired"));
+    flush
+        .flush_changes(100_000)
+        .await
+        .expect("changes are flushed");
+    flush.commit().await.expect("transaction commits");
+
+    let changes = database
+        .auto()
+        .changes_after(None, 100_000)
+        .await
+        .expect("changes are read");
+    assert!(changes.iter().any(|change| {
+        change.origin.raw_ref::<str>() == origin
+            && change.id == "test-change-log-running-1"
+    }));
+    assert!(!changes
+        .iter()
+        .any(|change| change.origin.raw_ref::<str>() == ORIGIN));
+}
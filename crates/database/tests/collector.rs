@@ -0,0 +1,709 @@
+mod common;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Local};
+use database::PgDatabase;
+use public_transport::{
+    client::Client,
+    collector::{self, Collector, CollectorInstance, CollectorLease, Continuation},
+    database::{CollectorRepo, Database},
+    server::Server,
+};
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicU32, Ordering};
+use tokio::time::sleep;
+use utility::id::Id;
+
+/// Collector, whose instances are only leased by the tests.
+struct LeaseTestCollector;
+
+#[async_trait]
+impl Collector for LeaseTestCollector {
+    type Error = String;
+    type State = ();
+
+    fn unique_id() -> &'static str {
+        "Lease Test"
+    }
+
+    fn from_state(_state: Self::State) -> Result<Self, Self::Error> {
+        Ok(Self)
+    }
+
+    async fn run<D: Database>(
+        &mut self,
+        _client: &Client<D>,
+        _state: Self::State,
+    ) -> Result<(Continuation, Self::State), Self::Error> {
+        Ok((Continuation::Exit, ()))
+    }
+}
+
+/// Collector, whose single run fails for some of its sources.
+struct PartialFailureCollector;
+
+#[async_trait]
+impl Collector for PartialFailureCollector {
+    type Error = String;
+    type State = Vec<String>;
+
+    fn unique_id() -> &'static str {
+        "Partial Failure Test"
+    }
+
+    fn from_state(_state: Self::State) -> Result<Self, Self::Error> {
+        Ok(Self)
+    }
+
+    async fn run<D: Database>(
+        &mut self,
+        _client: &Client<D>,
+        _state: Self::State,
+    ) -> Result<(Continuation, Self::State), Self::Error> {
+        Ok((Continuation::Exit, vec!["broken source".to_owned()]))
+    }
+
+    fn partial_failure(&self, state: &Self::State) -> Option<String> {
+        (!state.is_empty()).then(|| state.join(", "))
+    }
+
+    fn tick(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+/// Collector, which counts its runs in its state and continues as given by the
+/// count of the finished run.
+struct SchedulingTestCollector<const KIND: u8>;
+
+/// Continues after each run.
+const CONTINUE: u8 = 0;
+/// Sleeps after the first run and exits after the second.
+const SLEEP_THEN_EXIT: u8 = 1;
+
+#[async_trait]
+impl<const KIND: u8> Collector for SchedulingTestCollector<KIND> {
+    type Error = String;
+    type State = u32;
+
+    fn unique_id() -> &'static str {
+        match KIND {
+            CONTINUE => "Continue Test",
+            _ => "Sleep Then Exit Test",
+        }
+    }
+
+    fn from_state(_state: Self::State) -> Result<Self, Self::Error> {
+        Ok(Self)
+    }
+
+    async fn run<D: Database>(
+        &mut self,
+        _client: &Client<D>,
+        runs: Self::State,
+    ) -> Result<(Continuation, Self::State), Self::Error> {
+        let runs = runs + 1;
+        let continuation = match (KIND, runs) {
+            (CONTINUE, _) => Continuation::Continue,
+            (_, 1) => Continuation::Sleep(std::time::Duration::from_secs(1)),
+            _ => Continuation::Exit,
+        };
+        Ok((continuation, runs))
+    }
+
+    fn tick(&self) -> Option<std::time::Duration> {
+        Some(std::time::Duration::from_secs(2))
+    }
+}
+
+/// Runs of [`SharedTestCollector`] by all server instances.
+static SHARED_RUNS: AtomicU32 = AtomicU32::new(0);
+
+/// Tick of [`SharedTestCollector`].
+const SHARED_TICK: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Collector, which is run by several server instances and counts its runs in its
+/// state and in [`SHARED_RUNS`].
+struct SharedTestCollector;
+
+#[async_trait]
+impl Collector for SharedTestCollector {
+    type Error = String;
+    type State = u32;
+
+    fn unique_id() -> &'static str {
+        "Shared Test"
+    }
+
+    fn from_state(_state: Self::State) -> Result<Self, Self::Error> {
+        Ok(Self)
+    }
+
+    async fn run<D: Database>(
+        &mut self,
+        _client: &Client<D>,
+        runs: Self::State,
+    ) -> Result<(Continuation, Self::State), Self::Error> {
+        SHARED_RUNS.fetch_add(1, Ordering::SeqCst);
+        Ok((Continuation::Continue, runs + 1))
+    }
+
+    fn tick(&self) -> Option<std::time::Duration> {
+        Some(SHARED_TICK)
+    }
+}
+
+/// Collector, which counts its runs in its state and whose health check passes or
+/// fails as given by its kind.
+struct HealthTestCollector<const KIND: u8>;
+
+const HEALTHY: u8 = 0;
+/// Fails its health check after a while.
+const UNHEALTHY: u8 = 1;
+
+/// Duration of the failing health check.
+const UNHEALTHY_CHECK: std::time::Duration = std::time::Duration::from_secs(2);
+
+#[async_trait]
+impl<const KIND: u8> Collector for HealthTestCollector<KIND> {
+    type Error = String;
+    type State = u32;
+
+    fn unique_id() -> &'static str {
+        match KIND {
+            HEALTHY => "Healthy Test",
+            _ => "Unhealthy Test",
+        }
+    }
+
+    fn from_state(_state: Self::State) -> Result<Self, Self::Error> {
+        Ok(Self)
+    }
+
+    async fn run<D: Database>(
+        &mut self,
+        _client: &Client<D>,
+        runs: Self::State,
+    ) -> Result<(Continuation, Self::State), Self::Error> {
+        Ok((Continuation::Exit, runs + 1))
+    }
+
+    async fn healthcheck(&mut self, _state: &Self::State) -> Result<(), Self::Error> {
+        match KIND {
+            HEALTHY => Ok(()),
+            _ => {
+                sleep(UNHEALTHY_CHECK).await;
+                Err("credentials rejected".to_owned())
+            }
+        }
+    }
+}
+
+/// Collector, whose state is the number of its runs. Instances, which never run,
+/// are rejected.
+struct StartupTestCollector;
+
+#[async_trait]
+impl Collector for StartupTestCollector {
+    type Error = String;
+    type State = u32;
+
+    fn unique_id() -> &'static str {
+        "Startup Test"
+    }
+
+    fn from_state(state: Self::State) -> Result<Self, Self::Error> {
+        if state == 0 {
+            return Err("no runs left".to_owned());
+        }
+        Ok(Self)
+    }
+
+    async fn run<D: Database>(
+        &mut self,
+        _client: &Client<D>,
+        runs: Self::State,
+    ) -> Result<(Continuation, Self::State), Self::Error> {
+        Ok((Continuation::Exit, runs - 1))
+    }
+}
+
+/// Inserts an instance of the collector and starts it.
+async fn start<C>(client: &Client<PgDatabase>, pool: &PgPool) -> i32
+where
+    C: Collector<State = u32, Error = String> + Send + 'static,
+{
+    let (id,): (i32,) = sqlx::query_as(
+        "
+        INSERT INTO collectors(origin, kind, is_active, state)
+        VALUES ('migration', $1, true, '0')
+        RETURNING id;
+        ",
+    )
+    .bind(C::unique_id())
+    .fetch_one(pool)
+    .await
+    .expect("collector is inserted");
+    collector::run(
+        C::from_state,
+        client.clone(),
+        Id::<CollectorInstance<C>>::new(id),
+    )
+    .await
+    .expect("collector is started");
+    id
+}
+
+/// Runs of the collector and the stored time of its next run.
+async fn runs(pool: &PgPool, id: i32) -> (u32, Option<DateTime<Local>>) {
+    let (state, next_run_at): (String, Option<DateTime<Local>>) = sqlx::query_as(
+        "SELECT state::text, next_run_at FROM collectors WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_one(pool)
+    .await
+    .expect("collector is loaded");
+    (state.parse().expect("state is a count"), next_run_at)
+}
+
+async fn delete(pool: &PgPool, id: i32) {
+    sqlx::query("DELETE FROM collectors WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await
+        .expect("collector is deleted");
+}
+
+#[tokio::test]
+async fn continuing_collectors_run_once_per_tick() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let pool = common::pool().await;
+    let client = Server::new(database).client("migration");
+    let id = start::<SchedulingTestCollector<CONTINUE>>(&client, &pool).await;
+
+    // the first tick of an interval completes immediately, it must not cause a
+    // second run right after the first one.
+    sleep(std::time::Duration::from_millis(1500)).await;
+    assert_eq!(runs(&pool, id).await, (1, None), "runs before the tick");
+    sleep(std::time::Duration::from_millis(1500)).await;
+    assert_eq!(runs(&pool, id).await, (2, None), "runs after the tick");
+
+    delete(&pool, id).await;
+}
+
+#[tokio::test]
+async fn server_instances_sharing_a_database_run_a_collector_once_per_tick() {
+    // each instance has a pool of its own, like separate processes.
+    let (Some(first), Some(second)) =
+        (common::connect().await, common::connect().await)
+    else {
+        return;
+    };
+    let pool = common::pool().await;
+    let first = Server::new(first).client("migration");
+    let second = Server::new(second).client("migration");
+    let id = start::<SharedTestCollector>(&first, &pool).await;
+    collector::run(
+        SharedTestCollector::from_state,
+        second,
+        Id::<CollectorInstance<SharedTestCollector>>::new(id),
+    )
+    .await
+    .expect("collector is started by the second instance");
+
+    // a single instance runs right away and after each of the ticks.
+    sleep(SHARED_TICK * 3 + SHARED_TICK / 2).await;
+    let single_instance_runs = 4;
+    assert_eq!(
+        SHARED_RUNS.load(Ordering::SeqCst),
+        single_instance_runs,
+        "the second instance does not add runs"
+    );
+    assert_eq!(runs(&pool, id).await, (single_instance_runs, None));
+
+    delete(&pool, id).await;
+}
+
+#[tokio::test]
+async fn sleeping_collectors_store_their_next_run_until_it_starts() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let pool = common::pool().await;
+    let client = Server::new(database).client("migration");
+    let id = start::<SchedulingTestCollector<SLEEP_THEN_EXIT>>(&client, &pool).await;
+
+    sleep(std::time::Duration::from_millis(500)).await;
+    let (count, next_run_at) = runs(&pool, id).await;
+    assert_eq!(count, 1);
+    assert!(
+        next_run_at.is_some_and(|next_run_at| next_run_at > Local::now()),
+        "next run is stored while sleeping"
+    );
+
+    // the second run exits, so that no run follows.
+    sleep(std::time::Duration::from_millis(3000)).await;
+    assert_eq!(
+        runs(&pool, id).await,
+        (2, None),
+        "the stale next run is cleared and no run follows the exit"
+    );
+
+    delete(&pool, id).await;
+}
+
+#[tokio::test]
+async fn partially_failed_runs_are_recorded_as_failures() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let pool = common::pool().await;
+    let (id,): (i32,) = sqlx::query_as(
+        "
+        INSERT INTO collectors(origin, kind, is_active, state)
+        VALUES ('migration', $1, true, '[]')
+        RETURNING id;
+        ",
+    )
+    .bind(PartialFailureCollector::unique_id())
+    .fetch_one(&pool)
+    .await
+    .expect("collector is inserted");
+    let client = Server::new(database).client("migration");
+    collector::run(
+        PartialFailureCollector::from_state,
+        client.clone(),
+        Id::<CollectorInstance<PartialFailureCollector>>::new(id),
+    )
+    .await
+    .expect("collector is started");
+
+    let mut health = None;
+    for _ in 0..50 {
+        let collectors = client
+            .get_collector_health()
+            .await
+            .expect("health is loaded");
+        health = collectors.into_iter().find(|health| health.id == id);
+        if health
+            .as_ref()
+            .is_some_and(|health| health.consecutive_failures > 0)
+        {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let health = health.expect("collector is listed");
+    assert_eq!(health.consecutive_failures, 1);
+    assert_eq!(health.last_success_at, None);
+    let (state,): (String,) =
+        sqlx::query_as("SELECT state::text FROM collectors WHERE id = $1")
+            .bind(id)
+            .fetch_one(&pool)
+            .await
+            .expect("state is loaded");
+    assert_eq!(state, r#"["broken source"]"#, "state is stored");
+
+    sqlx::query("DELETE FROM collectors WHERE id = $1")
+        .bind(id)
+        .execute(&pool)
+        .await
+        .expect("collector is deleted");
+}
+
+#[tokio::test]
+async fn collectors_are_due_after_the_interval_of_their_last_successful_run() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let pool = common::pool().await;
+    let (id,): (i32,) = sqlx::query_as(
+        "
+        INSERT INTO collectors(origin, kind, is_active, state)
+        VALUES ('migration', $1, true, 'null')
+        RETURNING id;
+        ",
+    )
+    .bind(LeaseTestCollector::unique_id())
+    .fetch_one(&pool)
+    .await
+    .expect("collector is inserted");
+    let instance = Id::<CollectorInstance<LeaseTestCollector>>::new(id);
+    let mut auto = database.auto();
+    let health = || async {
+        let health = database
+            .auto()
+            .collector_health()
+            .await
+            .expect("health is loaded")
+            .into_iter()
+            .find(|health| health.id == id)
+            .expect("collector is listed");
+        let interval = health
+            .due_at
+            .zip(health.last_success_at)
+            .map(|(due_at, last_success_at)| due_at - last_success_at);
+        (health.consecutive_failures, interval)
+    };
+
+    assert_eq!(health().await, (0, None), "never succeeded");
+
+    auto.record_collector_run(&instance, true, Some(Duration::hours(24)))
+        .await
+        .expect("run is recorded");
+    assert_eq!(health().await, (0, Some(Duration::hours(24))));
+
+    auto.record_collector_run(&instance, false, None)
+        .await
+        .expect("run is recorded");
+    assert_eq!(
+        health().await,
+        (1, Some(Duration::hours(24))),
+        "failed runs keep the interval"
+    );
+
+    auto.record_collector_run(&instance, true, None)
+        .await
+        .expect("run is recorded");
+    assert_eq!(
+        health().await,
+        (0, Some(Duration::zero())),
+        "collectors, which do not run again, are due right away"
+    );
+
+    delete(&pool, id).await;
+}
+
+#[tokio::test]
+async fn leases_a_collector_to_one_run_at_a_time() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    // leases are taken by separate connections, so that the instance is committed.
+    let pool = common::pool().await;
+    let (id,): (i32,) = sqlx::query_as(
+        "
+        INSERT INTO collectors(origin, kind, is_active, state)
+        VALUES ('migration', $1, true, 'null')
+        RETURNING id;
+        ",
+    )
+    .bind(LeaseTestCollector::unique_id())
+    .fetch_one(&pool)
+    .await
+    .expect("collector is inserted");
+    let id: Id<CollectorInstance<LeaseTestCollector>> = Id::new(id);
+    let mut db = database.auto();
+    let minute = Duration::minutes(1);
+    let no_spacing = Duration::zero();
+
+    let CollectorLease::Acquired(first) = db
+        .try_lease_collector(&id, minute, no_spacing)
+        .await
+        .expect("lease is queried")
+    else {
+        panic!("collector is leased");
+    };
+    assert_eq!(
+        db.try_lease_collector(&id, minute, no_spacing)
+            .await
+            .expect("lease is queried"),
+        CollectorLease::Held,
+        "a leased collector is not leased again"
+    );
+
+    db.release_collector_lease(&id, first)
+        .await
+        .expect("lease is released");
+    assert_eq!(
+        db.try_lease_collector(&id, minute, minute)
+            .await
+            .expect("lease is queried"),
+        CollectorLease::NotDue,
+        "a collector is not run again within the spacing"
+    );
+    let CollectorLease::Acquired(second) = db
+        .try_lease_collector(&id, minute, no_spacing)
+        .await
+        .expect("lease is queried")
+    else {
+        panic!("released collector is leased");
+    };
+
+    // an expired lease is taken over and no longer renewed by its previous run.
+    sqlx::query("UPDATE collectors SET leased_until = NOW() - '1 second'::interval WHERE id = $1")
+        .bind(id.raw())
+        .execute(&pool)
+        .await
+        .expect("lease expires");
+    let CollectorLease::Acquired(third) = db
+        .try_lease_collector(&id, minute, no_spacing)
+        .await
+        .expect("lease is queried")
+    else {
+        panic!("expired lease is taken over");
+    };
+    assert!(!db
+        .renew_collector_lease(&id, second, minute)
+        .await
+        .expect("lease is renewed"));
+    assert!(db
+        .renew_collector_lease(&id, third, minute)
+        .await
+        .expect("lease is renewed"));
+
+    // a collector, which asked to sleep, is not run earlier by another instance.
+    db.release_collector_lease(&id, third)
+        .await
+        .expect("lease is released");
+    db.set_collector_next_run(&id, Some(Local::now() + Duration::hours(1)))
+        .await
+        .expect("next run is stored");
+    assert_eq!(
+        db.try_lease_collector(&id, minute, no_spacing)
+            .await
+            .expect("lease is queried"),
+        CollectorLease::NotDue,
+        "a sleeping collector is not leased"
+    );
+
+    sqlx::query("DELETE FROM collectors WHERE id = $1")
+        .bind(id.raw())
+        .execute(&pool)
+        .await
+        .expect("collector is deleted");
+}
+
+#[tokio::test]
+async fn failing_health_checks_do_not_block_other_collectors() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let pool = common::pool().await;
+    let client = Server::new(database).client("migration");
+    let registration = std::time::Instant::now();
+    let unhealthy = start::<HealthTestCollector<UNHEALTHY>>(&client, &pool).await;
+    let healthy = start::<HealthTestCollector<HEALTHY>>(&client, &pool).await;
+    assert!(
+        registration.elapsed() < UNHEALTHY_CHECK,
+        "registration waits for the health check"
+    );
+
+    sleep(std::time::Duration::from_millis(500)).await;
+    assert_eq!(runs(&pool, healthy).await.0, 1, "healthy collector runs");
+    assert_eq!(
+        runs(&pool, unhealthy).await.0,
+        0,
+        "still checking its health"
+    );
+
+    let health = |id: i32| {
+        let client = client.clone();
+        async move {
+            client
+                .get_collector_health()
+                .await
+                .expect("health is loaded")
+                .into_iter()
+                .find(|health| health.id == id)
+                .expect("collector is listed")
+        }
+    };
+    let mut unhealthy_health = health(unhealthy).await;
+    for _ in 0..50 {
+        if unhealthy_health.checked_at.is_some() {
+            break;
+        }
+        sleep(std::time::Duration::from_millis(100)).await;
+        unhealthy_health = health(unhealthy).await;
+    }
+    assert_eq!(unhealthy_health.healthy, Some(false));
+    assert!(
+        unhealthy_health
+            .error
+            .as_deref()
+            .is_some_and(|error| error.contains("credentials rejected")),
+        "{:?}",
+        unhealthy_health.error
+    );
+    let healthy_health = health(healthy).await;
+    assert_eq!(healthy_health.healthy, Some(true));
+    assert_eq!(healthy_health.error, None);
+
+    // a failing health check is reported, but the collector is run nonetheless.
+    sleep(std::time::Duration::from_millis(500)).await;
+    assert_eq!(runs(&pool, unhealthy).await.0, 1);
+
+    delete(&pool, unhealthy).await;
+    delete(&pool, healthy).await;
+}
+
+#[tokio::test]
+async fn corrupted_states_fail_the_start_of_their_instance_only() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let pool = common::pool().await;
+    // a corrupted state, one the collector rejects and a valid one.
+    let mut ids = vec![];
+    for state in [r#""1 run""#, "0", "1"] {
+        let (id,): (i32,) = sqlx::query_as(
+            "
+            INSERT INTO collectors(origin, kind, is_active, state)
+            VALUES ('migration', $1, true, $2::jsonb)
+            RETURNING id;
+            ",
+        )
+        .bind(StartupTestCollector::unique_id())
+        .bind(state)
+        .fetch_one(&pool)
+        .await
+        .expect("collector is inserted");
+        ids.push(id);
+    }
+    let server = Server::new(database);
+    let startups = server
+        .collectors::<StartupTestCollector>()
+        .await
+        .expect("collectors are listed");
+    // errors by the start of their message, the rest depends on the database.
+    let prefix = |error: Option<&str>| {
+        error.map(|error| error.split(':').next().unwrap_or_default().to_owned())
+    };
+    let expected = [
+        (ids[0], Some("could not load state".to_owned())),
+        (ids[1], Some("invalid state".to_owned())),
+        (ids[2], None),
+    ];
+    assert_eq!(
+        startups
+            .iter()
+            .map(|startup| (startup.id, prefix(startup.error.as_deref())))
+            .collect::<Vec<_>>(),
+        expected
+    );
+
+    // failures are exposed by the health of the instances.
+    let health = server
+        .client("migration")
+        .get_collector_health()
+        .await
+        .expect("health is loaded")
+        .into_iter()
+        .filter(|health| ids.contains(&health.id))
+        .map(|health| {
+            let error = health
+                .error
+                .as_deref()
+                .and_then(|error| error.strip_prefix("failed to start: "));
+            (health.id, prefix(error))
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(health, expected);
+
+    for id in ids {
+        delete(&pool, id).await;
+    }
+}
@@ -0,0 +1,182 @@
+//! The small city network of `model::fixtures` answers the queries as documented
+//! in its constants. The network is committed under an origin of its own, so that
+//! the client sees it. Services are deduplicated per origin, and their ids are
+//! assigned by the database, so the trips refer to the stored ones.
+
+mod common;
+
+use std::collections::HashMap;
+
+use chrono::{Duration, Local, NaiveDate, NaiveTime};
+use model::{
+    fixtures::{
+        NetworkFixture, SMALL_CITY_HARBOUR, SMALL_CITY_HBF, SMALL_CITY_HOLIDAY,
+        SMALL_CITY_MARKET, SMALL_CITY_STOPS_NEAR_HBF,
+        SMALL_CITY_TRIPS_AT_HARBOUR_DAILY, SMALL_CITY_TRIPS_AT_MARKET_ON_HOLIDAY,
+        SMALL_CITY_TRIPS_AT_MARKET_ON_WEEKDAY,
+    },
+    stop::Stop,
+    trip_instance::WindowMode,
+    DateTimeRange, WithOrigin,
+};
+use public_transport::{
+    client::{Client, TripInstantiationOptions},
+    database::{Database, DatabaseTransaction, Repo, TripRepo},
+    server::Server,
+};
+use utility::id::Id;
+
+const ORIGIN: &str = "test-fixtures";
+
+fn date((year, month, day): (i32, u32, u32)) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).expect("valid date")
+}
+
+/// Number of trips arriving at the stop on the given day. Unlike departures, these
+/// include trips ending at the stop, such as the ferry at the harbour.
+async fn trips_at<D>(client: &Client<D>, stop_id: &str, day: NaiveDate) -> usize
+where
+    D: Database,
+{
+    let origins = [Id::new(ORIGIN.to_owned())];
+    let stop_id = Id::<Stop>::new(stop_id.to_owned());
+    let stop_ids = [&stop_id];
+    let start = day
+        .and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .unwrap();
+    let end = start + Duration::days(1);
+    let trips = client
+        .get_all_trips_via_stops(
+            &stop_ids,
+            start,
+            end,
+            WindowMode::ArriveBetween,
+            &origins,
+        )
+        .await
+        .expect("trips are read");
+    client
+        .instanciate_trips_include(
+            trips,
+            DateTimeRange::new(start, end),
+            &TripInstantiationOptions {
+                stop_ids_of_interest: Some(&stop_ids),
+                window_mode: WindowMode::ArriveBetween,
+                ..Default::default()
+            },
+            &origins,
+        )
+        .await
+        .expect("trips are instantiated")
+        .len()
+}
+
+#[tokio::test]
+async fn small_city_answers_the_documented_queries() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let network = NetworkFixture::small_city();
+    let origin = Id::new(ORIGIN.to_owned());
+    let client = Server::new(database.clone()).client(ORIGIN);
+
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    for stop in network.stops.iter() {
+        tx.put(WithOrigin::new(origin.clone(), stop.clone()))
+            .await
+            .expect("stop is stored");
+    }
+    for line in network.lines.iter() {
+        tx.put(WithOrigin::new(origin.clone(), line.clone()))
+            .await
+            .expect("line is stored");
+    }
+    tx.commit().await.expect("transaction is committed");
+
+    let mut service_ids = HashMap::new();
+    for service in network.services {
+        let id = client
+            .push_service(service.content, None)
+            .await
+            .expect("service is stored");
+        service_ids.insert(service.id, id);
+    }
+
+    let mut tx = database.transaction().await.expect("transaction begins");
+    for mut trip in network.trips {
+        trip.content.service_id = trip.content.service_id.map(|id| service_ids[&id]);
+        let stop_times = std::mem::take(&mut trip.content.stops);
+        let trip_id = trip.id.clone();
+        tx.put(WithOrigin::new(origin.clone(), trip))
+            .await
+            .expect("trip is stored");
+        tx.put_stop_times(&trip_id, &origin, &stop_times, true)
+            .await
+            .expect("stop times are stored");
+    }
+    tx.commit().await.expect("transaction is committed");
+
+    let hbf = network.stops[0]
+        .content
+        .location
+        .clone()
+        .expect("hbf is located");
+    let nearby = client
+        .find_nearby(
+            hbf.latitude,
+            hbf.longitude,
+            1.0,
+            std::slice::from_ref(&origin),
+        )
+        .await
+        .expect("stops are found");
+    assert_eq!(nearby.len(), SMALL_CITY_STOPS_NEAR_HBF, "{:?}", nearby);
+    assert_eq!(nearby[0].content.id.raw_ref::<str>(), SMALL_CITY_HBF);
+
+    // monday, 7th of October 2024 and sunday, 6th of October 2024.
+    let monday = date((2024, 10, 7));
+    let sunday = date((2024, 10, 6));
+    let holiday = date(SMALL_CITY_HOLIDAY);
+    let cases = [
+        (
+            SMALL_CITY_MARKET,
+            monday,
+            SMALL_CITY_TRIPS_AT_MARKET_ON_WEEKDAY,
+        ),
+        (
+            SMALL_CITY_MARKET,
+            holiday,
+            SMALL_CITY_TRIPS_AT_MARKET_ON_HOLIDAY,
+        ),
+        (
+            SMALL_CITY_MARKET,
+            sunday,
+            SMALL_CITY_TRIPS_AT_MARKET_ON_HOLIDAY,
+        ),
+        (
+            SMALL_CITY_HARBOUR,
+            monday,
+            SMALL_CITY_TRIPS_AT_HARBOUR_DAILY,
+        ),
+        (
+            SMALL_CITY_HARBOUR,
+            holiday,
+            SMALL_CITY_TRIPS_AT_HARBOUR_DAILY,
+        ),
+        (
+            SMALL_CITY_HARBOUR,
+            sunday,
+            SMALL_CITY_TRIPS_AT_HARBOUR_DAILY,
+        ),
+    ];
+    for (stop_id, day, expected) in cases {
+        assert_eq!(
+            trips_at(&client, stop_id, day).await,
+            expected,
+            "trips at {} on {}",
+            stop_id,
+            day
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! Ingestion through the client, which commits. Ids are specific to these tests,
+//! so that repeated runs update the same rows.
+
+mod common;
+
+use model::{
+    agency::Agency,
+    origin::Origin,
+    shared_mobility::{RentalUris, SharedMobilityStation},
+    stop::{Location, Stop},
+    WithId,
+};
+use public_transport::{
+    database::{Database, DatabaseOperations, EmptyName},
+    server::Server,
+    RequestError,
+};
+use utility::id::Id;
+
+const ORIGIN: &str = "test-ingestion";
+
+fn station(name: &str, latitude: f64, longitude: f64) -> SharedMobilityStation {
+    SharedMobilityStation {
+        name: name.to_owned(),
+        latitude,
+        longitude,
+        capacity: 4,
+        rental_uris: RentalUris {
+            android: None,
+            ios: None,
+            web: None,
+        },
+        status: None,
+        area: None,
+        region_id: None,
+        is_virtual_station: false,
+    }
+}
+
+#[tokio::test]
+async fn invalid_coordinates_are_dropped() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    database
+        .auto()
+        .put_origin(WithId::new(
+            Id::new(ORIGIN.to_owned()),
+            Origin {
+                name: ORIGIN.to_owned(),
+                priority: 0,
+            },
+        ))
+        .await
+        .expect("origin is stored");
+    let client = Server::new(database).client(ORIGIN);
+
+    let stop = client
+        .push_stop(
+            Stop {
+                name: Some("Null Island".to_owned()),
+                description: None,
+                parent_id: None,
+                location: Some(Location {
+                    latitude: 0.0,
+                    longitude: 0.0,
+                    address: None,
+                }),
+                platform_code: None,
+                updated_at: None,
+                amenities: vec![],
+            },
+            Some("test-ingestion-null-island".to_owned()),
+        )
+        .await
+        .expect("stop is stored");
+    assert!(stop.content.content.location.is_none());
+
+    let stations = [
+        ("test-ingestion-valid", station("Rathaus", 54.32, 10.13)),
+        ("test-ingestion-null-island", station("Nirgendwo", 0.0, 0.0)),
+        (
+            "test-ingestion-out-of-range",
+            station("Jenseits", 91.0, 10.13),
+        ),
+    ]
+    .map(|(id, station)| WithId::new(Id::new(id.to_owned()), station));
+    let stored = client
+        .put_shared_mobility_stations(stations.to_vec())
+        .await
+        .expect("stations are stored");
+    let stored_ids = stored
+        .iter()
+        .map(|station| station.id.raw())
+        .collect::<Vec<_>>();
+    assert_eq!(stored_ids, vec!["test-ingestion-valid"]);
+    assert_eq!(client.invalid_coordinate_count(), 3);
+
+    let ids = stations.map(|station| station.id.raw());
+    let in_database: Vec<String> = sqlx::query_scalar(
+        "SELECT id FROM shared_mobility_stations WHERE id = ANY($1) ORDER BY id;",
+    )
+    .bind(&ids[..])
+    .fetch_all(&common::pool().await)
+    .await
+    .expect("stations are read");
+    assert_eq!(in_database, vec!["test-ingestion-valid"]);
+}
+
+#[tokio::test]
+async fn names_are_sanitized() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    database
+        .auto()
+        .put_origin(WithId::new(
+            Id::new(ORIGIN.to_owned()),
+            Origin {
+                name: ORIGIN.to_owned(),
+                priority: 0,
+            },
+        ))
+        .await
+        .expect("origin is stored");
+    let client = Server::new(database).client(ORIGIN);
+
+    let agency = Agency {
+        name: "<b></b>".to_owned(),
+        website: String::new(),
+        phone_number: None,
+        email: None,
+        fare_url: None,
+    };
+    match client
+        .push_agency(agency, Some("test-ingestion-agency".to_owned()))
+        .await
+    {
+        Err(RequestError::Other(why)) => assert!(why.is::<EmptyName>()),
+        result => panic!("agency without name is rejected, got {:?}", result),
+    }
+
+    let stations = [
+        (
+            "test-ingestion-tagged",
+            station("<b>Rathaus</b> ", 54.32, 10.13),
+        ),
+        ("test-ingestion-unnamed", station("<br>", 54.32, 10.13)),
+    ]
+    .map(|(id, station)| WithId::new(Id::new(id.to_owned()), station));
+    let stored = client
+        .put_shared_mobility_stations(stations.to_vec())
+        .await
+        .expect("stations are stored");
+    let stored = stored
+        .iter()
+        .map(|station| (station.id.raw(), station.content.name.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        stored,
+        vec![("test-ingestion-tagged".to_owned(), "Rathaus")]
+    );
+}
@@ -0,0 +1,53 @@
+//! Metrics of the instrumented database. The origin is committed, its id is
+//! specific to these tests.
+
+mod common;
+
+use model::{origin::Origin, stop::Stop, WithId};
+use public_transport::{
+    database::{Database, DatabaseOperations, DatabaseTransaction, Repo},
+    instrumented::InstrumentedDatabase,
+};
+use utility::id::Id;
+
+const ORIGIN: &str = "test-instrumented";
+
+#[tokio::test]
+async fn measures_operations_and_commits() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let database = InstrumentedDatabase::new(database);
+    let mut tx = database.transaction().await.expect("transaction begins");
+    tx.put_origin(WithId::new(
+        Id::new(ORIGIN.to_owned()),
+        Origin {
+            name: ORIGIN.to_owned(),
+            priority: 0,
+        },
+    ))
+    .await
+    .expect("origin is stored");
+    let missing =
+        Repo::<Stop>::get(&mut tx, Id::new("test-instrumented-missing".to_owned()))
+            .await
+            .expect("missing stop is read");
+    assert!(missing.source_data.is_empty());
+    tx.commit().await.expect("transaction is committed");
+
+    let metrics = database
+        .metrics()
+        .snapshot()
+        .into_iter()
+        .map(|metrics| (metrics.operation, metrics.calls, metrics.errors))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        metrics,
+        vec![
+            ("Database::transaction".to_owned(), 1, 0),
+            ("DatabaseOperations::put_origin".to_owned(), 1, 0),
+            ("DatabaseTransaction::commit".to_owned(), 1, 0),
+            ("Repo<Stop>::get".to_owned(), 1, 0),
+        ]
+    );
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use public_transport::{
+    client::Client,
+    database::{Database, UnconfirmedClear},
+    server::Server,
+    RequestError,
+};
+
+const ORIGIN: &str = "test-integrity";
+
+/// Invalid coordinates of all origins, as counted before clearing them.
+async fn invalid_count<D: Database>(client: &Client<D>) -> u64 {
+    client
+        .get_invalid_coordinates()
+        .await
+        .expect("invalid coordinates are counted")
+        .iter()
+        .map(|invalid| invalid.count as u64)
+        .sum()
+}
+
+#[tokio::test]
+async fn clears_invalid_coordinates_only_when_confirmed() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    // the client clears in its own transaction, so the stop is committed.
+    let pool = common::pool().await;
+    sqlx::query("INSERT INTO origins(id, name, priority) VALUES ($1, $1, 0)")
+        .bind(ORIGIN)
+        .execute(&pool)
+        .await
+        .expect("origin is inserted");
+    sqlx::query(
+        "
+        INSERT INTO stops(id, origin, name, latitude, longitude)
+        VALUES ('test-integrity-1', $1, 'Kiel Hbf', 540.32, 10.13);
+        ",
+    )
+    .bind(ORIGIN)
+    .execute(&pool)
+    .await
+    .expect("stop is inserted");
+
+    let client = Server::new(database).client(ORIGIN);
+    let expected = invalid_count(&client).await;
+    assert!(expected >= 1);
+
+    let unconfirmed = client.clear_invalid_coordinates(expected + 1).await;
+    let Err(RequestError::Other(error)) = unconfirmed else {
+        panic!("clearing an unconfirmed count fails");
+    };
+    assert_eq!(
+        error.downcast_ref::<UnconfirmedClear>(),
+        Some(&UnconfirmedClear {
+            expected: expected + 1,
+            found: expected,
+        })
+    );
+    assert_eq!(invalid_count(&client).await, expected, "nothing is cleared");
+
+    assert_eq!(
+        client
+            .clear_invalid_coordinates(expected)
+            .await
+            .expect("confirmed count is cleared"),
+        expected
+    );
+    assert_eq!(invalid_count(&client).await, 0);
+
+    sqlx::query("DELETE FROM stops WHERE origin = $1")
+        .bind(ORIGIN)
+        .execute(&pool)
+        .await
+        .expect("stop is deleted");
+    sqlx::query("DELETE FROM origins WHERE id = $1")
+        .bind(ORIGIN)
+        .execute(&pool)
+        .await
+        .expect("origin is deleted");
+}
@@ -0,0 +1,557 @@
+mod common;
+
+use chrono::{Duration, NaiveDate};
+use model::{
+    agency::Agency,
+    calendar::{CalendarDate, ServiceExceptionType},
+    fixtures::StopBuilder,
+    line::{Line, LineType},
+    shape::{LineShape, ShapePoint},
+    stop::Stop,
+    trip::{StopTime, Trip, TripDirection},
+    DatabaseEntry, WithId, WithOrigin,
+};
+use public_transport::database::{
+    LineRepo, Repo, ServiceRepo, ShapeRepo, StopRepo, TripRepo,
+};
+use utility::id::Id;
+
+const ORIGIN: &str = "test-line";
+/// Origin of a higher priority, which attributes lines to other agencies.
+const OTHER_ORIGIN: &str = "test-line-other";
+
+fn stop_time(stop_sequence: i32, hours: i64, minutes: i64) -> StopTime {
+    let time = Duration::hours(hours) + Duration::minutes(minutes);
+    StopTime {
+        stop_sequence,
+        stop_id: None,
+        arrival_time: Some(time),
+        departure_time: Some(time),
+        stop_headsign: None,
+        pickup_type: None,
+        drop_off_type: None,
+        area_reference: None,
+        stop_name: None,
+    }
+}
+
+#[tokio::test]
+async fn departure_spans_exclude_last_stops_and_exceed_midnight() {
+    let Some(db) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&db, ORIGIN).await;
+    let origin = Id::new(ORIGIN.to_owned());
+    let service_day = NaiveDate::from_ymd_opt(2024, 3, 8).unwrap();
+    let (service_id, _) = tx
+        .put_calendar_date(
+            None,
+            CalendarDate {
+                date: service_day,
+                exception_type: ServiceExceptionType::Added,
+            },
+        )
+        .await
+        .expect("service is stored");
+    let line_id = Id::new("test-line-span".to_owned());
+    tx.put(WithOrigin::new(
+        origin.clone(),
+        WithId::new(
+            line_id.clone(),
+            Line {
+                name: Some("11".to_owned()),
+                kind: LineType::Bus,
+                agency_id: None,
+                secondary_agency_ids: vec![],
+                updated_at: None,
+            },
+        ),
+    ))
+    .await
+    .expect("line is stored");
+
+    let trips = [
+        (
+            "test-line-span-morning",
+            vec![stop_time(1, 5, 10), stop_time(2, 6, 0)],
+        ),
+        (
+            "test-line-span-night",
+            vec![
+                stop_time(1, 24, 50),
+                stop_time(2, 25, 30),
+                stop_time(3, 26, 0),
+            ],
+        ),
+    ];
+    for (id, stops) in trips {
+        let trip_id = Id::new(id.to_owned());
+        tx.put(WithOrigin::new(
+            origin.clone(),
+            WithId::new(
+                trip_id.clone(),
+                Trip {
+                    line_id: line_id.clone(),
+                    service_id: Some(service_id),
+                    headsign: None,
+                    short_name: None,
+                    direction: None,
+                    shape_id: None,
+                    stops: vec![],
+                    frequencies: vec![],
+                    updated_at: None,
+                },
+            ),
+        ))
+        .await
+        .expect("trip is stored");
+        tx.put_stop_times(&trip_id, &origin, &stops, false)
+            .await
+            .expect("stop times are stored");
+    }
+
+    let span = tx
+        .get_departure_span(&line_id, service_day)
+        .await
+        .expect("span is read");
+    assert_eq!(
+        span,
+        Some((
+            Duration::hours(5) + Duration::minutes(10),
+            Duration::hours(25) + Duration::minutes(30)
+        ))
+    );
+    let other_day = service_day + Duration::days(1);
+    let span = tx
+        .get_departure_span(&line_id, other_day)
+        .await
+        .expect("span is read");
+    assert_eq!(span, None);
+}
+
+#[tokio::test]
+async fn search_matches_wildcards_literally() {
+    let Some(db) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&db, ORIGIN).await;
+    let origin = Id::new(ORIGIN.to_owned());
+    for (id, name) in [
+        ("test-line-search-re7", "RE 7"),
+        ("test-line-search-re70", "RE 70"),
+        ("test-line-search-re17", "RE17"),
+        ("test-line-search-underscore", "RE_7"),
+    ] {
+        tx.put(WithOrigin::new(
+            origin.clone(),
+            WithId::new(
+                Id::new(id.to_owned()),
+                Line {
+                    name: Some(name.to_owned()),
+                    kind: LineType::RegionalRail,
+                    agency_id: None,
+                    secondary_agency_ids: vec![],
+                    updated_at: None,
+                },
+            ),
+        ))
+        .await
+        .expect("line is stored");
+    }
+    let cases = [
+        (
+            "RE 7",
+            vec!["test-line-search-re7", "test-line-search-re70"],
+        ),
+        ("re7", vec!["test-line-search-re7"]),
+        ("e 7", vec!["test-line-search-re7", "test-line-search-re70"]),
+        ("RE_7", vec!["test-line-search-underscore"]),
+        ("RE%7", vec![]),
+    ];
+    for (pattern, expected) in cases {
+        let found = tx
+            .search_by_name(pattern, 10)
+            .await
+            .expect("lines are found")
+            .into_iter()
+            .filter(|line| line.id.raw().starts_with("test-line-search-"))
+            .map(|line| line.id.raw())
+            .collect::<Vec<_>>();
+        assert_eq!(found, expected, "lines of the pattern `{:?}`", pattern);
+    }
+}
+
+fn shape_point(latitude: f64, longitude: f64) -> ShapePoint {
+    ShapePoint {
+        latitude,
+        longitude,
+        distance: None,
+    }
+}
+
+#[tokio::test]
+async fn the_longest_shape_of_each_direction_represents_a_line() {
+    let Some(db) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&db, ORIGIN).await;
+    let origin = Id::new(ORIGIN.to_owned());
+    let line_id = Id::new("test-line-shape".to_owned());
+    tx.put(WithOrigin::new(
+        origin.clone(),
+        WithId::new(
+            line_id.clone(),
+            Line {
+                name: Some("11".to_owned()),
+                kind: LineType::Bus,
+                agency_id: None,
+                secondary_agency_ids: vec![],
+                updated_at: None,
+            },
+        ),
+    ))
+    .await
+    .expect("line is stored");
+
+    // a short turn to the university and the full route to the hospital.
+    let mut shape_ids = vec![];
+    for points in [
+        vec![shape_point(54.3150, 10.1318), shape_point(54.3386, 10.1224)],
+        vec![
+            shape_point(54.3150, 10.1318),
+            shape_point(54.3386, 10.1224),
+            shape_point(54.3530, 10.1050),
+        ],
+    ] {
+        let shape_id = tx.create_shape_id().await.expect("shape is created");
+        let points = points
+            .into_iter()
+            .enumerate()
+            .map(|(index, point)| (index as i32, point))
+            .collect();
+        tx.put_shape_points(&shape_id, points)
+            .await
+            .expect("points are stored");
+        shape_ids.push(shape_id);
+    }
+    let (short, long) = (shape_ids[0], shape_ids[1]);
+
+    let outbound = Some(TripDirection::Outbound);
+    let inbound = Some(TripDirection::Inbound);
+    let trips = [
+        ("test-line-shape-short", outbound, Some(short)),
+        ("test-line-shape-long", outbound, Some(long)),
+        ("test-line-shape-back", inbound, Some(short)),
+        ("test-line-shape-none", inbound, None),
+    ];
+    for (id, direction, shape_id) in trips {
+        tx.put(WithOrigin::new(
+            origin.clone(),
+            WithId::new(
+                Id::new(id.to_owned()),
+                Trip {
+                    line_id: line_id.clone(),
+                    service_id: None,
+                    headsign: None,
+                    short_name: None,
+                    direction,
+                    shape_id,
+                    stops: vec![],
+                    frequencies: vec![],
+                    updated_at: None,
+                },
+            ),
+        ))
+        .await
+        .expect("trip is stored");
+    }
+
+    let shapes = tx.get_line_shapes(&line_id).await.expect("shapes are read");
+    let representatives = LineShape::representatives(shapes)
+        .into_iter()
+        .map(|shape| (shape.direction, shape.shape_id, shape.shape.points.len()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        representatives,
+        vec![(outbound, long, 3), (inbound, short, 2)]
+    );
+}
+
+#[tokio::test]
+async fn lines_of_stops_at_once_equal_the_lines_of_each_stop() {
+    let Some(db) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&db, ORIGIN).await;
+    let origin = Id::new(ORIGIN.to_owned());
+    let stop_ids = ["west", "centre", "east", "unserved"]
+        .map(|name| Id::<Stop>::new(format!("test-line-stops-{}", name)));
+    for stop_id in stop_ids.iter() {
+        tx.put(WithOrigin::new(
+            origin.clone(),
+            WithId::new(
+                stop_id.clone(),
+                StopBuilder::new(stop_id.raw_ref::<str>())
+                    .at(54.32, 10.13)
+                    .build(),
+            ),
+        ))
+        .await
+        .expect("stop is stored");
+    }
+    let [west, centre, east, unserved] = stop_ids.each_ref();
+
+    // both lines serve the centre, one of them with two trips.
+    let lines = [
+        (
+            "test-line-stops-11",
+            vec![vec![west, centre], vec![centre, west]],
+        ),
+        ("test-line-stops-12", vec![vec![centre, east]]),
+    ];
+    for (line_id, trips) in lines {
+        let line_id = Id::new(line_id.to_owned());
+        tx.put(WithOrigin::new(
+            origin.clone(),
+            WithId::new(
+                line_id.clone(),
+                Line {
+                    name: None,
+                    kind: LineType::Bus,
+                    agency_id: None,
+                    secondary_agency_ids: vec![],
+                    updated_at: None,
+                },
+            ),
+        ))
+        .await
+        .expect("line is stored");
+        for (index, stops) in trips.into_iter().enumerate() {
+            let trip_id = Id::new(format!("{}-{}", line_id.raw(), index));
+            tx.put(WithOrigin::new(
+                origin.clone(),
+                WithId::new(
+                    trip_id.clone(),
+                    Trip {
+                        line_id: line_id.clone(),
+                        service_id: None,
+                        headsign: None,
+                        short_name: None,
+                        direction: None,
+                        shape_id: None,
+                        stops: vec![],
+                        frequencies: vec![],
+                        updated_at: None,
+                    },
+                ),
+            ))
+            .await
+            .expect("trip is stored");
+            let stop_times = stops
+                .into_iter()
+                .enumerate()
+                .map(|(sequence, stop_id)| StopTime {
+                    stop_id: Some(stop_id.clone()),
+                    ..stop_time(sequence as i32, 8, sequence as i64)
+                })
+                .collect::<Vec<_>>();
+            tx.put_stop_times(&trip_id, &origin, &stop_times, false)
+                .await
+                .expect("stop times are stored");
+        }
+    }
+
+    let line_ids = |lines: &[DatabaseEntry<Line>]| {
+        let mut ids = lines.iter().map(|line| line.id.raw()).collect::<Vec<_>>();
+        ids.sort();
+        ids
+    };
+    let stop_refs = stop_ids.iter().collect::<Vec<_>>();
+    let at_once = tx
+        .get_by_stop_ids(&stop_refs)
+        .await
+        .expect("lines of the stops are read");
+    for stop_id in stop_ids.iter() {
+        let each = tx
+            .get_by_stop_id(stop_id)
+            .await
+            .expect("lines of the stop are read");
+        let batched = at_once.get(stop_id).map(Vec::as_slice).unwrap_or_default();
+        assert_eq!(
+            line_ids(batched),
+            line_ids(&each),
+            "lines of stop {:?}",
+            stop_id
+        );
+    }
+    assert_eq!(
+        at_once.get(centre).map(|lines| line_ids(lines)),
+        Some(vec![
+            "test-line-stops-11".to_owned(),
+            "test-line-stops-12".to_owned()
+        ])
+    );
+    assert_eq!(
+        at_once.get(east).map(|lines| line_ids(lines)),
+        Some(vec!["test-line-stops-12".to_owned()])
+    );
+    assert!(
+        !at_once.contains_key(unserved),
+        "stops without lines are omitted"
+    );
+}
+
+#[tokio::test]
+async fn codeshares_keep_the_primary_agency_of_the_higher_priority_origin() {
+    let Some(db) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&db, ORIGIN).await;
+    common::put_origin(&mut tx, OTHER_ORIGIN).await;
+    let [origin, other_origin] =
+        [ORIGIN, OTHER_ORIGIN].map(|origin| Id::new(origin.to_owned()));
+    let agency_id = |name: &str| Id::<Agency>::new(format!("test-line-{}", name));
+    let line_id = Id::<Line>::new("test-line-re83".to_owned());
+    // the lower priority origin attributes the line to DB Regio and the
+    // Verkehrsverbund, the other one only to NAH.SH.
+    for (origin, primary, secondary) in [
+        (&origin, "db-regio", vec!["vrs"]),
+        (&other_origin, "nah-sh", vec![]),
+    ] {
+        for name in secondary.iter().chain([&primary]) {
+            tx.put(WithOrigin::new(
+                origin.clone(),
+                WithId::new(
+                    agency_id(name),
+                    Agency {
+                        name: name.to_string(),
+                        website: String::new(),
+                        phone_number: None,
+                        email: None,
+                        fare_url: None,
+                    },
+                ),
+            ))
+            .await
+            .expect("agency is stored");
+        }
+        tx.put(WithOrigin::new(
+            origin.clone(),
+            WithId::new(
+                line_id.clone(),
+                Line {
+                    name: Some("RE83".to_owned()),
+                    kind: LineType::RegionalRail,
+                    agency_id: Some(agency_id(primary)),
+                    secondary_agency_ids: secondary
+                        .iter()
+                        .map(|name| agency_id(name))
+                        .collect(),
+                    updated_at: None,
+                },
+            ),
+        ))
+        .await
+        .expect("line is stored");
+    }
+
+    let agencies = |line: WithId<Line>| {
+        (
+            line.content.agency_id.map(|id| id.raw()),
+            line.content
+                .secondary_agency_ids
+                .into_iter()
+                .map(|id| id.raw())
+                .collect::<Vec<_>>(),
+        )
+    };
+    let names = |names: &[&str]| {
+        names
+            .iter()
+            .map(|name| agency_id(name).raw())
+            .collect::<Vec<_>>()
+    };
+    // the last origin has the highest priority.
+    let cases = [
+        (
+            [origin.clone(), other_origin.clone()],
+            (Some(agency_id("nah-sh").raw()), names(&["db-regio", "vrs"])),
+        ),
+        (
+            [other_origin.clone(), origin.clone()],
+            (Some(agency_id("db-regio").raw()), names(&["vrs", "nah-sh"])),
+        ),
+    ];
+    for (origins, expected) in cases {
+        // repeated reads give the same attribution.
+        for _ in 0..2 {
+            let line = Repo::<Line>::get(&mut tx, line_id.clone())
+                .await
+                .expect("line is read")
+                .merge_from(&origins)
+                .expect("line has data");
+            assert_eq!(agencies(line), expected, "agencies of {:?}", origins);
+        }
+    }
+
+    // stops of lines the agency is a secondary agency of are listed on request.
+    let stop_id = Id::<Stop>::new("test-line-codeshare-stop".to_owned());
+    tx.put(WithOrigin::new(
+        origin.clone(),
+        WithId::new(
+            stop_id.clone(),
+            StopBuilder::new("Eckernförde").at(54.47, 9.83).build(),
+        ),
+    ))
+    .await
+    .expect("stop is stored");
+    let trip_id = Id::new("test-line-re83-0".to_owned());
+    tx.put(WithOrigin::new(
+        origin.clone(),
+        WithId::new(
+            trip_id.clone(),
+            Trip {
+                line_id: line_id.clone(),
+                service_id: None,
+                headsign: None,
+                short_name: None,
+                direction: None,
+                shape_id: None,
+                stops: vec![],
+                frequencies: vec![],
+                updated_at: None,
+            },
+        ),
+    ))
+    .await
+    .expect("trip is stored");
+    let stop_times = [StopTime {
+        stop_id: Some(stop_id.clone()),
+        ..stop_time(1, 8, 0)
+    }];
+    tx.put_stop_times(&trip_id, &origin, &stop_times, false)
+        .await
+        .expect("stop times are stored");
+    for (include_secondary, expected) in
+        [(false, vec![]), (true, vec![stop_id.raw()])]
+    {
+        let stops = tx
+            .get_page_by_agency_after(
+                &agency_id("vrs"),
+                include_secondary,
+                None,
+                None,
+                std::slice::from_ref(&origin),
+            )
+            .await
+            .expect("stops are read")
+            .into_iter()
+            .map(|stop| stop.id.raw())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stops, expected,
+            "stops including secondary agencies: {}",
+            include_secondary
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! Merge candidates of stops in a dense area, which is committed. Ids are specific
+//! to these tests, so that repeated runs update the same rows.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use database::PgDatabase;
+use model::{
+    filter_sort_subjects, fixtures::StopBuilder, merge::MergeStatus, stop::Stop,
+    WithId, WithOrigin,
+};
+use public_transport::database::{Database, DatabaseTransaction, MergableRepo, Repo};
+use utility::id::Id;
+
+const ORIGIN: &str = "test-merge-candidates";
+/// Origin of the stops, whose candidates are looked up.
+const NEW_ORIGIN: &str = "test-merge-candidates-new";
+
+/// Center of the grid near Hamburg Hbf.
+const LATITUDE: f64 = 53.553;
+const LONGITUDE: f64 = 10.006;
+/// Stops per row and column of the grid, which are about 10 m apart.
+const GRID_SIZE: usize = 30;
+const GRID_STEP_LATITUDE: f64 = 0.00009;
+const GRID_STEP_LONGITUDE: f64 = 0.00015;
+
+const NAMES: [&str; 6] = [
+    "Hamburg Hbf",
+    "Hauptbahnhof Süd",
+    "Steintorwall",
+    "Hamburg Hauptbahnhof",
+    "Mönckebergstraße",
+    "ZOB Hamburg",
+];
+const PARENT: &str = "test-merge-candidates-parent";
+
+/// Connects to the test database, limiting merge candidates to `limit`.
+async fn connect(limit: Option<usize>) -> Option<PgDatabase> {
+    let mut connection_info = common::connection_info()?;
+    connection_info.merge_candidate_limit = limit;
+    let database = PgDatabase::connect(connection_info)
+        .await
+        .expect("test database is reachable");
+    Some(database)
+}
+
+/// Stores the grid of stops with names, platforms and parents in turn.
+async fn put_grid(database: &PgDatabase) {
+    let mut tx = database.transaction().await.expect("transaction begins");
+    common::put_origin(&mut tx, ORIGIN).await;
+    let origin = Id::new(ORIGIN.to_owned());
+    let put = |id: String, stop: Stop| {
+        WithOrigin::new(origin.clone(), WithId::new(Id::new(id), stop))
+    };
+    tx.put(put(
+        PARENT.to_owned(),
+        StopBuilder::new("Hamburg Hbf")
+            .at(LATITUDE, LONGITUDE)
+            .build(),
+    ))
+    .await
+    .expect("parent is stored");
+    for row in 0..GRID_SIZE {
+        for column in 0..GRID_SIZE {
+            let index = row * GRID_SIZE + column;
+            let mut stop = StopBuilder::new(NAMES[index % NAMES.len()]).at(
+                LATITUDE + (row as f64 - GRID_SIZE as f64 / 2.0) * GRID_STEP_LATITUDE,
+                LONGITUDE
+                    + (column as f64 - GRID_SIZE as f64 / 2.0) * GRID_STEP_LONGITUDE,
+            );
+            if !index.is_multiple_of(3) {
+                stop = stop.platform(&(index % 12 + 1).to_string());
+            }
+            if index.is_multiple_of(4) {
+                stop = stop.parent(PARENT);
+            }
+            tx.put(put(format!("{}-{}", ORIGIN, index), stop.build()))
+                .await
+                .expect("stop is stored");
+        }
+    }
+    tx.commit().await.expect("transaction is committed");
+}
+
+/// Stops to be inserted, whose merge is decided.
+fn cases() -> Vec<Stop> {
+    let center = |name| StopBuilder::new(name).at(LATITUDE, LONGITUDE);
+    let corner = |name| {
+        StopBuilder::new(name).at(
+            LATITUDE + GRID_SIZE as f64 / 2.0 * GRID_STEP_LATITUDE,
+            LONGITUDE - GRID_SIZE as f64 / 2.0 * GRID_STEP_LONGITUDE,
+        )
+    };
+    vec![
+        center("Hamburg Hauptbahnhof").platform("5").build(),
+        center("Hamburg Hbf").build(),
+        corner("Hbf Hamburg").build(),
+        center("Steintorwall").platform("7").parent(PARENT).build(),
+        corner("Mönckebergstr.").platform("2").build(),
+        StopBuilder::new("Hamburg Hbf").build(),
+        StopBuilder::new("Kiel Hbf").at(54.3152, 10.1318).build(),
+    ]
+}
+
+/// Candidate, which is merged with the stop, as decided by
+/// `Client::push_stop` without rejected merges.
+fn decision(
+    stop: &Stop,
+    candidates: Vec<WithOrigin<WithId<Stop>>>,
+) -> Option<(String, String, MergeStatus)> {
+    let (similarity, candidate) =
+        filter_sort_subjects(stop, candidates).into_iter().next()?;
+    let status = MergeStatus::from_similarity(similarity)?;
+    Some((candidate.content.id.raw(), candidate.origin.raw(), status))
+}
+
+async fn candidates(
+    database: &PgDatabase,
+    stop: &Stop,
+) -> Vec<WithOrigin<WithId<Stop>>> {
+    database
+        .auto()
+        .merge_candidates(stop, &Id::new(NEW_ORIGIN.to_owned()))
+        .await
+        .expect("candidates are read")
+}
+
+#[tokio::test]
+async fn limited_candidates_lead_to_the_same_merge() {
+    let Some(limited) = connect(Some(database::DEFAULT_MERGE_CANDIDATE_LIMIT)).await
+    else {
+        return;
+    };
+    let unlimited = connect(None).await.expect("test database is configured");
+    put_grid(&limited).await;
+
+    for stop in cases() {
+        let all = candidates(&unlimited, &stop).await;
+        let some = candidates(&limited, &stop).await;
+        assert!(some.len() <= database::DEFAULT_MERGE_CANDIDATE_LIMIT);
+        if stop.location.is_some() && all.len() > some.len() {
+            assert_eq!(
+                some.iter().map(|c| &c.content.id).collect::<Vec<_>>(),
+                all.iter()
+                    .take(some.len())
+                    .map(|c| &c.content.id)
+                    .collect::<Vec<_>>(),
+                "candidates are ranked deterministically for {:?}",
+                stop.name
+            );
+        }
+        let expected = decision(&stop, all);
+        assert_eq!(decision(&stop, some), expected, "{:?}", stop);
+    }
+    // the dense cases are limited at all, and merged with a stop of the grid.
+    let dense = &cases()[0];
+    let all = candidates(&unlimited, dense).await;
+    assert!(all.len() > database::DEFAULT_MERGE_CANDIDATE_LIMIT);
+    let (_, origin, _) = decision(dense, all).expect("stop is merged");
+    assert_eq!(origin, ORIGIN);
+}
+
+/// Candidates looked up and scored per inserted stop.
+const MEASURED_RUNS: u32 = 20;
+
+/// Compares looking up and scoring the merge candidates of a stop in the dense
+/// grid with and without the limit. Run with
+/// `cargo test -p database --test merge_candidates -- --ignored --nocapture`.
+#[tokio::test]
+#[ignore = "measurement"]
+async fn measure_merge_candidates_in_a_dense_grid() {
+    let Some(limited) = connect(Some(database::DEFAULT_MERGE_CANDIDATE_LIMIT)).await
+    else {
+        return;
+    };
+    let unlimited = connect(None).await.expect("test database is configured");
+    put_grid(&limited).await;
+
+    let stop = &cases()[0];
+    for (label, database) in [("limited", &limited), ("unlimited", &unlimited)] {
+        let mut elapsed = Duration::ZERO;
+        let mut count = 0;
+        for _ in 0..MEASURED_RUNS {
+            let now = Instant::now();
+            let candidates = candidates(database, stop).await;
+            count = candidates.len();
+            decision(stop, candidates);
+            elapsed += now.elapsed();
+        }
+        println!(
+            "{}: {} candidates looked up and scored in {:?} (average of {} runs).",
+            label,
+            count,
+            elapsed / MEASURED_RUNS,
+            MEASURED_RUNS
+        );
+    }
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use chrono::Local;
+use model::merge::{MergeLogEntry, MergeStatus, MergeSubject};
+use public_transport::database::MergeLogRepo;
+use utility::id::Id;
+
+const ORIGIN: &str = "test-merge-log";
+
+fn entry(subject_original_id: Option<&str>) -> MergeLogEntry {
+    MergeLogEntry {
+        subject: MergeSubject::Stop,
+        origin: Id::new(ORIGIN.to_owned()),
+        subject_id: "test-merge-log-subject".to_owned(),
+        subject_original_id: subject_original_id.map(str::to_owned),
+        candidate_id: "test-merge-log-candidate".to_owned(),
+        similarity: 0.8,
+        status: MergeStatus::PendingReview,
+        created_at: Local::now(),
+        decided_at: None,
+    }
+}
+
+#[tokio::test]
+async fn rejections_are_tied_to_the_rejected_pair() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    let origin = Id::new(ORIGIN.to_owned());
+    let entry = tx
+        .log_merge(entry(Some("8000199")))
+        .await
+        .expect("merge is logged");
+    tx.decide_merge(&entry.id, MergeStatus::Rejected)
+        .await
+        .expect("merge is rejected");
+
+    let cases = [
+        (Some("8000199"), "test-merge-log-candidate", true),
+        (Some("8000199"), "test-merge-log-other", false),
+        (Some("8000200"), "test-merge-log-candidate", false),
+        (None, "test-merge-log-candidate", false),
+    ];
+    for (original_id, candidate_id, expected) in cases {
+        let is_rejected = tx
+            .is_merge_rejected(MergeSubject::Stop, &origin, original_id, candidate_id)
+            .await
+            .expect("rejection is queried");
+        assert_eq!(
+            is_rejected, expected,
+            "{:?} into {}",
+            original_id, candidate_id
+        );
+    }
+}
+
+#[tokio::test]
+async fn rejections_of_subjects_without_original_id_are_recognized() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    let origin = Id::new(ORIGIN.to_owned());
+    let entry = tx.log_merge(entry(None)).await.expect("merge is logged");
+    tx.decide_merge(&entry.id, MergeStatus::Rejected)
+        .await
+        .expect("merge is rejected");
+
+    let cases = [(None, true), (Some("8000199"), false)];
+    for (original_id, expected) in cases {
+        let is_rejected = tx
+            .is_merge_rejected(
+                MergeSubject::Stop,
+                &origin,
+                original_id,
+                "test-merge-log-candidate",
+            )
+            .await
+            .expect("rejection is queried");
+        assert_eq!(is_rejected, expected, "{:?}", original_id);
+    }
+}
+
+#[tokio::test]
+async fn merges_are_decided_at_most_once() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    let entry = tx
+        .log_merge(entry(Some("8000199")))
+        .await
+        .expect("merge is logged");
+
+    let confirmed = tx
+        .decide_merge(&entry.id, MergeStatus::Confirmed)
+        .await
+        .expect("merge is confirmed");
+    assert_eq!(
+        confirmed.map(|entry| entry.status),
+        Some(MergeStatus::Confirmed)
+    );
+    let rejected = tx
+        .decide_merge(&entry.id, MergeStatus::Rejected)
+        .await
+        .expect("decision is attempted");
+    assert!(rejected.is_none());
+    let stored = tx
+        .get_merge_log_entry(&entry.id)
+        .await
+        .expect("merge is stored");
+    assert_eq!(stored.status, MergeStatus::Confirmed);
+}
@@ -0,0 +1,67 @@
+//! Connecting without applying migrations. The test creates a database of its
+//! own, which has not been migrated yet.
+
+mod common;
+
+use database::{PendingMigrationsError, PgDatabase};
+
+const UNMIGRATED_DATABASE: &str = "public_transport_unmigrated";
+
+#[tokio::test]
+async fn refuses_databases_with_pending_migrations_without_auto_migrate() {
+    let Some(mut connection_info) = common::connection_info() else {
+        return;
+    };
+    connection_info.auto_migrate = false;
+    PgDatabase::connect(connection_info)
+        .await
+        .expect("migrated database is used without auto migrate");
+
+    let pool = common::pool().await;
+    // `CREATE DATABASE` takes no bind parameters.
+    for statement in [
+        format!("DROP DATABASE IF EXISTS {}", UNMIGRATED_DATABASE),
+        format!("CREATE DATABASE {}", UNMIGRATED_DATABASE),
+    ] {
+        sqlx::query(&statement)
+            .execute(&pool)
+            .await
+            .expect("unmigrated database is created");
+    }
+    let mut connection_info = common::connection_info().unwrap();
+    connection_info.database = UNMIGRATED_DATABASE.to_owned();
+    connection_info.auto_migrate = false;
+
+    let error = PgDatabase::connect(connection_info)
+        .await
+        .err()
+        .expect("unmigrated database is refused");
+    let pending = error
+        .downcast_ref::<PendingMigrationsError>()
+        .expect("pending migrations are the reason");
+    assert_eq!(
+        pending.0.first().map(|migration| migration.version),
+        Some(1)
+    );
+
+    // nothing was written, not even the tables of the partition maintenance.
+    let unmigrated = sqlx::PgPool::connect(
+        &common::url()
+            .unwrap()
+            .replace("/public_transport", &format!("/{}", UNMIGRATED_DATABASE)),
+    )
+    .await
+    .expect("unmigrated database is reachable");
+    let tables: i64 = sqlx::query_scalar(
+        "SELECT count(*) FROM pg_tables WHERE schemaname = 'public'",
+    )
+    .fetch_one(&unmigrated)
+    .await
+    .expect("tables are counted");
+    assert_eq!(tables, 0);
+    unmigrated.close().await;
+    sqlx::query(&format!("DROP DATABASE {}", UNMIGRATED_DATABASE))
+        .execute(&pool)
+        .await
+        .expect("unmigrated database is dropped");
+}
@@ -0,0 +1,91 @@
+//! Inserts of many rows in chunks, into a temporary table of a transaction.
+
+mod common;
+
+use database::queries::{InsertInto, MAX_CHUNK_SIZE};
+use sqlx::{postgres::PgArguments, Arguments};
+
+/// Rows inserted, which take three chunks.
+const ROWS: usize = 2 * MAX_CHUNK_SIZE + MAX_CHUNK_SIZE / 2;
+
+struct Row {
+    id: i32,
+    name: String,
+}
+
+fn bind(mut args: PgArguments, row: &Row) -> PgArguments {
+    args.add(row.id);
+    args.add(row.name.clone());
+    args
+}
+
+#[tokio::test]
+async fn rows_are_inserted_in_chunks() {
+    if common::url().is_none() {
+        return;
+    }
+    let pool = common::pool().await;
+    let mut tx = pool.begin().await.expect("transaction begins");
+    sqlx::query(
+        "CREATE TEMPORARY TABLE multi_row_insert(id INT PRIMARY KEY, name TEXT)",
+    )
+    .execute(&mut *tx)
+    .await
+    .expect("table is created");
+
+    let rows = (0..ROWS as i32)
+        .map(|id| Row {
+            id,
+            name: format!("row {}", id),
+        })
+        .collect::<Vec<_>>();
+    let values = rows.iter().collect::<Vec<_>>();
+    let inserted = InsertInto::new("multi_row_insert", &["id", "name"])
+        .values(&values)
+        .binder(bind)
+        .execute(&mut *tx)
+        .await
+        .expect("rows are inserted");
+    assert_eq!(inserted, ROWS as u64);
+    let (count, names): (i64, i64) =
+        sqlx::query_as("SELECT COUNT(*), COUNT(DISTINCT name) FROM multi_row_insert")
+            .fetch_one(&mut *tx)
+            .await
+            .expect("rows are counted");
+    assert_eq!((count, names), (ROWS as i64, ROWS as i64));
+
+    // conflicting rows of every chunk are updated.
+    let renamed = rows
+        .iter()
+        .filter(|row| row.id % 2 == 0)
+        .map(|row| Row {
+            id: row.id,
+            name: format!("renamed {}", row.id),
+        })
+        .collect::<Vec<_>>();
+    let values = renamed.iter().collect::<Vec<_>>();
+    let updated = InsertInto::new("multi_row_insert", &["id", "name"])
+        .on_conflict(&["id"])
+        .values(&values)
+        .binder(bind)
+        .execute(&mut *tx)
+        .await
+        .expect("rows are updated");
+    assert_eq!(updated, renamed.len() as u64);
+    let renamed_count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM multi_row_insert WHERE name LIKE 'renamed %'",
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .expect("rows are counted");
+    assert_eq!(renamed_count, renamed.len() as i64);
+
+    // nothing is executed without values, not even on a pool.
+    let inserted = InsertInto::new("multi_row_insert", &["id", "name"])
+        .values::<Row>(&[])
+        .binder(bind)
+        .execute(&pool)
+        .await
+        .expect("nothing is inserted");
+    assert_eq!(inserted, 0);
+}
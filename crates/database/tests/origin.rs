@@ -0,0 +1,269 @@
+mod common;
+
+use std::fmt::Debug;
+
+use chrono::Local;
+use model::{
+    fixtures::{StopBuilder, TripBuilder},
+    line::{Line, LineType},
+    origin::Origin,
+    stop::Stop,
+    trip::Trip,
+    trip_update::{TripStatus, TripUpdate, TripUpdateId},
+    DatabaseEntry, WithId, WithOrigin,
+};
+use public_transport::{
+    database::{DatabaseOperations, RealtimeRepo, Repo, SubjectRepo, TripRepo},
+    server::Server,
+};
+use serde::Serialize;
+use utility::id::{HasId, Id};
+
+const ORIGIN: &str = "test-origin";
+/// Origin of the renamed data, separate from `ORIGIN`, so that the tests do not
+/// wait for each other's transactions.
+const DATA_ORIGIN: &str = "test-origin-data";
+
+fn origin(id: &str) -> WithId<Origin> {
+    WithId::new(
+        Id::new(id.to_owned()),
+        Origin {
+            name: id.to_owned(),
+            priority: 0,
+        },
+    )
+}
+
+/// Origins of the source data of the entry.
+fn source_origins<T>(entry: DatabaseEntry<T>) -> Vec<String>
+where
+    T: Serialize + HasId,
+    T::IdType: Debug + Clone + Serialize,
+{
+    entry
+        .source_data
+        .into_iter()
+        .map(|source| source.origin.raw())
+        .collect()
+}
+
+/// Origin, which the given former id is an alias of.
+async fn aliased(tx: &mut impl DatabaseOperations, alias: &str) -> Option<String> {
+    tx.origin_by_alias(&Id::new(alias.to_owned()))
+        .await
+        .expect("alias is read")
+        .map(|id| id.raw())
+}
+
+#[tokio::test]
+async fn renamed_origins_are_found_by_their_former_id() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    assert_eq!(aliased(&mut tx, ORIGIN).await, None);
+
+    let renamed = tx
+        .rename_origin(&Id::new(ORIGIN.to_owned()), origin("test-origin-renamed"))
+        .await
+        .expect("origin is renamed");
+    assert_eq!(renamed.id.raw(), "test-origin-renamed");
+    assert_eq!(
+        aliased(&mut tx, ORIGIN).await.as_deref(),
+        Some("test-origin-renamed")
+    );
+    assert_eq!(aliased(&mut tx, "test-origin-renamed").await, None);
+}
+
+#[tokio::test]
+async fn aliases_follow_further_renames() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    tx.rename_origin(&Id::new(ORIGIN.to_owned()), origin("test-origin-b"))
+        .await
+        .expect("origin is renamed");
+    tx.rename_origin(
+        &Id::new("test-origin-b".to_owned()),
+        origin("test-origin-c"),
+    )
+    .await
+    .expect("origin is renamed again");
+
+    let cases = [
+        (ORIGIN, Some("test-origin-c")),
+        ("test-origin-b", Some("test-origin-c")),
+        ("test-origin-c", None),
+    ];
+    for (alias, expected) in cases {
+        assert_eq!(
+            aliased(&mut tx, alias).await.as_deref(),
+            expected,
+            "alias {}",
+            alias
+        );
+    }
+}
+
+#[tokio::test]
+async fn renaming_back_drops_the_alias_of_the_former_id() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    tx.rename_origin(&Id::new(ORIGIN.to_owned()), origin("test-origin-b"))
+        .await
+        .expect("origin is renamed");
+    tx.rename_origin(&Id::new("test-origin-b".to_owned()), origin(ORIGIN))
+        .await
+        .expect("origin is renamed back");
+
+    assert_eq!(aliased(&mut tx, ORIGIN).await, None);
+    assert_eq!(
+        aliased(&mut tx, "test-origin-b").await.as_deref(),
+        Some(ORIGIN)
+    );
+}
+
+#[tokio::test]
+async fn registering_a_renamed_origin_by_its_former_name_gives_the_renamed_one() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    // the client registers and renames in its own transactions, so the origin is
+    // committed.
+    let client = Server::new(database.clone()).client("test-origin");
+    let id = client
+        .put_origin("Test Origin Former", 0)
+        .await
+        .expect("origin is registered");
+    let renamed = client
+        .rename_origin(&id, "Test Origin Renamed")
+        .await
+        .expect("origin is renamed");
+
+    let registered = client
+        .put_origin("Test Origin Former", 0)
+        .await
+        .expect("former name is registered");
+    let origins = client.get_origin_ids().await.expect("origins are read");
+
+    let pool = common::pool().await;
+    sqlx::query("DELETE FROM origins WHERE id = $1")
+        .bind(renamed.id.raw())
+        .execute(&pool)
+        .await
+        .expect("origin is deleted");
+
+    assert_eq!(registered, renamed.id);
+    assert!(!origins.contains(&id), "former origin is not created anew");
+    assert!(origins.contains(&renamed.id));
+}
+
+#[tokio::test]
+async fn renamed_origins_take_their_data_along() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, DATA_ORIGIN).await;
+    let data_origin = Id::<Origin>::new(DATA_ORIGIN.to_owned());
+    let stops = ["test-origin-data-a", "test-origin-data-b"];
+    for (index, id) in stops.into_iter().enumerate() {
+        let stop = StopBuilder::new(id)
+            .at(54.2, 10.5 + index as f64 / 100.0)
+            .with_id(id);
+        tx.put(WithOrigin::new(data_origin.clone(), stop))
+            .await
+            .expect("stop is stored");
+    }
+    tx.put_original_id(
+        data_origin.clone(),
+        "a".to_owned(),
+        Id::<Stop>::new(stops[0].to_owned()),
+    )
+    .await
+    .expect("original id is stored");
+    tx.put(WithOrigin::new(
+        data_origin.clone(),
+        WithId::new(
+            Id::new("test-origin-data-line".to_owned()),
+            Line {
+                name: Some("1".to_owned()),
+                kind: LineType::Bus,
+                agency_id: None,
+                secondary_agency_ids: vec![],
+                updated_at: None,
+            },
+        ),
+    ))
+    .await
+    .expect("line is stored");
+    let trip_id = Id::<Trip>::new("test-origin-data-trip".to_owned());
+    let mut trip = TripBuilder::new("test-origin-data-line", 8, 0)
+        .stop(stops[0], 0)
+        .stop(stops[1], 5)
+        .with_id(trip_id.raw_ref::<str>());
+    let stop_times = std::mem::take(&mut trip.content.stops);
+    tx.put(WithOrigin::new(data_origin.clone(), trip))
+        .await
+        .expect("trip is stored");
+    tx.put_stop_times(&trip_id, &data_origin, &stop_times, true)
+        .await
+        .expect("stop times are stored");
+    let today = Local::now().date_naive();
+    tx.put_trip_updates(
+        &data_origin,
+        &[WithId::new(
+            Id::new(TripUpdateId::new(trip_id.clone(), today)),
+            TripUpdate {
+                status: TripStatus::Cancelled,
+                stops: vec![],
+                timestamp: Some(Local::now()),
+            },
+        )],
+    )
+    .await
+    .expect("trip update is stored");
+
+    // every foreign key containing the origin follows the rename, otherwise it
+    // fails.
+    let renamed = tx
+        .rename_origin(&data_origin, origin("test-origin-data-renamed"))
+        .await
+        .expect("origin is renamed");
+
+    let renamed_origins = vec![renamed.id.raw()];
+    let stop = Repo::<Stop>::get(&mut tx, Id::new(stops[0].to_owned()))
+        .await
+        .expect("stop is read");
+    assert_eq!(source_origins(stop), renamed_origins, "stop");
+    let trip = Repo::<Trip>::get(&mut tx, trip_id.clone())
+        .await
+        .expect("trip is read");
+    assert_eq!(source_origins(trip), renamed_origins, "trip");
+    for (origin, expected) in [(&data_origin, 0), (&renamed.id, 2)] {
+        let stop_times = tx
+            .get_stop_times(trip_id.clone(), origin.clone())
+            .await
+            .expect("stop times are read");
+        assert_eq!(stop_times.len(), expected, "stop times of {}", origin);
+    }
+    let update = tx
+        .get_realtime_for_trip(&trip_id, today, 0)
+        .await
+        .expect("trip update is read");
+    assert_eq!(source_origins(update), renamed_origins, "trip update");
+    for (origin, expected) in [(&data_origin, None), (&renamed.id, Some(stops[0]))] {
+        let id: Option<Id<Stop>> = tx
+            .id_by_original_id(origin.clone(), "a".to_owned())
+            .await
+            .expect("original id is looked up");
+        assert_eq!(
+            id.map(|id| id.raw()).as_deref(),
+            expected,
+            "original id of {}",
+            origin
+        );
+    }
+}
@@ -0,0 +1,218 @@
+mod common;
+
+use std::time::Duration as StdDuration;
+
+use chrono::{Local, NaiveDate};
+use database::PgDatabase;
+use model::{
+    fixtures::{stop_time, StopBuilder, TripBuilder},
+    line::{Line, LineType},
+    trip::Trip,
+    trip_update::{TripStatus, TripUpdate, TripUpdateId},
+    WithId, WithOrigin,
+};
+use public_transport::database::{RealtimeRepo, Repo, TripRepo};
+use serde::Serialize;
+use utility::id::{HasId, Id};
+
+const ORIGIN: &str = "test-partitions";
+
+fn with_id<T>(id: &str, content: T) -> WithOrigin<WithId<T>>
+where
+    T: Serialize + HasId<IdType = String>,
+{
+    WithOrigin::new(
+        Id::new(ORIGIN.to_owned()),
+        WithId::new(Id::new(id.to_owned()), content),
+    )
+}
+
+/// Whether the table is a partitioned table.
+async fn is_partitioned(pool: &sqlx::PgPool, table: &str) -> bool {
+    sqlx::query_scalar(
+        "
+        SELECT EXISTS (
+            SELECT 1 FROM pg_class
+            WHERE oid = to_regclass($1) AND relkind = 'p'
+        );
+        ",
+    )
+    .bind(table)
+    .fetch_one(pool)
+    .await
+    .expect("table is looked up")
+}
+
+/// Maintains the partitions as of the day, once no other process does.
+async fn maintain(database: &PgDatabase, today: NaiveDate) {
+    // connecting starts the maintenance in the background, which holds the lock
+    // until it is done.
+    while !database
+        .maintain_partitions(today, None)
+        .await
+        .expect("partitions are maintained")
+    {
+        tokio::time::sleep(StdDuration::from_millis(100)).await;
+    }
+}
+
+/// The partitions, which a query of trip updates of the day scans.
+async fn scanned_partitions(pool: &sqlx::PgPool, day: NaiveDate) -> Vec<String> {
+    let plan: Vec<String> = sqlx::query_scalar(&format!(
+        "EXPLAIN SELECT * FROM trip_updates WHERE trip_start_date = '{}';",
+        day
+    ))
+    .fetch_all(pool)
+    .await
+    .expect("query is explained");
+    // e.g. `Seq Scan on trip_updates_p2024_01 trip_updates`, bitmap index scans
+    // are on an index instead.
+    let mut partitions = plan
+        .iter()
+        .filter(|line| !line.contains("Bitmap Index Scan"))
+        .filter_map(|line| line.split(" on ").nth(1)?.split_whitespace().next())
+        .map(|relation| relation.to_owned())
+        .collect::<Vec<_>>();
+    partitions.dedup();
+    partitions
+}
+
+#[tokio::test]
+async fn repo_reads_and_writes_the_partitioned_tables() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    maintain(&database, Local::now().date_naive()).await;
+    let pool = common::pool().await;
+    assert!(is_partitioned(&pool, "trip_updates").await);
+    assert!(is_partitioned(&pool, "stop_times").await);
+    let today = Local::now().date_naive();
+    assert_eq!(
+        scanned_partitions(&pool, today).await,
+        [format!("trip_updates_p{}", today.format("%Y_%m"))]
+    );
+
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    tx.put(with_id(
+        "test-partitions-stop",
+        StopBuilder::new("Kiel Hbf").build(),
+    ))
+    .await
+    .expect("stop is stored");
+    tx.put(with_id(
+        "test-partitions-line",
+        Line {
+            name: Some("300".to_owned()),
+            kind: LineType::Bus,
+            agency_id: None,
+            secondary_agency_ids: vec![],
+            updated_at: None,
+        },
+    ))
+    .await
+    .expect("line is stored");
+    tx.put(with_id(
+        "test-partitions-trip",
+        TripBuilder::new("test-partitions-line", 0, 0).build(),
+    ))
+    .await
+    .expect("trip is stored");
+
+    let trip_id: Id<Trip> = Id::new("test-partitions-trip".to_owned());
+    let origin = Id::new(ORIGIN.to_owned());
+    for stop_time in [
+        stop_time(1, "test-partitions-stop", 0),
+        stop_time(2, "test-partitions-stop", 12),
+    ] {
+        tx.put_stop_time(trip_id.clone(), WithOrigin::new(origin.clone(), stop_time))
+            .await
+            .expect("stop time is stored");
+    }
+    let stop_times = tx
+        .get_stop_times(trip_id.clone(), origin.clone())
+        .await
+        .expect("stop times are read");
+    assert_eq!(
+        stop_times
+            .iter()
+            .map(|stop_time| stop_time.stop_sequence)
+            .collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+
+    // a trip of today lands in the partition of the current month.
+    tx.put_trip_updates(
+        &origin,
+        &[WithId::new(
+            Id::new(TripUpdateId::new(trip_id.clone(), today)),
+            TripUpdate {
+                status: TripStatus::Cancelled,
+                stops: vec![],
+                timestamp: None,
+            },
+        )],
+    )
+    .await
+    .expect("trip update is stored");
+    let entry = tx
+        .get_realtime_for_trip(&trip_id, today, 0)
+        .await
+        .expect("trip update is read");
+    let [source] = entry.source_data.as_slice() else {
+        panic!("one origin stored the trip update");
+    };
+    assert!(matches!(source.content.status, TripStatus::Cancelled));
+}
+
+#[tokio::test]
+async fn trip_updates_of_the_default_partition_are_moved_to_their_month() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    maintain(&database, Local::now().date_naive()).await;
+    let pool = common::pool().await;
+    // far enough ahead, that no partition of the month exists yet.
+    let month = NaiveDate::from_ymd_opt(2090, 1, 1).unwrap();
+    let trip_start_date = NaiveDate::from_ymd_opt(2090, 1, 17).unwrap();
+    sqlx::query(
+        "
+        INSERT INTO origins(id, name, priority) VALUES ($1, $1, 100)
+        ON CONFLICT DO NOTHING;
+        ",
+    )
+    .bind(ORIGIN)
+    .execute(&pool)
+    .await
+    .expect("origin is stored");
+    sqlx::query(
+        "
+        INSERT INTO trip_updates(origin, trip_id, trip_start_date, status)
+        VALUES ($1, 'test-partitions-moved', $2, 'cancelled')
+        ON CONFLICT DO NOTHING;
+        ",
+    )
+    .bind(ORIGIN)
+    .bind(trip_start_date)
+    .execute(&pool)
+    .await
+    .expect("trip update is stored");
+
+    maintain(&database, month).await;
+
+    let location: String = sqlx::query_scalar(
+        "
+        SELECT tableoid::regclass::text FROM trip_updates
+        WHERE origin = $1 AND trip_id = 'test-partitions-moved';
+        ",
+    )
+    .bind(ORIGIN)
+    .fetch_one(&pool)
+    .await
+    .expect("trip update is kept");
+    assert_eq!(location, "trip_updates_p2090_01");
+    // queries of a day only scan the partition of its month.
+    assert_eq!(
+        scanned_partitions(&pool, trip_start_date).await,
+        ["trip_updates_p2090_01"]
+    );
+}
@@ -0,0 +1,119 @@
+//! Routing of reads to a read replica. The replica is simulated by a second pool
+//! to the test database, whose connections are told apart by their application
+//! name, so that the queries of each pool can be counted.
+
+mod common;
+
+use std::time::Duration;
+
+use database::PgDatabase;
+use public_transport::server::Server;
+use sqlx::PgPool;
+use url::Url;
+
+const ORIGIN: &str = "test-replica";
+/// Application name of the connections of the simulated replica.
+const REPLICA: &str = "test-replica";
+
+/// Connects with a read replica, which is the test database itself.
+async fn connect() -> Option<PgDatabase> {
+    let mut connection_info = common::connection_info()?;
+    let mut read_url = Url::parse(&common::url().expect("TEST_DATABASE_URL is set"))
+        .expect("TEST_DATABASE_URL is a postgres url");
+    read_url
+        .query_pairs_mut()
+        .append_pair("application_name", REPLICA);
+    connection_info.read_url = Some(read_url.to_string());
+    let database = PgDatabase::connect(connection_info)
+        .await
+        .expect("test database is reachable");
+    Some(database)
+}
+
+/// Current time of the database, from which on queries are counted.
+async fn now(pool: &PgPool) -> chrono::DateTime<chrono::Utc> {
+    sqlx::query_scalar("SELECT clock_timestamp()")
+        .fetch_one(pool)
+        .await
+        .expect("time is read")
+}
+
+/// Number of connections of the replica and of the primary, which read the
+/// origins since the given time. Connections of the primary have no application
+/// name.
+async fn count_origin_reads(
+    pool: &PgPool,
+    since: chrono::DateTime<chrono::Utc>,
+) -> (i64, i64) {
+    sqlx::query_as(
+        "
+        SELECT
+            COUNT(*) FILTER (WHERE application_name = $1),
+            COUNT(*) FILTER (WHERE application_name = '')
+        FROM pg_stat_activity
+        WHERE datname = current_database()
+            AND query_start >= $2
+            AND query LIKE 'SELECT * FROM origins%';
+        ",
+    )
+    .bind(REPLICA)
+    .bind(since)
+    .fetch_one(pool)
+    .await
+    .expect("queries are counted")
+}
+
+/// Both databases in one test, as the primary of either is told apart from the
+/// replica only by its missing application name.
+#[tokio::test]
+async fn reads_are_routed_to_an_up_to_date_replica() {
+    let Some(database) = connect().await else {
+        return;
+    };
+    let status = database.replica_status().expect("replica is configured");
+    assert_eq!(status.lag, Some(Duration::ZERO), "a primary has no lag");
+    assert!(status.serves_reads);
+
+    let pool = common::pool().await;
+    let server = Server::new(database);
+
+    let since = now(&pool).await;
+    server
+        .client(ORIGIN)
+        .read_from_replica()
+        .get_origins()
+        .await
+        .expect("origins are read");
+    assert_eq!(
+        count_origin_reads(&pool, since).await,
+        (1, 0),
+        "clients of the web server read from the replica"
+    );
+
+    let since = now(&pool).await;
+    server
+        .client(ORIGIN)
+        .get_origins()
+        .await
+        .expect("origins are read");
+    assert_eq!(
+        count_origin_reads(&pool, since).await,
+        (0, 1),
+        "collectors read their own writes from the primary"
+    );
+
+    let database = common::connect().await.expect("configured");
+    assert!(database.replica_status().is_none());
+    let since = now(&pool).await;
+    Server::new(database)
+        .client(ORIGIN)
+        .read_from_replica()
+        .get_origins()
+        .await
+        .expect("origins are read");
+    assert_eq!(
+        count_origin_reads(&pool, since).await,
+        (0, 1),
+        "reads fall back to the primary without a replica"
+    );
+}
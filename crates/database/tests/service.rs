@@ -0,0 +1,88 @@
+//! Services pushed by value are shared by all trips with the same calendar. They
+//! are committed, the origin and the original ids are specific to this test.
+
+mod common;
+
+use chrono::{NaiveDate, Weekday};
+use model::{
+    calendar::{CalendarDate, Service, ServiceExceptionType},
+    fixtures::ServiceBuilder,
+};
+use public_transport::{database::SubjectRepo, server::Server};
+
+const ORIGIN: &str = "test-service-signature";
+
+fn date(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 6, day).expect("valid date")
+}
+
+/// Service of a single day, like the db collector pushes one per trip.
+fn single_day(day: u32) -> Service {
+    Service {
+        windows: vec![],
+        dates: vec![CalendarDate {
+            date: date(day),
+            exception_type: ServiceExceptionType::Added,
+        }],
+    }
+}
+
+#[tokio::test]
+async fn trips_with_the_same_calendar_share_a_service() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let server = Server::new(database.clone());
+    let origin = server.origin(ORIGIN, 0).await.expect("origin is stored");
+    let client = server.client(origin.raw());
+
+    let mut ids = vec![];
+    for (original_id, service) in [
+        ("trip-a", single_day(3)),
+        ("trip-b", single_day(3)),
+        ("trip-c", single_day(4)),
+    ] {
+        let id = client
+            .push_service(service, Some(original_id.to_owned()))
+            .await
+            .expect("service is stored");
+        ids.push(id);
+    }
+    assert_eq!(ids[0], ids[1], "trips of the same day share a service");
+    assert_ne!(ids[0], ids[2], "trips of another day have another service");
+
+    // the original ids of both trips refer to the shared service.
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    for original_id in ["trip-a", "trip-b"] {
+        let id = SubjectRepo::<Service>::id_by_original_id(
+            &mut tx,
+            origin.clone(),
+            original_id.to_owned(),
+        )
+        .await
+        .expect("original id is looked up");
+        assert_eq!(id, Some(ids[0]), "service of {}", original_id);
+    }
+
+    // the order of the exceptions does not matter.
+    let ordered = ServiceBuilder::new(date(3), date(16))
+        .weekdays()
+        .removed(date(5))
+        .added(date(8))
+        .build();
+    let mut reordered = ordered.clone();
+    reordered.dates.reverse();
+    let weekend = ServiceBuilder::new(date(3), date(16))
+        .days(&[Weekday::Sat, Weekday::Sun])
+        .build();
+    let mut ids = vec![];
+    for service in [ordered, reordered, weekend] {
+        let id = client
+            .push_service(service, None)
+            .await
+            .expect("service is stored");
+        ids.push(id);
+    }
+    assert_eq!(ids[0], ids[1]);
+    assert_ne!(ids[0], ids[2]);
+}
@@ -0,0 +1,375 @@
+//! Statuses of shared mobility stations, updated in bulk.
+
+mod common;
+
+use database::PgDatabase;
+use model::{
+    shared_mobility::{RentalUris, SharedMobilityStation, Status},
+    WithId,
+};
+use public_transport::{
+    database::{Database, DatabaseTransaction, SharedMobilityStationRepo},
+    server::Server,
+};
+use utility::id::Id;
+
+const ORIGIN: &str = "test-shared-mobility";
+const OTHER_ORIGIN: &str = "test-shared-mobility-other";
+
+const LATITUDE: f64 = 54.3233;
+const LONGITUDE: f64 = 10.1228;
+
+fn station(name: &str, latitude: f64, longitude: f64) -> SharedMobilityStation {
+    SharedMobilityStation {
+        name: name.to_owned(),
+        latitude,
+        longitude,
+        capacity: 8,
+        rental_uris: RentalUris {
+            android: None,
+            ios: None,
+            web: None,
+        },
+        status: None,
+        area: None,
+        region_id: None,
+        is_virtual_station: false,
+    }
+}
+
+fn status(num_bikes_available: u32) -> Status {
+    Status {
+        num_bikes_available,
+        num_docks_available: 8 - num_bikes_available,
+        num_bikes_disabled: None,
+        num_docks_disabled: None,
+        is_installed: Some(true),
+        is_renting: Some(true),
+        is_returning: Some(true),
+    }
+}
+
+fn id(id: &str) -> Id<SharedMobilityStation> {
+    Id::new(id.to_owned())
+}
+
+#[tokio::test]
+async fn statuses_without_a_station_of_the_origin_are_reported() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    common::put_origin(&mut tx, OTHER_ORIGIN).await;
+    let origin = Id::new(ORIGIN.to_owned());
+    tx.put_shared_mobility_stations(
+        &origin,
+        &[
+            WithId::new(
+                id("test-shared-mobility-1"),
+                station("Rathaus", LATITUDE, LONGITUDE),
+            ),
+            WithId::new(
+                id("test-shared-mobility-2"),
+                station("Hbf", LATITUDE, LONGITUDE),
+            ),
+        ],
+    )
+    .await
+    .expect("stations are stored");
+    tx.put_shared_mobility_stations(
+        &Id::new(OTHER_ORIGIN.to_owned()),
+        &[WithId::new(
+            id("test-shared-mobility-other"),
+            station("Dreiecksplatz", LATITUDE, LONGITUDE),
+        )],
+    )
+    .await
+    .expect("station of the other origin is stored");
+
+    let unknown = tx
+        .update_shared_mobility_station_statuses(
+            &origin,
+            &[
+                (id("test-shared-mobility-1"), Some(status(3))),
+                (id("test-shared-mobility-missing"), Some(status(1))),
+                (id("test-shared-mobility-other"), Some(status(2))),
+                (id("test-shared-mobility-2"), None),
+            ],
+        )
+        .await
+        .expect("statuses are updated");
+    let mut unknown = unknown.iter().map(Id::raw).collect::<Vec<_>>();
+    unknown.sort();
+    assert_eq!(
+        unknown,
+        ["test-shared-mobility-missing", "test-shared-mobility-other"]
+    );
+
+    let mut statuses = tx
+        .find_nearby_shared_mobility_stations(LATITUDE, LONGITUDE, 0.1)
+        .await
+        .expect("stations are read")
+        .into_iter()
+        .filter(|entry| entry.id.raw().starts_with("test-shared-mobility"))
+        .flat_map(|entry| {
+            let id = entry.id.raw();
+            entry.source_data.into_iter().map(move |source| {
+                (
+                    id.clone(),
+                    source
+                        .content
+                        .status
+                        .map(|status| status.num_bikes_available),
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    statuses.sort();
+    assert_eq!(
+        statuses,
+        [
+            ("test-shared-mobility-1".to_owned(), Some(3)),
+            ("test-shared-mobility-2".to_owned(), None),
+            // stations of other origins are left untouched.
+            ("test-shared-mobility-other".to_owned(), None),
+        ]
+    );
+}
+
+/// Stations of the origin, which take three chunks of statuses.
+const CHUNKED_STATIONS: usize = 2 * <PgDatabase as Database>::BULK_INSERT_MAX + 10;
+
+/// Statuses with stations and, within each chunk, statuses without one. Ids are
+/// specific to this test, as the client commits.
+#[tokio::test]
+async fn statuses_are_updated_in_chunks() {
+    const ORIGIN: &str = "test-shared-mobility-chunks";
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = database.transaction().await.expect("transaction begins");
+    common::put_origin(&mut tx, ORIGIN).await;
+    tx.commit().await.expect("transaction is committed");
+    let client = Server::new(database).client(ORIGIN);
+    let station_id = |index: usize| id(&format!("{}-{}", ORIGIN, index));
+
+    let stations = (0..CHUNKED_STATIONS)
+        .map(|index| {
+            WithId::new(
+                station_id(index),
+                station(
+                    &format!("Station {}", index),
+                    LATITUDE + index as f64 * 0.00001,
+                    // apart from the stations of the other test.
+                    LONGITUDE + 0.05,
+                ),
+            )
+        })
+        .collect::<Vec<_>>();
+    client
+        .put_shared_mobility_stations(stations)
+        .await
+        .expect("stations are stored");
+
+    let pool = common::pool().await;
+    let count_statuses = || async {
+        sqlx::query_as::<_, (i64, Option<i64>)>(
+            "
+            SELECT COUNT(status), SUM((status->>'numBikesAvailable')::INT)
+            FROM shared_mobility_stations
+            WHERE origin = $1;
+            ",
+        )
+        .bind(ORIGIN)
+        .fetch_one(&pool)
+        .await
+        .expect("statuses are counted")
+    };
+
+    // statuses are cleared first, so that repeated runs see them set again.
+    let cleared = (0..CHUNKED_STATIONS)
+        .map(|index| (station_id(index), None))
+        .collect::<Vec<_>>();
+    let unknown = client
+        .update_shared_mobility_station_statuses(&cleared)
+        .await
+        .expect("statuses are cleared");
+    assert!(unknown.is_empty());
+    assert_eq!(count_statuses().await, (0, None));
+
+    let mut statuses = vec![];
+    let mut expected_unknown = vec![];
+    for index in 0..CHUNKED_STATIONS {
+        if index % 500 == 250 {
+            let missing = id(&format!("{}-missing-{}", ORIGIN, index));
+            expected_unknown.push(missing.raw());
+            statuses.push((missing, Some(status(1))));
+        }
+        statuses.push((station_id(index), Some(status((index % 5) as u32))));
+    }
+    let mut unknown = client
+        .update_shared_mobility_station_statuses(&statuses)
+        .await
+        .expect("statuses are updated")
+        .iter()
+        .map(Id::raw)
+        .collect::<Vec<_>>();
+    unknown.sort();
+    expected_unknown.sort();
+    assert_eq!(
+        unknown, expected_unknown,
+        "statuses of every chunk are reported"
+    );
+    let bikes = (0..CHUNKED_STATIONS).map(|index| (index % 5) as i64).sum();
+    assert_eq!(
+        count_statuses().await,
+        (CHUNKED_STATIONS as i64, Some(bikes)),
+        "statuses of every chunk are set"
+    );
+}
+
+/// Stations of an origin of its own, which is committed, as the client reads
+/// outside of the transaction of the test.
+#[tokio::test]
+async fn only_rentable_stations_are_found_on_request() {
+    const ORIGIN: &str = "test-shared-mobility-rentable";
+    // apart from the stations of the other tests.
+    const LONGITUDE: f64 = self::LONGITUDE + 0.1;
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = database.transaction().await.expect("transaction begins");
+    common::put_origin(&mut tx, ORIGIN).await;
+    tx.commit().await.expect("transaction is committed");
+    let client = Server::new(database).client(ORIGIN);
+    let station_id = |name: &str| id(&format!("{}-{}", ORIGIN, name));
+
+    let not_renting = Status {
+        is_renting: Some(false),
+        ..status(3)
+    };
+    let stations = [
+        ("rentable", Some(status(3))),
+        ("empty", Some(status(0))),
+        ("not-renting", Some(not_renting)),
+        ("unknown", None),
+    ];
+    client
+        .put_shared_mobility_stations(
+            stations
+                .iter()
+                .map(|(name, _)| {
+                    WithId::new(station_id(name), station(name, LATITUDE, LONGITUDE))
+                })
+                .collect(),
+        )
+        .await
+        .expect("stations are stored");
+    let statuses = stations
+        .into_iter()
+        .map(|(name, status)| (station_id(name), status))
+        .collect::<Vec<_>>();
+    client
+        .update_shared_mobility_station_statuses(&statuses)
+        .await
+        .expect("statuses are updated");
+
+    let origins = &[Id::new(ORIGIN.to_owned())];
+    let find = |rentable_only: bool| {
+        let client = client.clone();
+        async move {
+            let mut found = client
+                .find_nearby_shared_mobility_stations(
+                    LATITUDE,
+                    LONGITUDE,
+                    0.1,
+                    rentable_only,
+                    10,
+                    origins,
+                )
+                .await
+                .expect("stations are found")
+                .into_iter()
+                .map(|station| station.content.id.raw())
+                .collect::<Vec<_>>();
+            found.sort();
+            found
+        }
+    };
+    assert_eq!(
+        find(false).await,
+        ["empty", "not-renting", "rentable", "unknown"]
+            .map(|name| station_id(name).raw())
+    );
+    assert_eq!(find(true).await, [station_id("rentable").raw()]);
+}
+
+#[tokio::test]
+async fn nearby_stations_are_ordered_by_distance() {
+    const ORIGIN: &str = "test-shared-mobility-distance";
+    // apart from the stations of the other tests.
+    const LONGITUDE: f64 = self::LONGITUDE + 0.2;
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = database.transaction().await.expect("transaction begins");
+    common::put_origin(&mut tx, ORIGIN).await;
+    tx.commit().await.expect("transaction is committed");
+    let client = Server::new(database).client(ORIGIN);
+    let station_id = |name: &str| id(&format!("{}-{}", ORIGIN, name));
+
+    // stored in another order than their distance, by offset in degrees north.
+    let stations = [
+        ("far", 0.0008),
+        ("near", 0.0001),
+        ("farthest", 0.0009),
+        ("next", 0.0),
+    ];
+    client
+        .put_shared_mobility_stations(
+            stations
+                .iter()
+                .map(|(name, offset)| {
+                    WithId::new(
+                        station_id(name),
+                        station(name, LATITUDE + offset, LONGITUDE),
+                    )
+                })
+                .collect(),
+        )
+        .await
+        .expect("stations are stored");
+
+    let origins = &[Id::new(ORIGIN.to_owned())];
+    let cases = [
+        (10, vec!["next", "near", "far", "farthest"]),
+        (2, vec!["next", "near"]),
+    ];
+    for (limit, expected) in cases {
+        let found = client
+            .find_nearby_shared_mobility_stations(
+                LATITUDE, LONGITUDE, 0.5, false, limit, origins,
+            )
+            .await
+            .expect("stations are found");
+        assert_eq!(
+            found
+                .iter()
+                .map(|station| station.content.id.raw())
+                .collect::<Vec<_>>(),
+            expected
+                .iter()
+                .map(|name| station_id(name).raw())
+                .collect::<Vec<_>>(),
+            "stations of limit {}",
+            limit
+        );
+        assert!(
+            found
+                .windows(2)
+                .all(|pair| pair[0].distance_km <= pair[1].distance_km),
+            "distances of limit {}",
+            limit
+        );
+    }
+}
@@ -0,0 +1,60 @@
+//! Statement timeouts. The test locks the origins, so that it runs in a binary of
+//! its own.
+
+mod common;
+
+use std::time::Duration;
+
+use database::PgDatabase;
+use public_transport::{
+    database::{Database, DatabaseError, DatabaseOperations},
+    deadline,
+};
+use tokio::time::Instant;
+
+#[tokio::test]
+async fn statements_time_out_with_and_without_transaction() {
+    let Some(mut connection_info) = common::connection_info() else {
+        return;
+    };
+    connection_info.statement_timeout = Some(Duration::from_millis(200));
+    let database = PgDatabase::connect(connection_info)
+        .await
+        .expect("test database is reachable");
+    database
+        .auto()
+        .origins()
+        .await
+        .expect("origins are read without lock");
+
+    let pool = common::pool().await;
+    let mut lock = pool.begin().await.expect("transaction begins");
+    sqlx::query("LOCK TABLE origins IN ACCESS EXCLUSIVE MODE")
+        .execute(&mut *lock)
+        .await
+        .expect("origins are locked");
+
+    // statements outside of transactions are limited by the timeout of the pool.
+    let result = database.auto().origins().await;
+    assert!(
+        matches!(result, Err(DatabaseError::Timeout)),
+        "statement outside of transaction times out, got {:?}",
+        result.map(|origins| origins.len())
+    );
+
+    // statements of transactions are limited by the deadline of the request.
+    let started_at = Instant::now();
+    let result = deadline::scope(started_at + Duration::from_millis(50), async {
+        let mut tx = database.transaction().await?;
+        tx.origins().await
+    })
+    .await;
+    assert!(
+        matches!(result, Err(DatabaseError::Timeout)),
+        "statement of transaction times out, got {:?}",
+        result.map(|origins| origins.len())
+    );
+    assert!(started_at.elapsed() < Duration::from_millis(200));
+
+    lock.rollback().await.expect("lock is released");
+}
@@ -0,0 +1,746 @@
+mod common;
+
+use chrono::{DateTime, Duration, Local};
+use database::PgDatabase;
+use model::{
+    calendar::{CalendarDate, ServiceExceptionType},
+    fixtures::{stop_time, StopBuilder, TripBuilder},
+    line::{Line, LineType},
+    stop::{Stop, StopAmenity},
+    trip::{PickupDropOffType, StopTime},
+    DatabaseEntry, WithId, WithOrigin,
+};
+use public_transport::database::{
+    Database, DatabaseTransaction, Repo, ServiceRepo, StopRepo,
+};
+use utility::id::Id;
+
+const ORIGIN: &str = "test-stop";
+
+fn with_id(id: &str, stop: Stop) -> WithOrigin<WithId<Stop>> {
+    with_origin(ORIGIN, id, stop)
+}
+
+fn with_origin(origin: &str, id: &str, stop: Stop) -> WithOrigin<WithId<Stop>> {
+    WithOrigin::new(
+        Id::new(origin.to_owned()),
+        WithId::new(Id::new(id.to_owned()), stop),
+    )
+}
+
+/// Ids of the entries and the origins of their data.
+fn ids_and_origins(entries: &[DatabaseEntry<Stop>]) -> Vec<(String, Vec<String>)> {
+    entries
+        .iter()
+        .map(|entry| {
+            let origins = entry
+                .source_data
+                .iter()
+                .map(|source| source.origin.raw())
+                .collect();
+            (entry.id.raw(), origins)
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn put_round_trips_all_columns() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+
+    let stored = tx
+        .put(with_id(
+            "test-stop-1",
+            StopBuilder::new("Kiel Hbf")
+                .at(54.32, 10.13)
+                .address("Am Bahnhof 1")
+                .platform("1a")
+                .amenity(StopAmenity::SteamPermission)
+                .build(),
+        ))
+        .await
+        .expect("stop is inserted");
+    assert_eq!(stored.content.content.platform_code.as_deref(), Some("1a"));
+
+    // the second put updates the existing stop.
+    let changed = StopBuilder::new("Kiel Hauptbahnhof")
+        .at(54.32, 10.13)
+        .address("Am Bahnhof 1")
+        .platform("2")
+        .build();
+    tx.put(with_id("test-stop-1", changed))
+        .await
+        .expect("stop is updated");
+
+    let entry: DatabaseEntry<Stop> = tx
+        .get(Id::new("test-stop-1".to_owned()))
+        .await
+        .expect("stop is read");
+    let [source] = entry.source_data.as_slice() else {
+        panic!("one origin stored the stop");
+    };
+    let stop = &source.content;
+    assert_eq!(stop.name.as_deref(), Some("Kiel Hauptbahnhof"));
+    assert_eq!(stop.platform_code.as_deref(), Some("2"));
+    assert!(stop.amenities.is_empty());
+    let location = stop.location.as_ref().expect("location is stored");
+    assert_eq!(location.latitude, 54.32);
+    assert_eq!(location.longitude, 10.13);
+    assert_eq!(location.address.as_deref(), Some("Am Bahnhof 1"));
+    assert!(stop.updated_at.is_some());
+}
+
+/// Puts the stop in a transaction of its own, as `now()` is fixed per transaction,
+/// and returns when it was last modified.
+async fn put_committed(database: &PgDatabase, stop: Stop) -> DateTime<Local> {
+    let origin = "test-stop-updated-at";
+    let mut tx = common::transaction(database, origin).await;
+    tx.put(with_origin(origin, "test-stop-updated-at", stop))
+        .await
+        .expect("stop is stored");
+    tx.commit().await.expect("transaction is committed");
+
+    let mut tx = database.transaction().await.expect("transaction begins");
+    let entry: DatabaseEntry<Stop> = tx
+        .get(Id::new("test-stop-updated-at".to_owned()))
+        .await
+        .expect("stop is read");
+    entry.source_data[0]
+        .content
+        .updated_at
+        .expect("stop has been modified")
+}
+
+#[tokio::test]
+async fn only_changes_bump_the_modification_time() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    // the stop may be left over from a previous run, with either name.
+    let modified =
+        put_committed(&database, StopBuilder::new("Kiel Hbf").build()).await;
+    let unchanged =
+        put_committed(&database, StopBuilder::new("Kiel Hbf").build()).await;
+    assert_eq!(unchanged, modified, "reimporting is no modification");
+
+    let changed =
+        put_committed(&database, StopBuilder::new("Kiel Hauptbahnhof").build()).await;
+    assert!(changed > modified, "{} is after {}", changed, modified);
+}
+
+#[tokio::test]
+async fn insert_stores_amenities() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+
+    let stored = tx
+        .insert(WithOrigin::new(
+            Id::new(ORIGIN.to_owned()),
+            StopBuilder::new("Kiel Hbf")
+                .platform("3")
+                .amenity(StopAmenity::SteamPermission)
+                .build(),
+        ))
+        .await
+        .expect("stop is inserted");
+    let entry = tx.get(stored.content.id).await.expect("stop is read");
+    let stop = &entry.source_data[0].content;
+    assert_eq!(stop.platform_code.as_deref(), Some("3"));
+    assert_eq!(stop.amenities.len(), 1);
+}
+
+#[tokio::test]
+async fn lists_platforms_in_natural_order() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+
+    tx.put(with_id(
+        "test-station",
+        StopBuilder::new("Kiel Hbf").build(),
+    ))
+    .await
+    .expect("station is inserted");
+    let codes = [
+        Some("10"),
+        Some("B"),
+        None,
+        Some("2"),
+        Some("2a"),
+        Some("1"),
+    ];
+    for (i, code) in codes.into_iter().enumerate() {
+        let id = format!("test-platform-{}", i);
+        let mut platform = StopBuilder::new("Kiel Hbf").parent("test-station");
+        if let Some(code) = code {
+            platform = platform.platform(code);
+        }
+        tx.put(with_id(&id, platform.build()))
+            .await
+            .expect("platform is inserted");
+    }
+
+    let platforms = tx
+        .get_platforms(&Id::new("test-station".to_owned()))
+        .await
+        .expect("platforms are read");
+    let codes = platforms
+        .iter()
+        .map(|entry| entry.source_data[0].content.platform_code.as_deref())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        codes,
+        [
+            Some("1"),
+            Some("2"),
+            Some("2a"),
+            Some("10"),
+            Some("B"),
+            None
+        ]
+    );
+}
+
+#[tokio::test]
+async fn redirect_fills_missing_fields_of_the_existing_stop() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+
+    let moved = StopBuilder::new("Kiel Hbf")
+        .at(54.32, 10.13)
+        .description("Bahnhofsvorplatz")
+        .platform("4")
+        .amenity(StopAmenity::SteamPermission)
+        .build();
+    tx.put(with_id("test-stop-moved", moved))
+        .await
+        .expect("moved stop is inserted");
+    let existing = StopBuilder::new("Kiel Hauptbahnhof").build();
+    tx.put(with_id("test-stop-existing", existing))
+        .await
+        .expect("existing stop is inserted");
+
+    tx.redirect_stop(
+        &Id::new(ORIGIN.to_owned()),
+        &Id::new("test-stop-moved".to_owned()),
+        &Id::new("test-stop-existing".to_owned()),
+    )
+    .await
+    .expect("stop is redirected");
+
+    let entry: DatabaseEntry<Stop> = tx
+        .get(Id::new("test-stop-existing".to_owned()))
+        .await
+        .expect("stop is read");
+    let stop = &entry.source_data[0].content;
+    assert_eq!(stop.name.as_deref(), Some("Kiel Hauptbahnhof"));
+    assert_eq!(stop.description.as_deref(), Some("Bahnhofsvorplatz"));
+    assert_eq!(stop.platform_code.as_deref(), Some("4"));
+    assert_eq!(stop.amenities, vec![StopAmenity::SteamPermission]);
+    let location = stop.location.as_ref().expect("location is taken over");
+    assert_eq!(location.latitude, 54.32);
+    let moved: DatabaseEntry<Stop> = tx
+        .get(Id::new("test-stop-moved".to_owned()))
+        .await
+        .expect("stop is read");
+    assert!(moved.source_data.is_empty());
+}
+
+#[tokio::test]
+async fn stations_without_location_get_the_centroid_of_their_platforms() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    let station = StopBuilder::new("Kiel Hbf").build();
+    let stops = [
+        ("test-stop-station", station.clone()),
+        (
+            "test-stop-platform-1",
+            StopBuilder::new("Kiel Hbf")
+                .parent("test-stop-station")
+                .platform("1")
+                .at(54.30, 10.10)
+                .build(),
+        ),
+        (
+            "test-stop-platform-2",
+            StopBuilder::new("Kiel Hbf")
+                .parent("test-stop-station")
+                .platform("2")
+                .at(54.32, 10.14)
+                .build(),
+        ),
+        // stations with a location keep it.
+        (
+            "test-stop-located",
+            StopBuilder::new("Kiel Sophienhof").at(54.0, 10.0).build(),
+        ),
+        (
+            "test-stop-located-platform",
+            StopBuilder::new("Kiel Sophienhof")
+                .parent("test-stop-located")
+                .at(54.5, 10.5)
+                .build(),
+        ),
+    ];
+    for (id, stop) in stops {
+        tx.put(with_id(id, stop)).await.expect("stop is stored");
+    }
+    // stops of other origins are left to their own backfill.
+    common::put_origin(&mut tx, "test-stop-other").await;
+    for (id, stop) in [
+        ("test-stop-station", station),
+        (
+            "test-stop-platform-3",
+            StopBuilder::new("Kiel Hbf")
+                .parent("test-stop-station")
+                .platform("3")
+                .at(55.0, 11.0)
+                .build(),
+        ),
+    ] {
+        tx.put(with_origin("test-stop-other", id, stop))
+            .await
+            .expect("stop of the other origin is stored");
+    }
+
+    let backfilled = tx
+        .backfill_centroids(&Id::new(ORIGIN.to_owned()))
+        .await
+        .expect("centroids are backfilled");
+    assert_eq!(backfilled, 1);
+
+    let locations = |entry: DatabaseEntry<Stop>| {
+        entry
+            .source_data
+            .into_iter()
+            .map(|source| {
+                let location = source.content.location;
+                (
+                    source.origin.raw(),
+                    location.map(|location| (location.latitude, location.longitude)),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let station = locations(
+        tx.get(Id::new("test-stop-station".to_owned()))
+            .await
+            .expect("station is read"),
+    );
+    assert!(station.contains(&("test-stop-other".to_owned(), None)));
+    let Some((_, Some((latitude, longitude)))) =
+        station.iter().find(|(origin, _)| origin == ORIGIN)
+    else {
+        panic!("station has a location: {:?}", station);
+    };
+    assert!((latitude - 54.31).abs() < 1e-9, "latitude is {}", latitude);
+    assert!(
+        (longitude - 10.12).abs() < 1e-9,
+        "longitude is {}",
+        longitude
+    );
+    assert_eq!(
+        locations(
+            tx.get(Id::new("test-stop-located".to_owned()))
+                .await
+                .expect("station is read")
+        ),
+        [(ORIGIN.to_owned(), Some((54.0, 10.0)))]
+    );
+}
+
+#[tokio::test]
+async fn pages_only_contain_stops_of_the_given_origins() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    const OTHER: &str = "test-stop-other";
+    common::put_origin(&mut tx, OTHER).await;
+
+    for id in ["test-stop-page-a", "test-stop-page-b", "test-stop-page-c"] {
+        tx.put(with_id(id, StopBuilder::new("Kiel").build()))
+            .await
+            .expect("stop is stored");
+    }
+    for id in ["test-stop-page-aa", "test-stop-page-c"] {
+        tx.put(with_origin(OTHER, id, StopBuilder::new("Kiel").build()))
+            .await
+            .expect("stop of the other origin is stored");
+    }
+    let origins = [Id::new(ORIGIN.to_owned())];
+
+    let first = tx
+        .get_page_after(None, 2, &origins)
+        .await
+        .expect("first page is read");
+    assert_eq!(
+        ids_and_origins(&first),
+        vec![
+            ("test-stop-page-a".to_owned(), vec![ORIGIN.to_owned()]),
+            ("test-stop-page-b".to_owned(), vec![ORIGIN.to_owned()]),
+        ]
+    );
+
+    // renaming a stop while paging neither skips nor repeats stops.
+    tx.put(with_id(
+        "test-stop-page-a",
+        StopBuilder::new("Altenholz").build(),
+    ))
+    .await
+    .expect("stop is renamed");
+    tx.put(with_id(
+        "test-stop-page-c",
+        StopBuilder::new("Aachen").build(),
+    ))
+    .await
+    .expect("stop is renamed");
+    // stops inserted while paging show up only if they sort after the last page.
+    for id in ["test-stop-page-ab", "test-stop-page-bb"] {
+        tx.put(with_id(id, StopBuilder::new("Kiel").build()))
+            .await
+            .expect("stop is inserted");
+    }
+    tx.put(with_origin(
+        OTHER,
+        "test-stop-page-bc",
+        StopBuilder::new("Kiel").build(),
+    ))
+    .await
+    .expect("stop of the other origin is inserted");
+
+    let second = tx
+        .get_page_after(Some(first[1].id.clone()), 2, &origins)
+        .await
+        .expect("second page is read");
+    assert_eq!(
+        ids_and_origins(&second),
+        vec![
+            ("test-stop-page-bb".to_owned(), vec![ORIGIN.to_owned()]),
+            ("test-stop-page-c".to_owned(), vec![ORIGIN.to_owned()]),
+        ]
+    );
+
+    let third = tx
+        .get_page_after(Some(second[1].id.clone()), 2, &origins)
+        .await
+        .expect("third page is read");
+    assert!(third.is_empty());
+}
+
+#[tokio::test]
+async fn all_stops_are_read_in_the_same_order() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    const OTHER: &str = "test-stop-other";
+    common::put_origin(&mut tx, OTHER).await;
+
+    // the stops are stored neither in the order of their ids nor of their origins.
+    for (origin, id) in [
+        (OTHER, "test-stop-all-b"),
+        (ORIGIN, "test-stop-all-c"),
+        (ORIGIN, "test-stop-all-a"),
+        (OTHER, "test-stop-all-a"),
+        (ORIGIN, "test-stop-all-b"),
+    ] {
+        tx.put(with_origin(origin, id, StopBuilder::new("Kiel").build()))
+            .await
+            .expect("stop is stored");
+    }
+    // updating a row moves it within the table.
+    tx.put(with_id(
+        "test-stop-all-a",
+        StopBuilder::new("Kiel Hbf").build(),
+    ))
+    .await
+    .expect("stop is updated");
+
+    let mut reads = vec![];
+    for _ in 0..2 {
+        let all = tx.get_all().await.expect("stops are read");
+        let mut stops = ids_and_origins(&all);
+        stops.retain(|(id, _)| id.starts_with("test-stop-all-"));
+        reads.push(stops);
+    }
+    let both_origins = vec![ORIGIN.to_owned(), OTHER.to_owned()];
+    assert_eq!(
+        reads[0],
+        vec![
+            ("test-stop-all-a".to_owned(), both_origins.clone()),
+            ("test-stop-all-b".to_owned(), both_origins),
+            ("test-stop-all-c".to_owned(), vec![ORIGIN.to_owned()]),
+        ]
+    );
+    assert_eq!(reads[0], reads[1]);
+}
+
+#[tokio::test]
+async fn search_ranks_stops_without_future_service_last() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    let origin = Id::new(ORIGIN.to_owned());
+    tx.put(with_id(
+        "test-stop-dead",
+        StopBuilder::new("Testdorf Mitte").build(),
+    ))
+    .await
+    .expect("dead stop is stored");
+    tx.put(with_id(
+        "test-stop-active",
+        StopBuilder::new("Testdorf Mitte Ost").build(),
+    ))
+    .await
+    .expect("active stop is stored");
+
+    let tomorrow = Local::now().date_naive() + Duration::days(1);
+    let (service_id, _) = tx
+        .put_calendar_date(
+            None,
+            CalendarDate {
+                date: tomorrow,
+                exception_type: ServiceExceptionType::Added,
+            },
+        )
+        .await
+        .expect("service is stored");
+    let line = Line {
+        name: Some("11".to_owned()),
+        kind: LineType::Bus,
+        agency_id: None,
+        secondary_agency_ids: vec![],
+        updated_at: None,
+    };
+    tx.put(WithOrigin::new(
+        origin.clone(),
+        WithId::new(Id::new("test-stop-line".to_owned()), line),
+    ))
+    .await
+    .expect("line is stored");
+    let trip = TripBuilder::new("test-stop-line", 0, 0)
+        .service(service_id.raw())
+        .build();
+    let trip_id = Id::new("test-stop-trip".to_owned());
+    tx.put(WithOrigin::new(
+        origin.clone(),
+        WithId::new(trip_id.clone(), trip),
+    ))
+    .await
+    .expect("trip is stored");
+    let stop_time = stop_time(1, "test-stop-active", 8 * 60);
+    public_transport::database::TripRepo::put_stop_times(
+        &mut tx,
+        &trip_id,
+        &origin,
+        &[stop_time],
+        false,
+    )
+    .await
+    .expect("stop time is stored");
+
+    let dead = Id::new("test-stop-dead".to_owned());
+    let active = Id::new("test-stop-active".to_owned());
+    let ids = |entries: Vec<DatabaseEntry<Stop>>| {
+        entries
+            .iter()
+            .map(|entry| entry.id.raw())
+            .collect::<Vec<_>>()
+    };
+    // not evaluated yet, so the exact match comes first.
+    let found = tx.search("Testdorf Mitte").await.expect("stops are found");
+    assert_eq!(ids(found), vec![dead.raw(), active.raw()]);
+
+    tx.refresh_service_summary(Some(&[&dead, &active]))
+        .await
+        .expect("summaries are refreshed");
+    let summaries = tx
+        .get_service_summaries(&[&dead, &active])
+        .await
+        .expect("summaries are read");
+    assert_eq!(summaries[&dead].last_service_date, None);
+    assert_eq!(summaries[&active].last_service_date, Some(tomorrow));
+    let found = tx.search("Testdorf Mitte").await.expect("stops are found");
+    assert_eq!(ids(found), vec![active.raw(), dead.raw()]);
+}
+
+#[tokio::test]
+async fn autocomplete_limits_stops_of_the_given_origins() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    const OTHER: &str = "test-stop-other";
+    common::put_origin(&mut tx, OTHER).await;
+
+    // only stops with a location are completed.
+    for (id, name) in [
+        ("test-stop-complete-a", "Qwzlk Nord"),
+        ("test-stop-complete-c", "Qwzlk West"),
+        ("test-stop-complete-d", "Qwz_lk Ost"),
+        ("test-stop-complete-e", "QwzXlk Ost"),
+    ] {
+        tx.put(with_id(id, StopBuilder::new(name).at(54.32, 10.13).build()))
+            .await
+            .expect("stop is stored");
+    }
+    for (id, name) in [
+        ("test-stop-complete-a", "Qwzlk Nord"),
+        ("test-stop-complete-b", "Qwzlk Mitte"),
+    ] {
+        tx.put(with_origin(
+            OTHER,
+            id,
+            StopBuilder::new(name).at(54.32, 10.13).build(),
+        ))
+        .await
+        .expect("stop of the other origin is stored");
+    }
+    let origins = [Id::new(ORIGIN.to_owned())];
+
+    // the limit counts stops of the given origins, not rows of all origins.
+    let completions = tx
+        .autocomplete("Qwzlk", 2, &origins)
+        .await
+        .expect("stops are completed");
+    assert_eq!(
+        ids_and_origins(&completions),
+        vec![
+            ("test-stop-complete-a".to_owned(), vec![ORIGIN.to_owned()]),
+            ("test-stop-complete-c".to_owned(), vec![ORIGIN.to_owned()]),
+        ]
+    );
+
+    // underscores are no wildcards, so only one of the stops starts with it.
+    let completions = tx
+        .autocomplete("Qwz_l", 1, &origins)
+        .await
+        .expect("stops are completed");
+    assert_eq!(
+        ids_and_origins(&completions),
+        vec![("test-stop-complete-d".to_owned(), vec![ORIGIN.to_owned()])]
+    );
+}
+
+#[tokio::test]
+async fn only_current_trips_decide_whether_stops_are_served_on_request() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    let origin = Id::new(ORIGIN.to_owned());
+    let mut services = vec![];
+    for date in [
+        Local::now().date_naive() + Duration::days(1),
+        Local::now().date_naive() - Duration::days(30),
+    ] {
+        let (service_id, _) = tx
+            .put_calendar_date(
+                None,
+                CalendarDate {
+                    date,
+                    exception_type: ServiceExceptionType::Added,
+                },
+            )
+            .await
+            .expect("service is stored");
+        services.push(service_id);
+    }
+    let (current, expired) = (services[0], services[1]);
+    tx.put(WithOrigin::new(
+        origin.clone(),
+        WithId::new(
+            Id::new("test-stop-request-line".to_owned()),
+            Line {
+                name: Some("11".to_owned()),
+                kind: LineType::Bus,
+                agency_id: None,
+                secondary_agency_ids: vec![],
+                updated_at: None,
+            },
+        ),
+    ))
+    .await
+    .expect("line is stored");
+
+    // stop, service of the trip and whether it only stops on request.
+    let calls = [
+        ("test-stop-mixed", current, false),
+        ("test-stop-mixed", current, true),
+        ("test-stop-on-request", current, true),
+        ("test-stop-on-request", expired, false),
+        ("test-stop-expired", expired, true),
+    ];
+    for (index, (stop_id, service_id, on_request)) in calls.into_iter().enumerate() {
+        tx.put(with_id(stop_id, StopBuilder::new(stop_id).build()))
+            .await
+            .expect("stop is stored");
+        let trip_id = Id::new(format!("test-stop-request-trip-{}", index));
+        tx.put(WithOrigin::new(
+            origin.clone(),
+            WithId::new(
+                trip_id.clone(),
+                TripBuilder::new("test-stop-request-line", 0, 0)
+                    .service(service_id.raw())
+                    .build(),
+            ),
+        ))
+        .await
+        .expect("trip is stored");
+        let pickup_drop_off = on_request.then_some(PickupDropOffType::PhoneAgency);
+        let stop_time = StopTime {
+            pickup_type: pickup_drop_off,
+            drop_off_type: pickup_drop_off,
+            ..stop_time(1, stop_id, 8 * 60)
+        };
+        assert_eq!(stop_time.is_on_request(), on_request);
+        public_transport::database::TripRepo::put_stop_times(
+            &mut tx,
+            &trip_id,
+            &origin,
+            &[stop_time],
+            false,
+        )
+        .await
+        .expect("stop time is stored");
+    }
+
+    let ids = [
+        "test-stop-mixed",
+        "test-stop-on-request",
+        "test-stop-expired",
+    ]
+    .map(|id| Id::new(id.to_owned()));
+    let refs = ids.iter().collect::<Vec<_>>();
+    tx.refresh_service_summary(Some(&refs))
+        .await
+        .expect("summaries are refreshed");
+    let summaries = tx
+        .get_service_summaries(&refs)
+        .await
+        .expect("summaries are read");
+    let cases = [
+        ("test-stop-mixed", false),
+        ("test-stop-on-request", true),
+        ("test-stop-expired", false),
+    ];
+    for (id, expected) in cases {
+        assert_eq!(
+            summaries[&Id::new(id.to_owned())].served_only_on_request,
+            expected,
+            "stop `{}`",
+            id
+        );
+    }
+}
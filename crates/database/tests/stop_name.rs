@@ -0,0 +1,317 @@
+//! Stop names stored with stop times, see migrations 0033 and 0042.
+
+mod common;
+
+use std::time::{Duration as StdDuration, Instant};
+
+use chrono::{Duration, Local, NaiveTime};
+use model::{
+    calendar::{CalendarDate, ServiceExceptionType},
+    fixtures::{stop_time, StopBuilder, TripBuilder},
+    line::{Line, LineType},
+    origin::Origin,
+    stop::Stop,
+    DateTimeRange, WithId, WithOrigin,
+};
+use public_transport::{
+    client::TripInstantiationOptions,
+    database::{
+        ChangeLogRepo, Database, DatabaseOperations, DatabaseTransaction, Repo,
+        ServiceRepo, StopRepo, TripRepo,
+    },
+    server::Server,
+};
+use serde::Serialize;
+use utility::id::{HasId, Id};
+
+const ORIGIN: &str = "test-stop-name";
+
+fn with_id<T>(id: &str, content: T) -> WithOrigin<WithId<T>>
+where
+    T: Serialize + HasId<IdType = String>,
+{
+    WithOrigin::new(
+        Id::new(ORIGIN.to_owned()),
+        WithId::new(Id::new(id.to_owned()), content),
+    )
+}
+
+fn line() -> Line {
+    Line {
+        name: Some("300".to_owned()),
+        kind: LineType::Bus,
+        agency_id: None,
+        secondary_agency_ids: vec![],
+        updated_at: None,
+    }
+}
+
+/// Stop names stored with the stop times of the trip, and the names of their
+/// stops, by stop sequence.
+async fn stored_and_joined_names<O>(
+    operations: &mut O,
+    trip_id: &str,
+) -> (Vec<(i32, Option<String>)>, Vec<(i32, Option<String>)>)
+where
+    O: DatabaseOperations + Send,
+{
+    let mut stop_times = operations
+        .get_stop_times(Id::new(trip_id.to_owned()), Id::new(ORIGIN.to_owned()))
+        .await
+        .expect("stop times are read");
+    stop_times.sort_by_key(|stop_time| stop_time.stop_sequence);
+    let mut stored = vec![];
+    let mut joined = vec![];
+    for stop_time in stop_times {
+        let stop_id = stop_time.stop_id.clone().expect("stop time has a stop");
+        let name = Repo::<Stop>::get_many(operations, &[stop_id])
+            .await
+            .expect("stop is read")
+            .into_iter()
+            .flat_map(|entry| entry.source_data)
+            .find(|source| source.origin.raw_ref::<str>() == ORIGIN)
+            .and_then(|source| source.content.name);
+        stored.push((stop_time.stop_sequence, stop_time.stop_name));
+        joined.push((stop_time.stop_sequence, name));
+    }
+    (stored, joined)
+}
+
+#[tokio::test]
+async fn stop_names_of_stop_times_follow_their_stops() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    let origin = Id::new(ORIGIN.to_owned());
+    let trip_id = "test-stop-name-trip";
+    for (id, name) in [
+        ("test-stop-name-1", "Kiel Hbf"),
+        ("test-stop-name-2", "Raisdorf"),
+        ("test-stop-name-3", "Preetz"),
+    ] {
+        tx.put(with_id(id, StopBuilder::new(name).build()))
+            .await
+            .expect("stop is stored");
+    }
+    tx.put(with_id("test-stop-name-line", line()))
+        .await
+        .expect("line is stored");
+    tx.put(with_id(
+        trip_id,
+        TripBuilder::new("test-stop-name-line", 0, 0).build(),
+    ))
+    .await
+    .expect("trip is stored");
+    tx.put_stop_times(
+        &Id::new(trip_id.to_owned()),
+        &origin,
+        &[
+            stop_time(1, "test-stop-name-1", 0),
+            stop_time(2, "test-stop-name-2", 12),
+            stop_time(3, "test-stop-name-3", 20),
+        ],
+        true,
+    )
+    .await
+    .expect("stop times are stored");
+    tx.put_stop_time(
+        Id::new(trip_id.to_owned()),
+        WithOrigin::new(origin.clone(), stop_time(4, "test-stop-name-1", 30)),
+    )
+    .await
+    .expect("stop time is stored");
+
+    let (stored, joined) = stored_and_joined_names(&mut tx, trip_id).await;
+    assert_eq!(stored, joined, "names are stored with the stop times");
+    assert_eq!(stored[0].1.as_deref(), Some("Kiel Hbf"));
+
+    tx.put(with_id(
+        "test-stop-name-1",
+        StopBuilder::new("Kiel Hauptbahnhof").build(),
+    ))
+    .await
+    .expect("stop is renamed");
+    tx.put(with_id(
+        "test-stop-name-3",
+        StopBuilder::new("Preetz ZOB").build(),
+    ))
+    .await
+    .expect("stop is renamed");
+    let (stored, joined) = stored_and_joined_names(&mut tx, trip_id).await;
+    assert_ne!(stored, joined, "names lag behind until they are synced");
+
+    tx.flush_changes(100_000)
+        .await
+        .expect("changes are flushed");
+    // stop times of other origins may be synced along.
+    let synced = tx.sync_stop_names().await.expect("names are synced");
+    assert!(synced >= 3, "{} stop times are synced", synced);
+    let (stored, joined) = stored_and_joined_names(&mut tx, trip_id).await;
+    assert_eq!(stored, joined, "names are synced");
+    assert_eq!(stored[0].1.as_deref(), Some("Kiel Hauptbahnhof"));
+    assert_eq!(
+        tx.sync_stop_names().await.expect("names are synced"),
+        0,
+        "changes are synced once"
+    );
+
+    // stop times of a merged stop get the name of the stop, it is merged into.
+    tx.redirect_stop(
+        &origin,
+        &Id::new("test-stop-name-2".to_owned()),
+        &Id::new("test-stop-name-3".to_owned()),
+    )
+    .await
+    .expect("stop is merged");
+    let (stored, joined) = stored_and_joined_names(&mut tx, trip_id).await;
+    assert_eq!(stored, joined, "names follow merged stops");
+    assert_eq!(stored[1].1.as_deref(), Some("Preetz ZOB"));
+}
+
+/// Stops of the measured network.
+const MEASURED_STOPS: usize = 60;
+/// Trips of the measured network, all running today.
+const MEASURED_TRIPS: usize = 400;
+/// Stop times of each trip of the measured network.
+const MEASURED_STOP_TIMES: usize = 30;
+/// Instantiations measured with and without looking up the stops.
+const MEASURED_RUNS: u32 = 10;
+
+/// Compares instantiating the trips of nearby stops with stop names looked up in
+/// the stops to taking them from the stop times, on a network of its own origin,
+/// which is committed. Run with
+/// `cargo test -p database --test stop_name -- --ignored --nocapture`.
+#[tokio::test]
+#[ignore = "measurement"]
+async fn measure_instantiation_with_stop_names_of_stop_times() {
+    const ORIGIN: &str = "test-stop-name-measure";
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let origin = Id::new(ORIGIN.to_owned());
+    let today = Local::now().date_naive();
+    let stop_id = |index: usize| format!("{}-stop-{}", ORIGIN, index);
+
+    let mut tx = database.transaction().await.expect("transaction begins");
+    tx.put_origin(WithId::new(
+        origin.clone(),
+        Origin {
+            name: ORIGIN.to_owned(),
+            priority: 0,
+        },
+    ))
+    .await
+    .expect("origin is stored");
+    let (service_id, _) = tx
+        .put_calendar_date(
+            None,
+            CalendarDate {
+                date: today,
+                exception_type: ServiceExceptionType::Added,
+            },
+        )
+        .await
+        .expect("service is stored");
+    let line_id = format!("{}-line", ORIGIN);
+    tx.put(WithOrigin::new(
+        origin.clone(),
+        WithId::new(Id::new(line_id.clone()), line()),
+    ))
+    .await
+    .expect("line is stored");
+    for index in 0..MEASURED_STOPS {
+        tx.put(WithOrigin::new(
+            origin.clone(),
+            WithId::new(
+                Id::new(stop_id(index)),
+                StopBuilder::new(&format!("Haltestelle {}", index)).build(),
+            ),
+        ))
+        .await
+        .expect("stop is stored");
+    }
+    for index in 0..MEASURED_TRIPS {
+        let trip_id = Id::new(format!("{}-trip-{}", ORIGIN, index));
+        tx.put(WithOrigin::new(
+            origin.clone(),
+            WithId::new(
+                trip_id.clone(),
+                TripBuilder::new(&line_id, 0, 0)
+                    .service(service_id.raw())
+                    .build(),
+            ),
+        ))
+        .await
+        .expect("trip is stored");
+        let start = (index * 3) as i64;
+        let stop_times = (0..MEASURED_STOP_TIMES)
+            .map(|sequence| {
+                stop_time(
+                    sequence as i32,
+                    &stop_id((index + sequence) % MEASURED_STOPS),
+                    start + 2 * sequence as i64,
+                )
+            })
+            .collect::<Vec<_>>();
+        tx.put_stop_times(&trip_id, &origin, &stop_times, true)
+            .await
+            .expect("stop times are stored");
+    }
+    tx.commit().await.expect("transaction is committed");
+
+    let client = Server::new(database).client(ORIGIN);
+    let origins = [origin];
+    let stop_ids = (0..10)
+        .map(|index| Id::new(stop_id(index)))
+        .collect::<Vec<_>>();
+    let stop_ids = stop_ids.iter().collect::<Vec<_>>();
+    let start = today
+        .and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .unwrap();
+    let end = start + Duration::days(1);
+    let mut elapsed = [StdDuration::ZERO; 2];
+    let mut instances = [0; 2];
+    for _ in 0..MEASURED_RUNS {
+        for (index, stop_names_from_stop_times) in
+            [false, true].into_iter().enumerate()
+        {
+            let trips = client
+                .get_all_trips_via_stops(
+                    &stop_ids,
+                    start,
+                    end,
+                    Default::default(),
+                    &origins,
+                )
+                .await
+                .expect("trips are read");
+            let now = Instant::now();
+            instances[index] = client
+                .instanciate_trips_include(
+                    trips,
+                    DateTimeRange::new(start, end),
+                    &TripInstantiationOptions {
+                        stop_ids_of_interest: Some(&stop_ids),
+                        stop_names_from_stop_times,
+                        ..TripInstantiationOptions::all_references()
+                    },
+                    &origins,
+                )
+                .await
+                .expect("trips are instantiated")
+                .len();
+            elapsed[index] += now.elapsed();
+        }
+    }
+    assert_eq!(instances[0], instances[1]);
+    println!(
+        "instantiated {} trips in {:?} with stops looked up, in {:?} with stop \
+         names of stop times (average of {} runs).",
+        instances[0],
+        elapsed[0] / MEASURED_RUNS,
+        elapsed[1] / MEASURED_RUNS,
+        MEASURED_RUNS
+    );
+}
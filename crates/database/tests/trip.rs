@@ -0,0 +1,425 @@
+mod common;
+
+use chrono::{Duration, NaiveDate};
+use model::{
+    calendar::{CalendarDate, ServiceExceptionType},
+    fixtures::{stop_time, StopBuilder, TripBuilder},
+    line::{Line, LineType},
+    stop::Stop,
+    trip::{CouplingKind, PickupDropOffType, StopTime, Trip},
+    DatabaseEntry, WithId, WithOrigin,
+};
+use public_transport::database::{Repo, ServiceRepo, SubjectRepo, TripRepo};
+use serde::Serialize;
+use utility::id::{HasId, Id};
+
+const ORIGIN: &str = "test-trip";
+
+fn with_id<T>(id: &str, content: T) -> WithOrigin<WithId<T>>
+where
+    T: Serialize + HasId<IdType = String>,
+{
+    with_origin(ORIGIN, id, content)
+}
+
+fn with_origin<T>(origin: &str, id: &str, content: T) -> WithOrigin<WithId<T>>
+where
+    T: Serialize + HasId<IdType = String>,
+{
+    WithOrigin::new(
+        Id::new(origin.to_owned()),
+        WithId::new(Id::new(id.to_owned()), content),
+    )
+}
+
+fn line() -> Line {
+    Line {
+        name: Some("300".to_owned()),
+        kind: LineType::Bus,
+        agency_id: None,
+        secondary_agency_ids: vec![],
+        updated_at: None,
+    }
+}
+
+/// Stores a line and a trip of it with the given id, and the stop `<id>-stop` for
+/// its stop times.
+async fn put_trip<D>(tx: &mut D, id: &str) -> Id<Trip>
+where
+    D: Repo<Stop> + Repo<Line> + Repo<Trip>,
+{
+    tx.put(with_id(
+        &format!("{}-stop", id),
+        StopBuilder::new(id).build(),
+    ))
+    .await
+    .expect("stop is stored");
+    let line_id = format!("{}-line", id);
+    tx.put(with_id(&line_id, line()))
+        .await
+        .expect("line is stored");
+    tx.put(with_id(id, TripBuilder::new(&line_id, 0, 0).build()))
+        .await
+        .expect("trip is stored");
+    Id::new(id.to_owned())
+}
+
+#[tokio::test]
+async fn unknown_pickup_and_drop_off_types_are_stored_as_unknown() {
+    let Some(db) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&db, ORIGIN).await;
+    let trip_id = put_trip(&mut tx, "test-trip-types").await;
+    let origin = Id::new(ORIGIN.to_owned());
+
+    tx.put_stop_time(
+        trip_id.clone(),
+        WithOrigin::new(
+            origin.clone(),
+            StopTime {
+                pickup_type: None,
+                drop_off_type: None,
+                ..stop_time(1, "test-trip-types-stop", 1)
+            },
+        ),
+    )
+    .await
+    .expect("stop time is stored");
+    tx.put_stop_times(
+        &trip_id,
+        &origin,
+        &[StopTime {
+            pickup_type: Some(PickupDropOffType::NotAvailable),
+            ..stop_time(2, "test-trip-types-stop", 2)
+        }],
+        false,
+    )
+    .await
+    .expect("stop times are stored");
+
+    let stop_times = tx
+        .get_stop_times(trip_id, origin)
+        .await
+        .expect("stop times are read");
+    let types = stop_times
+        .iter()
+        .map(|stop_time| (stop_time.pickup_type, stop_time.drop_off_type))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        types,
+        vec![
+            (None, None),
+            (
+                Some(PickupDropOffType::NotAvailable),
+                Some(PickupDropOffType::Regular)
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn pages_only_contain_trips_of_the_given_origins() {
+    let Some(db) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&db, ORIGIN).await;
+    const OTHER: &str = "test-trip-other";
+    common::put_origin(&mut tx, OTHER).await;
+
+    for id in ["test-trip-page-a", "test-trip-page-b", "test-trip-page-c"] {
+        put_trip(&mut tx, id).await;
+    }
+    tx.put(with_origin(OTHER, "test-trip-page-other-line", line()))
+        .await
+        .expect("line of the other origin is stored");
+    for id in ["test-trip-page-aa", "test-trip-page-c"] {
+        tx.put(with_origin(
+            OTHER,
+            id,
+            TripBuilder::new("test-trip-page-other-line", 0, 0).build(),
+        ))
+        .await
+        .expect("trip of the other origin is stored");
+    }
+    let origins = [Id::new(ORIGIN.to_owned())];
+    let ids_and_origins = |entries: &[DatabaseEntry<Trip>]| {
+        entries
+            .iter()
+            .map(|entry| {
+                let origins = entry
+                    .source_data
+                    .iter()
+                    .map(|source| source.origin.raw())
+                    .collect::<Vec<_>>();
+                (entry.id.raw(), origins)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let first = TripRepo::get_page_after(&mut tx, None, 2, &origins)
+        .await
+        .expect("first page is read");
+    assert_eq!(
+        ids_and_origins(&first),
+        vec![
+            ("test-trip-page-a".to_owned(), vec![ORIGIN.to_owned()]),
+            ("test-trip-page-b".to_owned(), vec![ORIGIN.to_owned()]),
+        ]
+    );
+    let second =
+        TripRepo::get_page_after(&mut tx, Some(first[1].id.clone()), 2, &origins)
+            .await
+            .expect("second page is read");
+    assert_eq!(
+        ids_and_origins(&second),
+        vec![("test-trip-page-c".to_owned(), vec![ORIGIN.to_owned()])]
+    );
+}
+
+#[tokio::test]
+async fn text_search_matches_line_names_followed_by_short_names() {
+    let Some(db) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&db, ORIGIN).await;
+    let service_day = NaiveDate::from_ymd_opt(2024, 3, 8).unwrap();
+    let (service_id, _) = tx
+        .put_calendar_date(
+            None,
+            CalendarDate {
+                date: service_day,
+                exception_type: ServiceExceptionType::Added,
+            },
+        )
+        .await
+        .expect("service is stored");
+    for (id, name) in [
+        ("test-trip-search-re7", "RE 7"),
+        ("test-trip-search-re70", "RE 70"),
+    ] {
+        tx.put(with_id(
+            id,
+            Line {
+                name: Some(name.to_owned()),
+                kind: LineType::RegionalRail,
+                ..line()
+            },
+        ))
+        .await
+        .expect("line is stored");
+    }
+    let trips = [
+        (
+            "test-trip-search-a",
+            "test-trip-search-re7",
+            "21034",
+            "Kiel Hbf",
+        ),
+        (
+            "test-trip-search-b",
+            "test-trip-search-re70",
+            "21035",
+            "Kiel_Hbf",
+        ),
+    ];
+    for (id, line_id, short_name, headsign) in trips {
+        tx.put(with_id(
+            id,
+            Trip {
+                service_id: Some(service_id),
+                short_name: Some(short_name.to_owned()),
+                headsign: Some(headsign.to_owned()),
+                ..TripBuilder::new(line_id, 0, 0).build()
+            },
+        ))
+        .await
+        .expect("trip is stored");
+    }
+
+    let cases = [
+        ("RE 7", vec!["test-trip-search-a", "test-trip-search-b"]),
+        ("RE 7 21034", vec!["test-trip-search-a"]),
+        ("7 2103", vec!["test-trip-search-a"]),
+        ("21035", vec!["test-trip-search-b"]),
+        ("Kiel_Hbf", vec!["test-trip-search-b"]),
+        ("Kiel%", vec![]),
+    ];
+    for (pattern, expected) in cases {
+        let found = tx
+            .search_text(pattern, service_day, 10)
+            .await
+            .expect("trips are found")
+            .into_iter()
+            .filter(|trip| trip.id.raw().starts_with("test-trip-search-"))
+            .map(|trip| trip.id.raw())
+            .collect::<Vec<_>>();
+        assert_eq!(found, expected, "trips of the pattern `{:?}`", pattern);
+    }
+    let other_day = service_day + Duration::days(2);
+    let found = tx
+        .search_text("RE 7", other_day, 10)
+        .await
+        .expect("trips are found");
+    assert!(found.is_empty(), "trips without service are found");
+}
+
+#[tokio::test]
+async fn deleting_a_trip_deletes_its_stop_times_and_original_ids() {
+    let Some(db) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&db, ORIGIN).await;
+    let origin = Id::new(ORIGIN.to_owned());
+    let deleted = put_trip(&mut tx, "test-trip-delete").await;
+    let kept = put_trip(&mut tx, "test-trip-keep").await;
+    for (trip_id, original_id) in [(&deleted, "delete"), (&kept, "keep")] {
+        let stop_id = format!("{}-stop", trip_id.raw());
+        tx.put_stop_times(
+            trip_id,
+            &origin,
+            &[stop_time(1, &stop_id, 1), stop_time(2, &stop_id, 2)],
+            false,
+        )
+        .await
+        .expect("stop times are stored");
+        tx.put_original_id(origin.clone(), original_id.to_owned(), trip_id.clone())
+            .await
+            .expect("original id is stored");
+    }
+
+    tx.delete_trip(&deleted, &origin)
+        .await
+        .expect("trip is deleted");
+
+    assert!(Repo::<Trip>::get(&mut tx, deleted.clone())
+        .await
+        .expect("trip is read")
+        .source_data
+        .is_empty());
+    assert!(tx
+        .get_stop_times(deleted.clone(), origin.clone())
+        .await
+        .expect("stop times are read")
+        .is_empty());
+    assert_eq!(
+        SubjectRepo::<Trip>::id_by_original_id(
+            &mut tx,
+            origin.clone(),
+            "delete".into()
+        )
+        .await
+        .expect("original id is looked up"),
+        None
+    );
+
+    // other trips of the origin are left untouched.
+    assert_eq!(
+        tx.get_stop_times(kept.clone(), origin.clone())
+            .await
+            .expect("stop times are read")
+            .len(),
+        2
+    );
+    assert_eq!(
+        SubjectRepo::<Trip>::id_by_original_id(
+            &mut tx,
+            origin.clone(),
+            "keep".into()
+        )
+        .await
+        .expect("original id is looked up"),
+        Some(kept)
+    );
+}
+
+/// Coupled trips, their headsigns and how they are coupled with the trip.
+async fn couplings<D: TripRepo>(
+    tx: &mut D,
+    trip_id: &Id<Trip>,
+) -> Vec<(String, Option<String>, CouplingKind)> {
+    tx.get_trip_couplings(std::slice::from_ref(trip_id))
+        .await
+        .expect("couplings are read")
+        .into_iter()
+        .map(|coupling| {
+            let coupling = coupling.content;
+            (
+                coupling.coupled_trip_id.raw(),
+                coupling.coupled_headsign,
+                coupling.kind,
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn couplings_follow_the_wings_of_a_trip() {
+    let Some(db) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&db, ORIGIN).await;
+    let origin = Id::new(ORIGIN.to_owned());
+    let front = put_trip(&mut tx, "test-trip-re72").await;
+    let rear = TripBuilder::new("test-trip-re72-line", 0, 0)
+        .headsign("Husum")
+        .build();
+    let rear = tx
+        .put(with_id("test-trip-re74", rear))
+        .await
+        .expect("trip is stored")
+        .content
+        .id;
+    for (trip_id, original_id) in [(&front, "72"), (&rear, "74")] {
+        tx.put_original_id(origin.clone(), original_id.to_owned(), trip_id.clone())
+            .await
+            .expect("original id is stored");
+    }
+
+    // the third part of the train is not stored yet.
+    let wings = [
+        ("74".to_owned(), CouplingKind::Split),
+        ("76".to_owned(), CouplingKind::Split),
+    ];
+    tx.put_trip_couplings(&front, &origin, 4, None, &wings)
+        .await
+        .expect("couplings are stored");
+    assert_eq!(
+        couplings(&mut tx, &front).await,
+        [(
+            "test-trip-re74".to_owned(),
+            Some("Husum".to_owned()),
+            CouplingKind::Split
+        )]
+    );
+
+    let third = put_trip(&mut tx, "test-trip-re76").await;
+    tx.put_original_id(origin.clone(), "76".to_owned(), third)
+        .await
+        .expect("original id is stored");
+    assert_eq!(couplings(&mut tx, &front).await.len(), 2);
+
+    // a change of the wings replaces the couplings at the stop.
+    tx.put_trip_couplings(
+        &front,
+        &origin,
+        4,
+        None,
+        &[("74".to_owned(), CouplingKind::Coupled)],
+    )
+    .await
+    .expect("couplings are replaced");
+    assert_eq!(
+        couplings(&mut tx, &front).await,
+        [(
+            "test-trip-re74".to_owned(),
+            Some("Husum".to_owned()),
+            CouplingKind::Coupled
+        )]
+    );
+
+    tx.put_trip_couplings(&front, &origin, 4, None, &[])
+        .await
+        .expect("couplings are removed");
+    assert!(couplings(&mut tx, &front).await.is_empty());
+}
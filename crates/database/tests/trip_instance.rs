@@ -0,0 +1,118 @@
+//! Trip instances as served by the client, which reads committed data. Ids are
+//! specific to these tests, so that repeated runs update the same rows.
+
+mod common;
+
+use chrono::{Duration, NaiveDate};
+use model::{
+    calendar::{CalendarDate, ServiceExceptionType},
+    fixtures::{stop_time, StopBuilder, TripBuilder},
+    line::{Line, LineType},
+    origin::Origin,
+    WithId, WithOrigin,
+};
+use public_transport::{
+    database::{
+        Database, DatabaseOperations, DatabaseTransaction, Repo, ServiceRepo,
+        TripRepo,
+    },
+    server::Server,
+    RequestError,
+};
+use utility::id::Id;
+
+const ORIGIN: &str = "test-trip-instance";
+
+#[tokio::test]
+async fn trip_instances_are_fetched_by_date_and_index() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let origin = Id::new(ORIGIN.to_owned());
+    let date = NaiveDate::from_ymd_opt(2024, 3, 8).unwrap();
+    let trip_id = Id::new("test-trip-instance-trip".to_owned());
+    let mut tx = database.transaction().await.expect("transaction begins");
+    tx.put_origin(WithId::new(
+        origin.clone(),
+        Origin {
+            name: ORIGIN.to_owned(),
+            priority: 0,
+        },
+    ))
+    .await
+    .expect("origin is stored");
+    let (service_id, _) = tx
+        .put_calendar_date(
+            None,
+            CalendarDate {
+                date,
+                exception_type: ServiceExceptionType::Added,
+            },
+        )
+        .await
+        .expect("service is stored");
+    let line_id = Id::new("test-trip-instance-line".to_owned());
+    let line = Line {
+        name: Some("11".to_owned()),
+        kind: LineType::Bus,
+        agency_id: None,
+        secondary_agency_ids: vec![],
+        updated_at: None,
+    };
+    tx.put(WithOrigin::new(
+        origin.clone(),
+        WithId::new(line_id.clone(), line),
+    ))
+    .await
+    .expect("line is stored");
+    let trip = TripBuilder::new(&line_id.raw(), 0, 0)
+        .service(service_id.raw())
+        .headsign("Dietrichsdorf")
+        .build();
+    tx.put(WithOrigin::new(
+        origin.clone(),
+        WithId::new(trip_id.clone(), trip),
+    ))
+    .await
+    .expect("trip is stored");
+    let stop_id = "test-trip-instance-stop";
+    tx.put(WithOrigin::new(
+        origin.clone(),
+        StopBuilder::new("Kiel Hbf").with_id(stop_id),
+    ))
+    .await
+    .expect("stop is stored");
+    tx.put_stop_times(
+        &trip_id,
+        &origin,
+        &[stop_time(1, stop_id, 480), stop_time(2, stop_id, 490)],
+        true,
+    )
+    .await
+    .expect("stop times are stored");
+    tx.commit().await.expect("transaction is committed");
+
+    let client = Server::new(database).client(ORIGIN);
+    let origins = [origin];
+    let instance = client
+        .get_trip_instance(trip_id.clone(), date, 0, &origins)
+        .await
+        .expect("instance is found");
+    assert_eq!(instance.info.trip_id, trip_id);
+    assert_eq!(instance.stops.len(), 2);
+    assert!(instance.stop_of_interest.is_none());
+
+    // the trip has a single instance per day and none on other days.
+    let cases = [(date, 1), (date + Duration::days(1), 0)];
+    for (date, index) in cases {
+        let result = client
+            .get_trip_instance(trip_id.clone(), date, index, &origins)
+            .await;
+        assert!(
+            matches!(result, Err(RequestError::NotFound)),
+            "{} #{}",
+            date,
+            index
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! Lines, agencies and stops of instantiated trips are resolved in batches. The
+//! referenced entries are committed, their ids are specific to this test.
+
+mod common;
+
+use chrono::{Duration, Local, NaiveDate, NaiveTime, Weekday};
+use model::{
+    agency::Agency,
+    calendar::Service,
+    fixtures::{ServiceBuilder, StopBuilder, TripBuilder},
+    line::{Line, LineType},
+    trip::Trip,
+    DateTimeRange, WithId, WithOrigin,
+};
+use public_transport::{
+    client::TripInstantiationOptions,
+    database::{DatabaseTransaction, Repo},
+    instrumented::InstrumentedDatabase,
+    server::Server,
+};
+use serde::Serialize;
+use utility::id::{HasId, Id};
+
+const ORIGIN: &str = "test-trip-references";
+const STOPS: usize = 4;
+const TRIPS: usize = 12;
+
+fn id<T: HasId<IdType = String>>(name: &str) -> Id<T> {
+    Id::new(format!("{}-{}", ORIGIN, name))
+}
+
+fn with_id<T>(name: &str, content: T) -> WithOrigin<WithId<T>>
+where
+    T: Serialize + HasId<IdType = String>,
+{
+    WithOrigin::new(Id::new(ORIGIN.to_owned()), WithId::new(id(name), content))
+}
+
+fn agency(name: &str) -> Agency {
+    Agency {
+        name: name.to_owned(),
+        website: String::new(),
+        phone_number: None,
+        email: None,
+        fare_url: None,
+    }
+}
+
+fn line(name: &str, agency: &str, secondary_agencies: &[&str]) -> Line {
+    Line {
+        name: Some(name.to_owned()),
+        kind: LineType::Bus,
+        agency_id: Some(id(agency)),
+        secondary_agency_ids: secondary_agencies
+            .iter()
+            .map(|name| id(name))
+            .collect(),
+        updated_at: None,
+    }
+}
+
+fn date(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 6, day).expect("valid date")
+}
+
+/// Trip on one of three lines, which serves all stops in the morning.
+fn trip(index: usize, service_id: Id<Service>) -> WithId<Trip> {
+    let mut trip = TripBuilder::new(&format!("{}-line-{}", ORIGIN, index % 3), 8, 0)
+        .service(service_id.raw());
+    for sequence in 0..STOPS {
+        let minutes = (index + sequence) as i64;
+        trip = trip.stop(&format!("{}-stop-{}", ORIGIN, sequence), minutes);
+    }
+    trip.with_id(&format!("{}-trip-{}", ORIGIN, index))
+}
+
+#[tokio::test]
+async fn references_are_resolved_with_one_query_per_entity_type() {
+    let Some(database) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&database, ORIGIN).await;
+    for name in ["kvg", "autokraft"] {
+        tx.put(with_id(name, agency(name)))
+            .await
+            .expect("agency is stored");
+    }
+    for (name, line) in [
+        ("line-0", line("11", "kvg", &[])),
+        ("line-1", line("300", "autokraft", &["kvg"])),
+        ("line-2", line("61", "kvg", &[])),
+    ] {
+        tx.put(with_id(name, line)).await.expect("line is stored");
+    }
+    for index in 0..STOPS {
+        let stop = StopBuilder::new(&format!("Haltestelle {}", index))
+            .at(54.32, 10.13)
+            .build();
+        tx.put(with_id(&format!("stop-{}", index), stop))
+            .await
+            .expect("stop is stored");
+    }
+    tx.commit().await.expect("transaction is committed");
+
+    let database = InstrumentedDatabase::new(database);
+    let client = Server::new(database.clone()).client(ORIGIN);
+    let service_id = client
+        .push_service(
+            ServiceBuilder::new(date(3), date(16))
+                .weekdays()
+                .days(&[Weekday::Sat, Weekday::Sun])
+                .build(),
+            None,
+        )
+        .await
+        .expect("service is stored");
+    let trips = (0..TRIPS)
+        .map(|index| trip(index, service_id))
+        .collect::<Vec<_>>();
+    let start = date(10)
+        .and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .unwrap();
+    let instances = client
+        .instanciate_trips_include(
+            trips,
+            DateTimeRange::new(start, start + Duration::days(1)),
+            &TripInstantiationOptions::all_references(),
+            &[Id::new(ORIGIN.to_owned())],
+        )
+        .await
+        .expect("trips are instantiated");
+
+    assert_eq!(instances.len(), TRIPS);
+    for instance in instances.iter() {
+        let line = instance.line.as_ref().expect("line is included");
+        let agency = instance.agency.as_ref().expect("agency is included");
+        assert_eq!(Some(&agency.id), line.content.agency_id.as_ref());
+        assert_eq!(
+            instance.secondary_agencies.len(),
+            line.content.secondary_agency_ids.len()
+        );
+        assert!(
+            instance.stops.iter().all(|stop| stop.stop_name.is_some()),
+            "stop names of {:?} are included",
+            instance.info.trip_id
+        );
+    }
+
+    let lookups = database
+        .metrics()
+        .snapshot()
+        .into_iter()
+        .filter(|metrics| {
+            ["Repo<Line>", "Repo<Agency>", "Repo<Stop>"]
+                .iter()
+                .any(|repo| metrics.operation.starts_with(repo))
+        })
+        .map(|metrics| (metrics.operation, metrics.calls))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lookups,
+        vec![
+            ("Repo<Agency>::get_many".to_owned(), 1),
+            ("Repo<Line>::get_many".to_owned(), 1),
+            ("Repo<Stop>::get_many".to_owned(), 1),
+        ]
+    );
+}
@@ -0,0 +1,524 @@
+mod common;
+
+use chrono::{DateTime, Duration, DurationRound, Local};
+use database::{
+    queries::trip_update::{get_for_trip_instances, TRIP_IDS_PER_QUERY},
+    PgDatabase,
+};
+use model::{
+    stop::Stop,
+    trip::Trip,
+    trip_update::{
+        HistoricDelay, StopTimeStatus, StopTimeUpdate, TripStatus, TripUpdate,
+        TripUpdateId,
+    },
+    DatabaseEntry, DateTimeRange, WithId, WithOrigin,
+};
+use public_transport::{
+    database::{Database, DatabaseTransaction, RealtimeRepo, Repo},
+    instrumented::InstrumentedDatabase,
+    server::Server,
+};
+use utility::id::Id;
+
+const ORIGIN: &str = "test-trip-update";
+
+fn stop(name: &str, parent_id: Option<&str>) -> Stop {
+    Stop {
+        name: Some(name.to_owned()),
+        description: None,
+        parent_id: parent_id.map(|id| Id::new(id.to_owned())),
+        location: None,
+        platform_code: None,
+        updated_at: None,
+        amenities: vec![],
+    }
+}
+
+fn update(stop_id: &str) -> TripUpdate {
+    TripUpdate {
+        status: TripStatus::Added,
+        stops: vec![StopTimeUpdate {
+            scheduled_stop_sequence: None,
+            stop_id: Some(Id::new(stop_id.to_owned())),
+            arrival_time: None,
+            departure_time: Some(Local::now()),
+            status: StopTimeStatus::Scheduled,
+            platform: None,
+            scheduled_platform: None,
+        }],
+        timestamp: None,
+    }
+}
+
+#[tokio::test]
+async fn updates_at_platforms_are_found_by_their_station() {
+    let Some(db) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&db, ORIGIN).await;
+    let origin = Id::new(ORIGIN.to_owned());
+    let stops = [
+        ("test-trip-update-station", stop("Kiel Hbf", None)),
+        (
+            "test-trip-update-platform",
+            stop("Kiel Hbf", Some("test-trip-update-station")),
+        ),
+        ("test-trip-update-other", stop("Kiel Sophienhof", None)),
+    ];
+    for (id, stop) in stops {
+        tx.put(WithOrigin::new(
+            origin.clone(),
+            WithId::new(Id::new(id.to_owned()), stop),
+        ))
+        .await
+        .expect("stop is stored");
+    }
+    let today = Local::now().date_naive();
+    let updates = [
+        ("added-a", update("test-trip-update-platform")),
+        ("added-b", update("test-trip-update-station")),
+        ("added-c", update("test-trip-update-other")),
+    ]
+    .map(|(trip_id, update)| {
+        WithId::new(
+            Id::new(TripUpdateId::new(Id::new(trip_id.to_owned()), today)),
+            update,
+        )
+    });
+    tx.put_trip_updates(&origin, &updates)
+        .await
+        .expect("trip updates are stored");
+
+    let now = Local::now();
+    let cases = [
+        ("test-trip-update-station", vec!["added-a", "added-b"]),
+        ("test-trip-update-platform", vec!["added-a"]),
+    ];
+    for (stop_id, expected) in cases {
+        let mut trip_ids = tx
+            .get_realtime_updates_for_stop(
+                &Id::new(stop_id.to_owned()),
+                DateTimeRange::new(now, now + Duration::hours(1)),
+            )
+            .await
+            .expect("trip updates are read")
+            .into_iter()
+            .map(|entry| entry.id.raw().trip_id.raw())
+            .collect::<Vec<_>>();
+        trip_ids.sort();
+        assert_eq!(trip_ids, expected, "{}", stop_id);
+    }
+}
+
+fn delay(
+    scheduled_stop_sequence: i32,
+    timestamp: DateTime<Local>,
+    cause: &str,
+) -> HistoricDelay {
+    HistoricDelay {
+        scheduled_stop_sequence,
+        stop_id: None,
+        timestamp,
+        arrival_time: None,
+        departure_time: Some(timestamp + Duration::minutes(5)),
+        cause: Some(cause.to_owned()),
+        source: None,
+    }
+}
+
+#[tokio::test]
+async fn duplicate_announcements_keep_the_last_delay() {
+    let Some(db) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&db, ORIGIN).await;
+    let origin = Id::new(ORIGIN.to_owned());
+    let trip_id = Id::new("test-trip-update-delayed".to_owned());
+    let today = Local::now().date_naive();
+    let announced = Local::now().duration_trunc(Duration::seconds(1)).unwrap();
+    let delays = [
+        delay(1, announced, "first"),
+        delay(2, announced, "other stop"),
+        delay(1, announced, "second"),
+        // the same row after rounding to microseconds.
+        delay(1, announced + Duration::nanoseconds(1), "last"),
+        delay(1, announced + Duration::minutes(1), "later"),
+    ];
+    tx.put_historic_delays(&origin, &trip_id, today, &delays)
+        .await
+        .expect("duplicate delays are stored");
+    // stored delays are updated by the next batch.
+    tx.put_historic_delays(&origin, &trip_id, today, &[delay(2, announced, "new")])
+        .await
+        .expect("delays are updated");
+
+    let causes = tx
+        .get_historic_delays(&trip_id, today)
+        .await
+        .expect("delays are read")
+        .into_iter()
+        .map(|delay| {
+            let delay = delay.content;
+            (
+                delay.scheduled_stop_sequence,
+                delay.cause.unwrap_or_default(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        causes,
+        [
+            (1, "last".to_owned()),
+            (1, "later".to_owned()),
+            (2, "new".to_owned()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn instances_of_a_trip_on_the_same_day_have_their_own_updates() {
+    let Some(db) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&db, ORIGIN).await;
+    let origin = Id::new(ORIGIN.to_owned());
+    let trip_id: Id<Trip> = Id::new("test-trip-update-frequent".to_owned());
+    let today = Local::now().date_naive();
+    let ids = [0, 1, 2].map(|instance| {
+        Id::new(TripUpdateId::of_instance(trip_id.clone(), today, instance))
+    });
+    let updates = [
+        (&ids[0], TripStatus::Scheduled),
+        (&ids[2], TripStatus::Cancelled),
+    ]
+    .map(|(id, status)| {
+        WithId::new(
+            id.clone(),
+            TripUpdate {
+                status,
+                stops: vec![],
+                timestamp: None,
+            },
+        )
+    });
+    tx.put_trip_updates(&origin, &updates)
+        .await
+        .expect("updates of both instances are stored");
+
+    let mut stored = tx
+        .get_realtime_for_trip_instances(&ids)
+        .await
+        .expect("updates are read")
+        .into_iter()
+        .map(|entry| {
+            let [source] = entry.source_data.as_slice() else {
+                panic!("one origin stored the update");
+            };
+            (
+                entry.id.raw().instance,
+                format!("{:?}", source.content.status),
+            )
+        })
+        .collect::<Vec<_>>();
+    stored.sort();
+    assert_eq!(
+        stored,
+        [(0, "Scheduled".to_owned()), (2, "Cancelled".to_owned())]
+    );
+    let cancelled = tx
+        .get_realtime_for_trip(&trip_id, today, 2)
+        .await
+        .expect("update is read");
+    assert_eq!(cancelled.id, ids[2]);
+    assert_eq!(cancelled.source_data.len(), 1);
+    let missing = tx
+        .get_realtime_for_trip(&trip_id, today, 1)
+        .await
+        .expect("missing update is read");
+    assert!(missing.source_data.is_empty());
+}
+
+#[tokio::test]
+async fn daily_trip_ids_only_see_the_updates_of_their_service_day() {
+    let Some(db) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&db, ORIGIN).await;
+    let origin = Id::new(ORIGIN.to_owned());
+    // e.g. the daily trip ids of DB data, which are the same on each day.
+    let trip_id: Id<Trip> = Id::new("test-trip-update-daily".to_owned());
+    let today = Local::now().date_naive();
+    let yesterday = today - Duration::days(1);
+    let updates = [
+        (yesterday, TripStatus::Cancelled),
+        (today, TripStatus::Scheduled),
+    ]
+    .map(|(day, status)| {
+        WithId::new(
+            Id::new(TripUpdateId::new(trip_id.clone(), day)),
+            TripUpdate {
+                status,
+                stops: vec![],
+                timestamp: None,
+            },
+        )
+    });
+    tx.put_trip_updates(&origin, &updates)
+        .await
+        .expect("updates of both days are stored");
+
+    for (day, expected) in [(yesterday, "Cancelled"), (today, "Scheduled")] {
+        let id = Id::new(TripUpdateId::new(trip_id.clone(), day));
+        let by_instances = tx
+            .get_realtime_for_trip_instances(std::slice::from_ref(&id))
+            .await
+            .expect("updates are read")
+            .into_iter()
+            .flat_map(|entry| {
+                assert_eq!(entry.id, id, "update of another day on {}", day);
+                entry.source_data
+            })
+            .map(|source| format!("{:?}", source.content.status))
+            .collect::<Vec<_>>();
+        assert_eq!(by_instances, [expected], "updates on {}", day);
+
+        let by_trip = tx
+            .get_realtime_for_trip(&trip_id, day, 0)
+            .await
+            .expect("update is read")
+            .source_data
+            .into_iter()
+            .map(|source| format!("{:?}", source.content.status))
+            .collect::<Vec<_>>();
+        assert_eq!(by_trip, [expected], "update of the trip on {}", day);
+    }
+}
+
+/// Latest timestamp of the updates of the test origin.
+async fn latest_timestamp(tx: &mut impl RealtimeRepo) -> Option<DateTime<Local>> {
+    tx.latest_update_timestamps()
+        .await
+        .expect("timestamps are read")
+        .into_iter()
+        .find(|(id, _)| id.raw_ref::<str>() == ORIGIN)
+        .map(|(_, timestamp)| timestamp)
+}
+
+#[tokio::test]
+async fn storing_updates_keeps_the_latest_timestamp_of_the_origin() {
+    let Some(db) = common::connect().await else {
+        return;
+    };
+    let mut tx = common::transaction(&db, ORIGIN).await;
+    let origin = Id::new(ORIGIN.to_owned());
+    let today = Local::now().date_naive();
+    // timestamps are stored with microseconds.
+    let now = Local::now().duration_trunc(Duration::seconds(1)).unwrap();
+    let put = |trip_id: &str, timestamp: Option<DateTime<Local>>| {
+        [WithId::new(
+            Id::new(TripUpdateId::new(Id::new(trip_id.to_owned()), today)),
+            TripUpdate {
+                timestamp,
+                ..update("test-trip-update-stop")
+            },
+        )]
+    };
+
+    assert_eq!(latest_timestamp(&mut tx).await, None, "no updates yet");
+
+    let cases = [
+        ("a", None, None),
+        (
+            "a",
+            Some(now - Duration::minutes(5)),
+            Some(now - Duration::minutes(5)),
+        ),
+        ("b", Some(now), Some(now)),
+        // e.g. a delayed update of another trip.
+        ("a", Some(now - Duration::minutes(1)), Some(now)),
+        ("c", None, Some(now)),
+    ];
+    for (trip_id, timestamp, expected) in cases {
+        tx.put_trip_updates(&origin, &put(trip_id, timestamp))
+            .await
+            .expect("update is stored");
+        assert_eq!(
+            latest_timestamp(&mut tx).await,
+            expected,
+            "latest timestamp after an update of {} at {:?}",
+            trip_id,
+            timestamp
+        );
+    }
+}
+
+/// Instances with updates, which take two chunks of trip ids.
+const CHUNKED_INSTANCES: usize = TRIP_IDS_PER_QUERY + 500;
+
+/// Compares the chunked lookup of the updates of trip instances with a single
+/// query for all of them, on updates of an origin of its own, which are committed,
+/// and checks that the lookup is supported by an index.
+#[tokio::test]
+async fn chunked_instance_lookups_are_equal_to_a_single_query() {
+    const ORIGIN: &str = "test-trip-update-chunks";
+    let Some(db) = common::connect().await else {
+        return;
+    };
+    let origin = Id::new(ORIGIN.to_owned());
+    let today = Local::now().date_naive();
+    let ids = (0..CHUNKED_INSTANCES)
+        .map(|index| {
+            Id::new(TripUpdateId::new(
+                Id::new(format!("{}-{}", ORIGIN, index)),
+                today - Duration::days(index as i64 % 2),
+            ))
+        })
+        .collect::<Vec<_>>();
+    let mut tx = db.transaction().await.expect("transaction begins");
+    common::put_origin(&mut tx, ORIGIN).await;
+    for chunk in ids.chunks(<PgDatabase as Database>::BULK_INSERT_MAX) {
+        let updates = chunk
+            .iter()
+            .map(|id| {
+                WithId::new(
+                    id.clone(),
+                    TripUpdate {
+                        status: TripStatus::Scheduled,
+                        stops: vec![],
+                        timestamp: None,
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        tx.put_trip_updates(&origin, &updates)
+            .await
+            .expect("trip updates are stored");
+    }
+    tx.commit().await.expect("transaction is committed");
+
+    // instances without updates are looked up along.
+    let mut lookup = ids.clone();
+    lookup.push(Id::new(TripUpdateId::new(
+        Id::new(format!("{}-missing", ORIGIN)),
+        today,
+    )));
+    let entry_ids = |entries: Vec<DatabaseEntry<TripUpdate>>| {
+        let mut ids = entries
+            .into_iter()
+            .filter(|entry| !entry.source_data.is_empty())
+            .map(|entry| {
+                let id = entry.id.raw();
+                (id.trip_id.raw(), id.trip_start_date, id.instance)
+            })
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    };
+    let chunked = db
+        .auto()
+        .get_realtime_for_trip_instances(&lookup)
+        .await
+        .expect("updates are read in chunks");
+    let pool = common::pool().await;
+    let single = get_for_trip_instances(&pool, &lookup)
+        .await
+        .expect("updates are read at once");
+    let chunked = entry_ids(chunked);
+    assert_eq!(chunked.len(), CHUNKED_INSTANCES);
+    assert_eq!(chunked, entry_ids(single));
+
+    // sequential scans are only chosen, if no index supports the lookup.
+    let mut connection = pool.acquire().await.expect("connection is acquired");
+    sqlx::query("SET enable_seqscan = off")
+        .execute(&mut *connection)
+        .await
+        .expect("sequential scans are disabled");
+    let plan: Vec<String> = sqlx::query_scalar(
+        "
+        EXPLAIN
+        SELECT origin, trip_id, trip_start_date, instance
+        FROM trip_updates
+        WHERE
+            (trip_id, trip_start_date, instance) IN (
+                SELECT * FROM UNNEST($1::text[], $2::date[], $3::integer[])
+            )
+            AND trip_start_date = ANY($2::date[]);
+        ",
+    )
+    .bind(vec![format!("{}-0", ORIGIN)])
+    .bind(vec![today])
+    .bind(vec![0])
+    .fetch_all(&mut *connection)
+    .await
+    .expect("lookup is explained");
+    sqlx::query("RESET enable_seqscan")
+        .execute(&mut *connection)
+        .await
+        .expect("sequential scans are enabled");
+    let plan = plan.join("\n");
+    assert!(!plan.contains("Seq Scan"), "{}", plan);
+    assert!(plan.contains("Index"), "{}", plan);
+}
+
+/// Applies the same stop time update twice through a client, on an origin of its
+/// own, which is committed. The times differ per run, so that each run starts with
+/// a change.
+#[tokio::test]
+async fn reapplying_an_identical_stop_time_update_writes_nothing() {
+    const ORIGIN: &str = "test-trip-update-reapply";
+    let Some(db) = common::connect().await else {
+        return;
+    };
+    let database = InstrumentedDatabase::new(db);
+    let server = Server::new(database.clone());
+    server.origin(ORIGIN, 0).await.expect("origin is stored");
+    let client = server.client(ORIGIN);
+    let trip_id = Id::new(format!("{}-trip", ORIGIN));
+    let today = Local::now().date_naive();
+    // times are stored with microseconds.
+    let now = Local::now().duration_trunc(Duration::seconds(1)).unwrap();
+    let stop_time = |delay: i64| StopTimeUpdate {
+        scheduled_stop_sequence: Some(1),
+        stop_id: Some(Id::new(format!("{}-stop", ORIGIN))),
+        arrival_time: Some(now + Duration::minutes(delay)),
+        departure_time: Some(now + Duration::minutes(delay)),
+        status: StopTimeStatus::Scheduled,
+        platform: Some("2".to_owned()),
+        scheduled_platform: Some("1".to_owned()),
+    };
+    let writes = || {
+        database
+            .metrics()
+            .snapshot()
+            .into_iter()
+            .find(|metrics| metrics.operation == "RealtimeRepo::put_trip_updates")
+            .map_or(0, |metrics| metrics.calls)
+    };
+
+    let cases = [
+        (stop_time(2), true, 1),
+        (stop_time(2), false, 1),
+        (stop_time(3), true, 2),
+        (
+            StopTimeUpdate {
+                platform: Some("3".to_owned()),
+                ..stop_time(3)
+            },
+            true,
+            3,
+        ),
+        (stop_time(3), true, 4),
+        (stop_time(3), false, 4),
+    ];
+    for (index, (stop_time, expected, expected_writes)) in
+        cases.into_iter().enumerate()
+    {
+        let written = client
+            .put_stop_time_update(&trip_id, today, stop_time)
+            .await
+            .expect("update is applied");
+        assert_eq!(written, expected, "written of case {}", index);
+        assert_eq!(writes(), expected_writes, "writes after case {}", index);
+    }
+}
@@ -77,6 +77,7 @@ struct BahnApiClientState {
 
 pub struct BahnApiClient {
     pub credentials: BahnApiCredentials,
+    api_url: String,
     state: RwLock<BahnApiClientState>,
     stats: RwLock<BahnApiClientStats>,
 }
@@ -85,6 +86,7 @@ impl BahnApiClient {
     pub fn new(credentials: &BahnApiCredentials) -> Self {
         Self {
             credentials: credentials.clone(),
+            api_url: BAHN_API_URL.to_owned(),
             state: RwLock::new(BahnApiClientState {
                 avaliable_requests: credentials.rate_limit_per_minute.unwrap_or(0),
                 last_refill: chrono::offset::Local::now(),
@@ -93,6 +95,12 @@ impl BahnApiClient {
         }
     }
 
+    /// Sets the url the endpoints are relative to, `BAHN_API_URL` by default.
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = api_url.to_owned();
+        self
+    }
+
     pub async fn stats_measure(&self) {
         let available_requests = self.avaliable_requests().await;
         self.stats.write().await.measure(available_requests);
@@ -147,7 +155,7 @@ impl BahnApiClient {
         };
 
         /* perform get-request */
-        let url = format!("{BAHN_API_URL}/{endpoint}");
+        let url = format!("{}/{endpoint}", self.api_url);
         let response = client
             .get(&url)
             .header("DB-Client-Id", &self.credentials.client_id)
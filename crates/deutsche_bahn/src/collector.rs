@@ -4,54 +4,175 @@ use async_trait::async_trait;
 use chrono::{DateTime, Local};
 use model::{
     agency::Agency,
-    calendar::CalendarDate,
-    line::Line,
-    stop::{Location, Stop},
-    trip::{StopTime, Trip},
-    trip_update::{StopTimeStatus, StopTimeUpdate},
+    calendar::{CalendarDate, Service},
+    line::{Line, LineType},
+    stop::{Location, Stop, StopAmenity},
+    trip::{CouplingKind, StopTime, Trip},
+    trip_update::{HistoricDelay, StopTimeStatus, StopTimeUpdate},
 };
 use public_transport::{
-    client::Client,
+    capability::{self, Requirement},
+    client::{Client, PushTripOptions},
     collector::{Collector, Continuation},
     database::Database,
     RequestError,
 };
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
+use utility::{
+    log_sampling::{LogSampler, LogVerbosity},
+    normalize::normalize_platform_code,
+};
 
 use crate::{
     client::{BahnApiClient, BahnApiCredentials},
     model::{
         station_data::SteamPermission,
-        timetables::{EventStatus, TimetableStop},
+        timetables::{Event, EventStatus, TimetableStop},
     },
     station_data::get_station_data,
-    timetables::{get_known_changes, get_plan},
+    timetables::{get_known_changes, get_plan, get_stations},
 };
 
-/// The plan will be fetched in advance for this amount of hours (if alread provided).
-/// TODO: maybe move into settings?
-const MAX_PREFETCH_HOURS: i64 = 24 * 2;
+/// The plan will be fetched in advance for this amount of hours (if alread provided),
+/// unless configured otherwise in the collector state.
+const DEFAULT_MAX_PREFETCH_HOURS: i64 = 24 * 2;
+
+fn default_max_prefetch_hours() -> i64 {
+    DEFAULT_MAX_PREFETCH_HOURS
+}
+
+/// Historic delays are kept for this amount of days, unless configured otherwise
+/// in the collector state.
+const DEFAULT_HISTORIC_DELAY_RETENTION_DAYS: i64 = 30;
+
+fn default_historic_delay_retention_days() -> i64 {
+    DEFAULT_HISTORIC_DELAY_RETENTION_DAYS
+}
 
 fn is_ignored_trip_category(category: &str) -> bool {
     matches!(category, "erx" | "NBE" | "ME" | "AKN" | "Bus")
 }
 
+/// Couplings with the wings of the stop. Wings only referenced on arrival are
+/// split from the trip at the stop, wings only referenced on departure are joined.
+/// A missing event does not separate the trips, e.g. at the first stop.
+fn couplings_of_stop(stop: &TimetableStop) -> Vec<(String, CouplingKind)> {
+    let arrival = stop.arrival.as_ref().map(|arrival| arrival.wing_trip_ids());
+    let departure = stop
+        .departure
+        .as_ref()
+        .map(|departure| departure.wing_trip_ids());
+    let arrival_wings = arrival.clone().or(departure.clone()).unwrap_or_default();
+    let departure_wings = departure.or(arrival).unwrap_or_default();
+    let mut couplings: Vec<(String, CouplingKind)> = vec![];
+    for wing in arrival_wings.iter().chain(departure_wings.iter()) {
+        if couplings.iter().any(|(known, _)| known == wing) {
+            continue;
+        }
+        let kind = CouplingKind::new(
+            arrival_wings.contains(wing),
+            departure_wings.contains(wing),
+        );
+        if let Some(kind) = kind {
+            couplings.push((wing.clone(), kind));
+        }
+    }
+    couplings
+}
+
+/// Platform the trip is expected at and the planned one. Both are taken from the
+/// departure, if it has a platform, as passengers board there. Without a change,
+/// the trip is expected at the planned platform.
+fn platforms_of_stop(stop: &TimetableStop) -> (Option<String>, Option<String>) {
+    let has_platform = |event: &&Event| {
+        event.planned_platform.is_some() || event.changed_platform.is_some()
+    };
+    let Some(event) = stop
+        .departure
+        .iter()
+        .chain(stop.arrival.iter())
+        .find(has_platform)
+    else {
+        return (None, None);
+    };
+    let planned = event
+        .planned_platform
+        .as_deref()
+        .and_then(normalize_platform_code);
+    let changed = event
+        .changed_platform
+        .as_deref()
+        .and_then(normalize_platform_code);
+    (changed.or_else(|| planned.clone()), planned)
+}
+
+/// Line type of a trip category. Train categories are split into long-distance
+/// (ICE, IC, EC, ...), regional (RE, RB, ...) and suburban (S) rail. Evu-specific
+/// categories fall back to the category the line is named after. Unknown
+/// categories are rail.
+fn line_type_of_trip_category(category: &str, line_name: &str) -> LineType {
+    match category.to_uppercase().as_str() {
+        "BUS" | "SEV" => LineType::Bus,
+        "STR" | "TRAM" | "STB" => LineType::TramStreetcarOrLighrail,
+        "U" => LineType::SubwayOrMetro,
+        "F" | "FÄHRE" | "SCHIFF" => LineType::Ferry,
+        // Wuppertaler Schwebebahn
+        "SWB" => LineType::Monorail,
+        _ => LineType::of_rail_category(category)
+            .or_else(|| {
+                is_ignored_trip_category(category)
+                    .then(|| LineType::of_rail_line_name(line_name))
+                    .flatten()
+            })
+            .unwrap_or(LineType::Rail),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StationState {
     pub eva: i64,
     pub last_plan_fetched: Option<DateTime<Local>>,
+
+    /// Overrides `CollectorState::max_prefetch_hours` for this station.
+    #[serde(default)]
+    pub max_prefetch_hours: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CollectorState {
     pub credentials: BahnApiCredentials,
     pub stations: Vec<StationState>,
+
+    /// The plan will be fetched in advance for this amount of hours.
+    #[serde(default = "default_max_prefetch_hours")]
+    pub max_prefetch_hours: i64,
+
+    /// Maximum number of plan requests per run, shared by all stations.
+    /// Stations, that were skipped because of this limit, are served first in
+    /// the next run.
+    #[serde(default)]
+    pub max_plan_requests_per_run: Option<usize>,
+
+    /// Whether to keep the delays announced over time (`hd`) of every stop.
+    /// Otherwise, only the latest prognosis is kept.
+    #[serde(default)]
+    pub keep_historic_delays: bool,
+
+    /// Historic delays of trips, which started more days ago, are deleted.
+    #[serde(default = "default_historic_delay_retention_days")]
+    pub historic_delay_retention_days: i64,
+
+    /// How much is logged about inserted trips and skipped updates.
+    #[serde(default)]
+    pub log_verbosity: LogVerbosity,
 }
 
 pub struct DeutscheBahnCollector {
     client: Arc<BahnApiClient>,
     initialized: bool,
+    inserted_log: LogSampler,
+    skipped_log: LogSampler,
 }
 
 #[async_trait]
@@ -63,11 +184,17 @@ impl Collector for DeutscheBahnCollector {
         "DB Timetables"
     }
 
-    fn from_state(state: Self::State) -> Self {
-        Self {
+    fn requirements() -> &'static [Requirement] {
+        &[capability::STOP_MERGING]
+    }
+
+    fn from_state(state: Self::State) -> Result<Self, Self::Error> {
+        Ok(Self {
             client: Arc::new(BahnApiClient::new(&state.credentials)),
             initialized: false,
-        }
+            inserted_log: LogSampler::new(state.log_verbosity),
+            skipped_log: LogSampler::new(state.log_verbosity),
+        })
     }
 
     async fn run<D: Database>(
@@ -82,9 +209,24 @@ impl Collector for DeutscheBahnCollector {
         }
         // insert planned trips
         state = self.insert_trips(client, state).await.unwrap();
+        if state.keep_historic_delays {
+            let retention =
+                chrono::Duration::days(state.historic_delay_retention_days);
+            match client.prune_historic_delays(retention).await {
+                Ok(0) => {}
+                Ok(deleted) => log::info!("Deleted {} historic delay(s).", deleted),
+                Err(why) => log::error!("{:?}", why),
+            }
+        }
         Ok((Continuation::Continue, state))
     }
 
+    /// Probes the credentials with a single station lookup.
+    async fn healthcheck(&mut self, _state: &Self::State) -> Result<(), Self::Error> {
+        get_stations(self.client.clone(), "Kiel Hbf").await?;
+        Ok(())
+    }
+
     fn tick(&self) -> Option<Duration> {
         Some(Duration::from_secs(60))
     }
@@ -113,23 +255,16 @@ impl DeutscheBahnCollector {
                 Some(number) => number,
                 None => continue,
             };
-            // steam permission emoji
-            let dampf = if station.ril100_identifiers.iter().any(|ril100| {
-                matches!(ril100.steam_permission, SteamPermission::Unrestricted)
-            }) {
-                "☁️"
-            } else {
-                ""
-            };
+            let has_steam_permission =
+                station.ril100_identifiers.iter().any(|ril100| {
+                    matches!(ril100.steam_permission, SteamPermission::Unrestricted)
+                });
             // build stop
             let stop = Stop {
                 name: Some(station.name),
-                description: station.product_line.map(|line| {
-                    format!(
-                        "{}{} ({}){}",
-                        dampf, line.product_line, line.segment, dampf
-                    )
-                }),
+                description: station
+                    .product_line
+                    .map(|line| format!("{} ({})", line.product_line, line.segment)),
                 location: eva.geographic_coordinates.as_ref().map(|point| Location {
                     longitude: point.coordinates[0],
                     latitude: point.coordinates[1],
@@ -137,6 +272,12 @@ impl DeutscheBahnCollector {
                 }),
                 parent_id: None,
                 platform_code: None,
+                amenities: if has_steam_permission {
+                    vec![StopAmenity::SteamPermission]
+                } else {
+                    vec![]
+                },
+                updated_at: None,
             };
             // insert stop
             client
@@ -150,6 +291,7 @@ impl DeutscheBahnCollector {
                     StationState {
                         eva: eva.number,
                         last_plan_fetched: None,
+                        max_prefetch_hours: None,
                     },
                 );
             }
@@ -164,7 +306,9 @@ impl DeutscheBahnCollector {
         mut state: CollectorState,
     ) -> Result<CollectorState, RequestError> {
         let mut front = vec![];
+        let mut skipped = vec![];
         let mut back = vec![];
+        let mut plan_requests = 0;
         for mut station in state.stations {
             let now = Local::now();
             let next = station
@@ -180,7 +324,16 @@ impl DeutscheBahnCollector {
                 .unwrap_or(now);
             let mut error = false;
             // fetch plan and insert
-            if (next - now).num_hours() <= MAX_PREFETCH_HOURS {
+            let max_prefetch_hours = station
+                .max_prefetch_hours
+                .unwrap_or(state.max_prefetch_hours);
+            let is_prefetched = (next - now).num_hours() > max_prefetch_hours;
+            let limit_reached = !is_prefetched
+                && state
+                    .max_plan_requests_per_run
+                    .is_some_and(|max| plan_requests >= max);
+            if !is_prefetched && !limit_reached {
+                plan_requests += 1;
                 match get_plan(&self.client, station.eva, next).await {
                     Ok(timetable) => {
                         for mut stop in timetable.stops {
@@ -204,8 +357,16 @@ impl DeutscheBahnCollector {
             // fetch updates
             match get_known_changes(&self.client, station.eva).await {
                 Ok(timetable) => {
-                    for stop in timetable.stops {
-                        self.insert_stop_changes(client, stop).await?;
+                    for mut stop in timetable.stops {
+                        if stop.eva.is_none() {
+                            stop.eva = Some(timetable.eva.unwrap_or(station.eva));
+                        }
+                        self.insert_stop_changes(
+                            client,
+                            stop,
+                            state.keep_historic_delays,
+                        )
+                        .await?;
                     }
                 }
                 Err(why) => {
@@ -218,11 +379,19 @@ impl DeutscheBahnCollector {
 
             if error {
                 front.push(station);
+            } else if limit_reached {
+                skipped.push(station);
             } else {
                 back.push(station);
             }
         }
-        state.stations = [front, back].concat();
+        if !skipped.is_empty() {
+            log::info!(
+                "Plan request limit reached, skipped {} station(s).",
+                skipped.len()
+            );
+        }
+        state.stations = [front, skipped, back].concat();
         Ok(state)
     }
 
@@ -231,6 +400,7 @@ impl DeutscheBahnCollector {
         client: &Client<D>,
         stop: TimetableStop,
     ) -> Result<(), RequestError> {
+        let couplings = couplings_of_stop(&stop);
         let Some(trip_label) = stop.trip_label else {
             return Ok(());
         };
@@ -238,7 +408,9 @@ impl DeutscheBahnCollector {
             return Ok(());
         };
 
-        log::info!("inserting {}...", stop.id.full_id_string());
+        if self.inserted_log.is_verbose() {
+            log::info!("inserting {}...", stop.id.full_id_string());
+        }
 
         let agency = client
             .push_agency(
@@ -287,10 +459,7 @@ impl DeutscheBahnCollector {
             format!("{}{}", trip_label.category, line_name)
         };
 
-        let kind = match trip_label.category.as_str() {
-            "Bus" => model::line::LineType::Bus,
-            _ => model::line::LineType::Rail,
-        };
+        let kind = line_type_of_trip_category(&trip_label.category, &line_name);
 
         let line = client
             .push_line(
@@ -298,6 +467,8 @@ impl DeutscheBahnCollector {
                     name: Some(line_name.clone()),
                     kind,
                     agency_id: Some(agency.content.id),
+                    secondary_agency_ids: vec![],
+                    updated_at: None,
                 },
                 Some(format!("{}-{}", trip_label.owner, line_name)),
             )
@@ -308,12 +479,15 @@ impl DeutscheBahnCollector {
             .date()
             .map_err(|why| RequestError::Other(Box::new(why)))?;
 
-        let service = client
-            .push_calendar_date(
-                None,
-                CalendarDate {
-                    date,
-                    exception_type: model::calendar::ServiceExceptionType::Added,
+        // trips running on the same day share a single service.
+        let service_id = client
+            .push_service(
+                Service {
+                    windows: vec![],
+                    dates: vec![CalendarDate {
+                        date,
+                        exception_type: model::calendar::ServiceExceptionType::Added,
+                    }],
                 },
                 Some(stop.id.trip_id_string()),
             )
@@ -323,13 +497,17 @@ impl DeutscheBahnCollector {
             .push_trip(
                 Trip {
                     line_id: line.content.id,
-                    service_id: Some(service.0),
+                    service_id: Some(service_id),
                     headsign: None,
                     short_name: None,
+                    direction: None,
+                    shape_id: None,
                     stops: vec![],
+                    frequencies: vec![],
+                    updated_at: None,
                 },
                 Some(stop.id.trip_id_string()),
-                false,
+                PushTripOptions::default(),
             )
             .await?;
 
@@ -337,9 +515,7 @@ impl DeutscheBahnCollector {
             .get_stop_id_by_original_id(format!("{}", eva))
             .await?;
 
-        let Some(Some(date)) = service
-            .1
-            .date
+        let Some(Some(date)) = date
             .and_hms_opt(0, 0, 0)
             .map(|x| x.and_local_timezone(Local).earliest())
         else {
@@ -347,10 +523,10 @@ impl DeutscheBahnCollector {
         };
         client
             .push_stop_time(
-                trip.content.id,
+                trip.content.id.clone(),
                 StopTime {
                     stop_sequence: stop.id.index_of_stop_in_trip,
-                    stop_id,
+                    stop_id: stop_id.clone(),
                     arrival_time: stop
                         .arrival
                         .as_ref()
@@ -362,22 +538,41 @@ impl DeutscheBahnCollector {
                         .and_then(|departure| departure.planned_time)
                         .map(|pt| pt - date),
                     stop_headsign: None,
+                    // the timetables do not tell.
+                    pickup_type: None,
+                    drop_off_type: None,
+                    area_reference: None,
+                    stop_name: None,
                 },
             )
             .await?;
 
-        log::info!(
-            "inserted trip {} ({}): {}",
-            stop.id.trip_id_string(),
-            date,
-            [
-                stop.arrival.map(|a| a.planned_path).unwrap_or(vec![]),
-                vec!["...".to_string()],
-                stop.departure.map(|d| d.planned_path).unwrap_or(vec![]),
-            ]
-            .concat()
-            .join(" | ")
-        );
+        client
+            .put_trip_couplings(
+                &trip.content.id,
+                stop.id.index_of_stop_in_trip,
+                stop_id.as_ref(),
+                couplings,
+            )
+            .await?;
+
+        if self.inserted_log.is_verbose() {
+            log::info!(
+                "inserted trip {} ({}): {}",
+                stop.id.trip_id_string(),
+                date,
+                [
+                    stop.arrival.map(|a| a.planned_path).unwrap_or(vec![]),
+                    vec!["...".to_string()],
+                    stop.departure.map(|d| d.planned_path).unwrap_or(vec![]),
+                ]
+                .concat()
+                .join(" | ")
+            );
+        }
+        if let Some(count) = self.inserted_log.count(1) {
+            log::info!("inserted {} trip(s) in the last minute.", count);
+        }
 
         Ok(())
     }
@@ -386,6 +581,7 @@ impl DeutscheBahnCollector {
         &self,
         client: &Client<D>,
         stop: TimetableStop,
+        keep_historic_delays: bool,
     ) -> Result<(), RequestError> {
         // TODO: when id does not exist, create new trip and insert anyway.
         // This would enable to also display added, unscheduled trips.
@@ -393,11 +589,19 @@ impl DeutscheBahnCollector {
             .get_trip_id_by_original_id(stop.id.trip_id_string())
             .await?
         else {
-            log::info!(
-                "skipped update {}: {}",
-                stop.id.trip_id_string(),
-                serde_json::to_string_pretty(&stop).unwrap_or("hä".to_owned())
-            );
+            if self.skipped_log.is_verbose() {
+                log::info!(
+                    "skipped update {}: {}",
+                    stop.id.trip_id_string(),
+                    serde_json::to_string_pretty(&stop).unwrap_or("hä".to_owned())
+                );
+            }
+            if let Some(count) = self.skipped_log.count(1) {
+                log::info!(
+                    "skipped {} update(s) of unknown trips in the last minute.",
+                    count
+                );
+            }
             return Ok(());
         };
 
@@ -406,12 +610,24 @@ impl DeutscheBahnCollector {
             .date()
             .map_err(|why| RequestError::Other(Box::new(why)))?;
 
+        let stop_id = match stop.eva {
+            Some(eva) => {
+                client
+                    .get_stop_id_by_original_id(format!("{}", eva))
+                    .await?
+            }
+            None => None,
+        };
+
+        let (platform, scheduled_platform) = platforms_of_stop(&stop);
+
         client
             .put_stop_time_update(
                 &id,
                 date,
                 StopTimeUpdate {
                     scheduled_stop_sequence: Some(stop.id.index_of_stop_in_trip),
+                    stop_id: stop_id.clone(),
                     arrival_time: stop.arrival.as_ref().and_then(|a| a.changed_time),
                     departure_time: stop
                         .departure
@@ -431,10 +647,270 @@ impl DeutscheBahnCollector {
                             EventStatus::Cancelled => StopTimeStatus::Cancelled,
                         })
                         .unwrap_or(StopTimeStatus::Unknown),
+                    platform,
+                    scheduled_platform,
                 },
             )
             .await?;
 
+        // changes without wings keep the couplings of the plan.
+        let wings_changed = stop
+            .arrival
+            .iter()
+            .chain(stop.departure.iter())
+            .any(|event| event.wings.is_some());
+        if wings_changed {
+            client
+                .put_trip_couplings(
+                    &id,
+                    stop.id.index_of_stop_in_trip,
+                    stop_id.as_ref(),
+                    couplings_of_stop(&stop),
+                )
+                .await?;
+        }
+
+        if keep_historic_delays {
+            let delays = stop
+                .historic_delays
+                .iter()
+                // delays without the time of announcement are no history.
+                .filter_map(|delay| {
+                    Some(HistoricDelay {
+                        scheduled_stop_sequence: stop.id.index_of_stop_in_trip,
+                        stop_id: stop_id.clone(),
+                        timestamp: delay.timestamp?,
+                        arrival_time: delay.arrival,
+                        departure_time: delay.departure,
+                        cause: delay.cause_of_delay.clone(),
+                        source: delay
+                            .delay_source
+                            .as_ref()
+                            .map(|source| source.to_string()),
+                    })
+                })
+                .collect::<Vec<_>>();
+            client.put_historic_delays(&id, date, delays).await?;
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::model::timetables::Timetable;
+
+    use super::*;
+
+    /// Plans of an RE72 and an RE74, which run coupled from Kiel Hbf and are split
+    /// in Rendsburg, and of the opposite pair, which is joined there. Ids and
+    /// train numbers are anonymized.
+    const WINGS_PLAN: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<timetable station="Rendsburg">
+  <s id="-1000000000000000072-2410071012-4">
+    <tl f="N" t="p" o="X1" c="RE" n="21072"/>
+    <ar pt="2410071048" pp="2" l="72" ppth="Kiel Hbf|Kiel-Hassee CAU|Felde|Schülldorf" wings="-1000000000000000074-2410071012-4"/>
+    <dp pt="2410071051" pp="2" l="72" ppth="Schleswig|Flensburg"/>
+  </s>
+  <s id="-1000000000000000074-2410071012-4">
+    <tl f="N" t="p" o="X1" c="RE" n="21074"/>
+    <ar pt="2410071048" pp="2" l="74" ppth="Kiel Hbf|Kiel-Hassee CAU|Felde|Schülldorf" wings="-1000000000000000072-2410071012-4"/>
+    <dp pt="2410071053" pp="3" l="74" ppth="Owschlag|Husum"/>
+  </s>
+  <s id="-2000000000000000072-2410071115-3">
+    <tl f="N" t="p" o="X1" c="RE" n="21073"/>
+    <ar pt="2410071203" pp="1" l="72" ppth="Flensburg|Schleswig"/>
+    <dp pt="2410071208" pp="1" l="72" ppth="Schülldorf|Felde|Kiel-Hassee CAU|Kiel Hbf" wings="-2000000000000000074-2410071115-3"/>
+  </s>
+  <s id="-2000000000000000074-2410071115-3">
+    <tl f="N" t="p" o="X1" c="RE" n="21075"/>
+    <ar pt="2410071205" pp="1" l="74" ppth="Husum|Owschlag"/>
+    <dp pt="2410071208" pp="1" l="74" ppth="Schülldorf|Felde|Kiel-Hassee CAU|Kiel Hbf" wings="-2000000000000000072-2410071115-3"/>
+  </s>
+</timetable>"#;
+
+    /// The departure of the split pair in Kiel Hbf, where the train starts coupled,
+    /// referencing two wings, one of them by the trip only.
+    const WINGS_START_PLAN: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<timetable station="Kiel Hbf">
+  <s id="-1000000000000000072-2410071012-1">
+    <tl f="N" t="p" o="X1" c="RE" n="21072"/>
+    <dp pt="2410071012" pp="5" l="72" ppth="Kiel-Hassee CAU|Felde|Schülldorf|Rendsburg" wings="-1000000000000000074-2410071012-1| -1000000000000000076-2410071012 |"/>
+  </s>
+</timetable>"#;
+
+    fn couplings(plan: &str) -> Vec<(String, Vec<(String, CouplingKind)>)> {
+        let timetable: Timetable =
+            serde_xml_rs::from_str(plan).expect("plan is deserialized");
+        timetable
+            .stops
+            .iter()
+            .map(|stop| (stop.id.trip_id_string(), couplings_of_stop(stop)))
+            .collect()
+    }
+
+    fn coupling(trip_id: &str, kind: CouplingKind) -> Vec<(String, CouplingKind)> {
+        vec![(trip_id.to_owned(), kind)]
+    }
+
+    #[test]
+    fn wings_are_split_and_joined() {
+        assert_eq!(
+            couplings(WINGS_PLAN),
+            vec![
+                (
+                    "-1000000000000000072-2410071012".to_owned(),
+                    coupling("-1000000000000000074-2410071012", CouplingKind::Split),
+                ),
+                (
+                    "-1000000000000000074-2410071012".to_owned(),
+                    coupling("-1000000000000000072-2410071012", CouplingKind::Split),
+                ),
+                (
+                    "-2000000000000000072-2410071115".to_owned(),
+                    coupling("-2000000000000000074-2410071115", CouplingKind::Joined),
+                ),
+                (
+                    "-2000000000000000074-2410071115".to_owned(),
+                    coupling("-2000000000000000072-2410071115", CouplingKind::Joined),
+                ),
+            ]
+        );
+        assert_eq!(
+            couplings(WINGS_START_PLAN),
+            vec![(
+                "-1000000000000000072-2410071012".to_owned(),
+                vec![
+                    (
+                        "-1000000000000000074-2410071012".to_owned(),
+                        CouplingKind::Coupled
+                    ),
+                    (
+                        "-1000000000000000076-2410071012".to_owned(),
+                        CouplingKind::Coupled
+                    ),
+                ],
+            )]
+        );
+    }
+
+    /// Stops of an RE72 in Rendsburg on four days, after the changes were merged
+    /// into the plan. It is moved from platform 3 to 5 on the first and last day,
+    /// where it ends. On the third day, only the arrival is moved, passengers still
+    /// board at platform 3. Ids are anonymized.
+    const PLATFORMS_PLAN: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<timetable station="Rendsburg">
+  <s id="-1000000000000000072-2410071012-4">
+    <tl f="N" t="p" o="X1" c="RE" n="21072"/>
+    <ar pt="2410071048" pp="3" cp="5" l="72"/>
+    <dp pt="2410071051" pp="3" cp="5" l="72"/>
+  </s>
+  <s id="-1000000000000000072-2410081012-4">
+    <tl f="N" t="p" o="X1" c="RE" n="21072"/>
+    <ar pt="2410081048" pp="3" l="72"/>
+    <dp pt="2410081051" pp="3" l="72"/>
+  </s>
+  <s id="-1000000000000000072-2410091012-4">
+    <tl f="N" t="p" o="X1" c="RE" n="21072"/>
+    <ar pt="2410091048" pp="2" cp="1" l="72"/>
+    <dp pt="2410091051" pp="3" l="72"/>
+  </s>
+  <s id="-1000000000000000072-2410101012-11">
+    <tl f="N" t="p" o="X1" c="RE" n="21072"/>
+    <ar pt="2410101148" pp="Gleis 3" cp="Gleis 5" l="72"/>
+  </s>
+</timetable>"#;
+
+    #[test]
+    fn platforms_are_changed_and_planned() {
+        let timetable: Timetable =
+            serde_xml_rs::from_str(PLATFORMS_PLAN).expect("plan is deserialized");
+        let platforms = timetable
+            .stops
+            .iter()
+            .map(platforms_of_stop)
+            .collect::<Vec<_>>();
+        let platform = |code: &str| Some(code.to_owned());
+        assert_eq!(
+            platforms,
+            vec![
+                (platform("5"), platform("3")),
+                (platform("3"), platform("3")),
+                (platform("3"), platform("3")),
+                (platform("5"), platform("3")),
+            ]
+        );
+    }
+
+    #[test]
+    fn trip_categories_are_mapped_to_line_types() {
+        let cases = [
+            ("Bus", "Bus 500", LineType::Bus),
+            ("SEV", "SEV", LineType::Bus),
+            ("STR", "STR 4", LineType::TramStreetcarOrLighrail),
+            ("str", "STR 4", LineType::TramStreetcarOrLighrail),
+            ("STB", "U79", LineType::TramStreetcarOrLighrail),
+            ("U", "U3", LineType::SubwayOrMetro),
+            ("F", "F1", LineType::Ferry),
+            ("Fähre", "Fähre", LineType::Ferry),
+            ("SWB", "SWB", LineType::Monorail),
+            ("S", "S 1", LineType::SuburbanRail),
+            ("ICE", "ICE 123", LineType::LongDistanceRail),
+            ("RE", "RE83", LineType::RegionalRail),
+            // evu-specific categories are named after the train category.
+            ("erx", "RB83", LineType::RegionalRail),
+            ("NBE", "RB64", LineType::RegionalRail),
+            ("erx", "erx", LineType::Rail),
+            // unknown categories are rail, regardless of the line name.
+            ("ABC", "RE1", LineType::Rail),
+        ];
+        for (index, (category, line_name, expected)) in cases.into_iter().enumerate()
+        {
+            assert_eq!(
+                line_type_of_trip_category(category, line_name),
+                expected,
+                "line type of case {}",
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn corrupted_states_fail_to_start() {
+        let credentials = r#"{"clientId": "id", "clientSecret": "secret"}"#;
+        let cases = [
+            (
+                format!(r#"{{"credentials": {}, "stations": []}}"#, credentials),
+                true,
+            ),
+            (
+                format!(
+                    r#"{{"credentials": {}, "stations": [{{"eva": 8000199}}]}}"#,
+                    credentials
+                ),
+                true,
+            ),
+            (
+                format!(
+                    r#"{{"credentials": {}, "stations": [{{"eva": "8000199"}}]}}"#,
+                    credentials
+                ),
+                false,
+            ),
+            (
+                r#"{"credentials": {"clientId": "id"}, "stations": []}"#.to_owned(),
+                false,
+            ),
+            (r#"{"stations": []}"#.to_owned(), false),
+            (format!(r#"{{"credentials": {}}}"#, credentials), false),
+        ];
+        for (i, (state, expected)) in cases.into_iter().enumerate() {
+            let started = serde_json::from_str(&state)
+                .map_err(Box::from)
+                .and_then(DeutscheBahnCollector::from_state)
+                .is_ok();
+            assert_eq!(started, expected, "state of case {}", i);
+        }
+    }
+}
@@ -366,6 +366,22 @@ pub struct Event {
 }
 
 impl Event {
+    /// Trip ids of the wings in the format of `TimetableStopId::trip_id_string`.
+    /// Wings, which reference a stop of the trip, are reduced to the trip.
+    pub fn wing_trip_ids(&self) -> Vec<String> {
+        self.wings
+            .as_deref()
+            .unwrap_or_default()
+            .split('|')
+            .map(str::trim)
+            .filter(|wing| !wing.is_empty())
+            .map(|wing| match TimetableStopId::parse_str(wing) {
+                Ok(id) => id.trip_id_string(),
+                Err(_) => wing.to_owned(),
+            })
+            .collect()
+    }
+
     pub fn calculate_all(&mut self) {
         self.calculate_actual_status();
         self.calculate_actual_path();
@@ -379,105 +395,68 @@ impl Event {
     }
 
     pub fn calculate_actual_path(&mut self) {
-        self.actual_path.clear();
-        /* if path has changed... */
-        if let Some(changed_path) = &self.changed_path {
-            let (mut i, mut j) = (0usize, 0usize);
-            while i < changed_path.len() || j < self.planned_path.len() {
-                /* last stop(s) cancelled */
-                if i >= changed_path.len() {
-                    self.actual_path.push(ActualPathStop {
-                        status: EventStatus::Cancelled,
-                        name: self.planned_path[j].clone(),
-                    });
-                    j += 1;
-                    continue;
-                }
-                /* last stop(s) added */
-                if j >= self.planned_path.len() {
-                    self.actual_path.push(ActualPathStop {
-                        status: EventStatus::Added,
-                        name: changed_path[i].clone(),
-                    });
-                    i += 1;
-                    continue;
-                }
-                /* planned stop */
-                if changed_path[i] == self.planned_path[j] {
-                    self.actual_path.push(ActualPathStop {
-                        status: EventStatus::Planned,
-                        name: changed_path[i].clone(),
-                    });
-                    i += 1;
-                    j += 1;
-                    continue;
-                }
-
-                /* stops added and cancelled */
-                let mut changed = false;
-
-                for pk in j..self.planned_path.len() + 1 {
-                    /* added stop(s) */
-                    for mut ck in i..changed_path.len() {
-                        /* difference, continue search for common path stop */
-                        if pk != self.planned_path.len()
-                            && changed_path[ck] != self.planned_path[pk]
-                        {
-                            continue;
-                        }
-                        /* special case where there are no more common stops in
-                        planned path and changed path */
-                        if pk == self.planned_path.len() {
-                            /* set ck value so all remaining stops are added */
-                            ck = changed_path.len();
-                        }
-                        /* append cancelled stops */
-                        self.actual_path.append(
-                            &mut self.planned_path[j..pk]
-                                .iter()
-                                .map(|elem| ActualPathStop {
-                                    status: EventStatus::Cancelled,
-                                    name: elem.clone(),
-                                })
-                                .collect(),
-                        );
-                        /* append new stops */
-                        self.actual_path.append(
-                            &mut changed_path[i..ck]
-                                .iter()
-                                .map(|elem| ActualPathStop {
-                                    status: EventStatus::Added,
-                                    name: elem.clone(),
-                                })
-                                .collect(),
-                        );
-                        /* set to new common start */
-                        i = ck;
-                        j = pk;
-                        changed = true;
-                        break;
-                    }
-                    /* break outer loop upon change */
-                    if changed {
-                        break;
-                    }
-                }
-            }
-
+        self.actual_path = match &self.changed_path {
+            /* if path has changed... */
+            Some(changed_path) => diff_path(&self.planned_path, changed_path),
             /* ...if path has NOT changed */
+            None => self
+                .planned_path
+                .iter()
+                .map(|elem| ActualPathStop {
+                    status: EventStatus::Planned,
+                    name: elem.clone(),
+                })
+                .collect(),
+        };
+    }
+}
+
+/// Diffs the planned and the changed path of an event, based on their longest
+/// common subsequence. Stops of both paths are marked as planned, stops only
+/// part of the planned path as cancelled and stops only part of the changed path
+/// as added. Between two planned stops, cancelled stops come before added stops.
+fn diff_path(planned: &[String], changed: &[String]) -> Vec<ActualPathStop> {
+    let (n, m) = (planned.len(), changed.len());
+    /* lcs[i * (m + 1) + j]: length of the lcs of `planned[i..]` and `changed[j..]` */
+    let mut lcs = vec![0usize; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i * (m + 1) + j] = if planned[i] == changed[j] {
+                lcs[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lcs[(i + 1) * (m + 1) + j].max(lcs[i * (m + 1) + j + 1])
+            };
+        }
+    }
+
+    let stop = |status: EventStatus, name: &String| ActualPathStop {
+        status,
+        name: name.clone(),
+    };
+    let mut path = Vec::with_capacity(n.max(m));
+    let (mut cancelled, mut added) = (Vec::new(), Vec::new());
+    let (mut i, mut j) = (0usize, 0usize);
+    while i < n || j < m {
+        if i < n && j < m && planned[i] == changed[j] {
+            /* common stop, flush the stops that differ before it */
+            path.append(&mut cancelled);
+            path.append(&mut added);
+            path.push(stop(EventStatus::Planned, &changed[j]));
+            i += 1;
+            j += 1;
+        } else if j == m
+            || (i < n && lcs[(i + 1) * (m + 1) + j] >= lcs[i * (m + 1) + j + 1])
+        {
+            cancelled.push(stop(EventStatus::Cancelled, &planned[i]));
+            i += 1;
         } else {
-            self.actual_path.append(
-                &mut self
-                    .planned_path
-                    .iter()
-                    .map(|elem| ActualPathStop {
-                        status: EventStatus::Planned,
-                        name: elem.clone(),
-                    })
-                    .collect(),
-            )
+            added.push(stop(EventStatus::Added, &changed[j]));
+            j += 1;
         }
     }
+    path.append(&mut cancelled);
+    path.append(&mut added);
+    path
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -555,6 +534,20 @@ pub enum DelaySource {
     AutomaticPrognosis, /* "A" */
 }
 
+impl fmt::Display for DelaySource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Leibit => write!(f, "LeiBit/LeiDis"),
+            Self::RisneAutIrisNe => write!(f, "IRIS-NE automatic"),
+            Self::RisneManIrisNe => write!(f, "IRIS-NE manual"),
+            Self::Vdv => write!(f, "VDV"),
+            Self::IstpAut => write!(f, "ISTP automatic"),
+            Self::IstpMan => write!(f, "ISTP manual"),
+            Self::AutomaticPrognosis => write!(f, "Automatic prognosis"),
+        }
+    }
+}
+
 /// It's the history of all delay-messages for a stop.
 /// This element extends HistoricChange
 #[serde_with::skip_serializing_none]
@@ -982,3 +975,178 @@ pub struct Timetable {
     #[serde(with = "timestamp_opt")]
     pub live_data_last_updated_at: Option<DateTime<Local>>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Instant;
+
+    use EventStatus::{Added, Cancelled, Planned};
+
+    fn path(stops: &[&str]) -> Vec<String> {
+        stops.iter().map(|stop| (*stop).to_owned()).collect()
+    }
+
+    fn diff(planned: &[&str], changed: &[&str]) -> Vec<(EventStatus, String)> {
+        diff_path(&path(planned), &path(changed))
+            .into_iter()
+            .map(|stop| (stop.status, stop.name))
+            .collect()
+    }
+
+    /// Planned path, changed path and the expected actual path.
+    type Case = (
+        &'static [&'static str],
+        &'static [&'static str],
+        &'static [(EventStatus, &'static str)],
+    );
+
+    #[test]
+    fn paths_are_diffed() {
+        let cases: [Case; 7] = [
+            // identical paths
+            (
+                &["Kiel", "Neumünster", "Hamburg"],
+                &["Kiel", "Neumünster", "Hamburg"],
+                &[
+                    (Planned, "Kiel"),
+                    (Planned, "Neumünster"),
+                    (Planned, "Hamburg"),
+                ],
+            ),
+            // cancelled prefix
+            (
+                &["Kiel", "Neumünster", "Hamburg"],
+                &["Neumünster", "Hamburg"],
+                &[
+                    (Cancelled, "Kiel"),
+                    (Planned, "Neumünster"),
+                    (Planned, "Hamburg"),
+                ],
+            ),
+            // added suffix
+            (
+                &["Kiel", "Neumünster"],
+                &["Kiel", "Neumünster", "Hamburg", "Hannover"],
+                &[
+                    (Planned, "Kiel"),
+                    (Planned, "Neumünster"),
+                    (Added, "Hamburg"),
+                    (Added, "Hannover"),
+                ],
+            ),
+            // diversion in the middle, rejoining the planned path
+            (
+                &["Kiel", "Neumünster", "Elmshorn", "Hamburg"],
+                &["Kiel", "Rendsburg", "Itzehoe", "Elmshorn", "Hamburg"],
+                &[
+                    (Planned, "Kiel"),
+                    (Cancelled, "Neumünster"),
+                    (Added, "Rendsburg"),
+                    (Added, "Itzehoe"),
+                    (Planned, "Elmshorn"),
+                    (Planned, "Hamburg"),
+                ],
+            ),
+            // diversion rejoining late, after several cancelled stops
+            (
+                &["Kiel", "Neumünster", "Elmshorn", "Pinneberg", "Hamburg"],
+                &["Kiel", "Rendsburg", "Hamburg"],
+                &[
+                    (Planned, "Kiel"),
+                    (Cancelled, "Neumünster"),
+                    (Cancelled, "Elmshorn"),
+                    (Cancelled, "Pinneberg"),
+                    (Added, "Rendsburg"),
+                    (Planned, "Hamburg"),
+                ],
+            ),
+            // disjoint paths
+            (
+                &["Kiel", "Neumünster"],
+                &["Eckernförde", "Schleswig"],
+                &[
+                    (Cancelled, "Kiel"),
+                    (Cancelled, "Neumünster"),
+                    (Added, "Eckernförde"),
+                    (Added, "Schleswig"),
+                ],
+            ),
+            // empty planned path
+            (&[], &["Kiel"], &[(Added, "Kiel")]),
+        ];
+        for (index, (planned, changed, expected)) in cases.into_iter().enumerate() {
+            let expected = expected
+                .iter()
+                .map(|(status, name)| (status.clone(), (*name).to_owned()))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                diff(planned, changed),
+                expected,
+                "actual path of case {}",
+                index
+            );
+        }
+    }
+
+    /// Planned path of 60 stops and a changed path, which diverts from the 10th to
+    /// the 30th stop onto 20 other stops.
+    fn synthetic_reroute() -> (Vec<String>, Vec<String>) {
+        let planned = (0..60)
+            .map(|index| format!("Halt {}", index))
+            .collect::<Vec<_>>();
+        let changed = planned[..10]
+            .iter()
+            .cloned()
+            .chain((0..20).map(|index| format!("Umleitung {}", index)))
+            .chain(planned[30..].iter().cloned())
+            .collect::<Vec<_>>();
+        (planned, changed)
+    }
+
+    #[test]
+    fn reroutes_keep_all_stops_in_order() {
+        let (planned, changed) = synthetic_reroute();
+        let path = diff_path(&planned, &changed);
+        let count = |status: EventStatus| {
+            path.iter().filter(|stop| stop.status == status).count()
+        };
+        assert_eq!(
+            (count(Planned), count(Cancelled), count(Added)),
+            (40, 20, 20)
+        );
+        let kept = |status: EventStatus| {
+            path.iter()
+                .filter(|stop| stop.status != status)
+                .map(|stop| stop.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kept(Added), planned, "planned path without added stops");
+        assert_eq!(
+            kept(Cancelled),
+            changed,
+            "changed path without cancelled stops"
+        );
+    }
+
+    /// Diffs the 60 stop reroute repeatedly. Run with
+    /// `cargo test -p deutsche_bahn measure_diff -- --ignored --nocapture`.
+    #[test]
+    #[ignore = "measurement"]
+    fn measure_diff_of_a_reroute() {
+        const RUNS: u32 = 10_000;
+        let (planned, changed) = synthetic_reroute();
+        let start = Instant::now();
+        for _ in 0..RUNS {
+            std::hint::black_box(diff_path(
+                std::hint::black_box(&planned),
+                std::hint::black_box(&changed),
+            ));
+        }
+        println!(
+            "diff of a 60 stop reroute: {:?} per run",
+            start.elapsed() / RUNS
+        );
+    }
+}
@@ -252,14 +252,50 @@ pub async fn get_plan(
 
 /* -- NEWS -- */
 
-/// For how many hours to fetch plan-data.
-const TIMETABLE_NEWS_PREFETCH: i64 = 2;
-
 /// Minimum update interval in Minutes.
 const TIMETABLE_UPDATE_INTERVAL: i64 = 2;
 
-/// After how many minutes to remove an outdated stop.
-const REMOVE_STOP_AFTER: i64 = 120;
+/// After how many minutes to remove an outdated stop, unless configured otherwise.
+const DEFAULT_REMOVE_STOP_AFTER: i64 = 120;
+
+/// For how many minutes a passed stop is still considered current, unless
+/// configured otherwise.
+const DEFAULT_CURRENT_TOLERANCE: i64 = 2;
+
+/// Why a stop was removed from a timetable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RemovalReason {
+    /// The stop passed without any live data.
+    Outdated,
+    /// The stop was cancelled.
+    Cancelled,
+    /// The stop passed and live data was known for it.
+    Departed,
+}
+
+impl RemovalReason {
+    fn of(stop: &TimetableStop) -> Self {
+        let has_live_data = [&stop.arrival, &stop.departure]
+            .into_iter()
+            .flatten()
+            .any(|event| event.changed_time.is_some());
+        if stop.status() == EventStatus::Cancelled {
+            Self::Cancelled
+        } else if has_live_data {
+            Self::Departed
+        } else {
+            Self::Outdated
+        }
+    }
+}
+
+/// A stop removed from a timetable.
+#[derive(Debug, Clone, Serialize)]
+pub struct RemovedStop {
+    pub stop: TimetableStop,
+    pub reason: RemovalReason,
+}
 
 pub enum UpdateResult<U, E> {
     Ok(U),
@@ -277,11 +313,15 @@ impl<U, E> UpdateResult<U, E> {
     }
 }
 
-/// Checks whether an event is outdated.
+/// Checks whether an event is outdated at `now`.
 /// minutes_tolerance specifies how many minutes an event must to be outdated,
 ///     to also be considered outdated by this function.
-fn is_event_outdated(event: &Event, minutes_tolerance: i64) -> bool {
-    let now = chrono::offset::Local::now() - Duration::minutes(minutes_tolerance);
+fn is_event_outdated(
+    event: &Event,
+    now: DateTime<Local>,
+    minutes_tolerance: i64,
+) -> bool {
+    let now = now - Duration::minutes(minutes_tolerance);
     if let Some(planned_time) = event.planned_time {
         if planned_time >= now {
             return false;
@@ -296,16 +336,20 @@ fn is_event_outdated(event: &Event, minutes_tolerance: i64) -> bool {
 }
 
 /// Same as is_event_outdated but for a stop.
-fn is_stop_outdated(stop: &TimetableStop, minutes_tolerance: i64) -> bool {
+fn is_stop_outdated(
+    stop: &TimetableStop,
+    now: DateTime<Local>,
+    minutes_tolerance: i64,
+) -> bool {
     let is_arrival_outdated = stop
         .arrival
         .as_ref()
-        .map(|arrival| is_event_outdated(arrival, minutes_tolerance))
+        .map(|arrival| is_event_outdated(arrival, now, minutes_tolerance))
         .unwrap_or(true);
     let is_departure_outdated = stop
         .departure
         .as_ref()
-        .map(|departure| is_event_outdated(departure, minutes_tolerance))
+        .map(|departure| is_event_outdated(departure, now, minutes_tolerance))
         .unwrap_or(true);
     is_arrival_outdated && is_departure_outdated
 }
@@ -315,19 +359,25 @@ pub struct TimetableNews {
     eva: i64,
     stops: RwLock<HashMap<String, Arc<RwLock<TimetableStop>>>>,
     fetch_next: RwLock<DateTime<Local>>,
+    prefetch_hours: i64,
+    current_tolerance: i64,
+    remove_stop_after: i64,
     last_outdated_removed: RwLock<DateTime<Local>>,
     last_update: RwLock<Option<DateTime<Local>>>,
     station_name: String,
     station_name_aliases: Vec<String>,
-    removed_stops: RwLock<Vec<TimetableStop>>,
+    removed_stops: RwLock<Vec<RemovedStop>>,
     unapplied_known_changes_cache: RwLock<Vec<TimetableStop>>,
 }
 
 impl TimetableNews {
+    /// Timetable of the station, for which plan data is fetched `prefetch_hours`
+    /// in advance.
     pub async fn new(
         bahn_api_client: Arc<BahnApiClient>,
         station_pattern: &str,
         name_aliases: Vec<String>,
+        prefetch_hours: i64,
         _ignore_known_at_launch: bool,
     ) -> Result<Self, ApiError> {
         let station = get_stations(bahn_api_client.clone(), station_pattern)
@@ -342,6 +392,9 @@ impl TimetableNews {
             eva: station.eva,
             stops: RwLock::new(HashMap::new()),
             fetch_next: RwLock::new(chrono::offset::Local::now()),
+            prefetch_hours,
+            current_tolerance: DEFAULT_CURRENT_TOLERANCE,
+            remove_stop_after: DEFAULT_REMOVE_STOP_AFTER,
             last_outdated_removed: RwLock::new(chrono::offset::Local::now()),
             last_update: RwLock::new(None),
             station_name: station.name.clone(),
@@ -353,6 +406,18 @@ impl TimetableNews {
         Ok(result)
     }
 
+    /// Sets for how many minutes a passed stop is still returned as current.
+    pub fn with_current_tolerance(mut self, minutes: i64) -> Self {
+        self.current_tolerance = minutes;
+        self
+    }
+
+    /// Sets after how many minutes a passed stop is removed from the timetable.
+    pub fn with_remove_stop_after(mut self, minutes: i64) -> Self {
+        self.remove_stop_after = minutes;
+        self
+    }
+
     pub async fn live_data_last_updated_at(&self) -> Option<DateTime<Local>> {
         *self.last_update.read().await
     }
@@ -532,10 +597,9 @@ impl TimetableNews {
         .await;
 
         /* filter outdated stops since remove_outated only removes VERY outdated stops. */
-        current_stops = current_stops
-            .into_iter()
-            .filter(|stop| !is_stop_outdated(stop, 2))
-            .collect();
+        let now = chrono::offset::Local::now();
+        current_stops
+            .retain(|stop| !is_stop_outdated(stop, now, self.current_tolerance));
 
         /* sort stops by departure/arrival times */
         current_stops.sort_by(|a, b| -> std::cmp::Ordering {
@@ -583,7 +647,7 @@ impl TimetableNews {
         {
             let mut fetch_next = self.fetch_next.write().await;
             while *fetch_next
-                < current_time + chrono::Duration::hours(TIMETABLE_NEWS_PREFETCH)
+                < current_time + chrono::Duration::hours(self.prefetch_hours)
             {
                 match get_plan(&self.bahn_api_client, self.eva, *fetch_next).await {
                     Ok(mut o) => {
@@ -621,33 +685,25 @@ impl TimetableNews {
         {
             let stops = self.stops.read().await;
             for (id, stop) in stops.iter() {
-                let mut is_outdated = true;
-                /* check if outdated */
-                if let Some(arrival) = &stop.read().await.arrival {
-                    if !is_event_outdated(&arrival, REMOVE_STOP_AFTER) {
-                        is_outdated = false;
-                    }
-                }
-                if let Some(departure) = &stop.read().await.departure {
-                    if !is_event_outdated(&departure, REMOVE_STOP_AFTER) {
-                        is_outdated = false;
-                    }
-                }
-
                 /* remember to remove */
-                if is_outdated {
+                if is_stop_outdated(&*stop.read().await, now, self.remove_stop_after)
+                {
                     remove.push(id.clone());
                 }
             }
         }
 
-        let mut removed_stops: Vec<TimetableStop> = Vec::new();
+        let mut removed_stops: Vec<RemovedStop> = Vec::new();
         {
             let mut stops = self.stops.write().await;
             for id in remove {
                 let stop_opt = stops.remove(&id);
                 if let Some(stop) = stop_opt {
-                    removed_stops.push(stop.read().await.clone());
+                    let stop = stop.read().await.clone();
+                    removed_stops.push(RemovedStop {
+                        reason: RemovalReason::of(&stop),
+                        stop,
+                    });
                 }
                 //println!("Removed outdated stop: {}", id);
             }
@@ -658,9 +714,10 @@ impl TimetableNews {
         *self.last_outdated_removed.write().await = now;
     }
 
-    /// Gets all removed stops and clears the removed stops cache.
+    /// Gets all removed stops with the reason of their removal and clears the
+    /// removed stops cache.
     /// When stops are removed, they get cached until this function is called.
-    pub async fn get_and_clear_removed_stops(&self) -> Vec<TimetableStop> {
+    pub async fn get_and_clear_removed_stops(&self) -> Vec<RemovedStop> {
         let mut removed_stops = self.removed_stops.write().await;
         let res = removed_stops.clone();
         removed_stops.clear();
@@ -682,3 +739,100 @@ impl TimetableNews {
         self.eva
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    /// Monday, 10th of June 2024 at noon, the time of all cases.
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 6, 10, 12, 0, 0).unwrap()
+    }
+
+    /// Stop of a trip at the given events, as sent by the timetables api.
+    fn stop(arrival: Option<Value>, departure: Option<Value>) -> TimetableStop {
+        let mut stop: TimetableStop = serde_json::from_value(json!({
+            "id": "-7874571842864554321-2406100800-11",
+            "ar": arrival,
+            "dp": departure,
+        }))
+        .expect("stop is deserialized");
+        stop.calculate_all();
+        stop
+    }
+
+    fn planned(time: &str) -> Option<Value> {
+        Some(json!({ "pt": time }))
+    }
+
+    #[test]
+    fn stops_are_removed_with_their_reason() {
+        let cases = [
+            (
+                stop(None, planned("2406100900")),
+                Some(RemovalReason::Outdated),
+            ),
+            (
+                stop(
+                    None,
+                    Some(json!({ "pt": "2406100900", "ct": "2406100905" })),
+                ),
+                Some(RemovalReason::Departed),
+            ),
+            (
+                stop(None, Some(json!({ "pt": "2406100900", "cs": "c" }))),
+                Some(RemovalReason::Cancelled),
+            ),
+            // trips ending at the stop only arrive.
+            (
+                stop(planned("2406100930"), None),
+                Some(RemovalReason::Outdated),
+            ),
+            // passed less than two hours ago.
+            (stop(None, planned("2406101100")), None),
+            (stop(planned("2406100930"), planned("2406101000")), None),
+            // delayed until exactly two hours ago.
+            (
+                stop(
+                    None,
+                    Some(json!({ "pt": "2406100900", "ct": "2406101000" })),
+                ),
+                None,
+            ),
+            // cancelled, but not passed yet.
+            (
+                stop(None, Some(json!({ "pt": "2406101300", "cs": "c" }))),
+                None,
+            ),
+        ];
+        for (index, (stop, expected)) in cases.into_iter().enumerate() {
+            let reason = is_stop_outdated(&stop, now(), DEFAULT_REMOVE_STOP_AFTER)
+                .then(|| RemovalReason::of(&stop));
+            assert_eq!(reason, expected, "removal of case {}", index);
+        }
+    }
+
+    #[test]
+    fn passed_stops_are_current_within_the_tolerance() {
+        let cases = [
+            ("2406101158", DEFAULT_CURRENT_TOLERANCE, false),
+            ("2406101157", DEFAULT_CURRENT_TOLERANCE, true),
+            ("2406101157", 5, false),
+            ("2406101155", 5, false),
+            ("2406101154", 5, true),
+        ];
+        for (index, (departure, tolerance, expected)) in cases.into_iter().enumerate()
+        {
+            let stop = stop(None, planned(departure));
+            assert_eq!(
+                is_stop_outdated(&stop, now(), tolerance),
+                expected,
+                "outdated of case {}",
+                index
+            );
+        }
+    }
+}
@@ -68,6 +68,9 @@ impl InternalTrip {
     }
 }
 
+/// Name, name aliases, pattern, client and prefetch window override of a station.
+type QueuedStation = (String, Vec<String>, String, Arc<BahnApiClient>, Option<i64>);
+
 pub struct Triptable {
     /// key: void station name key
     timetables: RwLock<HashMap<String, Arc<TimetableNews>>>,
@@ -76,18 +79,23 @@ pub struct Triptable {
     trips: RwLock<HashMap<String, Arc<RwLock<InternalTrip>>>>,
 
     /// might be obsolete
-    add_stations_queue: RwLock<Vec<(String, Vec<String>, String, Arc<BahnApiClient>)>>,
+    add_stations_queue: RwLock<Vec<QueuedStation>>,
 
     timetables_update_queue: RwLock<Vec<Arc<TimetableNews>>>,
+
+    /// For how many hours plan data is fetched in advance, unless overridden for
+    /// a station.
+    max_prefetch_hours: i64,
 }
 
 impl Triptable {
-    pub async fn new() -> Result<Self, ApiError> {
+    pub async fn new(max_prefetch_hours: i64) -> Result<Self, ApiError> {
         let result = Self {
             timetables: RwLock::new(HashMap::new()),
             trips: RwLock::new(HashMap::new()),
             add_stations_queue: RwLock::new(Vec::new()),
             timetables_update_queue: RwLock::new(Vec::new()),
+            max_prefetch_hours,
         };
         result.update().await?;
         Ok(result)
@@ -115,13 +123,15 @@ impl Triptable {
         name: &str,
         name_aliases: Vec<String>,
         pattern: &str,
-        bahn_api_client: Arc<BahnApiClient>
+        bahn_api_client: Arc<BahnApiClient>,
+        max_prefetch_hours: Option<i64>,
     ) -> Result<(), ApiError> {
         let timetable = Arc::new(
             TimetableNews::new(
                 bahn_api_client,
                 pattern,
                 name_aliases,
+                max_prefetch_hours.unwrap_or(self.max_prefetch_hours),
                 true,
             ).await?
         );
@@ -137,8 +147,9 @@ impl Triptable {
         name_aliases: Vec<String>,
         pattern: &str, // TODO: take StaDa-Entry instead, also save stada entry in TimetableNews
         bahn_api_client: Arc<BahnApiClient>,
+        max_prefetch_hours: Option<i64>, // overrides the one of the triptable
     ) {
-        if let Err(why) = self.try_add_station(name, name_aliases.clone(), pattern, bahn_api_client.clone()).await {
+        if let Err(why) = self.try_add_station(name, name_aliases.clone(), pattern, bahn_api_client.clone(), max_prefetch_hours).await {
             // TODO: vernünftiges logging system, das ist ja gruselig hier
             match why{
                 ApiError::StationDoesNotExist(s) => {
@@ -148,7 +159,7 @@ impl Triptable {
                     self.add_stations_queue
                         .write()
                         .await
-                        .push((name.to_owned(), name_aliases, pattern.to_owned(), bahn_api_client));
+                        .push((name.to_owned(), name_aliases, pattern.to_owned(), bahn_api_client, max_prefetch_hours));
                     println!("[TripTable]: Could not add Station '{}' -> added to queue: {:?}", pattern, why);
                     if !matches!(why, ApiError::RateLimitReached) {
                         println!("Cout not add station '{}': {}.", pattern, why);
@@ -160,17 +171,17 @@ impl Triptable {
 
     pub async fn add_stations(
         &self,
-        stations: Vec<(String, Vec<String>, String, Arc<BahnApiClient>)>, // TODO: same as add_station regarding pattern -> StaDa-Entry
+        stations: Vec<QueuedStation>, // TODO: same as add_station regarding pattern -> StaDa-Entry
     ) {
         let mut add_to_queue = Vec::new();
-        for (name, name_aliases, pattern, bahn_api_client) in stations {
-            if let Err(why) = self.try_add_station(&name, name_aliases.clone(), &pattern, bahn_api_client.clone()).await {
+        for (name, name_aliases, pattern, bahn_api_client, max_prefetch_hours) in stations {
+            if let Err(why) = self.try_add_station(&name, name_aliases.clone(), &pattern, bahn_api_client.clone(), max_prefetch_hours).await {
                 match why{
                     ApiError::StationDoesNotExist(s) => {
                         println!("Station '{}' does not exist. Not adding.", s);
                     },
                     _ => {
-                        add_to_queue.push((name.to_owned(), name_aliases, pattern.to_owned(), bahn_api_client.clone()));
+                        add_to_queue.push((name.to_owned(), name_aliases, pattern.to_owned(), bahn_api_client.clone(), max_prefetch_hours));
                         if !matches!(why, ApiError::RateLimitReached) {
                             println!("Cout not add station '{}': {}.", pattern, why);
                         }
@@ -181,7 +192,7 @@ impl Triptable {
         self.add_stations_queue.write().await.append(&mut add_to_queue);
     }
 
-    pub async fn update(&self) -> Result<(HashMap<String, (String, Vec<Arc<RwLock<TimetableStop>>>)>, Vec<(String, Vec<TimetableStop>)>), ApiError> {
+    pub async fn update(&self) -> Result<(HashMap<String, (String, Vec<Arc<RwLock<TimetableStop>>>)>, Vec<(String, Vec<RemovedStop>)>), ApiError> {
         /* first add queued-to-add stations, they should not be starved */
         let add_stations_queue = self.add_stations_queue
             .write()
@@ -193,7 +204,7 @@ impl Triptable {
         // TODO: this method must also return all successful updates if one failed.
         println!("- TRIPTABLE UPDATE -");
         let mut stations_updates = HashMap::<String, (String, Vec<Arc<RwLock<TimetableStop>>>)>::new();
-        let mut stations_removed_stops = Vec::<(String, Vec<TimetableStop>)>::new();
+        let mut stations_removed_stops = Vec::<(String, Vec<RemovedStop>)>::new();
 
         /* alle timetables durchgehen */
         let mut queue_prio_next = Vec::<Arc<TimetableNews>>::new();
@@ -391,3 +402,111 @@ impl Triptable {
         Ok((stations_updates, stations_removed_stops))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use crate::client::BahnApiCredentials;
+
+    use super::*;
+
+    /// Serves the stations of the given ds100 codes and evas with empty plans and
+    /// changes. Gives the client of the mock and the plan requests so far by eva.
+    async fn mock_api(
+        stations: &'static [(&'static str, i64)],
+    ) -> (Arc<BahnApiClient>, Arc<Mutex<HashMap<i64, usize>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let plan_requests = Arc::new(Mutex::new(HashMap::new()));
+        let requested = plan_requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buffer = [0; 4096];
+                while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                let request = String::from_utf8_lossy(&request).into_owned();
+                let path = request.split(' ').nth(1).unwrap_or_default();
+                let segments = path.split('/').collect::<Vec<_>>();
+                let body = match segments.as_slice() {
+                    ["", "timetables", "v1", "station", pattern] => {
+                        let station = stations
+                            .iter()
+                            .map(|(ds100, eva)| {
+                                format!(r#"<station name="{ds100}" eva="{eva}" ds100="{ds100}"/>"#)
+                            })
+                            .find(|station| station.contains(pattern))
+                            .unwrap_or_default();
+                        format!("<stations>{station}</stations>")
+                    }
+                    ["", "timetables", "v1", "plan", eva, ..] => {
+                        let eva = eva.parse::<i64>().unwrap();
+                        *requested.lock().unwrap().entry(eva).or_default() += 1;
+                        format!(r#"<timetable eva="{eva}"></timetable>"#)
+                    }
+                    ["", "timetables", "v1", "fchg", eva] => {
+                        format!(r#"<timetable eva="{eva}"></timetable>"#)
+                    }
+                    _ => panic!("unexpected request {}", path),
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        let credentials = BahnApiCredentials {
+            client_id: String::new(),
+            client_secret: String::new(),
+            rate_limit_per_minute: None,
+            proxy: None,
+        };
+        let client = BahnApiClient::new(&credentials).with_api_url(&url);
+        (Arc::new(client), plan_requests)
+    }
+
+    #[tokio::test]
+    async fn plan_requests_are_proportional_to_the_prefetch_window() {
+        // the plan is fetched by the hour, from the current one on.
+        for max_prefetch_hours in [2, 4, 8] {
+            let (client, plan_requests) = mock_api(&[("AK", 8000199)]).await;
+            let triptable = Triptable::new(max_prefetch_hours).await.unwrap();
+            triptable.add_station("Kiel Hbf", vec![], "AK", client, None).await;
+            triptable.update().await.unwrap();
+            let requests = plan_requests.lock().unwrap()[&8000199] as i64;
+            assert!(
+                (max_prefetch_hours..=max_prefetch_hours + 1).contains(&requests),
+                "{} plan requests for {} hours",
+                requests,
+                max_prefetch_hours
+            );
+            // until the window moves on, nothing is fetched again.
+            triptable.update().await.unwrap();
+            assert_eq!(plan_requests.lock().unwrap()[&8000199] as i64, requests);
+        }
+    }
+
+    #[tokio::test]
+    async fn stations_override_the_prefetch_window() {
+        let (client, plan_requests) =
+            mock_api(&[("AK", 8000199), ("AR", 8000314)]).await;
+        let triptable = Triptable::new(8).await.unwrap();
+        triptable.add_station("Kiel Hbf", vec![], "AK", client.clone(), None).await;
+        triptable.add_station("Rendsburg", vec![], "AR", client, Some(2)).await;
+        triptable.update().await.unwrap();
+        let plan_requests = plan_requests.lock().unwrap().clone();
+        assert!((8..=9).contains(&plan_requests[&8000199]), "{:?}", plan_requests);
+        assert!((2..=3).contains(&plan_requests[&8000314]), "{:?}", plan_requests);
+    }
+}
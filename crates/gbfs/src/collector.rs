@@ -1,26 +1,131 @@
-use std::{error::Error, time::Duration};
+use std::{collections::BTreeMap, error::Error, future::Future, time::Duration};
 
 use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use model::origin::Origin;
 use public_transport::{
     client::Client,
     collector::{Collector, Continuation},
     database::Database,
+    RequestResult,
 };
 use serde::{Deserialize, Serialize};
+use utility::{
+    id::Id,
+    log_sampling::{LogSampler, LogVerbosity},
+};
 
-pub struct StationsCollector {
-    url: String,
+/// Feed of an origin, managed by a collector instance of another origin.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Feed {
+    pub origin: Id<Origin>,
+    pub url: String,
 }
 
-impl StationsCollector {
-    pub fn new<S: Into<String>>(url: S) -> Self {
-        Self { url: url.into() }
-    }
+/// Outcome of the last run for the feed of an origin.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedStatus {
+    pub last_run: DateTime<Local>,
+    pub error: Option<String>,
 }
 
+/// State of the gbfs collectors. A single collector instance can manage the feeds
+/// of multiple origins, e.g., of all cities of a provider.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct StationsState {
-    pub url: String,
+pub struct FeedsState {
+    /// Feed of the origin of the collector instance.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Feeds of further origins.
+    #[serde(default)]
+    pub feeds: Vec<Feed>,
+    /// Outcome of the last run by origin.
+    #[serde(default)]
+    pub status: BTreeMap<String, FeedStatus>,
+    /// How much is logged about successful runs of the feeds. Failures are
+    /// always logged.
+    #[serde(default)]
+    pub log_verbosity: LogVerbosity,
+}
+
+impl FeedsState {
+    /// Fails, if there is no feed to run.
+    fn validate(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.url.is_none() && self.feeds.is_empty() {
+            return Err("neither a url nor feeds are configured.".into());
+        }
+        Ok(())
+    }
+
+    /// Checks, whether the feeds of all origins are reachable.
+    async fn probe_feeds(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        for url in self
+            .url
+            .iter()
+            .chain(self.feeds.iter().map(|feed| &feed.url))
+        {
+            crate::http::probe(url).await?;
+        }
+        Ok(())
+    }
+
+    /// Runs `action` for the feed of every origin. A failing feed does not affect
+    /// the feeds of other origins, its error is recorded in the status instead.
+    async fn run_feeds<D, F, Fut>(
+        &mut self,
+        client: &Client<D>,
+        sampler: &LogSampler,
+        action: F,
+    ) where
+        D: Database,
+        F: Fn(Client<D>, String) -> Fut,
+        Fut: Future<Output = RequestResult<()>>,
+    {
+        let own_feed = self.url.clone().map(|url| Feed {
+            origin: client.origin(),
+            url,
+        });
+        for feed in own_feed.into_iter().chain(self.feeds.clone()) {
+            let result = action(client.for_origin(&feed.origin), feed.url).await;
+            match &result {
+                Ok(()) if sampler.is_verbose() => {
+                    log::info!("gbfs feed of origin '{}' updated.", feed.origin)
+                }
+                Ok(()) => {}
+                Err(why) => {
+                    log::error!(
+                        "gbfs feed of origin '{}' failed: {:?}",
+                        feed.origin,
+                        why
+                    )
+                }
+            }
+            if let Some(count) = sampler.count(result.is_ok() as u64) {
+                log::info!("updated {} gbfs feed(s) in the last minute.", count);
+            }
+            self.status.insert(
+                feed.origin.raw(),
+                FeedStatus {
+                    last_run: Local::now(),
+                    error: result.err().map(|why| format!("{:?}", why)),
+                },
+            );
+        }
+    }
+}
+
+pub type StationsState = FeedsState;
+
+#[derive(Default)]
+pub struct StationsCollector {
+    log: LogSampler,
+}
+
+impl StationsCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
 }
 
 #[async_trait]
@@ -32,19 +137,28 @@ impl Collector for StationsCollector {
         "GBFS Stations"
     }
 
-    fn from_state(state: Self::State) -> Self {
-        Self { url: state.url }
+    fn from_state(state: Self::State) -> Result<Self, Self::Error> {
+        state.validate()?;
+        Ok(Self {
+            log: LogSampler::new(state.log_verbosity),
+        })
     }
 
     async fn run<D: Database>(
         &mut self,
         client: &Client<D>,
-        state: Self::State,
+        mut state: Self::State,
     ) -> Result<(Continuation, Self::State), Self::Error> {
-        crate::insert_station_information(client.clone(), &self.url)
-            .await
-            .unwrap();
-        Ok((Continuation::Exit, state))
+        state
+            .run_feeds(client, &self.log, |client, url| async move {
+                crate::insert_station_information(client, &url).await
+            })
+            .await;
+        Ok((Continuation::Continue, state))
+    }
+
+    async fn healthcheck(&mut self, state: &Self::State) -> Result<(), Self::Error> {
+        state.probe_feeds().await
     }
 
     fn tick(&self) -> Option<Duration> {
@@ -52,21 +166,19 @@ impl Collector for StationsCollector {
     }
 }
 
+pub type StatusState = FeedsState;
+
+#[derive(Default)]
 pub struct StatusCollector {
-    url: String,
+    log: LogSampler,
 }
 
 impl StatusCollector {
-    pub fn new<S: Into<String>>(url: S) -> Self {
-        Self { url: url.into() }
+    pub fn new() -> Self {
+        Self::default()
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct StatusState {
-    pub url: String,
-}
-
 #[async_trait]
 impl Collector for StatusCollector {
     type Error = Box<dyn Error + Send + Sync>;
@@ -76,22 +188,85 @@ impl Collector for StatusCollector {
         "GBFS Status"
     }
 
-    fn from_state(state: Self::State) -> Self {
-        Self { url: state.url }
+    fn from_state(state: Self::State) -> Result<Self, Self::Error> {
+        state.validate()?;
+        Ok(Self {
+            log: LogSampler::new(state.log_verbosity),
+        })
     }
 
     async fn run<D: Database>(
         &mut self,
         client: &Client<D>,
-        state: Self::State,
+        mut state: Self::State,
     ) -> Result<(Continuation, Self::State), Self::Error> {
-        crate::update_station_status(client.clone(), &self.url)
-            .await
-            .unwrap();
+        state
+            .run_feeds(client, &self.log, |client, url| async move {
+                let origin = client.origin();
+                let unknown = crate::update_station_status(client, &url).await?;
+                if !unknown.is_empty() {
+                    // the station information feed is likely ahead of ours.
+                    log::warn!(
+                        "{} station status(es) of origin '{}' have no station, e.g. {}.",
+                        unknown.len(),
+                        origin,
+                        unknown[0]
+                    );
+                }
+                Ok(())
+            })
+            .await;
         Ok((Continuation::Continue, state))
     }
 
+    async fn healthcheck(&mut self, state: &Self::State) -> Result<(), Self::Error> {
+        state.probe_feeds().await
+    }
+
     fn tick(&self) -> Option<Duration> {
         Some(Duration::from_secs(60))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether the collector starts from the stored state, which may be corrupted.
+    fn starts<C>(state: &str) -> bool
+    where
+        C: Collector<State = FeedsState, Error = Box<dyn Error + Send + Sync>>,
+    {
+        serde_json::from_str(state)
+            .map_err(Box::from)
+            .and_then(C::from_state)
+            .is_ok()
+    }
+
+    #[test]
+    fn corrupted_states_fail_to_start() {
+        let cases = [
+            (r#"{"url": "gbfs.json"}"#, true),
+            (r#"{"feeds": [{"origin": "a", "url": "gbfs.json"}]}"#, true),
+            ("{}", false),
+            (r#"{"url": null, "feeds": []}"#, false),
+            (r#"{"feeds": [{"origin": "a"}]}"#, false),
+            (r#"{"url": "gbfs.json", "log_verbosity": "loud"}"#, false),
+            (r#""gbfs.json""#, false),
+        ];
+        for (i, (state, expected)) in cases.into_iter().enumerate() {
+            assert_eq!(
+                starts::<StationsCollector>(state),
+                expected,
+                "stations state of case {}",
+                i
+            );
+            assert_eq!(
+                starts::<StatusCollector>(state),
+                expected,
+                "status state of case {}",
+                i
+            );
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::{error::Error, path::Path, sync::OnceLock, time::Duration};
+
+use rand::Rng;
+use reqwest::{Client, StatusCode};
+use serde::de::DeserializeOwned;
+
+/// Timeout of a single request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Number of attempts, before a fetch is given up.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry. Doubled for every further retry.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Shared by all gbfs fetches, so that connections are reused between polls.
+fn client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("failed to build http client")
+    })
+}
+
+/// Path of a feed on disk, given as `file://` url instead of fetching it, e.g.
+/// `file:///data/station_status.json`.
+fn local_path(url: &str) -> Option<&Path> {
+    url.strip_prefix("file://").map(Path::new)
+}
+
+/// Fetches and deserializes a gbfs feed, or reads it from disk if given as
+/// `file://` url. Transient failures (e.g. timeouts, connection errors, server
+/// errors) are retried with exponential backoff and jitter. The error of the last
+/// attempt is returned.
+pub(crate) async fn fetch_json<T: DeserializeOwned>(
+    url: &str,
+) -> Result<T, Box<dyn Error + Send + Sync>> {
+    if let Some(path) = local_path(url) {
+        let bytes = tokio::fs::read(path).await?;
+        return Ok(serde_json::from_slice(&bytes)?);
+    }
+    let mut attempt = 1;
+    loop {
+        match try_fetch_json(url).await {
+            Ok(value) => return Ok(value),
+            Err(why) if attempt < MAX_ATTEMPTS && is_transient(&why) => {
+                tokio::time::sleep(retry_delay(attempt)).await;
+                attempt += 1;
+            }
+            Err(why) => return Err(why.into()),
+        }
+    }
+}
+
+/// Checks, whether a gbfs feed is reachable, without fetching it. Feeds on disk
+/// are reachable, if the file exists. Servers not supporting `HEAD` requests are
+/// considered reachable.
+pub(crate) async fn probe(url: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if let Some(path) = local_path(url) {
+        tokio::fs::metadata(path).await?;
+        return Ok(());
+    }
+    let response = client().head(url).send().await?;
+    if response.status() == StatusCode::METHOD_NOT_ALLOWED {
+        return Ok(());
+    }
+    response.error_for_status()?;
+    Ok(())
+}
+
+async fn try_fetch_json<T: DeserializeOwned>(url: &str) -> Result<T, reqwest::Error> {
+    client()
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+}
+
+fn is_transient(why: &reqwest::Error) -> bool {
+    match why.status() {
+        Some(status) => {
+            status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+        }
+        None => !why.is_decode() && !why.is_builder(),
+    }
+}
+
+fn retry_delay(attempt: u32) -> Duration {
+    let backoff = RETRY_BASE_DELAY * 2u32.pow(attempt - 1);
+    let jitter = rand::thread_rng().gen_range(Duration::ZERO..RETRY_BASE_DELAY);
+    backoff + jitter
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use serde_json::Value;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// Serves the given statuses in turn, the last one repeatedly, with a json body.
+    /// Gives the url and the number of requests served.
+    async fn mock_server(statuses: &'static [u16]) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/gbfs.json", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let served = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buffer = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                let index = served.fetch_add(1, Ordering::SeqCst);
+                let status = statuses[index.min(statuses.len() - 1)];
+                let body = r#"{"ttl":60}"#;
+                let response = format!(
+                    "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried() {
+        let (url, requests) = mock_server(&[503, 200]).await;
+        let feed = fetch_json::<Value>(&url).await.expect("feed is fetched");
+        assert_eq!(feed["ttl"], 60);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn other_failures_are_not_retried() {
+        let (url, requests) = mock_server(&[404]).await;
+        assert!(fetch_json::<Value>(&url).await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn retries_back_off_with_jitter() {
+        for attempt in 1..MAX_ATTEMPTS {
+            let backoff = RETRY_BASE_DELAY * 2u32.pow(attempt - 1);
+            let delay = retry_delay(attempt);
+            assert!(
+                delay >= backoff && delay < backoff + RETRY_BASE_DELAY,
+                "delay of attempt {} is {:?}",
+                attempt,
+                delay
+            );
+        }
+    }
+}
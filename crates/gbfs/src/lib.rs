@@ -5,10 +5,11 @@ use model::{
 use public_transport::{
     client::Client, database::Database, RequestError, RequestResult,
 };
-use serde::Deserialize;
-use utility::id::Id;
+use serde::{Deserialize, Deserializer};
+use utility::{geo::GeoPolygon, id::Id};
 
 pub mod collector;
+mod http;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct StationInformation {
@@ -20,6 +21,12 @@ pub struct StationInformation {
     pub longitude: f64,
     pub capacity: u32,
     pub rental_uris: RentalUris,
+    /// Pick-up area of the station, since gbfs 2.1.
+    pub station_area: Option<GeoPolygon>,
+    pub region_id: Option<String>,
+    /// Since gbfs 2.1.
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub is_virtual_station: Option<bool>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -34,6 +41,33 @@ pub struct StationStatus {
     pub station_id: String,
     pub num_bikes_available: u32,
     pub num_docks_available: u32,
+    pub num_bikes_disabled: Option<u32>,
+    pub num_docks_disabled: Option<u32>,
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub is_installed: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub is_renting: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub is_returning: Option<bool>,
+}
+
+/// Flags are booleans since gbfs 2.0, but integers (`0` or `1`) before.
+fn deserialize_flag<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Flag {
+        Bool(bool),
+        Int(u8),
+    }
+
+    let flag = Option::<Flag>::deserialize(deserializer)?;
+    Ok(flag.map(|flag| match flag {
+        Flag::Bool(value) => value,
+        Flag::Int(value) => value != 0,
+    }))
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -46,42 +80,48 @@ pub struct Response<T> {
     pub data: T,
 }
 
+/// Updates the statuses of the stations of the origin. Gives the ids of the
+/// statuses without a known station.
 pub async fn update_station_status<D: Database>(
     client: Client<D>,
     url: &str,
-) -> RequestResult<()> {
-    let response: Response<StationRespones<StationStatus>> = reqwest::get(url)
-        .await
-        .map_err(|why| RequestError::Other(Box::new(why)))?
-        .json()
+) -> RequestResult<Vec<Id<SharedMobilityStation>>> {
+    let response: Response<StationRespones<StationStatus>> = http::fetch_json(url)
         .await
-        .map_err(|why| RequestError::Other(Box::new(why)))?;
+        .map_err(|why| RequestError::Other(why))?;
 
-    for status in response.data.stations {
-        client
-            .update_shared_mobility_station_status(
-                &Id::new(status.station_id),
+    let statuses = response
+        .data
+        .stations
+        .into_iter()
+        .map(|status| {
+            (
+                Id::new(status.station_id),
                 Some(shared_mobility::Status {
                     num_bikes_available: status.num_bikes_available,
                     num_docks_available: status.num_docks_available,
+                    num_bikes_disabled: status.num_bikes_disabled,
+                    num_docks_disabled: status.num_docks_disabled,
+                    is_installed: status.is_installed,
+                    is_renting: status.is_renting,
+                    is_returning: status.is_returning,
                 }),
             )
-            .await?;
-    }
-
-    Ok(())
+        })
+        .collect::<Vec<_>>();
+    client
+        .update_shared_mobility_station_statuses(&statuses)
+        .await
 }
 
 pub async fn insert_station_information<D: Database>(
     client: Client<D>,
     url: &str,
 ) -> RequestResult<()> {
-    let response: Response<StationRespones<StationInformation>> = reqwest::get(url)
-        .await
-        .map_err(|why| RequestError::Other(Box::new(why)))?
-        .json()
-        .await
-        .map_err(|why| RequestError::Other(Box::new(why)))?;
+    let response: Response<StationRespones<StationInformation>> =
+        http::fetch_json(url)
+            .await
+            .map_err(|why| RequestError::Other(why))?;
 
     client
         .put_shared_mobility_stations(
@@ -103,6 +143,11 @@ pub async fn insert_station_information<D: Database>(
                                 web: station.rental_uris.web,
                             },
                             status: None,
+                            area: station.station_area,
+                            region_id: station.region_id,
+                            is_virtual_station: station
+                                .is_virtual_station
+                                .unwrap_or_default(),
                         },
                     )
                 })
@@ -112,3 +157,145 @@ pub async fn insert_station_information<D: Database>(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statuses(json: &str) -> Vec<StationStatus> {
+        serde_json::from_str::<Response<StationRespones<StationStatus>>>(json)
+            .expect("statuses are deserialized")
+            .data
+            .stations
+    }
+
+    #[test]
+    fn statuses_of_all_gbfs_versions_are_deserialized() {
+        let statuses = statuses(
+            r#"{
+                "data": {
+                    "stations": [
+                        {
+                            "station_id": "v2",
+                            "num_bikes_available": 3,
+                            "num_docks_available": 5,
+                            "num_bikes_disabled": 1,
+                            "num_docks_disabled": 2,
+                            "is_installed": true,
+                            "is_renting": false,
+                            "is_returning": true
+                        },
+                        {
+                            "station_id": "v1",
+                            "num_bikes_available": 0,
+                            "num_docks_available": 8,
+                            "is_installed": 1,
+                            "is_renting": 0,
+                            "is_returning": 1
+                        },
+                        {
+                            "station_id": "minimal",
+                            "num_bikes_available": 2,
+                            "num_docks_available": 6
+                        }
+                    ]
+                }
+            }"#,
+        );
+        let flags = statuses
+            .iter()
+            .map(|status| {
+                (
+                    status.station_id.as_str(),
+                    status.num_bikes_disabled,
+                    status.num_docks_disabled,
+                    status.is_installed,
+                    status.is_renting,
+                    status.is_returning,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            flags,
+            [
+                ("v2", Some(1), Some(2), Some(true), Some(false), Some(true)),
+                ("v1", None, None, Some(true), Some(false), Some(true)),
+                ("minimal", None, None, None, None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn station_information_of_gbfs_2_3_is_deserialized() {
+        // shortened from the feed of a bike sharing system with virtual stations.
+        let response: Response<StationRespones<StationInformation>> =
+            serde_json::from_str(
+                r#"{
+                    "last_updated": 1718000000,
+                    "ttl": 60,
+                    "version": "2.3",
+                    "data": {
+                        "stations": [
+                            {
+                                "station_id": "1234",
+                                "name": "Kiel Hbf",
+                                "lat": 54.3154,
+                                "lon": 10.1317,
+                                "capacity": 12,
+                                "region_id": "kiel",
+                                "is_virtual_station": true,
+                                "station_area": {
+                                    "type": "MultiPolygon",
+                                    "coordinates": [
+                                        [
+                                            [
+                                                [10.1310, 54.3150],
+                                                [10.1325, 54.3150],
+                                                [10.1325, 54.3158],
+                                                [10.1310, 54.3158],
+                                                [10.1310, 54.3150]
+                                            ]
+                                        ]
+                                    ]
+                                },
+                                "rental_uris": {
+                                    "android": "https://bikes.example/app/station/1234",
+                                    "ios": "https://bikes.example/app/station/1234"
+                                },
+                                "rental_methods": ["creditcard", "applepay"],
+                                "is_valet_station": false,
+                                "is_charging_station": false
+                            },
+                            {
+                                "station_id": "5678",
+                                "name": "Dreiecksplatz",
+                                "lat": 54.3305,
+                                "lon": 10.1302,
+                                "capacity": 8,
+                                "rental_uris": {}
+                            }
+                        ]
+                    }
+                }"#,
+            )
+            .expect("station information is deserialized");
+        let [virtual_station, station] = response.data.stations.as_slice() else {
+            panic!("two stations are deserialized");
+        };
+
+        assert_eq!(virtual_station.capacity, 12);
+        assert_eq!(virtual_station.region_id.as_deref(), Some("kiel"));
+        assert_eq!(virtual_station.is_virtual_station, Some(true));
+        assert!(virtual_station.rental_uris.web.is_none());
+        let area = virtual_station
+            .station_area
+            .as_ref()
+            .expect("area is deserialized");
+        assert!(area.contains(54.3154, 10.1317));
+        assert!(!area.contains(54.3305, 10.1302));
+
+        assert!(station.station_area.is_none());
+        assert!(station.region_id.is_none());
+        assert_eq!(station.is_virtual_station, None);
+    }
+}
@@ -1,37 +1,167 @@
-use std::{error::Error, fs::File, path::Path, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use async_trait::async_trait;
-use model::line::LineType;
+use model::{
+    calendar::ServiceAvailability,
+    line::LineType,
+    shape::ShapePoint,
+    trip::{AreaKind, AreaReference},
+    trip_update::TripUpdate,
+    WithId,
+};
 use public_transport::{
-    client::Client,
+    capability::{self, Requirement},
+    client::{Client, PushTripOptions},
     collector::{Collector, Continuation},
     database::Database,
+    write_queue::WriteQueue,
     RequestError,
 };
 use serde::{Deserialize, Serialize};
-use utility::id::IdWrapper as _;
+use utility::{
+    id::{Id, IdWrapper as _},
+    log_sampling::{LogSampler, LogVerbosity},
+    normalize::normalize_platform_code,
+};
 
 use crate::{
+    csv_file::{is_invalid_field, open_csv, DEFAULT_DELIMITER},
     data_model::{
         agency::Agency,
         calendar::CalendarRow,
         calendar_dates::CalendarDate,
+        frequencies::Frequency,
+        levels::{Level, LevelId},
+        pathways::{Pathway, PathwayReferences},
         routes::{Route, RouteType},
+        shapes::ShapesRow,
         stop_times::StopTime,
         stops::Stop,
-        trips::Trip,
+        trips::{TravelDirection, Trip},
+        Timezone,
     },
-    download_gtfs,
-    realtime::update,
+    download_gtfs, probe_url,
+    realtime::{convert_updates, fetch_feed, FeedTracker},
+    retry::RetryBuffer,
 };
 
+const CALENDAR_HEADERS: &[&str] = &[
+    "service_id",
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+    "start_date",
+    "end_date",
+];
+
+/// Default number of trip updates buffered, while the database is unavailable.
+const DEFAULT_QUEUE_CAPACITY: usize = 100_000;
+
+/// How long schedules are kept, before they are imported again.
+const SCHEDULE_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// Stops deeper in the hierarchy of stations, e.g. boarding areas on platforms,
+/// are not resolved.
+const MAX_STOP_HIERARCHY_DEPTH: usize = 4;
+
+type TripUpdateQueue = Arc<WriteQueue<WithId<TripUpdate>>>;
+
 pub struct RealtimeCollector {
     update: Duration,
+    queue: Option<TripUpdateQueue>,
+    log: LogSampler,
+    /// Skips feeds and entities, which were already applied by earlier polls.
+    feed: FeedTracker,
 }
 
 impl RealtimeCollector {
     pub fn new<S: Into<String>>(update: Duration) -> Self {
-        Self { update }
+        Self {
+            update,
+            queue: None,
+            log: LogSampler::default(),
+            feed: FeedTracker::default(),
+        }
+    }
+
+    /// Opens the write queue and starts draining it into the database, unless
+    /// already done.
+    async fn queue<D: Database>(
+        &mut self,
+        client: &Client<D>,
+        state: &RealtimeCollectorState,
+    ) -> Result<Option<TripUpdateQueue>, Box<dyn Error + Send + Sync>> {
+        let Some(path) = &state.queue_file else {
+            return Ok(None);
+        };
+        if self.queue.is_none() {
+            let capacity = state.queue_capacity.unwrap_or(DEFAULT_QUEUE_CAPACITY);
+            let queue = Arc::new(WriteQueue::open(path, capacity).await?);
+            let client = client.clone();
+            queue.spawn_drain(move |updates| {
+                let client = client.clone();
+                async move { client.put_trip_updates(updates).await.map(|_| ()) }
+            });
+            self.queue = Some(queue);
+        }
+        Ok(self.queue.clone())
+    }
+
+    /// Logs every trip update, if the collector is verbose.
+    fn log_updates(&self, updates: &[WithId<TripUpdate>]) {
+        if !self.log.is_verbose() {
+            return;
+        }
+        for update in updates {
+            log::info!(
+                "inserting update {}: {}",
+                update.id.raw().trip_id,
+                update
+                    .content
+                    .stops
+                    .iter()
+                    .map(|st| format!(
+                        "{}-{}",
+                        st.arrival_time
+                            .map(|t| t.to_string())
+                            .unwrap_or("X".to_owned()),
+                        st.departure_time
+                            .map(|t| t.to_string())
+                            .unwrap_or("X".to_owned())
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            );
+        }
+    }
+
+    /// Logs the outcome of a run, or of all runs of the window in summary.
+    fn log_run(&self, updates: usize, queue_depth: Option<usize>) {
+        if let Some(depth) = queue_depth.filter(|_| self.log.is_verbose()) {
+            log::info!("write queue depth: {}", depth);
+        }
+        let Some(count) = self.log.count(updates as u64) else {
+            return;
+        };
+        match queue_depth {
+            Some(depth) => log::info!(
+                "received {} trip update(s) in the last minute, write queue depth: {}",
+                count,
+                depth
+            ),
+            None => log::info!("inserted {} trip update(s) in the last minute", count),
+        }
     }
 }
 
@@ -40,6 +170,16 @@ impl RealtimeCollector {
 pub struct RealtimeCollectorState {
     pub url: String,
     pub update_interval: Duration,
+    /// File buffering trip updates before they are written to the database. If
+    /// set, updates survive database outages and restarts.
+    #[serde(default)]
+    pub queue_file: Option<PathBuf>,
+    /// Maximum number of buffered trip updates. The oldest are dropped first.
+    #[serde(default)]
+    pub queue_capacity: Option<usize>,
+    /// How much is logged about the trip updates of every run.
+    #[serde(default)]
+    pub log_verbosity: LogVerbosity,
 }
 
 #[async_trait]
@@ -51,10 +191,15 @@ impl Collector for RealtimeCollector {
         "GTFS Realtime"
     }
 
-    fn from_state(state: Self::State) -> Self {
-        Self {
-            update: state.update_interval,
+    fn from_state(state: Self::State) -> Result<Self, Self::Error> {
+        // the interval of the collector would panic.
+        if state.update_interval.is_zero() {
+            return Err("update interval must not be zero.".into());
         }
+        Ok(Self {
+            log: LogSampler::new(state.log_verbosity),
+            ..Self::new::<String>(state.update_interval)
+        })
     }
 
     async fn run<D>(
@@ -65,11 +210,48 @@ impl Collector for RealtimeCollector {
     where
         D: Database,
     {
-        log::info!("update!");
-        update(client.clone(), &state.url).await.unwrap();
+        if self.log.is_verbose() {
+            log::info!("update!");
+        }
+        let message = fetch_feed(&state.url)
+            .await
+            .map_err(|why| format!("{:?}", why))?;
+        let Some((entities, snapshot)) = self.feed.changes(message) else {
+            if self.log.is_verbose() {
+                log::info!("feed did not advance, skipped.");
+            }
+            return Ok((Continuation::Continue, state));
+        };
+        let updates = convert_updates(client, entities)
+            .await
+            .map_err(|why| format!("{:?}", why))?;
+        self.log_updates(&updates);
+        let count = updates.len();
+        match self.queue(client, &state).await? {
+            Some(queue) => {
+                if !updates.is_empty() {
+                    queue.push(updates).await?;
+                }
+                self.log_run(count, Some(queue.depth()));
+            }
+            None => {
+                if !updates.is_empty() {
+                    client
+                        .put_trip_updates(updates)
+                        .await
+                        .map_err(|why| format!("{:?}", why))?;
+                }
+                self.log_run(count, None);
+            }
+        }
+        self.feed.apply(snapshot);
         Ok((Continuation::Continue, state))
     }
 
+    async fn healthcheck(&mut self, state: &Self::State) -> Result<(), Self::Error> {
+        probe_url(&state.url).await
+    }
+
     fn tick(&self) -> Option<Duration> {
         Some(self.update)
     }
@@ -86,6 +268,9 @@ impl ScheduleCollector {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScheduleCollectorState {
     pub url: String,
+    /// Delimiter of the csv files, if the feed does not use commas.
+    #[serde(default)]
+    pub delimiter: Option<char>,
 }
 
 #[async_trait]
@@ -97,8 +282,12 @@ impl Collector for ScheduleCollector {
         "GTFS Schedule"
     }
 
-    fn from_state(_state: Self::State) -> Self {
-        Self {}
+    fn requirements() -> &'static [Requirement] {
+        &[capability::STOP_MERGING]
+    }
+
+    fn from_state(_state: Self::State) -> Result<Self, Self::Error> {
+        Ok(Self {})
     }
 
     async fn run<D: Database>(
@@ -106,8 +295,16 @@ impl Collector for ScheduleCollector {
         client: &Client<D>,
         state: Self::State,
     ) -> Result<(Continuation, Self::State), Self::Error> {
-        download_and_insert(client, "", &state.url).await?;
-        Ok((Continuation::Exit, state))
+        let delimiter = match state.delimiter {
+            Some(delimiter) => u8::try_from(delimiter)?,
+            None => DEFAULT_DELIMITER,
+        };
+        download_and_insert(client, "", &state.url, delimiter).await?;
+        Ok((Continuation::Sleep(SCHEDULE_REFRESH_INTERVAL), state))
+    }
+
+    async fn healthcheck(&mut self, state: &Self::State) -> Result<(), Self::Error> {
+        probe_url(&state.url).await
     }
 
     fn tick(&self) -> Option<Duration> {
@@ -115,17 +312,149 @@ impl Collector for ScheduleCollector {
     }
 }
 
+/// Schedule feed, imported under its own origin.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleFeed {
+    /// Name of the origin, the feed is imported under.
+    pub origin: String,
+    /// Priority of the origin. Created with the name, if it does not exist yet.
+    pub priority: i32,
+    pub url: String,
+    /// Delimiter of the csv files, if the feed does not use commas.
+    #[serde(default)]
+    pub delimiter: Option<char>,
+}
+
+/// Imports multiple schedule feeds, e.g. all national feeds of a deployment, each
+/// under a distinct origin.
+pub struct MultiScheduleCollector {}
+
+impl MultiScheduleCollector {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for MultiScheduleCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiScheduleCollectorState {
+    pub feeds: Vec<ScheduleFeed>,
+    /// Why the feeds failed in the last run, by the name of their origin.
+    #[serde(default)]
+    pub failures: BTreeMap<String, String>,
+}
+
+#[async_trait]
+impl Collector for MultiScheduleCollector {
+    type Error = Box<dyn Error + Send + Sync>;
+    type State = MultiScheduleCollectorState;
+
+    fn unique_id() -> &'static str {
+        "GTFS Schedule (multiple feeds)"
+    }
+
+    fn requirements() -> &'static [Requirement] {
+        &[capability::STOP_MERGING]
+    }
+
+    fn from_state(_state: Self::State) -> Result<Self, Self::Error> {
+        Ok(Self {})
+    }
+
+    async fn run<D: Database>(
+        &mut self,
+        client: &Client<D>,
+        mut state: Self::State,
+    ) -> Result<(Continuation, Self::State), Self::Error> {
+        state.failures =
+            import_feeds(&state.feeds, |feed| import_feed(client, feed)).await;
+        Ok((Continuation::Sleep(SCHEDULE_REFRESH_INTERVAL), state))
+    }
+
+    fn partial_failure(&self, state: &Self::State) -> Option<String> {
+        describe_failures(&state.failures)
+    }
+
+    async fn healthcheck(&mut self, state: &Self::State) -> Result<(), Self::Error> {
+        for feed in state.feeds.iter() {
+            probe_url(&feed.url).await?;
+        }
+        Ok(())
+    }
+
+    fn tick(&self) -> Option<Duration> {
+        Some(Duration::from_secs(60 * 60 * 24 * 30))
+    }
+}
+
+/// Imports the feeds one after another. A broken feed does not keep the other
+/// origins from being updated. Returns why feeds failed, by origin.
+async fn import_feeds<F, Fut>(
+    feeds: &[ScheduleFeed],
+    mut import: F,
+) -> BTreeMap<String, String>
+where
+    F: FnMut(ScheduleFeed) -> Fut,
+    Fut: Future<Output = Result<(), Box<dyn Error + Send + Sync>>>,
+{
+    let mut failures = BTreeMap::new();
+    for feed in feeds.iter() {
+        if let Err(why) = import(feed.clone()).await {
+            log::error!("gtfs feed of origin '{}' failed: {}", feed.origin, why);
+            failures.insert(feed.origin.clone(), why.to_string());
+        }
+    }
+    failures
+}
+
+async fn import_feed<D: Database>(
+    client: &Client<D>,
+    feed: ScheduleFeed,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let delimiter = match feed.delimiter {
+        Some(delimiter) => u8::try_from(delimiter)?,
+        None => DEFAULT_DELIMITER,
+    };
+    let origin = client
+        .put_origin(&feed.origin, feed.priority)
+        .await
+        .map_err(|why| format!("{:?}", why))?;
+    log::info!("importing gtfs feed of origin '{}'", origin);
+    download_and_insert(&client.for_origin(&origin), "", &feed.url, delimiter).await
+}
+
+/// Summary of the failed feeds, or `None` if all of them were imported.
+fn describe_failures(failures: &BTreeMap<String, String>) -> Option<String> {
+    if failures.is_empty() {
+        return None;
+    }
+    let feeds = failures
+        .iter()
+        .map(|(origin, why)| format!("'{}': {}", origin, why))
+        .collect::<Vec<_>>();
+    Some(format!(
+        "{} gtfs feed(s) failed: {}",
+        feeds.len(),
+        feeds.join("; ")
+    ))
+}
+
 async fn download_and_insert<D: Database, P: Into<String>, S: Into<String>>(
     client: &Client<D>,
     path_prefix: P,
     url: S,
+    delimiter: u8,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
     println!("downloading gtfs...");
-    download_gtfs(&url.into()).await?;
+    let feed = download_gtfs(&url.into()).await?;
     println!("inserting gtfs tables...");
-    insert_tables(client, Path::new("./").join(&path_prefix.into()).as_path())
-        .await?
-        .print();
+    let path = feed.path().join(path_prefix.into());
+    insert_tables(client, &path, delimiter).await?.print();
     println!("gtfs complete.");
     Ok(())
 }
@@ -135,13 +464,30 @@ struct GtfsReport {
     skipped_agencies: usize,
     skipped_routes: usize,
     skipped_stops: usize,
+    centroid_stops: u64,
+    invalid_timezones: usize,
     skipped_calendar_rows: usize,
     skipped_calendar_dates: usize,
+    skipped_shape_points: usize,
     skipped_trips: usize,
     skipped_stop_times: usize,
+    skipped_frequencies: usize,
+    skipped_levels: usize,
+    /// Pathways skipped for other reasons than unknown stops, which are counted
+    /// as unresolved references.
+    skipped_pathways: usize,
+    /// Rows skipped, because the entities they reference were still missing
+    /// after the second pass, by file and reference.
+    unresolved_references: BTreeMap<&'static str, usize>,
 }
 
 impl GtfsReport {
+    fn count_unresolved(&mut self, reason: &'static str, why: &RequestError) {
+        if matches!(why, RequestError::IdMissing) {
+            *self.unresolved_references.entry(reason).or_default() += 1;
+        }
+    }
+
     fn print(&self) {
         println!(
             "gtfs report: {}",
@@ -175,22 +521,39 @@ impl Progress {
 async fn insert_tables<D: Database>(
     client: &Client<D>,
     path: &Path,
+    delimiter: u8,
 ) -> Result<GtfsReport, Box<dyn Error + Send + Sync>> {
     let mut report = GtfsReport {
         skipped_agencies: 0,
         skipped_routes: 0,
         skipped_stops: 0,
+        centroid_stops: 0,
+        invalid_timezones: 0,
         skipped_calendar_rows: 0,
         skipped_calendar_dates: 0,
+        skipped_shape_points: 0,
         skipped_trips: 0,
         skipped_stop_times: 0,
+        skipped_frequencies: 0,
+        skipped_levels: 0,
+        skipped_pathways: 0,
+        unresolved_references: BTreeMap::new(),
     };
     let mut progress = Progress::new(1000);
 
     // agencies
     log::info!("inserting agencies...");
-    let mut reader = csv::Reader::from_reader(File::open(path.join("agency.txt"))?);
-    for row in reader.deserialize() {
+    let mut reader = open_csv(
+        &path.join("agency.txt"),
+        delimiter,
+        &["agency_name", "agency_url", "agency_timezone"],
+    )?;
+    let headers = reader.headers()?.clone();
+    for record in reader.records() {
+        if is_invalid_timezone(&record, &headers, "agency_timezone") {
+            report.invalid_timezones += 1;
+        }
+        let row = record.and_then(|record| record.deserialize(Some(&headers)));
         if let Err(_) = insert_agency(client, row).await {
             report.skipped_agencies += 1;
         }
@@ -200,7 +563,11 @@ async fn insert_tables<D: Database>(
 
     // routes
     log::info!("inserting routes...");
-    let mut reader = csv::Reader::from_reader(File::open(path.join("routes.txt"))?);
+    let mut reader = open_csv(
+        &path.join("routes.txt"),
+        delimiter,
+        &["route_id", "route_type"],
+    )?;
     for row in reader.deserialize() {
         if let Err(_) = insert_route(client, row).await {
             report.skipped_routes += 1;
@@ -210,8 +577,31 @@ async fn insert_tables<D: Database>(
 
     // stops
     log::info!("inserting stops...");
-    let mut reader = csv::Reader::from_reader(File::open(path.join("stops.txt"))?);
-    for row in reader.deserialize() {
+    let pathways_path = path.join("pathways.txt");
+    // parents and levels of stops by original id, only kept for pathways.
+    let mut stop_locations = pathways_path.exists().then(HashMap::new);
+    let mut reader = open_csv(&path.join("stops.txt"), delimiter, &["stop_id"])?;
+    let headers = reader.headers()?.clone();
+    for record in reader.records() {
+        if is_invalid_timezone(&record, &headers, "stop_timezone") {
+            report.invalid_timezones += 1;
+        }
+        let row =
+            record.and_then(|record| record.deserialize::<Stop>(Some(&headers)));
+        if let (Some(stop_locations), Ok(stop)) = (&mut stop_locations, &row) {
+            if stop.parent_station.is_some() || stop.level_id.is_some() {
+                stop_locations.insert(
+                    stop.id.raw(),
+                    StopLocation {
+                        parent_station: stop
+                            .parent_station
+                            .as_ref()
+                            .map(|id| id.raw()),
+                        level_id: stop.level_id.clone(),
+                    },
+                );
+            }
+        }
         if let Err(_) = insert_stop(client, row).await {
             report.skipped_stops += 1;
         }
@@ -219,9 +609,59 @@ async fn insert_tables<D: Database>(
     }
     progress.reset();
 
+    // stations often only have coordinates for their platforms
+    log::info!("backfilling stop locations...");
+    match client.backfill_stop_centroids().await {
+        Ok(count) => report.centroid_stops = count,
+        Err(why) => log::error!("backfilling stop locations failed: {:?}", why),
+    }
+
+    // levels (optional), referenced by stops with pathways
+    let levels_path = path.join("levels.txt");
+    if levels_path.exists() {
+        log::info!("inserting levels...");
+        let mut reader =
+            open_csv(&levels_path, delimiter, &["level_id", "level_index"])?;
+        for row in reader.deserialize() {
+            if insert_level(client, row).await.is_err() {
+                report.skipped_levels += 1;
+            }
+            progress.inc();
+        }
+        progress.reset();
+    }
+
+    // pathways (optional), ways within stations between their locations
+    if let Some(stop_locations) = stop_locations {
+        log::info!("inserting pathways...");
+        let mut reader = open_csv(
+            &pathways_path,
+            delimiter,
+            &[
+                "pathway_id",
+                "from_stop_id",
+                "to_stop_id",
+                "pathway_mode",
+                "is_bidirectional",
+            ],
+        )?;
+        for row in reader.deserialize() {
+            if let Err(why) = insert_pathway(client, row, &stop_locations).await {
+                if matches!(why, RequestError::IdMissing) {
+                    report.count_unresolved("pathways.txt: unknown stop_id", &why);
+                } else {
+                    report.skipped_pathways += 1;
+                }
+            }
+            progress.inc();
+        }
+        progress.reset();
+    }
+
     // calendar
     log::info!("inserting calendar...");
-    let mut reader = csv::Reader::from_reader(File::open(path.join("calendar.txt"))?);
+    let mut reader =
+        open_csv(&path.join("calendar.txt"), delimiter, CALENDAR_HEADERS)?;
     for row in reader.deserialize() {
         if let Err(_) = insert_calendar_row(client, row).await {
             report.skipped_calendar_rows += 1;
@@ -232,8 +672,11 @@ async fn insert_tables<D: Database>(
 
     // calendar dates
     log::info!("inserting calendar dates...");
-    let mut reader =
-        csv::Reader::from_reader(File::open(path.join("calendar_dates.txt"))?);
+    let mut reader = open_csv(
+        &path.join("calendar_dates.txt"),
+        delimiter,
+        &["service_id", "date", "exception_type"],
+    )?;
     for row in reader.deserialize() {
         if let Err(_) = insert_calendar_date(client, row).await {
             report.skipped_calendar_dates += 1;
@@ -242,11 +685,76 @@ async fn insert_tables<D: Database>(
     }
     progress.reset();
 
+    // shapes (optional), inserted before the trips referencing them
+    let shapes_path = path.join("shapes.txt");
+    if shapes_path.exists() {
+        log::info!("inserting shapes...");
+        let mut reader = open_csv(
+            &shapes_path,
+            delimiter,
+            &[
+                "shape_id",
+                "shape_pt_lat",
+                "shape_pt_lon",
+                "shape_pt_sequence",
+            ],
+        )?;
+        // shapes, whose points of previous imports were already replaced.
+        let mut replaced = HashSet::new();
+        let mut chunk: Option<(String, Vec<(i32, ShapePoint)>)> = None;
+        for row in reader.deserialize::<ShapesRow>() {
+            let Ok(row) = row else {
+                report.skipped_shape_points += 1;
+                continue;
+            };
+            let shape_id = row.shape_id.raw();
+            // points of a shape are usually consecutive, but need not be.
+            if let Some((chunk_id, points)) = chunk.take_if(|(chunk_id, points)| {
+                *chunk_id != shape_id || points.len() >= SHAPE_CHUNK_SIZE
+            }) {
+                report.skipped_shape_points +=
+                    insert_shape_points(client, chunk_id, points, &mut replaced)
+                        .await;
+            }
+            chunk.get_or_insert_with(|| (shape_id, vec![])).1.push((
+                row.point_sequence as i32,
+                ShapePoint {
+                    latitude: row.point_latitude,
+                    longitude: row.point_longitude,
+                    distance: row.distance_traveled,
+                },
+            ));
+            progress.inc();
+        }
+        if let Some((chunk_id, points)) = chunk {
+            report.skipped_shape_points +=
+                insert_shape_points(client, chunk_id, points, &mut replaced).await;
+        }
+        progress.reset();
+    }
+
     // trips
     log::info!("inserting trips...");
-    let mut reader = csv::Reader::from_reader(File::open(path.join("trips.txt"))?);
-    for row in reader.deserialize() {
-        if let Err(_) = insert_trip(client, row).await {
+    let mut reader = open_csv(
+        &path.join("trips.txt"),
+        delimiter,
+        &["route_id", "service_id", "trip_id"],
+    )?;
+    let trip_headers = reader.headers()?.clone();
+    let mut trip_retries = RetryBuffer::new("trips.txt: unknown route_id");
+    let mut pushed_trips = HashSet::new();
+    for record in reader.into_records() {
+        let Ok(record) = record else {
+            report.skipped_trips += 1;
+            continue;
+        };
+        let result = insert_trip(
+            client,
+            record.deserialize(Some(&trip_headers)),
+            &mut pushed_trips,
+        )
+        .await;
+        if !retry_if_missing(result, record, &mut trip_retries)? {
             report.skipped_trips += 1;
         }
         progress.inc();
@@ -255,18 +763,161 @@ async fn insert_tables<D: Database>(
 
     // stop times
     log::info!("inserting stop times...");
-    let mut reader =
-        csv::Reader::from_reader(File::open(path.join("stop_times.txt"))?);
-    for row in reader.deserialize() {
-        if let Err(_) = insert_stop_time(client, row).await {
-            report.skipped_stop_times += 1;
+    let mut reader = open_csv(
+        &path.join("stop_times.txt"),
+        delimiter,
+        &["trip_id", "stop_sequence"],
+    )?;
+    let stop_time_headers = reader.headers()?.clone();
+    let mut stop_time_retries = RetryBuffer::new("stop_times.txt: unknown trip_id");
+    // trips, whose stop times of previous imports were already replaced.
+    let mut replaced = HashSet::new();
+    insert_stop_time_rows(
+        client,
+        reader.into_records(),
+        &stop_time_headers,
+        &mut replaced,
+        MissingTrip::Retry(&mut stop_time_retries),
+        &mut report,
+        &mut progress,
+    )
+    .await?;
+    progress.reset();
+
+    // frequencies (optional), periods in which trips run repeatedly
+    let frequencies_path = path.join("frequencies.txt");
+    let mut frequency_headers = csv::StringRecord::new();
+    let mut frequency_retries = RetryBuffer::new("frequencies.txt: unknown trip_id");
+    if frequencies_path.exists() {
+        log::info!("inserting frequencies...");
+        let mut reader = open_csv(
+            &frequencies_path,
+            delimiter,
+            &["trip_id", "start_time", "end_time", "headway_secs"],
+        )?;
+        frequency_headers = reader.headers()?.clone();
+        for record in reader.into_records() {
+            let Ok(record) = record else {
+                report.skipped_frequencies += 1;
+                continue;
+            };
+            let result = insert_frequency(
+                client,
+                record.deserialize(Some(&frequency_headers)),
+            )
+            .await;
+            if !retry_if_missing(result, record, &mut frequency_retries)? {
+                report.skipped_frequencies += 1;
+            }
+            progress.inc();
         }
-        progress.inc();
+        progress.reset();
+    }
+
+    // second pass over rows, whose references were missing during the first one.
+    // trips are retried first, as stop times and frequencies reference them.
+    if trip_retries.len() + stop_time_retries.len() + frequency_retries.len() > 0 {
+        log::info!(
+            "retrying {} trips, {} stop times and {} frequencies...",
+            trip_retries.len(),
+            stop_time_retries.len(),
+            frequency_retries.len()
+        );
+    }
+    let reason = trip_retries.reason();
+    for record in trip_retries.into_rows()? {
+        let result = insert_trip(
+            client,
+            record?.deserialize(Some(&trip_headers)),
+            &mut pushed_trips,
+        )
+        .await;
+        if let Err(why) = result {
+            report.skipped_trips += 1;
+            report.count_unresolved(reason, &why);
+        }
+    }
+    let reason = stop_time_retries.reason();
+    insert_stop_time_rows(
+        client,
+        stop_time_retries.into_rows()?,
+        &stop_time_headers,
+        &mut replaced,
+        MissingTrip::Unresolved(reason),
+        &mut report,
+        &mut progress,
+    )
+    .await?;
+    progress.reset();
+    // trips without stop times in this import keep none of previous imports.
+    for trip_id in pushed_trips.difference(&replaced) {
+        if let Err(why) = client.push_stop_times(trip_id, vec![], true).await {
+            log::warn!("could not clear stop times: {:?}", why);
+        }
+    }
+    let reason = frequency_retries.reason();
+    for record in frequency_retries.into_rows()? {
+        let result =
+            insert_frequency(client, record?.deserialize(Some(&frequency_headers)))
+                .await;
+        if let Err(why) = result {
+            report.skipped_frequencies += 1;
+            report.count_unresolved(reason, &why);
+        }
+    }
+
+    client.schedule_imported();
+
+    // stops without remaining service are ranked last in search
+    log::info!("refreshing stop service summary...");
+    if let Err(why) = client.refresh_stop_service_summary().await {
+        log::error!("refreshing stop service summary failed: {:?}", why);
+    }
+
+    // school-only and seasonal services can be hidden by clients
+    log::info!("classifying services...");
+    match client.classify_services().await {
+        Ok(count) => log::info!("tagged {} services.", count),
+        Err(why) => log::error!("classifying services failed: {:?}", why),
     }
 
     Ok(report)
 }
 
+/// Keeps the row for a second pass, if it references an entity, which does not
+/// exist (yet). Returns whether the row was inserted or kept.
+fn retry_if_missing(
+    result: Result<(), RequestError>,
+    record: csv::StringRecord,
+    retries: &mut RetryBuffer,
+) -> Result<bool, csv::Error> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(RequestError::IdMissing) => retries.push(record).map(|_| true),
+        Err(_) => Ok(false),
+    }
+}
+
+/// Rows with an unknown timezone are skipped instead of being stored with a zone
+/// that breaks time computations later on.
+fn is_invalid_timezone(
+    record: &Result<csv::StringRecord, csv::Error>,
+    headers: &csv::StringRecord,
+    column: &str,
+) -> bool {
+    match record {
+        Ok(record) if is_invalid_field::<Timezone>(record, headers, column) => {
+            log::warn!(
+                "skipping row with invalid {} at {:?}",
+                column,
+                record.position()
+            );
+            true
+        }
+        _ => false,
+    }
+}
+
 async fn insert_agency<D: Database>(
     client: &Client<D>,
     agency: Result<Agency, csv::Error>,
@@ -287,6 +938,31 @@ async fn insert_agency<D: Database>(
     Ok(())
 }
 
+/// Line type of a route type. Extended rail route types are split by service level.
+fn line_type_of_route_type(kind: &RouteType) -> LineType {
+    match kind {
+        RouteType::TramStreetcarOrLighrail => LineType::TramStreetcarOrLighrail,
+        RouteType::SubwayOrMetro => LineType::SubwayOrMetro,
+        RouteType::Bus => LineType::Bus,
+        RouteType::Ferry => LineType::Ferry,
+        RouteType::CableTram => LineType::CableTram,
+        RouteType::AerialLiftOrSuspendedCableCar => {
+            LineType::AerialLiftOrSuspendedCableCar
+        }
+        RouteType::Funicular => LineType::Funicular,
+        RouteType::Trolleybus => LineType::Trolleybus,
+        RouteType::Monorail => LineType::Monorail,
+        RouteType::Rail | RouteType::RailwayService => LineType::Rail,
+        RouteType::HighSpeedRail
+        | RouteType::LongDistanceTrains
+        | RouteType::SleeperRail => LineType::LongDistanceRail,
+        RouteType::InterRegionalRail | RouteType::RegionalRail => {
+            LineType::RegionalRail
+        }
+        RouteType::SuburbanRailway => LineType::SuburbanRail,
+    }
+}
+
 async fn insert_route<D: Database>(
     client: &Client<D>,
     route: Result<Route, csv::Error>,
@@ -294,7 +970,7 @@ async fn insert_route<D: Database>(
     let route = route.map_err(RequestError::other)?;
 
     // TODO: exclude rail lines for now, as trip merging is not yet completely implemented.
-    if matches!(route.kind, RouteType::Rail) {
+    if route.kind.is_rail() {
         return Ok(());
     }
 
@@ -308,23 +984,10 @@ async fn insert_route<D: Database>(
         .push_line(
             model::line::Line {
                 name,
-                kind: match route.kind {
-                    RouteType::TramStreetcarOrLighrail => {
-                        LineType::TramStreetcarOrLighrail
-                    }
-                    RouteType::SubwayOrMetro => LineType::SubwayOrMetro,
-                    RouteType::Rail => LineType::Rail,
-                    RouteType::Bus => LineType::Bus,
-                    RouteType::Ferry => LineType::Ferry,
-                    RouteType::CableTram => LineType::CableTram,
-                    RouteType::AerialLiftOrSuspendedCableCar => {
-                        LineType::AerialLiftOrSuspendedCableCar
-                    }
-                    RouteType::Funicular => LineType::Funicular,
-                    RouteType::Trolleybus => LineType::Trolleybus,
-                    RouteType::Monorail => LineType::Monorail,
-                },
+                kind: line_type_of_route_type(&route.kind),
                 agency_id,
+                secondary_agency_ids: vec![],
+                updated_at: None,
             },
             Some(route.id.raw()),
         )
@@ -332,6 +995,79 @@ async fn insert_route<D: Database>(
     Ok(())
 }
 
+/// Location of a stop within a station, as needed to resolve pathways.
+struct StopLocation {
+    parent_station: Option<String>,
+    level_id: Option<LevelId>,
+}
+
+/// Topmost ancestor of the stop, e.g. the station of a boarding area on a
+/// platform. `None`, if the stop has no parent.
+fn station_of(
+    stop_id: &str,
+    stop_locations: &HashMap<String, StopLocation>,
+) -> Option<String> {
+    let mut station = None;
+    let mut current = stop_id;
+    // bounded, as feeds may contain cycles.
+    for _ in 0..MAX_STOP_HIERARCHY_DEPTH {
+        let Some(parent) = stop_locations
+            .get(current)
+            .and_then(|location| location.parent_station.as_ref())
+        else {
+            break;
+        };
+        station = Some(parent.clone());
+        current = parent;
+    }
+    station
+}
+
+async fn insert_level<D: Database>(
+    client: &Client<D>,
+    level: Result<Level, csv::Error>,
+) -> Result<(), RequestError> {
+    let level = level.map_err(RequestError::other)?;
+    client.put_level(level.to_model()).await
+}
+
+/// Fails with `RequestError::IdMissing`, if one of the endpoints is not a known
+/// stop.
+async fn insert_pathway<D: Database>(
+    client: &Client<D>,
+    pathway: Result<Pathway, csv::Error>,
+    stop_locations: &HashMap<String, StopLocation>,
+) -> Result<(), RequestError> {
+    let pathway = pathway.map_err(RequestError::other)?;
+    let from_stop_id = pathway.from_stop_id.raw();
+    let to_stop_id = pathway.to_stop_id.raw();
+    let level_of = |stop_id: &str| {
+        stop_locations
+            .get(stop_id)
+            .and_then(|location| location.level_id.clone())
+    };
+    let station_id = match station_of(&from_stop_id, stop_locations)
+        .or_else(|| station_of(&to_stop_id, stop_locations))
+    {
+        Some(station_id) => client.get_stop_id_by_original_id(station_id).await?,
+        None => None,
+    };
+    let references = PathwayReferences {
+        from_level_id: level_of(&from_stop_id),
+        to_level_id: level_of(&to_stop_id),
+        from_stop_id: client
+            .get_stop_id_by_original_id(from_stop_id)
+            .await?
+            .ok_or(RequestError::IdMissing)?,
+        to_stop_id: client
+            .get_stop_id_by_original_id(to_stop_id)
+            .await?
+            .ok_or(RequestError::IdMissing)?,
+        station_id,
+    };
+    client.put_pathway(pathway.to_model(references)).await
+}
+
 async fn insert_stop<D: Database>(
     client: &Client<D>,
     stop: Result<Stop, csv::Error>,
@@ -353,7 +1089,12 @@ async fn insert_stop<D: Database>(
                     }
                     _ => None,
                 },
-                platform_code: stop.platform_code,
+                platform_code: stop
+                    .platform_code
+                    .as_deref()
+                    .and_then(normalize_platform_code),
+                amenities: vec![],
+                updated_at: None,
             },
             Some(stop.id.raw()),
         )
@@ -370,13 +1111,13 @@ async fn insert_calendar_row<D: Database>(
         .push_calendar_window(
             None,
             model::calendar::CalendarWindow {
-                monday: calendar_row.monday.into(),
-                tuesday: calendar_row.tuesday.into(),
-                wednesday: calendar_row.wednesday.into(),
-                thursday: calendar_row.thursday.into(),
-                friday: calendar_row.friday.into(),
-                saturday: calendar_row.saturday.into(),
-                sunday: calendar_row.sunday.into(),
+                monday: ServiceAvailability::from_bool(calendar_row.monday),
+                tuesday: ServiceAvailability::from_bool(calendar_row.tuesday),
+                wednesday: ServiceAvailability::from_bool(calendar_row.wednesday),
+                thursday: ServiceAvailability::from_bool(calendar_row.thursday),
+                friday: ServiceAvailability::from_bool(calendar_row.friday),
+                saturday: ServiceAvailability::from_bool(calendar_row.saturday),
+                sunday: ServiceAvailability::from_bool(calendar_row.sunday),
                 start_date: calendar_row.start_date,
                 end_date: calendar_row.end_date,
             },
@@ -408,12 +1149,45 @@ async fn insert_calendar_date<D: Database>(
     Ok(())
 }
 
+/// Maximum number of shape points inserted at once.
+const SHAPE_CHUNK_SIZE: usize = 1000;
+
+/// Inserts points of a shape. Points of previous imports are replaced, when the
+/// first chunk of a shape is inserted. Returns the number of skipped points.
+async fn insert_shape_points<D: Database>(
+    client: &Client<D>,
+    original_id: String,
+    points: Vec<(i32, ShapePoint)>,
+    replaced: &mut HashSet<String>,
+) -> usize {
+    let count = points.len();
+    let replace = replaced.insert(original_id.clone());
+    match client.push_shape_points(original_id, points, replace).await {
+        Ok(_) => 0,
+        Err(why) => {
+            log::warn!("could not insert shape points: {:?}", why);
+            count
+        }
+    }
+}
+
+/// Inserts the trip without stop times, which are inserted along with the stop
+/// times file. Records the id of the trip in `pushed`.
 async fn insert_trip<D: Database>(
     client: &Client<D>,
     trip: Result<Trip, csv::Error>,
+    pushed: &mut HashSet<Id<model::trip::Trip>>,
 ) -> Result<(), RequestError> {
     let trip = trip.map_err(RequestError::other)?;
-    client
+    let shape_id = match trip.shape_id {
+        Some(original_shape_id) => {
+            client
+                .get_shape_id_by_original_id(original_shape_id)
+                .await?
+        }
+        None => None,
+    };
+    let result = client
         .push_trip(
             model::trip::Trip {
                 line_id: client
@@ -426,20 +1200,135 @@ async fn insert_trip<D: Database>(
                     .unwrap(),
                 headsign: trip.headsign,
                 short_name: trip.short_name,
+                direction: trip.direction.map(TravelDirection::to_model),
+                shape_id,
                 stops: vec![],
+                frequencies: vec![],
+                updated_at: None,
             },
             Some(trip.id.raw()),
-            true,
+            PushTripOptions::default(),
         )
         .await?;
+    pushed.insert(result.content.id);
     Ok(())
 }
 
-async fn insert_stop_time<D: Database>(
+/// Maximum number of stop times inserted at once.
+const STOP_TIME_CHUNK_SIZE: usize = 1000;
+
+/// Rows of stop times of a trip, along with the original id of the trip.
+type StopTimeChunk = (String, Vec<(csv::StringRecord, StopTime)>);
+
+/// What happens to rows of stop times, whose trip does not exist (yet).
+enum MissingTrip<'a> {
+    /// Kept for a second pass.
+    Retry(&'a mut RetryBuffer),
+    /// Counted as unresolved references for the given reason.
+    Unresolved(&'static str),
+}
+
+/// Inserts stop times in chunks of consecutive rows of the same trip.
+async fn insert_stop_time_rows<D: Database>(
     client: &Client<D>,
-    stop_time: Result<StopTime, csv::Error>,
-) -> Result<(), RequestError> {
-    let stop_time = stop_time.map_err(RequestError::other)?;
+    records: impl Iterator<Item = Result<csv::StringRecord, csv::Error>>,
+    headers: &csv::StringRecord,
+    replaced: &mut HashSet<Id<model::trip::Trip>>,
+    mut missing: MissingTrip<'_>,
+    report: &mut GtfsReport,
+    progress: &mut Progress,
+) -> Result<(), csv::Error> {
+    let mut chunk: Option<StopTimeChunk> = None;
+    let mut records = records.peekable();
+    while records.peek().is_some() || chunk.is_some() {
+        let row = records.next().map(|record| {
+            record.and_then(|record| {
+                let stop_time: StopTime = record.deserialize(Some(headers))?;
+                Ok((record, stop_time))
+            })
+        });
+        let row = match row {
+            Some(Ok(row)) => Some(row),
+            Some(Err(_)) => {
+                report.skipped_stop_times += 1;
+                continue;
+            }
+            None => None,
+        };
+        // stop times of a trip are usually consecutive, but need not be.
+        let complete = chunk.take_if(|(chunk_id, rows)| match &row {
+            Some((_, stop_time)) => {
+                *chunk_id != stop_time.trip_id.raw_ref::<str>()
+                    || rows.len() >= STOP_TIME_CHUNK_SIZE
+            }
+            None => true,
+        });
+        if let Some(complete) = complete {
+            match insert_stop_times(client, complete, replaced).await {
+                Ok(skipped) => report.skipped_stop_times += skipped,
+                Err(rows) => match &mut missing {
+                    MissingTrip::Retry(retries) => {
+                        for row in rows {
+                            retries.push(row)?;
+                        }
+                    }
+                    MissingTrip::Unresolved(reason) => {
+                        report.skipped_stop_times += rows.len();
+                        for _ in rows {
+                            report.count_unresolved(reason, &RequestError::IdMissing);
+                        }
+                    }
+                },
+            }
+        }
+        if let Some((record, stop_time)) = row {
+            chunk
+                .get_or_insert_with(|| (stop_time.trip_id.raw(), vec![]))
+                .1
+                .push((record, stop_time));
+            progress.inc();
+        }
+    }
+    Ok(())
+}
+
+/// Inserts a chunk of stop times of a trip. Stop times of previous imports are
+/// replaced, when the first chunk of a trip is inserted. Returns the number of
+/// skipped stop times, or the rows of the chunk, if the trip does not exist.
+async fn insert_stop_times<D: Database>(
+    client: &Client<D>,
+    (original_trip_id, rows): StopTimeChunk,
+    replaced: &mut HashSet<Id<model::trip::Trip>>,
+) -> Result<usize, Vec<csv::StringRecord>> {
+    let trip_id = match client.get_trip_id_by_original_id(original_trip_id).await {
+        Ok(Some(trip_id)) => trip_id,
+        Ok(None) => return Err(rows.into_iter().map(|(record, _)| record).collect()),
+        Err(why) => {
+            log::warn!("could not look up trip of stop times: {:?}", why);
+            return Ok(rows.len());
+        }
+    };
+    let mut skipped = 0;
+    let mut stop_times = Vec::with_capacity(rows.len());
+    for (_, stop_time) in rows {
+        match convert_stop_time(client, stop_time).await {
+            Ok(stop_time) => stop_times.push(stop_time),
+            Err(_) => skipped += 1,
+        }
+    }
+    let count = stop_times.len();
+    let replace = replaced.insert(trip_id.clone());
+    if let Err(why) = client.push_stop_times(&trip_id, stop_times, replace).await {
+        log::warn!("could not insert stop times: {:?}", why);
+        skipped += count;
+    }
+    Ok(skipped)
+}
+
+async fn convert_stop_time<D: Database>(
+    client: &Client<D>,
+    stop_time: StopTime,
+) -> Result<model::trip::StopTime, RequestError> {
     let stop_id = if let Some(orignal_stop_id) = stop_time.stop_id {
         client
             .get_stop_id_by_original_id(orignal_stop_id.raw())
@@ -447,21 +1336,215 @@ async fn insert_stop_time<D: Database>(
     } else {
         None
     };
+    // gtfs flex: on-demand service within an area instead of at a stop.
+    let area_reference = match (stop_time.location_group_id, stop_time.location_id) {
+        (Some(id), _) => Some(AreaReference {
+            id,
+            kind: AreaKind::LocationGroup,
+        }),
+        (None, Some(id)) => Some(AreaReference {
+            id,
+            kind: AreaKind::Location,
+        }),
+        (None, None) => None,
+    };
+    Ok(model::trip::StopTime {
+        stop_sequence: stop_time.stop_sequence as i32,
+        stop_id,
+        arrival_time: stop_time.arrival_time,
+        departure_time: stop_time.departure_time,
+        stop_headsign: stop_time.stop_headsign,
+        pickup_type: Some(stop_time.pickup_type.to_model()),
+        drop_off_type: Some(stop_time.drop_off_type.to_model()),
+        area_reference,
+        stop_name: None,
+    })
+}
+
+async fn insert_frequency<D: Database>(
+    client: &Client<D>,
+    frequency: Result<Frequency, csv::Error>,
+) -> Result<(), RequestError> {
+    let frequency = frequency.map_err(RequestError::other)?;
     let trip_id = client
-        .get_trip_id_by_original_id(stop_time.trip_id.raw())
+        .get_trip_id_by_original_id(frequency.trip_id.raw())
         .await?
         .ok_or(RequestError::IdMissing)?;
-    client
-        .push_stop_time(
-            trip_id,
-            model::trip::StopTime {
-                stop_sequence: stop_time.stop_sequence as i32,
-                stop_id,
-                arrival_time: stop_time.arrival_time,
-                departure_time: stop_time.departure_time,
-                stop_headsign: stop_time.stop_headsign,
-            },
-        )
-        .await?;
-    Ok(())
+    client.put_frequency(&trip_id, frequency.to_model()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(origin: &str) -> ScheduleFeed {
+        ScheduleFeed {
+            origin: origin.to_owned(),
+            priority: 0,
+            url: format!("https://example.com/{}.zip", origin),
+            delimiter: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn failing_feeds_are_reported_without_stopping_the_others() {
+        let feeds = vec![feed("a"), feed("broken"), feed("c")];
+        let mut imported = Vec::new();
+        let failures = import_feeds(&feeds, |feed| {
+            imported.push(feed.origin.clone());
+            async move {
+                match feed.origin.as_str() {
+                    "broken" => Err("download failed".into()),
+                    _ => Ok(()),
+                }
+            }
+        })
+        .await;
+        assert_eq!(imported, vec!["a", "broken", "c"]);
+        assert_eq!(
+            failures,
+            BTreeMap::from([("broken".to_owned(), "download failed".to_owned())])
+        );
+        let collector = MultiScheduleCollector::new();
+        let state = MultiScheduleCollectorState { feeds, failures };
+        assert_eq!(
+            collector.partial_failure(&state).as_deref(),
+            Some("1 gtfs feed(s) failed: 'broken': download failed")
+        );
+    }
+
+    #[test]
+    fn runs_without_failures_succeed() {
+        let collector = MultiScheduleCollector::new();
+        let state: MultiScheduleCollectorState =
+            serde_json::from_str(r#"{"feeds": []}"#).expect("state without failures");
+        assert_eq!(collector.partial_failure(&state), None);
+    }
+
+    /// Whether the collector starts from the stored state, which may be corrupted.
+    fn starts<C>(state: &str) -> bool
+    where
+        C: Collector<Error = Box<dyn Error + Send + Sync>>,
+    {
+        serde_json::from_str(state)
+            .map_err(Box::from)
+            .and_then(C::from_state)
+            .is_ok()
+    }
+
+    #[test]
+    fn corrupted_states_fail_to_start() {
+        let realtime = [
+            (
+                r#"{"url": "rt.pb", "updateInterval": {"secs": 60, "nanos": 0}}"#,
+                true,
+            ),
+            (
+                r#"{"url": "rt.pb", "updateInterval": {"secs": 0, "nanos": 0}}"#,
+                false,
+            ),
+            (r#"{"url": "rt.pb", "updateInterval": 60}"#, false),
+            (r#"{"updateInterval": {"secs": 60, "nanos": 0}}"#, false),
+        ];
+        for (i, (state, expected)) in realtime.into_iter().enumerate() {
+            assert_eq!(
+                starts::<RealtimeCollector>(state),
+                expected,
+                "realtime state of case {}",
+                i
+            );
+        }
+        let schedule = [
+            (r#"{"url": "gtfs.zip"}"#, true),
+            (r#"{"url": "gtfs.zip", "delimiter": ";;"}"#, false),
+            (r#"{"url": 42}"#, false),
+            ("{}", false),
+        ];
+        for (i, (state, expected)) in schedule.into_iter().enumerate() {
+            assert_eq!(
+                starts::<ScheduleCollector>(state),
+                expected,
+                "schedule state of case {}",
+                i
+            );
+        }
+        let multi_schedule = [
+            (r#"{"feeds": []}"#, true),
+            (r#"{"feeds": {}}"#, false),
+            (r#"{"feeds": [{"origin": "a"}]}"#, false),
+            ("null", false),
+        ];
+        for (i, (state, expected)) in multi_schedule.into_iter().enumerate() {
+            assert_eq!(
+                starts::<MultiScheduleCollector>(state),
+                expected,
+                "multi schedule state of case {}",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn stations_are_the_topmost_parents_of_stops() {
+        let parents = [
+            ("boarding-area", "platform"),
+            ("platform", "station"),
+            ("entrance", "station"),
+            ("a", "b"),
+            ("b", "a"),
+        ];
+        let stop_locations = parents
+            .into_iter()
+            .map(|(stop, parent)| {
+                let location = StopLocation {
+                    parent_station: Some(parent.to_owned()),
+                    level_id: None,
+                };
+                (stop.to_owned(), location)
+            })
+            .collect::<HashMap<_, _>>();
+        let cases = [
+            ("boarding-area", Some("station")),
+            ("platform", Some("station")),
+            ("entrance", Some("station")),
+            ("station", None),
+            ("unknown", None),
+            // cycles end after the maximum depth.
+            ("a", Some("a")),
+        ];
+        for (stop, expected) in cases {
+            assert_eq!(
+                station_of(stop, &stop_locations).as_deref(),
+                expected,
+                "station of {}",
+                stop
+            );
+        }
+    }
+
+    #[test]
+    fn extended_rail_route_types_are_split_by_service_level() {
+        let cases = [
+            (0, LineType::TramStreetcarOrLighrail),
+            (2, LineType::Rail),
+            (3, LineType::Bus),
+            (100, LineType::Rail),
+            (101, LineType::LongDistanceRail),
+            (102, LineType::LongDistanceRail),
+            (103, LineType::RegionalRail),
+            (105, LineType::LongDistanceRail),
+            (106, LineType::RegionalRail),
+            (109, LineType::SuburbanRail),
+        ];
+        for (index, (route_type, expected)) in cases.into_iter().enumerate() {
+            let kind: RouteType = serde_json::from_value(route_type.into())
+                .expect("route type is known");
+            assert_eq!(
+                line_type_of_route_type(&kind),
+                expected,
+                "line type of case {}",
+                index
+            );
+        }
+    }
 }
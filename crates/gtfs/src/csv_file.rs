@@ -0,0 +1,192 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    str::FromStr,
+};
+
+use csv::{Reader, ReaderBuilder, StringRecord, Trim};
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Delimiter of gtfs files, if not specified otherwise.
+pub const DEFAULT_DELIMITER: u8 = b',';
+
+/// Opens a csv file of a gtfs feed.
+///
+/// A leading UTF-8 BOM is removed, as it would otherwise become part of the first
+/// header (often the id), which then can not be deserialized. Rows with a
+/// differing number of fields and whitespace around headers are tolerated.
+/// Missing `required` headers are logged, as they cause every row to be skipped.
+pub fn open_csv(
+    path: &Path,
+    delimiter: u8,
+    required: &[&str],
+) -> Result<Reader<BufReader<File>>, Box<dyn Error + Send + Sync>> {
+    let mut file = BufReader::new(File::open(path)?);
+    if file.fill_buf()?.starts_with(UTF8_BOM) {
+        file.consume(UTF8_BOM.len());
+    }
+    let mut reader = ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .trim(Trim::Headers)
+        .from_reader(file);
+    let headers = reader.headers()?;
+    let missing = required
+        .iter()
+        .filter(|header| !headers.iter().any(|present| present == **header))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        log::warn!(
+            "{}: missing headers {:?}, found {:?}. check the delimiter and encoding.",
+            path.display(),
+            missing,
+            headers
+        );
+    }
+    Ok(reader)
+}
+
+/// Whether `column` of a record holds a value, which can not be parsed.
+///
+/// Errors of custom deserializers do not tell the field they occurred in, so the
+/// raw value is checked instead.
+pub fn is_invalid_field<T: FromStr>(
+    record: &StringRecord,
+    headers: &StringRecord,
+    column: &str,
+) -> bool {
+    headers
+        .iter()
+        .position(|header| header == column)
+        .and_then(|index| record.get(index))
+        .map(str::trim)
+        .is_some_and(|value| !value.is_empty() && value.parse::<T>().is_err())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf, process};
+
+    use crate::data_model::{agency::Agency, stops::Stop, Timezone};
+
+    use super::*;
+
+    /// Removed, when dropped.
+    struct TempFile {
+        path: PathBuf,
+    }
+
+    impl TempFile {
+        fn new(name: &str, content: &[u8]) -> Self {
+            let path =
+                env::temp_dir().join(format!("gtfs-{}-{}", process::id(), name));
+            fs::write(&path, content).expect("file is written");
+            Self { path }
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    /// Stops with a BOM, spaces around the headers, quoted names and a row
+    /// without the trailing optional fields.
+    const STOPS: &[u8] =
+        b"\xef\xbb\xbfstop_id, stop_name ,stop_lat,stop_lon,platform_code
+kiel-hbf,\"Kiel, Hauptbahnhof\",54.3147,10.1322,1
+kiel-zob,\"Kiel \"\"ZOB\"\"\",54.3155,10.1310
+";
+
+    #[test]
+    fn stops_with_a_bom_are_read() {
+        let file = TempFile::new("stops-bom.txt", STOPS);
+
+        // without trimming the headers, the name is not found.
+        let plain = csv::Reader::from_path(&file.path)
+            .expect("file is read")
+            .headers()
+            .expect("headers are read")
+            .clone();
+        assert_eq!(plain.get(1), Some(" stop_name "));
+
+        let mut reader =
+            open_csv(&file.path, DEFAULT_DELIMITER, &["stop_id", "stop_name"])
+                .expect("file is read");
+        let headers = reader.headers().expect("headers are read").clone();
+        assert_eq!(headers.get(0), Some("stop_id"));
+        let stops = reader
+            .deserialize::<Stop>()
+            .map(|row| {
+                let stop = row.expect("row is deserialized");
+                (stop.id.raw(), stop.name, stop.platform_code)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stops,
+            [
+                (
+                    "kiel-hbf".to_owned(),
+                    Some("Kiel, Hauptbahnhof".to_owned()),
+                    Some("1".to_owned())
+                ),
+                ("kiel-zob".to_owned(), Some("Kiel \"ZOB\"".to_owned()), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn rows_are_read_with_another_delimiter() {
+        let file = TempFile::new(
+            "stops-semicolon.txt",
+            b"stop_id;stop_name\nkiel-hbf;Kiel Hbf\n",
+        );
+        let mut reader =
+            open_csv(&file.path, b';', &["stop_id"]).expect("file is read");
+        let stops = reader
+            .deserialize::<Stop>()
+            .map(|row| row.expect("row is deserialized").id.raw())
+            .collect::<Vec<_>>();
+        assert_eq!(stops, ["kiel-hbf"]);
+    }
+
+    #[test]
+    fn agencies_with_an_unknown_timezone_are_rejected() {
+        let file = TempFile::new(
+            "agency-timezone.txt",
+            b"agency_id,agency_name,agency_url,agency_timezone
+kvg,KVG,https://kvg-kiel.de,Europe/Berlin
+sh,SH,https://nah.sh,Europe/Berln
+",
+        );
+        let mut reader =
+            open_csv(&file.path, DEFAULT_DELIMITER, &["agency_timezone"])
+                .expect("file is read");
+        let headers = reader.headers().expect("headers are read").clone();
+        let records = reader
+            .records()
+            .map(|record| record.expect("record is read"))
+            .collect::<Vec<_>>();
+
+        let is_invalid = |record: &StringRecord, column: &str| {
+            is_invalid_field::<Timezone>(record, &headers, column)
+        };
+
+        let agency = records[0]
+            .deserialize::<Agency>(Some(&headers))
+            .expect("agency is deserialized");
+        assert_eq!(agency.timezone.name(), "Europe/Berlin");
+        assert!(!is_invalid(&records[0], "agency_timezone"));
+
+        assert!(records[1].deserialize::<Agency>(Some(&headers)).is_err());
+        assert!(is_invalid(&records[1], "agency_timezone"));
+        // missing columns and empty values are not invalid.
+        assert!(!is_invalid(&records[1], "stop_timezone"));
+        let empty = StringRecord::from(vec!["sh", "SH", "https://nah.sh", " "]);
+        assert!(!is_invalid(&empty, "agency_timezone"));
+    }
+}
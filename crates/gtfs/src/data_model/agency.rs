@@ -36,7 +36,7 @@ pub struct Agency {
     /// Timezone where the transit agency is located. If multiple agencies are
     /// specified in the dataset, each must have the same `agency_timezone`.
     #[serde(rename = "agency_timezone")]
-    pub timezone: Timezone, // TODO Type: Timezone
+    pub timezone: Timezone,
 
     /// Primary language used by this transit agency. Should be provided to help GTFS
     /// consumers choose capitalization rules and other language-specific settings for
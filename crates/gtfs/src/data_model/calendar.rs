@@ -1,34 +1,10 @@
 use serde::{Deserialize, Serialize};
-use serde_repr::{Deserialize_repr, Serialize_repr};
 use utility::{
     id::{HasId, Id},
     serde::date_time::deserialize_yyyymmdd,
 };
 
-/// Indicates whether the service operates. Note that exceptions for particular dates
-/// may be listed in calendar_dates.txt.
-/// See <https://gtfs.org/schedule/reference/#calendartxt>
-#[derive(Serialize_repr, Deserialize_repr, PartialEq, Debug, Clone)]
-#[repr(u8)]
-pub enum ServiceAvailability {
-    Unavailable = 0,
-    Available = 1,
-}
-
-impl ServiceAvailability {
-    pub fn is_available(&self) -> bool {
-        matches!(self, Self::Available)
-    }
-}
-
-impl Into<model::calendar::ServiceAvailability> for ServiceAvailability {
-    fn into(self) -> model::calendar::ServiceAvailability {
-        match self {
-            Self::Available => model::calendar::ServiceAvailability::Available,
-            Self::Unavailable => model::calendar::ServiceAvailability::Unavailable,
-        }
-    }
-}
+use crate::serde::int_bool;
 
 /// Service dates specified using a weekly schedule with start and end dates.
 ///
@@ -47,29 +23,34 @@ pub struct CalendarRow {
     /// specified by the start_date and end_date fields. Note that exceptions for
     /// particular dates may be listed in calendar_dates.txt. Valid options are:
     ///
-    /// `ServiceAvailability::Available` - Service is available for all Mondays in the
-    ///                                    date range.
-    /// `ServiceAvailability::Unavailable` - Service is not available for Mondays in
-    ///                                      the date range.
-    pub monday: ServiceAvailability,
+    /// `1` - Service is available for all Mondays in the date range.
+    /// `0` - Service is not available for Mondays in the date range.
+    #[serde(deserialize_with = "int_bool")]
+    pub monday: bool,
 
     /// Functions in the same way as `monday` except applies to Tuesdays.
-    pub tuesday: ServiceAvailability,
+    #[serde(deserialize_with = "int_bool")]
+    pub tuesday: bool,
 
     /// Functions in the same way as `monday` except applies to Wednesday.
-    pub wednesday: ServiceAvailability,
+    #[serde(deserialize_with = "int_bool")]
+    pub wednesday: bool,
 
     /// Functions in the same way as `monday` except applies to Thursday
-    pub thursday: ServiceAvailability,
+    #[serde(deserialize_with = "int_bool")]
+    pub thursday: bool,
 
     /// Functions in the same way as `monday` except applies to Friday.
-    pub friday: ServiceAvailability,
+    #[serde(deserialize_with = "int_bool")]
+    pub friday: bool,
 
     /// Functions in the same way as `monday` except applies to Saturday.
-    pub saturday: ServiceAvailability,
+    #[serde(deserialize_with = "int_bool")]
+    pub saturday: bool,
 
     /// Functions in the same way as `monday` except applies to Sunday.
-    pub sunday: ServiceAvailability,
+    #[serde(deserialize_with = "int_bool")]
+    pub sunday: bool,
 
     /// Start service day for the service interval.
     #[serde(deserialize_with = "deserialize_yyyymmdd")]
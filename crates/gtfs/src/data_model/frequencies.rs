@@ -1,11 +1,17 @@
-use serde::{Deserialize, Serialize};
+use chrono::Duration;
+use serde::Deserialize;
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use utility::serde::duration;
 
-use super::{trips::TripId, Time};
+use crate::serde::default_if_empty;
+
+use super::trips::TripId;
 
 /// Indicates the type of service for a trip. See the file description for more
 /// information.
 /// See <https://gtfs.org/schedule/reference/#frequenciestxt>
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[derive(Serialize_repr, Deserialize_repr, PartialEq, Debug, Clone, Default)]
+#[repr(u8)]
 pub enum TypeOfTripService {
     /// Frequency-based trips.
     #[default]
@@ -34,7 +40,7 @@ pub enum TypeOfTripService {
 ///   In schedule-based service operators try to strictly adhere to a schedule.
 ///
 /// See <https://gtfs.org/schedule/reference/#frequenciestxt>
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Frequency {
     /// Foreign ID referncing `trips.trip_id`.
     /// Identifies a trip to which the specified headway of service applies.
@@ -42,11 +48,13 @@ pub struct Frequency {
 
     /// Time at which the first vehicle departs from the first stop of the trip with
     /// the specified headway.
-    pub start_time: Time,
+    #[serde(deserialize_with = "duration::deserialize")]
+    pub start_time: Duration,
 
     /// Time at which service changes to a different headway (or ceases) at the first
     /// stop in the trip.
-    pub end_time: Time,
+    #[serde(deserialize_with = "duration::deserialize")]
+    pub end_time: Duration,
 
     /// Time, in seconds, between departures from the same stop (headway) for the
     /// trip, during the time interval specified by `start_time` and `end_time`.
@@ -61,6 +69,17 @@ pub struct Frequency {
     /// information.
     ///
     /// Defaults to: `TypeOfTripService::FrequencyBased`.
-    #[serde(default)]
+    #[serde(default, deserialize_with = "default_if_empty")]
     pub exact_times: TypeOfTripService,
 }
+
+impl Frequency {
+    pub fn to_model(self) -> model::trip::Frequency {
+        model::trip::Frequency {
+            start_time: self.start_time,
+            end_time: self.end_time,
+            headway: Duration::seconds(self.headway_seconds as i64),
+            exact_times: self.exact_times == TypeOfTripService::ScheduleBased,
+        }
+    }
+}
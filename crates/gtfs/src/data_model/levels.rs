@@ -0,0 +1,40 @@
+use serde::Deserialize;
+use utility::id::Id;
+
+use super::IdString;
+
+pub type LevelId = IdString;
+
+/// Levels in a station. Useful in conjunction with `pathways.txt`.
+///
+/// Primary key `(level_id)`
+///
+/// See <https://gtfs.org/schedule/reference/#levelstxt>
+#[derive(Debug, Clone, Deserialize)]
+pub struct Level {
+    /// Identifies a level in a station.
+    pub level_id: LevelId,
+
+    /// Numeric index of the level that indicates its relative position.
+    ///
+    /// Ground level should have index `0`, with levels above ground indicated by
+    /// positive indices and levels below ground by negative indices.
+    pub level_index: f64,
+
+    /// Name of the level as seen by the rider inside the building or station.
+    ///
+    /// Example: Take the elevator to "Mezzanine" or "Platforms" or "-1".
+    pub level_name: Option<String>,
+}
+
+impl Level {
+    pub fn to_model(self) -> model::WithId<model::pathway::Level> {
+        model::WithId::new(
+            Id::new(self.level_id),
+            model::pathway::Level {
+                index: self.level_index,
+                name: self.level_name,
+            },
+        )
+    }
+}
@@ -6,6 +6,8 @@ pub mod agency;
 pub mod calendar;
 pub mod calendar_dates;
 pub mod frequencies;
+pub mod levels;
+pub mod pathways;
 pub mod routes;
 pub mod shapes;
 pub mod stop_times;
@@ -165,11 +167,12 @@ pub type Time = String;
 /// TZ timezone from the https://www.iana.org/time-zones. Timezone names never contain
 /// the space character but may contain an underscore.
 /// Refer to http://en.wikipedia.org/wiki/List_of_tz_zones for a list of valid values.
+/// Unknown names are rejected when deserializing.
 ///
 /// # Examples
 ///
 /// `Asia/Tokyo`, `America/Los_Angeles` or `Africa/Cairo`.
-pub type Timezone = String;
+pub type Timezone = utility::tz::Tz;
 
 /// A fully qualified URL that includes http:// or https://, and any special
 /// characters in the URL must be correctly escaped.
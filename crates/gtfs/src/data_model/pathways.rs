@@ -0,0 +1,152 @@
+use chrono::Duration;
+use serde::Deserialize;
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use utility::id::Id;
+
+use crate::serde::int_bool;
+
+use super::{levels::LevelId, stops::StopId, IdString};
+
+pub type PathwayId = IdString;
+
+/// Type of pathway between the specified `(from_stop_id, to_stop_id)` pair.
+/// See <https://gtfs.org/schedule/reference/#pathwaystxt>
+#[derive(Serialize_repr, Deserialize_repr, PartialEq, Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum PathwayMode {
+    Walkway = 1,
+
+    Stairs = 2,
+
+    /// Moving sidewalk or travelator.
+    MovingSidewalk = 3,
+
+    Escalator = 4,
+
+    Elevator = 5,
+
+    /// A pathway that crosses into an area of the station where proof of payment
+    /// is required to cross. Fare gates may separate paid areas of the station
+    /// from unpaid ones, or separate different payment areas within the same
+    /// station from each other.
+    FareGate = 6,
+
+    /// A pathway exiting a paid area into an unpaid area where proof of payment is
+    /// not required to cross.
+    ExitGate = 7,
+}
+
+impl PathwayMode {
+    pub fn to_model(self) -> model::pathway::PathwayMode {
+        use model::pathway::PathwayMode as Mode;
+        match self {
+            Self::Walkway => Mode::Walkway,
+            Self::Stairs => Mode::Stairs,
+            Self::MovingSidewalk => Mode::MovingSidewalk,
+            Self::Escalator => Mode::Escalator,
+            Self::Elevator => Mode::Elevator,
+            Self::FareGate => Mode::FareGate,
+            Self::ExitGate => Mode::ExitGate,
+        }
+    }
+}
+
+/// Pathways linking together locations within stations.
+///
+/// Primary key `(pathway_id)`
+///
+/// Pathways are optional for feeds. If present, stations should be modeled
+/// completely, i.e. every platform, entrance and generic node of a station
+/// must be reachable through pathways.
+///
+/// See <https://gtfs.org/schedule/reference/#pathwaystxt>
+#[derive(Debug, Clone, Deserialize)]
+pub struct Pathway {
+    /// Identifies a pathway. Used by systems as an internal identifier for the
+    /// record. Must be unique in the dataset.
+    pub pathway_id: PathwayId,
+
+    /// Foreign ID referencing `stops.stop_id`.
+    /// Location at which the pathway begins.
+    pub from_stop_id: StopId,
+
+    /// Foreign ID referencing `stops.stop_id`.
+    /// Location at which the pathway ends.
+    pub to_stop_id: StopId,
+
+    /// Type of pathway between the specified `(from_stop_id, to_stop_id)` pair.
+    pub pathway_mode: PathwayMode,
+
+    /// Indicates the direction that the pathway can be taken. Exit gates must not
+    /// be bidirectional.
+    #[serde(deserialize_with = "int_bool")]
+    pub is_bidirectional: bool,
+
+    /// Horizontal length in meters of the pathway from the origin location
+    /// (defined in `from_stop_id`) to the destination location (defined in
+    /// `to_stop_id`).
+    pub length: Option<f64>,
+
+    /// Average time in seconds needed to walk through the pathway from the origin
+    /// location (defined in `from_stop_id`) to the destination location (defined
+    /// in `to_stop_id`).
+    pub traversal_time: Option<u32>,
+
+    /// Number of stairs of the pathway. A positive count implies that the rider
+    /// walks up from `from_stop_id` to `to_stop_id`, a negative one that the rider
+    /// walks down.
+    pub stair_count: Option<i32>,
+
+    /// Maximum slope ratio of the pathway. Positive for going up from
+    /// `from_stop_id` to `to_stop_id`, negative for going down.
+    pub max_slope: Option<f64>,
+
+    /// Minimum width of the pathway in meters.
+    pub min_width: Option<f64>,
+
+    /// Public facing text from physical signage that is visible to riders.
+    pub signposted_as: Option<String>,
+
+    /// Same as `signposted_as`, but when the pathway is used from the
+    /// `to_stop_id` to the `from_stop_id`.
+    pub reversed_signposted_as: Option<String>,
+}
+
+/// Resolved references of a pathway. Stop ids are those of the database, level
+/// ids those of the feed.
+pub struct PathwayReferences {
+    pub from_stop_id: Id<model::stop::Stop>,
+    pub to_stop_id: Id<model::stop::Stop>,
+    pub station_id: Option<Id<model::stop::Stop>>,
+    pub from_level_id: Option<LevelId>,
+    pub to_level_id: Option<LevelId>,
+}
+
+impl Pathway {
+    pub fn to_model(
+        self,
+        references: PathwayReferences,
+    ) -> model::WithId<model::pathway::Pathway> {
+        model::WithId::new(
+            Id::new(self.pathway_id),
+            model::pathway::Pathway {
+                from_stop_id: references.from_stop_id,
+                to_stop_id: references.to_stop_id,
+                station_id: references.station_id,
+                from_level_id: references.from_level_id.map(Id::new),
+                to_level_id: references.to_level_id.map(Id::new),
+                mode: self.pathway_mode.to_model(),
+                is_bidirectional: self.is_bidirectional,
+                length: self.length,
+                traversal_time: self
+                    .traversal_time
+                    .map(|seconds| Duration::seconds(seconds as i64)),
+                stair_count: self.stair_count,
+                max_slope: self.max_slope,
+                min_width: self.min_width,
+                signposted_as: self.signposted_as,
+                reversed_signposted_as: self.reversed_signposted_as,
+            },
+        )
+    }
+}
@@ -44,6 +44,28 @@ pub enum RouteType {
 
     /// Monorail. Railway in which the track consists of a single rail or a beam.
     Monorail = 12,
+
+    /// Railway service of unspecified service level (extended route type).
+    /// See <https://developers.google.com/transit/gtfs/reference/extended-route-types>
+    RailwayService = 100,
+
+    /// High speed rail service, e.g. ICE or TGV (extended route type).
+    HighSpeedRail = 101,
+
+    /// Long distance trains, e.g. IC or EC (extended route type).
+    LongDistanceTrains = 102,
+
+    /// Inter regional rail service, e.g. IRE (extended route type).
+    InterRegionalRail = 103,
+
+    /// Sleeper rail service (extended route type).
+    SleeperRail = 105,
+
+    /// Regional rail service, e.g. RE or RB (extended route type).
+    RegionalRail = 106,
+
+    /// Suburban railway, e.g. S-Bahn (extended route type).
+    SuburbanRailway = 109,
 }
 
 impl RouteType {
@@ -82,9 +104,31 @@ impl RouteType {
                 "Monorail. \
                  A Railway in which the track consists of a single rail or beam."
             }
+            Self::RailwayService => "Railway Service.",
+            Self::HighSpeedRail => "High Speed Rail Service.",
+            Self::LongDistanceTrains => "Long Distance Trains.",
+            Self::InterRegionalRail => "Inter Regional Rail Service.",
+            Self::SleeperRail => "Sleeper Rail Service.",
+            Self::RegionalRail => "Regional Rail Service.",
+            Self::SuburbanRailway => "Suburban Railway.",
         }
         .to_owned()
     }
+
+    /// Whether the route is any kind of rail, basic or extended.
+    pub fn is_rail(&self) -> bool {
+        matches!(
+            self,
+            Self::Rail
+                | Self::RailwayService
+                | Self::HighSpeedRail
+                | Self::LongDistanceTrains
+                | Self::InterRegionalRail
+                | Self::SleeperRail
+                | Self::RegionalRail
+                | Self::SuburbanRailway
+        )
+    }
 }
 
 /// Indicates that the rider can board the transit vehicle at any point along the
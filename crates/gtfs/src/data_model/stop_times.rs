@@ -1,5 +1,6 @@
-use crate::serde::default_if_empty;
+use crate::serde::{default_if_empty, default_true, int_bool_or_true};
 use chrono::Duration;
+use model::trip::PickupDropOffType;
 use serde::Deserialize;
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use utility::id::Id;
@@ -35,15 +36,16 @@ pub enum PickupMethod {
 
 impl PickupMethod {
     pub fn display_text(self) -> String {
+        self.to_model().pickup_display_text()
+    }
+
+    pub fn to_model(self) -> PickupDropOffType {
         match self {
-            Self::RegularlyScheduled => "Regularly scheduled pickup.",
-            Self::NotAvailable => "No pickup available.",
-            Self::MustPhoneAgency => "Must phone agency to arrange pickup.",
-            Self::MustCoordinateWithDriver => {
-                "Must coordinate with driver to arrange pickup."
-            }
+            Self::RegularlyScheduled => PickupDropOffType::Regular,
+            Self::NotAvailable => PickupDropOffType::NotAvailable,
+            Self::MustPhoneAgency => PickupDropOffType::PhoneAgency,
+            Self::MustCoordinateWithDriver => PickupDropOffType::CoordinateWithDriver,
         }
-        .to_owned()
     }
 }
 
@@ -68,15 +70,16 @@ pub enum DropOffMethod {
 
 impl DropOffMethod {
     pub fn display_text(self) -> String {
+        self.to_model().drop_off_display_text()
+    }
+
+    pub fn to_model(self) -> PickupDropOffType {
         match self {
-            Self::RegularlyScheduled => "Regularly scheduled drop off.",
-            Self::NotAvailable => "No drop off available.",
-            Self::MustPhoneAgency => "Must phone agency to arrange drop off.",
-            Self::MustCoordinateWithDriver => {
-                "Must coordinate with driver to arrange drop off."
-            }
+            Self::RegularlyScheduled => PickupDropOffType::Regular,
+            Self::NotAvailable => PickupDropOffType::NotAvailable,
+            Self::MustPhoneAgency => PickupDropOffType::PhoneAgency,
+            Self::MustCoordinateWithDriver => PickupDropOffType::CoordinateWithDriver,
         }
-        .to_owned()
     }
 }
 
@@ -323,6 +326,16 @@ pub struct StopTime {
     ///
     /// Recommended when `drop_off_type=2`.
     pub drop_off_booking_rule_id: Option<IdString>,
+
+    /// Indicates if arrival and departure times for a stop are strictly adhered to
+    /// by the vehicle or if they are instead approximate and/or interpolated times.
+    ///
+    /// `0` - Times are considered approximate.
+    /// `1` - Times are considered exact.
+    ///
+    /// Defaults to: `1`.
+    #[serde(default = "default_true", deserialize_with = "int_bool_or_true")]
+    pub timepoint: bool,
 }
 
 impl WithPrimaryKey<StopTimeKey> for StopTime {
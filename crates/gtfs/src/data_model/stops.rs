@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use utility::id::{HasId, Id};
 
-use crate::database::WithPrimaryKey;
+use crate::{database::WithPrimaryKey, serde::default_if_empty};
 
 use super::{IdString, Latitude, Longitude, Timezone, Url};
 
@@ -199,7 +199,8 @@ pub struct Stop {
     pub timezone: Option<Timezone>,
 
     /// Indicates whether wheelchair boardings are possible from the location.
-    #[serde(default)]
+    /// Defaults to: `WheechairBoarding::NoInformationOrInherit`
+    #[serde(default, deserialize_with = "default_if_empty")]
     pub wheelchair_boarding: WheechairBoarding,
 
     /// Foreign ID referencing `levels.level_id`
@@ -1,9 +1,10 @@
+use model::trip::TripDirection;
 use serde::{Deserialize, Serialize};
 
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use utility::id::{HasId, Id};
 
-use crate::database::WithPrimaryKey;
+use crate::{database::WithPrimaryKey, serde::default_if_empty};
 
 use super::{routes::Route, IdString};
 
@@ -27,7 +28,9 @@ pub enum WheelchairAccessibility {
 impl WheelchairAccessibility {
     pub fn display_text(self) -> String {
         match self {
-            Self::NoAccessibilityInformation => "No accessibility information for the trip.",
+            Self::NoAccessibilityInformation => {
+                "No accessibility information for the trip."
+            }
             Self::CanAccommodateAtLeastOneRiderInWheelchair => {
                 "Vehicle being used on this particular trip can accommodate at least \
                  one rider in a wheelchair."
@@ -81,6 +84,15 @@ pub enum TravelDirection {
     TravelInOppositeDirection = 1,
 }
 
+impl TravelDirection {
+    pub fn to_model(self) -> TripDirection {
+        match self {
+            Self::TravelInOneDirection => TripDirection::Outbound,
+            Self::TravelInOppositeDirection => TripDirection::Inbound,
+        }
+    }
+}
+
 pub type TripId = Id<Trip>;
 
 /// Trips for each route. A trip is a sequence of two or more stops that occur during
@@ -154,12 +166,12 @@ pub struct Trip {
 
     /// Indicates wheelchair accessibility.
     /// Defaults to: `WheelchairAccessibility::NoAccessibilityInformation`
-    #[serde(default)]
+    #[serde(default, deserialize_with = "default_if_empty")]
     pub wheelchair_accessible: WheelchairAccessibility,
 
     /// Indicates whether bikes are allowed.
     /// Defaults to: `BikesAllowed::NoBikeInformation`
-    #[serde(default)]
+    #[serde(default, deserialize_with = "default_if_empty")]
     pub bikes_allowed: BikesAllowed,
 }
 
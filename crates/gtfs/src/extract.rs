@@ -0,0 +1,342 @@
+//! Extraction of downloaded feeds. Feeds are untrusted, so entries escaping the
+//! destination and symlinks are rejected, and the extracted size is limited to
+//! keep zip bombs from filling the disk.
+
+use std::{
+    env, error, fmt,
+    fs::{self, File},
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use zip::{result::ZipError, ZipArchive};
+
+const DEFAULT_MAX_TOTAL_BYTES: u64 = 16 * 1024 * 1024 * 1024;
+const DEFAULT_MAX_FILE_BYTES: u64 = 8 * 1024 * 1024 * 1024;
+const DEFAULT_MAX_FILES: usize = 1_000;
+
+/// Limits of a single extraction.
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractLimits {
+    /// Uncompressed bytes of all files together.
+    pub max_total_bytes: u64,
+    /// Uncompressed bytes of a single file.
+    pub max_file_bytes: u64,
+    /// Number of entries, including directories.
+    pub max_files: usize,
+}
+
+impl ExtractLimits {
+    /// Defaults, overridden by `GTFS_EXTRACT_MAX_TOTAL_BYTES`,
+    /// `GTFS_EXTRACT_MAX_FILE_BYTES` and `GTFS_EXTRACT_MAX_FILES`.
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        }
+        Self {
+            max_total_bytes: var(
+                "GTFS_EXTRACT_MAX_TOTAL_BYTES",
+                DEFAULT_MAX_TOTAL_BYTES,
+            ),
+            max_file_bytes: var(
+                "GTFS_EXTRACT_MAX_FILE_BYTES",
+                DEFAULT_MAX_FILE_BYTES,
+            ),
+            max_files: var("GTFS_EXTRACT_MAX_FILES", DEFAULT_MAX_FILES),
+        }
+    }
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        Self {
+            max_total_bytes: DEFAULT_MAX_TOTAL_BYTES,
+            max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+            max_files: DEFAULT_MAX_FILES,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ExtractError {
+    Io(Arc<io::Error>),
+    Zip(Arc<ZipError>),
+    /// The entry is absolute or escapes the destination.
+    UnsafePath(String),
+    Symlink(String),
+    TooManyFiles {
+        limit: usize,
+    },
+    FileTooLarge {
+        name: String,
+        limit: u64,
+    },
+    TotalTooLarge {
+        limit: u64,
+    },
+}
+
+impl error::Error for ExtractError {}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExtractError::Io(e) => write!(f, "IO error: {}", e),
+            ExtractError::Zip(e) => write!(f, "Zip error: {}", e),
+            ExtractError::UnsafePath(name) => {
+                write!(f, "Entry outside of the destination: {}", name)
+            }
+            ExtractError::Symlink(name) => write!(f, "Symlink entry: {}", name),
+            ExtractError::TooManyFiles { limit } => {
+                write!(f, "More than {} entries", limit)
+            }
+            ExtractError::FileTooLarge { name, limit } => {
+                write!(f, "Entry {} exceeds {} bytes", name, limit)
+            }
+            ExtractError::TotalTooLarge { limit } => {
+                write!(f, "Entries exceed {} bytes in total", limit)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for ExtractError {
+    fn from(e: io::Error) -> Self {
+        ExtractError::Io(Arc::new(e))
+    }
+}
+
+impl From<ZipError> for ExtractError {
+    fn from(e: ZipError) -> Self {
+        ExtractError::Zip(Arc::new(e))
+    }
+}
+
+/// Extracts the archive into `destination`, which is created. Nothing is left in
+/// `destination`, if the extraction fails.
+pub fn extract_zip(
+    archive: &Path,
+    destination: &Path,
+    limits: &ExtractLimits,
+) -> Result<(), ExtractError> {
+    fs::create_dir_all(destination)?;
+    let result = extract_entries(archive, destination, limits);
+    if result.is_err() {
+        let _ = fs::remove_dir_all(destination);
+    }
+    result
+}
+
+fn extract_entries(
+    archive: &Path,
+    destination: &Path,
+    limits: &ExtractLimits,
+) -> Result<(), ExtractError> {
+    let mut archive = ZipArchive::new(File::open(archive)?)?;
+    if archive.len() > limits.max_files {
+        return Err(ExtractError::TooManyFiles {
+            limit: limits.max_files,
+        });
+    }
+
+    let mut total_bytes = 0;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().to_owned();
+        let outpath = destination.join(enclosed_path(&name, entry.enclosed_name())?);
+        if entry.is_symlink() {
+            return Err(ExtractError::Symlink(name));
+        }
+
+        if entry.is_dir() {
+            fs::create_dir_all(&outpath)?;
+            continue;
+        }
+
+        // the declared size is checked upfront, but not trusted while copying.
+        let allowed = limits
+            .max_file_bytes
+            .min(limits.max_total_bytes - total_bytes);
+        let limit_error = |written: u64| {
+            if written > limits.max_file_bytes {
+                ExtractError::FileTooLarge {
+                    name: name.clone(),
+                    limit: limits.max_file_bytes,
+                }
+            } else {
+                ExtractError::TotalTooLarge {
+                    limit: limits.max_total_bytes,
+                }
+            }
+        };
+        if entry.size() > allowed {
+            return Err(limit_error(entry.size()));
+        }
+
+        if let Some(parent) = outpath.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut outfile = File::create(&outpath)?;
+        let written = io::copy(&mut (&mut entry).take(allowed + 1), &mut outfile)?;
+        if written > allowed {
+            return Err(limit_error(written));
+        }
+        total_bytes += written;
+        log::debug!("extracted {} ({} bytes)", outpath.display(), written);
+    }
+
+    Ok(())
+}
+
+/// The path of an entry relative to the destination. Absolute paths and paths
+/// escaping the destination are rejected rather than skipped, as they are never
+/// part of a well-formed feed.
+fn enclosed_path(
+    name: &str,
+    enclosed_name: Option<PathBuf>,
+) -> Result<PathBuf, ExtractError> {
+    let unsafe_path = || ExtractError::UnsafePath(name.to_owned());
+    let is_absolute = name.starts_with(['/', '\\'])
+        || Path::new(name)
+            .components()
+            .any(|component| matches!(component, Component::Prefix(_)));
+    if is_absolute {
+        return Err(unsafe_path());
+    }
+    enclosed_name.ok_or_else(unsafe_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FeedDir;
+    use std::io::Write;
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    /// Writes an archive of the given files into the directory.
+    fn archive(dir: &FeedDir, files: &[(&str, &[u8])]) -> PathBuf {
+        let path = dir.path.join("feed.zip");
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        for (name, content) in files {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap();
+        path
+    }
+
+    fn limits(max_total_bytes: u64, max_file_bytes: u64) -> ExtractLimits {
+        ExtractLimits {
+            max_total_bytes,
+            max_file_bytes,
+            max_files: 10,
+        }
+    }
+
+    #[test]
+    fn extracts_well_formed_feeds() {
+        let dir = FeedDir::new().unwrap();
+        let archive = archive(&dir, &[("stops.txt", b"stop_id\n1\n")]);
+
+        extract_zip(&archive, &dir.path(), &limits(1024, 1024)).unwrap();
+
+        let stops = fs::read_to_string(dir.path().join("stops.txt")).unwrap();
+        assert_eq!(stops, "stop_id\n1\n");
+    }
+
+    #[test]
+    fn rejects_zip_bombs() {
+        // zeros compress to a fraction of their size.
+        let zeros = vec![0; 64 * 1024];
+        let cases = [
+            (limits(1024 * 1024, 1024), "FileTooLarge"),
+            (limits(100 * 1024, 100 * 1024), "TotalTooLarge"),
+        ];
+        for (limits, expected) in cases {
+            let dir = FeedDir::new().unwrap();
+            let archive = archive(&dir, &[("a.txt", &zeros), ("b.txt", &zeros)]);
+            assert!(fs::metadata(&archive).unwrap().len() < 1024);
+
+            let result = extract_zip(&archive, &dir.path(), &limits);
+
+            assert!(
+                matches!(
+                    (&result, expected),
+                    (Err(ExtractError::FileTooLarge { .. }), "FileTooLarge")
+                        | (Err(ExtractError::TotalTooLarge { .. }), "TotalTooLarge")
+                ),
+                "extraction with {:?} results in {:?}",
+                limits,
+                result
+            );
+            assert!(!dir.path().exists(), "partial extraction is removed");
+        }
+    }
+
+    #[test]
+    fn rejects_too_many_files() {
+        let dir = FeedDir::new().unwrap();
+        let files = (0..11).map(|i| format!("{}.txt", i)).collect::<Vec<_>>();
+        let files = files
+            .iter()
+            .map(|name| (name.as_str(), b"" as &[u8]))
+            .collect::<Vec<_>>();
+        let archive = archive(&dir, &files);
+
+        let result = extract_zip(&archive, &dir.path(), &limits(1024, 1024));
+
+        assert!(matches!(
+            result,
+            Err(ExtractError::TooManyFiles { limit: 10 })
+        ));
+    }
+
+    #[test]
+    fn rejects_entries_escaping_the_destination() {
+        for name in [
+            "../escaped.txt",
+            "/absolute.txt",
+            "nested/../../escaped.txt",
+        ] {
+            let dir = FeedDir::new().unwrap();
+            let archive = archive(&dir, &[("stops.txt", b""), (name, b"")]);
+
+            let result = extract_zip(&archive, &dir.path(), &limits(1024, 1024));
+
+            assert!(
+                matches!(&result, Err(ExtractError::UnsafePath(path)) if path == name),
+                "entry `{}` results in {:?}",
+                name,
+                result
+            );
+            assert!(!dir.path.join("escaped.txt").exists());
+            assert!(!dir.path().exists(), "partial extraction is removed");
+        }
+    }
+
+    #[test]
+    fn rejects_symlinks() {
+        let dir = FeedDir::new().unwrap();
+        let path = dir.path.join("feed.zip");
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        writer
+            .add_symlink("stops.txt", "/etc/passwd", SimpleFileOptions::default())
+            .unwrap();
+        writer.finish().unwrap();
+
+        let result = extract_zip(&path, &dir.path(), &limits(1024, 1024));
+
+        assert!(
+            matches!(&result, Err(ExtractError::Symlink(name)) if name == "stops.txt"),
+            "symlink results in {:?}",
+            result
+        );
+        assert!(!dir.path().exists(), "partial extraction is removed");
+    }
+}
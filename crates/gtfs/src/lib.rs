@@ -1,20 +1,28 @@
 use data_model::agency::{Agency, AgencyId};
 use database::{GtfsDatabase, InMemoryPrimaryKeyTable, PrimaryKeyTable};
+use extract::extract_zip;
 use reqwest;
 use reqwest::cookie::Jar;
 use std::fs::{self, File};
-use std::io::{self, copy};
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::{env, process};
 use std::{error::Error, io::Cursor};
 
 pub mod collector;
+pub mod csv_file;
 pub mod data_model;
 pub mod database;
 pub mod domain_model;
+mod extract;
 pub mod realtime;
+mod retry;
 mod serde;
 
+pub use extract::{ExtractError, ExtractLimits};
+
 pub mod sources {
     /// # Deutschland gesamt
     ///
@@ -65,13 +73,88 @@ pub fn open_database() -> Result<GtfsDatabase, Box<dyn Error>> {
     })
 }
 
-pub async fn download_gtfs(url: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let zip_name = "latest.zip";
-    download_file(url, zip_name).await?;
-    extract_zip(zip_name)?;
+/// Distinguishes the temporary directories of concurrent downloads.
+static FEED_DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Temporary directory of a downloaded feed. Removed, when dropped.
+pub struct FeedDir {
+    path: PathBuf,
+}
+
+impl FeedDir {
+    fn new() -> Result<Self, io::Error> {
+        let name = format!(
+            "gtfs-feed-{}-{}",
+            process::id(),
+            FEED_DIR_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = env::temp_dir().join(name);
+        fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+
+    /// The directory of the extracted feed files.
+    pub fn path(&self) -> PathBuf {
+        self.path.join("feed")
+    }
+}
+
+impl Drop for FeedDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Path of a feed on disk, given as `file://` url instead of downloading it,
+/// e.g. `file:///data/feed.zip`.
+pub fn local_path(url: &str) -> Option<&Path> {
+    url.strip_prefix("file://").map(Path::new)
+}
+
+/// Copies the files of an extracted feed, as the feed files are not nested.
+fn copy_feed_files(source: &Path, destination: &Path) -> Result<(), io::Error> {
+    fs::create_dir_all(destination)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            fs::copy(entry.path(), destination.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Extracts the archive on a blocking thread, as large feeds take a while.
+async fn extract_feed(
+    archive: PathBuf,
+    destination: PathBuf,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let limits = ExtractLimits::from_env();
+    tokio::task::spawn_blocking(move || extract_zip(&archive, &destination, &limits))
+        .await??;
     Ok(())
 }
 
+/// Downloads and extracts the feed at `url` into a temporary directory. Feeds
+/// on disk are given as `file://` url, either zipped or already extracted.
+pub async fn download_gtfs(
+    url: &str,
+) -> Result<FeedDir, Box<dyn Error + Send + Sync>> {
+    let dir = FeedDir::new()?;
+    if let Some(path) = local_path(url) {
+        if path.is_dir() {
+            copy_feed_files(path, &dir.path())?;
+        } else {
+            extract_feed(path.to_path_buf(), dir.path()).await?;
+        }
+        return Ok(dir);
+    }
+    let zip_path = dir.path.join("latest.zip");
+    download_file(url, &zip_path.to_string_lossy()).await?;
+    extract_feed(zip_path.clone(), dir.path()).await?;
+    fs::remove_file(&zip_path)?;
+    Ok(dir)
+}
+
 pub async fn download_file(
     url: &str,
     file_name: &str,
@@ -90,54 +173,16 @@ pub async fn download_file(
     Ok(())
 }
 
-fn extract_zip(filename: &str) -> Result<(), io::Error> {
-    let fname = Path::new(filename);
-    let file = File::open(&fname)?;
-    let mut archive = zip::ZipArchive::new(file)?;
-
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
-
-        let outpath = match file.enclosed_name() {
-            Some(path) => path.to_owned(),
-            None => continue, // Skip to the next file if the path is None.
-        };
-
-        let comment = file.comment();
-        if !comment.is_empty() {
-            println!("File {} comment: {}", i, comment); // Print the file comment if it's not empty.
-        }
-
-        if file.name().ends_with('/') {
-            println!("File {} extracted to \"{}\"", i, outpath.display()); // Print a message indicating the directory extraction.
-            fs::create_dir_all(&outpath)?; // Create the directory.
-        } else {
-            println!(
-                "File {} extracted to \"{}\" ({} bytes)",
-                i,
-                outpath.display(),
-                file.size()
-            );
-
-            if let Some(p) = outpath.parent() {
-                if !p.exists() {
-                    fs::create_dir_all(&p)?;
-                }
-            }
-
-            let mut outfile = File::create(&outpath)?;
-            copy(&mut file, &mut outfile)?;
-        }
-
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-
-            if let Some(mode) = file.unix_mode() {
-                fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
-            }
-        }
+/// Checks, whether the resource at `url` is reachable, without downloading it.
+/// Servers not supporting `HEAD` requests are considered reachable.
+pub async fn probe_url(url: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if let Some(path) = local_path(url) {
+        fs::metadata(path)?;
+        return Ok(());
+    }
+    let response = reqwest::Client::new().head(url).send().await?;
+    if response.status() != reqwest::StatusCode::METHOD_NOT_ALLOWED {
+        response.error_for_status()?;
     }
-
     Ok(())
 }
@@ -1,7 +1,15 @@
-use std::{error::Error, fs::File, io::Read};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    error::Error,
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::Read,
+};
 
-use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime, TimeZone};
 use model::{
+    stop::Stop,
+    trip::Trip,
     trip_instance::TripInstance,
     trip_update::{
         StopTimeStatus, StopTimeUpdate, TripStatus, TripUpdate, TripUpdateId,
@@ -10,7 +18,7 @@ use model::{
 };
 use prost::Message;
 use public_transport::{
-    client::{instantiate_trip_naive, Client},
+    client::{instantiate_trips_naive, Client},
     database::Database,
     not_found_to_none, RequestError,
 };
@@ -24,6 +32,28 @@ pub async fn update<D: Database>(
     client: Client<D>,
     url: &str,
 ) -> Result<Vec<WithId<TripUpdate>>, RequestError> {
+    let updates = fetch_updates(&client, url).await?;
+    client.put_trip_updates(updates).await
+}
+
+/// Downloads the feed and converts its trip updates, without writing them.
+pub async fn fetch_updates<D: Database>(
+    client: &Client<D>,
+    url: &str,
+) -> Result<Vec<WithId<TripUpdate>>, RequestError> {
+    let message = fetch_feed(url).await?;
+    convert_updates(client, message.entity).await
+}
+
+/// Downloads and decodes the feed, or reads it from disk if given as `file://`
+/// url.
+pub async fn fetch_feed(url: &str) -> Result<realtime::FeedMessage, RequestError> {
+    if let Some(path) = crate::local_path(url) {
+        let bytes =
+            fs::read(path).map_err(|why| RequestError::Other(Box::new(why)))?;
+        return realtime::FeedMessage::decode(&*bytes)
+            .map_err(|why| RequestError::Other(Box::new(why)));
+    }
     let response = reqwest::get(url)
         .await
         .map_err(|why| RequestError::Other(Box::new(why)))?;
@@ -31,11 +61,71 @@ pub async fn update<D: Database>(
         .bytes()
         .await
         .map_err(|why| RequestError::Other(Box::new(why)))?;
-    let message = realtime::FeedMessage::decode(&*bytes)
-        .map_err(|why| RequestError::Other(Box::new(why)))?;
+    realtime::FeedMessage::decode(&*bytes)
+        .map_err(|why| RequestError::Other(Box::new(why)))
+}
+
+/// Feed of the latest poll, which has been applied. Feeds are polled far more
+/// often than they change, so unchanged feeds and entities are skipped.
+#[derive(Debug, Default)]
+pub struct FeedTracker {
+    applied: FeedSnapshot,
+}
+
+/// Header timestamp and hashed content of the entities by id of a feed.
+#[derive(Debug, Default)]
+pub struct FeedSnapshot {
+    timestamp: Option<u64>,
+    entities: HashMap<String, u64>,
+}
+
+impl FeedTracker {
+    /// Entities of the feed, which are new or changed since the applied feed,
+    /// and the snapshot to mark the feed as applied with. `None`, if the header
+    /// timestamp of the feed did not advance.
+    pub fn changes(
+        &self,
+        message: realtime::FeedMessage,
+    ) -> Option<(Vec<realtime::FeedEntity>, FeedSnapshot)> {
+        let timestamp = message.header.timestamp;
+        if let (Some(timestamp), Some(applied)) = (timestamp, self.applied.timestamp)
+        {
+            if timestamp <= applied {
+                return None;
+            }
+        }
+        let mut snapshot = FeedSnapshot {
+            timestamp: timestamp.or(self.applied.timestamp),
+            entities: HashMap::with_capacity(message.entity.len()),
+        };
+        let mut changed = vec![];
+        for entity in message.entity {
+            let mut hasher = DefaultHasher::new();
+            entity.encode_to_vec().hash(&mut hasher);
+            let hash = hasher.finish();
+            let unchanged = self.applied.entities.get(&entity.id) == Some(&hash);
+            snapshot.entities.insert(entity.id.clone(), hash);
+            if !unchanged {
+                changed.push(entity);
+            }
+        }
+        Some((changed, snapshot))
+    }
 
+    /// Marks the feed of the snapshot as applied, once its changes are written.
+    pub fn apply(&mut self, snapshot: FeedSnapshot) {
+        self.applied = snapshot;
+    }
+}
+
+/// Converts the trip updates of the entities.
+pub async fn convert_updates<D: Database>(
+    client: &Client<D>,
+    entities: Vec<realtime::FeedEntity>,
+) -> Result<Vec<WithId<TripUpdate>>, RequestError> {
     let mut updates = vec![];
-    for entity in message.entity {
+    let mut stop_ids: HashMap<String, Option<Id<Stop>>> = HashMap::new();
+    for entity in entities {
         if let Some(trip_update) = entity.trip_update {
             // only care for updates with trip ids (for now)
             let original_trip_id = if let Some(id) = &trip_update.trip.trip_id {
@@ -43,14 +133,22 @@ pub async fn update<D: Database>(
             } else {
                 continue;
             };
-            // get internal trip id
-            let trip_id = if let Some(id) = client
+            // get internal trip id. Added trips are not part of the schedule, but
+            // are kept to be found by their stops.
+            let trip_id = match client
                 .get_trip_id_by_original_id(original_trip_id.clone())
                 .await?
             {
-                id
-            } else {
-                continue;
+                Some(id) => id,
+                None if trip_update.trip.schedule_relationship()
+                    == ScheduleRelationship::Added =>
+                {
+                    match added_trip_id(original_trip_id) {
+                        Some(id) => id,
+                        None => continue,
+                    }
+                }
+                None => continue,
             };
             // only care for updates with trip start date (for now)
             let start_date = if let Some(date) = &trip_update.trip.start_date {
@@ -60,23 +158,43 @@ pub async fn update<D: Database>(
                 continue;
             };
 
-            // instanciate trip
-            let trip = if let Some(trip) = not_found_to_none(
+            // instanciate trip. Instances of frequency based trips are told apart
+            // by their start time.
+            let instances = match not_found_to_none(
                 client
                     .get_trip(trip_id.clone(), vec![client.origin()])
                     .await,
             )? {
-                instantiate_trip_naive(&trip, &start_date, None, None)
-            } else {
-                None
+                Some(trip) => instantiate_trips_naive(&trip, &start_date, None, None),
+                None => vec![],
+            };
+            let start_time = trip_update.trip.start_time.as_deref();
+            let Some((instance, trip)) =
+                instance_starting_at(instances, start_date, start_time)
+            else {
+                continue;
             };
 
             // stop times
             let mut stop_times = vec![];
             for stop in trip_update.stop_time_update {
                 let (arrival_time, departure_time) = get_times_for_stop(&trip, &stop);
+                let stop_id = match &stop.stop_id {
+                    Some(original_id) => match stop_ids.get(original_id) {
+                        Some(id) => id.clone(),
+                        None => {
+                            let id = client
+                                .get_stop_id_by_original_id(original_id.clone())
+                                .await?;
+                            stop_ids.insert(original_id.clone(), id.clone());
+                            id
+                        }
+                    },
+                    None => None,
+                };
                 stop_times.push(StopTimeUpdate {
                     scheduled_stop_sequence: stop.stop_sequence.map(|i| i as i32),
+                    stop_id,
                     arrival_time,
                     departure_time,
                     status: match stop.schedule_relationship() {
@@ -93,28 +211,11 @@ pub async fn update<D: Database>(
                             StopTimeStatus::Unknown // TODO!
                         }
                     },
+                    platform: None,
+                    scheduled_platform: None,
                 });
             }
 
-            // Debug Print
-            log::info!(
-                "inserting update {}: {}",
-                trip_id,
-                stop_times
-                    .iter()
-                    .map(|st| format!(
-                        "{}-{}",
-                        st.arrival_time
-                            .map(|t| t.to_string())
-                            .unwrap_or("X".to_owned()),
-                        st.departure_time
-                            .map(|t| t.to_string())
-                            .unwrap_or("X".to_owned())
-                    ))
-                    .collect::<Vec<_>>()
-                    .join(" | ")
-            );
-
             let update = TripUpdate {
                 status: match trip_update.trip.schedule_relationship() {
                     ScheduleRelationship::Added => TripStatus::Added,
@@ -134,14 +235,76 @@ pub async fn update<D: Database>(
             };
 
             updates.push(WithId::new(
-                Id::new(TripUpdateId::new(trip_id, start_date)),
+                Id::new(TripUpdateId::of_instance(trip_id, start_date, instance)),
                 update,
             ));
         }
         // TODO: service alerts...
     }
 
-    client.put_trip_updates(updates).await
+    Ok(updates)
+}
+
+/// The index and instance of the trip, which departs from its first stop at the
+/// start time (`HH:MM:SS` since midnight of the service day). Without start time,
+/// or for trips, which are no instances of the schedule, it is the first one.
+/// `None`, if no instance departs at the start time.
+fn instance_starting_at(
+    instances: Vec<TripInstance>,
+    service_day: NaiveDate,
+    start_time: Option<&str>,
+) -> Option<(u32, Option<TripInstance>)> {
+    let start_time = match start_time {
+        Some(start_time) if instances.len() > 1 => parse_start_time(start_time)?,
+        _ => return Some((0, instances.into_iter().next())),
+    };
+    let start = service_day
+        .and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()?
+        + start_time;
+    instances
+        .into_iter()
+        .enumerate()
+        .find(|(_, instance)| {
+            instance.stops.first().and_then(|stop_time| {
+                stop_time.departure_time.or(stop_time.arrival_time)
+            }) == Some(start)
+        })
+        .map(|(index, instance)| (index as u32, Some(instance)))
+}
+
+/// Parses a time like `25:10:00`, which may exceed a day.
+fn parse_start_time(time: &str) -> Option<Duration> {
+    let mut parts = time.split(':').map(|part| part.parse::<i64>().ok());
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(Some(hours)), Some(Some(minutes)), Some(Some(seconds)), None) => Some(
+            Duration::hours(hours)
+                + Duration::minutes(minutes)
+                + Duration::seconds(seconds),
+        ),
+        _ => None,
+    }
+}
+
+/// Id of an added trip, which has no counterpart in the schedule. `None` for an
+/// empty original id.
+/// Derived from the original id, so that updates of the trip replace each other.
+/// Distinct original ids result in distinct ids: bytes other than lowercase
+/// letters and digits, as well as `x`, are escaped as `x` and two hex digits.
+fn added_trip_id(original_id: &str) -> Option<Id<Trip>> {
+    if original_id.is_empty() {
+        return None;
+    }
+    let slug = original_id
+        .bytes()
+        .map(|byte| match byte {
+            b'x' => "x78".to_owned(),
+            b'a'..=b'z' | b'0'..=b'9' => char::from(byte).to_string(),
+            _ => format!("x{:02x}", byte),
+        })
+        .collect::<String>();
+    Some(Id::new(format!("added-{}", slug)))
 }
 
 fn get_times_for_stop(
@@ -209,3 +372,210 @@ pub fn test() -> Result<(), Box<dyn Error>> {
     dbg!(message);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use model::{
+        trip::{Frequency, PickupDropOffType, StopTime},
+        trip_instance::TripInstanceId,
+    };
+
+    use super::*;
+
+    fn stop_time(stop_sequence: i32, minutes: i64) -> StopTime {
+        StopTime {
+            stop_sequence,
+            stop_id: None,
+            arrival_time: Some(Duration::minutes(minutes)),
+            departure_time: Some(Duration::minutes(minutes)),
+            stop_headsign: None,
+            pickup_type: Some(PickupDropOffType::Regular),
+            drop_off_type: Some(PickupDropOffType::Regular),
+            area_reference: None,
+            stop_name: None,
+        }
+    }
+
+    /// Departs every 20 minutes from 8:00 until before 9:00.
+    fn frequency_based_trip() -> WithId<Trip> {
+        WithId::new(
+            Id::new("trip".to_owned()),
+            Trip {
+                line_id: Id::new("line".to_owned()),
+                service_id: None,
+                headsign: None,
+                short_name: None,
+                direction: None,
+                shape_id: None,
+                stops: vec![stop_time(1, 0), stop_time(2, 12)],
+                frequencies: vec![Frequency {
+                    start_time: Duration::hours(8),
+                    end_time: Duration::hours(9),
+                    headway: Duration::minutes(20),
+                    exact_times: true,
+                }],
+                updated_at: None,
+            },
+        )
+    }
+
+    #[test]
+    fn instances_of_frequency_based_trips_are_found_by_start_time() {
+        let day = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        let trip = frequency_based_trip();
+        let cases = [
+            (None, Some(0)),
+            (Some("08:00:00"), Some(0)),
+            (Some("08:20:00"), Some(1)),
+            (Some("8:40:00"), Some(2)),
+            (Some("09:00:00"), None),
+            (Some("08:10:00"), None),
+            (Some("8 Uhr"), None),
+        ];
+        for (start_time, expected) in cases {
+            let instances = instantiate_trips_naive(&trip, &day, None, None);
+            let found = instance_starting_at(instances, day, start_time);
+            assert_eq!(
+                found.as_ref().map(|(index, _)| *index),
+                expected,
+                "start time `{:?}`",
+                start_time
+            );
+            if let Some((index, instance)) = found {
+                let instance = instance.expect("instance is found");
+                assert_eq!(
+                    instance.info.instance_id,
+                    TripInstanceId::new(&trip.id, day, index)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn other_trips_are_their_first_instance_regardless_of_start_time() {
+        let day = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        let mut trip = frequency_based_trip();
+        trip.content.frequencies.clear();
+        let instances = instantiate_trips_naive(&trip, &day, None, None);
+        let (index, instance) =
+            instance_starting_at(instances, day, Some("23:00:00")).unwrap();
+        assert_eq!(index, 0);
+        assert!(instance.is_some());
+        assert!(matches!(
+            instance_starting_at(vec![], day, Some("08:00:00")),
+            Some((0, None))
+        ));
+    }
+
+    #[test]
+    fn added_trip_ids_are_distinct_slugs() {
+        let ids = ["A_1", "a-1", "a1", "x", "ax78", "Zug 12:30"]
+            .map(|original_id| added_trip_id(original_id).unwrap().raw());
+        assert_eq!(
+            ids,
+            [
+                "added-x41x5f1",
+                "added-ax2d1",
+                "added-a1",
+                "added-x78",
+                "added-ax7878",
+                "added-x5augx2012x3a30",
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_original_ids_have_no_added_trip_id() {
+        assert!(added_trip_id("").is_none());
+    }
+
+    /// Feed with the header timestamp and entities of the given ids, which differ
+    /// in whether they are deleted.
+    fn feed(
+        timestamp: Option<u64>,
+        entities: &[(&str, bool)],
+    ) -> realtime::FeedMessage {
+        realtime::FeedMessage {
+            header: realtime::FeedHeader {
+                gtfs_realtime_version: "2.0".to_owned(),
+                incrementality: None,
+                timestamp,
+            },
+            entity: entities
+                .iter()
+                .map(|(id, is_deleted)| realtime::FeedEntity {
+                    id: id.to_string(),
+                    is_deleted: Some(*is_deleted),
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    /// Ids of the changed entities, or `None` if the feed is skipped.
+    fn changed_ids(
+        tracker: &FeedTracker,
+        feed: realtime::FeedMessage,
+    ) -> Option<Vec<String>> {
+        tracker.changes(feed).map(|(entities, _)| {
+            entities.into_iter().map(|entity| entity.id).collect()
+        })
+    }
+
+    #[test]
+    fn unchanged_feeds_are_not_applied_twice() {
+        let mut tracker = FeedTracker::default();
+        let first = feed(Some(100), &[("a", false), ("b", false)]);
+        let (entities, snapshot) =
+            tracker.changes(first.clone()).expect("feed is new");
+        assert_eq!(entities.len(), 2);
+        tracker.apply(snapshot);
+
+        // polled again, the collector has nothing to write.
+        assert_eq!(changed_ids(&tracker, first), None);
+        let cases = [
+            (Some(99), vec![("a", false), ("b", true)], None),
+            (Some(101), vec![("a", false), ("b", false)], Some(vec![])),
+            (
+                Some(101),
+                vec![("a", false), ("b", true), ("c", false)],
+                Some(vec!["b".to_owned(), "c".to_owned()]),
+            ),
+            (None, vec![("a", false), ("b", false)], Some(vec![])),
+        ];
+        for (i, (timestamp, entities, expected)) in cases.into_iter().enumerate() {
+            assert_eq!(
+                changed_ids(&tracker, feed(timestamp, &entities)),
+                expected,
+                "changes of case {}",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn feeds_without_timestamp_skip_unchanged_entities() {
+        let mut tracker = FeedTracker::default();
+        let unchanged = feed(None, &[("a", false)]);
+        for expected in [vec!["a".to_owned()], vec![]] {
+            let (entities, snapshot) = tracker
+                .changes(unchanged.clone())
+                .expect("feed is not skipped");
+            assert_eq!(
+                entities
+                    .into_iter()
+                    .map(|entity| entity.id)
+                    .collect::<Vec<_>>(),
+                expected
+            );
+            tracker.apply(snapshot);
+        }
+        // entities are only applied with their feed.
+        let changed = feed(None, &[("a", true)]);
+        assert_eq!(
+            changed_ids(&tracker, changed.clone()),
+            Some(vec!["a".to_owned()])
+        );
+        assert_eq!(changed_ids(&tracker, changed), Some(vec!["a".to_owned()]));
+    }
+}
@@ -0,0 +1,185 @@
+//! Rows, whose references could not be resolved during the import of their file,
+//! are retried once all files are imported. Feeds do not always list referenced
+//! entities first.
+
+use std::{
+    env,
+    fs::{self, File},
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    vec,
+};
+
+use csv::{
+    ReaderBuilder, StringRecord, StringRecordsIntoIter, Writer, WriterBuilder,
+};
+
+/// Rows kept in memory, before further rows are spilled to a temporary file.
+const MAX_BUFFERED_ROWS: usize = 100_000;
+
+/// Distinguishes the temporary files of multiple buffers.
+static SPILL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Raw rows of a single file, which are retried later on.
+pub struct RetryBuffer {
+    /// Recorded in the import report for rows, which fail again.
+    reason: &'static str,
+    rows: Vec<StringRecord>,
+    spill: Option<(Writer<File>, TempFile)>,
+    len: usize,
+}
+
+impl RetryBuffer {
+    pub fn new(reason: &'static str) -> Self {
+        Self {
+            reason,
+            rows: vec![],
+            spill: None,
+            len: 0,
+        }
+    }
+
+    pub fn reason(&self) -> &'static str {
+        self.reason
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn push(&mut self, row: StringRecord) -> Result<(), csv::Error> {
+        self.len += 1;
+        if self.rows.len() < MAX_BUFFERED_ROWS {
+            self.rows.push(row);
+            return Ok(());
+        }
+        let (writer, _) = match &mut self.spill {
+            Some(spill) => spill,
+            None => {
+                let file = TempFile::new();
+                log::info!("spilling rows to retry to {}.", file.path.display());
+                // rows of gtfs files may differ in length.
+                let writer =
+                    WriterBuilder::new().flexible(true).from_path(&file.path)?;
+                self.spill.insert((writer, file))
+            }
+        };
+        writer.write_record(&row)
+    }
+
+    /// All rows in the order they were pushed.
+    pub fn into_rows(self) -> Result<RetryRows, csv::Error> {
+        let spilled = match self.spill {
+            Some((mut writer, file)) => {
+                writer.flush()?;
+                let records = ReaderBuilder::new()
+                    .has_headers(false)
+                    .flexible(true)
+                    .from_path(&file.path)?
+                    .into_records();
+                Some((records, file))
+            }
+            None => None,
+        };
+        Ok(RetryRows {
+            rows: self.rows.into_iter(),
+            spilled,
+        })
+    }
+}
+
+pub struct RetryRows {
+    rows: vec::IntoIter<StringRecord>,
+    spilled: Option<(StringRecordsIntoIter<File>, TempFile)>,
+}
+
+impl Iterator for RetryRows {
+    type Item = Result<StringRecord, csv::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.rows.next() {
+            Some(row) => Some(Ok(row)),
+            None => self.spilled.as_mut()?.0.next(),
+        }
+    }
+}
+
+/// Removed, when dropped.
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn new() -> Self {
+        let name = format!(
+            "gtfs-retry-{}-{}.csv",
+            process::id(),
+            SPILL_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        Self {
+            path: env::temp_dir().join(name),
+        }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(index: usize) -> StringRecord {
+        // rows of different lengths, like in feeds with trailing empty fields.
+        let mut row = StringRecord::from(vec![index.to_string()]);
+        if index.is_multiple_of(2) {
+            row.push_field("");
+        }
+        row
+    }
+
+    #[test]
+    fn rows_are_returned_in_order_after_spilling() {
+        for count in [0, 3, MAX_BUFFERED_ROWS, MAX_BUFFERED_ROWS + 3] {
+            let mut buffer = RetryBuffer::new("test");
+            for index in 0..count {
+                buffer.push(row(index)).expect("row is kept");
+            }
+            assert_eq!(buffer.len(), count, "length of case {}", count);
+            assert_eq!(
+                buffer.spill.is_some(),
+                count > MAX_BUFFERED_ROWS,
+                "spill of case {}",
+                count
+            );
+            let rows = buffer
+                .into_rows()
+                .expect("rows are read")
+                .collect::<Result<Vec<_>, _>>()
+                .expect("rows are read");
+            assert!(
+                rows.into_iter().eq((0..count).map(row)),
+                "rows of case {}",
+                count
+            );
+        }
+    }
+
+    #[test]
+    fn the_spill_file_is_removed_with_the_rows() {
+        let mut buffer = RetryBuffer::new("test");
+        for index in 0..=MAX_BUFFERED_ROWS {
+            buffer.push(row(index)).expect("row is kept");
+        }
+        let (_, file) = buffer.spill.as_ref().expect("rows are spilled");
+        let path = file.path.clone();
+        let rows = buffer.into_rows().expect("rows are read");
+        assert!(path.exists());
+        drop(rows);
+        assert!(!path.exists());
+    }
+}
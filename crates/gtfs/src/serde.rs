@@ -1,8 +1,146 @@
+//! Helpers for fields, whose GTFS encoding serde does not handle out of the box.
+//! Enums encoded as integers derive `Deserialize_repr` and use [`default_if_empty`]
+//! for their documented default. Booleans are encoded as `0` or `1`.
+
+use serde::{
+    de::{Error as _, Unexpected},
+    Deserialize, Deserializer,
+};
+
+/// Empty values deserialize to the default of `T`. Missing columns additionally
+/// need `#[serde(default)]`.
 pub(crate) fn default_if_empty<'de, D, T>(de: D) -> Result<T, D::Error>
 where
-    D: serde::Deserializer<'de>,
-    T: serde::Deserialize<'de> + Default,
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
 {
-    use serde::Deserialize;
     Option::<T>::deserialize(de).map(|x| x.unwrap_or_else(|| T::default()))
 }
+
+/// A required GTFS boolean, e.g. the weekdays of calendar.txt. Values other than
+/// `0` and `1`, including empty ones, are rejected.
+pub(crate) fn int_bool<'de, D>(de: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    u8::deserialize(de).and_then(from_int::<D>)
+}
+
+/// An optional GTFS boolean, which is `true` if empty, e.g. `timepoint`. Missing
+/// columns additionally need `#[serde(default = "default_true")]`.
+pub(crate) fn int_bool_or_true<'de, D>(de: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<u8>::deserialize(de)?.map_or(Ok(true), from_int::<D>)
+}
+
+pub(crate) fn default_true() -> bool {
+    true
+}
+
+fn from_int<'de, D>(value: u8) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match value {
+        0 => Ok(false),
+        1 => Ok(true),
+        other => Err(D::Error::invalid_value(
+            Unexpected::Unsigned(other.into()),
+            &"0 or 1",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::DeserializeOwned;
+
+    use crate::data_model::stops::WheechairBoarding;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Required {
+        #[serde(deserialize_with = "int_bool")]
+        value: bool,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Optional {
+        #[serde(default = "default_true", deserialize_with = "int_bool_or_true")]
+        value: bool,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Enum {
+        #[serde(default, deserialize_with = "default_if_empty")]
+        value: WheechairBoarding,
+    }
+
+    /// The first row of a csv file with the given content.
+    fn row<T: DeserializeOwned>(content: &str) -> Option<T> {
+        csv::Reader::from_reader(content.as_bytes())
+            .deserialize()
+            .next()
+            .expect("file has a row")
+            .ok()
+    }
+
+    #[test]
+    fn booleans_are_read_from_0_and_1() {
+        let cases = [
+            ("0", Some(false), Some(false)),
+            ("1", Some(true), Some(true)),
+            ("", None, Some(true)),
+            ("2", None, None),
+            ("true", None, None),
+            ("-1", None, None),
+        ];
+        for (value, required, optional) in cases {
+            let content = format!("value,other\n{},x\n", value);
+            assert_eq!(
+                row::<Required>(&content).map(|row| row.value),
+                required,
+                "required value of case {:?}",
+                value
+            );
+            assert_eq!(
+                row::<Optional>(&content).map(|row| row.value),
+                optional,
+                "optional value of case {:?}",
+                value
+            );
+        }
+        let missing = "other\nx\n";
+        assert!(row::<Required>(missing).is_none());
+        assert_eq!(row::<Optional>(missing).map(|row| row.value), Some(true));
+    }
+
+    #[test]
+    fn empty_enums_are_their_default() {
+        use WheechairBoarding::*;
+        let cases = [
+            ("", Some(NoInformationOrInherit)),
+            ("1", Some(SomeAccessable)),
+            ("2", Some(NotAccessable)),
+            ("3", None),
+            ("yes", None),
+        ];
+        for (value, expected) in cases {
+            let content = format!("value,other\n{},x\n", value);
+            assert_eq!(
+                row::<Enum>(&content).map(|row| row.value),
+                expected,
+                "value of case {:?}",
+                value
+            );
+        }
+        let missing = "other\nx\n";
+        assert_eq!(
+            row::<Enum>(missing).map(|row| row.value),
+            Some(NoInformationOrInherit)
+        );
+    }
+}
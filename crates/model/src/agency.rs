@@ -1,37 +1,70 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use utility::id::HasId;
+use utility::{
+    id::HasId,
+    normalize::{
+        normalize_email, normalize_phone_number, normalize_url, prefer_non_blank,
+    },
+};
 
 use crate::ExampleData;
 use crate::Mergable;
 
 #[serde_with::skip_serializing_none]
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Mergable)]
 #[serde(rename_all = "camelCase")]
 pub struct Agency {
+    #[merge(custom = "prefer_non_blank")]
     pub name: String,
+    /// Empty, if unknown.
+    #[merge(custom = "prefer_non_blank")]
     pub website: String,
+    #[merge(prefer_non_none)]
     pub phone_number: Option<String>,
+    #[merge(prefer_non_none)]
     pub email: Option<String>,
+    #[merge(prefer_non_none)]
     pub fare_url: Option<String>,
 }
 
-impl HasId for Agency {
-    type IdType = String;
-}
-
-impl Mergable for Agency {
-    fn merge(self, other: Self) -> Self {
-        Self {
-            name: other.name,
-            website: other.website,
-            phone_number: other.phone_number.or(self.phone_number),
-            email: other.email.or(self.email),
-            fare_url: other.fare_url.or(self.fare_url),
+impl Agency {
+    /// Normalizes urls, the phone number and the email address, so that variants
+    /// from different origins compare equal. Invalid values are dropped.
+    /// Returns the number of dropped values.
+    pub fn normalize(&mut self) -> usize {
+        let mut dropped = 0;
+        self.website = match normalize_url(&self.website) {
+            Some(website) => website,
+            None => {
+                if !self.website.trim().is_empty() {
+                    dropped += 1;
+                }
+                String::new()
+            }
+        };
+        for (value, normalize) in [
+            (
+                &mut self.fare_url,
+                normalize_url as fn(&str) -> Option<String>,
+            ),
+            (&mut self.phone_number, normalize_phone_number),
+            (&mut self.email, normalize_email),
+        ] {
+            if let Some(raw) = value.take() {
+                *value = normalize(&raw);
+                if value.is_none() && !raw.trim().is_empty() {
+                    dropped += 1;
+                }
+            }
         }
+        dropped
     }
 }
 
+impl HasId for Agency {
+    type IdType = String;
+}
+
 impl ExampleData for Agency {
     fn example_data() -> Self {
         Self {
@@ -43,3 +76,37 @@ impl ExampleData for Agency {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizing_drops_invalid_fields_only() {
+        let mut agency = Agency {
+            name: "Kieler Verkehrsgesellschaft".to_owned(),
+            website: "www.KVG-Kiel.de".to_owned(),
+            phone_number: Some("0431 / 71 07-0".to_owned()),
+            email: Some("mailto:Info@KVG-Kiel.de".to_owned()),
+            fare_url: Some("not a url".to_owned()),
+        };
+        assert_eq!(agency.normalize(), 1, "the fare url is dropped");
+        assert_eq!(agency.website, "https://www.kvg-kiel.de/");
+        assert_eq!(agency.phone_number.as_deref(), Some("043171070"));
+        assert_eq!(agency.email.as_deref(), Some("Info@kvg-kiel.de"));
+        assert_eq!(agency.fare_url, None);
+
+        // blank values are missing rather than invalid.
+        let mut agency = Agency {
+            name: "Kieler Verkehrsgesellschaft".to_owned(),
+            website: " ".to_owned(),
+            phone_number: Some(String::new()),
+            email: None,
+            fare_url: Some("buy.kvg-kiel.de".to_owned()),
+        };
+        assert_eq!(agency.normalize(), 0);
+        assert_eq!(agency.website, "");
+        assert_eq!(agency.phone_number, None);
+        assert_eq!(agency.fare_url.as_deref(), Some("https://buy.kvg-kiel.de/"));
+    }
+}
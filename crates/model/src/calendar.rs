@@ -1,9 +1,21 @@
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    str::FromStr,
+};
 
 use chrono::{Datelike, NaiveDate, Weekday};
 use schemars::JsonSchema;
-use serde::Serialize;
-use utility::id::HasId;
+use serde::{Deserialize, Serialize};
+use utility::id::{HasId, Id};
+
+use crate::origin::Origin;
+
+/// Services spanning at most this share of the feed are considered seasonal.
+const SEASONAL_MAX_SHARE: f64 = 0.5;
+
+/// Feeds must span at least this number of days, to tell seasonal services apart.
+const SEASONAL_MIN_FEED_DAYS: i64 = 180;
 
 // TODO: rename file to 'service.rs'
 
@@ -53,6 +65,40 @@ pub struct Service {
 }
 
 impl Service {
+    /// Normalized representation of the windows and dates of the service, which
+    /// is equal for services available at the same days for the same reasons.
+    pub fn signature(&self) -> String {
+        let mut parts = self
+            .windows
+            .iter()
+            .map(|window| {
+                let weekdays = [
+                    window.monday,
+                    window.tuesday,
+                    window.wednesday,
+                    window.thursday,
+                    window.friday,
+                    window.saturday,
+                    window.sunday,
+                ]
+                .into_iter()
+                .map(|day| if day.is_available() { '1' } else { '0' })
+                .collect::<String>();
+                format!("w{}-{}-{}", window.start_date, window.end_date, weekdays)
+            })
+            .chain(self.dates.iter().map(|date| {
+                let sign = match date.exception_type {
+                    ServiceExceptionType::Added => '+',
+                    ServiceExceptionType::Removed => '-',
+                };
+                format!("d{}{}", date.date, sign)
+            }))
+            .collect::<Vec<_>>();
+        parts.sort();
+        parts.dedup();
+        parts.join(",")
+    }
+
     pub fn check_availability(&self, date: chrono::NaiveDate) -> ServiceAvailability {
         ServiceAvailability::from_bool(
             self.windows
@@ -107,6 +153,64 @@ impl Service {
         days
     }
 
+    /// Like `available_days`, but also returns the calendar windows and exceptions,
+    /// which make the service available on each day.
+    pub fn explain_available_days(
+        &self,
+        earliest: Option<NaiveDate>,
+        latest: Option<NaiveDate>,
+    ) -> Vec<ServiceDay> {
+        self.available_days(earliest, latest)
+            .into_iter()
+            .map(|date| ServiceDay {
+                date,
+                windows: self
+                    .windows
+                    .iter()
+                    .filter(|window| window.check_availability(date).is_available())
+                    .map(|window| (window.start_date, window.end_date))
+                    .collect(),
+                added: self.dates.iter().any(|entry| {
+                    entry.date == date
+                        && entry.exception_type == ServiceExceptionType::Added
+                }),
+            })
+            .collect()
+    }
+
+    /// Tags derived from the days, the service is available at. `feed_span` is the
+    /// first and last day of all services of the feed, so that services of a short
+    /// feed are not considered seasonal.
+    pub fn classify(
+        &self,
+        calendar: &HolidayCalendar,
+        feed_span: (NaiveDate, NaiveDate),
+    ) -> Vec<ServiceTag> {
+        let days = self.available_days(None, None);
+        let (Some(first), Some(last)) = (days.first(), days.last()) else {
+            return vec![];
+        };
+        let mut tags = vec![];
+        // a service only available on school days must pause during a vacation.
+        // otherwise, it can not be told apart from a service on workdays.
+        let is_school_only = days.iter().all(|day| calendar.is_school_day(*day))
+            && calendar
+                .vacations
+                .iter()
+                .any(|(start, end)| start >= first && end <= last);
+        if is_school_only {
+            tags.push(ServiceTag::School);
+        }
+        let feed_days = (feed_span.1 - feed_span.0).num_days() + 1;
+        let service_days = (*last - *first).num_days() + 1;
+        if feed_days >= SEASONAL_MIN_FEED_DAYS
+            && service_days as f64 <= SEASONAL_MAX_SHARE * feed_days as f64
+        {
+            tags.push(ServiceTag::Seasonal);
+        }
+        tags
+    }
+
     // moves all weekly repeating dates into a window and merges all windows with
     // the same start / end date.
     pub fn optimize(&mut self) {
@@ -114,11 +218,97 @@ impl Service {
     }
 }
 
+/// Day, at which a service is available.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceDay {
+    pub date: NaiveDate,
+    /// Start and end date of each calendar window, which includes the day.
+    pub windows: Vec<(NaiveDate, NaiveDate)>,
+    /// Whether the day was added by an exception.
+    pub added: bool,
+}
+
 impl HasId for Service {
     type IdType = i32; // TODO: maybe bigger int?
 }
 
+/// Tag of a service, derived from its calendar.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum ServiceTag {
+    /// Only available on school days.
+    School,
+    /// Only available during a part of the year.
+    Seasonal,
+}
+
+impl fmt::Display for ServiceTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::School => write!(f, "school"),
+            Self::Seasonal => write!(f, "seasonal"),
+        }
+    }
+}
+
+impl FromStr for ServiceTag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "school" => Ok(Self::School),
+            "seasonal" => Ok(Self::Seasonal),
+            other => Err(format!("unknown service tag '{}'.", other)),
+        }
+    }
+}
+
+/// Public holidays and school vacations of a region.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HolidayCalendar {
+    #[serde(default)]
+    pub holidays: Vec<NaiveDate>,
+    /// First and last day of each school vacation.
+    #[serde(default)]
+    pub vacations: Vec<(NaiveDate, NaiveDate)>,
+}
+
+impl HolidayCalendar {
+    pub fn is_school_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+            && !self.holidays.contains(&date)
+            && !self
+                .vacations
+                .iter()
+                .any(|(start, end)| date >= *start && date <= *end)
+    }
+}
+
+/// Holiday calendars by region, and the region of each origin.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HolidayCalendars {
+    #[serde(default)]
+    pub regions: HashMap<String, HolidayCalendar>,
+    /// Region by origin id.
+    #[serde(default)]
+    pub origins: HashMap<String, String>,
+}
+
+impl HolidayCalendars {
+    pub fn for_origin(&self, origin: &Id<Origin>) -> Option<&HolidayCalendar> {
+        self.origins
+            .get(origin.raw_ref::<str>())
+            .and_then(|region| self.regions.get(region))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct CalendarWindow {
     pub monday: ServiceAvailability,
     pub tuesday: ServiceAvailability,
@@ -186,8 +376,148 @@ pub enum ServiceExceptionType {
 }
 
 #[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct CalendarDate {
     #[serde(skip)] // TODO!
     pub date: chrono::NaiveDate,
     pub exception_type: ServiceExceptionType,
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use ServiceAvailability::*;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, month, day).expect("valid date")
+    }
+
+    /// Labour day and the summer vacation of 2024.
+    fn calendar() -> HolidayCalendar {
+        HolidayCalendar {
+            holidays: vec![date(5, 1)],
+            vacations: vec![(date(7, 22), date(8, 31))],
+        }
+    }
+
+    /// The whole year 2024, which spans 366 days.
+    fn feed_span() -> (NaiveDate, NaiveDate) {
+        (date(1, 1), date(12, 31))
+    }
+
+    fn window(
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+        weekend: bool,
+    ) -> CalendarWindow {
+        let weekend = ServiceAvailability::from_bool(weekend);
+        CalendarWindow {
+            monday: Available,
+            tuesday: Available,
+            wednesday: Available,
+            thursday: Available,
+            friday: Available,
+            saturday: weekend,
+            sunday: weekend,
+            start_date,
+            end_date,
+        }
+    }
+
+    fn exception(
+        date: NaiveDate,
+        exception_type: ServiceExceptionType,
+    ) -> CalendarDate {
+        CalendarDate {
+            date,
+            exception_type,
+        }
+    }
+
+    /// Weekdays before and after the summer vacation, optionally without labour
+    /// day, and with the given additional days.
+    fn school_days(without_holiday: bool, added: &[NaiveDate]) -> Service {
+        let mut dates = added
+            .iter()
+            .map(|day| exception(*day, ServiceExceptionType::Added))
+            .collect::<Vec<_>>();
+        if without_holiday {
+            dates.push(exception(date(5, 1), ServiceExceptionType::Removed));
+        }
+        Service {
+            windows: vec![
+                window(date(4, 1), date(7, 19), false),
+                window(date(9, 2), date(10, 31), false),
+            ],
+            dates,
+        }
+    }
+
+    fn daily(start_date: NaiveDate, days: i64) -> Service {
+        Service {
+            windows: vec![window(
+                start_date,
+                start_date + Duration::days(days - 1),
+                true,
+            )],
+            dates: vec![],
+        }
+    }
+
+    #[test]
+    fn services_are_classified_at_the_boundaries() {
+        let cases = [
+            (school_days(true, &[]), vec![ServiceTag::School]),
+            // available on a holiday.
+            (school_days(false, &[]), vec![]),
+            // available on a saturday.
+            (school_days(true, &[date(6, 8)]), vec![]),
+            // available on the first day of the vacation.
+            (school_days(true, &[date(7, 22)]), vec![]),
+            // only school days, but no vacation within its span.
+            (
+                Service {
+                    windows: vec![window(date(4, 1), date(7, 19), false)],
+                    dates: vec![exception(date(5, 1), ServiceExceptionType::Removed)],
+                },
+                vec![ServiceTag::Seasonal],
+            ),
+            // exactly half of the feed.
+            (daily(date(4, 1), 183), vec![ServiceTag::Seasonal]),
+            // one day more than half of the feed.
+            (daily(date(4, 1), 184), vec![]),
+            (
+                Service {
+                    windows: vec![],
+                    dates: vec![],
+                },
+                vec![],
+            ),
+        ];
+        for (index, (service, expected)) in cases.into_iter().enumerate() {
+            assert_eq!(
+                service.classify(&calendar(), feed_span()),
+                expected,
+                "tags of case {}",
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn services_of_short_feeds_are_not_seasonal() {
+        let service = daily(date(4, 1), 30);
+        let cases = [(179, vec![]), (180, vec![ServiceTag::Seasonal])];
+        for (index, (feed_days, expected)) in cases.into_iter().enumerate() {
+            let feed_span = (date(1, 1), date(1, 1) + Duration::days(feed_days - 1));
+            assert_eq!(
+                service.classify(&calendar(), feed_span),
+                expected,
+                "tags of case {}",
+                index
+            );
+        }
+    }
+}
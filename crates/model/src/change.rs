@@ -0,0 +1,44 @@
+use chrono::{DateTime, Local};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use utility::id::Id;
+
+use crate::origin::Origin;
+
+/// Kind of entity a change is about. Changes of stop times are changes of their
+/// trip.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangedEntity {
+    Stop,
+    Line,
+    Trip,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeOperation {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// Position of a change within the change log. Changes are ordered by their
+/// sequence, which is assigned once the transaction, which made them, finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ChangePosition {
+    pub sequence: i64,
+}
+
+/// Modification of the data of an entity by an origin.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Change {
+    #[serde(skip)]
+    pub position: ChangePosition,
+    pub entity: ChangedEntity,
+    pub id: String,
+    pub origin: Id<Origin>,
+    pub operation: ChangeOperation,
+    pub changed_at: DateTime<Local>,
+}
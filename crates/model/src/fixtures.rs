@@ -0,0 +1,395 @@
+//! Deterministic model data for test suites of all crates.
+//!
+//! Enabled by the `test-util` feature. Builders cover single entities, while
+//! [`NetworkFixture::small_city`] assembles a coherent mini network, whose expected
+//! query answers are documented in the constants of this module.
+
+use chrono::{Duration, NaiveDate, Weekday};
+use utility::id::Id;
+
+use crate::{
+    calendar::{
+        CalendarDate, CalendarWindow, Service, ServiceAvailability,
+        ServiceExceptionType,
+    },
+    line::{Line, LineType},
+    stop::{Location, Stop, StopAmenity},
+    trip::{PickupDropOffType, StopTime, Trip},
+    WithId,
+};
+
+pub struct StopBuilder {
+    stop: Stop,
+}
+
+impl StopBuilder {
+    pub fn new(name: &str) -> Self {
+        Self {
+            stop: Stop {
+                name: Some(name.to_owned()),
+                description: None,
+                parent_id: None,
+                location: None,
+                platform_code: None,
+                amenities: vec![],
+                updated_at: None,
+            },
+        }
+    }
+
+    pub fn at(mut self, latitude: f64, longitude: f64) -> Self {
+        self.stop.location = Some(Location {
+            latitude,
+            longitude,
+            address: None,
+        });
+        self
+    }
+
+    /// Sets the address of the location given by [`Self::at`].
+    pub fn address(mut self, address: &str) -> Self {
+        if let Some(location) = &mut self.stop.location {
+            location.address = Some(address.to_owned());
+        }
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.stop.description = Some(description.to_owned());
+        self
+    }
+
+    pub fn amenity(mut self, amenity: StopAmenity) -> Self {
+        self.stop.amenities.push(amenity);
+        self
+    }
+
+    pub fn platform(mut self, platform_code: &str) -> Self {
+        self.stop.platform_code = Some(platform_code.to_owned());
+        self
+    }
+
+    pub fn parent(mut self, parent_id: &str) -> Self {
+        self.stop.parent_id = Some(Id::new(parent_id.to_owned()));
+        self
+    }
+
+    pub fn build(self) -> Stop {
+        self.stop
+    }
+
+    pub fn with_id(self, id: &str) -> WithId<Stop> {
+        WithId::new(Id::new(id.to_owned()), self.stop)
+    }
+}
+
+/// Builds a trip from stop times relative to its start.
+pub struct TripBuilder {
+    trip: Trip,
+    start: Duration,
+}
+
+impl TripBuilder {
+    /// Trip of the line starting at `hours:minutes` of its service day.
+    pub fn new(line_id: &str, hours: i64, minutes: i64) -> Self {
+        Self {
+            trip: Trip {
+                line_id: Id::new(line_id.to_owned()),
+                service_id: None,
+                headsign: None,
+                short_name: None,
+                direction: None,
+                shape_id: None,
+                stops: vec![],
+                frequencies: vec![],
+                updated_at: None,
+            },
+            start: Duration::hours(hours) + Duration::minutes(minutes),
+        }
+    }
+
+    pub fn service(mut self, service_id: i32) -> Self {
+        self.trip.service_id = Some(Id::new(service_id));
+        self
+    }
+
+    pub fn headsign(mut self, headsign: &str) -> Self {
+        self.trip.headsign = Some(headsign.to_owned());
+        self
+    }
+
+    /// Calls at the stop `minutes` after the start of the trip.
+    pub fn stop(self, stop_id: &str, minutes: i64) -> Self {
+        self.stop_with_dwell(stop_id, minutes, minutes)
+    }
+
+    /// Arrives and departs at the stop the given minutes after the start of the
+    /// trip.
+    pub fn stop_with_dwell(
+        mut self,
+        stop_id: &str,
+        arrival_minutes: i64,
+        departure_minutes: i64,
+    ) -> Self {
+        let stop_sequence = self.trip.stops.len() as i32 + 1;
+        let arrival_time = self.start + Duration::minutes(arrival_minutes);
+        let departure_time = self.start + Duration::minutes(departure_minutes);
+        self.trip.stops.push(StopTime {
+            arrival_time: Some(arrival_time),
+            departure_time: Some(departure_time),
+            ..stop_time(stop_sequence, stop_id, 0)
+        });
+        self
+    }
+
+    pub fn build(self) -> Trip {
+        self.trip
+    }
+
+    pub fn with_id(self, id: &str) -> WithId<Trip> {
+        WithId::new(Id::new(id.to_owned()), self.trip)
+    }
+}
+
+/// Stop time of a regular stop at the stop `minutes` after the start of the
+/// service day.
+pub fn stop_time(stop_sequence: i32, stop_id: &str, minutes: i64) -> StopTime {
+    StopTime {
+        stop_sequence,
+        stop_id: Some(Id::new(stop_id.to_owned())),
+        arrival_time: Some(Duration::minutes(minutes)),
+        departure_time: Some(Duration::minutes(minutes)),
+        stop_headsign: None,
+        pickup_type: Some(PickupDropOffType::Regular),
+        drop_off_type: Some(PickupDropOffType::Regular),
+        area_reference: None,
+        stop_name: None,
+    }
+}
+
+/// Trip `trip` of the line `line` to Raisdorf, calling at `a`, `b` and `c` at
+/// 08:00, 08:10 and 08:20.
+pub fn trip() -> WithId<Trip> {
+    TripBuilder::new("line", 8, 0)
+        .headsign("Raisdorf")
+        .stop("a", 0)
+        .stop("b", 10)
+        .stop("c", 20)
+        .with_id("trip")
+}
+
+/// Trip `trip` of two hours from `kiel` via `oldenburg` to `puttgarden`,
+/// departing at 10:00.
+pub fn long_trip() -> WithId<Trip> {
+    TripBuilder::new("line", 10, 0)
+        .headsign("Raisdorf")
+        .stop("kiel", 0)
+        .stop("oldenburg", 60)
+        .stop("puttgarden", 120)
+        .with_id("trip")
+}
+
+/// Builds a service of a single calendar window with exceptions.
+pub struct ServiceBuilder {
+    window: CalendarWindow,
+    dates: Vec<CalendarDate>,
+}
+
+impl ServiceBuilder {
+    /// Service between both dates (inclusive) without any days yet.
+    pub fn new(start_date: NaiveDate, end_date: NaiveDate) -> Self {
+        Self {
+            window: CalendarWindow {
+                monday: ServiceAvailability::Unavailable,
+                tuesday: ServiceAvailability::Unavailable,
+                wednesday: ServiceAvailability::Unavailable,
+                thursday: ServiceAvailability::Unavailable,
+                friday: ServiceAvailability::Unavailable,
+                saturday: ServiceAvailability::Unavailable,
+                sunday: ServiceAvailability::Unavailable,
+                start_date,
+                end_date,
+            },
+            dates: vec![],
+        }
+    }
+
+    pub fn days(mut self, days: &[Weekday]) -> Self {
+        for day in days {
+            let availability = match day {
+                Weekday::Mon => &mut self.window.monday,
+                Weekday::Tue => &mut self.window.tuesday,
+                Weekday::Wed => &mut self.window.wednesday,
+                Weekday::Thu => &mut self.window.thursday,
+                Weekday::Fri => &mut self.window.friday,
+                Weekday::Sat => &mut self.window.saturday,
+                Weekday::Sun => &mut self.window.sunday,
+            };
+            *availability = ServiceAvailability::Available;
+        }
+        self
+    }
+
+    /// Monday to friday.
+    pub fn weekdays(self) -> Self {
+        self.days(&[
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+        ])
+    }
+
+    pub fn added(mut self, date: NaiveDate) -> Self {
+        self.dates.push(CalendarDate {
+            date,
+            exception_type: ServiceExceptionType::Added,
+        });
+        self
+    }
+
+    pub fn removed(mut self, date: NaiveDate) -> Self {
+        self.dates.push(CalendarDate {
+            date,
+            exception_type: ServiceExceptionType::Removed,
+        });
+        self
+    }
+
+    pub fn build(self) -> Service {
+        Service {
+            windows: vec![self.window],
+            dates: self.dates,
+        }
+    }
+
+    pub fn with_id(self, id: i32) -> WithId<Service> {
+        WithId::new(Id::new(id), self.build())
+    }
+}
+
+/// Stop ids of the small city network.
+pub const SMALL_CITY_HBF: &str = "fixture-hbf";
+pub const SMALL_CITY_MARKET: &str = "fixture-market";
+pub const SMALL_CITY_UNIVERSITY: &str = "fixture-university";
+pub const SMALL_CITY_HARBOUR: &str = "fixture-harbour";
+
+/// Line ids of the small city network.
+pub const SMALL_CITY_BUS_LINE: &str = "fixture-bus-1";
+pub const SMALL_CITY_FERRY_LINE: &str = "fixture-ferry-f1";
+
+/// Id of the weekday service of the small city network.
+pub const SMALL_CITY_WEEKDAY_SERVICE: i32 = 1;
+/// Id of the daily service of the small city network.
+pub const SMALL_CITY_DAILY_SERVICE: i32 = 2;
+
+/// First and last day of the services of the small city network.
+pub const SMALL_CITY_START_DATE: (i32, u32, u32) = (2024, 1, 1);
+pub const SMALL_CITY_END_DATE: (i32, u32, u32) = (2024, 12, 31);
+
+/// Public holiday without weekday service.
+pub const SMALL_CITY_HOLIDAY: (i32, u32, u32) = (2024, 10, 3);
+
+/// Stops within 1 km of the hbf: the hbf itself and the market.
+pub const SMALL_CITY_STOPS_NEAR_HBF: usize = 2;
+/// Trips calling at the market on an ordinary weekday, e.g. 2024-10-07.
+pub const SMALL_CITY_TRIPS_AT_MARKET_ON_WEEKDAY: usize = 2;
+/// Trips calling at the market on the holiday and on weekends.
+pub const SMALL_CITY_TRIPS_AT_MARKET_ON_HOLIDAY: usize = 0;
+/// Trips calling at the harbour on any day of the year.
+pub const SMALL_CITY_TRIPS_AT_HARBOUR_DAILY: usize = 1;
+
+/// Coherent mini network for tests, using the ids of the constants above.
+pub struct NetworkFixture {
+    pub stops: Vec<WithId<Stop>>,
+    pub lines: Vec<WithId<Line>>,
+    pub services: Vec<WithId<Service>>,
+    pub trips: Vec<WithId<Trip>>,
+}
+
+impl NetworkFixture {
+    /// A bus line from the hbf via the market to the university on weekdays, and
+    /// a ferry from the hbf to the harbour every day.
+    pub fn small_city() -> Self {
+        let date =
+            |(y, m, d): (i32, u32, u32)| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        Self {
+            stops: vec![
+                StopBuilder::new("Kiel Hbf")
+                    .at(54.3150, 10.1318)
+                    .platform("1")
+                    .with_id(SMALL_CITY_HBF),
+                StopBuilder::new("Kiel Alter Markt")
+                    .at(54.3210, 10.1370)
+                    .with_id(SMALL_CITY_MARKET),
+                StopBuilder::new("Kiel Universität")
+                    .at(54.3386, 10.1224)
+                    .with_id(SMALL_CITY_UNIVERSITY),
+                StopBuilder::new("Kiel Dietrichsdorf")
+                    .at(54.3341, 10.1747)
+                    .with_id(SMALL_CITY_HARBOUR),
+            ],
+            lines: vec![
+                WithId::new(
+                    Id::new(SMALL_CITY_BUS_LINE.to_owned()),
+                    Line {
+                        name: Some("1".to_owned()),
+                        kind: LineType::Bus,
+                        agency_id: None,
+                        secondary_agency_ids: vec![],
+                        updated_at: None,
+                    },
+                ),
+                WithId::new(
+                    Id::new(SMALL_CITY_FERRY_LINE.to_owned()),
+                    Line {
+                        name: Some("F1".to_owned()),
+                        kind: LineType::Ferry,
+                        agency_id: None,
+                        secondary_agency_ids: vec![],
+                        updated_at: None,
+                    },
+                ),
+            ],
+            services: vec![
+                ServiceBuilder::new(
+                    date(SMALL_CITY_START_DATE),
+                    date(SMALL_CITY_END_DATE),
+                )
+                .weekdays()
+                .removed(date(SMALL_CITY_HOLIDAY))
+                .with_id(SMALL_CITY_WEEKDAY_SERVICE),
+                ServiceBuilder::new(
+                    date(SMALL_CITY_START_DATE),
+                    date(SMALL_CITY_END_DATE),
+                )
+                .weekdays()
+                .days(&[Weekday::Sat, Weekday::Sun])
+                .with_id(SMALL_CITY_DAILY_SERVICE),
+            ],
+            trips: vec![
+                TripBuilder::new(SMALL_CITY_BUS_LINE, 8, 0)
+                    .service(SMALL_CITY_WEEKDAY_SERVICE)
+                    .headsign("Universität")
+                    .stop(SMALL_CITY_HBF, 0)
+                    .stop_with_dwell(SMALL_CITY_MARKET, 4, 5)
+                    .stop(SMALL_CITY_UNIVERSITY, 12)
+                    .with_id("fixture-bus-1-0800"),
+                TripBuilder::new(SMALL_CITY_BUS_LINE, 8, 30)
+                    .service(SMALL_CITY_WEEKDAY_SERVICE)
+                    .headsign("Hbf")
+                    .stop(SMALL_CITY_UNIVERSITY, 0)
+                    .stop(SMALL_CITY_MARKET, 7)
+                    .stop(SMALL_CITY_HBF, 12)
+                    .with_id("fixture-bus-1-0830"),
+                TripBuilder::new(SMALL_CITY_FERRY_LINE, 9, 0)
+                    .service(SMALL_CITY_DAILY_SERVICE)
+                    .headsign("Dietrichsdorf")
+                    .stop(SMALL_CITY_HBF, 0)
+                    .stop(SMALL_CITY_HARBOUR, 20)
+                    .with_id("fixture-ferry-f1-0900"),
+            ],
+        }
+    }
+}
@@ -2,16 +2,26 @@ use chrono::{DateTime, TimeZone};
 use indexmap::IndexMap;
 use origin::Origin;
 use schemars::JsonSchema;
-use std::{fmt::Debug, hash::Hash};
+use std::{collections::BTreeMap, fmt::Debug, hash::Hash};
 
+pub use model_derive::Mergable;
 use serde::{Deserialize, Serialize};
 pub use serde_with;
 use utility::id::{HasId, Id};
 
+// allows the derive macros to refer to this crate as `::model` within it.
+extern crate self as model;
+
 pub mod agency;
 pub mod calendar;
+pub mod change;
+#[cfg(feature = "test-util")]
+pub mod fixtures;
 pub mod line;
+pub mod locale;
+pub mod merge;
 pub mod origin;
+pub mod pathway;
 pub mod shape;
 pub mod shared_mobility;
 pub mod stop;
@@ -61,9 +71,15 @@ impl<T> WithDistance<T> {
     {
         WithDistance::new(self.distance_km, WithId::new(id, self.content))
     }
+
+    /// Sorts nearest first. Values with the same distance keep their order.
+    pub fn sort(values: &mut [Self]) {
+        values.sort_by(|a, b| a.distance_km.total_cmp(&b.distance_km));
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DatabaseEntry<V>
 where
     V: Serialize + HasId,
@@ -132,6 +148,19 @@ where
             .map(|value| WithId::new(self.id, value))
     }
 
+    /// like `merge_from`, but additionally returns which origin supplied the final
+    /// value of each field. see `merge_all_from_with_provenance`.
+    pub fn merge_from_with_provenance(
+        self,
+        origins: &[Id<Origin>],
+    ) -> Option<(WithId<V>, Provenance)>
+    where
+        V: Clone,
+    {
+        merge_all_from_with_provenance(self.source_data, origins)
+            .map(|(value, provenance)| (WithId::new(self.id, value), provenance))
+    }
+
     pub fn merge_all_from(data: Vec<Self>, origins: &[Id<Origin>]) -> Vec<WithId<V>>
     where
         V: Clone,
@@ -162,7 +191,8 @@ where
     }
 }
 
-#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(bound(deserialize = "V: Deserialize<'de>, V::IdType: Deserialize<'de>"))]
 pub struct WithId<V>
 where
     V: HasId,
@@ -216,6 +246,42 @@ where
     }
 }
 
+/// Identifies an item within a collection, so that collections can be merged item
+/// by item. The key must identify the same item for all origins, e.g. stop
+/// sequences do not, as each origin numbers the stops of its trips on its own.
+pub trait Keyed {
+    type Key: Ord;
+
+    fn key(&self) -> Self::Key;
+}
+
+/// Unions both collections by key. Items present in both are merged, so that the
+/// item of the other collection wins. The result is ordered by key.
+///
+/// Collections of items without a key are merged with the `concat_dedup`
+/// strategy of the derive macro instead, which appends the items of the other
+/// collection, that are not present yet.
+impl<T> Mergable for Vec<T>
+where
+    T: Keyed + Mergable,
+{
+    fn merge(self, other: Self) -> Self {
+        let mut merged = self
+            .into_iter()
+            .map(|item| (item.key(), item))
+            .collect::<BTreeMap<_, _>>();
+        for item in other {
+            let key = item.key();
+            let item = match merged.remove(&key) {
+                Some(old) => old.merge(item),
+                None => item,
+            };
+            merged.insert(key, item);
+        }
+        merged.into_values().collect()
+    }
+}
+
 pub trait Subject {
     fn same_subject_as(&self, other: &Self) -> Option<f64>;
 }
@@ -299,3 +365,176 @@ where
     }
     result
 }
+
+/// Origin that supplied the final value of each (top level) field of a merged
+/// value, by field name.
+pub type Provenance = BTreeMap<String, Id<Origin>>;
+
+/// Like `merge_all_from`, but additionally records which origin supplied the final
+/// value of each field. A field is attributed to the origin with the highest
+/// priority, whose value is equal to the merged value.
+/// Fields are compared in their serialized form, so this is a lot slower than
+/// `merge_all_from` and only meant for inspecting the source data.
+pub fn merge_all_from_with_provenance<T>(
+    values: Vec<WithOrigin<T>>,
+    origins: &[Id<Origin>],
+) -> Option<(T, Provenance)>
+where
+    T: Mergable + Serialize + Clone,
+{
+    // highest priority first.
+    let sources = origins
+        .iter()
+        .rev()
+        .filter_map(|origin| {
+            values
+                .iter()
+                .find(|value| value.origin == *origin)
+                .and_then(|value| serde_json::to_value(&value.content).ok())
+                .map(|value| (origin, value))
+        })
+        .collect::<Vec<_>>();
+    let merged = merge_all_from(values, origins)?;
+    let mut provenance = Provenance::new();
+    if let Ok(serde_json::Value::Object(fields)) = serde_json::to_value(&merged) {
+        for (field, value) in fields {
+            if value.is_null() {
+                continue;
+            }
+            let source = sources
+                .iter()
+                .find(|(_, source)| source.get(&field) == Some(&value));
+            if let Some((origin, _)) = source {
+                provenance.insert(field, (*origin).clone());
+            }
+        }
+    }
+    Some((merged, provenance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Item {
+        key: u32,
+        value: &'static str,
+    }
+
+    impl Keyed for Item {
+        type Key = u32;
+
+        fn key(&self) -> u32 {
+            self.key
+        }
+    }
+
+    impl Mergable for Item {
+        fn merge(self, other: Self) -> Self {
+            other
+        }
+    }
+
+    fn items(items: &[(u32, &'static str)]) -> Vec<Item> {
+        items
+            .iter()
+            .map(|&(key, value)| Item { key, value })
+            .collect()
+    }
+
+    #[test]
+    fn keyed_collections_are_united_by_key() {
+        let cases = [
+            (vec![(1, "old"), (2, "old")], vec![(3, "new")]),
+            (vec![(3, "old")], vec![(2, "new"), (1, "new")]),
+            (vec![(1, "old"), (2, "old")], vec![(2, "new"), (3, "new")]),
+            (vec![], vec![(1, "new")]),
+            (vec![(1, "old")], vec![]),
+        ];
+        let expected = [
+            vec![(1, "old"), (2, "old"), (3, "new")],
+            vec![(1, "new"), (2, "new"), (3, "old")],
+            vec![(1, "old"), (2, "new"), (3, "new")],
+            vec![(1, "new")],
+            vec![(1, "old")],
+        ];
+        for ((old, new), expected) in cases.iter().zip(expected) {
+            assert_eq!(
+                items(old).merge(items(new)),
+                items(&expected),
+                "merge of `{:?}` and `{:?}`",
+                old,
+                new
+            );
+        }
+    }
+
+    fn stop(
+        name: &str,
+        description: Option<&str>,
+        latitude: f64,
+        address: Option<&str>,
+        platform_code: Option<&str>,
+    ) -> stop::Stop {
+        stop::Stop {
+            name: Some(name.to_owned()),
+            description: description.map(str::to_owned),
+            parent_id: None,
+            location: Some(stop::Location {
+                latitude,
+                longitude: 10.13,
+                address: address.map(str::to_owned),
+            }),
+            platform_code: platform_code.map(str::to_owned),
+            amenities: vec![],
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn provenance_points_to_the_origin_of_each_field() {
+        let [low, high, ignored] = ["low", "high", "ignored"]
+            .map(|origin| Id::<Origin>::new(origin.to_owned()));
+        let values = vec![
+            WithOrigin::new(
+                low.clone(),
+                stop("Kiel Hbf", Some("Bahnhof"), 54.31, Some("Kaistraße"), None),
+            ),
+            WithOrigin::new(
+                high.clone(),
+                stop("Kiel Hauptbahnhof", None, 54.315, None, Some("1")),
+            ),
+            WithOrigin::new(
+                ignored,
+                stop("Kiel", Some("Ignoriert"), 54.0, None, Some("9")),
+            ),
+        ];
+        // the last origin has the highest priority.
+        let (merged, provenance) = merge_all_from_with_provenance(
+            values.clone(),
+            &[low.clone(), high.clone()],
+        )
+        .expect("stops are merged");
+        assert_eq!(merged.name.as_deref(), Some("Kiel Hauptbahnhof"));
+        assert_eq!(merged.description.as_deref(), Some("Bahnhof"));
+        assert_eq!(
+            provenance,
+            Provenance::from([
+                ("name".to_owned(), high.clone()),
+                ("description".to_owned(), low.clone()),
+                ("platformCode".to_owned(), high.clone()),
+            ]),
+            "the location is combined from both origins, so it has no single one"
+        );
+
+        // with reversed priorities, the location is taken from `low` as a whole.
+        let (_, provenance) =
+            merge_all_from_with_provenance(values, &[high.clone(), low.clone()])
+                .expect("stops are merged");
+        assert_eq!(provenance.get("name"), Some(&low));
+        assert_eq!(provenance.get("description"), Some(&low));
+        assert_eq!(provenance.get("platformCode"), Some(&high));
+        assert_eq!(provenance.get("location"), Some(&low));
+    }
+}
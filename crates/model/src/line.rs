@@ -1,5 +1,6 @@
-use std::cmp;
+use std::{cmp, collections::HashSet};
 
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use utility::{
@@ -12,11 +13,13 @@ use crate::{agency::Agency, ExampleData, Mergable, Subject};
 
 /// taken from gtfs.
 #[serde_with::skip_serializing_none]
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum LineType {
     TramStreetcarOrLighrail,
     SubwayOrMetro,
+    /// Rail of unknown service level. Also the v1 representation of the finer
+    /// rail kinds below.
     Rail,
     Bus,
     Ferry,
@@ -25,6 +28,12 @@ pub enum LineType {
     Funicular,
     Trolleybus,
     Monorail,
+    /// Intercity and long-distance rail, e.g. ICE, IC or EC.
+    LongDistanceRail,
+    /// Regional rail, e.g. RE or RB.
+    RegionalRail,
+    /// Suburban rail, e.g. S-Bahn.
+    SuburbanRail,
 }
 
 struct LineTypeSimilarityVec {
@@ -41,7 +50,87 @@ struct LineTypeSimilarityVec {
 }
 
 impl LineType {
+    /// Rail kind of a train category, e.g. "ICE" or "RB". `None` for categories
+    /// that are not (confidently) rail.
+    pub fn of_rail_category(category: &str) -> Option<Self> {
+        match category.to_uppercase().as_str() {
+            "ICE" | "ICD" | "IC" | "EC" | "ECE" | "RJ" | "RJX" | "NJ" | "EN"
+            | "FLX" | "TGV" | "WB" => Some(Self::LongDistanceRail),
+            "RE" | "RB" | "IRE" | "MEX" => Some(Self::RegionalRail),
+            "S" => Some(Self::SuburbanRail),
+            _ => None,
+        }
+    }
+
+    /// Rail kind of a line named after its train category, e.g. "RE83" or "S 1".
+    pub fn of_rail_line_name(name: &str) -> Option<Self> {
+        let name = name.trim_start();
+        let category = name
+            .split(|c: char| !c.is_ascii_alphabetic())
+            .next()
+            .unwrap_or_default();
+        let number = name[category.len()..].trim_start();
+        number
+            .starts_with(|c: char| c.is_ascii_digit())
+            .then(|| Self::of_rail_category(category))
+            .flatten()
+    }
+
+    pub fn is_rail(&self) -> bool {
+        matches!(
+            self,
+            Self::Rail
+                | Self::LongDistanceRail
+                | Self::RegionalRail
+                | Self::SuburbanRail
+        )
+    }
+
+    /// The line type as known before rail was split by service level. Used by
+    /// API versions that only know [`LineType::Rail`].
+    pub fn legacy(self) -> Self {
+        if self.is_rail() {
+            Self::Rail
+        } else {
+            self
+        }
+    }
+
+    /// Whether a line of this type is selected by a mode filter. The filter may be
+    /// of either granularity: [`LineType::Rail`] selects all rail.
+    pub fn matches(&self, filter: &Self) -> bool {
+        match filter {
+            Self::Rail => self.is_rail(),
+            filter => self == filter,
+        }
+    }
+
+    /// The more specific of two types for the same line, so that an origin
+    /// without service level information does not reset a finer rail kind.
+    pub fn finer(self, other: Self) -> Self {
+        if other == Self::Rail && self.is_rail() {
+            self
+        } else {
+            other
+        }
+    }
+
+    /// Similarity of the types. Rail of differing service levels is slightly less
+    /// similar, unless one of them is of unknown service level.
     pub fn similarity(&self, other: &Self) -> f64 {
+        let similarity = self.type_similarity(other);
+        let distinct_service_levels = self.is_rail()
+            && other.is_rail()
+            && self != other
+            && !matches!((self, other), (Self::Rail, _) | (_, Self::Rail));
+        if distinct_service_levels {
+            0.9 * similarity
+        } else {
+            similarity
+        }
+    }
+
+    fn type_similarity(&self, other: &Self) -> f64 {
         let similarity_vector = self.similarity_vec();
 
         match other {
@@ -59,6 +148,9 @@ impl LineType {
             LineType::Funicular => similarity_vector.funicular,
             LineType::Trolleybus => similarity_vector.trolleybus,
             LineType::Monorail => similarity_vector.monorail,
+            LineType::LongDistanceRail
+            | LineType::RegionalRail
+            | LineType::SuburbanRail => similarity_vector.rail,
         }
     }
 
@@ -88,7 +180,10 @@ impl LineType {
                 trolleybus: 0.3,
                 monorail: 0.5,
             },
-            LineType::Rail => LineTypeSimilarityVec {
+            LineType::Rail
+            | LineType::LongDistanceRail
+            | LineType::RegionalRail
+            | LineType::SuburbanRail => LineTypeSimilarityVec {
                 tram_streetcar_or_lightrail: 0.8,
                 subway_or_metro: 0.7,
                 rail: 1.0,
@@ -192,30 +287,125 @@ impl LineType {
 pub struct Line {
     pub name: Option<String>,
     pub kind: LineType,
+    /// The agency operating the line.
     #[serde(skip)]
     pub agency_id: Option<Id<Agency>>,
+    /// Further agencies the line is marketed by, e.g. a Verkehrsverbund for a
+    /// line operated by DB Regio. Never contains the primary agency.
+    #[serde(skip)]
+    pub secondary_agency_ids: Vec<Id<Agency>>,
+    /// Last modification of the line by any origin. Maintained by the database.
+    #[serde(
+        rename = "updatedAt",
+        skip_serializing_if = "Option::is_none",
+        skip_deserializing
+    )]
+    pub updated_at: Option<DateTime<Local>>,
+}
+
+/// Role of an agency attributed to a line.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AgencyRole {
+    Primary,
+    Secondary,
+}
+
+/// An agency attributed to a line.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LineAgency {
+    pub agency_id: Id<Agency>,
+    pub role: AgencyRole,
 }
 
 impl Mergable for Line {
+    /// Keeps the primary agency of the higher-priority origin. The primary agency
+    /// of the other origin is kept as secondary, so that codeshares asserted
+    /// differently by the origins retain both agencies.
     fn merge(self, other: Self) -> Self {
-        Line {
+        let agency_id = other.agency_id.or(self.agency_id.clone());
+        let mut secondary_agency_ids: Vec<Id<Agency>> = vec![];
+        for id in other
+            .secondary_agency_ids
+            .into_iter()
+            .chain(self.agency_id)
+            .chain(self.secondary_agency_ids)
+        {
+            if Some(&id) != agency_id.as_ref() && !secondary_agency_ids.contains(&id)
+            {
+                secondary_agency_ids.push(id);
+            }
+        }
+        Self {
             name: other.name.or(self.name),
-            kind: other.kind,
-            agency_id: other.agency_id.or(self.agency_id),
+            kind: self.kind.finer(other.kind),
+            agency_id,
+            secondary_agency_ids,
+            updated_at: cmp::max(self.updated_at, other.updated_at),
         }
     }
 }
 
+impl Line {
+    /// Trims the name and collapses repeated whitespace, so that variants from
+    /// different origins compare equal. Empty names are dropped, as are secondary
+    /// agencies repeating the primary one or each other.
+    pub fn normalize(&mut self) {
+        self.name = self
+            .name
+            .take()
+            .map(|name| name.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|name| !name.is_empty());
+        let primary = self.agency_id.clone();
+        let mut seen = HashSet::new();
+        self.secondary_agency_ids
+            .retain(|id| Some(id) != primary.as_ref() && seen.insert(id.clone()));
+    }
+
+    /// All agencies attributed to the line, the primary one first.
+    pub fn agencies(&self) -> Vec<LineAgency> {
+        self.agency_id
+            .iter()
+            .map(|id| (id, AgencyRole::Primary))
+            .chain(
+                self.secondary_agency_ids
+                    .iter()
+                    .map(|id| (id, AgencyRole::Secondary)),
+            )
+            .map(|(id, role)| LineAgency {
+                agency_id: id.clone(),
+                role,
+            })
+            .collect()
+    }
+
+    /// Whether the agency is attributed to the line in any role.
+    pub fn is_attributed_to(&self, agency_id: &Id<Agency>) -> bool {
+        self.agency_id.as_ref() == Some(agency_id)
+            || self.secondary_agency_ids.contains(agency_id)
+    }
+}
+
 impl Subject for Line {
     fn same_subject_as(&self, other: &Self) -> Option<f64> {
         const NAME_WEIGHT: f64 = 0.5;
         const AGENCY_WEIGHT: f64 = 0.3;
         const KIND_WEIGHT: f64 = 0.2;
 
-        // agency similarty (binary, exclusion criteria if present for both)
+        // agency similarty (binary, exclusion criteria if present for both).
+        // codeshares match, if one of the agencies is attributed to both.
         let agency_similarity = match (&self.agency_id, &other.agency_id) {
-            (Some(a), Some(b)) if a != b => return None,
             (Some(a), Some(b)) if a == b => 1.0,
+            (Some(_), Some(_))
+                if self
+                    .agencies()
+                    .iter()
+                    .any(|agency| other.is_attributed_to(&agency.agency_id)) =>
+            {
+                1.0
+            }
+            (Some(_), Some(_)) => return None,
             _ => 0.0,
         };
 
@@ -254,8 +444,147 @@ impl ExampleData for Line {
     fn example_data() -> Self {
         Self {
             name: Some("erx RE83".to_owned()),
-            kind: LineType::Rail,
+            kind: LineType::RegionalRail,
             agency_id: Some(Id::new("erixx-holstein".to_owned())),
+            secondary_agency_ids: vec![],
+            updated_at: None,
+        }
+    }
+}
+
+/// First and last departure of a line on a service day. Departures after midnight
+/// belong to the previous service day, but are reported with their calendar date.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceSpan {
+    pub first_departure: DateTime<Local>,
+    pub last_departure: DateTime<Local>,
+}
+
+impl ServiceSpan {
+    /// Span of departures given relative to the start of the service day. They
+    /// may exceed 24 hours, e.g., 25:30 is 01:30 on the next day.
+    pub fn on_service_day(
+        service_day: NaiveDate,
+        first_departure: Duration,
+        last_departure: Duration,
+    ) -> Option<Self> {
+        let start = service_day
+            .and_time(NaiveTime::default())
+            .and_local_timezone(Local)
+            .earliest()?;
+        Some(Self {
+            first_departure: start + first_departure,
+            last_departure: start + last_departure,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn departures_after_midnight_are_on_the_next_day() {
+        let service_day = NaiveDate::from_ymd_opt(2024, 3, 8).unwrap();
+        let span = ServiceSpan::on_service_day(
+            service_day,
+            Duration::minutes(5 * 60 + 10),
+            Duration::minutes(25 * 60 + 30),
+        )
+        .expect("service day starts at midnight");
+
+        assert_eq!(span.first_departure.date_naive(), service_day);
+        assert_eq!(
+            span.first_departure.time(),
+            NaiveTime::from_hms_opt(5, 10, 0).unwrap()
+        );
+        assert_eq!(
+            span.last_departure.date_naive(),
+            NaiveDate::from_ymd_opt(2024, 3, 9).unwrap()
+        );
+        assert_eq!(
+            span.last_departure.time(),
+            NaiveTime::from_hms_opt(1, 30, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn rail_kinds_are_inferred_from_categories_and_line_names() {
+        let cases = [
+            ("ICE", "ICE 123", Some(LineType::LongDistanceRail)),
+            ("ic", "IC 2024", Some(LineType::LongDistanceRail)),
+            ("EC", "EC 7", Some(LineType::LongDistanceRail)),
+            ("RE", "RE83", Some(LineType::RegionalRail)),
+            ("RB", "RB 64", Some(LineType::RegionalRail)),
+            ("S", "S1", Some(LineType::SuburbanRail)),
+            ("s", " S 21", Some(LineType::SuburbanRail)),
+            // names without a number are not named after their category.
+            ("Bus", "RE", None),
+            ("erx", "erx RE83", None),
+            ("U", "U3", None),
+            ("", "", None),
+        ];
+        for (index, (category, name, expected)) in cases.into_iter().enumerate() {
+            assert_eq!(
+                LineType::of_rail_category(category),
+                expected,
+                "kind of category of case {}",
+                index
+            );
+            assert_eq!(
+                LineType::of_rail_line_name(name),
+                expected,
+                "kind of line name of case {}",
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn finer_rail_kinds_are_rail_in_v1() {
+        let cases = [
+            (LineType::Rail, LineType::Rail),
+            (LineType::LongDistanceRail, LineType::Rail),
+            (LineType::RegionalRail, LineType::Rail),
+            (LineType::SuburbanRail, LineType::Rail),
+            (LineType::Bus, LineType::Bus),
+            (LineType::SubwayOrMetro, LineType::SubwayOrMetro),
+        ];
+        for (index, (kind, legacy)) in cases.into_iter().enumerate() {
+            assert_eq!(kind.legacy(), legacy, "legacy kind of case {}", index);
+            // the legacy kind selects the line in filters.
+            assert!(kind.matches(&legacy), "legacy filter of case {}", index);
+            assert!(kind.matches(&kind), "exact filter of case {}", index);
+        }
+        assert!(!LineType::Rail.matches(&LineType::RegionalRail));
+        assert!(!LineType::SuburbanRail.matches(&LineType::RegionalRail));
+        assert!(!LineType::Bus.matches(&LineType::Rail));
+    }
+
+    #[test]
+    fn merging_keeps_the_finer_rail_kind() {
+        let cases = [
+            (
+                LineType::RegionalRail,
+                LineType::Rail,
+                LineType::RegionalRail,
+            ),
+            (
+                LineType::Rail,
+                LineType::RegionalRail,
+                LineType::RegionalRail,
+            ),
+            (
+                LineType::RegionalRail,
+                LineType::SuburbanRail,
+                LineType::SuburbanRail,
+            ),
+            (LineType::Bus, LineType::Rail, LineType::Rail),
+            (LineType::RegionalRail, LineType::Bus, LineType::Bus),
+        ];
+        for (index, (kind, other, expected)) in cases.into_iter().enumerate() {
+            assert_eq!(kind.finer(other), expected, "kind of case {}", index);
         }
     }
 }
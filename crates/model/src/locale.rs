@@ -0,0 +1,364 @@
+//! Localized labels of rider-facing enums, for simple clients like displays and
+//! signage, which cannot translate machine-readable values themselves.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    line::LineType,
+    trip::PickupDropOffType,
+    trip_update::{StopTimeStatus, TripStatus},
+};
+
+/// Languages labels are available in. German is the default, as most riders are
+/// German speaking.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    De,
+    En,
+}
+
+impl Locale {
+    /// Locale of a language tag like `de-DE`, if supported.
+    pub fn from_language_tag(tag: &str) -> Option<Self> {
+        let language = tag.trim().split(['-', '_']).next()?;
+        match language.to_lowercase().as_str() {
+            "de" => Some(Self::De),
+            "en" => Some(Self::En),
+            _ => None,
+        }
+    }
+
+    /// The supported locale the client prefers most according to an
+    /// `Accept-Language` header, e.g. `en-US,en;q=0.9,de;q=0.8`.
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        let mut languages = header
+            .split(',')
+            .filter_map(|language| {
+                let mut parts = language.split(';');
+                let tag = parts.next()?;
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |quality| quality.parse::<f64>().ok())?;
+                Some((Self::from_language_tag(tag)?, quality))
+            })
+            .filter(|(_, quality)| *quality > 0.0)
+            .collect::<Vec<_>>();
+        // stable, so that equally preferred languages keep the order of the header.
+        languages.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        languages.first().map(|(locale, _)| *locale)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::De => "de",
+            Self::En => "en",
+        }
+    }
+}
+
+/// A label in every supported locale. A missing translation does not compile.
+#[derive(Debug, Clone, Copy)]
+pub struct Label {
+    pub de: &'static str,
+    pub en: &'static str,
+}
+
+impl Label {
+    pub fn get(self, locale: Locale) -> &'static str {
+        match locale {
+            Locale::De => self.de,
+            Locale::En => self.en,
+        }
+    }
+}
+
+impl PickupDropOffType {
+    pub fn pickup_label(self) -> Label {
+        match self {
+            Self::Regular => Label {
+                de: "Planmäßiger Einstieg",
+                en: "Regularly scheduled pickup",
+            },
+            Self::NotAvailable => Label {
+                de: "Kein Einstieg",
+                en: "No pickup available",
+            },
+            Self::PhoneAgency => Label {
+                de: "Einstieg nach telefonischer Anmeldung",
+                en: "Phone agency to arrange pickup",
+            },
+            Self::CoordinateWithDriver => Label {
+                de: "Einstieg nach Absprache mit dem Fahrpersonal",
+                en: "Coordinate with driver to arrange pickup",
+            },
+        }
+    }
+
+    pub fn drop_off_label(self) -> Label {
+        match self {
+            Self::Regular => Label {
+                de: "Planmäßiger Ausstieg",
+                en: "Regularly scheduled drop off",
+            },
+            Self::NotAvailable => Label {
+                de: "Kein Ausstieg",
+                en: "No drop off available",
+            },
+            Self::PhoneAgency => Label {
+                de: "Ausstieg nach telefonischer Anmeldung",
+                en: "Phone agency to arrange drop off",
+            },
+            Self::CoordinateWithDriver => Label {
+                de: "Ausstieg nach Absprache mit dem Fahrpersonal",
+                en: "Coordinate with driver to arrange drop off",
+            },
+        }
+    }
+}
+
+impl TripStatus {
+    pub fn label(&self) -> Label {
+        match self {
+            Self::Scheduled => Label {
+                de: "Planmäßig",
+                en: "Scheduled",
+            },
+            Self::Unscheduled => Label {
+                de: "Außerplanmäßig",
+                en: "Unscheduled",
+            },
+            Self::Cancelled => Label {
+                de: "Fällt aus",
+                en: "Cancelled",
+            },
+            Self::Added => Label {
+                de: "Zusatzfahrt",
+                en: "Additional trip",
+            },
+            Self::Deleted => Label {
+                de: "Entfällt",
+                en: "Removed",
+            },
+        }
+    }
+}
+
+impl StopTimeStatus {
+    pub fn label(&self) -> Label {
+        match self {
+            Self::Scheduled => Label {
+                de: "Planmäßig",
+                en: "Scheduled",
+            },
+            Self::Cancelled => Label {
+                de: "Halt entfällt",
+                en: "Stop cancelled",
+            },
+            Self::Added => Label {
+                de: "Zusätzlicher Halt",
+                en: "Additional stop",
+            },
+            Self::Unknown => Label {
+                de: "Unbekannt",
+                en: "Unknown",
+            },
+        }
+    }
+}
+
+impl LineType {
+    pub fn label(self) -> Label {
+        match self {
+            Self::TramStreetcarOrLighrail => Label {
+                de: "Straßenbahn",
+                en: "Tram",
+            },
+            Self::SubwayOrMetro => Label {
+                de: "U-Bahn",
+                en: "Subway",
+            },
+            Self::Rail => Label {
+                de: "Zug",
+                en: "Train",
+            },
+            Self::Bus => Label {
+                de: "Bus",
+                en: "Bus",
+            },
+            Self::Ferry => Label {
+                de: "Fähre",
+                en: "Ferry",
+            },
+            Self::CableTram => Label {
+                de: "Kabelstraßenbahn",
+                en: "Cable tram",
+            },
+            Self::AerialLiftOrSuspendedCableCar => Label {
+                de: "Seilbahn",
+                en: "Aerial lift",
+            },
+            Self::Funicular => Label {
+                de: "Standseilbahn",
+                en: "Funicular",
+            },
+            Self::Trolleybus => Label {
+                de: "Oberleitungsbus",
+                en: "Trolleybus",
+            },
+            Self::Monorail => Label {
+                de: "Einschienenbahn",
+                en: "Monorail",
+            },
+            Self::LongDistanceRail => Label {
+                de: "Fernverkehr",
+                en: "Long-distance train",
+            },
+            Self::RegionalRail => Label {
+                de: "Regionalverkehr",
+                en: "Regional train",
+            },
+            Self::SuburbanRail => Label {
+                de: "S-Bahn",
+                en: "Suburban train",
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// All labels by the enum they belong to. Each label function matches
+    /// exhaustively, so a new variant does not compile until it is labelled here.
+    fn labels() -> Vec<(&'static str, Vec<Label>)> {
+        use PickupDropOffType as P;
+        let pickup_types = [
+            P::Regular,
+            P::NotAvailable,
+            P::PhoneAgency,
+            P::CoordinateWithDriver,
+        ];
+        vec![
+            (
+                "pickup types",
+                pickup_types.iter().map(|t| t.pickup_label()).collect(),
+            ),
+            (
+                "drop off types",
+                pickup_types.iter().map(|t| t.drop_off_label()).collect(),
+            ),
+            (
+                "trip status",
+                [
+                    TripStatus::Scheduled,
+                    TripStatus::Unscheduled,
+                    TripStatus::Cancelled,
+                    TripStatus::Added,
+                    TripStatus::Deleted,
+                ]
+                .iter()
+                .map(TripStatus::label)
+                .collect(),
+            ),
+            (
+                "stop time status",
+                [
+                    StopTimeStatus::Scheduled,
+                    StopTimeStatus::Cancelled,
+                    StopTimeStatus::Added,
+                    StopTimeStatus::Unknown,
+                ]
+                .iter()
+                .map(StopTimeStatus::label)
+                .collect(),
+            ),
+            (
+                "line types",
+                [
+                    LineType::TramStreetcarOrLighrail,
+                    LineType::SubwayOrMetro,
+                    LineType::Rail,
+                    LineType::Bus,
+                    LineType::Ferry,
+                    LineType::CableTram,
+                    LineType::AerialLiftOrSuspendedCableCar,
+                    LineType::Funicular,
+                    LineType::Trolleybus,
+                    LineType::Monorail,
+                    LineType::LongDistanceRail,
+                    LineType::RegionalRail,
+                    LineType::SuburbanRail,
+                ]
+                .iter()
+                .map(|t| t.label())
+                .collect(),
+            ),
+        ]
+    }
+
+    #[test]
+    fn every_label_is_translated_to_every_locale() {
+        for (name, labels) in labels() {
+            for locale in [Locale::De, Locale::En] {
+                let texts = labels
+                    .iter()
+                    .map(|label| label.get(locale))
+                    .collect::<Vec<_>>();
+                assert!(
+                    texts.iter().all(|text| !text.trim().is_empty()),
+                    "empty {:?} label of {}: {:?}",
+                    locale,
+                    name,
+                    texts
+                );
+                assert_eq!(
+                    texts.iter().collect::<HashSet<_>>().len(),
+                    texts.len(),
+                    "{:?} labels of {} are not distinct: {:?}",
+                    locale,
+                    name,
+                    texts
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn prefers_the_most_preferred_supported_language() {
+        let cases = [
+            ("de", Some(Locale::De)),
+            ("en", Some(Locale::En)),
+            ("EN-us", Some(Locale::En)),
+            ("de_AT", Some(Locale::De)),
+            ("en-US,en;q=0.9,de;q=0.8", Some(Locale::En)),
+            ("de;q=0.5, en;q=0.8", Some(Locale::En)),
+            ("fr-FR,fr;q=0.9,de;q=0.7,en;q=0.5", Some(Locale::De)),
+            // equally preferred languages keep the order of the header.
+            ("en, de", Some(Locale::En)),
+            ("de;q=0.7, en;q=0.7", Some(Locale::De)),
+            // rejected languages are never chosen.
+            ("en;q=0, de;q=0.1", Some(Locale::De)),
+            ("en;q=0", None),
+            // unparsable qualities skip the language.
+            ("en;q=high, de;q=0.1", Some(Locale::De)),
+            ("fr, it", None),
+            ("*", None),
+            ("", None),
+        ];
+        for (header, expected) in cases {
+            assert_eq!(
+                Locale::from_accept_language(header),
+                expected,
+                "locale of `{}`",
+                header
+            );
+        }
+    }
+}
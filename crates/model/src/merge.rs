@@ -0,0 +1,71 @@
+use chrono::{DateTime, Local};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use utility::id::{HasId, Id};
+
+use crate::origin::Origin;
+
+/// Similarity from which subjects of different origins are merged without review.
+pub const AUTO_MERGE_THRESHOLD: f64 = 0.6;
+
+/// Similarity from which a merge is proposed for review. Below this threshold,
+/// subjects are considered distinct.
+pub const REVIEW_THRESHOLD: f64 = 0.55;
+
+/// Kind of subject a merge decision is about.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum MergeSubject {
+    Stop,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum MergeStatus {
+    /// Merged automatically, because the similarity was high enough.
+    AutoAccepted,
+    /// Not merged (yet). Waiting for someone to confirm or reject the merge.
+    PendingReview,
+    /// Merged after review.
+    Confirmed,
+    /// Not merged after review. The merge will not be proposed again.
+    Rejected,
+}
+
+/// Decision about merging a subject of an origin into an existing subject.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeLogEntry {
+    pub subject: MergeSubject,
+    /// Origin of the subject to be merged.
+    pub origin: Id<Origin>,
+    /// Id of the subject to be merged.
+    pub subject_id: String,
+    /// Original id of the subject to be merged, by which a rejected merge is
+    /// recognized, when the subject is imported again.
+    pub subject_original_id: Option<String>,
+    /// Id of the existing subject, the subject is (to be) merged into.
+    pub candidate_id: String,
+    pub similarity: f64,
+    pub status: MergeStatus,
+    pub created_at: DateTime<Local>,
+    pub decided_at: Option<DateTime<Local>>,
+}
+
+impl HasId for MergeLogEntry {
+    type IdType = i32;
+}
+
+impl MergeStatus {
+    /// Status of a merge with the given similarity, or `None` if the subjects are
+    /// not similar enough to be merged at all.
+    pub fn from_similarity(similarity: f64) -> Option<Self> {
+        if similarity >= AUTO_MERGE_THRESHOLD {
+            Some(Self::AutoAccepted)
+        } else if similarity >= REVIEW_THRESHOLD {
+            Some(Self::PendingReview)
+        } else {
+            None
+        }
+    }
+}
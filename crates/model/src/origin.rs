@@ -1,7 +1,15 @@
-use std::fmt::Debug;
+use std::{
+    error,
+    fmt::{self, Debug},
+};
 
 use serde::Serialize;
-use utility::id::{HasId, Id};
+use utility::{
+    id::{HasId, Id},
+    let_also::LetAlso,
+};
+
+use crate::WithId;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct Origin {
@@ -13,7 +21,96 @@ impl HasId for Origin {
     type IdType = String;
 }
 
+impl Origin {
+    /// Id of the origin with the given name, e.g. `gtfs-nah-sh` for `GTFS NAH.SH`.
+    /// Characters other than ascii letters and digits separate words.
+    pub fn id_from_name(name: &str) -> Id<Origin> {
+        name.to_lowercase()
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>()
+            .join("-")
+            .let_owned(Id::new)
+    }
+
+    /// Checks, that the id is a slug like `gtfs-nah-sh`, i.e., lowercase ascii
+    /// letters and digits in words separated by single dashes.
+    pub fn validate_id(id: &str) -> Result<(), OriginIdError> {
+        let is_valid = !id.is_empty()
+            && id.split('-').all(|word| {
+                !word.is_empty()
+                    && word
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+            });
+        if is_valid {
+            Ok(())
+        } else {
+            Err(OriginIdError::Invalid(id.to_owned()))
+        }
+    }
+
+    /// Key, under which names and ids of distinct origins must not collide. It
+    /// ignores case, punctuation and spacing, so that e.g. `GTFS NAH.SH`,
+    /// `gtfs-nahsh` and `GTFS-NAH-SH` collide.
+    pub fn collision_key(name_or_id: &str) -> String {
+        name_or_id
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_lowercase())
+            .collect()
+    }
+
+    /// The first of the `others`, which `id` or `name` collides with.
+    pub fn find_collision<'a>(
+        id: &Id<Origin>,
+        name: &str,
+        others: &'a [WithId<Origin>],
+    ) -> Option<&'a WithId<Origin>> {
+        let keys = [
+            Self::collision_key(id.raw_ref::<str>()),
+            Self::collision_key(name),
+        ];
+        others.iter().find(|origin| {
+            let other_keys = [
+                Self::collision_key(origin.id.raw_ref::<str>()),
+                Self::collision_key(&origin.content.name),
+            ];
+            keys.iter().any(|key| other_keys.contains(key))
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OriginIdError {
+    /// The id is no slug, see [`Origin::validate_id`].
+    Invalid(String),
+    /// The id or name collides with the one of an existing origin, see
+    /// [`Origin::collision_key`].
+    Collision { id: String, existing: String },
+}
+
+impl error::Error for OriginIdError {}
+
+impl fmt::Display for OriginIdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OriginIdError::Invalid(id) => write!(
+                f,
+                "Invalid origin id '{}', expected lowercase letters and digits separated by single dashes",
+                id
+            ),
+            OriginIdError::Collision { id, existing } => write!(
+                f,
+                "Origin '{}' collides with the existing origin '{}'",
+                id, existing
+            ),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct OriginalIdMapping<S>
 where
     S: HasId,
@@ -23,3 +120,90 @@ where
     pub original_id: String,
     pub id: Id<S>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin(id: &str, name: &str) -> WithId<Origin> {
+        WithId::new(
+            Id::new(id.to_owned()),
+            Origin {
+                name: name.to_owned(),
+                priority: 0,
+            },
+        )
+    }
+
+    #[test]
+    fn ids_are_slugs_of_names() {
+        let cases = [
+            ("GTFS NAH.SH", "gtfs-nah-sh"),
+            ("  Deutsche  Bahn ", "deutsche-bahn"),
+            ("gbfs_donkey-kiel", "gbfs-donkey-kiel"),
+            ("Kieler Förde 2", "kieler-f-rde-2"),
+            ("...", ""),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(
+                Origin::id_from_name(name).raw(),
+                expected,
+                "id of case {}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn only_slugs_are_valid_ids() {
+        let cases = [
+            ("gtfs-nah-sh", true),
+            ("db2", true),
+            ("", false),
+            ("GTFS-NAH-SH", false),
+            ("gtfs--nah", false),
+            ("-gtfs", false),
+            ("gtfs-", false),
+            ("gtfs nah", false),
+            ("gtfs_nah", false),
+        ];
+        for (id, is_valid) in cases {
+            assert_eq!(
+                Origin::validate_id(id),
+                if is_valid {
+                    Ok(())
+                } else {
+                    Err(OriginIdError::Invalid(id.to_owned()))
+                },
+                "validity of case {:?}",
+                id
+            );
+        }
+    }
+
+    #[test]
+    fn collisions_ignore_case_punctuation_and_spacing() {
+        let others = [
+            origin("deutsche-bahn", "Deutsche Bahn"),
+            origin("gtfs-nah-sh", "GTFS NAH.SH"),
+        ];
+        let cases = [
+            ("gtfs-nahsh", "Other", Some("gtfs-nah-sh")),
+            ("other", "GTFS-NAH-SH", Some("gtfs-nah-sh")),
+            ("other", "Deutsche Bahn!", Some("deutsche-bahn")),
+            ("gtfs-nah-sh-2", "GTFS NAH.SH 2", None),
+            ("other", "Other", None),
+        ];
+        for (id, name, expected) in cases {
+            let collision =
+                Origin::find_collision(&Id::new(id.to_owned()), name, &others);
+            assert_eq!(
+                collision.map(|origin| origin.id.raw_ref::<str>()),
+                expected,
+                "collision of case {} {}",
+                id,
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use chrono::Duration;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use utility::{
+    id::{HasId, Id},
+    serde::duration,
+};
+
+use crate::{stop::Stop, WithId, WithOrigin};
+
+/// A level of a station, e.g. the ground floor or a platform level underground.
+/// Ids are those of the feed, as levels are only referenced within an origin.
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Level {
+    /// Position relative to the ground level `0`. Levels below are negative.
+    pub index: f64,
+    pub name: Option<String>,
+}
+
+impl HasId for Level {
+    type IdType = String;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum PathwayMode {
+    Walkway,
+    Stairs,
+    /// Moving sidewalk or travelator.
+    MovingSidewalk,
+    Escalator,
+    Elevator,
+    /// Entering the paid area of the station, which requires a ticket.
+    FareGate,
+    /// Leaving the paid area of the station.
+    ExitGate,
+}
+
+/// A way between two locations of a station, e.g. from an entrance to a
+/// platform. Ids are those of the feed.
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Pathway {
+    pub from_stop_id: Id<Stop>,
+    pub to_stop_id: Id<Stop>,
+    /// Station, the locations belong to.
+    pub station_id: Option<Id<Stop>>,
+    pub from_level_id: Option<Id<Level>>,
+    pub to_level_id: Option<Id<Level>>,
+    pub mode: PathwayMode,
+    /// Whether the pathway can also be used from `to_stop_id` to `from_stop_id`.
+    pub is_bidirectional: bool,
+    /// Length in meters.
+    pub length: Option<f64>,
+    /// Average time to walk through the pathway.
+    #[serde(
+        default,
+        serialize_with = "duration::serialize_option",
+        deserialize_with = "duration::deserialize_option"
+    )]
+    #[schemars(schema_with = "duration::schema_option")]
+    pub traversal_time: Option<Duration>,
+    /// Number of stairs, negative if they lead down from `from_stop_id`.
+    pub stair_count: Option<i32>,
+    /// Ratio of the slope, negative if it leads down from `from_stop_id`.
+    pub max_slope: Option<f64>,
+    /// Minimum width in meters.
+    pub min_width: Option<f64>,
+    /// Text of the signs leading along the pathway.
+    pub signposted_as: Option<String>,
+    /// Text of the signs leading along the pathway in the opposite direction.
+    pub reversed_signposted_as: Option<String>,
+}
+
+impl HasId for Pathway {
+    type IdType = String;
+}
+
+/// Pathways of a station along with the levels they connect.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathwayGraph {
+    pub pathways: Vec<WithOrigin<WithId<Pathway>>>,
+    pub levels: Vec<WithOrigin<WithId<Level>>>,
+}
@@ -1,5 +1,12 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
-use utility::id::HasId;
+use utility::{
+    geo,
+    id::{HasId, Id},
+};
+
+use crate::trip::TripDirection;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShapePoint {
@@ -16,3 +23,62 @@ pub struct Shape {
 impl HasId for Shape {
     type IdType = i32;
 }
+
+impl Shape {
+    /// Length of the path along all points in km.
+    pub fn length_km(&self) -> f64 {
+        self.points
+            .windows(2)
+            .map(|pair| {
+                geo::haversine_distance(
+                    pair[0].latitude,
+                    pair[0].longitude,
+                    pair[1].latitude,
+                    pair[1].longitude,
+                )
+            })
+            .sum()
+    }
+}
+
+/// A shape used by trips of a line in the given direction.
+#[derive(Debug, Clone)]
+pub struct LineShape {
+    pub direction: Option<TripDirection>,
+    pub shape_id: Id<Shape>,
+    pub shape: Shape,
+}
+
+impl LineShape {
+    /// Picks a representative shape per direction, so that a line can be drawn
+    /// as a single geometry. Branches and short turns of a line use shorter
+    /// shapes, so the longest shape covers the most of it.
+    pub fn representatives(shapes: Vec<LineShape>) -> Vec<LineShape> {
+        let mut longest = HashMap::<Option<TripDirection>, (f64, LineShape)>::new();
+        for shape in shapes {
+            let length = shape.shape.length_km();
+            let is_longer = match longest.get(&shape.direction) {
+                // ties are broken by id, so that the choice is stable.
+                Some((longest_length, longest_shape)) => {
+                    (length, -shape.shape_id.raw())
+                        > (*longest_length, -longest_shape.shape_id.raw())
+                }
+                None => true,
+            };
+            if is_longer {
+                longest.insert(shape.direction, (length, shape));
+            }
+        }
+        let mut representatives = longest
+            .into_values()
+            .map(|(_, shape)| shape)
+            .collect::<Vec<_>>();
+        representatives.sort_by_key(|shape| {
+            (
+                shape.direction.map(|direction| direction as u8),
+                shape.shape_id.raw(),
+            )
+        });
+        representatives
+    }
+}
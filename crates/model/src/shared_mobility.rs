@@ -1,7 +1,8 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use utility::geo;
+use utility::geo::{self, GeoPolygon};
 use utility::id::HasId;
+use utility::normalize::prefer_non_blank;
 
 use crate::Mergable;
 use crate::WithDistance;
@@ -16,6 +17,12 @@ pub struct SharedMobilityStation {
     pub capacity: u32,
     pub rental_uris: RentalUris,
     pub status: Option<Status>,
+    /// Area, in which vehicles can be picked up and returned.
+    pub area: Option<GeoPolygon>,
+    pub region_id: Option<String>,
+    /// Whether the station is an area without physical infrastructure.
+    #[serde(default)]
+    pub is_virtual_station: bool,
 }
 
 impl HasId for SharedMobilityStation {
@@ -25,7 +32,7 @@ impl HasId for SharedMobilityStation {
 impl Mergable for SharedMobilityStation {
     fn merge(self, other: Self) -> Self {
         Self {
-            name: other.name,
+            name: prefer_non_blank(self.name, other.name),
             latitude: other.latitude,
             longitude: other.longitude,
             capacity: other.capacity,
@@ -34,7 +41,10 @@ impl Mergable for SharedMobilityStation {
                 ios: other.rental_uris.ios.or(self.rental_uris.ios),
                 web: other.rental_uris.web.or(self.rental_uris.web),
             },
-            status: other.status,
+            status: other.status.or(self.status),
+            area: other.area.or(self.area),
+            region_id: other.region_id.or(self.region_id),
+            is_virtual_station: other.is_virtual_station,
         }
     }
 }
@@ -45,14 +55,51 @@ impl SharedMobilityStation {
         latitude: f64,
         longitude: f64,
     ) -> Option<WithDistance<Self>> {
-        let distance = geo::haversine_distance(
-            latitude,
-            longitude,
-            self.latitude,
-            self.longitude,
-        );
+        // the station is right here, if the point is within its area.
+        let is_within_area = self
+            .area
+            .as_ref()
+            .is_some_and(|area| area.contains(latitude, longitude));
+        let distance = if is_within_area {
+            0.0
+        } else {
+            geo::haversine_distance(
+                latitude,
+                longitude,
+                self.latitude,
+                self.longitude,
+            )
+        };
         Some(WithDistance::new(distance, self))
     }
+
+    /// Share of the capacity taken by available vehicles within [0, 1], or
+    /// `None` if the status is unknown or the station has no capacity, e.g. a
+    /// virtual station.
+    pub fn occupancy_ratio(&self) -> Option<f64> {
+        let status = self.status.as_ref()?;
+        if self.capacity == 0 {
+            return None;
+        }
+        Some((status.num_bikes_available as f64 / self.capacity as f64).min(1.0))
+    }
+
+    /// Whether no vehicle can be returned, as all docks are taken. `None` if the
+    /// status is unknown or the station has no docks.
+    pub fn is_full(&self) -> Option<bool> {
+        let status = self.status.as_ref()?;
+        if self.capacity == 0 {
+            return None;
+        }
+        Some(status.num_docks_available == 0)
+    }
+
+    /// Whether no vehicle is available. `None` if the status is unknown.
+    pub fn is_empty(&self) -> Option<bool> {
+        self.status
+            .as_ref()
+            .map(|status| status.num_bikes_available == 0)
+    }
 }
 
 #[serde_with::skip_serializing_none]
@@ -70,5 +117,140 @@ pub struct RentalUris {
 pub struct Status {
     pub num_bikes_available: u32,
     pub num_docks_available: u32,
+    #[serde(default)]
+    pub num_bikes_disabled: Option<u32>,
+    #[serde(default)]
+    pub num_docks_disabled: Option<u32>,
+    /// Whether the station is installed on the street.
+    #[serde(default)]
+    pub is_installed: Option<bool>,
+    /// Whether vehicles can be rented at the station.
+    #[serde(default)]
+    pub is_renting: Option<bool>,
+    /// Whether vehicles can be returned to the station.
+    #[serde(default)]
+    pub is_returning: Option<bool>,
     // TODO: hier detailierte informationen zu Fahrzeugtypen etc.
 }
+
+impl Status {
+    /// Whether a vehicle can be rented at the station right now. Unknown flags are
+    /// assumed to be set.
+    pub fn is_rentable(&self) -> bool {
+        self.is_installed.unwrap_or(true)
+            && self.is_renting.unwrap_or(true)
+            && self.num_bikes_available > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(
+        num_bikes_available: u32,
+        is_installed: Option<bool>,
+        is_renting: Option<bool>,
+    ) -> Status {
+        Status {
+            num_bikes_available,
+            num_docks_available: 4,
+            num_bikes_disabled: None,
+            num_docks_disabled: None,
+            is_installed,
+            is_renting,
+            is_returning: None,
+        }
+    }
+
+    #[test]
+    fn statuses_stored_without_flags_are_deserialized() {
+        let status: Status = serde_json::from_str(
+            r#"{"numBikesAvailable": 2, "numDocksAvailable": 4}"#,
+        )
+        .expect("status is deserialized");
+        assert_eq!(
+            (
+                status.num_bikes_disabled,
+                status.num_docks_disabled,
+                status.is_installed,
+                status.is_renting,
+                status.is_returning,
+            ),
+            (None, None, None, None, None)
+        );
+        assert!(status.is_rentable());
+    }
+
+    #[test]
+    fn stations_are_rentable_with_vehicles_unless_flagged_otherwise() {
+        let cases = [
+            (status(2, Some(true), Some(true)), true),
+            (status(2, None, None), true),
+            (status(0, Some(true), Some(true)), false),
+            (status(2, Some(false), Some(true)), false),
+            (status(2, Some(true), Some(false)), false),
+            (status(2, None, Some(false)), false),
+        ];
+        for (index, (status, expected)) in cases.into_iter().enumerate() {
+            assert_eq!(status.is_rentable(), expected, "rentable of case {}", index);
+        }
+    }
+
+    fn station(
+        capacity: u32,
+        available: Option<(u32, u32)>,
+    ) -> SharedMobilityStation {
+        SharedMobilityStation {
+            name: "Kiel Hbf".to_owned(),
+            latitude: 54.3154,
+            longitude: 10.1317,
+            capacity,
+            rental_uris: RentalUris {
+                android: None,
+                ios: None,
+                web: None,
+            },
+            status: available.map(|(bikes, docks)| Status {
+                num_docks_available: docks,
+                ..status(bikes, None, None)
+            }),
+            area: None,
+            region_id: None,
+            is_virtual_station: false,
+        }
+    }
+
+    #[test]
+    fn occupancy_is_derived_from_status_and_capacity() {
+        let cases = [
+            // full, all docks are taken.
+            (station(8, Some((8, 0))), Some(1.0), Some(true), Some(false)),
+            // empty, no vehicle is available.
+            (station(8, Some((0, 8))), Some(0.0), Some(false), Some(true)),
+            (
+                station(8, Some((2, 6))),
+                Some(0.25),
+                Some(false),
+                Some(false),
+            ),
+            // more vehicles than capacity, e.g. parked next to the docks.
+            (
+                station(8, Some((10, 0))),
+                Some(1.0),
+                Some(true),
+                Some(false),
+            ),
+            // unknown, without a status.
+            (station(8, None), None, None, None),
+            // virtual stations have no capacity.
+            (station(0, Some((3, 0))), None, None, Some(false)),
+            (station(0, Some((0, 0))), None, None, Some(true)),
+        ];
+        for (index, (station, ratio, full, empty)) in cases.into_iter().enumerate() {
+            assert_eq!(station.occupancy_ratio(), ratio, "ratio of case {}", index);
+            assert_eq!(station.is_full(), full, "full of case {}", index);
+            assert_eq!(station.is_empty(), empty, "empty of case {}", index);
+        }
+    }
+}
@@ -1,5 +1,6 @@
-use std::cmp;
+use std::{cmp, fmt, str::FromStr};
 
+use chrono::{DateTime, Local, NaiveDate};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use utility::{
@@ -11,6 +12,22 @@ use utility::{
 
 use crate::{ExampleData, Mergable, Subject, WithDistance};
 
+/// How a stop is served according to the schedule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServiceSummary {
+    /// Last date, at which any trip calls at the stop, or `None` if no trip calls
+    /// at the stop at all.
+    pub last_service_date: Option<NaiveDate>,
+    /// Whether all trips calling at the stop today or later only stop on request.
+    pub served_only_on_request: bool,
+}
+
+impl ServiceSummary {
+    pub fn has_service_since(&self, date: NaiveDate) -> bool {
+        self.last_service_date.is_some_and(|last| last >= date)
+    }
+}
+
 #[serde_with::skip_serializing_none]
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
@@ -18,20 +35,68 @@ pub struct StopNameSuggestion {
     #[serde(skip)]
     pub id: Id<Stop>,
     pub name: String,
-    pub latitude: f64,
-    pub longitude: f64,
+    /// `null` for stops without coordinates, e.g. some parent stations.
+    #[serialize_always]
+    pub location: Option<Location>,
+    /// Whether any trip calls at the stop today or later. Stops without future
+    /// service are ranked last.
+    pub has_future_service: bool,
+    /// Parts of the name matching the search pattern as `[start, end)` char
+    /// ranges, for highlighting.
+    pub match_ranges: Vec<(usize, usize)>,
 }
 
 #[serde_with::skip_serializing_none]
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Mergable)]
 #[serde(rename_all = "camelCase")]
 pub struct Stop {
+    #[merge(prefer_non_none)]
     pub name: Option<String>,
+    #[merge(prefer_non_none)]
     pub description: Option<String>,
     #[serde(skip)]
+    #[merge(prefer_non_none)]
     pub parent_id: Option<Id<Stop>>,
+    #[merge(custom = "Mergable::merge")]
     pub location: Option<Location>,
+    #[merge(prefer_non_none)]
     pub platform_code: Option<String>,
+    /// Structured facts about the stop, which are not part of its description.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[merge(concat_dedup)]
+    pub amenities: Vec<StopAmenity>,
+    /// Last modification of the stop by any origin. Maintained by the database.
+    #[serde(skip_deserializing)]
+    #[merge(custom = "std::cmp::max")]
+    pub updated_at: Option<DateTime<Local>>,
+}
+
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum StopAmenity {
+    /// Steam locomotives may enter the station without restrictions.
+    SteamPermission,
+}
+
+impl fmt::Display for StopAmenity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SteamPermission => write!(f, "steamPermission"),
+        }
+    }
+}
+
+impl FromStr for StopAmenity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "steamPermission" => Ok(Self::SteamPermission),
+            other => Err(format!("unknown stop amenity `{}`", other)),
+        }
+    }
 }
 
 impl Stop {
@@ -70,19 +135,15 @@ impl HasId for Stop {
     type IdType = String;
 }
 
-impl Mergable for Stop {
-    fn merge(self, other: Self) -> Self {
-        Stop {
-            name: other.name.or(self.name),
-            description: other.description.or(self.description),
-            parent_id: other.parent_id.or(self.parent_id),
-            location: self.location.merge(other.location),
-            platform_code: other.platform_code.or(self.platform_code),
-        }
-    }
-}
-
 pub const DISTANCE_THRESHOLD_KM: f64 = 0.25;
+/// Weight of the proximity of two stops in their similarity.
+pub const GEO_WEIGHT: f64 = 0.5;
+/// Weight of the similarity of the names of two stops in their similarity.
+pub const NAME_WEIGHT: f64 = 0.3;
+/// Weight of equal platform codes of two stops in their similarity.
+pub const PLATFORM_WEIGHT: f64 = 0.1;
+/// Weight of equal parents of two stops in their similarity.
+pub const PARENT_WEIGHT: f64 = 0.1;
 impl Subject for Stop {
     fn same_subject_as(&self, other: &Self) -> Option<f64> {
         const REPLACE: &[(&str, &[&str])] = &[
@@ -90,10 +151,6 @@ impl Subject for Stop {
             ("bf", &["bahnhof", "bhf"]),
             ("str", &["straße", "street"]),
         ];
-        const GEO_WEIGHT: f64 = 0.5;
-        const NAME_WEIGHT: f64 = 0.3;
-        const PLATFORM_WIGHT: f64 = 0.1;
-        const PARENT_WEIGHT: f64 = 0.1;
 
         // calculate distance between both stops
         let geo_distance =
@@ -162,8 +219,13 @@ impl Subject for Stop {
         let name_similarity = names.map(|names| {
             // calculate distance
             let distance = edit_distance(&names[0], &names[1]);
-            // normalize
-            distance as f64 / cmp::max(names[0].len(), names[1].len()) as f64
+            // normalize, so that equal names are most similar.
+            let length = cmp::max(names[0].len(), names[1].len());
+            if length == 0 {
+                1.0
+            } else {
+                1.0 - distance as f64 / length as f64
+            }
         });
 
         // avoid further caclulation if not enough data to match is available
@@ -174,7 +236,7 @@ impl Subject for Stop {
         // evaluate overall similarty
         let combined = GEO_WEIGHT * geo_similarity.unwrap_or(0.0)
             + NAME_WEIGHT * name_similarity.unwrap_or(0.0)
-            + PLATFORM_WIGHT * platform_similarity
+            + PLATFORM_WEIGHT * platform_similarity
             + PARENT_WEIGHT * parent_similarity;
 
         Some(sigmoid(combined))
@@ -189,6 +251,8 @@ impl ExampleData for Stop {
             parent_id: None,
             location: None,
             platform_code: Some("1".to_owned()),
+            amenities: vec![],
+            updated_at: None,
         }
     }
 }
@@ -211,3 +275,57 @@ impl Mergable for Location {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stop(name: &str, latitude: f64, platform_code: Option<&str>) -> Stop {
+        Stop {
+            name: Some(name.to_owned()),
+            description: None,
+            parent_id: None,
+            location: Some(Location {
+                latitude,
+                longitude: 10.006,
+                address: None,
+            }),
+            platform_code: platform_code.map(str::to_owned),
+            amenities: vec![],
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn similar_names_and_close_stops_are_more_similar() {
+        let hbf = stop("Hamburg Hbf", 53.553, None);
+        let platform = stop("Hamburg Hbf", 53.553, Some("5"));
+        let cases = [
+            // abbreviations count as the same name.
+            (
+                stop("Hamburg Hauptbahnhof", 53.553, None),
+                &hbf,
+                stop("Hamburg Steintorwall", 53.553, None),
+            ),
+            (hbf.clone(), &hbf, stop("Hamburg Hbf", 53.554, None)),
+            (platform.clone(), &platform, hbf.clone()),
+        ];
+        for (stop, more_similar, less_similar) in cases {
+            let more = stop.same_subject_as(more_similar);
+            let less = stop.same_subject_as(&less_similar);
+            assert!(more > less, "{:?} vs {:?}", more_similar, less_similar);
+        }
+    }
+
+    #[test]
+    fn distant_stops_and_other_platforms_are_distinct() {
+        let stop_a = stop("Hamburg Hbf", 53.553, Some("5"));
+        let cases = [
+            stop("Hamburg Hbf", 53.56, Some("5")),
+            stop("Hamburg Hbf", 53.553, Some("6")),
+        ];
+        for other in cases {
+            assert_eq!(stop_a.same_subject_as(&other), None, "{:?}", other);
+        }
+    }
+}
@@ -1,39 +1,52 @@
-use chrono::Duration;
+use chrono::{DateTime, Duration, Local};
 use schemars::JsonSchema;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use utility::id::{HasId, Id};
 use utility::serde::duration;
 
 use crate::ExampleData;
-use crate::{calendar::Service, line::Line, stop::Stop, Mergable};
+use crate::{calendar::Service, line::Line, shape::Shape, stop::Stop, Mergable};
 
-#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[derive(Debug, Clone, Serialize, JsonSchema, Mergable)]
+#[serde(rename_all = "camelCase")]
 pub struct Trip {
     #[serde(skip)]
+    #[merge(prefer_other)]
     pub line_id: Id<Line>,
     #[serde(skip)]
+    #[merge(prefer_non_none)]
     pub service_id: Option<Id<Service>>, // TODO: this sould not be optional!
+    #[merge(prefer_non_none)]
     pub headsign: Option<String>,
+    #[merge(prefer_non_none)]
     pub short_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[merge(prefer_non_none)]
+    pub direction: Option<TripDirection>,
+    /// The path the vehicle travels along, if known.
+    #[serde(skip)]
+    #[merge(prefer_non_none)]
+    pub shape_id: Option<Id<Shape>>,
+    /// Stop sequences of different origins are unrelated, so the stops of the
+    /// other origin replace these instead of being merged by sequence.
+    #[merge(prefer_other)]
     pub stops: Vec<StopTime>,
+    /// Periods, in which the trip runs repeatedly instead of once per service day.
+    /// The stop times then only describe the intervals between the stops.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[merge(prefer_other)]
+    pub frequencies: Vec<Frequency>,
+    /// Last modification of the trip by any origin, not including its stop times.
+    /// Maintained by the database.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[merge(custom = "std::cmp::max")]
+    pub updated_at: Option<DateTime<Local>>,
 }
 
 impl HasId for Trip {
     type IdType = String;
 }
 
-impl Mergable for Trip {
-    fn merge(self, other: Self) -> Self {
-        Self {
-            line_id: other.line_id,
-            service_id: other.service_id,
-            headsign: other.headsign.or(self.headsign),
-            short_name: other.short_name.or(self.short_name),
-            stops: other.stops, // TODO: merge strategy
-        }
-    }
-}
-
 impl ExampleData for Trip {
     fn example_data() -> Self {
         Self {
@@ -41,14 +54,30 @@ impl ExampleData for Trip {
             service_id: Some(Id::new(123)),
             headsign: Some("Kiel Hbf".to_owned()),
             short_name: Some("Lübeck-Kiel".to_owned()),
+            direction: Some(TripDirection::Outbound),
+            shape_id: None,
             stops: vec![
                 // TODO!
             ],
+            frequencies: vec![],
+            updated_at: None,
         }
     }
 }
 
+/// Separates the trips of a line by direction, e.g. for time tables.
+/// taken from gtfs, which does not assign a meaning to both directions.
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum TripDirection {
+    Outbound,
+    Inbound,
+}
+
 #[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct StopTime {
     pub stop_sequence: i32,
 
@@ -67,16 +96,344 @@ pub struct StopTime {
     pub departure_time: Option<Duration>,
 
     pub stop_headsign: Option<String>,
+
+    /// `None` if the origin does not know how riders may board.
+    pub pickup_type: Option<PickupDropOffType>,
+
+    /// `None` if the origin does not know how riders may alight.
+    pub drop_off_type: Option<PickupDropOffType>,
+
+    /// The on-demand area served instead of a stop (gtfs flex).
+    pub area_reference: Option<AreaReference>,
+
+    /// Name of the stop as given by the origin of the stop time. It is set by the
+    /// database on write and may lag behind a rename of the stop, until it is
+    /// synced.
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub stop_name: Option<String>,
+}
+
+impl StopTime {
+    /// Whether this stop time serves an on-demand area rather than a stop.
+    pub fn is_on_demand(&self) -> bool {
+        self.area_reference.is_some()
+    }
+
+    /// Whether the vehicle only stops on request ("Bedarfshalt"), i.e. neither
+    /// boarding nor alighting is regularly scheduled, but at least one of them
+    /// can be arranged.
+    pub fn is_on_request(&self) -> bool {
+        let pickup_type = self.pickup_type.unwrap_or_default();
+        let drop_off_type = self.drop_off_type.unwrap_or_default();
+        pickup_type != PickupDropOffType::Regular
+            && drop_off_type != PickupDropOffType::Regular
+            && (pickup_type.is_on_request() || drop_off_type.is_on_request())
+    }
 }
 
 impl Mergable for StopTime {
     fn merge(self, other: Self) -> Self {
+        // the name is the one of the merged stop, known by either origin, if both
+        // reference the same stop.
+        let stop_name = match (&self.stop_id, &other.stop_id) {
+            (_, None) => self.stop_name,
+            (Some(stop_id), Some(other_stop_id)) if stop_id == other_stop_id => {
+                other.stop_name.or(self.stop_name)
+            }
+            _ => other.stop_name,
+        };
         Self {
             stop_sequence: other.stop_sequence,
             stop_id: other.stop_id.or(self.stop_id),
             arrival_time: other.arrival_time.or(self.arrival_time),
             departure_time: other.departure_time.or(self.departure_time),
             stop_headsign: other.stop_headsign.or(self.stop_headsign),
+            pickup_type: other.pickup_type.or(self.pickup_type),
+            drop_off_type: other.drop_off_type.or(self.drop_off_type),
+            area_reference: other.area_reference.or(self.area_reference),
+            stop_name,
+        }
+    }
+}
+
+/// References an area, in which riders may request pickup or drop off.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AreaReference {
+    /// The id of the area as used by the origin.
+    pub id: String,
+    pub kind: AreaKind,
+}
+
+/// taken from gtfs.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum AreaKind {
+    /// A group of stops.
+    LocationGroup,
+    /// A zone described by a GeoJSON location.
+    Location,
+}
+
+/// Whether and how passengers can board or alight at a stop.
+/// taken from gtfs.
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum PickupDropOffType {
+    #[default]
+    Regular,
+    NotAvailable,
+    PhoneAgency,
+    CoordinateWithDriver,
+}
+
+impl PickupDropOffType {
+    /// Whether boarding or alighting has to be arranged with the agency or the
+    /// driver.
+    pub fn is_on_request(self) -> bool {
+        matches!(self, Self::PhoneAgency | Self::CoordinateWithDriver)
+    }
+
+    pub fn pickup_display_text(self) -> String {
+        match self {
+            Self::Regular => "Regularly scheduled pickup.",
+            Self::NotAvailable => "No pickup available.",
+            Self::PhoneAgency => "Must phone agency to arrange pickup.",
+            Self::CoordinateWithDriver => {
+                "Must coordinate with driver to arrange pickup."
+            }
+        }
+        .to_owned()
+    }
+
+    pub fn drop_off_display_text(self) -> String {
+        match self {
+            Self::Regular => "Regularly scheduled drop off.",
+            Self::NotAvailable => "No drop off available.",
+            Self::PhoneAgency => "Must phone agency to arrange drop off.",
+            Self::CoordinateWithDriver => {
+                "Must coordinate with driver to arrange drop off."
+            }
+        }
+        .to_owned()
+    }
+}
+
+/// A period, in which a trip departs from its first stop every `headway`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Frequency {
+    /// First departure as a duration since midnight of the service day.
+    #[serde(serialize_with = "duration::serialize")]
+    #[schemars(schema_with = "duration::schema")]
+    pub start_time: Duration,
+    /// The trip departs before, but not at this time.
+    #[serde(serialize_with = "duration::serialize")]
+    #[schemars(schema_with = "duration::schema")]
+    pub end_time: Duration,
+    #[serde(serialize_with = "duration::serialize")]
+    #[schemars(schema_with = "duration::schema")]
+    pub headway: Duration,
+    /// Whether the departures are scheduled. Otherwise only the headway is kept
+    /// and departures are approximate.
+    pub exact_times: bool,
+}
+
+impl Frequency {
+    /// Departures from the first stop within this period.
+    pub fn departures(&self) -> impl Iterator<Item = Duration> + '_ {
+        let headway = self.headway.num_seconds().max(1);
+        (self.start_time.num_seconds()..self.end_time.num_seconds())
+            .step_by(headway as usize)
+            .map(Duration::seconds)
+    }
+}
+
+/// How the vehicle of a trip and the vehicle of another trip are coupled at a
+/// stop, e.g. a train, whose front part continues to another destination.
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum CouplingKind {
+    /// The vehicles arrive and depart coupled.
+    Coupled,
+    /// The vehicles arrive coupled and depart separately.
+    Split,
+    /// The vehicles arrive separately and depart coupled.
+    Joined,
+}
+
+impl CouplingKind {
+    /// `None`, if the vehicles are neither coupled on arrival nor on departure.
+    pub fn new(arrives_coupled: bool, departs_coupled: bool) -> Option<Self> {
+        match (arrives_coupled, departs_coupled) {
+            (true, true) => Some(Self::Coupled),
+            (true, false) => Some(Self::Split),
+            (false, true) => Some(Self::Joined),
+            (false, false) => None,
+        }
+    }
+}
+
+/// The vehicle of a trip is coupled with the vehicle of another trip at one of
+/// its stops.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TripCoupling {
+    pub trip_id: Id<Trip>,
+    pub stop_sequence: i32,
+    pub stop_id: Option<Id<Stop>>,
+    pub coupled_trip_id: Id<Trip>,
+    /// The headsign of the coupled trip or the name of its last stop.
+    pub coupled_headsign: Option<String>,
+    pub kind: CouplingKind,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stop_time(
+        pickup_type: Option<PickupDropOffType>,
+        drop_off_type: Option<PickupDropOffType>,
+    ) -> StopTime {
+        stop_time_at(1, "stop", pickup_type, drop_off_type)
+    }
+
+    fn stop_time_at(
+        stop_sequence: i32,
+        stop_id: &str,
+        pickup_type: Option<PickupDropOffType>,
+        drop_off_type: Option<PickupDropOffType>,
+    ) -> StopTime {
+        StopTime {
+            stop_sequence,
+            stop_id: Some(Id::new(stop_id.to_owned())),
+            arrival_time: Some(Duration::hours(8)),
+            departure_time: Some(Duration::hours(8)),
+            stop_headsign: None,
+            pickup_type,
+            drop_off_type,
+            area_reference: None,
+            stop_name: None,
+        }
+    }
+
+    #[test]
+    fn merging_keeps_known_pickup_and_drop_off_types() {
+        let drop_off_only = stop_time(
+            Some(PickupDropOffType::NotAvailable),
+            Some(PickupDropOffType::Regular),
+        );
+        let merged = drop_off_only.merge(stop_time(None, None));
+        assert_eq!(merged.pickup_type, Some(PickupDropOffType::NotAvailable));
+        assert_eq!(merged.drop_off_type, Some(PickupDropOffType::Regular));
+    }
+
+    #[test]
+    fn merging_prefers_known_types_of_the_other_stop_time() {
+        let on_request = stop_time(
+            Some(PickupDropOffType::PhoneAgency),
+            Some(PickupDropOffType::CoordinateWithDriver),
+        );
+        let merged =
+            stop_time(None, Some(PickupDropOffType::Regular)).merge(on_request);
+        assert_eq!(merged.pickup_type, Some(PickupDropOffType::PhoneAgency));
+        assert_eq!(
+            merged.drop_off_type,
+            Some(PickupDropOffType::CoordinateWithDriver)
+        );
+        assert!(merged.is_on_request());
+    }
+
+    #[test]
+    fn unknown_types_are_not_on_request() {
+        assert!(!stop_time(None, None).is_on_request());
+        assert!(
+            !stop_time(None, Some(PickupDropOffType::PhoneAgency)).is_on_request()
+        );
+        assert!(stop_time(
+            Some(PickupDropOffType::NotAvailable),
+            Some(PickupDropOffType::PhoneAgency)
+        )
+        .is_on_request());
+    }
+
+    #[test]
+    fn merging_trips_keeps_the_stops_of_one_origin() {
+        let trip = |stops: Vec<StopTime>| Trip {
+            line_id: Id::new("line".to_owned()),
+            service_id: None,
+            headsign: None,
+            short_name: None,
+            direction: None,
+            shape_id: None,
+            stops,
+            frequencies: vec![],
+            updated_at: None,
+        };
+        // both origins number their stops differently.
+        let old = trip(vec![
+            stop_time_at(1, "a", None, None),
+            stop_time_at(2, "b", None, None),
+            stop_time_at(3, "c", None, None),
+        ]);
+        let new = trip(vec![
+            stop_time_at(10, "a", None, None),
+            stop_time_at(20, "c", None, None),
+        ]);
+        let stops = old
+            .merge(new)
+            .stops
+            .into_iter()
+            .map(|stop| (stop.stop_sequence, stop.stop_id.unwrap().raw()))
+            .collect::<Vec<_>>();
+        assert_eq!(stops, vec![(10, "a".to_owned()), (20, "c".to_owned())]);
+    }
+
+    #[test]
+    fn periods_depart_every_headway_before_their_end_time() {
+        let minutes = |minutes: &[i64]| {
+            minutes
+                .iter()
+                .map(|m| Duration::minutes(*m))
+                .collect::<Vec<_>>()
+        };
+        // start, end and headway in minutes since midnight.
+        let cases = [
+            ((480, 510, 10), minutes(&[480, 490, 500])),
+            ((480, 505, 10), minutes(&[480, 490, 500])),
+            ((480, 481, 10), minutes(&[480])),
+            ((480, 480, 10), minutes(&[])),
+            ((510, 480, 10), minutes(&[])),
+        ];
+        for (index, ((start, end, headway), expected)) in
+            cases.into_iter().enumerate()
+        {
+            let frequency = Frequency {
+                start_time: Duration::minutes(start),
+                end_time: Duration::minutes(end),
+                headway: Duration::minutes(headway),
+                exact_times: true,
+            };
+            assert_eq!(
+                frequency.departures().collect::<Vec<_>>(),
+                expected,
+                "departures of case {}",
+                index
+            );
         }
+        // a headway of zero does not loop forever.
+        let frequency = Frequency {
+            start_time: Duration::seconds(0),
+            end_time: Duration::seconds(3),
+            headway: Duration::zero(),
+            exact_times: false,
+        };
+        assert_eq!(frequency.departures().count(), 3);
     }
 }
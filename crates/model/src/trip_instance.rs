@@ -1,14 +1,17 @@
-use chrono::{DateTime, Local};
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
 use schemars::JsonSchema;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use utility::id::Id;
 
 use crate::{
     agency::Agency,
-    calendar::Service,
+    calendar::{Service, ServiceTag},
     line::Line,
     stop::{Location, Stop},
-    trip::Trip,
+    trip::{CouplingKind, PickupDropOffType, Trip, TripCoupling},
+    trip_update::{StopTimeUpdate, TripUpdate, TripUpdateId},
     WithId,
 };
 
@@ -19,12 +22,92 @@ pub struct TripInstance {
     #[serde(flatten)]
     pub info: TripInstanceInfo,
     pub stops: Vec<StopTimeInstance>,
+    /// Stop time, at which the filters of the instantiation matched. `None`
+    /// without filters.
     pub stop_of_interest: Option<StopTimeInstance>,
+    /// Whether the instance matches the filters of the instantiation, i.e. has a
+    /// stop of interest. Always set without filters.
+    #[serde(skip)]
+    pub matches_filters: bool,
     pub line: Option<WithId<Line>>,
     pub agency: Option<WithId<Agency>>,
+    /// Agencies the line is marketed by besides `agency`, e.g. of codeshares.
+    pub secondary_agencies: Vec<WithId<Agency>>,
+    /// Other trips, whose vehicles are coupled with the one of this trip for a
+    /// part of the way.
+    pub coupled_with: Vec<CoupledTrip>,
 }
 
 impl TripInstance {
+    /// Sets the trips coupled with this one from the couplings of this trip at
+    /// its stops. Stop names are taken from the stops of this trip.
+    pub fn set_couplings<'a>(
+        &mut self,
+        couplings: impl IntoIterator<Item = &'a TripCoupling>,
+    ) {
+        let mut coupled_with: Vec<CoupledTrip> = vec![];
+        for coupling in couplings {
+            if coupling.trip_id != self.info.trip_id {
+                continue;
+            }
+            let idx = match coupled_with
+                .iter()
+                .position(|trip| trip.trip_id == coupling.coupled_trip_id)
+            {
+                Some(idx) => idx,
+                None => {
+                    coupled_with.push(CoupledTrip {
+                        trip_id: coupling.coupled_trip_id.clone(),
+                        headsign: None,
+                        split_stop: None,
+                        join_stop: None,
+                    });
+                    coupled_with.len() - 1
+                }
+            };
+            let coupled = &mut coupled_with[idx];
+            coupled.headsign = coupled
+                .headsign
+                .take()
+                .or_else(|| coupling.coupled_headsign.clone());
+            let stop = CouplingStop {
+                stop_sequence: coupling.stop_sequence,
+                stop_id: coupling.stop_id.clone(),
+                stop_name: self
+                    .stops
+                    .iter()
+                    .find(|stop| stop.stop_sequence == coupling.stop_sequence)
+                    .and_then(|stop| stop.stop_name.clone()),
+            };
+            match coupling.kind {
+                CouplingKind::Split => coupled.split_stop = Some(stop),
+                CouplingKind::Joined => coupled.join_stop = Some(stop),
+                CouplingKind::Coupled => {}
+            }
+        }
+        self.coupled_with = coupled_with;
+    }
+
+    /// Applies the platforms announced by the update to the stops of this trip.
+    pub fn apply_platform_changes(&mut self, update: &TripUpdate) {
+        for stop_time in self
+            .stops
+            .iter_mut()
+            .chain(self.stop_of_interest.iter_mut())
+        {
+            if let Some(stop_update) =
+                update.stop_time_update(stop_time.stop_sequence)
+            {
+                stop_time.apply_platform_change(stop_update);
+            }
+        }
+    }
+
+    /// Whether the service of the trip has any of the given tags.
+    pub fn has_any_tag(&self, tags: &[ServiceTag]) -> bool {
+        self.info.tags.iter().any(|tag| tags.contains(tag))
+    }
+
     pub fn get_stop_time_by_sequence(
         &self,
         stop_sequence: i32,
@@ -37,29 +120,251 @@ impl TripInstance {
         None
     }
 
+    /// Index of the stop the vehicle departed from last, i.e., the vehicle is
+    /// between this stop and the next one. Times of `update` take precedence over
+    /// the schedule. `None` before the departure at the first stop and after the
+    /// arrival at the last stop.
+    pub fn current_segment(
+        &self,
+        update: Option<&TripUpdate>,
+        now: DateTime<Local>,
+    ) -> Option<usize> {
+        let times = self
+            .stops
+            .iter()
+            .map(|stop| {
+                let realtime = update
+                    .and_then(|update| update.stop_time_update(stop.stop_sequence));
+                let arrival = realtime
+                    .and_then(|realtime| realtime.arrival_time)
+                    .or(stop.arrival_time);
+                let departure = realtime
+                    .and_then(|realtime| realtime.departure_time)
+                    .or(stop.departure_time);
+                (arrival.or(departure), departure.or(arrival))
+            })
+            .collect::<Vec<_>>();
+        let first_departure = times.first()?.1?;
+        let last_arrival = times.last()?.0?;
+        if now < first_departure || now >= last_arrival {
+            return None;
+        }
+        times
+            .iter()
+            .rposition(|(_, departure)| departure.is_some_and(|time| time <= now))
+    }
+
     pub fn sorted(mut trips: Vec<TripInstance>) -> Vec<TripInstance> {
         Self::sort(&mut trips);
         trips
     }
 
     pub fn sort(trips: &mut Vec<TripInstance>) {
-        trips.sort_by(|lhs, rhs| {
-            let first = lhs
-                .stop_of_interest
-                .as_ref()
-                .and_then(|soi| soi.departure_time.or(soi.arrival_time));
-            let second = rhs
-                .stop_of_interest
-                .as_ref()
-                .and_then(|soi| soi.departure_time.or(soi.arrival_time));
-            match (first, second) {
-                (Some(first), Some(second)) => first.cmp(&second),
-                (Some(_), None) => std::cmp::Ordering::Less,
-                (None, Some(_)) => std::cmp::Ordering::Greater,
-                _ => std::cmp::Ordering::Equal,
-            }
+        trips.sort_by(|lhs, rhs| match (lhs.sort_time(), rhs.sort_time()) {
+            (Some(first), Some(second)) => first.cmp(&second).then_with(|| {
+                lhs.info
+                    .trip_id
+                    .raw_ref::<str>()
+                    .cmp(rhs.info.trip_id.raw_ref())
+            }),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            _ => Ordering::Equal,
         });
     }
+
+    /// The time at the stop of interest the trip instances are sorted by.
+    fn sort_time(&self) -> Option<DateTime<Local>> {
+        self.stop_of_interest
+            .as_ref()
+            .and_then(|soi| soi.departure_time.or(soi.arrival_time))
+    }
+
+    /// Returns the cursor pointing right after this trip instance.
+    /// Trip instances without a stop of interest can not be paginated.
+    pub fn cursor(&self) -> Option<TripInstanceCursor> {
+        self.sort_time().map(|time| TripInstanceCursor {
+            time,
+            trip_id: self.info.trip_id.clone(),
+        })
+    }
+
+    /// Sorts the given trip instances and returns at most `limit` of them, starting
+    /// right after `after`. If more trip instances are available, the cursor to the
+    /// next page is returned as well.
+    pub fn page(
+        mut trips: Vec<TripInstance>,
+        after: Option<&TripInstanceCursor>,
+        limit: Option<usize>,
+    ) -> (Vec<TripInstance>, Option<TripInstanceCursor>) {
+        Self::sort(&mut trips);
+        if let Some(after) = after {
+            trips.retain(|trip| {
+                trip.cursor()
+                    .map(|cursor| cursor.cmp(after) == Ordering::Greater)
+                    .unwrap_or(false)
+            });
+        }
+        match limit {
+            Some(limit) if trips.len() > limit => {
+                trips.truncate(limit);
+                let next = trips.last().and_then(TripInstance::cursor);
+                (trips, next)
+            }
+            _ => (trips, None),
+        }
+    }
+}
+
+/// Another trip, whose vehicle is coupled with the one of a trip instance, e.g.
+/// the part of a train, which is split from it to continue to another
+/// destination.
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CoupledTrip {
+    pub trip_id: Id<Trip>,
+    pub headsign: Option<String>,
+    /// Where the vehicles are split. `None`, if they stay coupled until the end
+    /// of the trip or it is not known.
+    pub split_stop: Option<CouplingStop>,
+    /// Where the vehicles are joined. `None`, if they are coupled from the start
+    /// of the trip or it is not known.
+    pub join_stop: Option<CouplingStop>,
+}
+
+/// A stop of a trip instance, at which it is coupled with another trip.
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CouplingStop {
+    pub stop_sequence: i32,
+    pub stop_id: Option<Id<Stop>>,
+    pub stop_name: Option<String>,
+}
+
+/// Determines which time at a stop of interest has to lie within the requested
+/// time window for a trip to be of interest.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum WindowMode {
+    /// The trip departs from the stop of interest within the window.
+    /// Trips ending at the stop of interest are never of interest.
+    #[default]
+    DepartBetween,
+
+    /// The trip arrives at the stop of interest within the window.
+    /// Trips starting at the stop of interest are never of interest.
+    ArriveBetween,
+}
+
+/// Position within a sorted list of trip instances.
+///
+/// The cursor consists of the scheduled time at the stop of interest and the trip
+/// id as a tie breaker. Since realtime data is not taken into account here, the
+/// cursor stays valid even if a trip is delayed between two requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TripInstanceCursor {
+    pub time: DateTime<Local>,
+    pub trip_id: Id<Trip>,
+}
+
+impl PartialOrd for TripInstanceCursor {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TripInstanceCursor {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.time
+            .cmp(&other.time)
+            .then_with(|| self.trip_id.raw_ref::<str>().cmp(other.trip_id.raw_ref()))
+    }
+}
+
+impl fmt::Display for TripInstanceCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.time.timestamp(), self.trip_id)
+    }
+}
+
+impl FromStr for TripInstanceCursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (timestamp, trip_id) = s
+            .split_once(':')
+            .ok_or_else(|| format!("invalid cursor '{}'", s))?;
+        let time = timestamp
+            .parse::<i64>()
+            .ok()
+            .and_then(|timestamp| Local.timestamp_opt(timestamp, 0).single())
+            .ok_or_else(|| format!("invalid timestamp in cursor '{}'", s))?;
+        Ok(Self {
+            time,
+            trip_id: Id::new(trip_id.to_owned()),
+        })
+    }
+}
+
+/// Identifies a trip instance across requests and processes, e.g., to match
+/// refreshed realtime data with trip instances a client already shows.
+///
+/// The id is derived from the trip id, the service day and the index of the
+/// instance on that day, which distinguishes instances of frequency based trips
+/// and is 0 otherwise. Clients must treat it as an opaque token.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct TripInstanceId(String);
+
+impl TripInstanceId {
+    pub fn new(trip_id: &Id<Trip>, service_day: NaiveDate, index: u32) -> Self {
+        let plain = format!("{}/{}/{}", service_day.format("%Y%m%d"), index, trip_id);
+        Self(plain.bytes().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    /// Id of the realtime update of the instance.
+    pub fn update_id(&self) -> Option<Id<TripUpdate>> {
+        self.decode().map(|(trip_id, service_day, index)| {
+            Id::new(TripUpdateId::of_instance(trip_id, service_day, index))
+        })
+    }
+
+    /// The trip id, the service day and the index the id was derived from.
+    pub fn decode(&self) -> Option<(Id<Trip>, NaiveDate, u32)> {
+        let bytes = (0..self.0.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(self.0.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<_>>>()?;
+        let plain = String::from_utf8(bytes).ok()?;
+        let mut parts = plain.splitn(3, '/');
+        let service_day = NaiveDate::parse_from_str(parts.next()?, "%Y%m%d").ok()?;
+        let index = parts.next()?.parse().ok()?;
+        let trip_id = parts.next().filter(|trip_id| !trip_id.is_empty())?;
+        Some((Id::new(trip_id.to_owned()), service_day, index))
+    }
+}
+
+impl fmt::Display for TripInstanceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for TripInstanceId {
+    type Err = String;
+
+    /// Only accepts ids, which can be decoded.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = Self(s.to_ascii_lowercase());
+        match id.decode() {
+            Some(_) => Ok(id),
+            None => Err(format!("invalid trip instance id '{}'", s)),
+        }
+    }
 }
 
 // TODO: skip ids when serializing
@@ -70,6 +375,9 @@ pub struct TripInstanceInfo {
     //#[serde(skip)]
     pub trip_id: Id<Trip>,
 
+    /// Stable id of this instance of the trip, see [`TripInstanceId`].
+    pub instance_id: TripInstanceId,
+
     #[serde(skip)]
     pub line_id: Id<Line>,
 
@@ -79,6 +387,9 @@ pub struct TripInstanceInfo {
     pub headsign: Option<String>,
 
     pub short_name: Option<String>,
+
+    /// Tags of the service, e.g., whether the trip only runs on school days.
+    pub tags: Vec<ServiceTag>,
 }
 
 #[serde_with::skip_serializing_none]
@@ -102,4 +413,210 @@ pub struct StopTimeInstance {
     pub interest_flag: bool,
 
     pub location: Option<Location>,
+
+    pub pickup_type: PickupDropOffType,
+
+    pub drop_off_type: PickupDropOffType,
+
+    /// Describes the pickup method, unless it is regularly scheduled.
+    pub pickup_text: Option<String>,
+
+    /// Describes the drop off method, unless it is regularly scheduled.
+    pub drop_off_text: Option<String>,
+
+    /// Service is provided on demand within an area instead of at a stop.
+    pub on_demand: bool,
+
+    /// The vehicle only stops on request, see `StopTime::is_on_request`.
+    pub on_request: bool,
+
+    /// Platform code of the stop, unless a realtime update announced another one.
+    pub platform: Option<String>,
+
+    /// Whether a realtime update moved the stop time to another platform.
+    pub platform_changed: bool,
+}
+
+impl StopTimeInstance {
+    /// The platform announced by the update overrides the one of the stop. It is
+    /// considered changed, if it differs from the platform the source of the update
+    /// scheduled, or, if unknown, from the platform of the stop.
+    pub fn apply_platform_change(&mut self, update: &StopTimeUpdate) {
+        let Some(platform) = &update.platform else {
+            return;
+        };
+        let scheduled = update
+            .scheduled_platform
+            .as_ref()
+            .or(self.platform.as_ref());
+        self.platform_changed =
+            scheduled.is_some_and(|scheduled| scheduled != platform);
+        self.platform = Some(platform.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn departure(trip_id: &str, time: DateTime<Local>) -> TripInstance {
+        let trip_id = Id::new(trip_id.to_owned());
+        TripInstance {
+            info: TripInstanceInfo {
+                instance_id: TripInstanceId::new(&trip_id, time.date_naive(), 0),
+                trip_id,
+                line_id: Id::new("line".to_owned()),
+                service_id: None,
+                headsign: None,
+                short_name: None,
+                tags: vec![],
+            },
+            stops: vec![],
+            stop_of_interest: Some(StopTimeInstance {
+                stop_sequence: 1,
+                stop_id: None,
+                stop_name: None,
+                arrival_time: Some(time),
+                departure_time: Some(time),
+                stop_headsign: None,
+                interest_flag: true,
+                location: None,
+                pickup_type: PickupDropOffType::default(),
+                drop_off_type: PickupDropOffType::default(),
+                pickup_text: None,
+                drop_off_text: None,
+                on_demand: false,
+                on_request: false,
+                platform: None,
+                platform_changed: false,
+            }),
+            matches_filters: true,
+            line: None,
+            agency: None,
+            secondary_agencies: vec![],
+            coupled_with: vec![],
+        }
+    }
+
+    /// Four departures per minute, which share their time, in reverse order.
+    fn dense_departures(count: usize) -> Vec<TripInstance> {
+        let start = Local.with_ymd_and_hms(2024, 6, 3, 8, 0, 0).unwrap();
+        (0..count)
+            .rev()
+            .map(|index| {
+                departure(
+                    &format!("trip-{:02}", index),
+                    start + Duration::minutes(index as i64 / 4),
+                )
+            })
+            .collect()
+    }
+
+    fn trip_ids(trips: &[TripInstance]) -> Vec<String> {
+        trips.iter().map(|trip| trip.info.trip_id.raw()).collect()
+    }
+
+    /// Pages through the departures, passing the cursors on as strings like
+    /// clients do.
+    fn page_through(trips: &[TripInstance], limit: usize) -> Vec<Vec<String>> {
+        let mut pages = vec![];
+        let mut after: Option<TripInstanceCursor> = None;
+        loop {
+            let (page, next) =
+                TripInstance::page(trips.to_vec(), after.as_ref(), Some(limit));
+            pages.push(trip_ids(&page));
+            let Some(next) = next else {
+                return pages;
+            };
+            after = Some(next.to_string().parse().expect("cursor is parsed"));
+        }
+    }
+
+    #[test]
+    fn pages_through_dense_departures() {
+        let trips = dense_departures(60);
+        let pages = page_through(&trips, 7);
+        assert_eq!(pages.len(), 9);
+        assert!(pages[..8].iter().all(|page| page.len() == 7));
+        assert_eq!(pages[8].len(), 4);
+        assert_eq!(
+            pages.concat(),
+            trip_ids(&TripInstance::sorted(trips)),
+            "every departure is paged once in order"
+        );
+
+        let (all, next) = TripInstance::page(dense_departures(60), None, None);
+        assert_eq!(all.len(), 60);
+        assert!(next.is_none());
+        let (exact, next) = TripInstance::page(dense_departures(7), None, Some(7));
+        assert_eq!(exact.len(), 7);
+        assert!(next.is_none(), "no further page without further departures");
+    }
+
+    #[test]
+    fn cursors_stay_valid_when_departures_disappear() {
+        let trips = dense_departures(20);
+        let (first, next) = TripInstance::page(trips.clone(), None, Some(5));
+        let next = next.expect("departures continue");
+        assert_eq!(next.trip_id.raw(), "trip-04");
+
+        // the last departure of the page is cancelled before the next request.
+        let remaining = trips
+            .into_iter()
+            .filter(|trip| trip.info.trip_id.raw() != "trip-04")
+            .collect::<Vec<_>>();
+        let (second, _) = TripInstance::page(remaining, Some(&next), Some(5));
+        assert_eq!(trip_ids(&first)[4], "trip-04");
+        assert_eq!(
+            trip_ids(&second),
+            ["trip-05", "trip-06", "trip-07", "trip-08", "trip-09"]
+        );
+    }
+
+    #[test]
+    fn departures_without_a_stop_of_interest_are_not_paged() {
+        let start = Local.with_ymd_and_hms(2024, 6, 3, 8, 0, 0).unwrap();
+        let mut unfiltered = departure("unfiltered", start);
+        unfiltered.stop_of_interest = None;
+        let trips = vec![unfiltered, departure("filtered", start)];
+        let (page, _) = TripInstance::page(trips.clone(), None, None);
+        assert_eq!(trip_ids(&page), ["filtered", "unfiltered"]);
+        let after = departure("a", start).cursor().unwrap();
+        let (page, _) = TripInstance::page(trips, Some(&after), None);
+        assert_eq!(trip_ids(&page), ["filtered"]);
+        assert!("no-timestamp".parse::<TripInstanceCursor>().is_err());
+        assert!("noon:trip".parse::<TripInstanceCursor>().is_err());
+    }
+
+    #[test]
+    fn instance_ids_are_stable_and_round_trip() {
+        let trip_id = Id::new("kvg:11".to_owned());
+        let day = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let id = TripInstanceId::new(&trip_id, day, 0);
+        // the id must not change between releases, as clients keep it.
+        assert_eq!(id.to_string(), "32303234303630332f302f6b76673a3131");
+        assert_eq!(id, TripInstanceId::new(&trip_id, day, 0));
+        assert_eq!(id.decode(), Some((trip_id.clone(), day, 0)));
+        assert_eq!(id.to_string().to_uppercase().parse(), Ok(id.clone()));
+
+        let others = [
+            TripInstanceId::new(&trip_id, day, 1),
+            TripInstanceId::new(&trip_id, day.succ_opt().unwrap(), 0),
+            TripInstanceId::new(&Id::new("kvg:12".to_owned()), day, 0),
+        ];
+        for other in others.iter() {
+            assert_ne!(&id, other);
+            assert_eq!(other.to_string().parse().as_ref(), Ok(other));
+        }
+
+        for invalid in ["", "3", "zz", "32303234", "6b76673a3131"] {
+            assert!(
+                invalid.parse::<TripInstanceId>().is_err(),
+                "{} is rejected",
+                invalid
+            );
+        }
+    }
 }
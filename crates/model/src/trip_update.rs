@@ -1,8 +1,8 @@
-use chrono::{DateTime, Local, NaiveDate};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime};
 use serde::{Deserialize, Serialize};
 use utility::id::{HasId, Id};
 
-use crate::{trip::Trip, Mergable};
+use crate::{stop::Stop, trip::Trip, Mergable};
 
 #[serde_with::skip_serializing_none]
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,11 +16,15 @@ pub enum TripStatus {
 }
 
 #[serde_with::skip_serializing_none]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Mergable)]
 #[serde(rename_all = "camelCase")]
 pub struct TripUpdate {
+    // TODO: merge appropriate!!
+    #[merge(prefer_other)]
     pub status: TripStatus,
+    #[merge(prefer_other)]
     pub stops: Vec<StopTimeUpdate>,
+    #[merge(prefer_non_none)]
     pub timestamp: Option<DateTime<Local>>,
 }
 
@@ -30,20 +34,51 @@ pub struct TripUpdate {
 pub struct TripUpdateId {
     pub trip_id: Id<Trip>,
     pub trip_start_date: NaiveDate,
+    /// Index of the departure of a frequency based trip on the service day, as
+    /// its instances share the trip and service day. `0` for other trips.
+    #[serde(default)]
+    pub instance: u32,
 }
 
 impl TripUpdateId {
     pub fn new(trip_id: Id<Trip>, trip_start_date: NaiveDate) -> Self {
+        Self::of_instance(trip_id, trip_start_date, 0)
+    }
+
+    pub fn of_instance(
+        trip_id: Id<Trip>,
+        trip_start_date: NaiveDate,
+        instance: u32,
+    ) -> Self {
         Self {
             trip_id,
             trip_start_date,
+            instance,
         }
     }
 }
 
-impl Mergable for TripUpdate {
-    fn merge(self, other: Self) -> Self {
-        other // TODO: merge appropriate!!
+impl TripUpdate {
+    /// Update of the stop time with the given scheduled stop sequence.
+    pub fn stop_time_update(&self, stop_sequence: i32) -> Option<&StopTimeUpdate> {
+        self.stops
+            .iter()
+            .find(|stop| stop.scheduled_stop_sequence == Some(stop_sequence))
+    }
+
+    /// Whether the update may belong to the trip instance of the service day.
+    /// Updates announced more than `horizon` before the service day began are
+    /// stale, e.g. left over from another day of a trip, whose id is reused daily.
+    /// Updates without timestamp are kept.
+    pub fn is_valid_for(&self, service_day: NaiveDate, horizon: Duration) -> bool {
+        let Some(timestamp) = self.timestamp else {
+            return true;
+        };
+        service_day
+            .and_time(NaiveTime::MIN)
+            .and_local_timezone(Local)
+            .earliest()
+            .is_none_or(|start| timestamp >= start - horizon)
     }
 }
 
@@ -52,7 +87,7 @@ impl HasId for TripUpdate {
 }
 
 #[serde_with::skip_serializing_none]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub enum StopTimeStatus {
     Scheduled,
@@ -67,7 +102,55 @@ pub enum StopTimeStatus {
 pub struct StopTimeUpdate {
     //pub stop_sequence: i32,
     pub scheduled_stop_sequence: Option<i32>,
+    /// Stop the update applies to. Allows finding updates of trips, which are not
+    /// part of the schedule, by stop.
+    #[serde(default)]
+    pub stop_id: Option<Id<Stop>>,
     pub arrival_time: Option<DateTime<Local>>,
     pub departure_time: Option<DateTime<Local>>,
     pub status: StopTimeStatus,
+    /// Platform the vehicle is expected at, as announced by the update.
+    pub platform: Option<String>,
+    /// Platform according to the schedule of the source of the update. Not
+    /// necessarily the platform of the stop.
+    pub scheduled_platform: Option<String>,
+}
+
+impl StopTimeUpdate {
+    /// Whether both updates announce the same times, status and platform, i.e.
+    /// whether replacing one with the other would not change anything for
+    /// passengers.
+    pub fn is_unchanged_by(&self, other: &Self) -> bool {
+        self.arrival_time == other.arrival_time
+            && self.departure_time == other.departure_time
+            && self.status == other.status
+            && self.platform == other.platform
+    }
+
+    /// Whether the announced platform differs from the scheduled one.
+    pub fn platform_changed(&self) -> bool {
+        matches!(
+            (&self.platform, &self.scheduled_platform),
+            (Some(platform), Some(scheduled)) if platform != scheduled
+        )
+    }
+}
+
+/// A delay of a stop time, as it was announced at some point in time. Unlike
+/// `StopTimeUpdate`, which only holds the latest prognosis, these are kept to
+/// look into the punctuality of a trip afterwards.
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoricDelay {
+    pub scheduled_stop_sequence: i32,
+    pub stop_id: Option<Id<Stop>>,
+    /// When the delay was announced.
+    pub timestamp: DateTime<Local>,
+    pub arrival_time: Option<DateTime<Local>>,
+    pub departure_time: Option<DateTime<Local>>,
+    /// Cause of the delay in the words of the source.
+    pub cause: Option<String>,
+    /// System, which announced the delay.
+    pub source: Option<String>,
 }
@@ -0,0 +1,7 @@
+//! Compile errors of the derive macros, which are expected in `tests/ui`.
+
+#[test]
+fn fields_without_merge_strategy_are_rejected() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/missing_merge_strategy.rs");
+}
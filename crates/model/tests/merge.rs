@@ -0,0 +1,298 @@
+//! Merging never replaces a value of one origin with a missing one of another.
+//! Every combination of present and missing fields of two values is merged, and
+//! each field present in either value has to be present in the result.
+//!
+//! Values are given as read from the database, where blank optional strings are
+//! already `None`. Required strings are blank, if missing.
+
+use chrono::{Duration, Local, TimeZone};
+use model::{
+    agency::Agency,
+    fixtures::{self, StopBuilder, TripBuilder},
+    line::{Line, LineType},
+    shared_mobility::{RentalUris, SharedMobilityStation},
+    stop::Stop,
+    trip::{
+        AreaKind, AreaReference, PickupDropOffType, StopTime, Trip, TripDirection,
+    },
+    trip_update::{TripStatus, TripUpdate},
+    Mergable,
+};
+use utility::{geo::GeoPolygon, id::Id};
+
+/// A field, which may be missing: whether it is present and how to remove it.
+struct Field<T> {
+    name: &'static str,
+    is_present: fn(&T) -> bool,
+    remove: fn(&mut T),
+}
+
+/// A field of type `Option`.
+macro_rules! optional {
+    ($($field:ident).+) => {
+        Field {
+            name: stringify!($($field).+),
+            is_present: |value| value.$($field).+.is_some(),
+            remove: |value| value.$($field).+ = None,
+        }
+    };
+}
+
+/// A required string, which is blank if missing.
+macro_rules! required {
+    ($field:ident) => {
+        Field {
+            name: stringify!($field),
+            is_present: |value| !value.$field.trim().is_empty(),
+            remove: |value| value.$field = " ".to_owned(),
+        }
+    };
+}
+
+/// The value with the fields of the mask removed.
+fn without<T: Clone>(value: &T, fields: &[Field<T>], mask: u32) -> T {
+    let mut value = value.clone();
+    for (index, field) in fields.iter().enumerate() {
+        if mask & (1 << index) != 0 {
+            (field.remove)(&mut value);
+        }
+    }
+    value
+}
+
+/// Merges all combinations of the fields of both values and asserts, that the
+/// fields present in either value are present in the result.
+fn assert_keeps_present_fields<T>(value: T, other: T, fields: &[Field<T>])
+where
+    T: Mergable + Clone,
+{
+    let combinations = 1 << fields.len();
+    for mask in 0..combinations {
+        for other_mask in 0..combinations {
+            let value = without(&value, fields, mask);
+            let other = without(&other, fields, other_mask);
+            let is_present = fields
+                .iter()
+                .map(|field| (field.is_present)(&value) || (field.is_present)(&other))
+                .collect::<Vec<_>>();
+            let merged = value.merge(other);
+            for (field, is_present) in fields.iter().zip(is_present) {
+                assert_eq!(
+                    (field.is_present)(&merged),
+                    is_present,
+                    "{} of {} merged with {}",
+                    field.name,
+                    mask,
+                    other_mask
+                );
+            }
+        }
+    }
+}
+
+fn agency(name: &str) -> Agency {
+    Agency {
+        name: name.to_owned(),
+        website: format!("https://{}.de", name),
+        phone_number: Some("+49 431 123".to_owned()),
+        email: Some(format!("info@{}.de", name)),
+        fare_url: Some(format!("https://{}.de/fares", name)),
+    }
+}
+
+fn line(name: &str, kind: LineType) -> Line {
+    Line {
+        name: Some(name.to_owned()),
+        kind,
+        agency_id: Some(Id::new(format!("agency-{}", name))),
+        secondary_agency_ids: vec![],
+        updated_at: Some(Local.with_ymd_and_hms(2024, 10, 7, 8, 0, 0).unwrap()),
+    }
+}
+
+fn stop(name: &str) -> Stop {
+    Stop {
+        updated_at: Some(Local.with_ymd_and_hms(2024, 10, 7, 8, 0, 0).unwrap()),
+        ..StopBuilder::new(name)
+            .description(&format!("{} description", name))
+            .parent("station")
+            .at(54.3141, 10.1318)
+            .address(&format!("{} 1, Kiel", name))
+            .platform("1")
+            .build()
+    }
+}
+
+fn stop_time(headsign: &str) -> StopTime {
+    StopTime {
+        departure_time: Some(Duration::hours(8) + Duration::minutes(1)),
+        stop_headsign: Some(headsign.to_owned()),
+        drop_off_type: Some(PickupDropOffType::PhoneAgency),
+        area_reference: Some(AreaReference {
+            id: "area".to_owned(),
+            kind: AreaKind::Location,
+        }),
+        stop_name: Some("Kiel Hbf".to_owned()),
+        ..fixtures::stop_time(1, "stop", 8 * 60)
+    }
+}
+
+fn trip(headsign: &str) -> Trip {
+    Trip {
+        short_name: Some(format!("{} 100", headsign)),
+        direction: Some(TripDirection::Outbound),
+        shape_id: Some(Id::new(1)),
+        updated_at: Some(Local.with_ymd_and_hms(2024, 10, 7, 8, 0, 0).unwrap()),
+        ..TripBuilder::new("line", 0, 0)
+            .service(1)
+            .headsign(headsign)
+            .build()
+    }
+}
+
+fn station(name: &str) -> SharedMobilityStation {
+    SharedMobilityStation {
+        name: name.to_owned(),
+        latitude: 54.3141,
+        longitude: 10.1318,
+        capacity: 10,
+        rental_uris: RentalUris {
+            android: Some(format!("{}://android", name)),
+            ios: Some(format!("{}://ios", name)),
+            web: Some(format!("https://{}.de", name)),
+        },
+        status: Some(
+            serde_json::from_value(serde_json::json!({
+                "numBikesAvailable": 3,
+                "numDocksAvailable": 7,
+            }))
+            .expect("status is valid"),
+        ),
+        area: Some(GeoPolygon { polygons: vec![] }),
+        region_id: Some("kiel".to_owned()),
+        is_virtual_station: false,
+    }
+}
+
+#[test]
+fn agencies_keep_present_fields() {
+    assert_keeps_present_fields(
+        agency("kvg"),
+        agency("sfk"),
+        &[
+            required!(name),
+            required!(website),
+            optional!(phone_number),
+            optional!(email),
+            optional!(fare_url),
+        ],
+    );
+}
+
+#[test]
+fn lines_keep_present_fields() {
+    assert_keeps_present_fields(
+        line("RE83", LineType::RegionalRail),
+        line("RE 83", LineType::Rail),
+        &[optional!(name), optional!(agency_id), optional!(updated_at)],
+    );
+}
+
+#[test]
+fn stops_keep_present_fields() {
+    assert_keeps_present_fields(
+        stop("Kiel Hbf"),
+        stop("Kiel Hauptbahnhof"),
+        &[
+            optional!(name),
+            optional!(description),
+            optional!(parent_id),
+            optional!(location),
+            optional!(platform_code),
+            optional!(updated_at),
+        ],
+    );
+    let location = stop("Kiel Hbf").location.expect("stop has a location");
+    assert_keeps_present_fields(location.clone(), location, &[optional!(address)]);
+}
+
+#[test]
+fn stop_times_keep_present_fields() {
+    // both reference the same stop, so that either name is the one of the stop.
+    // the name is only known along with the stop.
+    let stop = Field {
+        name: "stop_id",
+        is_present: |stop_time: &StopTime| stop_time.stop_id.is_some(),
+        remove: |stop_time| {
+            stop_time.stop_id = None;
+            stop_time.stop_name = None;
+        },
+    };
+    assert_keeps_present_fields(
+        stop_time("Kiel Hbf"),
+        stop_time("Kiel"),
+        &[
+            stop,
+            optional!(arrival_time),
+            optional!(departure_time),
+            optional!(stop_headsign),
+            optional!(pickup_type),
+            optional!(drop_off_type),
+            optional!(area_reference),
+            optional!(stop_name),
+        ],
+    );
+}
+
+#[test]
+fn trips_keep_present_fields() {
+    assert_keeps_present_fields(
+        trip("Kiel Hbf"),
+        trip("Kiel"),
+        &[
+            optional!(service_id),
+            optional!(headsign),
+            optional!(short_name),
+            optional!(direction),
+            optional!(shape_id),
+            optional!(updated_at),
+        ],
+    );
+}
+
+#[test]
+fn trip_updates_keep_present_fields() {
+    let update = TripUpdate {
+        status: TripStatus::Scheduled,
+        stops: vec![],
+        timestamp: Some(Local.with_ymd_and_hms(2024, 10, 7, 8, 0, 0).unwrap()),
+    };
+    assert_keeps_present_fields(update.clone(), update, &[optional!(timestamp)]);
+}
+
+#[test]
+fn shared_mobility_stations_keep_present_fields() {
+    assert_keeps_present_fields(
+        station("sprottenflotte"),
+        station("donkey"),
+        &[
+            required!(name),
+            optional!(rental_uris.android),
+            optional!(rental_uris.ios),
+            optional!(rental_uris.web),
+            optional!(status),
+            optional!(area),
+            optional!(region_id),
+        ],
+    );
+}
+
+#[test]
+fn stop_names_of_other_stops_are_not_kept() {
+    let mut other = stop_time("Kiel");
+    other.stop_id = Some(Id::new("other stop".to_owned()));
+    other.stop_name = None;
+    let merged = stop_time("Kiel Hbf").merge(other);
+    assert_eq!(merged.stop_id, Some(Id::new("other stop".to_owned())));
+    assert_eq!(merged.stop_name, None);
+}
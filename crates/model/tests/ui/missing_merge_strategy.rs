@@ -0,0 +1,10 @@
+use model::Mergable;
+
+#[derive(Mergable)]
+struct Stop {
+    #[merge(prefer_non_none)]
+    name: Option<String>,
+    platform_code: Option<String>,
+}
+
+fn main() {}
@@ -0,0 +1,155 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Error, Expr, Field, Fields, LitStr, Path,
+    Result,
+};
+
+/// How a field of two values is merged. The other value has the higher priority.
+enum Strategy {
+    /// The field of the other value.
+    PreferOther,
+    /// The field of the other value, if it is `Some`.
+    PreferNonNone,
+    /// Items of both values, without adding items of the other value, which are
+    /// already present.
+    ConcatDedup,
+    /// Items of both values united by key, see `Mergable` for `Vec`.
+    Keyed,
+    /// `path(self.field, other.field)`.
+    Custom(Path),
+}
+
+impl Strategy {
+    fn of(field: &Field) -> Result<Self> {
+        let mut strategy = None;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("merge"))
+        {
+            attr.parse_nested_meta(|meta| {
+                let next = if meta.path.is_ident("prefer_other") {
+                    Strategy::PreferOther
+                } else if meta.path.is_ident("prefer_non_none") {
+                    Strategy::PreferNonNone
+                } else if meta.path.is_ident("concat_dedup") {
+                    Strategy::ConcatDedup
+                } else if meta.path.is_ident("keyed") {
+                    Strategy::Keyed
+                } else if meta.path.is_ident("custom") {
+                    Strategy::Custom(meta.value()?.parse::<LitStr>()?.parse()?)
+                } else {
+                    return Err(meta.error("unknown merge strategy"));
+                };
+                if strategy.replace(next).is_some() {
+                    return Err(meta.error("a field has exactly one merge strategy"));
+                }
+                Ok(())
+            })?;
+        }
+        // a forgotten field must not silently get some default behavior.
+        strategy.ok_or_else(|| {
+            Error::new_spanned(
+                field,
+                format!(
+                    "missing merge strategy of field `{}`, annotate it with \
+                     `#[merge(prefer_other | prefer_non_none | concat_dedup | \
+                     keyed | custom = \"path\")]`",
+                    field.ident.as_ref().expect("named field"),
+                ),
+            )
+        })
+    }
+
+    fn expr(&self, field: &syn::Ident) -> Expr {
+        match self {
+            Strategy::PreferOther => syn::parse_quote!(other.#field),
+            Strategy::PreferNonNone => {
+                syn::parse_quote!(other.#field.or(self.#field))
+            }
+            Strategy::ConcatDedup => syn::parse_quote!({
+                let mut merged = self.#field;
+                for item in other.#field {
+                    if !merged.contains(&item) {
+                        merged.push(item);
+                    }
+                }
+                merged
+            }),
+            Strategy::Keyed => {
+                syn::parse_quote!(::model::Mergable::merge(self.#field, other.#field))
+            }
+            Strategy::Custom(path) => {
+                syn::parse_quote!(#path(self.#field, other.#field))
+            }
+        }
+    }
+}
+
+/// Derives `model::Mergable` for a struct with named fields. Every field has to
+/// declare its merge strategy with `#[merge(...)]`:
+///
+/// - `prefer_other`: the field of the other value.
+/// - `prefer_non_none`: the field of the other value, if it is `Some`.
+/// - `concat_dedup`: items of both values without duplicates.
+/// - `keyed`: items of both values united by their `model::Keyed` key, items
+///   of the other value win.
+/// - `custom = "path"`: the result of `path(self.field, other.field)`.
+#[proc_macro_derive(Mergable, attributes(merge))]
+pub fn derive_mergable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "Mergable can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "Mergable can only be derived for structs",
+            ))
+        }
+    };
+    let mut errors: Option<Error> = None;
+    let mut merged_fields = vec![];
+    for field in fields {
+        let ident = field.ident.as_ref().expect("named field");
+        match Strategy::of(field) {
+            Ok(strategy) => {
+                let expr = strategy.expr(ident);
+                merged_fields.push(quote!(#ident: #expr));
+            }
+            // report all fields at once.
+            Err(why) => match errors.as_mut() {
+                Some(errors) => errors.combine(why),
+                None => errors = Some(why),
+            },
+        }
+    }
+    if let Some(errors) = errors {
+        return Err(errors);
+    }
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::model::Mergable for #name #ty_generics #where_clause {
+            fn merge(self, other: Self) -> Self {
+                Self {
+                    #(#merged_fields,)*
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,183 @@
+//! Features of the database beyond plain Postgres, e.g. extensions, which
+//! components require or use if available. Missing capabilities are detected
+//! before the components run, instead of failing deep inside them.
+
+use std::fmt;
+
+use serde::Serialize;
+
+/// Required by collectors, which merge the stops they import with existing ones.
+pub const STOP_MERGING: Requirement =
+    Requirement::required(Capability::PgTrgm, "merging stops");
+
+/// Used by the stop search if available. Falls back to substring matches.
+pub const STOP_SEARCH: Requirement =
+    Requirement::optional(Capability::PgTrgm, "fuzzy stop search");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Capability {
+    /// Trigram similarity of texts.
+    PgTrgm,
+    /// Spatial types and indexes.
+    PostGis,
+}
+
+impl Capability {
+    pub const ALL: [Self; 2] = [Self::PgTrgm, Self::PostGis];
+
+    /// Name of the extension providing the capability.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::PgTrgm => "pg_trgm",
+            Self::PostGis => "postgis",
+        }
+    }
+
+    fn flag(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// Set of capabilities available in a database.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities(u8);
+
+impl Capabilities {
+    pub fn none() -> Self {
+        Self(0)
+    }
+
+    pub fn all() -> Self {
+        Capability::ALL
+            .into_iter()
+            .fold(Self::none(), |capabilities, capability| {
+                capabilities.with(capability)
+            })
+    }
+
+    pub fn with(self, capability: Capability) -> Self {
+        Self(self.0 | capability.flag())
+    }
+
+    pub fn has(self, capability: Capability) -> bool {
+        self.0 & capability.flag() != 0
+    }
+
+    /// How stops are searched by name with these capabilities.
+    pub fn search_mode(self) -> SearchMode {
+        if self.has(Capability::PgTrgm) {
+            SearchMode::Trigram
+        } else {
+            SearchMode::Substring
+        }
+    }
+
+    pub fn status(self) -> Vec<CapabilityStatus> {
+        Capability::ALL
+            .into_iter()
+            .map(|capability| CapabilityStatus {
+                capability,
+                extension: capability.extension(),
+                available: self.has(capability),
+            })
+            .collect()
+    }
+}
+
+/// Whether a capability is available, as exposed by the status of the server.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapabilityStatus {
+    pub capability: Capability,
+    pub extension: &'static str,
+    pub available: bool,
+}
+
+/// How stops are searched by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SearchMode {
+    /// Fuzzy matches ranked by similarity.
+    Trigram,
+    /// Only names containing the pattern, ignoring case.
+    Substring,
+}
+
+/// A capability a component needs. A missing optional capability downgrades the
+/// behavior of the component, instead of keeping it from running.
+#[derive(Debug, Clone, Copy)]
+pub struct Requirement {
+    pub capability: Capability,
+    pub optional: bool,
+    /// What the capability is needed for, e.g. `merging stops`.
+    pub purpose: &'static str,
+}
+
+impl Requirement {
+    pub const fn required(capability: Capability, purpose: &'static str) -> Self {
+        Self {
+            capability,
+            optional: false,
+            purpose,
+        }
+    }
+
+    pub const fn optional(capability: Capability, purpose: &'static str) -> Self {
+        Self {
+            capability,
+            optional: true,
+            purpose,
+        }
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "CREATE EXTENSION {} {} for {}",
+            self.capability.extension(),
+            if self.optional {
+                "recommended"
+            } else {
+                "required"
+            },
+            self.purpose
+        )
+    }
+}
+
+/// Checks the requirements of a component, e.g. `collector 'gtfs-schedule'`,
+/// against the available capabilities. Gives the missing optional requirements.
+/// Fails with a message listing the missing required ones.
+pub fn check(
+    component: &str,
+    requirements: &[Requirement],
+    capabilities: Capabilities,
+) -> Result<Vec<Requirement>, String> {
+    let (optional, required): (Vec<_>, Vec<_>) = requirements
+        .iter()
+        .filter(|requirement| !capabilities.has(requirement.capability))
+        .copied()
+        .partition(|requirement| requirement.optional);
+    if required.is_empty() {
+        Ok(optional)
+    } else {
+        Err(format!(
+            "{} can not run: {}.",
+            component,
+            required
+                .iter()
+                .map(|requirement| requirement.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    }
+}
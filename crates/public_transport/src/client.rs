@@ -1,31 +1,58 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    env, slice,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+};
 
 use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime};
 use model::{
     agency::Agency,
-    calendar::{CalendarDate, CalendarWindow, Service},
+    calendar::{
+        CalendarDate, CalendarWindow, HolidayCalendar, HolidayCalendars, Service,
+        ServiceDay,
+    },
+    change::{Change, ChangePosition},
     filter_sort_subjects,
-    line::Line,
+    line::{Line, ServiceSpan},
+    merge::{MergeLogEntry, MergeStatus, MergeSubject},
     merge_all_from,
-    origin::Origin,
+    origin::{Origin, OriginIdError},
+    pathway::{Level, Pathway, PathwayGraph},
+    shape::{LineShape, Shape, ShapePoint},
     shared_mobility::{SharedMobilityStation, Status},
-    stop::{Stop, StopNameSuggestion},
-    trip::{StopTime, Trip},
-    trip_instance::{StopTimeInstance, TripInstance, TripInstanceInfo},
-    trip_update::{StopTimeUpdate, TripStatus, TripUpdate, TripUpdateId},
-    DatabaseEntry, DatabaseEntryCollection, DateTimeRange, Mergable, WithDistance,
-    WithId, WithOrigin,
+    stop::{ServiceSummary, Stop, StopNameSuggestion},
+    trip::{CouplingKind, Frequency, PickupDropOffType, StopTime, Trip},
+    trip_instance::{
+        StopTimeInstance, TripInstance, TripInstanceId, TripInstanceInfo, WindowMode,
+    },
+    trip_update::{
+        HistoricDelay, StopTimeUpdate, TripStatus, TripUpdate, TripUpdateId,
+    },
+    DatabaseEntry, DatabaseEntryCollection, DateTimeRange, Mergable, Provenance,
+    WithDistance, WithId, WithOrigin,
 };
 use serde::Serialize;
-use utility::{id::Id, let_also::LetAlso};
+use tokio::sync::RwLock;
+use utility::{
+    geo,
+    id::{HasId, Id},
+    let_also::LetAlso,
+    text::{match_ranges, sanitize},
+};
 
 use crate::{
+    collector::CollectorHealth,
     database::{
-        AgencyRepo, Database, DatabaseOperations, DatabaseTransaction, LineRepo,
-        MergableRepo, RealtimeRepo, Repo, ServiceRepo, SharedMobilityStationRepo,
-        StopRepo, SubjectRepo, TripRepo,
+        AgencyRepo, ChangeLogRepo, CollectorRepo, Database, DatabaseOperations,
+        DatabaseTransaction, EmptyName, IntegrityRepo, InvalidCoordinates, LineRepo,
+        MergableRepo, MergeAlreadyDecided, MergeLogRepo, Orphans, PathwayRepo,
+        RealtimeRepo, Repo, ServiceRepo, ShapeRepo, SharedMobilityStationRepo,
+        StopRepo, SubjectRepo, TripRepo, UnconfirmedClear,
     },
-    not_found_to_none, RequestError, RequestResult,
+    RequestError, RequestResult,
 };
 
 #[derive(Debug, Clone)]
@@ -33,6 +60,193 @@ pub enum Update {
     TripUpdate { origin: Id<Origin>, id: Id<Trip> },
 }
 
+/// Service spans by line and service day, along with the schedule generation they
+/// were computed in.
+type ServiceSpanCache = HashMap<(Id<Line>, NaiveDate), (u64, Option<ServiceSpan>)>;
+
+/// Representative shapes by line along with the time they were chosen.
+type LineShapeCache = HashMap<Id<Line>, (DateTime<Local>, Vec<LineShape>)>;
+
+/// Stops and coverage by agency, inclusion of secondary attributions and origins,
+/// along with the schedule generation they were computed in.
+type AgencyCoverageCache =
+    HashMap<(Id<Agency>, bool, Vec<Id<Origin>>), (u64, AgencyCoverage)>;
+
+/// Incremented after each schedule import, see [`Client::schedule_imported`].
+/// Process wide, as collectors and the web server use separate clients.
+static SCHEDULE_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// How long representative shapes of a line are reused. Shapes only change on
+/// import, but lines are requested far more often than they are imported.
+const LINE_SHAPE_CACHE_HOURS: i64 = 1;
+
+/// Service spans cached at most. Any line may be requested for any date, so the
+/// cache is cleared once full.
+const SERVICE_SPAN_CACHE_CAPACITY: usize = 10_000;
+
+/// Holiday calendars used to classify services. Loaded from the json file at
+/// `HOLIDAY_CALENDARS_FILE`, if set.
+fn holiday_calendars() -> &'static HolidayCalendars {
+    static CALENDARS: OnceLock<HolidayCalendars> = OnceLock::new();
+    CALENDARS.get_or_init(|| {
+        let Ok(path) = env::var("HOLIDAY_CALENDARS_FILE") else {
+            return HolidayCalendars::default();
+        };
+        std::fs::read_to_string(&path)
+            .map_err(|why| why.to_string())
+            .and_then(|json| {
+                serde_json::from_str(&json).map_err(|why| why.to_string())
+            })
+            .unwrap_or_else(|why| {
+                eprintln!(
+                    "Could not load holiday calendars from '{}': {}",
+                    path, why
+                );
+                HolidayCalendars::default()
+            })
+    })
+}
+
+/// Origins, whose subjects may be located at exactly (0, 0). Configured by the
+/// comma separated `NULL_ISLAND_ORIGINS` environment variable.
+fn null_island_origins() -> &'static [Id<Origin>] {
+    static ORIGINS: OnceLock<Vec<Id<Origin>>> = OnceLock::new();
+    ORIGINS.get_or_init(|| {
+        env::var("NULL_ISLAND_ORIGINS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|origin| !origin.is_empty())
+            .map(|origin| Id::new(origin.to_owned()))
+            .collect()
+    })
+}
+
+/// How many pending changes are appended to the change log per transaction.
+const CHANGE_LOG_FLUSH_BATCH_SIZE: usize = 10_000;
+
+const DEFAULT_REALTIME_UPDATE_HORIZON_HOURS: i64 = 12;
+
+/// How long before its service day an update of a trip instance may have been
+/// announced, before it is considered stale. Configured by the
+/// `REALTIME_UPDATE_HORIZON_HOURS` environment variable.
+fn realtime_update_horizon() -> Duration {
+    static HORIZON: OnceLock<Duration> = OnceLock::new();
+    *HORIZON.get_or_init(|| {
+        env::var("REALTIME_UPDATE_HORIZON_HOURS")
+            .ok()
+            .and_then(|hours| hours.trim().parse().ok())
+            .map(Duration::hours)
+            .unwrap_or(Duration::hours(DEFAULT_REALTIME_UPDATE_HORIZON_HOURS))
+    })
+}
+
+/// Drops the updates of the entry, which are stale for its service day, see
+/// [`TripUpdate::is_valid_for`].
+fn without_stale_updates(
+    mut entry: DatabaseEntry<TripUpdate>,
+) -> DatabaseEntry<TripUpdate> {
+    let service_day = entry.id.raw().trip_start_date;
+    let horizon = realtime_update_horizon();
+    entry
+        .source_data
+        .retain(|update| update.content.is_valid_for(service_day, horizon));
+    entry
+}
+
+/// Maximum lengths of free texts of feeds in chars. Longer texts are truncated.
+/// Configured by the `MAX_NAME_LENGTH`, `MAX_DESCRIPTION_LENGTH` and
+/// `MAX_HEADSIGN_LENGTH` environment variables.
+struct TextLimits {
+    name: usize,
+    description: usize,
+    headsign: usize,
+}
+
+fn text_limits() -> &'static TextLimits {
+    static LIMITS: OnceLock<TextLimits> = OnceLock::new();
+    LIMITS.get_or_init(|| {
+        let limit = |key: &str, default: usize| {
+            env::var(key)
+                .ok()
+                .and_then(|value| value.trim().parse().ok())
+                .filter(|limit| *limit > 0)
+                .unwrap_or(default)
+        };
+        TextLimits {
+            name: limit("MAX_NAME_LENGTH", 200),
+            description: limit("MAX_DESCRIPTION_LENGTH", 1000),
+            headsign: limit("MAX_HEADSIGN_LENGTH", 200),
+        }
+    })
+}
+
+/// Sanitizes an optional free text, see [`sanitize`].
+fn sanitize_option(text: Option<String>, max_chars: usize) -> Option<String> {
+    text.and_then(|text| sanitize(&text, max_chars))
+}
+
+/// The id to continue a page with, if the page is full.
+fn next_page<T>(entries: &[DatabaseEntry<T>], limit: usize) -> Option<Id<T>>
+where
+    T: Serialize + HasId<IdType = String>,
+{
+    entries
+        .last()
+        .filter(|_| entries.len() == limit)
+        .map(|entry| entry.id.clone())
+}
+
+/// Which trips are instantiated and which references are included, see
+/// [`Client::instanciate_trips_include`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TripInstantiationOptions<'a> {
+    /// Whether the arrival or the departure at a stop of interest has to lie
+    /// within the requested range.
+    pub window_mode: WindowMode,
+    /// Trips are only instantiated at these stops, prioritized by position.
+    pub stop_ids_of_interest: Option<&'a [&'a Id<Stop>]>,
+    pub include_stop_names: bool,
+    /// Takes included stop names from the stop times, which saves looking up the
+    /// stops. Locations and platforms of stops are not included then, and names
+    /// may lag behind renamed stops, see [`Server::stop_name_sync`].
+    ///
+    /// [`Server::stop_name_sync`]: crate::server::Server::stop_name_sync
+    pub stop_names_from_stop_times: bool,
+    pub include_lines: bool,
+    /// Implies `include_lines` for now.
+    pub include_agencies: bool,
+}
+
+impl<'a> TripInstantiationOptions<'a> {
+    /// Includes stop names, lines and agencies.
+    pub fn all_references() -> Self {
+        Self {
+            include_stop_names: true,
+            include_lines: true,
+            include_agencies: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// All stops served by an agency, see [`Client::get_agency_coverage`].
+#[derive(Debug, Clone)]
+pub struct AgencyCoverage {
+    pub stops: Vec<WithId<Stop>>,
+    /// Convex hull of the located stops as `(latitude, longitude)` pairs, see
+    /// [`geo::convex_hull`].
+    pub hull: Vec<(f64, f64)>,
+}
+
+/// How a trip is stored, see [`Client::push_trip`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PushTripOptions {
+    /// Deletes other stop times of this origin stored for the trip before, e.g.
+    /// when the stop times given replace all previous ones.
+    pub clear_stop_times: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct Client<D>
 where
@@ -40,6 +254,11 @@ where
 {
     id: String,
     pub database: D,
+    reads_from_replica: bool,
+    service_spans: Arc<RwLock<ServiceSpanCache>>,
+    line_shapes: Arc<RwLock<LineShapeCache>>,
+    agency_coverages: Arc<RwLock<AgencyCoverageCache>>,
+    invalid_coordinates: Arc<AtomicU64>,
 }
 
 impl<D> Client<D>
@@ -53,6 +272,27 @@ where
         Self {
             id: id.into(),
             database,
+            reads_from_replica: false,
+            service_spans: Arc::new(RwLock::new(HashMap::new())),
+            line_shapes: Arc::new(RwLock::new(HashMap::new())),
+            agency_coverages: Arc::new(RwLock::new(HashMap::new())),
+            invalid_coordinates: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Serves read only operations from a read replica, if the database has one.
+    /// Should not be used by collectors, as they need to read their own writes.
+    pub fn read_from_replica(mut self) -> Self {
+        self.reads_from_replica = true;
+        self
+    }
+
+    /// Autocommit connection for read only operations.
+    fn reader(&self) -> D::Autocommit {
+        if self.reads_from_replica {
+            self.database.read()
+        } else {
+            self.database.auto()
         }
     }
 
@@ -60,8 +300,132 @@ where
         Id::new(self.id.clone())
     }
 
+    /// Client of another origin, sharing the database and configuration.
+    /// Allows a single collector instance to manage multiple origins.
+    pub fn for_origin(&self, origin: &Id<Origin>) -> Self {
+        Self {
+            id: origin.raw(),
+            database: self.database.clone(),
+            reads_from_replica: self.reads_from_replica,
+            service_spans: Arc::new(RwLock::new(HashMap::new())),
+            line_shapes: Arc::new(RwLock::new(HashMap::new())),
+            agency_coverages: Arc::new(RwLock::new(HashMap::new())),
+            invalid_coordinates: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Whether subjects of this origin may be stored at the given coordinates.
+    /// Counts and warns about invalid coordinates.
+    fn check_coordinates(
+        &self,
+        latitude: f64,
+        longitude: f64,
+        subject: &str,
+    ) -> bool {
+        let allow_null_island = null_island_origins().contains(&self.origin());
+        if geo::is_valid_coordinate(latitude, longitude, allow_null_island) {
+            return true;
+        }
+        let count = self.invalid_coordinates.fetch_add(1, Ordering::Relaxed) + 1;
+        println!(
+            "Warning: {}::'{}' has invalid coordinates ({}, {}). Dropped {} invalid coordinates so far.",
+            self.id, subject, latitude, longitude, count
+        );
+        false
+    }
+
+    /// Number of invalid coordinates dropped during ingestion by this client.
+    pub fn invalid_coordinate_count(&self) -> u64 {
+        self.invalid_coordinates.load(Ordering::Relaxed)
+    }
+
+    /// Creates the origin with the given name, or updates its priority if it
+    /// already exists. Fails with an [`OriginIdError`], if the name collides with
+    /// another origin, e.g. `GTFS NAHSH` with `GTFS NAH.SH`, as a typo would
+    /// otherwise silently split the data of an origin. A new origin is logged, so
+    /// that one created by mistake can be noticed and renamed. The name of a
+    /// renamed origin gives the renamed one, which is left as is.
+    pub async fn put_origin(
+        &self,
+        name: &str,
+        priority: i32,
+    ) -> RequestResult<Id<Origin>> {
+        let id = Origin::id_from_name(name);
+        Origin::validate_id(id.raw_ref::<str>()).map_err(RequestError::other)?;
+        let mut tx = self.database.transaction().await?;
+        if let Some(renamed) = tx.origin_by_alias(&id).await? {
+            log::warn!(
+                "origin '{}' was renamed to {}, but is still registered by its former name by client {}.",
+                name,
+                renamed,
+                self.id
+            );
+            return Ok(renamed);
+        }
+        let origins = tx.origins().await?;
+        let (existing, others): (Vec<_>, Vec<_>) =
+            origins.into_iter().partition(|origin| origin.id == id);
+        check_origin_collision(&id, name, &others).map_err(RequestError::other)?;
+        tx.put_origin(WithId::new(
+            id.clone(),
+            Origin {
+                name: name.to_owned(),
+                priority,
+            },
+        ))
+        .await?;
+        tx.commit().await?;
+        if existing.is_empty() {
+            log::warn!(
+                "registered new origin '{}' ({}) by client {}.",
+                name,
+                id,
+                self.id
+            );
+        }
+        Ok(id)
+    }
+
+    /// Renames the origin to the given name and the id derived from it, see
+    /// [`Origin::id_from_name`]. All data of the origin is moved to the new id in
+    /// a single transaction. Collectors registering the origin by its former name
+    /// keep getting the renamed origin, see [`Client::put_origin`].
+    pub async fn rename_origin(
+        &self,
+        id: &Id<Origin>,
+        name: &str,
+    ) -> RequestResult<WithId<Origin>> {
+        let new_id = Origin::id_from_name(name);
+        Origin::validate_id(new_id.raw_ref::<str>()).map_err(RequestError::other)?;
+        let mut tx = self.database.transaction().await?;
+        let (origin, others): (Vec<_>, Vec<_>) = tx
+            .origins()
+            .await?
+            .into_iter()
+            .partition(|origin| origin.id == *id);
+        let Some(origin) = origin.into_iter().next() else {
+            return Err(RequestError::NotFound);
+        };
+        check_origin_collision(&new_id, name, &others)
+            .map_err(RequestError::other)?;
+        let renamed = tx
+            .rename_origin(
+                id,
+                WithId::new(
+                    new_id,
+                    Origin {
+                        name: name.to_owned(),
+                        priority: origin.content.priority,
+                    },
+                ),
+            )
+            .await?;
+        tx.commit().await?;
+        Ok(renamed)
+    }
+
     pub async fn get_origins(&self) -> RequestResult<Vec<WithId<Origin>>> {
-        Ok(self.database.auto().origins().await?)
+        Ok(self.reader().origins().await?)
     }
 
     pub async fn get_origin_ids(&self) -> RequestResult<Vec<Id<Origin>>> {
@@ -89,6 +453,44 @@ where
         merge_all_from(values, &default_origin_order)
             .ok_or(crate::RequestError::NotFound)
     }
+
+    /// Orphaned rows, i.e., rows referencing subjects that do not exist.
+    pub async fn get_orphans(&self) -> RequestResult<Vec<Orphans>> {
+        Ok(self.database.auto().orphans().await?)
+    }
+
+    /// Outcome of the health checks of all collector instances.
+    pub async fn get_collector_health(&self) -> RequestResult<Vec<CollectorHealth>> {
+        Ok(self.reader().collector_health().await?)
+    }
+
+    /// Stored rows with coordinates, which would be rejected during ingestion.
+    pub async fn get_invalid_coordinates(
+        &self,
+    ) -> RequestResult<Vec<InvalidCoordinates>> {
+        Ok(self
+            .database
+            .auto()
+            .invalid_coordinates(null_island_origins())
+            .await?)
+    }
+
+    /// Removes stored invalid coordinates, if there are as many as `expected`,
+    /// i.e. as reported by [`Client::get_invalid_coordinates`] before. Otherwise
+    /// nothing is removed and [`UnconfirmedClear`] is returned. Returns the number
+    /// of affected rows.
+    pub async fn clear_invalid_coordinates(
+        &self,
+        expected: u64,
+    ) -> RequestResult<u64> {
+        let mut tx = self.database.transaction().await?;
+        let found = tx.clear_invalid_coordinates(null_island_origins()).await?;
+        if found != expected {
+            return Err(RequestError::other(UnconfirmedClear { expected, found }));
+        }
+        tx.commit().await?;
+        Ok(found)
+    }
 }
 
 impl<D> Client<D>
@@ -112,8 +514,7 @@ where
         &self,
         origins: Vec<Id<Origin>>,
     ) -> RequestResult<Vec<WithId<Agency>>> {
-        self.database
-            .auto()
+        self.reader()
             .get_all()
             .await?
             .merge_all_from(&origins)
@@ -125,17 +526,96 @@ where
         id: Id<Agency>,
         origins: Vec<Id<Origin>>,
     ) -> RequestResult<WithId<Agency>> {
-        let result = self.database.auto().get(id).await?;
+        let result = self.reader().get(id).await?;
         result
             .merge_from(&origins)
             .ok_or(crate::RequestError::NotFound)
     }
 
+    /// Returns at most `limit` stops served by the agency following the stop with
+    /// id `after`, and the id to continue with, if there may be more stops. See
+    /// [`Client::get_stops_page`]. Lines the agency is a secondary agency of count,
+    /// if `include_secondary`.
+    pub async fn get_agency_stops_page(
+        &self,
+        agency_id: &Id<Agency>,
+        include_secondary: bool,
+        after: Option<Id<Stop>>,
+        limit: usize,
+        origins: &[Id<Origin>],
+    ) -> RequestResult<(Vec<WithId<Stop>>, Option<Id<Stop>>)> {
+        let entries = self
+            .reader()
+            .get_page_by_agency_after(
+                agency_id,
+                include_secondary,
+                after,
+                Some(limit),
+                origins,
+            )
+            .await?;
+        let next = next_page(&entries, limit);
+        Ok((entries.merge_all_from(origins), next))
+    }
+
+    /// All stops served by the agency and their convex hull. Results are cached
+    /// per agency until the next schedule import.
+    pub async fn get_agency_coverage(
+        &self,
+        agency_id: &Id<Agency>,
+        include_secondary: bool,
+        origins: &[Id<Origin>],
+    ) -> RequestResult<AgencyCoverage> {
+        let generation = SCHEDULE_GENERATION.load(Ordering::Relaxed);
+        let key = (agency_id.clone(), include_secondary, origins.to_vec());
+        if let Some((computed_in, coverage)) =
+            self.agency_coverages.read().await.get(&key)
+        {
+            if *computed_in == generation {
+                return Ok(coverage.clone());
+            }
+        }
+        let stops = self
+            .reader()
+            .get_page_by_agency_after(
+                agency_id,
+                include_secondary,
+                None,
+                None,
+                origins,
+            )
+            .await?
+            .merge_all_from(origins);
+        let points = stops
+            .iter()
+            .filter_map(|stop| stop.content.location.as_ref())
+            .map(|location| (location.latitude, location.longitude))
+            .collect::<Vec<_>>();
+        let hull = geo::convex_hull(&points);
+        let coverage = AgencyCoverage { stops, hull };
+        let mut cache = self.agency_coverages.write().await;
+        cache.retain(|_, (computed_in, _)| *computed_in == generation);
+        cache.insert(key, (generation, coverage.clone()));
+        Ok(coverage)
+    }
+
     pub async fn push_agency(
         &self,
-        agency: Agency,
+        mut agency: Agency,
         original_id: Option<String>,
     ) -> RequestResult<WithOrigin<WithId<Agency>>> {
+        agency.name = sanitize(&agency.name, text_limits().name)
+            .ok_or_else(|| RequestError::other(EmptyName))?;
+        // normalize before merging, so variants of the same value compare equal.
+        let dropped = agency.normalize();
+        if dropped > 0 {
+            log::warn!(
+                "Dropped {} invalid field(s) of agency '{}' from {}.",
+                dropped,
+                agency.name,
+                self.id
+            );
+        }
         let mut tx = self.database.transaction().await?;
         let agencies_with_same_name = tx.agency_by_name(&agency.name).await?;
         // insert into database
@@ -188,8 +668,7 @@ where
         &self,
         origins: Vec<Id<Origin>>,
     ) -> RequestResult<Vec<WithId<Line>>> {
-        self.database
-            .auto()
+        self.reader()
             .get_all()
             .await?
             .merge_all_from(&origins)
@@ -201,7 +680,7 @@ where
         id: Id<Line>,
         origins: Vec<Id<Origin>>,
     ) -> RequestResult<WithId<Line>> {
-        let result = self.database.auto().get(id).await?;
+        let result = self.reader().get(id).await?;
         result
             .merge_from(&origins)
             .ok_or(crate::RequestError::NotFound)
@@ -209,9 +688,11 @@ where
 
     pub async fn push_line(
         &self,
-        line: Line,
+        mut line: Line,
         original_id: Option<String>,
     ) -> RequestResult<WithOrigin<WithId<Line>>> {
+        line.name = sanitize_option(line.name, text_limits().name);
+        line.normalize();
         // TODO: lines with the same name and agency are currently merged.
         // This causes e.g, all db intercities to count as one line.
         let mut tx = self.database.transaction().await?;
@@ -255,13 +736,102 @@ where
         stop_id: &Id<Stop>,
         origins: &[Id<Origin>],
     ) -> RequestResult<Vec<WithId<Line>>> {
-        self.database
-            .auto()
+        self.reader()
             .get_by_stop_id(stop_id)
             .await?
             .merge_all_from(origins)
             .let_owned(Ok)
     }
+
+    /// Lines at each of the given stops with a single query. Stops without lines
+    /// are omitted.
+    pub async fn get_lines_at_stops(
+        &self,
+        stop_ids: &[&Id<Stop>],
+        origins: &[Id<Origin>],
+    ) -> RequestResult<HashMap<Id<Stop>, Vec<WithId<Line>>>> {
+        if stop_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        Ok(self
+            .reader()
+            .get_by_stop_ids(stop_ids)
+            .await?
+            .into_iter()
+            .map(|(stop_id, lines)| (stop_id, lines.merge_all_from(origins)))
+            .collect())
+    }
+
+    /// Lines, whose names contain the pattern, at most `limit`.
+    pub async fn search_lines<S: Into<String>>(
+        &self,
+        pattern: S,
+        limit: usize,
+        origins: &[Id<Origin>],
+    ) -> RequestResult<Vec<WithId<Line>>> {
+        self.reader()
+            .search_by_name(pattern.into(), limit)
+            .await?
+            .merge_all_from(origins)
+            .let_owned(Ok)
+    }
+
+    /// First and last departure of the line on the given service day. The last
+    /// stops of trips are not departures. Results are cached per line and service
+    /// day until the next schedule import.
+    pub async fn get_line_service_span(
+        &self,
+        id: &Id<Line>,
+        service_day: NaiveDate,
+    ) -> RequestResult<Option<ServiceSpan>> {
+        let generation = SCHEDULE_GENERATION.load(Ordering::Relaxed);
+        let key = (id.clone(), service_day);
+        if let Some((computed_in, span)) = self.service_spans.read().await.get(&key) {
+            if *computed_in == generation {
+                return Ok(*span);
+            }
+        }
+        let span = self
+            .reader()
+            .get_departure_span(id, service_day)
+            .await?
+            .and_then(|(first, last)| {
+                ServiceSpan::on_service_day(service_day, first, last)
+            });
+        let mut cache = self.service_spans.write().await;
+        // spans of past service days are no longer requested
+        let yesterday = Local::now().date_naive() - Duration::days(1);
+        cache.retain(|(_, day), (computed_in, _)| {
+            *day >= yesterday && *computed_in == generation
+        });
+        if cache.len() >= SERVICE_SPAN_CACHE_CAPACITY {
+            cache.clear();
+        }
+        cache.insert(key, (generation, span));
+        Ok(span)
+    }
+
+    /// Representative shapes of the line, one per direction, see
+    /// [`LineShape::representatives`]. Empty, if no trip of the line has a shape.
+    /// Results are cached for [`LINE_SHAPE_CACHE_HOURS`].
+    pub async fn get_line_shapes(
+        &self,
+        id: &Id<Line>,
+    ) -> RequestResult<Vec<LineShape>> {
+        let now = Local::now();
+        let max_age = Duration::hours(LINE_SHAPE_CACHE_HOURS);
+        if let Some((chosen_at, shapes)) = self.line_shapes.read().await.get(id) {
+            if now - *chosen_at < max_age {
+                return Ok(shapes.clone());
+            }
+        }
+        let shapes =
+            LineShape::representatives(self.reader().get_line_shapes(id).await?);
+        let mut cache = self.line_shapes.write().await;
+        cache.retain(|_, (chosen_at, _)| now - *chosen_at < max_age);
+        cache.insert(id.clone(), (now, shapes.clone()));
+        Ok(shapes)
+    }
 }
 
 impl<D> Client<D>
@@ -285,30 +855,70 @@ where
         &self,
         origins: Vec<Id<Origin>>,
     ) -> RequestResult<Vec<WithId<Stop>>> {
-        self.database
-            .auto()
+        self.reader()
             .get_all()
             .await?
             .merge_all_from(&origins)
             .let_owned(|stops| Ok(stops))
     }
 
+    /// Returns at most `limit` stops following the stop with id `after`, and the id
+    /// to continue with, if there may be more stops.
+    pub async fn get_stops_page(
+        &self,
+        after: Option<Id<Stop>>,
+        limit: usize,
+        origins: &[Id<Origin>],
+    ) -> RequestResult<(Vec<WithId<Stop>>, Option<Id<Stop>>)> {
+        let entries =
+            StopRepo::get_page_after(&mut self.reader(), after, limit, origins)
+                .await?;
+        let next = next_page(&entries, limit);
+        Ok((entries.merge_all_from(origins), next))
+    }
+
     pub async fn get_stop(
         &self,
         id: Id<Stop>,
         origins: Vec<Id<Origin>>,
     ) -> RequestResult<WithId<Stop>> {
-        let result = self.database.auto().get(id).await?;
+        let result = self.reader().get(id).await?;
         result
             .merge_from(&origins)
             .ok_or(crate::RequestError::NotFound)
     }
 
+    /// Returns the merged stop, which origin supplied each of its fields and the
+    /// unmerged data of all origins.
+    pub async fn get_stop_sources(
+        &self,
+        id: Id<Stop>,
+        origins: &[Id<Origin>],
+    ) -> RequestResult<(WithId<Stop>, Provenance, Vec<WithOrigin<Stop>>)> {
+        let result = self.reader().get(id).await?;
+        let sources = result.source_data.clone();
+        result
+            .merge_from_with_provenance(origins)
+            .map(|(stop, provenance)| (stop, provenance, sources))
+            .ok_or(crate::RequestError::NotFound)
+    }
+
     pub async fn push_stop(
         &self,
-        stop: Stop,
+        mut stop: Stop,
         original_id: Option<String>,
     ) -> RequestResult<WithOrigin<WithId<Stop>>> {
+        stop.name = sanitize_option(stop.name, text_limits().name);
+        stop.description =
+            sanitize_option(stop.description, text_limits().description);
+        stop.location = stop.location.filter(|location| {
+            let subject = stop.name.as_deref().or(original_id.as_deref());
+            self.check_coordinates(
+                location.latitude,
+                location.longitude,
+                subject.unwrap_or("<unknown>"),
+            )
+        });
         let mut tx = self.database.transaction().await?;
         let origin = Id::new(self.id.clone());
         let stop_with_same_original_id = match &original_id {
@@ -317,6 +927,32 @@ where
             }
             None => None,
         };
+        // find the most similar subject, that was not rejected to be merged
+        let mut merge = None;
+        if stop_with_same_original_id.is_none() {
+            // candidates are limited to the most promising ones, which are all
+            // scored, so that the most similar one is merged. rejections are only
+            // looked up, until a candidate is found.
+            let candidates = tx.merge_candidates(&stop, &origin).await?;
+            for (similarity, candidate) in filter_sort_subjects(&stop, candidates) {
+                // candidates are sorted by similarity.
+                let Some(status) = MergeStatus::from_similarity(similarity) else {
+                    break;
+                };
+                let is_rejected = tx
+                    .is_merge_rejected(
+                        MergeSubject::Stop,
+                        &origin,
+                        original_id.as_deref(),
+                        candidate.content.id.raw_ref::<str>(),
+                    )
+                    .await?;
+                if !is_rejected {
+                    merge = Some((similarity, candidate, status));
+                    break;
+                }
+            }
+        }
         // insert into database
         let result: Result<_, RequestError> = if let Some(id) =
             stop_with_same_original_id
@@ -327,9 +963,8 @@ where
                 WithId::new(id, stop),
             ))
             .await
-        } else if let Some((similarity, same_subject)) =
-            filter_sort_subjects(&stop, tx.merge_candidates(&stop, &origin).await?)
-                .first()
+        } else if let Some((similarity, same_subject, MergeStatus::AutoAccepted)) =
+            &merge
         {
             println!(
                 "Identified Stops {}::'{}' and {}::'{}' to be Subject-Equal. Confidence: {}.",
@@ -351,12 +986,28 @@ where
             ))
             .await
         } else {
-            // insert completely new
+            // insert completely new. uncertain merges are inserted separately,
+            // until they are reviewed.
             tx.insert(WithOrigin::new(Id::new(self.id.clone()), stop))
                 .await
         }
         .map_err(|why| why.into());
         let result = result?;
+        // log merge decision
+        if let Some((similarity, same_subject, status)) = merge {
+            tx.log_merge(MergeLogEntry {
+                subject: MergeSubject::Stop,
+                origin: origin.clone(),
+                subject_id: result.content.id.raw(),
+                subject_original_id: original_id.clone(),
+                candidate_id: same_subject.content.id.raw(),
+                similarity,
+                status,
+                created_at: Local::now(),
+                decided_at: None,
+            })
+            .await?;
+        }
         // insert original id if given
         if let Some(original_id) = original_id {
             tx.put_original_id(
@@ -370,6 +1021,127 @@ where
         tx.commit().await.map(|_| result).map_err(|why| why.into())
     }
 
+    /// Gives stops of this origin without a location the centroid of their
+    /// children. Returns the number of updated stops.
+    pub async fn backfill_stop_centroids(&self) -> RequestResult<u64> {
+        Ok(self
+            .database
+            .auto()
+            .backfill_centroids(&self.origin())
+            .await?)
+    }
+
+    /// Changes of stops, lines and trips after the position, oldest first, along
+    /// with the position to continue from. The position is the given one, if
+    /// there are no changes yet. `None`, if deletions after the position were
+    /// compacted, so that a mirror at the position has to sync from scratch.
+    pub async fn get_changes(
+        &self,
+        after: Option<ChangePosition>,
+        limit: usize,
+    ) -> RequestResult<Option<(Vec<Change>, Option<ChangePosition>)>> {
+        let mut reader = self.reader();
+        let changes = reader.changes_after(after, limit).await?;
+        // the horizon is read after the changes, so that deletions compacted in
+        // between are not missed.
+        let horizon = reader.change_log_horizon().await?;
+        if after.is_some_and(|after| horizon.is_some_and(|horizon| after < horizon)) {
+            return Ok(None);
+        }
+        let next = changes.last().map(|change| change.position).or(after);
+        Ok(Some((changes, next)))
+    }
+
+    /// Appends the pending changes of finished transactions to the change log,
+    /// so that they are served by [`Client::get_changes`]. Does nothing, if the
+    /// changes are flushed by someone else right now. Returns the number of
+    /// flushed changes.
+    pub async fn flush_changes(&self) -> RequestResult<u64> {
+        let mut flushed = 0;
+        loop {
+            let mut tx = self.database.transaction().await?;
+            if !tx.try_lock_change_flush().await? {
+                return Ok(flushed);
+            }
+            let count = tx.flush_changes(CHANGE_LOG_FLUSH_BATCH_SIZE).await?;
+            tx.commit().await?;
+            flushed += count;
+            if count < CHANGE_LOG_FLUSH_BATCH_SIZE as u64 {
+                return Ok(flushed);
+            }
+        }
+    }
+
+    /// Compacts changes older than `retention` to the latest change of each
+    /// entity, dropping it as well, if it is a deletion. Returns the number of
+    /// deleted changes.
+    pub async fn compact_changes(&self, retention: Duration) -> RequestResult<u64> {
+        Ok(self
+            .database
+            .auto()
+            .compact_changes(Local::now() - retention)
+            .await?)
+    }
+
+    /// Syncs the stop names of stop times with their stops, see
+    /// [`TripRepo::sync_stop_names`]. Returns the number of updated stop times.
+    pub async fn sync_stop_names(&self) -> RequestResult<u64> {
+        Ok(self.database.auto().sync_stop_names().await?)
+    }
+
+    pub async fn get_merge_log(
+        &self,
+        status: Option<MergeStatus>,
+    ) -> RequestResult<Vec<WithId<MergeLogEntry>>> {
+        Ok(self.reader().get_merge_log(status).await?)
+    }
+
+    pub async fn get_merge_log_entry(
+        &self,
+        id: &Id<MergeLogEntry>,
+    ) -> RequestResult<MergeLogEntry> {
+        Ok(self.reader().get_merge_log_entry(id).await?)
+    }
+
+    /// Merges the subject of the given entry into the candidate. Fails with
+    /// [`MergeAlreadyDecided`], if the entry is not pending review.
+    pub async fn confirm_merge(
+        &self,
+        id: &Id<MergeLogEntry>,
+    ) -> RequestResult<MergeLogEntry> {
+        let mut tx = self.database.transaction().await?;
+        // deciding first locks the entry, so that it is merged at most once.
+        let Some(entry) = tx.decide_merge(id, MergeStatus::Confirmed).await? else {
+            return Err(already_decided(&mut tx, id).await);
+        };
+        match entry.subject {
+            MergeSubject::Stop => {
+                tx.redirect_stop(
+                    &entry.origin,
+                    &Id::new(entry.subject_id.clone()),
+                    &Id::new(entry.candidate_id.clone()),
+                )
+                .await?
+            }
+        }
+        tx.commit().await?;
+        Ok(entry)
+    }
+
+    /// Keeps the subject of the given entry separate. Merging the subject into the
+    /// candidate will not be proposed again, when it is imported again. Fails with
+    /// [`MergeAlreadyDecided`], if the entry is not pending review.
+    pub async fn reject_merge(
+        &self,
+        id: &Id<MergeLogEntry>,
+    ) -> RequestResult<MergeLogEntry> {
+        let mut database = self.database.auto();
+        match database.decide_merge(id, MergeStatus::Rejected).await? {
+            Some(entry) => Ok(entry),
+            None => Err(already_decided(&mut database, id).await),
+        }
+    }
+
     pub async fn find_nearby(
         &self,
         latitude: f64,
@@ -377,8 +1149,7 @@ where
         radius_km: f64,
         origins: &[Id<Origin>],
     ) -> RequestResult<Vec<WithDistance<WithId<Stop>>>> {
-        self.database
-            .auto()
+        self.reader()
             .find_nearby(latitude, longitude, radius_km)
             .await?
             .merge_all_from(origins)
@@ -388,33 +1159,107 @@ where
                     .with_distance_to(latitude, longitude)
                     .map(|with_distance| with_distance.with_id(stop.id))
             })
+            // stations are also found by the bounding box of their area, which
+            // does not have to contain the location.
+            .filter(|station| station.distance_km < radius_km)
             .collect::<Vec<_>>()
             .let_owned(|stops| Ok(stops))
     }
 
+    /// Stops matching the pattern, at most `limit`.
     pub async fn search_stop<S: Into<String>>(
         &self,
         pattern: S,
+        limit: usize,
         origins: &[Id<Origin>],
     ) -> RequestResult<Vec<StopNameSuggestion>> {
-        self.database
-            .auto()
-            .search(pattern.into())
+        let pattern: String = pattern.into();
+        let mut stops = self
+            .reader()
+            .search(pattern.clone())
             .await?
-            .merge_all_from(origins)
+            .merge_all_from(origins);
+        stops.truncate(limit);
+        self.name_suggestions(stops, &pattern).await
+    }
+
+    /// At most `limit` stops, whose names complete the pattern, for suggestions
+    /// while typing. Unlike [`Client::search_stop`], whether they are served is
+    /// not looked up.
+    pub async fn autocomplete_stop<S: Into<String>>(
+        &self,
+        pattern: S,
+        limit: usize,
+        origins: &[Id<Origin>],
+    ) -> RequestResult<Vec<WithId<Stop>>> {
+        Ok(self
+            .reader()
+            .autocomplete(pattern.into(), limit, origins)
+            .await?
+            .merge_all_from(origins))
+    }
+
+    /// Suggestions for the stops having a name, in the given order.
+    async fn name_suggestions(
+        &self,
+        stops: Vec<WithId<Stop>>,
+        pattern: &str,
+    ) -> RequestResult<Vec<StopNameSuggestion>> {
+        let served = self
+            .has_future_service(
+                &stops.iter().map(|stop| &stop.id).collect::<Vec<_>>(),
+            )
+            .await?;
+        stops
             .into_iter()
-            .filter_map(|stop| match (stop.content.name, stop.content.location) {
-                (Some(name), Some(location)) => Some(StopNameSuggestion {
+            .filter_map(|stop| {
+                let name = stop.content.name?;
+                Some(StopNameSuggestion {
+                    has_future_service: served.get(&stop.id).copied().unwrap_or(true),
+                    match_ranges: match_ranges(&name, pattern),
                     id: stop.id,
                     name,
-                    latitude: location.latitude,
-                    longitude: location.longitude,
-                }),
-                _ => None,
+                    location: stop.content.location,
+                })
             })
             .collect::<Vec<_>>()
             .let_owned(|stops| Ok(stops))
     }
+
+    /// Whether any trip calls at each of the stops today or later.
+    pub async fn has_future_service(
+        &self,
+        ids: &[&Id<Stop>],
+    ) -> RequestResult<HashMap<Id<Stop>, bool>> {
+        let today = Local::now().date_naive();
+        self.get_service_summaries(ids)
+            .await?
+            .into_iter()
+            .map(|(id, summary)| (id, summary.has_service_since(today)))
+            .collect::<HashMap<_, _>>()
+            .let_owned(Ok)
+    }
+
+    /// How each of the stops is served. Stops imported after the last refresh of
+    /// the summaries, see [`Client::refresh_stop_service_summary`], are omitted.
+    pub async fn get_service_summaries(
+        &self,
+        ids: &[&Id<Stop>],
+    ) -> RequestResult<HashMap<Id<Stop>, ServiceSummary>> {
+        Ok(self.reader().get_service_summaries(ids).await?)
+    }
+
+    /// Invalidates results cached until the next schedule import, in all clients.
+    /// Should be called after schedule data has been imported.
+    pub fn schedule_imported(&self) {
+        SCHEDULE_GENERATION.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Recomputes whether stops are served. Should be called after schedule data
+    /// has been imported.
+    pub async fn refresh_stop_service_summary(&self) -> RequestResult<u64> {
+        Ok(self.database.auto().refresh_service_summary(None).await?)
+    }
 }
 
 impl<D> Client<D>
@@ -439,8 +1284,7 @@ where
         origins: Vec<Id<Origin>>,
     ) -> RequestResult<Vec<WithId<Trip>>> {
         // todo: insert stops
-        self.database
-            .auto()
+        self.reader()
             .get_all()
             .await?
             .merge_all_from(&origins)
@@ -452,22 +1296,78 @@ where
         id: Id<Trip>,
         origins: Vec<Id<Origin>>,
     ) -> RequestResult<WithId<Trip>> {
-        let mut result = self.database.auto().get(id.clone()).await?;
-        self.with_stop_times(&mut result).await?;
+        let mut result = self.reader().get(id.clone()).await?;
+        self.with_stop_times(slice::from_mut(&mut result)).await?;
         result
             .merge_from(&origins)
             .ok_or(crate::RequestError::NotFound)
     }
 
+    /// Returns at most `limit` trips following the trip with id `after`, and the id
+    /// to continue with, if there may be more trips.
+    pub async fn get_trips_page(
+        &self,
+        after: Option<Id<Trip>>,
+        limit: usize,
+        origins: &[Id<Origin>],
+    ) -> RequestResult<(Vec<WithId<Trip>>, Option<Id<Trip>>)> {
+        let mut entries =
+            TripRepo::get_page_after(&mut self.reader(), after, limit, origins)
+                .await?;
+        let next = next_page(&entries, limit);
+        self.with_stop_times(&mut entries).await?;
+        Ok((entries.merge_all_from(origins), next))
+    }
+
+    /// Instances of at most `limit` trips matching the pattern, see
+    /// [`TripRepo::search_text`], which are underway or depart within a day after
+    /// `start`. Ordered by departure.
+    pub async fn search_trips<S: Into<String>>(
+        &self,
+        pattern: S,
+        start: DateTime<Local>,
+        limit: usize,
+        origins: &[Id<Origin>],
+    ) -> RequestResult<Vec<TripInstance>> {
+        let mut entries = TripRepo::search_text(
+            &mut self.reader(),
+            pattern.into(),
+            start.date_naive(),
+            limit,
+        )
+        .await?;
+        self.with_stop_times(&mut entries).await?;
+        let mut trips = self
+            .instanciate_trips_include(
+                entries.merge_all_from(origins),
+                DateTimeRange::new(start, start + Duration::days(1)),
+                &TripInstantiationOptions::all_references(),
+                origins,
+            )
+            .await?;
+        TripInstance::sort(&mut trips);
+        Ok(trips)
+    }
+
     pub async fn push_trip(
         &self,
         mut trip: Trip,
         original_id: Option<String>,
-        clear_stop_times: bool,
+        options: PushTripOptions,
     ) -> RequestResult<WithOrigin<WithId<Trip>>> {
         // TODO: think about how to identify trips from different sources as the same.
+        trip.headsign = sanitize_option(trip.headsign, text_limits().headsign);
+        trip.short_name = sanitize_option(trip.short_name, text_limits().name);
         let mut tx = self.database.transaction().await?;
-        let stop_times = trip.stops.drain(..).collect::<Vec<_>>();
+        let stop_times = trip
+            .stops
+            .drain(..)
+            .map(|mut stop_time| {
+                stop_time.stop_headsign =
+                    sanitize_option(stop_time.stop_headsign, text_limits().headsign);
+                stop_time
+            })
+            .collect::<Vec<_>>();
         let origin = Id::new(self.id.clone());
         let trip_with_same_original_id = match &original_id {
             Some(original_id) => {
@@ -486,19 +1386,15 @@ where
             }
             .map_err(|why| why.into());
         let result = result?;
-        // delete stop times (if existant from older version)
-        if clear_stop_times {
-            tx.delete_stop_times(result.content.id.clone(), Id::new(self.id.clone()))
-                .await?;
-        }
-        // insert stops (if given)
-        for stop_time in stop_times {
-            tx.put_stop_time(
-                result.content.id.clone(),
-                WithOrigin::new(result.origin.clone(), stop_time),
+        // insert stops (if given), replacing those of an older version
+        let written = tx
+            .put_stop_times(
+                &result.content.id,
+                &result.origin,
+                &stop_times,
+                options.clear_stop_times,
             )
             .await?;
-        }
         // insert original id if given
         if let Some(original_id) = original_id {
             tx.put_original_id(
@@ -509,14 +1405,30 @@ where
             .await?;
         }
         // commit changes
-        tx.commit().await.map(|_| result).map_err(|why| why.into())
+        tx.commit().await?;
+        tracing::debug!(
+            origin = self.id,
+            trip_id = result.content.id.raw_ref::<str>(),
+            stop_times = stop_times.len(),
+            written_stop_times = written,
+            "pushed trip"
+        );
+        Ok(result)
+    }
+
+    /// Deletes the trip of this client's origin, including its stop times and
+    /// original id mappings. Data of other origins is kept.
+    pub async fn delete_trip(&self, id: &Id<Trip>) -> RequestResult<()> {
+        Ok(self.database.auto().delete_trip(id, &self.origin()).await?)
     }
 
     pub async fn push_stop_time(
         &self,
         trip_id: Id<Trip>,
-        stop_time: StopTime,
+        mut stop_time: StopTime,
     ) -> RequestResult<WithOrigin<StopTime>> {
+        stop_time.stop_headsign =
+            sanitize_option(stop_time.stop_headsign, text_limits().headsign);
         self.database
             .auto()
             .put_stop_time(
@@ -527,92 +1439,309 @@ where
             .let_owned(Ok)
     }
 
+    /// Stores the stop times of the trip in a single transaction. If `replace` is
+    /// set, other stop times of this origin are deleted, e.g. when the given stop
+    /// times are all of the trip. Stop times, that did not change, are not
+    /// written, so that the trip is not logged as changed. Returns the number of
+    /// written stop times.
+    pub async fn push_stop_times(
+        &self,
+        trip_id: &Id<Trip>,
+        stop_times: Vec<StopTime>,
+        replace: bool,
+    ) -> RequestResult<u64> {
+        let stop_times = stop_times
+            .into_iter()
+            .map(|mut stop_time| {
+                stop_time.stop_headsign =
+                    sanitize_option(stop_time.stop_headsign, text_limits().headsign);
+                stop_time
+            })
+            .collect::<Vec<_>>();
+        Ok(self
+            .database
+            .auto()
+            .put_stop_times(trip_id, &self.origin(), &stop_times, replace)
+            .await?)
+    }
+
+    /// Replaces the couplings of the trip at the stop with the given sequence.
+    /// Coupled trips are referenced by their original id.
+    pub async fn put_trip_couplings(
+        &self,
+        trip_id: &Id<Trip>,
+        stop_sequence: i32,
+        stop_id: Option<&Id<Stop>>,
+        couplings: Vec<(String, CouplingKind)>,
+    ) -> RequestResult<()> {
+        Ok(self
+            .database
+            .auto()
+            .put_trip_couplings(
+                trip_id,
+                &self.origin(),
+                stop_sequence,
+                stop_id,
+                &couplings,
+            )
+            .await?)
+    }
+
+    /// Inserts or replaces the period of the trip, which starts at the same time.
+    pub async fn put_frequency(
+        &self,
+        trip_id: &Id<Trip>,
+        frequency: Frequency,
+    ) -> RequestResult<()> {
+        Ok(self
+            .database
+            .auto()
+            .put_frequency(trip_id, &self.origin(), &frequency)
+            .await?)
+    }
+
+    /// Inserts or replaces the level of a station.
+    pub async fn put_level(&self, level: WithId<Level>) -> RequestResult<()> {
+        Ok(self
+            .database
+            .auto()
+            .put_level(&self.origin(), &level)
+            .await?)
+    }
+
+    /// Inserts or replaces the pathway within a station.
+    pub async fn put_pathway(&self, pathway: WithId<Pathway>) -> RequestResult<()> {
+        Ok(self
+            .database
+            .auto()
+            .put_pathway(&self.origin(), &pathway)
+            .await?)
+    }
+
+    /// Children of the stop, e.g. the platforms of a station, in natural order of
+    /// their platform codes.
+    pub async fn get_platforms(
+        &self,
+        stop_id: &Id<Stop>,
+        origins: &[Id<Origin>],
+    ) -> RequestResult<Vec<WithId<Stop>>> {
+        Ok(self
+            .reader()
+            .get_platforms(stop_id)
+            .await?
+            .merge_all_from(origins))
+    }
+
+    /// Pathways of the station and the levels they connect, of the given origins.
+    pub async fn get_station_pathway_graph(
+        &self,
+        station_id: &Id<Stop>,
+        origins: &[Id<Origin>],
+    ) -> RequestResult<PathwayGraph> {
+        let mut reader = self.reader();
+        let mut pathways = reader.get_station_pathways(station_id).await?;
+        let mut levels = reader.get_station_levels(station_id).await?;
+        pathways.retain(|pathway| origins.contains(&pathway.origin));
+        levels.retain(|level| origins.contains(&level.origin));
+        Ok(PathwayGraph { pathways, levels })
+    }
+
     pub async fn get_all_trips_via_stops(
         &self,
         stop_ids: &[&Id<Stop>],
         start: DateTime<Local>,
         end: DateTime<Local>,
+        mode: WindowMode,
         origins: &[Id<Origin>],
     ) -> RequestResult<Vec<WithId<Trip>>> {
         let mut result = self
-            .database
-            .auto()
-            .get_all_via_stop(
-                stop_ids,
-                // Since trips that extend beyond one day have arrival and departure
-                // times past midnight and still belong to the previous day, the
-                // previous day must always be included in a request.
-                start - Duration::days(1),
-                end,
-            )
+            .reader()
+            .get_all_via_stop(stop_ids, start, end, mode)
             .await?;
 
-        for entry in result.iter_mut() {
-            self.with_stop_times(entry).await?;
-        }
+        self.with_stop_times(&mut result).await?;
 
         Ok(result.merge_all_from(&origins))
     }
 
-    /// Instancates the given trips in the given range and includes other information.
-    ///
-    /// # WARNING
-    ///
-    /// Currently, lines are always included if you include agencies.
-    /// TODO: introduce repo function to fetch agency by `line_id` to solve this
-    ///       issue.
-    pub async fn instanciate_trips_include(
+    /// Instancates the given trips in the given range and includes other information.
+    ///
+    /// # WARNING
+    ///
+    /// Currently, lines are always included if you include agencies.
+    /// TODO: introduce repo function to fetch agency by `line_id` to solve this
+    ///       issue.
+    pub async fn instanciate_trips_include(
+        &self,
+        mut trips: Vec<WithId<Trip>>,
+        range: DateTimeRange<Local>,
+        options: &TripInstantiationOptions<'_>,
+        origins: &[Id<Origin>],
+    ) -> RequestResult<Vec<TripInstance>> {
+        let stop_names = (options.include_stop_names
+            && options.stop_names_from_stop_times)
+            .then(|| take_stop_names(&mut trips));
+        let mut trips = self
+            .instanciate_trips(
+                trips,
+                range,
+                options.window_mode,
+                options.stop_ids_of_interest,
+            )
+            .await?;
+        if let Some(stop_names) = stop_names {
+            for trip in trips.iter_mut() {
+                let Some(names) = stop_names.get(&trip.info.trip_id) else {
+                    continue;
+                };
+                for stop_time in trip
+                    .stops
+                    .iter_mut()
+                    .chain(trip.stop_of_interest.iter_mut())
+                {
+                    stop_time.stop_name = names
+                        .binary_search_by_key(
+                            &stop_time.stop_sequence,
+                            |(sequence, _)| *sequence,
+                        )
+                        .ok()
+                        .map(|index| names[index].1.clone());
+                }
+            }
+        }
+        self.include_references(&mut trips, options, origins)
+            .await?;
+        Ok(trips)
+    }
+
+    /// Instantiates the trip on the given service day, including stop names and
+    /// locations. The index selects one of the departures of frequency based
+    /// trips. Fails with `NotFound`, if the trip does not run on that day.
+    pub async fn get_trip_instance(
         &self,
-        trips: Vec<WithId<Trip>>,
-        range: DateTimeRange<Local>,
-        stop_ids_of_interest: Option<&[&Id<Stop>]>,
-        include_stop_names: bool,
-        include_lines: bool,
-        include_agencies: bool,
+        trip_id: Id<Trip>,
+        date: NaiveDate,
+        index: u32,
         origins: &[Id<Origin>],
-    ) -> RequestResult<Vec<TripInstance>> {
-        let mut trips = self
-            .instanciate_trips(trips, range, stop_ids_of_interest)
+    ) -> RequestResult<TripInstance> {
+        let trip = self.get_trip(trip_id, origins.to_vec()).await?;
+        let service_id = trip
+            .content
+            .service_id
+            .ok_or(crate::RequestError::NotFound)?;
+        let is_serviced = self
+            .get_service(&service_id)
+            .await?
+            .available_days(Some(date), Some(date))
+            .contains(&date);
+        if !is_serviced {
+            return Err(crate::RequestError::NotFound);
+        }
+        let mut trips = instantiate_trips_naive(&trip, &date, None, None)
+            .into_iter()
+            .nth(index as usize)
+            .into_iter()
+            .collect::<Vec<_>>();
+        let options = TripInstantiationOptions {
+            include_stop_names: true,
+            ..Default::default()
+        };
+        self.include_references(&mut trips, &options, origins)
             .await?;
+        trips.pop().ok_or(crate::RequestError::NotFound)
+    }
 
-        let mut stops: HashMap<Id<Stop>, Option<Stop>> = HashMap::new();
-        let mut lines: HashMap<Id<Line>, Option<WithId<Line>>> = HashMap::new();
-        let mut agencies: HashMap<Id<Agency>, Option<WithId<Agency>>> =
-            HashMap::new();
+    /// Fills in stop names and locations, lines and agencies of trip instances.
+    async fn include_references(
+        &self,
+        trips: &mut [TripInstance],
+        options: &TripInstantiationOptions<'_>,
+        origins: &[Id<Origin>],
+    ) -> RequestResult<()> {
+        let TripInstantiationOptions {
+            include_stop_names,
+            stop_names_from_stop_times,
+            include_lines,
+            include_agencies,
+            ..
+        } = *options;
+        let include_stops = include_stop_names && !stop_names_from_stop_times;
+        // resolve all referenced ids up front with one query per entity type, so
+        // that the enrichment below does not wait for the database once per trip.
+        let mut lines: HashMap<Id<Line>, WithId<Line>> = HashMap::new();
+        let mut agencies: HashMap<Id<Agency>, WithId<Agency>> = HashMap::new();
+        let mut stops: HashMap<Id<Stop>, WithId<Stop>> = HashMap::new();
+        if include_lines || include_agencies {
+            let ids = trips
+                .iter()
+                .map(|trip| trip.info.line_id.clone())
+                .collect::<HashSet<_>>();
+            lines = self.get_many_merged(ids, origins).await?;
+        }
+        if include_agencies {
+            let ids = lines
+                .values()
+                .flat_map(|line| {
+                    line.content
+                        .agency_id
+                        .iter()
+                        .chain(&line.content.secondary_agency_ids)
+                        .cloned()
+                })
+                .collect::<HashSet<_>>();
+            agencies = self.get_many_merged(ids, origins).await?;
+        }
+        if include_stops {
+            let ids = trips
+                .iter()
+                .flat_map(|trip| {
+                    trip.stops.iter().chain(trip.stop_of_interest.iter())
+                })
+                .filter_map(|stop_time| stop_time.stop_id.clone())
+                .collect::<HashSet<_>>();
+            stops = self.get_many_merged(ids, origins).await?;
+        }
+        let trip_ids = trips
+            .iter()
+            .map(|trip| trip.info.trip_id.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let couplings = self
+            .reader()
+            .get_trip_couplings(&trip_ids)
+            .await?
+            .into_iter()
+            .filter(|coupling| origins.contains(&coupling.origin))
+            .map(|coupling| coupling.content)
+            .collect::<Vec<_>>();
+        tracing::debug!(
+            trips = trips.len(),
+            couplings = couplings.len(),
+            lines = lines.len(),
+            agencies = agencies.len(),
+            stops = stops.len(),
+            "resolved trip references"
+        );
 
         for trip in trips.iter_mut() {
             // lines
             if include_lines || include_agencies {
-                let id = &trip.info.line_id;
-                trip.line = if let Some(cached) = lines.get(id) {
-                    cached.clone()
-                } else {
-                    let fetched = self
-                        .get_line(id.clone(), origins.to_vec())
-                        .await
-                        .let_owned(not_found_to_none)?;
-                    lines.insert(id.clone(), fetched.clone());
-                    fetched
-                };
+                trip.line = lines.get(&trip.info.line_id).cloned();
             }
             // agencies
             if include_agencies {
-                if let Some(id) = trip
+                trip.agency = trip
                     .line
                     .as_ref()
                     .and_then(|line| line.content.agency_id.as_ref())
-                {
-                    trip.agency = if let Some(cached) = agencies.get(id) {
-                        cached.clone()
-                    } else {
-                        let fetched = self
-                            .get_agency(id.clone(), origins.to_vec())
-                            .await
-                            .let_owned(not_found_to_none)?;
-                        agencies.insert(id.clone(), fetched.clone());
-                        fetched
-                    };
-                }
+                    .and_then(|id| agencies.get(id).cloned());
+                trip.secondary_agencies = trip
+                    .line
+                    .iter()
+                    .flat_map(|line| &line.content.secondary_agency_ids)
+                    .filter_map(|id| agencies.get(id).cloned())
+                    .collect();
             }
             // stop names
             if include_stop_names {
@@ -621,22 +1750,12 @@ where
                     .iter_mut()
                     .chain(trip.stop_of_interest.iter_mut())
                 {
-                    if let Some(id) = stop_time.stop_id.as_ref() {
-                        let stop = if let Some(cached) = stops.get(id) {
-                            cached.clone()
-                        } else {
-                            let fetched = self
-                                .get_stop(id.clone(), origins.to_vec())
-                                .await
-                                .let_owned(not_found_to_none)?
-                                .map(|stop| stop.content);
-                            stops.insert(id.clone(), fetched.clone());
-                            fetched
-                        };
-                        if let Some(stop) = stop {
-                            stop_time.stop_name = stop.name;
-                            stop_time.location = stop.location;
-                        }
+                    if let Some(stop) =
+                        stop_time.stop_id.as_ref().and_then(|id| stops.get(id))
+                    {
+                        stop_time.stop_name = stop.content.name.clone();
+                        stop_time.location = stop.content.location.clone();
+                        stop_time.platform = stop.content.platform_code.clone();
                     }
                 }
 
@@ -654,9 +1773,36 @@ where
                     })
                     .cloned();
             }
+            // couplings, after stop names to name the stops of splitting and joining.
+            trip.set_couplings(&couplings);
         }
 
-        Ok(trips)
+        Ok(())
+    }
+
+    /// Fetches the given subjects with a single query and merges each of them from
+    /// the given origins. Ids without data of these origins are omitted.
+    async fn get_many_merged<T>(
+        &self,
+        ids: HashSet<Id<T>>,
+        origins: &[Id<Origin>],
+    ) -> RequestResult<HashMap<Id<T>, WithId<T>>>
+    where
+        T: Serialize + Mergable + HasId<IdType = String> + Clone + Send + Sync,
+        D::Autocommit: Repo<T>,
+    {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let ids = ids.into_iter().collect::<Vec<_>>();
+        Ok(self
+            .reader()
+            .get_many(&ids)
+            .await?
+            .merge_all_from(origins)
+            .into_iter()
+            .map(|entry| (entry.id.clone(), entry))
+            .collect())
     }
 
     /// Instanciates the passed trips within a given datetime range at the given
@@ -667,6 +1813,7 @@ where
         &self,
         trips: Vec<WithId<Trip>>,
         range: DateTimeRange<Local>,
+        mode: WindowMode,
         stop_ids_of_interest: Option<&[&Id<Stop>]>, // accept multiple ids an prioritize by position in array.
     ) -> RequestResult<Vec<TripInstance>> {
         let start: DateTime<Local> = range.first;
@@ -697,68 +1844,223 @@ where
                 available
             };
             // instanciate trip for each service day within interest window.
-            let result = days.iter().filter_map(|day| {
-                instantiate_trip_naive(&trip, day, Some(&range), stop_ids_of_interest)
+            let result = days.iter().flat_map(|day| {
+                instantiate_trips_naive(
+                    &trip,
+                    day,
+                    Some((&range, mode)),
+                    stop_ids_of_interest,
+                )
+                .into_iter()
+                .filter(|instance| instance.matches_filters)
             });
             results.extend(result);
         }
 
+        // tags of the services
+        let service_ids = days_of_services.into_keys().collect::<Vec<_>>();
+        let tags = self.reader().get_service_tags(&service_ids).await?;
+        for result in results.iter_mut() {
+            if let Some(service_tags) =
+                result.info.service_id.as_ref().and_then(|id| tags.get(id))
+            {
+                result.info.tags = service_tags.clone();
+            }
+        }
+
         Ok(results)
     }
 
     async fn with_stop_times(
         &self,
-        entry: &mut DatabaseEntry<Trip>,
+        entries: &mut [DatabaseEntry<Trip>],
     ) -> RequestResult<()> {
-        for source in entry.source_data.iter_mut() {
-            let mut stops = self
-                .database
-                .auto()
-                .get_stop_times(entry.id.clone(), source.origin.clone())
-                .await?;
-            // muss das? oder sortier ich schon wo anders? ich weiß es nicht.
-            stops.sort_by_key(|stop| stop.stop_sequence);
-            source.content.stops = stops;
+        let trip_ids = entries
+            .iter()
+            .map(|entry| entry.id.clone())
+            .collect::<Vec<_>>();
+        let mut frequencies = HashMap::<_, Vec<_>>::new();
+        for (trip_id, frequency) in self.reader().get_frequencies(&trip_ids).await? {
+            frequencies
+                .entry((trip_id, frequency.origin))
+                .or_default()
+                .push(frequency.content);
+        }
+        for entry in entries.iter_mut() {
+            for source in entry.source_data.iter_mut() {
+                let mut stops = self
+                    .reader()
+                    .get_stop_times(entry.id.clone(), source.origin.clone())
+                    .await?;
+                // muss das? oder sortier ich schon wo anders? ich weiß es nicht.
+                stops.sort_by_key(|stop| stop.stop_sequence);
+                source.content.stops = stops;
+                source.content.frequencies = frequencies
+                    .remove(&(entry.id.clone(), source.origin.clone()))
+                    .unwrap_or_default();
+            }
         }
         Ok(())
     }
 }
 
+/// The error of deciding a merge, which is not pending review. Not found, if there
+/// is no such merge at all.
+async fn already_decided<R>(repo: &mut R, id: &Id<MergeLogEntry>) -> RequestError
+where
+    R: MergeLogRepo + Send,
+{
+    match repo.get_merge_log_entry(id).await {
+        Ok(_) => RequestError::other(MergeAlreadyDecided),
+        Err(why) => why.into(),
+    }
+}
+
+/// Takes the stop names out of the stop times of the trips, which are not needed
+/// to instantiate them, by trip and ordered by stop sequence.
+fn take_stop_names(
+    trips: &mut [WithId<Trip>],
+) -> HashMap<Id<Trip>, Vec<(i32, String)>> {
+    trips
+        .iter_mut()
+        .map(|trip| {
+            let mut names = trip
+                .content
+                .stops
+                .iter_mut()
+                .filter_map(|stop_time| {
+                    let name = stop_time.stop_name.take()?;
+                    Some((stop_time.stop_sequence, name))
+                })
+                .collect::<Vec<_>>();
+            names.sort_unstable_by_key(|(sequence, _)| *sequence);
+            (trip.id.clone(), names)
+        })
+        .collect()
+}
+
+fn check_origin_collision(
+    id: &Id<Origin>,
+    name: &str,
+    others: &[WithId<Origin>],
+) -> Result<(), OriginIdError> {
+    match Origin::find_collision(id, name, others) {
+        Some(existing) => Err(OriginIdError::Collision {
+            id: id.raw(),
+            existing: existing.id.raw(),
+        }),
+        None => Ok(()),
+    }
+}
+
 /// Instantiates the trip for the given date, regardless of the trip is serviced
 /// on that that particular date (thus naive).
-/// If `range` or `stop_ids_of_interest` are given, the trip is only instantiated,
-/// if these filters match.
-/// If these are not specified, the trip is always instantiated.
+/// If `range` or `stop_ids_of_interest` are given, the instance only matches
+/// the filters, if it calls at one of the stops within the range. The
+/// `WindowMode` passed along with the range decides, whether the arrival or the
+/// departure at a stop has to lie within the range. Instances are given
+/// regardless of the filters, see [`TripInstance::matches_filters`].
+///
+/// Frequency based trips only yield their first departure of the day, see
+/// [`instantiate_trips_naive`] for all of them.
 pub fn instantiate_trip_naive(
     trip: &WithId<Trip>,
     date: &NaiveDate,
-    range: Option<&DateTimeRange<Local>>,
+    range: Option<(&DateTimeRange<Local>, WindowMode)>,
+    stop_ids_of_interest: Option<&[&Id<Stop>]>,
+) -> Option<TripInstance> {
+    let offset = departure_offsets(&trip.content).into_iter().next()?;
+    instantiate_trip_at(trip, date, 0, offset, range, stop_ids_of_interest)
+}
+
+/// Like [`instantiate_trip_naive`], but frequency based trips are instantiated
+/// once per departure within their periods, ordered by departure.
+pub fn instantiate_trips_naive(
+    trip: &WithId<Trip>,
+    date: &NaiveDate,
+    range: Option<(&DateTimeRange<Local>, WindowMode)>,
+    stop_ids_of_interest: Option<&[&Id<Stop>]>,
+) -> Vec<TripInstance> {
+    departure_offsets(&trip.content)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, offset)| {
+            instantiate_trip_at(
+                trip,
+                date,
+                index as u32,
+                offset,
+                range,
+                stop_ids_of_interest,
+            )
+        })
+        .collect()
+}
+
+/// Offsets of the departures of the trip from its stop times, one per instance
+/// on a service day. Stop times of frequency based trips only describe the
+/// intervals between the stops, so they are shifted to each departure. A period
+/// ends before its end time, which is the start time of the next one.
+fn departure_offsets(trip: &Trip) -> Vec<Duration> {
+    if trip.frequencies.is_empty() {
+        return vec![Duration::zero()];
+    }
+    let Some(first_departure) = trip
+        .stops
+        .iter()
+        .find_map(|stop_time| stop_time.departure_time.or(stop_time.arrival_time))
+    else {
+        return vec![];
+    };
+    trip.frequencies
+        .iter()
+        .flat_map(Frequency::departures)
+        .map(|departure| departure - first_departure)
+        .collect()
+}
+
+/// Instance with the given index of the trip, whose stop times are shifted by
+/// `offset`.
+fn instantiate_trip_at(
+    trip: &WithId<Trip>,
+    date: &NaiveDate,
+    index: u32,
+    offset: Duration,
+    range: Option<(&DateTimeRange<Local>, WindowMode)>,
     stop_ids_of_interest: Option<&[&Id<Stop>]>,
 ) -> Option<TripInstance> {
     // common trip instance info.
     let trip_info = TripInstanceInfo {
         trip_id: trip.id.clone(),
+        instance_id: TripInstanceId::new(&trip.id, *date, index),
         line_id: trip.content.line_id.clone(),
         service_id: trip.content.service_id,
         headsign: trip.content.headsign.clone(),
         short_name: trip.content.short_name.clone(),
+        tags: vec![],
     };
     // local datetime
     let datetime = date
         .and_time(NaiveTime::default())
         .and_local_timezone(Local)
         .earliest()?; // TODO: handle invalid date
+    let has_filters = stop_ids_of_interest.is_some() || range.is_some();
     let mut stop_time_instance_of_interest_idx = None; // index of stop of interst in stop_ids
     let mut stop_time_instance_of_interest = None;
     let mut instance_headsign = trip_info.headsign.clone();
+    let last_idx = trip.content.stops.len().saturating_sub(1);
     let stop_times = trip
         .content
         .stops
         .iter()
-        .map(|stop_time| {
+        .enumerate()
+        .map(|(stop_time_idx, stop_time)| {
             // calculate arrival and departure time.
-            let arrival_time = stop_time.arrival_time.map(|time| datetime + time);
-            let departure_time = stop_time.departure_time.map(|time| datetime + time);
+            let arrival_time =
+                stop_time.arrival_time.map(|time| datetime + time + offset);
+            let departure_time = stop_time
+                .departure_time
+                .map(|time| datetime + time + offset);
 
             // if no headsign and this stop comes before or at stop of interest...
             if let (None, Some(stop_headsign)) =
@@ -772,18 +2074,25 @@ pub fn instantiate_trip_naive(
                 stop_ids_of_interest
                     .and_then(|ids| ids.iter().position(|i| i == &stop_id))
             });
-            let is_stop_of_interest = idx.is_some() || stop_ids_of_interest.is_none();
+            // on-demand areas are no concrete place to board or alight at.
+            let is_stop_of_interest = !stop_time.is_on_demand()
+                && (idx.is_some() || stop_ids_of_interest.is_none());
 
             // is time in frame?
-            let is_time_of_interest = if let Some(range) = range.as_ref() {
-                let start = range.first;
-                let end = range.last;
-                arrival_time
-                    .map(|time| time >= start && time <= end)
+            let is_time_of_interest = if let Some((range, mode)) = range {
+                // a trip can not be departed from at its last stop and not be
+                // arrived with at its first stop.
+                let time = match mode {
+                    WindowMode::DepartBetween if stop_time_idx != last_idx => {
+                        departure_time.or(arrival_time)
+                    }
+                    WindowMode::ArriveBetween if stop_time_idx != 0 => {
+                        arrival_time.or(departure_time)
+                    }
+                    _ => None,
+                };
+                time.map(|time| time >= range.first && time <= range.last)
                     .unwrap_or(false)
-                    || departure_time
-                        .map(|time| time >= start && time <= end)
-                        .unwrap_or(false)
             } else {
                 true
             };
@@ -791,6 +2100,9 @@ pub fn instantiate_trip_naive(
             // is stop_time combined interesting?
             let is_stop_time_of_interest = is_stop_of_interest && is_time_of_interest;
 
+            // unknown types are shown as regular ones.
+            let pickup_type = stop_time.pickup_type.unwrap_or_default();
+            let drop_off_type = stop_time.drop_off_type.unwrap_or_default();
             let stop_time_instance = StopTimeInstance {
                 stop_sequence: stop_time.stop_sequence,
                 stop_id: stop_time.stop_id.clone(),
@@ -800,28 +2112,26 @@ pub fn instantiate_trip_naive(
                 stop_headsign: stop_time.stop_headsign.clone(),
                 interest_flag: is_stop_time_of_interest,
                 location: None,
+                pickup_type,
+                drop_off_type,
+                pickup_text: (pickup_type != PickupDropOffType::Regular)
+                    .then(|| pickup_type.pickup_display_text()),
+                drop_off_text: (drop_off_type != PickupDropOffType::Regular)
+                    .then(|| drop_off_type.drop_off_display_text()),
+                on_demand: stop_time.is_on_demand(),
+                on_request: stop_time.is_on_request(),
+                platform: None,
+                platform_changed: false,
             };
 
-            // update stop time of interest.
+            // update stop time of interest. stops earlier in `stop_ids` have a
+            // higher prio, otherwise the first matching stop time is taken.
             // TODO: unnötiges rumgeklone kann man verhindern.
-            if is_stop_time_of_interest {
-                match (idx, stop_time_instance_of_interest_idx) {
-                    // is stop time of interest and has higher prio than current?
-                    (Some(this), Some(curr)) => {
-                        if this < curr {
-                            stop_time_instance_of_interest =
-                                Some(stop_time_instance.clone());
-                            stop_time_instance_of_interest_idx = idx;
-                        }
-                    }
-                    // is first found stop timie of interest?
-                    (Some(_), None) => {
-                        stop_time_instance_of_interest =
-                            Some(stop_time_instance.clone());
-                        stop_time_instance_of_interest_idx = idx;
-                    }
-                    // stop time is not interesting
-                    _ => {}
+            if has_filters && is_stop_time_of_interest {
+                let prio = idx.unwrap_or(0);
+                if stop_time_instance_of_interest_idx.is_none_or(|curr| prio < curr) {
+                    stop_time_instance_of_interest = Some(stop_time_instance.clone());
+                    stop_time_instance_of_interest_idx = Some(prio);
                 }
             }
 
@@ -829,7 +2139,7 @@ pub fn instantiate_trip_naive(
         })
         .collect::<Vec<_>>();
 
-    stop_time_instance_of_interest.map(|stop_of_interest| TripInstance {
+    Some(TripInstance {
         info: trip_info
             .clone()
             .let_owned(|mut trip_info: TripInstanceInfo| {
@@ -837,13 +2147,12 @@ pub fn instantiate_trip_naive(
                 trip_info
             }),
         stops: stop_times,
-        stop_of_interest: if stop_ids_of_interest.is_some() || range.is_some() {
-            Some(stop_of_interest)
-        } else {
-            None
-        },
+        matches_filters: !has_filters || stop_time_instance_of_interest.is_some(),
+        stop_of_interest: stop_time_instance_of_interest,
         line: None,
         agency: None,
+        secondary_agencies: vec![],
+        coupled_with: vec![],
     })
 }
 
@@ -864,19 +2173,149 @@ where
         .let_owned(Ok)
     }
 
+    pub async fn get_shape_id_by_original_id(
+        &self,
+        original_id: String,
+    ) -> RequestResult<Option<Id<Shape>>> {
+        SubjectRepo::<Shape>::id_by_original_id(
+            &mut self.database.auto(),
+            Id::new(self.id.clone()),
+            original_id,
+        )
+        .await?
+        .let_owned(Ok)
+    }
+
+    /// Inserts or updates points of the shape with the given original id, which
+    /// is created if it does not exist yet. Existing points are removed first, if
+    /// `replace` is set. Otherwise, the points are added to the shape, which allows
+    /// inserting large shapes in multiple chunks.
+    pub async fn push_shape_points(
+        &self,
+        original_id: String,
+        points: Vec<(i32, ShapePoint)>,
+        replace: bool,
+    ) -> RequestResult<Id<Shape>> {
+        let origin = Id::new(self.id.clone());
+        let mut tx = self.database.transaction().await?;
+        let existing_id = SubjectRepo::<Shape>::id_by_original_id(
+            &mut tx,
+            origin.clone(),
+            original_id.clone(),
+        )
+        .await?;
+        let id = match existing_id {
+            Some(id) => {
+                if replace {
+                    tx.delete_shape_points(&id).await?;
+                }
+                id
+            }
+            None => {
+                let id = tx.create_shape_id().await?;
+                SubjectRepo::put_original_id(&mut tx, origin, original_id, id)
+                    .await?;
+                id
+            }
+        };
+        tx.put_shape_points(&id, points).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
     pub async fn get_service(
         &self,
         service_id: &Id<Service>,
     ) -> RequestResult<Service> {
-        let windows = self
-            .database
-            .auto()
-            .get_calendar_windows(service_id)
-            .await?;
-        let dates = self.database.auto().get_calendar_dates(service_id).await?;
+        let windows = self.reader().get_calendar_windows(service_id).await?;
+        let dates = self.reader().get_calendar_dates(service_id).await?;
         Ok(Service { windows, dates })
     }
 
+    /// Tags the services of this origin based on their calendars, e.g., services
+    /// only available on school days. Replaces previous tags, so it can be rerun
+    /// at any time. Should be called after schedule data has been imported.
+    /// Returns the number of tagged services.
+    pub async fn classify_services(&self) -> RequestResult<usize> {
+        let origin = self.origin();
+        let calendar = holiday_calendars()
+            .for_origin(&origin)
+            .cloned()
+            .unwrap_or_else(HolidayCalendar::default);
+        let ids = self.reader().get_service_ids(&origin).await?;
+        let mut services = Vec::with_capacity(ids.len());
+        for id in ids.iter() {
+            services.push((*id, self.get_service(id).await?));
+        }
+        // first and last day of the feed
+        let days = services
+            .iter()
+            .flat_map(|(_, service)| {
+                let days = service.available_days(None, None);
+                days.first()
+                    .copied()
+                    .into_iter()
+                    .chain(days.last().copied())
+            })
+            .collect::<Vec<_>>();
+        let (Some(first), Some(last)) = (days.iter().min(), days.iter().max()) else {
+            return Ok(0);
+        };
+        let tags = services
+            .iter()
+            .flat_map(|(id, service)| {
+                service
+                    .classify(&calendar, (*first, *last))
+                    .into_iter()
+                    .map(|tag| (*id, tag))
+            })
+            .collect::<Vec<_>>();
+        self.database.auto().set_service_tags(&ids, &tags).await?;
+        Ok(tags.iter().map(|(id, _)| id).collect::<HashSet<_>>().len())
+    }
+
+    /// Days, at which the service is available within the given range, and why.
+    pub async fn get_service_days(
+        &self,
+        service_id: &Id<Service>,
+        earliest: Option<NaiveDate>,
+        latest: Option<NaiveDate>,
+    ) -> RequestResult<Vec<ServiceDay>> {
+        let service = self.get_service(service_id).await?;
+        if service.windows.is_empty() && service.dates.is_empty() {
+            return Err(RequestError::NotFound);
+        }
+        Ok(service.explain_available_days(earliest, latest))
+    }
+
+    /// Stores the service by value. If this origin already has a service with
+    /// the same windows and dates, its id is returned instead of creating another
+    /// one. The original id is mapped to the service either way.
+    pub async fn push_service(
+        &self,
+        service: Service,
+        original_id: Option<String>,
+    ) -> RequestResult<Id<Service>> {
+        let origin = self.origin();
+        let mut tx = self.database.transaction().await?;
+        let (id, created) = tx
+            .claim_service_signature(&origin, &service.signature())
+            .await?;
+        if created {
+            for window in service.windows {
+                tx.put_calendar_window(Some(&id), window).await?;
+            }
+            for date in service.dates {
+                tx.put_calendar_date(Some(&id), date).await?;
+            }
+        }
+        if let Some(original_id) = original_id {
+            SubjectRepo::put_original_id(&mut tx, origin, original_id, id).await?;
+        }
+        tx.commit().await?;
+        Ok(id)
+    }
+
     pub async fn push_calendar_window<S>(
         &self,
         service_id: Option<&Id<Service>>,
@@ -951,10 +2390,7 @@ where
         let mut tx = self.database.transaction().await?;
         let mut new_updates = vec![];
         for update in updates {
-            let update_id = update.id.raw();
-            let timestamp = tx
-                .get_timestamp(&origin, &update_id.trip_id, update_id.trip_start_date)
-                .await?;
+            let timestamp = tx.get_timestamp(&origin, &update.id.raw()).await?;
             let is_new = update
                 .content
                 .timestamp
@@ -973,15 +2409,19 @@ where
         Ok(new_updates)
     }
 
+    /// Applies the update of a single stop time to the stored update of the trip.
+    /// Returns `false` without writing anything, if the stored stop time already
+    /// has the same times and status, as high frequency feeds mostly repeat
+    /// themselves.
     pub async fn put_stop_time_update(
         &self,
         trip_id: &Id<Trip>,
         trip_start_date: NaiveDate,
         stop_time: StopTimeUpdate,
-    ) -> RequestResult<()> {
+    ) -> RequestResult<bool> {
         let mut tx = self.database.transaction().await?;
         let realtime = if let Some(mut current) = tx
-            .get_realtime_for_trip(trip_id, trip_start_date)
+            .get_realtime_for_trip(trip_id, trip_start_date, 0)
             .await?
             .merge_from(&[Id::new(self.id.clone())])
         {
@@ -995,6 +2435,13 @@ where
                     })
                     .unwrap_or(false);
                 if is_same {
+                    // still write, if the stop was not known before.
+                    if stop_update.is_unchanged_by(&stop_time)
+                        && (stop_update.stop_id.is_some()
+                            || stop_time.stop_id.is_none())
+                    {
+                        return Ok(false);
+                    }
                     *stop_update = stop_time.clone();
                     set = true;
                     break;
@@ -1017,33 +2464,105 @@ where
         tx.put_trip_updates(&Id::new(self.id.clone()), &[realtime])
             .await?;
         tx.commit().await?;
-        Ok(())
+        Ok(true)
+    }
+
+    /// Stores announced delays of a trip instance. Of delays announced for the
+    /// same stop at the same time, the last one is kept.
+    pub async fn put_historic_delays(
+        &self,
+        trip_id: &Id<Trip>,
+        trip_start_date: NaiveDate,
+        delays: Vec<HistoricDelay>,
+    ) -> RequestResult<()> {
+        Ok(self
+            .database
+            .auto()
+            .put_historic_delays(&self.origin(), trip_id, trip_start_date, &delays)
+            .await?)
     }
 
+    /// Announced delays of a trip instance by the given origins, ordered by stop
+    /// sequence and time of announcement.
+    pub async fn get_historic_delays(
+        &self,
+        trip_id: &Id<Trip>,
+        trip_start_date: NaiveDate,
+        origins: &[Id<Origin>],
+    ) -> RequestResult<Vec<HistoricDelay>> {
+        Ok(self
+            .reader()
+            .get_historic_delays(trip_id, trip_start_date)
+            .await?
+            .into_iter()
+            .filter(|delay| origins.contains(&delay.origin))
+            .map(|delay| delay.content)
+            .collect())
+    }
+
+    /// Deletes announced delays of trip instances, which started more than
+    /// `retention` ago.
+    pub async fn prune_historic_delays(
+        &self,
+        retention: Duration,
+    ) -> RequestResult<u64> {
+        let before = (Local::now() - retention).date_naive();
+        Ok(self
+            .database
+            .auto()
+            .delete_historic_delays_before(before)
+            .await?)
+    }
+
+    /// Update of the trip instance starting on the date. The instance selects one
+    /// of the departures of frequency based trips. Stale updates are ignored, see
+    /// [`TripUpdate::is_valid_for`].
     pub async fn get_realtime_for_trip(
         &self,
         trip_id: &Id<Trip>,
         trip_start_date: NaiveDate,
+        instance: u32,
         origins: &[Id<Origin>],
     ) -> RequestResult<WithId<TripUpdate>> {
-        self.database
-            .auto()
-            .get_realtime_for_trip(trip_id, trip_start_date)
+        self.reader()
+            .get_realtime_for_trip(trip_id, trip_start_date, instance)
             .await?
+            .let_owned(without_stale_updates)
             .merge_from(origins)
             .ok_or(crate::RequestError::NotFound)
     }
 
-    pub async fn get_realtime_for_trips_in_range<'c>(
+    /// Updates of the trip instances, each identified by its trip, service day and
+    /// instance. Stale updates are ignored, see [`TripUpdate::is_valid_for`].
+    pub async fn get_realtime_for_trip_instances(
+        &self,
+        ids: &[Id<TripUpdate>],
+        origins: &[Id<Origin>],
+    ) -> RequestResult<Vec<WithId<TripUpdate>>> {
+        self.reader()
+            .get_realtime_for_trip_instances(ids)
+            .await?
+            .into_iter()
+            .map(without_stale_updates)
+            .collect::<Vec<_>>()
+            .merge_all_from(origins)
+            .let_owned(Ok)
+    }
+
+    /// Updates with stop time updates at the specified stop, including those of
+    /// added trips, which are not part of the schedule.
+    pub async fn get_realtime_updates_for_stop(
         &self,
-        trip_ids: &[Id<Trip>],
+        stop_id: &Id<Stop>,
         range: DateTimeRange<Local>,
         origins: &[Id<Origin>],
     ) -> RequestResult<Vec<WithId<TripUpdate>>> {
-        self.database
-            .auto()
-            .get_realtime_for_trips_in_range(trip_ids, range)
+        self.reader()
+            .get_realtime_updates_for_stop(stop_id, range)
             .await?
+            .into_iter()
+            .map(without_stale_updates)
+            .collect::<Vec<_>>()
             .merge_all_from(origins)
             .let_owned(Ok)
     }
@@ -1058,6 +2577,27 @@ where
         &self,
         stations: Vec<WithId<SharedMobilityStation>>,
     ) -> RequestResult<Vec<WithId<SharedMobilityStation>>> {
+        // stations can not exist without a location or name.
+        let stations = stations
+            .into_iter()
+            .filter_map(|mut station| {
+                let Some(name) = sanitize(&station.content.name, text_limits().name)
+                else {
+                    println!(
+                        "Dropped shared mobility station '{}' without name from {}.",
+                        station.id, self.id
+                    );
+                    return None;
+                };
+                station.content.name = name;
+                self.check_coordinates(
+                    station.content.latitude,
+                    station.content.longitude,
+                    &station.content.name,
+                )
+                .then_some(station)
+            })
+            .collect::<Vec<_>>();
         let origin = Id::new(self.id.clone());
         let mut tx = self.database.transaction().await?;
         for chunk in stations.chunks(D::BULK_INSERT_MAX) {
@@ -1086,25 +2626,368 @@ where
         Ok(())
     }
 
+    /// Sets the statuses of the stations of this origin in chunks. Gives the ids
+    /// of the statuses without a station, e.g. as the station information is
+    /// outdated.
+    pub async fn update_shared_mobility_station_statuses(
+        &self,
+        statuses: &[(Id<SharedMobilityStation>, Option<Status>)],
+    ) -> RequestResult<Vec<Id<SharedMobilityStation>>> {
+        let origin = Id::new(self.id.clone());
+        let mut unknown = vec![];
+        let mut tx = self.database.transaction().await?;
+        for chunk in statuses.chunks(D::BULK_INSERT_MAX) {
+            unknown.extend(
+                tx.update_shared_mobility_station_statuses(&origin, chunk)
+                    .await?,
+            );
+        }
+        tx.commit().await?;
+        Ok(unknown)
+    }
+
+    /// Nearest first, at most `limit`. With `rentable_only`, stations without a
+    /// known status are excluded as well.
     pub async fn find_nearby_shared_mobility_stations(
         &self,
         latitude: f64,
         longitude: f64,
         radius_km: f64,
+        rentable_only: bool,
+        limit: usize,
         origins: &[Id<Origin>],
     ) -> RequestResult<Vec<WithDistance<WithId<SharedMobilityStation>>>> {
-        self.database
-            .auto()
+        let mut stations = self
+            .reader()
             .find_nearby_shared_mobility_stations(latitude, longitude, radius_km)
             .await?
             .merge_all_from(origins)
             .into_iter()
+            .filter(|station| {
+                !rentable_only
+                    || station
+                        .content
+                        .status
+                        .as_ref()
+                        .is_some_and(|status| status.is_rentable())
+            })
             .filter_map(|stop| {
                 stop.content
                     .with_distance_to(latitude, longitude)
                     .map(|with_distance| with_distance.with_id(stop.id))
             })
-            .collect::<Vec<_>>()
-            .let_owned(|stops| Ok(stops))
+            .collect::<Vec<_>>();
+        WithDistance::sort(&mut stations);
+        stations.truncate(limit);
+        Ok(stations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::{
+        fixtures::{long_trip, stop_time, trip},
+        trip::{AreaKind, AreaReference},
+    };
+
+    #[test]
+    fn long_trips_arrive_at_their_terminus_but_do_not_depart_from_it() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let at = |hour| {
+            date.and_hms_opt(hour, 0, 0)
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap()
+        };
+        let [kiel, puttgarden] =
+            ["kiel", "puttgarden"].map(|id| Id::<Stop>::new(id.to_owned()));
+        let matches = |stop: &Id<Stop>, window: &DateTimeRange<Local>, mode| {
+            instantiate_trip_naive(
+                &long_trip(),
+                &date,
+                Some((window, mode)),
+                Some(&[stop]),
+            )
+            .expect("trip is instantiated")
+            .matches_filters
+        };
+
+        // the window only covers the arrival at the terminus.
+        let terminus = DateTimeRange::new(at(11) + Duration::minutes(30), at(12));
+        assert!(matches(&puttgarden, &terminus, WindowMode::ArriveBetween));
+        assert!(!matches(&puttgarden, &terminus, WindowMode::DepartBetween));
+        assert!(!matches(&kiel, &terminus, WindowMode::DepartBetween));
+
+        // the window only covers the departure at the origin.
+        let origin = DateTimeRange::new(at(10), at(10) + Duration::minutes(30));
+        assert!(matches(&kiel, &origin, WindowMode::DepartBetween));
+        assert!(!matches(&kiel, &origin, WindowMode::ArriveBetween));
+        assert!(!matches(&puttgarden, &origin, WindowMode::ArriveBetween));
+    }
+
+    #[test]
+    fn trips_are_instantiated_for_all_filter_combinations() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let at = |hour, minute| {
+            date.and_hms_opt(hour, minute, 0)
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap()
+        };
+        let morning = DateTimeRange::new(at(8, 5), at(8, 25));
+        let early = DateTimeRange::new(at(7, 55), at(8, 5));
+        let late = DateTimeRange::new(at(8, 15), at(8, 25));
+        let [a, b, c, unknown] =
+            ["a", "b", "c", "unknown"].map(|id| Id::<Stop>::new(id.to_owned()));
+        let depart = WindowMode::DepartBetween;
+        let arrive = WindowMode::ArriveBetween;
+
+        // filters, whether they match, and the stop sequence of the stop of
+        // interest.
+        let cases: [(_, Option<&[&Id<Stop>]>, _, _); 12] = [
+            (None, None, true, None),
+            (None, Some(&[&b]), true, Some(2)),
+            (None, Some(&[&c, &b]), true, Some(3)),
+            (None, Some(&[&unknown]), false, None),
+            (Some((&morning, depart)), None, true, Some(2)),
+            // no departure from the last stop, no arrival at the first one.
+            (Some((&late, depart)), None, false, None),
+            (Some((&late, arrive)), None, true, Some(3)),
+            (Some((&early, arrive)), None, false, None),
+            (Some((&early, depart)), None, true, Some(1)),
+            (Some((&morning, depart)), Some(&[&a]), false, None),
+            (Some((&morning, depart)), Some(&[&a, &b]), true, Some(2)),
+            (Some((&morning, arrive)), Some(&[&c, &b]), true, Some(3)),
+        ];
+        for (index, (range, stop_ids, matches_filters, stop_of_interest)) in
+            cases.into_iter().enumerate()
+        {
+            let instance = instantiate_trip_naive(&trip(), &date, range, stop_ids)
+                .unwrap_or_else(|| panic!("trip is instantiated in case {}", index));
+            assert_eq!(instance.stops.len(), 3, "stops of case {}", index);
+            assert_eq!(
+                instance.matches_filters, matches_filters,
+                "matching filters of case {}",
+                index
+            );
+            assert_eq!(
+                instance
+                    .stop_of_interest
+                    .as_ref()
+                    .map(|stop_time| stop_time.stop_sequence),
+                stop_of_interest,
+                "stop of interest of case {}",
+                index
+            );
+        }
+
+        // without filters, every stop time is of interest.
+        let instance = instantiate_trip_naive(&trip(), &date, None, None).unwrap();
+        assert!(instance
+            .stops
+            .iter()
+            .all(|stop_time| stop_time.interest_flag));
+        assert_eq!(
+            instance.stops[0].departure_time,
+            Some(at(8, 0)),
+            "stop times are shifted to the date"
+        );
+    }
+
+    #[test]
+    fn on_demand_areas_are_no_stops_of_interest() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let at = |hour, minute| {
+            date.and_hms_opt(hour, minute, 0)
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap()
+        };
+        // `b` is served within an area, e.g. with its stop as a meeting point.
+        let mut trip = trip();
+        trip.content.stops[1].area_reference = Some(AreaReference {
+            id: "area".to_owned(),
+            kind: AreaKind::LocationGroup,
+        });
+        let [a, b] = ["a", "b"].map(|id| Id::<Stop>::new(id.to_owned()));
+        let around_b = DateTimeRange::new(at(8, 5), at(8, 15));
+        let depart = WindowMode::DepartBetween;
+
+        let instance = instantiate_trip_naive(&trip, &date, None, None)
+            .expect("trip is instantiated");
+        let on_demand = instance
+            .stops
+            .iter()
+            .map(|stop_time| stop_time.on_demand)
+            .collect::<Vec<_>>();
+        assert_eq!(on_demand, [false, true, false]);
+
+        // filters, whether they match, and the stop sequence of the stop of
+        // interest.
+        let cases: [(_, Option<&[&Id<Stop>]>, _, _); 4] = [
+            (None, Some(&[&b]), false, None),
+            (None, Some(&[&b, &a]), true, Some(1)),
+            (Some((&around_b, depart)), None, false, None),
+            (Some((&around_b, depart)), Some(&[&b]), false, None),
+        ];
+        for (index, (range, stop_ids, matches_filters, stop_of_interest)) in
+            cases.into_iter().enumerate()
+        {
+            let instance = instantiate_trip_naive(&trip, &date, range, stop_ids)
+                .unwrap_or_else(|| panic!("trip is instantiated in case {}", index));
+            assert_eq!(
+                instance.matches_filters, matches_filters,
+                "matching filters of case {}",
+                index
+            );
+            assert_eq!(
+                instance
+                    .stop_of_interest
+                    .as_ref()
+                    .map(|stop_time| stop_time.stop_sequence),
+                stop_of_interest,
+                "stop of interest of case {}",
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn stops_allowing_drop_off_only_show_why_there_is_no_pickup() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let mut trip = trip();
+        trip.content.stops[1].pickup_type = Some(PickupDropOffType::NotAvailable);
+        let instance = instantiate_trip_naive(&trip, &date, None, None)
+            .expect("trip is instantiated");
+
+        let stop = &instance.stops[1];
+        assert_eq!(stop.pickup_type, PickupDropOffType::NotAvailable);
+        assert_eq!(stop.pickup_text.as_deref(), Some("No pickup available."));
+        assert_eq!(stop.drop_off_type, PickupDropOffType::Regular);
+        assert_eq!(stop.drop_off_text, None);
+        assert!(!stop.on_request, "no pickup is not on request");
+        // regular stops have no texts.
+        assert_eq!(instance.stops[0].pickup_text, None);
+    }
+
+    /// Trip calling at `a` and `b` ten minutes apart, which departs every ten
+    /// minutes from 08:00 and every fifteen minutes from 08:30, until 09:00.
+    fn shuttle() -> WithId<Trip> {
+        let period = |start: i64, end: i64, headway: i64| Frequency {
+            start_time: Duration::minutes(start),
+            end_time: Duration::minutes(end),
+            headway: Duration::minutes(headway),
+            exact_times: true,
+        };
+        let mut trip = trip();
+        trip.id = Id::new("shuttle".to_owned());
+        // only the intervals between the stops are kept.
+        trip.content.stops = vec![stop_time(1, "a", 360), stop_time(2, "b", 370)];
+        trip.content.frequencies = vec![period(480, 510, 10), period(510, 540, 15)];
+        trip
+    }
+
+    #[test]
+    fn frequency_based_trips_depart_within_their_periods() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let at = |hour, minute| {
+            date.and_hms_opt(hour, minute, 0)
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap()
+        };
+        let instances = instantiate_trips_naive(&shuttle(), &date, None, None);
+        let departures = instances
+            .iter()
+            .map(|instance| {
+                let index = instance.info.instance_id.decode().map(|id| id.2);
+                (index, instance.stops[0].departure_time)
+            })
+            .collect::<Vec<_>>();
+        // the second period starts at the end of the first one, without a
+        // departure at both.
+        assert_eq!(
+            departures,
+            [
+                (Some(0), Some(at(8, 0))),
+                (Some(1), Some(at(8, 10))),
+                (Some(2), Some(at(8, 20))),
+                (Some(3), Some(at(8, 30))),
+                (Some(4), Some(at(8, 45))),
+            ]
+        );
+        assert_eq!(
+            instances[3].stops[1].arrival_time,
+            Some(at(8, 40)),
+            "stop times are shifted to the departure"
+        );
+        // the first departure of the day, without instantiating all of them.
+        let first = instantiate_trip_naive(&shuttle(), &date, None, None).unwrap();
+        assert_eq!(first.stops[0].departure_time, Some(at(8, 0)));
+    }
+
+    #[test]
+    fn frequency_based_departures_are_sorted_with_scheduled_ones() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let at = |hour, minute| {
+            date.and_hms_opt(hour, minute, 0)
+                .unwrap()
+                .and_local_timezone(Local)
+                .unwrap()
+        };
+        let a = Id::<Stop>::new("a".to_owned());
+        // scheduled trips departing from `a` at 08:25 and at 09:05.
+        let scheduled = [505, 545].map(|minutes| {
+            let mut trip = trip();
+            trip.id = Id::new(format!("trip-{}", minutes));
+            trip.content.stops =
+                vec![stop_time(1, "a", minutes), stop_time(2, "b", minutes + 10)];
+            trip
+        });
+        let departures = |window: &DateTimeRange<Local>| {
+            let mut instances = scheduled
+                .iter()
+                .chain([&shuttle()])
+                .flat_map(|trip| {
+                    instantiate_trips_naive(
+                        trip,
+                        &date,
+                        Some((window, WindowMode::DepartBetween)),
+                        Some(&[&a]),
+                    )
+                })
+                .filter(|instance| instance.matches_filters)
+                .collect::<Vec<_>>();
+            TripInstance::sort(&mut instances);
+            instances
+                .into_iter()
+                .map(|instance| {
+                    let departure = instance
+                        .stop_of_interest
+                        .and_then(|stop_time| stop_time.departure_time);
+                    (instance.info.trip_id.raw(), departure)
+                })
+                .collect::<Vec<_>>()
+        };
+        let departure =
+            |trip: &str, hour, minute| (trip.to_owned(), Some(at(hour, minute)));
+
+        let window = DateTimeRange::new(at(8, 15), at(8, 50));
+        assert_eq!(
+            departures(&window),
+            [
+                departure("shuttle", 8, 20),
+                departure("trip-505", 8, 25),
+                departure("shuttle", 8, 30),
+                departure("shuttle", 8, 45),
+            ]
+        );
+        // the shuttle does not depart at the end of its last period.
+        let window = DateTimeRange::new(at(8, 40), at(9, 20));
+        assert_eq!(
+            departures(&window),
+            [departure("shuttle", 8, 45), departure("trip-545", 9, 5)]
+        );
     }
 }
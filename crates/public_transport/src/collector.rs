@@ -5,21 +5,44 @@ use std::any::Any;
 use std::fmt::Debug;
 use std::panic::AssertUnwindSafe;
 use std::time::Duration;
-use tokio::time::{self, sleep};
+use tokio::{
+    task::JoinHandle,
+    time::{self, sleep, Instant},
+};
 use utility::id::{HasId, Id};
 
 use async_trait::async_trait;
 use chrono::{DateTime, Local};
 
 use crate::{
+    capability::Requirement,
     client::Client,
     database::{CollectorRepo, Database},
 };
 
+/// How long to wait for the lease of a collector, e.g. while another instance
+/// finishes running it, before skipping the run.
+const LEASE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait between two attempts to lease a collector.
+const LEASE_RETRY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long the lease of a run lasts, unless it is renewed. Bounds how long a
+/// collector is blocked by an instance, which crashed while running it.
+const LEASE_DURATION: Duration = Duration::from_secs(5 * 60);
+
+/// How often the lease of a running collector is renewed.
+const LEASE_RENEWAL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long a health check may take, before the collector is considered unhealthy.
+const HEALTHCHECK_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub struct CollectorInstance<C: Collector> {
     pub origin: Id<Origin>,
     pub is_active: bool,
     pub state: C::State,
+    /// When the collector is run next, if it asked to sleep.
+    pub next_run_at: Option<DateTime<Local>>,
 }
 
 impl<C> HasId for CollectorInstance<C>
@@ -29,12 +52,54 @@ where
     type IdType = i32;
 }
 
+/// Outcome of an attempt to lease a collector instance for a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollectorLease {
+    /// The run started at the given time, which identifies the lease.
+    Acquired(DateTime<Local>),
+    /// Another run holds the lease.
+    Held,
+    /// The instance asked to sleep until later or was run less than the spacing
+    /// ago.
+    NotDue,
+}
+
+/// Outcome of the health check of a collector instance.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectorHealth {
+    pub id: i32,
+    pub origin: Id<Origin>,
+    pub kind: String,
+    pub is_active: bool,
+    /// `None`, if the collector was not checked yet.
+    pub healthy: Option<bool>,
+    pub checked_at: Option<DateTime<Local>>,
+    pub error: Option<String>,
+    /// Failed runs since the last successful one.
+    pub consecutive_failures: i32,
+    pub last_success_at: Option<DateTime<Local>>,
+    /// When the collector is run next, if it asked to sleep.
+    pub next_run_at: Option<DateTime<Local>>,
+    /// When the next successful run is expected, the last successful one plus the
+    /// delay the collector asked for.
+    pub due_at: Option<DateTime<Local>>,
+}
+
+/// When to run a collector again after a successful run.
 #[derive(Clone)]
 pub enum Continuation {
-    ContinueAfter(Duration),
+    /// Run again after the given delay, regardless of the tick. The time of the
+    /// next run is stored, so restarting the server does not run it earlier.
+    Sleep(Duration),
+    /// Run again at the given time. Stored like `Sleep`.
     ContinueAt(DateTime<Local>),
+    /// Run again after the next tick, or immediately without a tick.
     Continue,
+    /// Create the collector again from its stored state and run it after the next
+    /// tick.
     Restart,
+    /// Do not run again, until the server is restarted.
     Exit,
 }
 
@@ -46,7 +111,7 @@ pub enum SupervisionStrategy {
 }
 
 #[async_trait]
-pub trait Collector {
+pub trait Collector: Sized {
     type Error: Debug;
     type State: Debug
         + Clone
@@ -65,8 +130,15 @@ pub trait Collector {
     fn unique_id() -> &'static str;
 
     /// Creates a new instance of the collector from a given state.
-    /// Usually, this state is loaded from the database.
-    fn from_state(state: Self::State) -> Self;
+    /// Usually, this state is loaded from the database. Fails, if the state is
+    /// invalid, e.g. misses required settings.
+    fn from_state(state: Self::State) -> Result<Self, Self::Error>;
+
+    /// Capabilities of the database the collector needs. Instances of collectors
+    /// with missing required capabilities are not started.
+    fn requirements() -> &'static [Requirement] {
+        &[]
+    }
 
     /// This method is regularly called and supposed to gahter data and push
     /// it to the database.
@@ -76,6 +148,21 @@ pub trait Collector {
         state: Self::State,
     ) -> Result<(Continuation, Self::State), Self::Error>;
 
+    /// Cheap check of the configuration, e.g., whether the source is reachable and
+    /// the credentials are accepted. Called once, before the collector is first
+    /// run. A failing check is reported, but does not keep the collector from
+    /// being scheduled.
+    async fn healthcheck(&mut self, _state: &Self::State) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Describes failures of a run, which returned a new state nonetheless, e.g.
+    /// of some of multiple sources. Such runs are recorded as failed, but continue
+    /// as requested, so that the succeeded parts are not repeated.
+    fn partial_failure(&self, _state: &Self::State) -> Option<String> {
+        None
+    }
+
     /// Specifies how long to wait between calls to the `run` method.
     fn tick(&self) -> Option<Duration> {
         Some(Duration::from_secs(10))
@@ -100,6 +187,23 @@ pub trait Collector {
 
 pub struct CollectorRef;
 
+/// Outcome of starting a collector instance.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectorStartup {
+    pub id: i32,
+    pub origin: Id<Origin>,
+    pub kind: String,
+    /// `None`, if the collector was started.
+    pub error: Option<String>,
+}
+
+impl CollectorStartup {
+    pub fn is_started(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
 async fn run_persistent<'a, D, C>(
     id: Id<CollectorInstance<C>>,
     collector: &mut C,
@@ -109,44 +213,325 @@ where
     D: Database,
     C: Collector + 'static,
 {
-    let data = client.database.auto().get_collector(&id).await.unwrap();
+    // Multiple server instances may share the same database. The lease ensures,
+    // that a collector is only run by one of them at a time, and not again by
+    // another one within half a tick. It is renewed while the collector runs, so
+    // that no transaction is held open for the whole run.
+    let spacing = collector.tick().map_or(Duration::ZERO, |tick| tick / 2);
+    let Some(started_at) = lease(client, &id, spacing).await else {
+        log::info!(
+            "Skipped run of collector '{}' ({}), as it is leased by another instance or was run recently.",
+            C::unique_id(),
+            id
+        );
+        return Ok(Continuation::Continue);
+    };
+    // a panicking run stops renewing the lease, which then expires.
+    let renewal = LeaseRenewal::spawn(client.clone(), id, started_at);
+    let result = run_leased(id, collector, client).await;
+    drop(renewal);
+    if let Err(why) = client
+        .database
+        .auto()
+        .release_collector_lease(&id, started_at)
+        .await
+    {
+        eprintln!("could not release lease of collector: {:?}", why);
+    }
+    result
+}
+
+/// Runs the collector with its stored state and stores the new state.
+async fn run_leased<D, C>(
+    id: Id<CollectorInstance<C>>,
+    collector: &mut C,
+    client: &Client<D>,
+) -> Result<Continuation, C::Error>
+where
+    D: Database,
+    C: Collector + 'static,
+{
+    let data = match client.database.auto().get_collector(&id).await {
+        Ok(data) => data,
+        Err(why) => {
+            eprintln!("could not load state of collector: {:?}", why);
+            return Ok(Continuation::Continue);
+        }
+    };
     if !data.is_active {
         return Ok(Continuation::Exit);
     }
     let result = collector.run(client, data.state).await;
-    match result {
-        Ok((continuation, new_state)) => {
-            client
-                .database
-                .auto()
-                .set_collector_state(&id, new_state)
-                .await
-                .unwrap();
-            Ok(continuation)
+    if let Ok((_, new_state)) = &result {
+        if let Err(why) = client
+            .database
+            .auto()
+            .set_collector_state(&id, new_state.clone())
+            .await
+        {
+            eprintln!("could not store state of collector: {:?}", why);
+        }
+    }
+    let succeeded = match &result {
+        Ok((_, new_state)) => match collector.partial_failure(new_state) {
+            Some(why) => {
+                eprintln!(
+                    "collector '{}' ({}) failed partially: {}",
+                    C::unique_id(),
+                    id,
+                    why
+                );
+                false
+            }
+            None => true,
+        },
+        Err(_) => false,
+    };
+    let run_interval = result
+        .as_ref()
+        .ok()
+        .and_then(|(continuation, _)| run_interval(continuation, collector.tick()));
+    record_run(client, &id, succeeded, run_interval).await;
+    result.map(|(continuation, _)| continuation)
+}
+
+/// Leases the collector for a run, waiting up to [`LEASE_TIMEOUT`] for another
+/// run to release it. Returns the start of the run, or `None`, if the run is
+/// skipped. A collector, which is not due, is skipped right away, as it would
+/// otherwise run within the spacing after the run of another instance.
+async fn lease<D, C>(
+    client: &Client<D>,
+    id: &Id<CollectorInstance<C>>,
+    spacing: Duration,
+) -> Option<DateTime<Local>>
+where
+    D: Database,
+    C: Collector + 'static,
+{
+    let duration = chrono::Duration::from_std(LEASE_DURATION).ok()?;
+    let spacing = chrono::Duration::from_std(spacing).ok()?;
+    let deadline = Instant::now() + LEASE_TIMEOUT;
+    loop {
+        match client
+            .database
+            .auto()
+            .try_lease_collector(id, duration, spacing)
+            .await
+        {
+            Ok(CollectorLease::Acquired(started_at)) => return Some(started_at),
+            Ok(CollectorLease::Held) => {}
+            Ok(CollectorLease::NotDue) => return None,
+            Err(why) => {
+                eprintln!("could not lease collector: {:?}", why);
+                return None;
+            }
+        }
+        if Instant::now() >= deadline {
+            return None;
         }
-        Err(why) => Err(why),
+        sleep(LEASE_RETRY_INTERVAL).await;
+    }
+}
+
+/// Renews the lease of a run periodically, until it is dropped.
+struct LeaseRenewal(JoinHandle<()>);
+
+impl LeaseRenewal {
+    fn spawn<D, C>(
+        client: Client<D>,
+        id: Id<CollectorInstance<C>>,
+        started_at: DateTime<Local>,
+    ) -> Self
+    where
+        D: Database,
+        C: Collector + 'static,
+    {
+        Self(tokio::spawn(async move {
+            let Ok(duration) = chrono::Duration::from_std(LEASE_DURATION) else {
+                return;
+            };
+            let mut interval = time::interval(LEASE_RENEWAL_INTERVAL);
+            // the first tick completes immediately, right after the lease.
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                match client
+                    .database
+                    .auto()
+                    .renew_collector_lease(&id, started_at, duration)
+                    .await
+                {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        eprintln!(
+                            "lease of collector '{}' ({}) expired during its run.",
+                            C::unique_id(),
+                            id
+                        );
+                        return;
+                    }
+                    Err(why) => {
+                        eprintln!("could not renew lease of collector: {:?}", why)
+                    }
+                }
+            }
+        }))
+    }
+}
+
+impl Drop for LeaseRenewal {
+    fn drop(&mut self) {
+        self.0.abort();
     }
 }
 
+/// Delay until the next run, which a run asked for by its continuation. `None`, if
+/// the collector does not run again.
+fn run_interval(
+    continuation: &Continuation,
+    tick: Option<Duration>,
+) -> Option<chrono::Duration> {
+    let delay = match continuation {
+        Continuation::Sleep(delay) => *delay,
+        Continuation::ContinueAt(next_run_at) => delay_until(*next_run_at),
+        Continuation::Continue | Continuation::Restart => tick.unwrap_or_default(),
+        Continuation::Exit => return None,
+    };
+    chrono::Duration::from_std(delay).ok()
+}
+
+/// Stores the outcome of a run, which the alert rules are evaluated on.
+async fn record_run<D, C>(
+    client: &Client<D>,
+    id: &Id<CollectorInstance<C>>,
+    succeeded: bool,
+    run_interval: Option<chrono::Duration>,
+) where
+    D: Database,
+    C: Collector + 'static,
+{
+    if let Err(why) = client
+        .database
+        .auto()
+        .record_collector_run(id, succeeded, run_interval)
+        .await
+    {
+        eprintln!("could not store run of collector: {:?}", why);
+    }
+}
+
+/// Stores the time of the next run and waits for it.
+async fn sleep_persistent<D, C>(
+    client: &Client<D>,
+    id: &Id<CollectorInstance<C>>,
+    next_run_at: Option<DateTime<Local>>,
+    delay: Duration,
+) where
+    D: Database,
+    C: Collector + 'static,
+{
+    if let Err(why) = client
+        .database
+        .auto()
+        .set_collector_next_run(id, next_run_at)
+        .await
+    {
+        eprintln!("could not store next run of collector: {:?}", why);
+    }
+    sleep(delay).await;
+}
+
+/// Removes the stored time of the next run, once the collector runs again.
+async fn clear_next_run<D, C>(client: &Client<D>, id: &Id<CollectorInstance<C>>)
+where
+    D: Database,
+    C: Collector + 'static,
+{
+    if let Err(why) = client
+        .database
+        .auto()
+        .set_collector_next_run(id, None)
+        .await
+    {
+        eprintln!("could not clear next run of collector: {:?}", why);
+    }
+}
+
+/// Time until the given one, zero if it has passed.
+fn delay_until(time: DateTime<Local>) -> Duration {
+    (time - Local::now()).to_std().unwrap_or(Duration::ZERO)
+}
+
+/// Starts the collector instance. Fails, if its state could not be loaded or the
+/// collector could not be created from it.
 pub async fn run<D, C, F>(
     factory: F,
     client: Client<D>,
     id: Id<CollectorInstance<C>>,
-) -> CollectorRef
+) -> Result<CollectorRef, String>
 where
     D: Database,
     C: Collector + Send + 'static,
     <C as Collector>::Error: Send,
-    F: 'static + Send + Fn(C::State) -> C,
+    F: 'static + Send + Fn(C::State) -> Result<C, C::Error>,
 {
-    let instance = client.database.auto().get_collector(&id).await.unwrap(); // TODO!
-    let mut collector = factory(instance.state);
+    let instance = client
+        .database
+        .auto()
+        .get_collector(&id)
+        .await
+        .map_err(|why| format!("could not load state: {:?}", why))?;
+    let state = instance.state.clone();
+    let next_run_at = instance.next_run_at;
+    let mut collector =
+        factory(instance.state).map_err(|why| format!("invalid state: {:?}", why))?;
 
     // run actor
     tokio::spawn(async move {
+        // check health. does not delay the registration of other collectors.
+        let health =
+            match time::timeout(HEALTHCHECK_TIMEOUT, collector.healthcheck(&state))
+                .await
+            {
+                Ok(Ok(())) => None,
+                Ok(Err(why)) => Some(format!("{:?}", why)),
+                Err(_) => Some("health check timed out.".to_owned()),
+            };
+        if let Some(why) = &health {
+            eprintln!(
+                "health check of collector '{}' ({}) failed: {}",
+                C::unique_id(),
+                id,
+                why
+            );
+        }
+        if let Err(why) = client
+            .database
+            .auto()
+            .set_collector_health(&id, health)
+            .await
+        {
+            eprintln!("could not store collector health: {:?}", why);
+        }
+
+        // a collector, which asked to sleep, is not run earlier after a restart.
+        let mut is_sleep_stored = next_run_at.is_some();
+        if let Some(next_run_at) = next_run_at {
+            sleep(delay_until(next_run_at)).await;
+        }
+
         let mut interval = collector.tick().map(|tick| time::interval(tick));
+        // the first tick completes immediately, not a tick after the first run.
+        if let Some(tick) = &mut interval {
+            tick.tick().await;
+        }
         let mut backoff = collector.tick().unwrap_or(Duration::from_secs(10));
         loop {
+            // the sleep is over, so that the stored time of the next run is stale.
+            if is_sleep_stored {
+                clear_next_run(&client, &id).await;
+                is_sleep_stored = false;
+            }
             // run
             let result =
                 AssertUnwindSafe(run_persistent(id, &mut collector, &client))
@@ -161,17 +546,32 @@ where
                 }
                 Err(why) => {
                     eprintln!("collector paniced: {:?}", why);
+                    record_run(&client, &id, false, None).await;
                     Err(collector.on_panic(why))
                 }
             };
             // continue
             if let Ok(continuation) = result.clone() {
                 match continuation {
-                    Continuation::ContinueAfter(_) => {
-                        todo!();
+                    Continuation::Sleep(delay) => {
+                        let next_run_at = chrono::Duration::from_std(delay)
+                            .ok()
+                            .and_then(|delay| Local::now().checked_add_signed(delay));
+                        sleep_persistent(&client, &id, next_run_at, delay).await;
+                        is_sleep_stored = true;
+                        // the tick counts from the end of the sleep.
+                        if let Some(tick) = &mut interval {
+                            tick.reset();
+                        }
                     }
-                    Continuation::ContinueAt(_) => {
-                        todo!();
+                    Continuation::ContinueAt(next_run_at) => {
+                        let delay = delay_until(next_run_at);
+                        sleep_persistent(&client, &id, Some(next_run_at), delay)
+                            .await;
+                        is_sleep_stored = true;
+                        if let Some(tick) = &mut interval {
+                            tick.reset();
+                        }
                     }
                     Continuation::Continue => {
                         if let Some(tick) = &mut interval {
@@ -180,12 +580,21 @@ where
                     }
                     Continuation::Restart => {
                         match client.database.auto().get_collector(&id).await {
-                            Ok(value) => {
-                                collector = factory(value.state);
-                                if let Some(tick) = &mut interval {
-                                    tick.tick().await;
+                            Ok(value) => match factory(value.state) {
+                                Ok(restarted) => {
+                                    collector = restarted;
+                                    if let Some(tick) = &mut interval {
+                                        tick.tick().await;
+                                    }
                                 }
-                            }
+                                Err(why) => {
+                                    eprintln!(
+                                        "collector could not restart: {:?}",
+                                        why
+                                    );
+                                    result = Err(collector.on_error(why))
+                                }
+                            },
                             Err(why) => {
                                 result = Err(collector.on_panic(Box::new(why)))
                             }
@@ -202,9 +611,18 @@ where
                 match strategy {
                     SupervisionStrategy::Restart => {
                         match client.database.auto().get_collector(&id).await {
-                            Ok(value) => {
-                                collector = factory(value.state);
-                            }
+                            Ok(value) => match factory(value.state) {
+                                Ok(restarted) => {
+                                    collector = restarted;
+                                }
+                                Err(why) => {
+                                    eprintln!(
+                                        "collector could not restart: {:?}",
+                                        why
+                                    );
+                                    result = Err(collector.on_error(why))
+                                }
+                            },
                             Err(why) => {
                                 result = Err(collector.on_panic(Box::new(why)))
                             }
@@ -223,5 +641,49 @@ where
         }
     });
 
-    CollectorRef {}
+    Ok(CollectorRef {})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_intervals_are_the_delays_until_the_next_run() {
+        let seconds = |secs| Some(chrono::Duration::seconds(secs));
+        let tick = Some(Duration::from_secs(10));
+        let cases = [
+            (
+                Continuation::Sleep(Duration::from_secs(3600)),
+                tick,
+                seconds(3600),
+            ),
+            (Continuation::Continue, tick, seconds(10)),
+            (Continuation::Continue, None, seconds(0)),
+            (Continuation::Restart, tick, seconds(10)),
+            (
+                Continuation::ContinueAt(Local::now() - chrono::Duration::hours(1)),
+                tick,
+                seconds(0),
+            ),
+            (Continuation::Exit, tick, None),
+        ];
+        for (index, (continuation, tick, expected)) in cases.into_iter().enumerate() {
+            assert_eq!(
+                run_interval(&continuation, tick),
+                expected,
+                "run interval of case {}",
+                index
+            );
+        }
+        let in_a_day = Local::now() + chrono::Duration::days(1);
+        let interval =
+            run_interval(&Continuation::ContinueAt(in_a_day), tick).unwrap();
+        assert!(
+            interval > chrono::Duration::hours(23)
+                && interval <= chrono::Duration::days(1),
+            "run interval until tomorrow is {}",
+            interval
+        );
+    }
 }
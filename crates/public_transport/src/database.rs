@@ -1,27 +1,44 @@
-use std::{error, fmt::Debug, future::Future, result};
+use std::{
+    collections::HashMap,
+    error,
+    fmt::{self, Debug},
+    future::Future,
+    result,
+};
 
 use async_trait::async_trait;
-use chrono::{DateTime, Local, NaiveDate};
+use chrono::{DateTime, Duration, Local, NaiveDate};
 use model::{
     agency::Agency,
-    calendar::{CalendarDate, CalendarWindow, Service},
+    calendar::{CalendarDate, CalendarWindow, Service, ServiceTag},
+    change::{Change, ChangePosition},
     line::Line,
+    merge::{MergeLogEntry, MergeStatus, MergeSubject},
     origin::{Origin, OriginalIdMapping},
+    pathway::{Level, Pathway},
+    shape::{LineShape, Shape, ShapePoint},
     shared_mobility::{SharedMobilityStation, Status},
-    stop::Stop,
-    trip::{StopTime, Trip},
-    trip_update::TripUpdate,
+    stop::{ServiceSummary, Stop},
+    trip::{CouplingKind, Frequency, StopTime, Trip, TripCoupling},
+    trip_instance::WindowMode,
+    trip_update::{HistoricDelay, TripUpdate, TripUpdateId},
     DatabaseEntry, DateTimeRange, WithId, WithOrigin,
 };
 use serde::Serialize;
 use utility::id::{HasId, Id};
 
-use crate::collector::{Collector, CollectorInstance};
+use crate::{
+    capability::Capabilities,
+    collector::{Collector, CollectorHealth, CollectorInstance, CollectorLease},
+    notification::Alert,
+};
 
 #[derive(Debug)]
 pub enum DatabaseError {
     NotFound,
     IdMissing,
+    /// The operation was cancelled, because the deadline of its request passed.
+    Timeout,
     Other(Box<dyn error::Error + Send + Sync>),
 }
 
@@ -33,7 +50,11 @@ where
     <T as HasId>::IdType: Debug + Clone + Serialize,
 {
     async fn get(&mut self, id: Id<T>) -> Result<DatabaseEntry<T>>;
+    /// All entries ordered by id, so that repeated calls return them (and their
+    /// source data) in the same order.
     async fn get_all(&mut self) -> Result<Vec<DatabaseEntry<T>>>;
+    /// Entries of all given ids, that exist. Missing ids are omitted.
+    async fn get_many(&mut self, ids: &[Id<T>]) -> Result<Vec<DatabaseEntry<T>>>;
     async fn insert(
         &mut self,
         element: WithOrigin<T>,
@@ -110,6 +131,30 @@ pub trait LineRepo: SubjectRepo<Line> + Repo<Line> {
         &mut self,
         stop_id: &Id<Stop>,
     ) -> Result<Vec<DatabaseEntry<Line>>>;
+
+    /// lines at each of the given stops with a single query. stops without lines
+    /// are omitted.
+    async fn get_by_stop_ids(
+        &mut self,
+        stop_ids: &[&Id<Stop>],
+    ) -> Result<HashMap<Id<Stop>, Vec<DatabaseEntry<Line>>>>;
+
+    /// At most `limit` lines, whose names contain the pattern. Names equal to the
+    /// pattern, regardless of spaces and case, come first.
+    async fn search_by_name<S: Into<String> + Send>(
+        &mut self,
+        pattern: S,
+        limit: usize,
+    ) -> Result<Vec<DatabaseEntry<Line>>>;
+
+    /// Returns the earliest and latest departure of all trips of the line, which
+    /// operate on the given service day, relative to the start of the service day.
+    /// Returns `None` if no trip of the line operates on that day.
+    async fn get_departure_span(
+        &mut self,
+        id: &Id<Line>,
+        service_day: NaiveDate,
+    ) -> Result<Option<(Duration, Duration)>>;
 }
 
 #[async_trait]
@@ -130,6 +175,76 @@ pub trait StopRepo: SubjectRepo<Stop> + Repo<Stop> + MergableRepo<Stop> {
         &mut self,
         pattern: S,
     ) -> Result<Vec<DatabaseEntry<Stop>>>;
+
+    /// At most `limit` stops of the given origins, whose names start with or
+    /// resemble the pattern. Prefix matches come first. Only the data of the given
+    /// origins is returned.
+    async fn autocomplete<S: Into<String> + Send>(
+        &mut self,
+        pattern: S,
+        limit: usize,
+        origins: &[Id<Origin>],
+    ) -> Result<Vec<DatabaseEntry<Stop>>>;
+
+    /// Returns at most `limit` stops of the given origins ordered by id, which come
+    /// after the stop with id `after`. Only the data of the given origins is
+    /// returned. Ids never change, so that no stop is skipped or returned twice,
+    /// if stops are renamed while paging.
+    async fn get_page_after(
+        &mut self,
+        after: Option<Id<Stop>>,
+        limit: usize,
+        origins: &[Id<Origin>],
+    ) -> Result<Vec<DatabaseEntry<Stop>>>;
+
+    /// Like `get_page_after`, but only stops, at which trips of the agency's lines
+    /// call. Lines the agency is a secondary agency of count, if
+    /// `include_secondary`. All of them, if `limit` is `None`.
+    async fn get_page_by_agency_after(
+        &mut self,
+        agency_id: &Id<Agency>,
+        include_secondary: bool,
+        after: Option<Id<Stop>>,
+        limit: Option<usize>,
+        origins: &[Id<Origin>],
+    ) -> Result<Vec<DatabaseEntry<Stop>>>;
+
+    /// Children of the stop, e.g. the platforms of a station, in natural order of
+    /// their platform codes. Children without platform code come last.
+    async fn get_platforms(
+        &mut self,
+        parent_id: &Id<Stop>,
+    ) -> Result<Vec<DatabaseEntry<Stop>>>;
+
+    /// Moves the stop of the given origin from id `from` to id `to`, so that it
+    /// becomes part of the subject `to`. Original ids and stop times of the origin
+    /// are redirected accordingly. If the origin already has a stop `to`, its
+    /// fields are kept and only missing ones are taken from `from`.
+    async fn redirect_stop(
+        &mut self,
+        origin: &Id<Origin>,
+        from: &Id<Stop>,
+        to: &Id<Stop>,
+    ) -> Result<()>;
+
+    /// Sets the location of stops of the given origin, which have no location, to
+    /// the centroid of their children (e.g. a station to the centroid of its
+    /// platforms). Returns the number of updated stops.
+    async fn backfill_centroids(&mut self, origin: &Id<Origin>) -> Result<u64>;
+
+    /// Recomputes the last service date of the given stops, or of all stops if
+    /// `None`. Returns the number of updated stops.
+    async fn refresh_service_summary(
+        &mut self,
+        ids: Option<&[&Id<Stop>]>,
+    ) -> Result<u64>;
+
+    /// Returns the service summary of each given stop. Stops without a computed
+    /// summary are omitted.
+    async fn get_service_summaries(
+        &mut self,
+        ids: &[&Id<Stop>],
+    ) -> Result<HashMap<Id<Stop>, ServiceSummary>>;
 }
 
 #[async_trait]
@@ -140,26 +255,47 @@ pub trait TripRepo: SubjectRepo<Trip> + Repo<Trip> {
         stop_time: WithOrigin<StopTime>,
     ) -> Result<WithOrigin<StopTime>>;
 
+    /// Stores the stop times of the trip for the given origin. Stop times, that
+    /// did not change, are not written. If `replace` is set, other stop times of
+    /// the origin are deleted. Returns the number of written stop times.
+    async fn put_stop_times(
+        &mut self,
+        trip_id: &Id<Trip>,
+        origin: &Id<Origin>,
+        stop_times: &[StopTime],
+        replace: bool,
+    ) -> Result<u64>;
+
     async fn get_stop_times(
         &mut self,
         trip_id: Id<Trip>,
         origin: Id<Origin>,
     ) -> Result<Vec<StopTime>>;
 
-    // TODO: return deleted data
+    /// Deletes the stop times of the trip, which belong to the given origin, and
+    /// returns them ordered by stop sequence. Stop times of other origins are kept.
     async fn delete_stop_times(
         &mut self,
         trip_id: Id<Trip>,
         origin: Id<Origin>,
-    ) -> Result<()>;
+    ) -> Result<Vec<StopTime>>;
+
+    /// Sets the stop names of stop times, which differ from their stop, and
+    /// returns their number. Only stops changed according to the change log since
+    /// the last sync are considered.
+    async fn sync_stop_names(&mut self) -> Result<u64>;
+
+    /// Deletes the trip of the given origin, including its stop times and
+    /// original id mappings.
+    async fn delete_trip(&mut self, id: &Id<Trip>, origin: &Id<Origin>)
+        -> Result<()>;
 
     /// Returns all trips, which stop at the specified stop.
     ///
-    /// TODO: maybe take a naive date rather than a datetime, as checking a date and
-    ///       time range requires instanciation of the trips, which a database can
-    ///       not do.
-    ///
-    /// TODO: take separate time range to filter trips based on time of day.
+    /// Depending on `mode`, either the arrival or the departure time at the stop
+    /// has to lie within `start` and `end`. Since trips that extend beyond one day
+    /// have times past midnight and still belong to the previous day, trips
+    /// serviced on the day before `start` have to be considered as well.
     ///
     /// TODO: take optional list of stops where the trip should also stop at.
     ///       maybe make that a separate method. This could be used to implement
@@ -176,7 +312,62 @@ pub trait TripRepo: SubjectRepo<Trip> + Repo<Trip> {
         stops: &[&Id<Stop>],
         start: DateTime<Local>,
         end: DateTime<Local>,
+        mode: WindowMode,
+    ) -> Result<Vec<DatabaseEntry<Trip>>>;
+
+    /// Returns at most `limit` trips of the given origins ordered by id, which come
+    /// after the trip with id `after`. Only the data of the given origins is
+    /// returned.
+    async fn get_page_after(
+        &mut self,
+        after: Option<Id<Trip>>,
+        limit: usize,
+        origins: &[Id<Origin>],
+    ) -> Result<Vec<DatabaseEntry<Trip>>>;
+
+    /// At most `limit` trips, whose headsign, short name or line name combined
+    /// with the short name contain the pattern, and which operate on the given
+    /// service day or the next one. Exact line and short name matches come first.
+    async fn search_text<S: Into<String> + Send>(
+        &mut self,
+        pattern: S,
+        service_day: NaiveDate,
+        limit: usize,
     ) -> Result<Vec<DatabaseEntry<Trip>>>;
+
+    /// Replaces the couplings of the trip of the given origin at one of its stops.
+    /// Coupled trips are referenced by their original id of the same origin, so
+    /// that they may be stored later on.
+    async fn put_trip_couplings(
+        &mut self,
+        trip_id: &Id<Trip>,
+        origin: &Id<Origin>,
+        stop_sequence: i32,
+        stop_id: Option<&Id<Stop>>,
+        couplings: &[(String, CouplingKind)],
+    ) -> Result<()>;
+
+    /// Couplings of the given trips of all origins ordered by trip and stop
+    /// sequence. Couplings with trips, which are not stored, are omitted.
+    async fn get_trip_couplings(
+        &mut self,
+        trip_ids: &[Id<Trip>],
+    ) -> Result<Vec<WithOrigin<TripCoupling>>>;
+
+    /// Inserts or replaces the period of the given origin, which starts at the
+    /// same time.
+    async fn put_frequency(
+        &mut self,
+        trip_id: &Id<Trip>,
+        origin: &Id<Origin>,
+        frequency: &Frequency,
+    ) -> Result<()>;
+
+    /// Periods of the given trips of all origins ordered by trip and start time.
+    async fn get_frequencies(
+        &mut self,
+        trip_ids: &[Id<Trip>],
+    ) -> Result<Vec<(Id<Trip>, WithOrigin<Frequency>)>>;
 }
 
 #[async_trait]
@@ -213,6 +404,35 @@ pub trait ServiceRepo: SubjectRepo<Service> {
         &mut self,
         service_id: &Id<Service>,
     ) -> Result<Vec<CalendarDate>>;
+
+    /// ids of all services of an origin.
+    async fn get_service_ids(
+        &mut self,
+        origin: &Id<Origin>,
+    ) -> Result<Vec<Id<Service>>>;
+
+    /// replaces the tags of the given services.
+    async fn set_service_tags(
+        &mut self,
+        service_ids: &[Id<Service>],
+        tags: &[(Id<Service>, ServiceTag)],
+    ) -> Result<()>;
+
+    /// tags of the given services. Services without tags are omitted.
+    async fn get_service_tags(
+        &mut self,
+        service_ids: &[Id<Service>],
+    ) -> Result<HashMap<Id<Service>, Vec<ServiceTag>>>;
+
+    /// id of the service of the origin with the given signature, see
+    /// `Service::signature`. reserves a new id for the signature, if there is
+    /// none yet, which is indicated by `true`. the calendar of a new service
+    /// has to be put with the returned id afterwards.
+    async fn claim_service_signature(
+        &mut self,
+        origin: &Id<Origin>,
+        signature: &str,
+    ) -> Result<(Id<Service>, bool)>;
 }
 
 #[async_trait]
@@ -228,11 +448,13 @@ pub trait RealtimeRepo {
         updates: &[WithId<TripUpdate>],
     ) -> Result<WithOrigin<Vec<WithId<TripUpdate>>>>;
 
-    /// get realtime info for a specific trip on a specific day.
+    /// get realtime info for a specific trip on a specific day. The instance is
+    /// the index of the departure of a frequency based trip, `0` otherwise.
     async fn get_realtime_for_trip(
         &mut self,
         trip_id: &Id<Trip>,
         trip_start_date: NaiveDate,
+        instance: u32,
     ) -> Result<DatabaseEntry<TripUpdate>>;
 
     /// return the update timestamp (if set) of the realtime info (if exists) for the
@@ -240,23 +462,55 @@ pub trait RealtimeRepo {
     async fn get_timestamp(
         &mut self,
         origin: &Id<Origin>,
-        trip_id: &Id<Trip>,
-        trip_start_date: NaiveDate,
+        id: &TripUpdateId,
     ) -> Result<Option<DateTime<Local>>>;
 
-    /// returns all updates for the specified trips in the specified date-time range.
-    ///
-    /// # WARNING
-    ///
-    /// Might, depending on the database implementation, return trip updates which do
-    /// not meet the specified parameters for performance reasons. This are typically
-    /// elements, where testing for the specified parameters is expensive and thus
-    /// left to the user to decide when it is best to check for those parameters.
-    async fn get_realtime_for_trips_in_range<'c>(
+    /// returns the updates of the specified trip instances, each identified by its
+    /// trip, service day and instance. Instances without updates are omitted.
+    async fn get_realtime_for_trip_instances(
+        &mut self,
+        ids: &[Id<TripUpdate>],
+    ) -> Result<Vec<DatabaseEntry<TripUpdate>>>;
+
+    /// returns all updates of trips starting in the specified date-time range, which
+    /// have a stop time update for the specified stop or one of its children. In
+    /// contrast to looking up the updates by the trips of the schedule, this
+    /// includes added trips.
+    async fn get_realtime_updates_for_stop(
         &mut self,
-        trip_id: &[Id<Trip>],
+        stop_id: &Id<Stop>,
         range: DateTimeRange<Local>,
     ) -> Result<Vec<DatabaseEntry<TripUpdate>>>;
+
+    /// Stores announced delays of a trip instance. Delays, which are already
+    /// stored, are updated. Of delays announced for the same stop at the same
+    /// time, the last one is kept.
+    async fn put_historic_delays(
+        &mut self,
+        origin: &Id<Origin>,
+        trip_id: &Id<Trip>,
+        trip_start_date: NaiveDate,
+        delays: &[HistoricDelay],
+    ) -> Result<()>;
+
+    /// Announced delays of a trip instance of all origins, ordered by stop
+    /// sequence and time of announcement.
+    async fn get_historic_delays(
+        &mut self,
+        trip_id: &Id<Trip>,
+        trip_start_date: NaiveDate,
+    ) -> Result<Vec<WithOrigin<HistoricDelay>>>;
+
+    /// Deletes announced delays of trip instances starting before the date.
+    /// Returns the number of deleted delays.
+    async fn delete_historic_delays_before(&mut self, date: NaiveDate)
+        -> Result<u64>;
+
+    /// Latest timestamp of the updates of each origin with updates since
+    /// yesterday, which is kept by storing updates rather than looked up.
+    async fn latest_update_timestamps(
+        &mut self,
+    ) -> Result<Vec<(Id<Origin>, DateTime<Local>)>>;
 }
 
 #[async_trait]
@@ -280,6 +534,14 @@ pub trait SharedMobilityStationRepo: SubjectRepo<SharedMobilityStation> {
         id: &Id<SharedMobilityStation>,
         status: Option<Status>,
     ) -> Result<()>;
+
+    /// Sets the statuses of many stations of the origin with a single query.
+    /// Gives the ids of the statuses, for which there is no station.
+    async fn update_shared_mobility_station_statuses(
+        &mut self,
+        origin: &Id<Origin>,
+        statuses: &[(Id<SharedMobilityStation>, Option<Status>)],
+    ) -> Result<Vec<Id<SharedMobilityStation>>>;
 }
 
 #[async_trait]
@@ -302,6 +564,301 @@ pub trait CollectorRepo {
     ) -> Result<C::State>
     where
         C: Collector + 'static;
+
+    /// Tries to lease the given collector instance for a run of at most
+    /// `duration`, unless the lease is renewed. Fails, if the instance is leased by
+    /// someone else, asked to sleep until later, or was run less than `spacing`
+    /// ago. Returns the start of the run, which identifies the lease, or why it
+    /// failed.
+    async fn try_lease_collector<C>(
+        &mut self,
+        id: &Id<CollectorInstance<C>>,
+        duration: Duration,
+        spacing: Duration,
+    ) -> Result<CollectorLease>
+    where
+        C: Collector + 'static;
+
+    /// Extends the lease of the run started at `started_at` by `duration`.
+    /// Returns `false`, if the lease expired and was taken by someone else.
+    async fn renew_collector_lease<C>(
+        &mut self,
+        id: &Id<CollectorInstance<C>>,
+        started_at: DateTime<Local>,
+        duration: Duration,
+    ) -> Result<bool>
+    where
+        C: Collector + 'static;
+
+    /// Releases the lease of the run started at `started_at`, if it is still held.
+    async fn release_collector_lease<C>(
+        &mut self,
+        id: &Id<CollectorInstance<C>>,
+        started_at: DateTime<Local>,
+    ) -> Result<()>
+    where
+        C: Collector + 'static;
+
+    /// Stores the outcome of a health check. `error` is `None`, if the collector is
+    /// healthy.
+    async fn set_collector_health<C>(
+        &mut self,
+        id: &Id<CollectorInstance<C>>,
+        error: Option<String>,
+    ) -> Result<()>
+    where
+        C: Collector + 'static;
+
+    /// Stores, when the collector is run next. `None`, if it is run as soon as
+    /// it is started.
+    async fn set_collector_next_run<C>(
+        &mut self,
+        id: &Id<CollectorInstance<C>>,
+        next_run_at: Option<DateTime<Local>>,
+    ) -> Result<()>
+    where
+        C: Collector + 'static;
+
+    /// Stores the outcome of a run. Counts consecutive failures and remembers the
+    /// time of the last successful run, and the delay until the next run it asked
+    /// for. `run_interval` is `None`, if the collector does not run again.
+    async fn record_collector_run<C>(
+        &mut self,
+        id: &Id<CollectorInstance<C>>,
+        succeeded: bool,
+        run_interval: Option<Duration>,
+    ) -> Result<()>
+    where
+        C: Collector + 'static;
+
+    /// Health of all collector instances, regardless of their kind.
+    async fn collector_health(&mut self) -> Result<Vec<CollectorHealth>>;
+}
+
+#[async_trait]
+pub trait AlertRepo {
+    /// Opens an incident of the rule of the alert. Returns `false`, if an incident
+    /// of the rule is open already.
+    async fn open_alert(&mut self, alert: &Alert) -> Result<bool>;
+
+    /// Closes the incident of the rule. Returns the closed incident, `None` if
+    /// there was none, e.g. because another instance closed it first.
+    async fn close_alert(&mut self, rule: &str) -> Result<Option<Alert>>;
+
+    /// All open incidents, oldest first.
+    async fn open_alerts(&mut self) -> Result<Vec<Alert>>;
+}
+
+#[async_trait]
+pub trait MergeLogRepo {
+    async fn log_merge(
+        &mut self,
+        entry: MergeLogEntry,
+    ) -> Result<WithId<MergeLogEntry>>;
+
+    async fn get_merge_log_entry(
+        &mut self,
+        id: &Id<MergeLogEntry>,
+    ) -> Result<MergeLogEntry>;
+
+    /// Returns all entries with the given status, or all entries if no status is
+    /// given. Oldest entries first.
+    async fn get_merge_log(
+        &mut self,
+        status: Option<MergeStatus>,
+    ) -> Result<Vec<WithId<MergeLogEntry>>>;
+
+    /// Sets the status of an entry, which is pending review, and marks it as
+    /// decided. `None`, if the entry is not pending review (anymore).
+    async fn decide_merge(
+        &mut self,
+        id: &Id<MergeLogEntry>,
+        status: MergeStatus,
+    ) -> Result<Option<MergeLogEntry>>;
+
+    /// Whether merging the subject with the given original id of the origin into
+    /// the candidate was rejected. Subjects without original id only match
+    /// rejections of subjects without original id.
+    async fn is_merge_rejected(
+        &mut self,
+        subject: MergeSubject,
+        origin: &Id<Origin>,
+        original_id: Option<&str>,
+        candidate_id: &str,
+    ) -> Result<bool>;
+}
+
+#[async_trait]
+pub trait ChangeLogRepo {
+    /// Changes after the position, oldest first. Changes are only returned once
+    /// they are flushed, see [`ChangeLogRepo::flush_changes`], so that no change
+    /// is inserted before the last returned one later on.
+    async fn changes_after(
+        &mut self,
+        after: Option<ChangePosition>,
+        limit: usize,
+    ) -> Result<Vec<Change>>;
+
+    /// Tries to acquire the exclusive lock for flushing changes without waiting.
+    /// Returns `false` if the lock is held by someone else. The lock is released
+    /// at the end of the current transaction, so this is only meaningful within
+    /// a transaction.
+    async fn try_lock_change_flush(&mut self) -> Result<bool>;
+
+    /// Appends up to `limit` changes of finished transactions to the change log,
+    /// oldest transaction first. Returns the number of flushed changes.
+    async fn flush_changes(&mut self, limit: usize) -> Result<u64>;
+
+    /// Deletes changes made before the given time, which are followed by a newer
+    /// change of the same entity, and deletions, which are not. Returns the number
+    /// of deleted changes.
+    async fn compact_changes(&mut self, before: DateTime<Local>) -> Result<u64>;
+
+    /// Position of the latest compacted deletion. Mirrors, which synced up to an
+    /// earlier position, may have missed deletions. `None`, if no deletion was
+    /// compacted yet.
+    async fn change_log_horizon(&mut self) -> Result<Option<ChangePosition>>;
+}
+
+/// Rows referencing a subject, which does not exist.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Orphans {
+    /// Table containing the orphaned rows.
+    pub table: String,
+    /// Kind of the missing subject.
+    pub missing: String,
+    pub count: i64,
+}
+
+/// The number of invalid coordinates changed since they were counted, so that
+/// clearing them was not confirmed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnconfirmedClear {
+    /// Number of rows confirmed to be cleared.
+    pub expected: u64,
+    /// Number of rows, which would have been cleared.
+    pub found: u64,
+}
+
+impl error::Error for UnconfirmedClear {}
+
+impl fmt::Display for UnconfirmedClear {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Expected {} invalid coordinates, but found {}, nothing was cleared",
+            self.expected, self.found
+        )
+    }
+}
+
+/// The merge was already confirmed or rejected, so that it can not be decided
+/// again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeAlreadyDecided;
+
+impl error::Error for MergeAlreadyDecided {}
+
+impl fmt::Display for MergeAlreadyDecided {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The merge was already decided.")
+    }
+}
+
+/// Nothing is left of a required name after sanitizing it, e.g. of a name
+/// consisting of html tags only.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmptyName;
+
+impl error::Error for EmptyName {}
+
+impl fmt::Display for EmptyName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The name is empty.")
+    }
+}
+
+/// Rows with coordinates out of range, or exactly at (0, 0).
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InvalidCoordinates {
+    /// Table containing the rows.
+    pub table: String,
+    pub count: i64,
+}
+
+#[async_trait]
+pub trait IntegrityRepo {
+    /// Counts orphaned rows, which are not prevented by the database schema.
+    /// Only returns entries with at least one orphaned row.
+    async fn orphans(&mut self) -> Result<Vec<Orphans>>;
+
+    /// Counts rows with invalid coordinates. Rows of `null_island_origins` may be
+    /// located at exactly (0, 0). Only returns entries with at least one row.
+    async fn invalid_coordinates(
+        &mut self,
+        null_island_origins: &[Id<Origin>],
+    ) -> Result<Vec<InvalidCoordinates>>;
+
+    /// Removes invalid coordinates. Stops lose their location, shared mobility
+    /// stations, which can not exist without a location, are deleted.
+    /// Returns the number of affected rows.
+    async fn clear_invalid_coordinates(
+        &mut self,
+        null_island_origins: &[Id<Origin>],
+    ) -> Result<u64>;
+}
+
+#[async_trait]
+pub trait ShapeRepo: SubjectRepo<Shape> {
+    /// creates the id of a new shape without any points.
+    async fn create_shape_id(&mut self) -> Result<Id<Shape>>;
+
+    /// removes all points of a shape.
+    async fn delete_shape_points(&mut self, shape_id: &Id<Shape>) -> Result<()>;
+
+    /// inserts or updates the points of a shape by their sequence number.
+    async fn put_shape_points(
+        &mut self,
+        shape_id: &Id<Shape>,
+        points: Vec<(i32, ShapePoint)>,
+    ) -> Result<()>;
+
+    /// all distinct shapes used by trips of the line along with the direction of
+    /// the trips.
+    async fn get_line_shapes(&mut self, line_id: &Id<Line>)
+        -> Result<Vec<LineShape>>;
+}
+
+#[async_trait]
+pub trait PathwayRepo {
+    /// Inserts or replaces the level of the given origin.
+    async fn put_level(
+        &mut self,
+        origin: &Id<Origin>,
+        level: &WithId<Level>,
+    ) -> Result<()>;
+
+    /// Inserts or replaces the pathway of the given origin.
+    async fn put_pathway(
+        &mut self,
+        origin: &Id<Origin>,
+        pathway: &WithId<Pathway>,
+    ) -> Result<()>;
+
+    /// Pathways of all origins, which belong to the station, or start or end at
+    /// the station or one of its children.
+    async fn get_station_pathways(
+        &mut self,
+        station_id: &Id<Stop>,
+    ) -> Result<Vec<WithOrigin<WithId<Pathway>>>>;
+
+    /// Levels referenced by the pathways of the station.
+    async fn get_station_levels(
+        &mut self,
+        station_id: &Id<Stop>,
+    ) -> Result<Vec<WithOrigin<WithId<Level>>>>;
 }
 
 #[async_trait]
@@ -311,14 +868,35 @@ pub trait DatabaseOperations:
     + StopRepo
     + TripRepo
     + ServiceRepo
+    + ShapeRepo
+    + PathwayRepo
     + RealtimeRepo
     + SharedMobilityStationRepo
     + CollectorRepo
+    + AlertRepo
+    + ChangeLogRepo
+    + MergeLogRepo
+    + IntegrityRepo
 {
     /// Returns all known origins sorted by their priority. Last element has highest priority.
     async fn origins(&mut self) -> Result<Vec<WithId<Origin>>>;
 
     async fn put_origin(&mut self, origin: WithId<Origin>) -> Result<WithId<Origin>>;
+
+    /// Changes the id and name of the origin `id`. All rows of the origin follow
+    /// the new id, and the former id becomes an alias of it. Fails with
+    /// [`DatabaseError::NotFound`], if the origin does not exist.
+    async fn rename_origin(
+        &mut self,
+        id: &Id<Origin>,
+        origin: WithId<Origin>,
+    ) -> Result<WithId<Origin>>;
+
+    /// Id of the origin, which was renamed from `alias`, if any.
+    async fn origin_by_alias(
+        &mut self,
+        alias: &Id<Origin>,
+    ) -> Result<Option<Id<Origin>>>;
 }
 
 #[async_trait]
@@ -341,6 +919,17 @@ pub trait Database: Clone + Send + Sync + Sized {
 
     fn auto(&self) -> Self::Autocommit;
 
+    /// Autocommit connection for read only operations. May be served by a read
+    /// replica and thus lag slightly behind. Defaults to `auto`.
+    fn read(&self) -> Self::Autocommit {
+        self.auto()
+    }
+
+    /// Capabilities of the database, as detected when it was connected.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::all()
+    }
+
     // maybe deprecate
     async fn perform_transaction<T, F, Fut>(&self, action: F) -> Result<T>
     where
@@ -0,0 +1,32 @@
+//! Deadline of the request, on whose behalf database operations are performed,
+//! so that the database can cancel queries, whose result would not be awaited
+//! anymore.
+
+use std::{future::Future, time::Duration};
+
+use tokio::time::Instant;
+
+tokio::task_local! {
+    static DEADLINE: Instant;
+}
+
+/// Runs the future with the deadline. Nested scopes never extend the deadline of
+/// the outer scope.
+pub async fn scope<F: Future>(deadline: Instant, future: F) -> F::Output {
+    let deadline = match current() {
+        Some(outer) => outer.min(deadline),
+        None => deadline,
+    };
+    DEADLINE.scope(deadline, future).await
+}
+
+/// Deadline of the current scope, or `None` outside of any.
+pub fn current() -> Option<Instant> {
+    DEADLINE.try_with(|deadline| *deadline).ok()
+}
+
+/// Time left until the deadline of the current scope, which is zero once it has
+/// passed, or `None` outside of any scope.
+pub fn remaining() -> Option<Duration> {
+    current().map(|deadline| deadline.saturating_duration_since(Instant::now()))
+}
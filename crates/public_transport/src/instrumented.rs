@@ -0,0 +1,831 @@
+use std::{
+    any,
+    collections::HashMap,
+    fmt::{self, Debug},
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local, NaiveDate};
+use model::{
+    agency::Agency,
+    calendar::{CalendarDate, CalendarWindow, Service, ServiceTag},
+    change::{Change, ChangePosition},
+    line::Line,
+    merge::{MergeLogEntry, MergeStatus, MergeSubject},
+    origin::{Origin, OriginalIdMapping},
+    pathway::{Level, Pathway},
+    shape::{LineShape, Shape, ShapePoint},
+    shared_mobility::{SharedMobilityStation, Status},
+    stop::{ServiceSummary, Stop},
+    trip::{CouplingKind, Frequency, StopTime, Trip, TripCoupling},
+    trip_instance::WindowMode,
+    trip_update::{HistoricDelay, TripUpdate, TripUpdateId},
+    DatabaseEntry, DateTimeRange, WithId, WithOrigin,
+};
+use serde::Serialize;
+use utility::id::{HasId, Id};
+
+use crate::{
+    capability::Capabilities,
+    collector::{Collector, CollectorHealth, CollectorInstance, CollectorLease},
+    database::{
+        AgencyRepo, AlertRepo, ChangeLogRepo, CollectorRepo, Database,
+        DatabaseAutocommit, DatabaseError, DatabaseOperations, DatabaseTransaction,
+        IntegrityRepo, InvalidCoordinates, LineRepo, MergableRepo, MergeLogRepo,
+        Orphans, PathwayRepo, RealtimeRepo, Repo, Result, ServiceRepo, ShapeRepo,
+        SharedMobilityStationRepo, StopRepo, SubjectRepo, TripRepo,
+    },
+    notification::Alert,
+};
+
+#[derive(Debug, Default)]
+struct OperationStats {
+    calls: u64,
+    errors: u64,
+    total: Duration,
+    max: Duration,
+}
+
+/// Database operation, e.g. `StopRepo::find_nearby`, or `Repo::get` of stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Operation {
+    name: &'static str,
+    /// Type name of the subject of generic repos.
+    subject: Option<&'static str>,
+}
+
+impl Operation {
+    const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            subject: None,
+        }
+    }
+
+    fn of<T>(name: &'static str) -> Self {
+        Self {
+            name,
+            subject: Some(any::type_name::<T>()),
+        }
+    }
+}
+
+/// E.g. `Repo<Stop>::get` for operations of generic repos.
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Some(subject) = self.subject else {
+            return write!(f, "{}", self.name);
+        };
+        let subject = subject.rsplit("::").next().unwrap_or_default();
+        match self.name.split_once("::") {
+            Some((repo, method)) => write!(f, "{}<{}>::{}", repo, subject, method),
+            None => write!(f, "{}<{}>", self.name, subject),
+        }
+    }
+}
+
+/// Call counts and latencies of database operations, shared by all connections
+/// of an [`InstrumentedDatabase`].
+#[derive(Debug, Default)]
+pub struct DatabaseMetrics {
+    operations: Mutex<HashMap<Operation, OperationStats>>,
+}
+
+/// Metrics of a single database operation since startup.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationMetrics {
+    /// Name of the operation, e.g. `StopRepo::find_nearby` or `Repo<Stop>::get`.
+    pub operation: String,
+    pub calls: u64,
+    /// Failed calls. Lookups of missing entries do not count as failure.
+    pub errors: u64,
+    pub total_secs: f64,
+    pub max_secs: f64,
+}
+
+impl DatabaseMetrics {
+    fn record<T>(&self, operation: Operation, elapsed: Duration, result: &Result<T>) {
+        let failed =
+            matches!(result, Err(why) if !matches!(why, DatabaseError::NotFound));
+        tracing::debug!(
+            operation = %operation,
+            elapsed_ms = elapsed.as_secs_f64() * 1000.0,
+            failed,
+            "database operation"
+        );
+        let mut operations = self.operations.lock().unwrap();
+        let stats = operations.entry(operation).or_default();
+        stats.calls += 1;
+        stats.errors += failed as u64;
+        stats.total += elapsed;
+        stats.max = stats.max.max(elapsed);
+    }
+
+    /// Metrics of all operations called so far, sorted by operation.
+    pub fn snapshot(&self) -> Vec<OperationMetrics> {
+        let operations = self.operations.lock().unwrap();
+        let mut snapshot = operations
+            .iter()
+            .map(|(operation, stats)| OperationMetrics {
+                operation: operation.to_string(),
+                calls: stats.calls,
+                errors: stats.errors,
+                total_secs: stats.total.as_secs_f64(),
+                max_secs: stats.max.as_secs_f64(),
+            })
+            .collect::<Vec<_>>();
+        snapshot.sort_by(|a, b| a.operation.cmp(&b.operation));
+        snapshot
+    }
+
+    async fn measure<T, Fut>(&self, operation: Operation, future: Fut) -> Result<T>
+    where
+        Fut: Future<Output = Result<T>>,
+    {
+        let start = Instant::now();
+        let result = future.await;
+        self.record(operation, start.elapsed(), &result);
+        result
+    }
+}
+
+/// Database, which records the call count and latency of every operation of its
+/// connections in [`DatabaseMetrics`] and emits a `tracing` event per call.
+/// Otherwise behaves exactly like the wrapped database.
+#[derive(Clone)]
+pub struct InstrumentedDatabase<D> {
+    inner: D,
+    metrics: Arc<DatabaseMetrics>,
+}
+
+impl<D> InstrumentedDatabase<D>
+where
+    D: Database,
+{
+    pub fn new(inner: D) -> Self {
+        Self {
+            inner,
+            metrics: Arc::new(DatabaseMetrics::default()),
+        }
+    }
+
+    /// The wrapped database, e.g. for health checks specific to it.
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    pub fn metrics(&self) -> &DatabaseMetrics {
+        &self.metrics
+    }
+
+    fn wrap<T>(&self, inner: T) -> Instrumented<T> {
+        Instrumented {
+            inner,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl<D> Database for InstrumentedDatabase<D>
+where
+    D: Database,
+{
+    type Transaction = Instrumented<D::Transaction>;
+    type Autocommit = Instrumented<D::Autocommit>;
+
+    const BULK_INSERT_MAX: usize = D::BULK_INSERT_MAX;
+
+    async fn transaction(&self) -> Result<Self::Transaction> {
+        self.metrics
+            .measure(
+                Operation::new("Database::transaction"),
+                self.inner.transaction(),
+            )
+            .await
+            .map(|tx| self.wrap(tx))
+    }
+
+    fn auto(&self) -> Self::Autocommit {
+        self.wrap(self.inner.auto())
+    }
+
+    fn read(&self) -> Self::Autocommit {
+        self.wrap(self.inner.read())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    async fn perform_transaction<T, F, Fut>(&self, action: F) -> Result<T>
+    where
+        T: Send,
+        F: Send + FnOnce(&mut Self::Transaction) -> Fut + Send,
+        Fut: Future<Output = Result<T>> + Send,
+    {
+        let mut tx = self.transaction().await?;
+        let result = action(&mut tx).await;
+        tx.commit().await?;
+        result
+    }
+}
+
+/// Connection of an [`InstrumentedDatabase`], which forwards all operations to
+/// the wrapped connection.
+pub struct Instrumented<T> {
+    inner: T,
+    metrics: Arc<DatabaseMetrics>,
+}
+
+/// Implements a repo for [`Instrumented`], measuring each method of the wrapped
+/// connection under the name `Repo::method`. Repos generic over the subject, e.g.
+/// `Repo<T>`, are measured per subject. Generic parameters of methods are given in
+/// brackets, e.g. `async fn search[S: Into<String> + Send](pattern: S) -> ...;`.
+macro_rules! instrument {
+    (
+        $repo:ident<$subject:ident> {
+            $(
+                async fn $method:ident $([$($generics:tt)*])? (
+                    $($arg:ident: $type:ty),* $(,)?
+                ) -> $output:ty;
+            )*
+        }
+    ) => {
+        #[async_trait]
+        impl<$subject, R> $repo<$subject> for Instrumented<R>
+        where
+            $subject: Serialize + HasId + Send + Sync + 'static,
+            <$subject as HasId>::IdType: Debug + Clone + Serialize + Send + Sync,
+            R: $repo<$subject> + Send,
+        {
+            $(
+                async fn $method $(<$($generics)*>)? (
+                    &mut self,
+                    $($arg: $type),*
+                ) -> $output {
+                    let operation = Operation::of::<$subject>(
+                        concat!(stringify!($repo), "::", stringify!($method)),
+                    );
+                    self.metrics
+                        .measure(operation, self.inner.$method($($arg),*))
+                        .await
+                }
+            )*
+        }
+    };
+    (
+        $repo:ident {
+            $(
+                async fn $method:ident $([$($generics:tt)*])? (
+                    $($arg:ident: $type:ty),* $(,)?
+                ) -> $output:ty;
+            )*
+        }
+    ) => {
+        #[async_trait]
+        impl<R> $repo for Instrumented<R>
+        where
+            R: $repo + Send,
+        {
+            $(
+                async fn $method $(<$($generics)*>)? (
+                    &mut self,
+                    $($arg: $type),*
+                ) -> $output {
+                    let operation = Operation::new(
+                        concat!(stringify!($repo), "::", stringify!($method)),
+                    );
+                    self.metrics
+                        .measure(operation, self.inner.$method($($arg),*))
+                        .await
+                }
+            )*
+        }
+    };
+}
+
+instrument! {
+    Repo<T> {
+        async fn get(id: Id<T>) -> Result<DatabaseEntry<T>>;
+
+        async fn get_all() -> Result<Vec<DatabaseEntry<T>>>;
+
+        async fn get_many(ids: &[Id<T>]) -> Result<Vec<DatabaseEntry<T>>>;
+
+        async fn insert(element: WithOrigin<T>) -> Result<WithOrigin<WithId<T>>>;
+
+        async fn put(element: WithOrigin<WithId<T>>) -> Result<WithOrigin<WithId<T>>>;
+
+        async fn update(
+            element: WithOrigin<WithId<T>>,
+        ) -> Result<WithOrigin<WithId<T>>>;
+
+        async fn exists(id: Id<T>) -> Result<bool>;
+
+        async fn exists_with_origin(id: Id<T>, origin: Id<Origin>) -> Result<bool>;
+    }
+}
+
+instrument! {
+    SubjectRepo<S> {
+        async fn id_by_original_id(
+            origin: Id<Origin>,
+            original_id: String,
+        ) -> Result<Option<Id<S>>>;
+
+        async fn put_original_id(
+            origin: Id<Origin>,
+            original_id: String,
+            id: Id<S>,
+        ) -> Result<OriginalIdMapping<S>>;
+    }
+}
+
+instrument! {
+    MergableRepo<S> {
+        async fn merge_candidates(
+            element: &S,
+            excluded_origin: &Id<Origin>,
+        ) -> Result<Vec<WithOrigin<WithId<S>>>>;
+    }
+}
+
+instrument! {
+    AgencyRepo {
+        async fn agency_by_name[S: Into<String> + Send](
+            name: S,
+        ) -> Result<Vec<DatabaseEntry<Agency>>>;
+    }
+}
+
+instrument! {
+    LineRepo {
+        async fn line_by_name_and_agency[S: Into<String> + Send](
+            name: S,
+            agency: &Id<Agency>,
+        ) -> Result<Vec<DatabaseEntry<Line>>>;
+
+        async fn get_by_stop_id(
+            stop_id: &Id<Stop>,
+        ) -> Result<Vec<DatabaseEntry<Line>>>;
+
+        async fn get_by_stop_ids(
+            stop_ids: &[&Id<Stop>],
+        ) -> Result<HashMap<Id<Stop>, Vec<DatabaseEntry<Line>>>>;
+
+        async fn search_by_name[S: Into<String> + Send](
+            pattern: S,
+            limit: usize,
+        ) -> Result<Vec<DatabaseEntry<Line>>>;
+
+        async fn get_departure_span(
+            id: &Id<Line>,
+            service_day: NaiveDate,
+        ) -> Result<Option<(chrono::Duration, chrono::Duration)>>;
+    }
+}
+
+instrument! {
+    StopRepo {
+        async fn find_nearby(
+            latitude: f64,
+            longitude: f64,
+            radius: f64,
+        ) -> Result<Vec<DatabaseEntry<Stop>>>;
+
+        async fn stop_by_name[S: Into<String> + Send](
+            name: S,
+        ) -> Result<Vec<DatabaseEntry<Stop>>>;
+
+        async fn search[S: Into<String> + Send](
+            pattern: S,
+        ) -> Result<Vec<DatabaseEntry<Stop>>>;
+
+        async fn autocomplete[S: Into<String> + Send](
+            pattern: S,
+            limit: usize,
+            origins: &[Id<Origin>],
+        ) -> Result<Vec<DatabaseEntry<Stop>>>;
+
+        async fn get_page_after(
+            after: Option<Id<Stop>>,
+            limit: usize,
+            origins: &[Id<Origin>],
+        ) -> Result<Vec<DatabaseEntry<Stop>>>;
+
+        async fn get_page_by_agency_after(
+            agency_id: &Id<Agency>,
+            include_secondary: bool,
+            after: Option<Id<Stop>>,
+            limit: Option<usize>,
+            origins: &[Id<Origin>],
+        ) -> Result<Vec<DatabaseEntry<Stop>>>;
+
+        async fn get_platforms(
+            parent_id: &Id<Stop>,
+        ) -> Result<Vec<DatabaseEntry<Stop>>>;
+
+        async fn redirect_stop(
+            origin: &Id<Origin>,
+            from: &Id<Stop>,
+            to: &Id<Stop>,
+        ) -> Result<()>;
+
+        async fn backfill_centroids(origin: &Id<Origin>) -> Result<u64>;
+
+        async fn refresh_service_summary(ids: Option<&[&Id<Stop>]>) -> Result<u64>;
+
+        async fn get_service_summaries(
+            ids: &[&Id<Stop>],
+        ) -> Result<HashMap<Id<Stop>, ServiceSummary>>;
+    }
+}
+
+instrument! {
+    TripRepo {
+        async fn put_stop_time(
+            trip_id: Id<Trip>,
+            stop_time: WithOrigin<StopTime>,
+        ) -> Result<WithOrigin<StopTime>>;
+
+        async fn put_stop_times(
+            trip_id: &Id<Trip>,
+            origin: &Id<Origin>,
+            stop_times: &[StopTime],
+            replace: bool,
+        ) -> Result<u64>;
+
+        async fn get_stop_times(
+            trip_id: Id<Trip>,
+            origin: Id<Origin>,
+        ) -> Result<Vec<StopTime>>;
+
+        async fn delete_stop_times(
+            trip_id: Id<Trip>,
+            origin: Id<Origin>,
+        ) -> Result<Vec<StopTime>>;
+
+        async fn sync_stop_names() -> Result<u64>;
+
+        async fn delete_trip(id: &Id<Trip>, origin: &Id<Origin>) -> Result<()>;
+
+        async fn get_all_via_stop(
+            stops: &[&Id<Stop>],
+            start: DateTime<Local>,
+            end: DateTime<Local>,
+            mode: WindowMode,
+        ) -> Result<Vec<DatabaseEntry<Trip>>>;
+
+        async fn get_page_after(
+            after: Option<Id<Trip>>,
+            limit: usize,
+            origins: &[Id<Origin>],
+        ) -> Result<Vec<DatabaseEntry<Trip>>>;
+
+        async fn search_text[S: Into<String> + Send](
+            pattern: S,
+            service_day: NaiveDate,
+            limit: usize,
+        ) -> Result<Vec<DatabaseEntry<Trip>>>;
+
+        async fn put_trip_couplings(
+            trip_id: &Id<Trip>,
+            origin: &Id<Origin>,
+            stop_sequence: i32,
+            stop_id: Option<&Id<Stop>>,
+            couplings: &[(String, CouplingKind)],
+        ) -> Result<()>;
+
+        async fn get_trip_couplings(
+            trip_ids: &[Id<Trip>],
+        ) -> Result<Vec<WithOrigin<TripCoupling>>>;
+
+        async fn put_frequency(
+            trip_id: &Id<Trip>,
+            origin: &Id<Origin>,
+            frequency: &Frequency,
+        ) -> Result<()>;
+
+        async fn get_frequencies(
+            trip_ids: &[Id<Trip>],
+        ) -> Result<Vec<(Id<Trip>, WithOrigin<Frequency>)>>;
+    }
+}
+
+instrument! {
+    ServiceRepo {
+        async fn put_calendar_window(
+            service_id: Option<&Id<Service>>,
+            window: CalendarWindow,
+        ) -> Result<(Id<Service>, CalendarWindow)>;
+
+        async fn put_calendar_date(
+            service_id: Option<&Id<Service>>,
+            date: CalendarDate,
+        ) -> Result<(Id<Service>, CalendarDate)>;
+
+        async fn get_calendar_windows(
+            service_id: &Id<Service>,
+        ) -> Result<Vec<CalendarWindow>>;
+
+        async fn get_calendar_dates(
+            service_id: &Id<Service>,
+        ) -> Result<Vec<CalendarDate>>;
+
+        async fn get_service_ids(origin: &Id<Origin>) -> Result<Vec<Id<Service>>>;
+
+        async fn set_service_tags(
+            service_ids: &[Id<Service>],
+            tags: &[(Id<Service>, ServiceTag)],
+        ) -> Result<()>;
+
+        async fn get_service_tags(
+            service_ids: &[Id<Service>],
+        ) -> Result<HashMap<Id<Service>, Vec<ServiceTag>>>;
+
+        async fn claim_service_signature(
+            origin: &Id<Origin>,
+            signature: &str,
+        ) -> Result<(Id<Service>, bool)>;
+    }
+}
+
+instrument! {
+    RealtimeRepo {
+        async fn put_trip_updates(
+            origin: &Id<Origin>,
+            updates: &[WithId<TripUpdate>],
+        ) -> Result<WithOrigin<Vec<WithId<TripUpdate>>>>;
+
+        async fn get_realtime_for_trip(
+            trip_id: &Id<Trip>,
+            trip_start_date: NaiveDate,
+            instance: u32,
+        ) -> Result<DatabaseEntry<TripUpdate>>;
+
+        async fn get_timestamp(
+            origin: &Id<Origin>,
+            id: &TripUpdateId,
+        ) -> Result<Option<DateTime<Local>>>;
+
+        async fn get_realtime_for_trip_instances(
+            ids: &[Id<TripUpdate>],
+        ) -> Result<Vec<DatabaseEntry<TripUpdate>>>;
+
+        async fn get_realtime_updates_for_stop(
+            stop_id: &Id<Stop>,
+            range: DateTimeRange<Local>,
+        ) -> Result<Vec<DatabaseEntry<TripUpdate>>>;
+
+        async fn put_historic_delays(
+            origin: &Id<Origin>,
+            trip_id: &Id<Trip>,
+            trip_start_date: NaiveDate,
+            delays: &[HistoricDelay],
+        ) -> Result<()>;
+
+        async fn get_historic_delays(
+            trip_id: &Id<Trip>,
+            trip_start_date: NaiveDate,
+        ) -> Result<Vec<WithOrigin<HistoricDelay>>>;
+
+        async fn delete_historic_delays_before(date: NaiveDate) -> Result<u64>;
+
+        async fn latest_update_timestamps()
+            -> Result<Vec<(Id<Origin>, DateTime<Local>)>>;
+    }
+}
+
+instrument! {
+    SharedMobilityStationRepo {
+        async fn find_nearby_shared_mobility_stations(
+            latitude: f64,
+            longitude: f64,
+            radius: f64,
+        ) -> Result<Vec<DatabaseEntry<SharedMobilityStation>>>;
+
+        async fn put_shared_mobility_stations(
+            origin: &Id<Origin>,
+            stations: &[WithId<SharedMobilityStation>],
+        ) -> Result<WithOrigin<Vec<WithId<SharedMobilityStation>>>>;
+
+        async fn update_shared_mobility_station_status(
+            origin: &Id<Origin>,
+            id: &Id<SharedMobilityStation>,
+            status: Option<Status>,
+        ) -> Result<()>;
+
+        async fn update_shared_mobility_station_statuses(
+            origin: &Id<Origin>,
+            statuses: &[(Id<SharedMobilityStation>, Option<Status>)],
+        ) -> Result<Vec<Id<SharedMobilityStation>>>;
+    }
+}
+
+instrument! {
+    CollectorRepo {
+        async fn collectors[C: Collector + 'static]()
+            -> Result<Vec<WithId<CollectorInstance<C>>>>;
+
+        async fn get_collector[C: Collector + 'static](
+            id: &Id<CollectorInstance<C>>,
+        ) -> Result<CollectorInstance<C>>;
+
+        async fn set_collector_state[C: Collector + 'static](
+            id: &Id<CollectorInstance<C>>,
+            state: C::State,
+        ) -> Result<C::State>;
+
+        async fn try_lease_collector[C: Collector + 'static](
+            id: &Id<CollectorInstance<C>>,
+            duration: chrono::Duration,
+            spacing: chrono::Duration,
+        ) -> Result<CollectorLease>;
+
+        async fn renew_collector_lease[C: Collector + 'static](
+            id: &Id<CollectorInstance<C>>,
+            started_at: DateTime<Local>,
+            duration: chrono::Duration,
+        ) -> Result<bool>;
+
+        async fn release_collector_lease[C: Collector + 'static](
+            id: &Id<CollectorInstance<C>>,
+            started_at: DateTime<Local>,
+        ) -> Result<()>;
+
+        async fn set_collector_health[C: Collector + 'static](
+            id: &Id<CollectorInstance<C>>,
+            error: Option<String>,
+        ) -> Result<()>;
+
+        async fn set_collector_next_run[C: Collector + 'static](
+            id: &Id<CollectorInstance<C>>,
+            next_run_at: Option<DateTime<Local>>,
+        ) -> Result<()>;
+
+        async fn record_collector_run[C: Collector + 'static](
+            id: &Id<CollectorInstance<C>>,
+            succeeded: bool,
+            run_interval: Option<chrono::Duration>,
+        ) -> Result<()>;
+
+        async fn collector_health() -> Result<Vec<CollectorHealth>>;
+    }
+}
+
+instrument! {
+    AlertRepo {
+        async fn open_alert(alert: &Alert) -> Result<bool>;
+
+        async fn close_alert(rule: &str) -> Result<Option<Alert>>;
+
+        async fn open_alerts() -> Result<Vec<Alert>>;
+    }
+}
+
+instrument! {
+    ChangeLogRepo {
+        async fn changes_after(
+            after: Option<ChangePosition>,
+            limit: usize,
+        ) -> Result<Vec<Change>>;
+
+        async fn try_lock_change_flush() -> Result<bool>;
+
+        async fn flush_changes(limit: usize) -> Result<u64>;
+
+        async fn compact_changes(before: DateTime<Local>) -> Result<u64>;
+
+        async fn change_log_horizon() -> Result<Option<ChangePosition>>;
+    }
+}
+
+instrument! {
+    MergeLogRepo {
+        async fn log_merge(entry: MergeLogEntry) -> Result<WithId<MergeLogEntry>>;
+
+        async fn get_merge_log_entry(id: &Id<MergeLogEntry>) -> Result<MergeLogEntry>;
+
+        async fn get_merge_log(
+            status: Option<MergeStatus>,
+        ) -> Result<Vec<WithId<MergeLogEntry>>>;
+
+        async fn decide_merge(
+            id: &Id<MergeLogEntry>,
+            status: MergeStatus,
+        ) -> Result<Option<MergeLogEntry>>;
+
+        async fn is_merge_rejected(
+            subject: MergeSubject,
+            origin: &Id<Origin>,
+            original_id: Option<&str>,
+            candidate_id: &str,
+        ) -> Result<bool>;
+    }
+}
+
+instrument! {
+    IntegrityRepo {
+        async fn orphans() -> Result<Vec<Orphans>>;
+
+        async fn invalid_coordinates(
+            null_island_origins: &[Id<Origin>],
+        ) -> Result<Vec<InvalidCoordinates>>;
+
+        async fn clear_invalid_coordinates(
+            null_island_origins: &[Id<Origin>],
+        ) -> Result<u64>;
+    }
+}
+
+instrument! {
+    ShapeRepo {
+        async fn create_shape_id() -> Result<Id<Shape>>;
+
+        async fn delete_shape_points(shape_id: &Id<Shape>) -> Result<()>;
+
+        async fn put_shape_points(
+            shape_id: &Id<Shape>,
+            points: Vec<(i32, ShapePoint)>,
+        ) -> Result<()>;
+
+        async fn get_line_shapes(line_id: &Id<Line>) -> Result<Vec<LineShape>>;
+    }
+}
+
+instrument! {
+    PathwayRepo {
+        async fn put_level(origin: &Id<Origin>, level: &WithId<Level>) -> Result<()>;
+
+        async fn put_pathway(
+            origin: &Id<Origin>,
+            pathway: &WithId<Pathway>,
+        ) -> Result<()>;
+
+        async fn get_station_pathways(
+            station_id: &Id<Stop>,
+        ) -> Result<Vec<WithOrigin<WithId<Pathway>>>>;
+
+        async fn get_station_levels(
+            station_id: &Id<Stop>,
+        ) -> Result<Vec<WithOrigin<WithId<Level>>>>;
+    }
+}
+
+instrument! {
+    DatabaseOperations {
+        async fn origins() -> Result<Vec<WithId<Origin>>>;
+
+        async fn put_origin(origin: WithId<Origin>) -> Result<WithId<Origin>>;
+
+        async fn rename_origin(
+            id: &Id<Origin>,
+            origin: WithId<Origin>,
+        ) -> Result<WithId<Origin>>;
+
+        async fn origin_by_alias(alias: &Id<Origin>) -> Result<Option<Id<Origin>>>;
+    }
+}
+
+#[async_trait]
+impl<R> DatabaseTransaction for Instrumented<R>
+where
+    R: DatabaseTransaction + Send,
+{
+    async fn commit(self) -> Result<()> {
+        self.metrics
+            .measure(
+                Operation::new("DatabaseTransaction::commit"),
+                self.inner.commit(),
+            )
+            .await
+    }
+}
+
+impl<R> DatabaseAutocommit for Instrumented<R> where R: DatabaseAutocommit + Send {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_operations_of_generic_repos_by_subject() {
+        assert_eq!(
+            Operation::new("StopRepo::find_nearby").to_string(),
+            "StopRepo::find_nearby"
+        );
+        assert_eq!(
+            Operation::of::<Stop>("Repo::get").to_string(),
+            "Repo<Stop>::get"
+        );
+        assert_ne!(
+            Operation::of::<Stop>("Repo::get"),
+            Operation::of::<Trip>("Repo::get")
+        );
+    }
+}
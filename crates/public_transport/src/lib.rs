@@ -3,15 +3,22 @@ use std::error::Error;
 use model::{agency::Agency, WithId, WithOrigin};
 use tokio::sync::{mpsc, oneshot};
 
+pub mod capability;
 pub mod client;
 pub mod collector;
 pub mod database;
+pub mod deadline;
+pub mod instrumented;
+pub mod notification;
 pub mod server;
+pub mod write_queue;
 
 #[derive(Debug)]
 pub enum RequestError {
     NotFound,
     IdMissing,
+    /// The deadline of the request passed.
+    Timeout,
     SendError(mpsc::error::SendError<Request>),
     ResponseError(oneshot::error::RecvError),
     Other(Box<dyn Error + Send>),
@@ -34,6 +41,7 @@ impl From<database::DatabaseError> for RequestError {
         match value {
             database::DatabaseError::NotFound => Self::NotFound,
             database::DatabaseError::IdMissing => Self::IdMissing,
+            database::DatabaseError::Timeout => Self::Timeout,
             database::DatabaseError::Other(why) => Self::Other(why),
         }
     }
@@ -0,0 +1,450 @@
+//! Alerts on collectors failing repeatedly and realtime data going stale, which
+//! are posted as JSON to webhooks, e.g. of a chat. Each incident is announced
+//! once when it opens and once when it recovers, even across restarts and
+//! multiple server instances sharing the database.
+
+use std::{collections::HashSet, env, time::Duration};
+
+use chrono::{DateTime, Local};
+use model::origin::Origin;
+use serde::Serialize;
+use tokio::time;
+use utility::id::Id;
+
+use crate::{
+    collector::CollectorHealth,
+    database::{AlertRepo, CollectorRepo, Database, RealtimeRepo},
+};
+
+/// How often the rules are evaluated.
+const EVALUATION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long a webhook may take to accept a notification.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+const DEFAULT_MAX_CONSECUTIVE_FAILURES: i32 = 5;
+const DEFAULT_MAX_COLLECTOR_SILENCE_MINUTES: i64 = 60;
+const DEFAULT_MAX_REALTIME_AGE_MINUTES: i64 = 10;
+
+/// Webhooks and thresholds of the alert rules.
+#[derive(Debug, Clone)]
+pub struct AlertConfig {
+    /// Urls, every notification is posted to.
+    pub webhooks: Vec<String>,
+    /// Consecutive failed runs of a collector, after which it is alerted.
+    pub max_consecutive_failures: i32,
+    /// Time an active collector may be overdue, after which it is alerted. It is
+    /// due at its last successful run plus the delay until the next run, which
+    /// it asked for, e.g. a day for daily schedule imports.
+    pub max_collector_silence: chrono::Duration,
+    /// Age of the latest trip update of an origin, after which it is alerted.
+    pub max_realtime_age: chrono::Duration,
+}
+
+impl AlertConfig {
+    /// Reads the comma separated `ALERT_WEBHOOK_URLS`, and the thresholds
+    /// `ALERT_MAX_CONSECUTIVE_FAILURES`, `ALERT_MAX_COLLECTOR_SILENCE_MINUTES` and
+    /// `ALERT_MAX_REALTIME_AGE_MINUTES`. `None`, if there are no webhooks. Fails
+    /// with the name of the variable, whose value is invalid.
+    pub fn from_env() -> Result<Option<Self>, &'static str> {
+        let var = |name| env::var(name).ok().filter(|value| !value.is_empty());
+        let webhooks = var("ALERT_WEBHOOK_URLS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        if webhooks.is_empty() {
+            return Ok(None);
+        }
+        let max_consecutive_failures = match var("ALERT_MAX_CONSECUTIVE_FAILURES") {
+            Some(value) => value
+                .parse()
+                .map_err(|_| "ALERT_MAX_CONSECUTIVE_FAILURES")?,
+            None => DEFAULT_MAX_CONSECUTIVE_FAILURES,
+        };
+        let minutes = |name, default| match var(name) {
+            Some(value) => value
+                .parse()
+                .map(chrono::Duration::minutes)
+                .map_err(|_| name),
+            None => Ok(chrono::Duration::minutes(default)),
+        };
+        Ok(Some(Self {
+            webhooks,
+            max_consecutive_failures,
+            max_collector_silence: minutes(
+                "ALERT_MAX_COLLECTOR_SILENCE_MINUTES",
+                DEFAULT_MAX_COLLECTOR_SILENCE_MINUTES,
+            )?,
+            max_realtime_age: minutes(
+                "ALERT_MAX_REALTIME_AGE_MINUTES",
+                DEFAULT_MAX_REALTIME_AGE_MINUTES,
+            )?,
+        }))
+    }
+}
+
+/// An open incident of an alert rule.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Alert {
+    /// Identifies the rule and its subject, e.g. `collector-failures:3`.
+    pub rule: String,
+    pub message: String,
+    pub opened_at: DateTime<Local>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NotificationKind {
+    Alert,
+    Recovery,
+    Test,
+}
+
+/// Body posted to the webhooks. `text` is shown by chats like Slack, which only
+/// look at this field.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    pub kind: NotificationKind,
+    pub rule: String,
+    pub message: String,
+    pub opened_at: DateTime<Local>,
+    /// When the incident recovered.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub closed_at: Option<DateTime<Local>>,
+    pub text: String,
+}
+
+impl Notification {
+    fn new(
+        kind: NotificationKind,
+        alert: Alert,
+        closed_at: Option<DateTime<Local>>,
+    ) -> Self {
+        let text = match kind {
+            NotificationKind::Alert => format!(":rotating_light: {}", alert.message),
+            NotificationKind::Recovery => format!(
+                ":white_check_mark: recovered after {} min: {}",
+                closed_at
+                    .map(|closed_at| (closed_at - alert.opened_at).num_minutes())
+                    .unwrap_or_default(),
+                alert.message
+            ),
+            NotificationKind::Test => format!(":bell: {}", alert.message),
+        };
+        Self {
+            kind,
+            rule: alert.rule,
+            message: alert.message,
+            opened_at: alert.opened_at,
+            closed_at,
+            text,
+        }
+    }
+}
+
+/// Posts notifications to the configured webhooks.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    config: AlertConfig,
+    http: reqwest::Client,
+}
+
+impl Notifier {
+    pub fn new(config: AlertConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    pub fn config(&self) -> &AlertConfig {
+        &self.config
+    }
+
+    /// Posts the notification to all webhooks. Fails with the errors of the
+    /// webhooks, which did not accept it.
+    pub async fn send(&self, notification: &Notification) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        for url in &self.config.webhooks {
+            let result = self
+                .http
+                .post(url)
+                .timeout(WEBHOOK_TIMEOUT)
+                .json(notification)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            if let Err(why) = result {
+                errors.push(why.to_string());
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Posts a test notification, e.g. to check the configuration of a webhook.
+    pub async fn send_test(&self) -> Result<(), Vec<String>> {
+        let alert = Alert {
+            rule: "test".to_owned(),
+            message: "test alert, please ignore.".to_owned(),
+            opened_at: Local::now(),
+        };
+        self.send(&Notification::new(NotificationKind::Test, alert, None))
+            .await
+    }
+
+    async fn send_logged(&self, notification: Notification) {
+        if let Err(errors) = self.send(&notification).await {
+            eprintln!(
+                "could not send notification of alert '{}': {}",
+                notification.rule,
+                errors.join(", ")
+            );
+        }
+    }
+}
+
+/// Rules, which are violated at `now`, as incidents opened at `now`. Realtime
+/// alerts stay open while their origin has no recent updates at all.
+fn violations(
+    config: &AlertConfig,
+    collectors: &[CollectorHealth],
+    realtime: &[(Id<Origin>, DateTime<Local>)],
+    open: &[Alert],
+    now: DateTime<Local>,
+) -> Vec<Alert> {
+    let alert = |rule: String, message: String| Alert {
+        rule,
+        message,
+        opened_at: now,
+    };
+    let mut violations = vec![];
+    for collector in collectors.iter().filter(|collector| collector.is_active) {
+        if collector.consecutive_failures >= config.max_consecutive_failures {
+            violations.push(alert(
+                format!("collector-failures:{}", collector.id),
+                format!(
+                    "collector '{}' ({}) of origin {} failed {} consecutive runs.",
+                    collector.kind,
+                    collector.id,
+                    collector.origin,
+                    collector.consecutive_failures
+                ),
+            ));
+        }
+        let is_overdue = collector
+            .due_at
+            .is_some_and(|due_at| now - due_at > config.max_collector_silence);
+        if let (true, Some(last_success_at)) = (is_overdue, collector.last_success_at)
+        {
+            violations.push(alert(
+                format!("collector-silent:{}", collector.id),
+                format!(
+                    "collector '{}' ({}) of origin {} did not run successfully since {}.",
+                    collector.kind,
+                    collector.id,
+                    collector.origin,
+                    last_success_at.format("%Y-%m-%d %H:%M")
+                ),
+            ));
+        }
+    }
+    let mut origins = HashSet::new();
+    for (origin, latest) in realtime {
+        origins.insert(format!("realtime-stale:{}", origin));
+        if now - *latest > config.max_realtime_age {
+            violations.push(alert(
+                format!("realtime-stale:{}", origin),
+                format!(
+                    "no realtime data of origin {} since {}.",
+                    origin,
+                    latest.format("%Y-%m-%d %H:%M")
+                ),
+            ));
+        }
+    }
+    violations.extend(
+        open.iter()
+            .filter(|alert| alert.rule.starts_with("realtime-stale:"))
+            .filter(|alert| !origins.contains(&alert.rule))
+            .cloned(),
+    );
+    violations
+}
+
+/// Evaluates the rules once. Opens incidents of violated rules and closes the
+/// others, sending a notification for every incident opened or closed by this
+/// call.
+pub async fn evaluate<D: Database>(
+    database: &D,
+    notifier: &Notifier,
+) -> crate::database::Result<()> {
+    let mut auto = database.auto();
+    let collectors = auto.collector_health().await?;
+    let realtime = auto.latest_update_timestamps().await?;
+    let open = auto.open_alerts().await?;
+    let now = Local::now();
+    let violations =
+        violations(notifier.config(), &collectors, &realtime, &open, now);
+
+    let violated = violations
+        .iter()
+        .map(|alert| alert.rule.clone())
+        .collect::<HashSet<_>>();
+    for alert in violations {
+        if auto.open_alert(&alert).await? {
+            notifier
+                .send_logged(Notification::new(NotificationKind::Alert, alert, None))
+                .await;
+        }
+    }
+    for alert in open.iter().filter(|alert| !violated.contains(&alert.rule)) {
+        if let Some(closed) = auto.close_alert(&alert.rule).await? {
+            notifier
+                .send_logged(Notification::new(
+                    NotificationKind::Recovery,
+                    closed,
+                    Some(now),
+                ))
+                .await;
+        }
+    }
+    Ok(())
+}
+
+/// Evaluates the rules periodically, until the server stops.
+pub async fn watch<D: Database + 'static>(database: D, notifier: Notifier) {
+    let mut interval = time::interval(EVALUATION_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(why) = evaluate(&database, &notifier).await {
+            eprintln!("could not evaluate alert rules: {:?}", why);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn config() -> AlertConfig {
+        AlertConfig {
+            webhooks: vec![],
+            max_consecutive_failures: 5,
+            max_collector_silence: chrono::Duration::minutes(60),
+            max_realtime_age: chrono::Duration::minutes(10),
+        }
+    }
+
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 3, 8, 12, 0, 0).unwrap()
+    }
+
+    /// A healthy collector, which last succeeded at `last_success_at` and asked to
+    /// run again after `interval`.
+    fn collector(
+        last_success_at: DateTime<Local>,
+        interval: chrono::Duration,
+    ) -> CollectorHealth {
+        CollectorHealth {
+            id: 1,
+            origin: Id::new("origin".to_owned()),
+            kind: "collector".to_owned(),
+            is_active: true,
+            healthy: Some(true),
+            checked_at: None,
+            error: None,
+            consecutive_failures: 0,
+            last_success_at: Some(last_success_at),
+            next_run_at: None,
+            due_at: Some(last_success_at + interval),
+        }
+    }
+
+    fn rules(alerts: Vec<Alert>) -> Vec<String> {
+        alerts.into_iter().map(|alert| alert.rule).collect()
+    }
+
+    #[test]
+    fn collectors_are_silent_once_overdue() {
+        let hours = chrono::Duration::hours;
+        let minutes = chrono::Duration::minutes;
+        let cases = [
+            // runs every 10 seconds.
+            (minutes(59), chrono::Duration::seconds(10), false),
+            (minutes(61), chrono::Duration::seconds(10), true),
+            // imports daily, and is running or about to run.
+            (hours(24), hours(24), false),
+            (hours(24) + minutes(59), hours(24), false),
+            (hours(25) + minutes(1), hours(24), true),
+        ];
+        for (since_success, interval, expected) in cases {
+            let collectors = [collector(now() - since_success, interval)];
+            let silent = rules(violations(&config(), &collectors, &[], &[], now()))
+                .contains(&"collector-silent:1".to_owned());
+            assert_eq!(
+                silent, expected,
+                "silence of a collector with interval {} after {}",
+                interval, since_success
+            );
+        }
+    }
+
+    #[test]
+    fn collectors_without_successful_or_recorded_runs_are_not_silent() {
+        let mut never_succeeded = collector(now(), chrono::Duration::zero());
+        never_succeeded.last_success_at = None;
+        never_succeeded.due_at = None;
+        let mut inactive =
+            collector(now() - chrono::Duration::days(2), chrono::Duration::zero());
+        inactive.is_active = false;
+        inactive.consecutive_failures = 10;
+
+        let violations =
+            violations(&config(), &[never_succeeded, inactive], &[], &[], now());
+
+        assert!(violations.is_empty(), "{:?}", violations);
+    }
+
+    #[test]
+    fn collectors_fail_after_the_maximum_of_consecutive_failures() {
+        for (failures, expected) in [(4, false), (5, true), (6, true)] {
+            let mut failing = collector(now(), chrono::Duration::zero());
+            failing.consecutive_failures = failures;
+            let failed = rules(violations(&config(), &[failing], &[], &[], now()))
+                .contains(&"collector-failures:1".to_owned());
+            assert_eq!(failed, expected, "{} failures", failures);
+        }
+    }
+
+    #[test]
+    fn realtime_alerts_stay_open_while_the_origin_has_no_recent_updates() {
+        let minutes = chrono::Duration::minutes;
+        let realtime = [
+            (Id::new("fresh".to_owned()), now() - minutes(9)),
+            (Id::new("stale".to_owned()), now() - minutes(11)),
+        ];
+        let open = ["realtime-stale:gone", "realtime-stale:fresh"]
+            .into_iter()
+            .map(|rule| Alert {
+                rule: rule.to_owned(),
+                message: String::new(),
+                opened_at: now() - minutes(30),
+            })
+            .collect::<Vec<_>>();
+
+        let mut rules = rules(violations(&config(), &[], &realtime, &open, now()));
+        rules.sort();
+
+        assert_eq!(rules, ["realtime-stale:gone", "realtime-stale:stale"]);
+    }
+}
@@ -1,13 +1,29 @@
-use model::{origin::Origin, WithId};
+use std::time::{Duration, Instant};
+
+use model::origin::Origin;
+use tokio::time;
 use utility::id::Id;
 
 use crate::{
+    capability,
     client::Client,
-    collector::{self, Collector, CollectorInstance},
-    database::{CollectorRepo, Database, DatabaseOperations},
+    collector::{self, Collector, CollectorInstance, CollectorStartup},
+    database::{CollectorRepo, Database},
+    notification::{self, Notifier},
     RequestResult,
 };
 
+/// How often pending changes are appended to the change log, which bounds how
+/// long changes take to show up in the feed.
+const CHANGE_LOG_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often the change log is compacted.
+const CHANGE_LOG_COMPACTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often stop names of stop times are synced with renamed stops, which bounds
+/// how long they lag behind.
+const STOP_NAME_SYNC_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
 pub struct Server<D>
 where
     D: Database + Send + Sync + Sized + 'static,
@@ -27,18 +43,61 @@ where
         Client::new(id, self.database.clone())
     }
 
+    /// Sends the notifications of the alert rules, until the server stops.
+    pub fn alerting(&self, notifier: Notifier) {
+        tokio::spawn(notification::watch(self.database.clone(), notifier));
+    }
+
+    /// Flushes pending changes to the change log and compacts it periodically,
+    /// until the server stops. Changes older than `retention` are only kept, if
+    /// they are the latest of their entity and not a deletion.
+    pub fn change_log_maintenance(&self, retention: chrono::Duration) {
+        let client = self.client("change log maintenance");
+        tokio::spawn(async move {
+            let mut interval = time::interval(CHANGE_LOG_FLUSH_INTERVAL);
+            let mut last_compaction: Option<Instant> = None;
+            loop {
+                interval.tick().await;
+                if let Err(why) = client.flush_changes().await {
+                    eprintln!("could not flush change log: {:?}", why);
+                }
+                if last_compaction
+                    .is_some_and(|at| at.elapsed() < CHANGE_LOG_COMPACTION_INTERVAL)
+                {
+                    continue;
+                }
+                match client.compact_changes(retention).await {
+                    Ok(_) => last_compaction = Some(Instant::now()),
+                    Err(why) => eprintln!("could not compact change log: {:?}", why),
+                }
+            }
+        });
+    }
+
+    /// Syncs the stop names of stop times with stops renamed according to the
+    /// change log periodically, until the server stops. Each sync continues at
+    /// the change, where the last one stopped, so that no rename is missed, even
+    /// while the server is down.
+    pub fn stop_name_sync(&self) {
+        let client = self.client("stop name sync");
+        tokio::spawn(async move {
+            let mut interval = time::interval(STOP_NAME_SYNC_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(why) = client.sync_stop_names().await {
+                    eprintln!("could not sync stop names: {:?}", why);
+                }
+            }
+        });
+    }
+
     pub async fn origin<S: Into<String>>(
         &self,
         name: S,
         priority: i32,
     ) -> RequestResult<Id<Origin>> {
         let name: String = name.into();
-        let id = Id::new(name.to_lowercase().replace(' ', "-").replace('.', "-"));
-        self.database
-            .auto()
-            .put_origin(WithId::new(id.clone(), Origin { name, priority }))
-            .await?;
-        Ok(id)
+        self.client(name.clone()).put_origin(&name, priority).await
     }
 
     pub async fn collector<C, F>(
@@ -46,25 +105,82 @@ where
         id: &Id<CollectorInstance<C>>,
         origin: &Id<Origin>,
         factory: F,
-    ) where
+    ) -> CollectorStartup
+    where
         C: Collector + Send + 'static,
         <C as Collector>::Error: Send,
-        F: 'static + Send + Fn(C::State) -> C,
+        F: 'static + Send + Fn(C::State) -> Result<C, C::Error>,
     {
         let client = self.client(origin.clone().raw());
-        collector::run(factory, client, id.clone()).await;
+        let error = collector::run(factory, client, *id).await.err();
+        self.startup(id, origin, error).await
+    }
+
+    /// Outcome of starting a collector instance. A failure is exposed by the
+    /// health of the collector.
+    async fn startup<C>(
+        &self,
+        id: &Id<CollectorInstance<C>>,
+        origin: &Id<Origin>,
+        error: Option<String>,
+    ) -> CollectorStartup
+    where
+        C: Collector + Send + 'static,
+    {
+        if let Some(why) = &error {
+            if let Err(why) = self
+                .database
+                .auto()
+                .set_collector_health(id, Some(format!("failed to start: {}", why)))
+                .await
+            {
+                eprintln!("could not store collector health: {:?}", why);
+            }
+        }
+        CollectorStartup {
+            id: id.raw(),
+            origin: origin.clone(),
+            kind: C::unique_id().to_owned(),
+            error,
+        }
     }
 
-    pub async fn collectors<C: Collector + 'static>(&self) -> RequestResult<()>
+    /// Starts all instances of the collector. A failing instance does not keep
+    /// the others from starting, but is part of the returned outcomes.
+    pub async fn collectors<C>(&self) -> RequestResult<Vec<CollectorStartup>>
     where
         C: Collector + Send + 'static,
         <C as Collector>::Error: Send,
     {
-        let instances = self.database.auto().collectors::<C>().await?;
-        for instance in instances {
-            self.collector(&instance.id, &instance.content.origin, C::from_state)
-                .await
+        // listed by their health, which does not load their states, so that a
+        // corrupted state only fails the start of its own instance.
+        let instances = self
+            .database
+            .auto()
+            .collector_health()
+            .await?
+            .into_iter()
+            .filter(|instance| instance.kind == C::unique_id())
+            .map(|instance| (Id::new(instance.id), instance.origin))
+            .collect::<Vec<(Id<CollectorInstance<C>>, _)>>();
+        let mut startups = Vec::with_capacity(instances.len());
+        // fails fast on missing capabilities, instead of deep inside every run.
+        let component = format!("collector '{}'", C::unique_id());
+        let missing = capability::check(
+            &component,
+            C::requirements(),
+            self.database.capabilities(),
+        );
+        for requirement in missing.iter().flatten() {
+            log::warn!("{} runs downgraded: {}.", component, requirement);
+        }
+        for (id, origin) in instances {
+            let startup = match &missing {
+                Ok(_) => self.collector(&id, &origin, C::from_state).await,
+                Err(why) => self.startup(&id, &origin, Some(why.clone())).await,
+            };
+            startups.push(startup);
         }
-        Ok(())
+        Ok(startups)
     }
 }
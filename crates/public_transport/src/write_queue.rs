@@ -0,0 +1,412 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    future::Future,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::{sync::Notify, task::spawn_blocking, time::sleep};
+
+use crate::RequestResult;
+
+/// Maximum number of entries written at once by the drain task.
+const DRAIN_BATCH_SIZE: usize = 256;
+
+/// Delay before retrying a failed write. Doubled after each failure.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Upper bound for the delay between two write attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Records appended to the log since the last compaction, beyond twice the
+/// entries still queued, before the log is rewritten.
+const COMPACTION_SLACK: usize = 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct QueueEntry<T> {
+    seq: u64,
+    item: T,
+}
+
+/// Line of the log. Entries leave the queue in order of their sequence numbers,
+/// both when written and when dropped, so removals only store the last one.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum LogRecord<T> {
+    Push(QueueEntry<T>),
+    RemoveThrough(u64),
+}
+
+struct QueueInner<T> {
+    entries: VecDeque<QueueEntry<T>>,
+    next_seq: u64,
+    dropped: u64,
+}
+
+/// Append-only file, the queue is persisted in.
+struct QueueLog {
+    path: PathBuf,
+    file: fs::File,
+    /// Records in the file.
+    records: usize,
+    /// Set, if an append failed and may have left a partial line behind.
+    broken: bool,
+}
+
+/// Durable buffer between ingestion and database writes.
+///
+/// Collectors push parsed data into the queue, which is persisted to a local file
+/// before anything is written to the database. A drain task writes the entries in
+/// order and retries with backoff, so that brief database outages (e.g. a
+/// failover) do not lose data. Entries still in the file are drained first after
+/// a restart.
+///
+/// The file is a log of pushed and removed entries, which is only appended to and
+/// rewritten from time to time. File operations run on the blocking thread pool.
+///
+/// The queue holds at most `capacity` entries. If it is full, the oldest entries
+/// are dropped, as old realtime data is the least valuable.
+pub struct WriteQueue<T> {
+    capacity: usize,
+    inner: Mutex<QueueInner<T>>,
+    /// Locked before `inner` by writers, so that records are appended in the
+    /// order of their sequence numbers.
+    log: Mutex<QueueLog>,
+    notify: Notify,
+}
+
+impl<T> WriteQueue<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Send + 'static,
+{
+    /// Opens the queue persisted at `path`, restoring entries of a previous run.
+    pub async fn open<P: AsRef<Path>>(path: P, capacity: usize) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        spawn_blocking(move || Self::open_blocking(path, capacity)).await?
+    }
+
+    fn open_blocking(path: PathBuf, capacity: usize) -> io::Result<Self> {
+        let entries = if path.exists() {
+            replay(&path)?
+        } else {
+            VecDeque::new()
+        };
+        let next_seq = entries.back().map(|entry| entry.seq + 1).unwrap_or(0);
+        let mut inner = QueueInner {
+            entries,
+            next_seq,
+            dropped: 0,
+        };
+        while inner.entries.len() > capacity {
+            inner.entries.pop_front();
+            inner.dropped += 1;
+        }
+        // also removes a partial line, which a crash may have left behind.
+        let file = rewrite(&path, &inner.entries)?;
+        let log = QueueLog {
+            path,
+            file,
+            records: inner.entries.len(),
+            broken: false,
+        };
+        Ok(Self {
+            capacity,
+            inner: Mutex::new(inner),
+            log: Mutex::new(log),
+            notify: Notify::new(),
+        })
+    }
+
+    /// Number of entries, that are not written to the database yet.
+    pub fn depth(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    /// Number of entries dropped since opening the queue, because it was full.
+    pub fn dropped(&self) -> u64 {
+        self.inner.lock().unwrap().dropped
+    }
+
+    /// Appends the items and persists them, before they are handed to the drain
+    /// task. Once this returns `Ok`, the items survive a restart.
+    pub async fn push(self: &Arc<Self>, items: Vec<T>) -> io::Result<()> {
+        if items.is_empty() {
+            return Ok(());
+        }
+        let queue = self.clone();
+        spawn_blocking(move || queue.push_blocking(items)).await??;
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    fn push_blocking(&self, items: Vec<T>) -> io::Result<()> {
+        let mut log = self.log.lock().unwrap();
+        let mut records = Vec::with_capacity(items.len() + 1);
+        {
+            let mut inner = self.inner.lock().unwrap();
+            for item in items {
+                let entry = QueueEntry {
+                    seq: inner.next_seq,
+                    item,
+                };
+                inner.next_seq += 1;
+                inner.entries.push_back(entry.clone());
+                records.push(LogRecord::Push(entry));
+            }
+            let mut dropped_through = None;
+            while inner.entries.len() > self.capacity {
+                dropped_through = inner.entries.pop_front().map(|entry| entry.seq);
+                inner.dropped += 1;
+            }
+            records.extend(dropped_through.map(LogRecord::RemoveThrough));
+        }
+        self.append(&mut log, &records)
+    }
+
+    /// Spawns the task, that writes the queued entries in order using `write`.
+    /// A failed batch is retried with exponential backoff until it succeeds, later
+    /// entries wait for it, so that the order of updates is preserved.
+    pub fn spawn_drain<F, Fut>(self: &Arc<Self>, write: F)
+    where
+        F: Fn(Vec<T>) -> Fut + Send + 'static,
+        Fut: Future<Output = RequestResult<()>> + Send,
+    {
+        let queue = self.clone();
+        tokio::spawn(async move {
+            let mut backoff = INITIAL_BACKOFF;
+            loop {
+                let batch = queue.front(DRAIN_BATCH_SIZE);
+                let Some(last_seq) = batch.last().map(|entry| entry.seq) else {
+                    queue.notify.notified().await;
+                    continue;
+                };
+                let items = batch.into_iter().map(|entry| entry.item).collect();
+                match write(items).await {
+                    Ok(()) => {
+                        backoff = INITIAL_BACKOFF;
+                        if let Err(why) = queue.remove_through(last_seq).await {
+                            tracing::error!("could not persist write queue: {}", why);
+                        }
+                        tracing::debug!(
+                            depth = queue.depth(),
+                            dropped = queue.dropped(),
+                            "write queue drained batch"
+                        );
+                    }
+                    Err(why) => {
+                        tracing::warn!(
+                            depth = queue.depth(),
+                            "write queue could not write batch, retrying in {:?}: {:?}",
+                            backoff,
+                            why
+                        );
+                        sleep(backoff).await;
+                        backoff = (backoff * 2).min(MAX_BACKOFF);
+                    }
+                }
+            }
+        });
+    }
+
+    fn front(&self, count: usize) -> Vec<QueueEntry<T>> {
+        let inner = self.inner.lock().unwrap();
+        inner.entries.iter().take(count).cloned().collect()
+    }
+
+    /// Removes written entries. Entries may have been dropped meanwhile, so they
+    /// are identified by sequence number rather than position.
+    async fn remove_through(self: &Arc<Self>, seq: u64) -> io::Result<()> {
+        let queue = self.clone();
+        spawn_blocking(move || {
+            let mut log = queue.log.lock().unwrap();
+            {
+                let mut inner = queue.inner.lock().unwrap();
+                while inner.entries.front().is_some_and(|entry| entry.seq <= seq) {
+                    inner.entries.pop_front();
+                }
+            }
+            queue.append(&mut log, &[LogRecord::RemoveThrough(seq)])
+        })
+        .await?
+    }
+
+    /// Appends the records and syncs the file. Rewrites the file instead, once
+    /// most of its records are obsolete.
+    fn append(&self, log: &mut QueueLog, records: &[LogRecord<T>]) -> io::Result<()> {
+        let depth = self.depth();
+        if log.broken || log.records + records.len() > 2 * depth + COMPACTION_SLACK {
+            let entries = self.inner.lock().unwrap().entries.clone();
+            log.file = rewrite(&log.path, &entries)?;
+            log.records = entries.len();
+            log.broken = false;
+            return Ok(());
+        }
+        let result =
+            write_records(&log.file, records).and_then(|_| log.file.sync_data());
+        match result {
+            Ok(()) => log.records += records.len(),
+            Err(_) => log.broken = true,
+        }
+        result
+    }
+}
+
+/// Restores the queued entries from the log. Lines, which can not be read, e.g.
+/// a partial one written during a crash, are skipped.
+fn replay<T: DeserializeOwned>(path: &Path) -> io::Result<VecDeque<QueueEntry<T>>> {
+    let mut entries: VecDeque<QueueEntry<T>> = VecDeque::new();
+    for line in BufReader::new(fs::File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<LogRecord<T>>(&line) {
+            Ok(LogRecord::Push(entry)) => entries.push_back(entry),
+            Ok(LogRecord::RemoveThrough(seq)) => {
+                while entries.front().is_some_and(|entry| entry.seq <= seq) {
+                    entries.pop_front();
+                }
+            }
+            // files written before the log only contain entries.
+            Err(why) => match serde_json::from_str::<QueueEntry<T>>(&line) {
+                Ok(entry) => entries.push_back(entry),
+                Err(_) => {
+                    tracing::warn!("skipping corrupt write queue entry: {}", why)
+                }
+            },
+        }
+    }
+    Ok(entries)
+}
+
+/// Replaces the log atomically with one holding only the given entries. Returns
+/// the new file, opened for appending.
+fn rewrite<T: Serialize>(
+    path: &Path,
+    entries: &VecDeque<QueueEntry<T>>,
+) -> io::Result<fs::File> {
+    let tmp_path = path.with_extension("tmp");
+    let mut file = io::BufWriter::new(fs::File::create(&tmp_path)?);
+    for entry in entries.iter() {
+        let record = LogRecord::Push(QueueEntry {
+            seq: entry.seq,
+            item: &entry.item,
+        });
+        serde_json::to_writer(&mut file, &record)?;
+        file.write_all(b"\n")?;
+    }
+    file.into_inner()?.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    fs::OpenOptions::new().append(true).open(path)
+}
+
+/// Writes the records at once, rather than line by line.
+fn write_records<T: Serialize>(
+    mut file: &fs::File,
+    records: &[LogRecord<T>],
+) -> io::Result<()> {
+    let mut buffer = Vec::new();
+    for record in records {
+        serde_json::to_writer(&mut buffer, record)?;
+        buffer.push(b'\n');
+    }
+    file.write_all(&buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Path of a queue file, which does not exist yet.
+    fn queue_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "write-queue-{}-{}.jsonl",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn items(queue: &WriteQueue<u32>) -> Vec<u32> {
+        queue
+            .front(usize::MAX)
+            .into_iter()
+            .map(|entry| entry.item)
+            .collect()
+    }
+
+    fn lines(path: &Path) -> usize {
+        fs::read_to_string(path).unwrap().lines().count()
+    }
+
+    #[tokio::test]
+    async fn replays_the_log_after_a_crash() {
+        let path = queue_path("replay");
+        let queue = Arc::new(WriteQueue::open(&path, 100).await.unwrap());
+        queue.push(vec![1, 2, 3]).await.unwrap();
+        queue.remove_through(1).await.unwrap();
+        queue.push(vec![4]).await.unwrap();
+        assert_eq!(items(&queue), vec![3, 4]);
+        // pushes and removals are appended, rather than rewriting the file.
+        assert_eq!(lines(&path), 5);
+        drop(queue);
+
+        // a crash during an append leaves a partial line behind.
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"push":{"seq":4,"it"#).unwrap();
+        drop(file);
+
+        let queue = Arc::new(WriteQueue::<u32>::open(&path, 100).await.unwrap());
+        assert_eq!(items(&queue), vec![3, 4]);
+        assert_eq!(lines(&path), 2, "the log is compacted on open");
+        queue.push(vec![5]).await.unwrap();
+        assert_eq!(queue.front(usize::MAX).last().unwrap().seq, 4);
+        drop(queue);
+        let queue = WriteQueue::<u32>::open(&path, 100).await.unwrap();
+        assert_eq!(items(&queue), vec![3, 4, 5]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn drops_the_oldest_entries_durably() {
+        let path = queue_path("capacity");
+        let queue = Arc::new(WriteQueue::open(&path, 2).await.unwrap());
+        queue.push(vec![1, 2, 3]).await.unwrap();
+        assert_eq!(items(&queue), vec![2, 3]);
+        assert_eq!(queue.dropped(), 1);
+        drop(queue);
+        let queue = WriteQueue::<u32>::open(&path, 2).await.unwrap();
+        assert_eq!(items(&queue), vec![2, 3]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn compacts_the_log_once_most_records_are_obsolete() {
+        let path = queue_path("compaction");
+        let queue = Arc::new(WriteQueue::open(&path, 100).await.unwrap());
+        for item in 0..COMPACTION_SLACK as u32 {
+            queue.push(vec![item]).await.unwrap();
+            queue.remove_through(item as u64).await.unwrap();
+        }
+        assert_eq!(queue.depth(), 0);
+        assert!(lines(&path) <= COMPACTION_SLACK + 1);
+        drop(queue);
+        let queue = WriteQueue::<u32>::open(&path, 100).await.unwrap();
+        assert_eq!(queue.depth(), 0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn restores_files_written_before_the_log() {
+        let path = queue_path("legacy");
+        fs::write(&path, "{\"seq\":7,\"item\":1}\n{\"seq\":8,\"item\":2}\n").unwrap();
+        let queue = WriteQueue::<u32>::open(&path, 100).await.unwrap();
+        assert_eq!(items(&queue), vec![1, 2]);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,5 +1,21 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
 pub const EARTH_RADIUS_KM: f64 = 6371.0;
 
+/// Whether latitude is within [-90, 90] and longitude within [-180, 180].
+/// Exactly (0, 0) is typically the result of missing values in a feed and is only
+/// considered valid with `allow_null_island`.
+pub fn is_valid_coordinate(
+    latitude: f64,
+    longitude: f64,
+    allow_null_island: bool,
+) -> bool {
+    (-90.0..=90.0).contains(&latitude)
+        && (-180.0..=180.0).contains(&longitude)
+        && (allow_null_island || latitude != 0.0 || longitude != 0.0)
+}
+
 fn to_radians(degrees: f64) -> f64 {
     degrees * std::f64::consts::PI / 180.0
 }
@@ -54,3 +70,328 @@ pub fn haversine_distance(
 
     EARTH_RADIUS_KM * c
 }
+
+/// Simplifies a polyline of `(latitude, longitude)` pairs with the
+/// Ramer-Douglas-Peucker algorithm. Points closer than `tolerance_km` to the
+/// simplified line are dropped. The first and the last point are always kept.
+pub fn simplify(points: &[(f64, f64)], tolerance_km: f64) -> Vec<(f64, f64)> {
+    if points.len() < 3 || tolerance_km <= 0.0 {
+        return points.to_vec();
+    }
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    let mut sections = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = sections.pop() {
+        let (index, distance) = (first + 1..last)
+            .map(|i| (i, segment_distance(points[i], points[first], points[last])))
+            .fold((first, 0.0), |max, candidate| {
+                if candidate.1 > max.1 {
+                    candidate
+                } else {
+                    max
+                }
+            });
+        if distance > tolerance_km {
+            keep[index] = true;
+            sections.push((first, index));
+            sections.push((index, last));
+        }
+    }
+    points
+        .iter()
+        .zip(keep)
+        .filter_map(|(point, keep)| keep.then_some(*point))
+        .collect()
+}
+
+/// Distance in km between `point` and the segment from `start` to `end`.
+/// Coordinates are projected onto a plane, which is accurate enough for the short
+/// segments of a route.
+fn segment_distance(point: (f64, f64), start: (f64, f64), end: (f64, f64)) -> f64 {
+    let scale = to_radians(start.0).cos();
+    let project = |(latitude, longitude): (f64, f64)| {
+        (
+            to_radians(longitude) * scale * EARTH_RADIUS_KM,
+            to_radians(latitude) * EARTH_RADIUS_KM,
+        )
+    };
+    let (px, py) = project(point);
+    let (ax, ay) = project(start);
+    let (bx, by) = project(end);
+    let (dx, dy) = (bx - ax, by - ay);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((px - ax) * dx + (py - ay) * dy) / length_squared).clamp(0.0, 1.0)
+    };
+    ((px - ax - t * dx).powi(2) + (py - ay - t * dy).powi(2)).sqrt()
+}
+
+/// Convex hull of `(latitude, longitude)` pairs with Andrew's monotone chain.
+/// Counterclockwise and without repeating the first point. Coordinates are treated
+/// as planar, which is accurate enough away from the poles and the antimeridian.
+/// Fewer than three points are returned, if all points are collinear.
+pub fn convex_hull(points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.total_cmp(&b.0)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    // positive, if `a`, `b` and `c` turn counterclockwise with longitude as x.
+    let cross = |a: (f64, f64), b: (f64, f64), c: (f64, f64)| {
+        (b.1 - a.1) * (c.0 - a.0) - (b.0 - a.0) * (c.1 - a.1)
+    };
+    let mut hull: Vec<(f64, f64)> = Vec::with_capacity(points.len() + 1);
+    // lower hull from west to east, then upper hull back.
+    for pass in [points.clone(), points.into_iter().rev().collect()] {
+        let start = hull.len();
+        for point in pass {
+            while hull.len() >= start + 2
+                && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0.0
+            {
+                hull.pop();
+            }
+            hull.push(point);
+        }
+        // the last point is the first of the next pass.
+        hull.pop();
+    }
+    hull
+}
+
+/// Area of one or more polygons, e.g. the pick-up zone of a shared mobility
+/// station. (De)serialized as GeoJSON `Polygon` or `MultiPolygon` geometry and
+/// always serialized as the latter.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "GeoJsonArea", into = "GeoJsonArea")]
+pub struct GeoPolygon {
+    /// Rings of `[longitude, latitude]` positions per polygon. The first ring of
+    /// a polygon is its boundary, the others are holes.
+    pub polygons: Vec<Vec<Vec<[f64; 2]>>>,
+}
+
+impl GeoPolygon {
+    /// Whether the point lies within any of the polygons, but not in one of its
+    /// holes. Points on an edge may be considered on either side.
+    pub fn contains(&self, latitude: f64, longitude: f64) -> bool {
+        self.polygons.iter().any(|rings| {
+            let mut rings = rings.iter();
+            rings
+                .next()
+                .is_some_and(|boundary| ring_contains(boundary, latitude, longitude))
+                && !rings.any(|hole| ring_contains(hole, latitude, longitude))
+        })
+    }
+
+    /// `((min_latitude, min_longitude), (max_latitude, max_longitude))` of all
+    /// boundaries, or `None` if there are no positions.
+    pub fn bounding_box(&self) -> Option<((f64, f64), (f64, f64))> {
+        self.polygons
+            .iter()
+            .filter_map(|rings| rings.first())
+            .flatten()
+            .fold(None, |bounds, &[longitude, latitude]| {
+                let ((min_lat, min_lon), (max_lat, max_lon)) =
+                    bounds.unwrap_or(((latitude, longitude), (latitude, longitude)));
+                Some((
+                    (min_lat.min(latitude), min_lon.min(longitude)),
+                    (max_lat.max(latitude), max_lon.max(longitude)),
+                ))
+            })
+    }
+}
+
+/// Even-odd rule: a ray from the point crosses the ring an odd number of times,
+/// if the point is inside. Positions are treated as planar, which is accurate
+/// enough for areas of a few kilometers.
+fn ring_contains(ring: &[[f64; 2]], latitude: f64, longitude: f64) -> bool {
+    let mut inside = false;
+    let mut previous = match ring.last() {
+        Some(previous) => previous,
+        None => return false,
+    };
+    for current in ring {
+        let [lon_a, lat_a] = *previous;
+        let [lon_b, lat_b] = *current;
+        if (lat_a > latitude) != (lat_b > latitude)
+            && longitude
+                < lon_a + (latitude - lat_a) / (lat_b - lat_a) * (lon_b - lon_a)
+        {
+            inside = !inside;
+        }
+        previous = current;
+    }
+    inside
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type")]
+enum GeoJsonArea {
+    Polygon {
+        coordinates: Vec<Vec<[f64; 2]>>,
+    },
+    MultiPolygon {
+        coordinates: Vec<Vec<Vec<[f64; 2]>>>,
+    },
+}
+
+impl From<GeoJsonArea> for GeoPolygon {
+    fn from(area: GeoJsonArea) -> Self {
+        let polygons = match area {
+            GeoJsonArea::Polygon { coordinates } => vec![coordinates],
+            GeoJsonArea::MultiPolygon { coordinates } => coordinates,
+        };
+        Self { polygons }
+    }
+}
+
+impl From<GeoPolygon> for GeoJsonArea {
+    fn from(area: GeoPolygon) -> Self {
+        GeoJsonArea::MultiPolygon {
+            coordinates: area.polygons,
+        }
+    }
+}
+
+impl JsonSchema for GeoPolygon {
+    fn schema_name() -> String {
+        "GeoPolygon".to_string()
+    }
+
+    fn json_schema(
+        gen: &mut schemars::gen::SchemaGenerator,
+    ) -> schemars::schema::Schema {
+        GeoJsonArea::json_schema(gen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_coordinates() {
+        let cases = [
+            (54.32, 10.13, false, true),
+            (-90.0, -180.0, false, true),
+            (90.0, 180.0, false, true),
+            (90.1, 10.13, false, false),
+            (54.32, -180.1, false, false),
+            (f64::NAN, 10.13, false, false),
+            (54.32, f64::INFINITY, false, false),
+            (0.0, 0.0, false, false),
+            (0.0, 0.0, true, true),
+            (0.0, 10.13, false, true),
+            (54.32, 0.0, false, true),
+        ];
+        for (latitude, longitude, allow_null_island, expected) in cases {
+            assert_eq!(
+                is_valid_coordinate(latitude, longitude, allow_null_island),
+                expected,
+                "({}, {}), null island allowed: {}",
+                latitude,
+                longitude,
+                allow_null_island
+            );
+        }
+    }
+
+    /// Square around the point with the given half width in degrees.
+    fn square(latitude: f64, longitude: f64, half: f64) -> Vec<[f64; 2]> {
+        vec![
+            [longitude - half, latitude - half],
+            [longitude + half, latitude - half],
+            [longitude + half, latitude + half],
+            [longitude - half, latitude + half],
+            [longitude - half, latitude - half],
+        ]
+    }
+
+    #[test]
+    fn points_in_polygons_but_not_in_their_holes_are_contained() {
+        let area = GeoPolygon {
+            polygons: vec![
+                vec![square(54.32, 10.13, 0.01), square(54.32, 10.13, 0.002)],
+                vec![square(54.40, 10.20, 0.001)],
+            ],
+        };
+        let cases = [
+            (54.315, 10.125, true),
+            (54.32, 10.13, false),
+            (54.3201, 10.1299, false),
+            (54.40, 10.20, true),
+            (54.33, 10.13, false),
+            (54.35, 10.15, false),
+            (0.0, 0.0, false),
+        ];
+        for (latitude, longitude, expected) in cases {
+            assert_eq!(
+                area.contains(latitude, longitude),
+                expected,
+                "({}, {})",
+                latitude,
+                longitude
+            );
+        }
+        assert!(!GeoPolygon { polygons: vec![] }.contains(54.32, 10.13));
+        assert!(!GeoPolygon {
+            polygons: vec![vec![vec![]]]
+        }
+        .contains(54.32, 10.13));
+    }
+
+    #[test]
+    fn areas_are_read_from_polygons_and_written_as_multi_polygons() {
+        let area: GeoPolygon = serde_json::from_str(
+            r#"{
+                "type": "Polygon",
+                "coordinates": [
+                    [[10.12, 54.31], [10.14, 54.31], [10.14, 54.33], [10.12, 54.31]]
+                ]
+            }"#,
+        )
+        .expect("polygon is deserialized");
+        assert_eq!(area.bounding_box(), Some(((54.31, 10.12), (54.33, 10.14))));
+
+        let json = serde_json::to_value(&area).unwrap();
+        assert_eq!(json["type"], "MultiPolygon");
+        assert_eq!(
+            serde_json::from_value::<GeoPolygon>(json).expect("area is deserialized"),
+            area
+        );
+        assert_eq!(GeoPolygon { polygons: vec![] }.bounding_box(), None);
+    }
+
+    #[test]
+    fn hulls_enclose_all_points_counterclockwise() {
+        let square = [(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0)];
+        let cases = [
+            (vec![], vec![]),
+            (vec![(1.0, 1.0), (1.0, 1.0)], vec![(1.0, 1.0)]),
+            (
+                vec![(0.0, 0.0), (0.0, 2.0), (0.0, 1.0)],
+                vec![(0.0, 0.0), (0.0, 2.0)],
+            ),
+            (square.to_vec(), square.to_vec()),
+            // points inside, on an edge or repeated do not belong to the hull.
+            (
+                vec![
+                    (0.5, 0.5),
+                    (1.0, 1.0),
+                    (0.0, 0.5),
+                    (0.0, 0.0),
+                    (1.0, 0.0),
+                    (0.0, 1.0),
+                    (1.0, 1.0),
+                ],
+                square.to_vec(),
+            ),
+        ];
+        for (index, (points, expected)) in cases.into_iter().enumerate() {
+            assert_eq!(convex_hull(&points), expected, "hull of case {}", index);
+        }
+    }
+}
@@ -2,5 +2,9 @@ pub mod edit_distance;
 pub mod geo;
 pub mod id;
 pub mod let_also;
+pub mod log_sampling;
 pub mod math;
+pub mod normalize;
 pub mod serde;
+pub mod text;
+pub mod tz;
@@ -0,0 +1,161 @@
+//! Sampling of log messages of high-frequency collectors. Instead of a message
+//! per event, e.g. per inserted trip, events are counted and summarized once per
+//! time window. Errors are meant to be logged as they occur.
+
+use std::{
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Window, after which the events are summarized, unless configured otherwise.
+pub const DEFAULT_SUMMARY_WINDOW: Duration = Duration::from_secs(60);
+
+/// How much a collector logs about frequent events.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LogVerbosity {
+    /// Nothing is logged about the events.
+    Quiet,
+    /// The number of events is logged once per window.
+    #[default]
+    Summary,
+    /// Every event is logged.
+    Verbose,
+}
+
+#[derive(Debug)]
+struct Window {
+    started: Option<Instant>,
+    events: u64,
+}
+
+/// Counts events and decides, what is logged about them.
+#[derive(Debug)]
+pub struct LogSampler {
+    verbosity: LogVerbosity,
+    window: Duration,
+    current: Mutex<Window>,
+}
+
+impl LogSampler {
+    pub fn new(verbosity: LogVerbosity) -> Self {
+        Self::with_window(verbosity, DEFAULT_SUMMARY_WINDOW)
+    }
+
+    pub fn with_window(verbosity: LogVerbosity, window: Duration) -> Self {
+        Self {
+            verbosity,
+            window,
+            current: Mutex::new(Window {
+                started: None,
+                events: 0,
+            }),
+        }
+    }
+
+    pub fn verbosity(&self) -> LogVerbosity {
+        self.verbosity
+    }
+
+    /// Whether every single event is logged.
+    pub fn is_verbose(&self) -> bool {
+        self.verbosity == LogVerbosity::Verbose
+    }
+
+    /// Adds events to the current window. Once the window has passed, the
+    /// number of its events is returned to be logged and the next window starts.
+    /// Always `None`, unless events are summarized.
+    pub fn count(&self, events: u64) -> Option<u64> {
+        self.count_at(events, Instant::now())
+    }
+
+    /// Like [`LogSampler::count`], at the given point in time.
+    pub fn count_at(&self, events: u64, now: Instant) -> Option<u64> {
+        if self.verbosity != LogVerbosity::Summary {
+            return None;
+        }
+        let mut window = self.current.lock().unwrap_or_else(PoisonError::into_inner);
+        window.events += events;
+        let started = *window.started.get_or_insert(now);
+        if now.duration_since(started) < self.window {
+            return None;
+        }
+        let events = window.events;
+        window.started = Some(now);
+        window.events = 0;
+        Some(events)
+    }
+}
+
+impl Default for LogSampler {
+    fn default() -> Self {
+        Self::new(LogVerbosity::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_summarized_once_per_window() {
+        let sampler =
+            LogSampler::with_window(LogVerbosity::Summary, Duration::from_secs(60));
+        let start = Instant::now();
+        let at = |seconds| start + Duration::from_secs(seconds);
+        // events by seconds since the first one and the expected summary.
+        let cases = [
+            (0, 3, None),
+            (30, 5, None),
+            (59, 1, None),
+            (60, 2, Some(11)),
+            (61, 4, None),
+            (119, 1, None),
+            (120, 1, Some(6)),
+            // a window without events is not summarized until the next event.
+            (300, 7, Some(7)),
+            (301, 0, None),
+        ];
+        for (i, (seconds, events, expected)) in cases.into_iter().enumerate() {
+            assert_eq!(
+                sampler.count_at(events, at(seconds)),
+                expected,
+                "summary of case {}",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn events_are_only_summarized_with_summary_verbosity() {
+        let cases = [
+            (LogVerbosity::Quiet, None),
+            (LogVerbosity::Summary, Some(2)),
+            (LogVerbosity::Verbose, None),
+        ];
+        let start = Instant::now();
+        for (i, (verbosity, expected)) in cases.into_iter().enumerate() {
+            let sampler = LogSampler::with_window(verbosity, Duration::from_secs(1));
+            assert_eq!(
+                sampler.count_at(1, start),
+                None,
+                "first count of case {}",
+                i
+            );
+            assert_eq!(
+                sampler.count_at(1, start + Duration::from_secs(1)),
+                expected,
+                "summary of case {}",
+                i
+            );
+            assert_eq!(
+                sampler.is_verbose(),
+                verbosity == LogVerbosity::Verbose,
+                "verbose of case {}",
+                i
+            );
+        }
+    }
+}
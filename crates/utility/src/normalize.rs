@@ -0,0 +1,345 @@
+use url::Url;
+
+/// Normalizes a website url.
+/// A missing scheme is assumed to be `https://`. The url is then parsed and
+/// returned in its canonical form, so that different spellings of the same url
+/// (e.g., `www.Example.de` and `https://www.example.de/`) compare equal.
+/// Returns `None` if the url is empty or invalid.
+pub fn normalize_url(url: &str) -> Option<String> {
+    let url = url.trim();
+    if url.is_empty() {
+        return None;
+    }
+    let with_scheme = if url.contains("://") {
+        url.to_owned()
+    } else {
+        format!("https://{}", url.trim_start_matches('/'))
+    };
+    let parsed = Url::parse(&with_scheme).ok()?;
+    match (parsed.scheme(), parsed.host_str()) {
+        ("http" | "https", Some(host)) if host.contains('.') => Some(parsed.into()),
+        _ => None,
+    }
+}
+
+/// Normalizes a phone number by removing formatting noise like spaces, dashes,
+/// slashes, dots and parentheses. A leading `00` is replaced by `+`. The trunk
+/// prefix `(0)` of international numbers (e.g., `+49 (0)431`) is dropped.
+/// Dialable letters (e.g., `503-238-RIDE`) are kept and uppercased.
+/// Returns `None` if the phone number contains no digits or unexpected characters.
+pub fn normalize_phone_number(phone_number: &str) -> Option<String> {
+    let phone_number = phone_number.trim();
+    let phone_number = phone_number.strip_prefix("tel:").unwrap_or(phone_number);
+    let is_international =
+        phone_number.starts_with('+') || phone_number.starts_with("00");
+    let phone_number = if is_international {
+        phone_number.replace("(0)", "")
+    } else {
+        phone_number.to_owned()
+    };
+    let mut result = String::new();
+    for (i, c) in phone_number.chars().enumerate() {
+        match c {
+            '+' if i == 0 => result.push(c),
+            c if c.is_ascii_digit() => result.push(c),
+            c if c.is_ascii_alphabetic() => result.push(c.to_ascii_uppercase()),
+            ' ' | '-' | '/' | '.' | '(' | ')' => {}
+            _ => return None,
+        }
+    }
+    if !result.chars().any(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    match result.strip_prefix("00") {
+        Some(international) => Some(format!("+{}", international)),
+        None => Some(result),
+    }
+}
+
+/// Checks the syntax of an email address. A leading `mailto:` is removed and the
+/// domain part is lowercased.
+/// Returns `None` if the email address is syntactically invalid.
+pub fn normalize_email(email: &str) -> Option<String> {
+    let email = email.trim();
+    let email = email.strip_prefix("mailto:").unwrap_or(email);
+    let (local, domain) = email.split_once('@')?;
+    let is_valid = !local.is_empty()
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !email.chars().any(|c| c.is_whitespace());
+    if !is_valid {
+        return None;
+    }
+    Some(format!("{}@{}", local, domain.to_lowercase()))
+}
+
+/// Drops empty and whitespace-only values. Origins use them for missing values,
+/// which must not replace an actual value of another origin when merging.
+pub fn non_blank(value: Option<String>) -> Option<String> {
+    value.filter(|value| !value.trim().is_empty())
+}
+
+/// Like `non_blank`, but borrows the value, e.g. to bind it to a query.
+pub fn non_blank_ref(value: &Option<String>) -> Option<&str> {
+    value.as_deref().filter(|value| !value.trim().is_empty())
+}
+
+/// `other`, unless it is empty or whitespace-only, for values which are required
+/// but may still be empty, e.g. the website of an agency.
+pub fn prefer_non_blank(value: String, other: String) -> String {
+    if other.trim().is_empty() {
+        value
+    } else {
+        other
+    }
+}
+
+/// Prefixes of platform codes, which name the kind of platform rather than the
+/// platform, e.g. `Gleis 1`. Compared case-insensitively.
+const PLATFORM_PREFIXES: [&str; 8] = [
+    "gleis",
+    "gl.",
+    "bahnsteig",
+    "bstg.",
+    "bussteig",
+    "steig",
+    "platform",
+    "track",
+];
+
+/// Numbers of sort keys are padded to this width, so that keys compare bytewise.
+const PLATFORM_SORT_KEY_DIGITS: usize = 8;
+
+/// Canonical display value of a platform code. Prefixes like `Gleis` or
+/// `Platform` are removed, as are leading zeros of numbers and repeated
+/// whitespace, e.g. `1a` for `Gleis 01a`. Alphabetic and composite codes like
+/// `A` or `2 A-C` are kept otherwise.
+/// Returns `None` if the platform code is blank.
+pub fn normalize_platform_code(code: &str) -> Option<String> {
+    let code = code.split_whitespace().collect::<Vec<_>>().join(" ");
+    let code = PLATFORM_PREFIXES
+        .iter()
+        .find_map(|prefix| {
+            let head = code.get(..prefix.len())?;
+            let rest = &code[prefix.len()..];
+            // a word prefix must not continue, e.g. in `Steigerwald`.
+            let is_word = !prefix.ends_with('.')
+                && rest.starts_with(|c: char| c.is_alphabetic());
+            let rest = rest.trim_start();
+            (head.eq_ignore_ascii_case(prefix) && !is_word && !rest.is_empty())
+                .then_some(rest)
+        })
+        .unwrap_or(&code);
+
+    let mut result = String::with_capacity(code.len());
+    let mut chars = code.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '0' && !result.ends_with(|c: char| c.is_ascii_digit()) {
+            // the last digit of a number is kept, e.g. of `0`.
+            if chars.peek().is_some_and(|next| next.is_ascii_digit()) {
+                continue;
+            }
+        }
+        result.push(c);
+    }
+    (!result.is_empty()).then_some(result)
+}
+
+/// Key ordering platform codes naturally, i.e. numbers by their value followed
+/// by their suffix, and alphabetic codes after all numeric ones, e.g. `2` <
+/// `2a` < `2 A-C` < `10` < `A` < `B`. Case and whitespace are ignored. Expects
+/// a normalized platform code.
+pub fn platform_sort_key(code: &str) -> String {
+    let mut key = String::with_capacity(code.len() + PLATFORM_SORT_KEY_DIGITS);
+    let mut chars = code.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            let mut number = c.to_string();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                number.push(digit);
+            }
+            key.push_str(&format!(
+                "{:0>width$}",
+                number,
+                width = PLATFORM_SORT_KEY_DIGITS
+            ));
+        } else if !c.is_whitespace() {
+            key.extend(c.to_lowercase());
+        }
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_urls() {
+        let cases = [
+            ("https://www.example.de/", Some("https://www.example.de/")),
+            ("www.example.de", Some("https://www.example.de/")),
+            ("WWW.Example.DE", Some("https://www.example.de/")),
+            ("//www.example.de", Some("https://www.example.de/")),
+            (" http://example.de ", Some("http://example.de/")),
+            ("example.de/fahrplan/", Some("https://example.de/fahrplan/")),
+            ("example.de/fahrplan", Some("https://example.de/fahrplan")),
+            ("http://", None),
+            ("ftp://example.de", None),
+            ("localhost", None),
+            ("www example de", None),
+            ("", None),
+            ("   ", None),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(
+                normalize_url(url).as_deref(),
+                expected,
+                "normalized `{}`",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn normalizes_email_addresses() {
+        let cases = [
+            ("info@example.de", Some("info@example.de")),
+            ("mailto:info@example.de", Some("info@example.de")),
+            (" mailto:Info@KVG-Kiel.DE ", Some("Info@kvg-kiel.de")),
+            ("Info@example.de", Some("Info@example.de")),
+            ("info@example", None),
+            ("@example.de", None),
+            ("info@@example.de", None),
+            ("info@.example.de", None),
+            ("info@example.de.", None),
+            ("info @example.de", None),
+            ("mailto:", None),
+            ("", None),
+        ];
+        for (email, expected) in cases {
+            assert_eq!(
+                normalize_email(email).as_deref(),
+                expected,
+                "normalized `{}`",
+                email
+            );
+        }
+    }
+
+    #[test]
+    fn normalizes_phone_numbers() {
+        let cases = [
+            ("0431 123456", Some("0431123456")),
+            ("0431/12 34-56", Some("0431123456")),
+            ("(0431) 123456", Some("0431123456")),
+            ("tel:+49 431 123456", Some("+49431123456")),
+            ("0049 431 123456", Some("+49431123456")),
+            ("+49 (0)431 123456", Some("+49431123456")),
+            ("+49(0)431-123456", Some("+49431123456")),
+            ("0049 (0) 431 123456", Some("+49431123456")),
+            ("(0)431 123456", Some("0431123456")),
+            ("503-238-ride", Some("503238RIDE")),
+            ("+49 431 +123", None),
+            ("call us", None),
+            ("", None),
+        ];
+        for (phone_number, expected) in cases {
+            assert_eq!(
+                normalize_phone_number(phone_number).as_deref(),
+                expected,
+                "normalized `{}`",
+                phone_number
+            );
+        }
+    }
+
+    #[test]
+    fn normalizes_platform_codes() {
+        let cases = [
+            ("1", Some("1")),
+            ("01", Some("1")),
+            ("001a", Some("1a")),
+            ("0", Some("0")),
+            ("00", Some("0")),
+            ("10", Some("10")),
+            ("100", Some("100")),
+            ("1a", Some("1a")),
+            ("Gleis 1", Some("1")),
+            ("gleis 01", Some("1")),
+            ("Gleis1", Some("1")),
+            ("Gl. 3", Some("3")),
+            ("Gl.3", Some("3")),
+            ("Bahnsteig 4", Some("4")),
+            ("Bstg. 5", Some("5")),
+            ("Bussteig B", Some("B")),
+            ("Steig 7", Some("7")),
+            ("Platform 02b", Some("2b")),
+            ("Track 12", Some("12")),
+            ("  2   A-C ", Some("2 A-C")),
+            ("2 A-C", Some("2 A-C")),
+            ("A", Some("A")),
+            ("B", Some("B")),
+            ("11-12", Some("11-12")),
+            ("05-06", Some("5-6")),
+            ("Steigerwald", Some("Steigerwald")),
+            ("Gleisdreieck", Some("Gleisdreieck")),
+            ("Gleis", Some("Gleis")),
+            ("", None),
+            ("   ", None),
+        ];
+        for (code, expected) in cases {
+            assert_eq!(
+                normalize_platform_code(code).as_deref(),
+                expected,
+                "normalized `{}`",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn orders_platform_codes_naturally() {
+        let ordered = [
+            "1", "2", "2a", "2 A-C", "2b", "3", "9", "10", "10a", "11-12", "12",
+            "100", "A", "B", "b1", "Nord",
+        ];
+        for pair in ordered.windows(2) {
+            assert!(
+                platform_sort_key(pair[0]) < platform_sort_key(pair[1]),
+                "`{}` sorts before `{}`",
+                pair[0],
+                pair[1]
+            );
+        }
+    }
+
+    #[test]
+    fn orders_observed_platform_codes_after_normalizing() {
+        let observed = [
+            "Gleis 10",
+            "Platform 02b",
+            "A",
+            "01",
+            "Gleis 2",
+            "1a",
+            "2 A-C",
+            "B",
+        ];
+        let mut codes = observed
+            .iter()
+            .filter_map(|code| normalize_platform_code(code))
+            .collect::<Vec<_>>();
+        codes.sort_by_key(|code| platform_sort_key(code));
+        assert_eq!(codes, ["1", "1a", "2", "2 A-C", "2b", "10", "A", "B"]);
+    }
+
+    #[test]
+    fn ignores_case_and_whitespace_in_platform_sort_keys() {
+        assert_eq!(platform_sort_key("2 a"), platform_sort_key("2A"));
+        assert_eq!(platform_sort_key("7"), "00000007");
+        assert_eq!(platform_sort_key("123456789"), "123456789");
+    }
+}
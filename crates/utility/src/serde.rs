@@ -207,3 +207,25 @@ pub mod duration {
         .into()
     }
 }
+
+pub mod comma_separated {
+    use std::{fmt::Display, str::FromStr};
+
+    use serde::{de::Error as DeError, Deserialize, Deserializer};
+
+    /// Deserializes a comma separated list, e.g., `school,seasonal`. Empty items
+    /// are skipped.
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: FromStr,
+        T::Err: Display,
+    {
+        String::deserialize(deserializer)?
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| item.parse().map_err(D::Error::custom))
+            .collect()
+    }
+}
@@ -0,0 +1,219 @@
+/// Folds a character for matching, i.e. lowercases it and strips diacritics.
+/// E.g., `Ä` becomes `a` and `ß` becomes `ss`.
+fn fold(c: char) -> impl Iterator<Item = char> {
+    c.to_lowercase().flat_map(|c| {
+        let folded: &[char] = match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ą' => &['a'],
+            'æ' => &['a', 'e'],
+            'ç' | 'ć' | 'č' => &['c'],
+            'ď' => &['d'],
+            'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => &['e'],
+            'ì' | 'í' | 'î' | 'ï' | 'ī' => &['i'],
+            'ł' => &['l'],
+            'ñ' | 'ń' | 'ň' => &['n'],
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' => &['o'],
+            'œ' => &['o', 'e'],
+            'ř' => &['r'],
+            'ś' | 'š' | 'ş' => &['s'],
+            'ß' => &['s', 's'],
+            'ť' => &['t'],
+            'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' => &['u'],
+            'ý' | 'ÿ' => &['y'],
+            'ź' | 'ż' | 'ž' => &['z'],
+            _ => return vec![c],
+        };
+        folded.to_vec()
+    })
+}
+
+/// Ranges of `text`, which match the words of `pattern`, ignoring case and
+/// diacritics. Ranges are `[start, end)` in chars (not bytes) of `text`, sorted
+/// and merged where they overlap or touch. Used for highlighting search results.
+pub fn match_ranges(text: &str, pattern: &str) -> Vec<(usize, usize)> {
+    // folded chars of the text with the index of the char they originate from.
+    let (folded, origins): (Vec<char>, Vec<usize>) = text
+        .chars()
+        .enumerate()
+        .flat_map(|(i, c)| fold(c).map(move |folded| (folded, i)))
+        .unzip();
+    let mut ranges = vec![];
+    for word in pattern.split_whitespace() {
+        let word = word.chars().flat_map(fold).collect::<Vec<_>>();
+        if word.is_empty() || word.len() > folded.len() {
+            continue;
+        }
+        for start in 0..=folded.len() - word.len() {
+            if folded[start..start + word.len()] == word[..] {
+                let end = start + word.len() - 1;
+                ranges.push((origins[start], origins[end] + 1));
+            }
+        }
+    }
+    ranges.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = vec![];
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Marker appended to truncated texts.
+const TRUNCATION_MARKER: char = '…';
+
+/// Whether the character must not appear in free texts. Besides control
+/// characters, this includes bidirectional formatting characters, which can be
+/// used to disguise the text (e.g. `U+202E RIGHT-TO-LEFT OVERRIDE`).
+fn is_forbidden(c: char) -> bool {
+    (c.is_control() && !c.is_whitespace())
+        || matches!(
+            c,
+            '\u{200B}'..='\u{200F}'
+                | '\u{202A}'..='\u{202E}'
+                | '\u{2066}'..='\u{2069}'
+                | '\u{FEFF}'
+        )
+}
+
+/// Elements, whose content is removed along with their tags.
+const SKIPPED_ELEMENTS: &[&str] = &["script", "style"];
+
+/// Sanitizes free text of a feed (names, descriptions, headsigns, ...) before it
+/// is stored. Html tags and forbidden characters are removed, whitespace is
+/// collapsed and texts longer than `max_chars` are truncated with a `…` marker.
+/// Returns `None` if nothing is left.
+pub fn sanitize(text: &str, max_chars: usize) -> Option<String> {
+    let mut result = String::with_capacity(text.len().min(max_chars * 4));
+    let mut count = 0;
+    let mut pending_space = false;
+    // element, whose content is skipped until its closing tag.
+    let mut skipped_element: Option<String> = None;
+    // a `<` after the last `>` can not start a tag, e.g. in `a<b`.
+    let last_tag_end = text.rfind('>');
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        // only `<` followed by a letter, `/` or `!` starts a tag, e.g. not `1 < 2`.
+        let starts_tag = c == '<'
+            && last_tag_end.is_some_and(|end| i < end)
+            && chars.peek().is_some_and(|(_, next)| {
+                next.is_ascii_alphabetic() || matches!(next, '/' | '!')
+            });
+        if starts_tag {
+            let tag = chars
+                .by_ref()
+                .map(|(_, c)| c)
+                .take_while(|c| *c != '>')
+                .collect::<String>();
+            let name = tag
+                .trim_start_matches('/')
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            match &skipped_element {
+                Some(element) if tag.starts_with('/') && *element == name => {
+                    skipped_element = None;
+                }
+                Some(_) => {}
+                // self-closing elements, e.g. `<script/>`, have no content.
+                None if SKIPPED_ELEMENTS.contains(&name.as_str())
+                    && !tag.ends_with('/') =>
+                {
+                    skipped_element = Some(name);
+                }
+                // tags like `<br>` separate words.
+                None => pending_space = true,
+            }
+            continue;
+        }
+        if skipped_element.is_some() || is_forbidden(c) {
+            continue;
+        }
+        if c.is_whitespace() {
+            pending_space = true;
+            continue;
+        }
+        if pending_space && count > 0 {
+            result.push(' ');
+            count += 1;
+        }
+        pending_space = false;
+        result.push(c);
+        count += 1;
+        // stop early, so that huge inputs are not processed entirely.
+        if count > max_chars {
+            break;
+        }
+    }
+    if count > max_chars {
+        let mut truncated = result
+            .chars()
+            .take(max_chars.saturating_sub(1))
+            .collect::<String>()
+            .trim_end()
+            .to_owned();
+        truncated.push(TRUNCATION_MARKER);
+        return Some(truncated);
+    }
+    (!result.is_empty()).then_some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitizes_free_texts() {
+        let cases = [
+            ("Kiel Hbf", Some("Kiel Hbf")),
+            ("  Kiel \t\n Hbf  ", Some("Kiel Hbf")),
+            ("Kiel<br>Hbf", Some("Kiel Hbf")),
+            ("<b>Kiel</b> Hbf", Some("Kiel Hbf")),
+            ("Kiel<script>alert(1)</script> Hbf", Some("Kiel Hbf")),
+            ("Kiel<SCRIPT type=x>alert(1)</Script> Hbf", Some("Kiel Hbf")),
+            ("Kiel<style>b {}</style> Hbf", Some("Kiel Hbf")),
+            ("Kiel <script/> Hbf", Some("Kiel Hbf")),
+            ("Kiel <script /> Hbf", Some("Kiel Hbf")),
+            ("Kiel <!-- note --> Hbf", Some("Kiel Hbf")),
+            ("a<b", Some("a<b")),
+            ("a<b c", Some("a<b c")),
+            ("a<b> c<d", Some("a c<d")),
+            ("1 < 2 > 0", Some("1 < 2 > 0")),
+            ("Kiel\u{202E}fbH", Some("KielfbH")),
+            ("Kiel\u{0007} Hbf", Some("Kiel Hbf")),
+            ("<b></b>", None),
+            ("<script>Kiel</script>", None),
+            ("\u{200B}", None),
+            ("", None),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(
+                sanitize(text, 100).as_deref(),
+                expected,
+                "sanitized `{}`",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn truncates_long_texts() {
+        let cases = [
+            ("Kiel Hbf", 8, Some("Kiel Hbf")),
+            ("Kiel Hauptbahnhof", 8, Some("Kiel Ha…")),
+            ("Kiel Hauptbahnhof", 6, Some("Kiel…")),
+            ("Kiel<br>Hauptbahnhof", 8, Some("Kiel Ha…")),
+        ];
+        for (text, max_chars, expected) in cases {
+            assert_eq!(
+                sanitize(text, max_chars).as_deref(),
+                expected,
+                "truncated `{}` to {}",
+                text,
+                max_chars
+            );
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::{fmt, str::FromStr};
+
+use serde::de::Error as DeError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// IANA timezone, e.g. `Europe/Berlin`.
+///
+/// Unlike a plain string, unknown zone names (typos like `Europe/Berln`) are
+/// rejected when parsing, instead of silently breaking time computations later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tz(chrono_tz::Tz);
+
+/// Error for timezone names, that are not part of the IANA database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownTimezone(pub String);
+
+impl Tz {
+    pub fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    pub fn inner(&self) -> chrono_tz::Tz {
+        self.0
+    }
+}
+
+impl From<chrono_tz::Tz> for Tz {
+    fn from(value: chrono_tz::Tz) -> Self {
+        Self(value)
+    }
+}
+
+impl FromStr for Tz {
+    type Err = UnknownTimezone;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim()
+            .parse::<chrono_tz::Tz>()
+            .map(Self)
+            .map_err(|_| UnknownTimezone(s.to_owned()))
+    }
+}
+
+impl fmt::Display for Tz {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::Display for UnknownTimezone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown timezone `{}`", self.0)
+    }
+}
+
+impl std::error::Error for UnknownTimezone {}
+
+impl Serialize for Tz {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Tz {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::{value::StrDeserializer, IntoDeserializer};
+
+    use super::*;
+
+    #[test]
+    fn known_timezones_are_parsed() {
+        let cases = [
+            ("Europe/Berlin", Ok(chrono_tz::Europe::Berlin)),
+            (" Europe/Berlin ", Ok(chrono_tz::Europe::Berlin)),
+            ("UTC", Ok(chrono_tz::UTC)),
+            (
+                "Europe/Berln",
+                Err(UnknownTimezone("Europe/Berln".to_owned())),
+            ),
+            ("", Err(UnknownTimezone("".to_owned()))),
+        ];
+        for (index, (name, expected)) in cases.into_iter().enumerate() {
+            assert_eq!(
+                name.parse::<Tz>(),
+                expected.map(Tz::from),
+                "timezone of case {}",
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_timezones_are_not_deserialized() {
+        let deserializer: StrDeserializer<serde::de::value::Error> =
+            "Europe/Berlin".into_deserializer();
+        let tz = Tz::deserialize(deserializer).expect("timezone is deserialized");
+        assert_eq!(tz.name(), "Europe/Berlin");
+
+        let deserializer: StrDeserializer<serde::de::value::Error> =
+            "Europe/Berln".into_deserializer();
+        let error = Tz::deserialize(deserializer).expect_err("timezone is rejected");
+        assert_eq!(error.to_string(), "unknown timezone `Europe/Berln`");
+    }
+}
@@ -9,6 +9,10 @@ pub mod v1;
 
 use crate::{
     common::{route_not_found, METHOD_FILTER_ALL},
+    middleware::{
+        content_negotiation::content_negotiation_middleware,
+        deadline::deadline_middleware,
+    },
     WebState,
 };
 
@@ -24,6 +28,8 @@ pub fn routes(state: WebState) -> Router {
         .route("/ping", get(ping))
         .nest_service("/v1", v1::routes(state))
         .fallback_service(on(METHOD_FILTER_ALL, route_not_found))
+        .layer(axum::middleware::from_fn(deadline_middleware))
+        .layer(axum::middleware::from_fn(content_negotiation_middleware))
 }
 
 async fn ping() -> impl IntoResponse {
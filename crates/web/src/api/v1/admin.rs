@@ -0,0 +1,352 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{OriginalUri, Path, Query, State},
+    http::{Method, StatusCode},
+    routing::{get, on, post},
+    Extension, Router,
+};
+use model::{
+    merge::{MergeLogEntry, MergeStatus, MergeSubject},
+    origin::{Origin, OriginIdError},
+    WithId,
+};
+use public_transport::{
+    database::{InvalidCoordinates, MergeAlreadyDecided, Orphans, UnconfirmedClear},
+    RequestError,
+};
+use serde::Deserialize;
+use utility::{id::Id, let_also::LetAlso};
+
+use crate::{
+    common::{
+        route_not_found, HateoasResult, RouteErrorResponse, VecResponse,
+        METHOD_FILTER_ALL,
+    },
+    hateoas,
+    middleware::{
+        admin_auth::admin_auth_middleware,
+        base_url::{base_url_middleware, BaseUrl},
+    },
+    WebState,
+};
+
+use super::stops::StopResource;
+
+macro_rules! resource {
+    ($($arg:tt)*) => {
+        crate::api::v1::resource!("/admin{}", format_args!($($arg)*))
+    };
+}
+
+pub(crate) fn routes(state: WebState) -> Router {
+    Router::new()
+        .route("/merges", get(get_merges))
+        .route("/merges/:id/confirm", post(confirm_merge))
+        .route("/merges/:id/reject", post(reject_merge))
+        .route("/orphans", get(get_orphans))
+        .route("/invalid-coordinates", get(get_invalid_coordinates))
+        .route(
+            "/invalid-coordinates/clear",
+            post(clear_invalid_coordinates),
+        )
+        .route("/alerts/test", post(test_alerts))
+        .route("/origins/:id/rename", post(rename_origin))
+        .layer(axum::middleware::from_fn_with_state(
+            state.admin_auth.clone(),
+            admin_auth_middleware,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.base_url.clone(),
+            base_url_middleware,
+        ))
+        .with_state(state)
+        .fallback_service(on(METHOD_FILTER_ALL, route_not_found))
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum MergeStatusFilter {
+    AutoAccepted,
+    Pending,
+    Confirmed,
+    Rejected,
+}
+
+impl From<MergeStatusFilter> for MergeStatus {
+    fn from(value: MergeStatusFilter) -> Self {
+        match value {
+            MergeStatusFilter::AutoAccepted => Self::AutoAccepted,
+            MergeStatusFilter::Pending => Self::PendingReview,
+            MergeStatusFilter::Confirmed => Self::Confirmed,
+            MergeStatusFilter::Rejected => Self::Rejected,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct MergesQuery {
+    status: Option<MergeStatusFilter>,
+}
+
+async fn get_merges(
+    OriginalUri(original_uri): OriginalUri,
+    State(WebState { transit_client, .. }): State<WebState>,
+    Query(params): Query<MergesQuery>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<VecResponse<hateoas::Response<MergeLogEntry>>> {
+    transit_client
+        .get_merge_log(params.status.map(MergeStatus::from))
+        .await
+        .map(|entries| {
+            entries
+                .into_iter()
+                .map(|entry| merge_hateoas(entry, base_url.clone()))
+                .collect::<Vec<_>>()
+                .let_owned(|data| VecResponse::non_paginated(data).hateoas().json())
+        })
+        .map_err(|why| {
+            RouteErrorResponse::from(why)
+                .with_method(&Method::GET)
+                .with_uri(original_uri.path())
+        })
+}
+
+async fn confirm_merge(
+    OriginalUri(original_uri): OriginalUri,
+    Path(id): Path<i32>,
+    State(WebState { transit_client, .. }): State<WebState>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<MergeLogEntry> {
+    let id = Id::new(id);
+    transit_client
+        .confirm_merge(&id)
+        .await
+        .map(|entry| merge_hateoas(WithId::new(id, entry), base_url).json())
+        .map_err(|why| decision_error(why, original_uri.path()))
+}
+
+async fn reject_merge(
+    OriginalUri(original_uri): OriginalUri,
+    Path(id): Path<i32>,
+    State(WebState { transit_client, .. }): State<WebState>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<MergeLogEntry> {
+    let id = Id::new(id);
+    transit_client
+        .reject_merge(&id)
+        .await
+        .map(|entry| merge_hateoas(WithId::new(id, entry), base_url).json())
+        .map_err(|why| decision_error(why, original_uri.path()))
+}
+
+async fn get_orphans(
+    OriginalUri(original_uri): OriginalUri,
+    State(WebState { transit_client, .. }): State<WebState>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<Vec<Orphans>> {
+    transit_client
+        .get_orphans()
+        .await
+        .map(|orphans| {
+            hateoas::Response::builder(orphans, base_url)
+                .link("self", resource!("/orphans"))
+                .build()
+                .json()
+        })
+        .map_err(|why| {
+            RouteErrorResponse::from(why)
+                .with_method(&Method::GET)
+                .with_uri(original_uri.path())
+        })
+}
+
+async fn get_invalid_coordinates(
+    OriginalUri(original_uri): OriginalUri,
+    State(WebState { transit_client, .. }): State<WebState>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<Vec<InvalidCoordinates>> {
+    transit_client
+        .get_invalid_coordinates()
+        .await
+        .map(|invalid| {
+            let count = invalid.iter().map(|invalid| invalid.count).sum::<i64>();
+            hateoas::Response::builder(invalid, base_url)
+                .link("self", resource!("/invalid-coordinates"))
+                .link(
+                    "clear",
+                    resource!("/invalid-coordinates/clear?expected={}", count),
+                )
+                .build()
+                .json()
+        })
+        .map_err(|why| {
+            RouteErrorResponse::from(why)
+                .with_method(&Method::GET)
+                .with_uri(original_uri.path())
+        })
+}
+
+#[derive(Deserialize)]
+struct ClearInvalidCoordinatesQuery {
+    /// Number of rows to be cleared, as counted by `GET /invalid-coordinates`.
+    expected: u64,
+}
+
+/// One-off cleanup of coordinates stored before they were validated on ingestion.
+/// The rows to be cleared are listed by `GET /invalid-coordinates` first, whose
+/// total count has to be confirmed. Fails with 409 and clears nothing, if the
+/// count changed in the meantime.
+async fn clear_invalid_coordinates(
+    OriginalUri(original_uri): OriginalUri,
+    State(WebState { transit_client, .. }): State<WebState>,
+    Query(params): Query<ClearInvalidCoordinatesQuery>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<u64> {
+    transit_client
+        .clear_invalid_coordinates(params.expected)
+        .await
+        .map(|count| {
+            hateoas::Response::builder(count, base_url)
+                .link("check", resource!("/invalid-coordinates"))
+                .build()
+                .json()
+        })
+        .map_err(|why| {
+            let unconfirmed = match &why {
+                RequestError::Other(other) => {
+                    other.downcast_ref::<UnconfirmedClear>()
+                }
+                _ => None,
+            };
+            match unconfirmed {
+                Some(error) => RouteErrorResponse::new(StatusCode::CONFLICT)
+                    .with_message(error.to_string()),
+                None => RouteErrorResponse::from(why),
+            }
+            .with_method(&Method::POST)
+            .with_uri(original_uri.path())
+        })
+}
+
+/// Sends a test notification to all webhooks, e.g. to check their configuration.
+/// Returns the number of webhooks notified.
+async fn test_alerts(
+    OriginalUri(original_uri): OriginalUri,
+    State(WebState { notifier, .. }): State<WebState>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<usize> {
+    let error = |status| {
+        RouteErrorResponse::new(status)
+            .with_method(&Method::POST)
+            .with_uri(original_uri.path())
+    };
+    let Some(notifier) = notifier else {
+        return Err(
+            error(StatusCode::NOT_FOUND).with_message("Alerting is not configured.")
+        );
+    };
+    notifier.send_test().await.map_err(|errors| {
+        error(StatusCode::BAD_GATEWAY).with_message(format!(
+            "Webhooks did not accept the notification: {}",
+            errors.join(", ")
+        ))
+    })?;
+    hateoas::Response::builder(notifier.config().webhooks.len(), base_url)
+        .link("self", resource!("/alerts/test"))
+        .build()
+        .json()
+        .let_owned(Ok)
+}
+
+#[derive(Deserialize)]
+struct RenameOriginQuery {
+    name: String,
+}
+
+/// Renames the origin, moving all of its data to the id derived from the new
+/// name. Fails with 400, if the id would be invalid, and with 409, if the name
+/// collides with another origin.
+async fn rename_origin(
+    OriginalUri(original_uri): OriginalUri,
+    Path(id): Path<String>,
+    State(WebState { transit_client, .. }): State<WebState>,
+    Query(params): Query<RenameOriginQuery>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<WithId<Origin>> {
+    transit_client
+        .rename_origin(&Id::new(id), &params.name)
+        .await
+        .map(|origin| {
+            let rename = resource!("/origins/{}/rename", origin.id);
+            hateoas::Response::builder(origin, base_url)
+                .link("rename", rename)
+                .build()
+                .json()
+        })
+        .map_err(|why| {
+            let origin_id_error = match &why {
+                RequestError::Other(other) => other.downcast_ref::<OriginIdError>(),
+                _ => None,
+            };
+            match origin_id_error {
+                Some(error @ OriginIdError::Invalid(_)) => {
+                    RouteErrorResponse::new(StatusCode::BAD_REQUEST)
+                        .with_message(error.to_string())
+                }
+                Some(error @ OriginIdError::Collision { .. }) => {
+                    RouteErrorResponse::new(StatusCode::CONFLICT)
+                        .with_message(error.to_string())
+                }
+                None => RouteErrorResponse::from(why),
+            }
+            .with_method(&Method::POST)
+            .with_uri(original_uri.path())
+        })
+}
+
+/// Only merges awaiting review can be confirmed or rejected, others conflict.
+fn decision_error(why: RequestError, uri: &str) -> RouteErrorResponse {
+    let decided = match &why {
+        RequestError::Other(other) => other.is::<MergeAlreadyDecided>(),
+        _ => false,
+    };
+    if decided {
+        RouteErrorResponse::new(StatusCode::CONFLICT)
+            .with_message(MergeAlreadyDecided.to_string())
+    } else {
+        RouteErrorResponse::from(why)
+    }
+    .with_method(&Method::POST)
+    .with_uri(uri)
+}
+
+fn merge_hateoas(
+    entry: WithId<MergeLogEntry>,
+    base_url: Arc<BaseUrl>,
+) -> hateoas::Response<MergeLogEntry> {
+    let id = entry.id.raw();
+    let is_pending = entry.content.status == MergeStatus::PendingReview;
+    let (subject, candidate) = match entry.content.subject {
+        MergeSubject::Stop => (
+            StopResource {
+                id: Id::new(entry.content.subject_id.clone()),
+            },
+            StopResource {
+                id: Id::new(entry.content.candidate_id.clone()),
+            },
+        ),
+    };
+    hateoas::Response::builder(entry.content, base_url)
+        .link_to("subject", &subject)
+        .link_to("candidate", &candidate)
+        .link_option(
+            "confirm",
+            is_pending.then(|| resource!("/merges/{}/confirm", id)),
+        )
+        .link_option(
+            "reject",
+            is_pending.then(|| resource!("/merges/{}/reject", id)),
+        )
+        .build()
+}
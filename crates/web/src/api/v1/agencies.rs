@@ -1,37 +1,132 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::{OriginalUri, Path, State},
-    http::Method,
+    extract::{OriginalUri, Path, Query, State},
+    http::{header::CACHE_CONTROL, Method},
+    response::{IntoResponse, Response},
     routing::{get, on},
     Extension, Router,
 };
-use model::{agency::Agency, WithId};
+use model::{agency::Agency, stop::Stop, WithId};
+use public_transport::client::AgencyCoverage;
+use serde::{Deserialize, Serialize};
 use utility::{id::Id, let_also::LetAlso};
 
 use crate::{
     common::{
-        route_not_found, schema, HateoasResult, RouteErrorResponse, VecResponse,
-        METHOD_FILTER_ALL,
+        cursor::{Cursor, PageParams},
+        route_not_found, schema, HateoasResult, RouteErrorResponse, RouteResult,
+        VecResponse, METHOD_FILTER_ALL,
     },
-    hateoas,
+    hateoas::{self, PathParams, Resource},
     middleware::base_url::{base_url_middleware, BaseUrl},
     WebState,
 };
 
+use super::{stops::stop_hateoas, trips::Geometry};
+
 macro_rules! resource {
     ($($arg:tt)*) => {
         crate::api::v1::resource!("/agencies{}", format_args!($($arg)*))
     };
 }
-pub(crate) use resource;
+
+/// A single agency.
+pub(crate) struct AgencyResource {
+    pub id: Id<Agency>,
+}
+
+impl Resource for AgencyResource {
+    const ROUTE: &'static str = "/:id";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn path_params(&self) -> impl PathParams {
+        [self.id.raw()]
+    }
+}
+
+/// A page of the stops served by an agency.
+pub(crate) struct AgencyStopsResource {
+    pub id: Id<Agency>,
+    pub include_secondary: bool,
+    pub after: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl Resource for AgencyStopsResource {
+    const ROUTE: &'static str = "/:id/stops";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn path_params(&self) -> impl PathParams {
+        [self.id.raw()]
+    }
+
+    fn query_params(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            include_secondary_param(self.include_secondary),
+            ("after", self.after.clone()),
+            ("limit", self.limit.map(|limit| limit.to_string())),
+        ]
+    }
+}
+
+/// The stops served by an agency and their convex hull as GeoJSON.
+pub(crate) struct AgencyCoverageResource {
+    pub id: Id<Agency>,
+    pub include_secondary: bool,
+}
+
+impl Resource for AgencyCoverageResource {
+    const ROUTE: &'static str = "/:id/coverage.geojson";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn path_params(&self) -> impl PathParams {
+        [self.id.raw()]
+    }
+
+    fn query_params(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![include_secondary_param(self.include_secondary)]
+    }
+}
+
+/// Query parameters of the agency-scoped collections.
+#[derive(Debug, Clone, Deserialize)]
+struct AgencyScopeParams {
+    /// Includes lines the agency is a secondary agency of, e.g. codeshares
+    /// operated by another agency.
+    #[serde(default)]
+    include_secondary: bool,
+}
+
+fn include_secondary_param(
+    include_secondary: bool,
+) -> (&'static str, Option<String>) {
+    (
+        "include_secondary",
+        include_secondary.then(|| "true".to_owned()),
+    )
+}
 
 pub(crate) fn routes(state: WebState) -> Router {
     Router::new()
         .route("/schema", get(schema::<Agency>))
-        .route("/:id", get(get_agency))
+        .route(AgencyResource::ROUTE, get(get_agency))
+        .route(AgencyStopsResource::ROUTE, get(get_agency_stops))
+        .route(AgencyCoverageResource::ROUTE, get(get_agency_coverage))
         .route("/", get(get_agencies))
-        .layer(axum::middleware::from_fn(base_url_middleware))
+        .layer(axum::middleware::from_fn_with_state(
+            state.base_url.clone(),
+            base_url_middleware,
+        ))
         .with_state(state)
         .fallback_service(on(METHOD_FILTER_ALL, route_not_found))
 }
@@ -77,11 +172,213 @@ async fn get_agency(
         })
 }
 
+/// Stops of any line of the agency, ordered like all stops.
+async fn get_agency_stops(
+    OriginalUri(original_uri): OriginalUri,
+    Path(id): Path<String>,
+    State(WebState { transit_client, .. }): State<WebState>,
+    Query(params): Query<PageParams>,
+    Query(scope): Query<AgencyScopeParams>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<VecResponse<hateoas::Response<Stop>>> {
+    let map_err = |why| {
+        RouteErrorResponse::from(why)
+            .with_method(&Method::GET)
+            .with_uri(original_uri.path())
+    };
+    let origins = transit_client.get_origin_ids().await?;
+    let after = params
+        .cursor::<Stop>(&Method::GET, original_uri.path())?
+        .map(Cursor::into_inner);
+    let limit = params.limit();
+    // unknown agencies are not found, rather than serving no stops.
+    let agency = transit_client
+        .get_agency(Id::new(id), origins.clone())
+        .await
+        .map_err(map_err)?;
+    let (stops, next) = transit_client
+        .get_agency_stops_page(
+            &agency.id,
+            scope.include_secondary,
+            after,
+            limit,
+            &origins,
+        )
+        .await
+        .map_err(map_err)?;
+    stops
+        .into_iter()
+        .map(|stop| stop_hateoas(stop, base_url.clone()))
+        .collect::<Vec<_>>()
+        .let_owned(|data| {
+            hateoas::Response::builder(VecResponse::non_paginated(data), base_url)
+                .link_to(
+                    "agency",
+                    &AgencyResource {
+                        id: agency.id.clone(),
+                    },
+                )
+                .link_to_option(
+                    "next",
+                    next.map(|next| AgencyStopsResource {
+                        id: agency.id,
+                        include_secondary: scope.include_secondary,
+                        after: Some(Cursor::from(next).encode()),
+                        limit: Some(limit),
+                    }),
+                )
+                .build()
+                .json()
+        })
+        .let_owned(Ok)
+}
+
+/// Coverage changes with imports at most.
+const COVERAGE_CACHE_CONTROL: &str = "public, max-age=3600";
+
+/// One point feature per stop and a polygon feature of their convex hull, if the
+/// stops span an area.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename = "FeatureCollection")]
+struct AgencyCoverageDto {
+    features: Vec<AgencyCoverageFeature>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename = "Feature")]
+struct AgencyCoverageFeature {
+    geometry: Geometry,
+    properties: AgencyCoverageProperties,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum AgencyCoverageProperties {
+    Stop {
+        id: Id<Stop>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Hull { stop_count: usize },
+}
+
+impl AgencyCoverageDto {
+    fn new(coverage: AgencyCoverage) -> Self {
+        let stop_count = coverage.stops.len();
+        let hull = (coverage.hull.len() >= 3).then(|| {
+            // GeoJSON rings end with their first position.
+            let ring = coverage
+                .hull
+                .iter()
+                .chain(coverage.hull.first())
+                .map(|(latitude, longitude)| [*longitude, *latitude])
+                .collect();
+            AgencyCoverageFeature {
+                geometry: Geometry::Polygon {
+                    coordinates: vec![ring],
+                },
+                properties: AgencyCoverageProperties::Hull { stop_count },
+            }
+        });
+        let features = coverage
+            .stops
+            .into_iter()
+            .filter_map(|stop| {
+                let location = stop.content.location?;
+                Some(AgencyCoverageFeature {
+                    geometry: Geometry::Point {
+                        coordinates: [location.longitude, location.latitude],
+                    },
+                    properties: AgencyCoverageProperties::Stop {
+                        id: stop.id,
+                        name: stop.content.name,
+                    },
+                })
+            })
+            .chain(hull)
+            .collect();
+        Self { features }
+    }
+}
+
+async fn get_agency_coverage(
+    OriginalUri(original_uri): OriginalUri,
+    Path(id): Path<String>,
+    State(WebState { transit_client, .. }): State<WebState>,
+    Query(scope): Query<AgencyScopeParams>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> RouteResult<Response> {
+    let map_err = |why| {
+        RouteErrorResponse::from(why)
+            .with_method(&Method::GET)
+            .with_uri(original_uri.path())
+    };
+    let origins = transit_client.get_origin_ids().await?;
+    let agency = transit_client
+        .get_agency(Id::new(id), origins.clone())
+        .await
+        .map_err(map_err)?;
+    let coverage = transit_client
+        .get_agency_coverage(&agency.id, scope.include_secondary, &origins)
+        .await
+        .map_err(map_err)?;
+    hateoas::Response::builder(AgencyCoverageDto::new(coverage), base_url)
+        .link_to(
+            "self",
+            &AgencyCoverageResource {
+                id: agency.id.clone(),
+                include_secondary: scope.include_secondary,
+            },
+        )
+        .link_to(
+            "agency",
+            &AgencyResource {
+                id: agency.id.clone(),
+            },
+        )
+        .link_to(
+            "stops",
+            &AgencyStopsResource {
+                id: agency.id,
+                include_secondary: scope.include_secondary,
+                after: None,
+                limit: None,
+            },
+        )
+        .build()
+        .json()
+        .let_owned(|coverage| {
+            Ok(([(CACHE_CONTROL, COVERAGE_CACHE_CONTROL)], coverage).into_response())
+        })
+}
+
 pub(crate) fn agency_hateoas(
     agency: WithId<Agency>,
     base_url: Arc<BaseUrl>,
 ) -> hateoas::Response<Agency> {
     hateoas::Response::builder(agency.content, base_url)
-        .link("self", resource!("/{}", agency.id.raw()))
+        .link_to(
+            "self",
+            &AgencyResource {
+                id: agency.id.clone(),
+            },
+        )
+        .link_to(
+            "stops",
+            &AgencyStopsResource {
+                id: agency.id.clone(),
+                include_secondary: false,
+                after: None,
+                limit: None,
+            },
+        )
+        .link_to(
+            "coverage",
+            &AgencyCoverageResource {
+                id: agency.id,
+                include_secondary: false,
+            },
+        )
         .build()
 }
@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{OriginalUri, Query, State},
+    http::{Method, StatusCode},
+    routing::{get, on},
+    Extension, Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use model::change::{Change, ChangePosition};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{
+        cursor::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE},
+        route_not_found, HateoasResult, RouteErrorResponse, METHOD_FILTER_ALL,
+    },
+    hateoas::{self, Resource},
+    middleware::base_url::{base_url_middleware, BaseUrl},
+    WebState,
+};
+
+macro_rules! resource {
+    ($($arg:tt)*) => {
+        crate::api::v1::resource!("/changes{}", format_args!($($arg)*))
+    };
+}
+
+/// Changes of stops, lines and trips after the `since` cursor, for incremental
+/// sync of mirrors. Without a cursor, the feed starts at the beginning.
+pub(crate) struct ChangesResource {
+    pub since: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl Resource for ChangesResource {
+    const ROUTE: &'static str = "/";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn query_params(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("since", self.since.clone()),
+            ("limit", self.limit.map(|limit| limit.to_string())),
+        ]
+    }
+}
+
+pub(crate) fn routes(state: WebState) -> Router {
+    Router::new()
+        .route(ChangesResource::ROUTE, get(get_changes))
+        .layer(axum::middleware::from_fn_with_state(
+            state.base_url.clone(),
+            base_url_middleware,
+        ))
+        .with_state(state)
+        .fallback_service(on(METHOD_FILTER_ALL, route_not_found))
+}
+
+/// Encodes the position as an opaque, url safe cursor.
+fn encode_cursor(position: ChangePosition) -> String {
+    let payload = serde_json::json!(["change", position.sequence]);
+    URL_SAFE_NO_PAD.encode(payload.to_string())
+}
+
+fn decode_cursor(encoded: &str) -> Option<ChangePosition> {
+    let bytes = URL_SAFE_NO_PAD.decode(encoded).ok()?;
+    let (kind, sequence): (String, i64) = serde_json::from_slice(&bytes).ok()?;
+    (kind == "change").then_some(ChangePosition { sequence })
+}
+
+#[derive(Deserialize)]
+struct ChangesQuery {
+    since: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChangesDto {
+    changes: Vec<Change>,
+    /// Cursor to pass as `since` to continue after the returned changes. `None`,
+    /// if there are no changes at all yet.
+    cursor: Option<String>,
+    /// Whether more changes are available right away. Otherwise, the cursor is
+    /// polled again later.
+    has_more: bool,
+}
+
+/// A page of changes, oldest first. Entities are identified by their id and
+/// origin. Changes older than the retention of the change log are compacted
+/// to the latest change of each entity, so that a mirror, which fell further
+/// behind, only sees the latest operation of an entity. Deletions are dropped
+/// then, so that cursors before them are rejected with `410 Gone`, and the
+/// mirror syncs from scratch.
+async fn get_changes(
+    OriginalUri(original_uri): OriginalUri,
+    State(WebState { transit_client, .. }): State<WebState>,
+    Query(params): Query<ChangesQuery>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<ChangesDto> {
+    let error = |status| {
+        RouteErrorResponse::new(status)
+            .with_method(&Method::GET)
+            .with_uri(original_uri.path())
+    };
+    let since = params
+        .since
+        .as_deref()
+        .map(|encoded| {
+            decode_cursor(encoded).ok_or_else(|| {
+                error(StatusCode::BAD_REQUEST).with_message("invalid cursor.")
+            })
+        })
+        .transpose()?;
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let (changes, next) = transit_client
+        .get_changes(since, limit)
+        .await
+        .map_err(|why| {
+            RouteErrorResponse::from(why)
+                .with_method(&Method::GET)
+                .with_uri(original_uri.path())
+        })?
+        .ok_or_else(|| {
+            error(StatusCode::GONE).with_message(
+                "deletions after the cursor were compacted, sync from scratch \
+                 without `since`.",
+            )
+        })?;
+    let has_more = changes.len() == limit;
+    let cursor = next.map(encode_cursor);
+    Ok(hateoas::Response::builder(
+        ChangesDto {
+            changes,
+            cursor: cursor.clone(),
+            has_more,
+        },
+        base_url,
+    )
+    .link_to(
+        "self",
+        &ChangesResource {
+            since: params.since,
+            limit: Some(limit),
+        },
+    )
+    .link_to_option(
+        "next",
+        has_more.then_some(ChangesResource {
+            since: cursor,
+            limit: Some(limit),
+        }),
+    )
+    .build()
+    .json())
+}
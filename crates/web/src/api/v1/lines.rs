@@ -2,37 +2,104 @@ use std::sync::Arc;
 
 use axum::{
     extract::{OriginalUri, Path, Query, State},
-    http::Method,
+    http::{header::CACHE_CONTROL, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
     routing::{get, on},
     Extension, Router,
 };
-use model::{line::Line, WithId};
-use serde::Deserialize;
-use utility::{id::Id, let_also::LetAlso};
+use chrono::NaiveDate;
+use model::{
+    line::{Line, LineAgency, LineType, ServiceSpan},
+    shape::LineShape,
+    stop::Stop,
+    trip::TripDirection,
+    WithId,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use utility::{geo, id::Id, let_also::LetAlso};
 
 use crate::{
     common::{
-        route_not_found, schema, HateoasResult, RouteErrorResponse, VecResponse,
-        METHOD_FILTER_ALL,
+        etag::EntityTag,
+        query::{ValidQuery, Validate},
+        route_not_found, schema, HateoasResult, RouteErrorResponse, RouteResult,
+        VecResponse, METHOD_FILTER_ALL,
     },
-    hateoas,
+    hateoas::{self, PathParams, Resource},
     middleware::base_url::{base_url_middleware, BaseUrl},
     WebState,
 };
 
+use super::{agencies::AgencyResource, trips::Geometry};
+
 macro_rules! resource {
     ($($arg:tt)*) => {
         crate::api::v1::resource!("/lines{}", format_args!($($arg)*))
     };
 }
-pub(crate) use resource;
+
+/// A single line.
+pub(crate) struct LineResource {
+    pub id: Id<Line>,
+}
+
+impl Resource for LineResource {
+    const ROUTE: &'static str = "/:id";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn path_params(&self) -> impl PathParams {
+        [self.id.raw()]
+    }
+}
+
+/// The representative shapes of a line as GeoJSON.
+pub(crate) struct LineShapeResource {
+    pub id: Id<Line>,
+}
+
+impl Resource for LineShapeResource {
+    const ROUTE: &'static str = "/:id/shape";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn path_params(&self) -> impl PathParams {
+        [self.id.raw()]
+    }
+}
+
+/// All lines, or those calling at a stop.
+pub(crate) struct LinesResource {
+    pub stop: Option<Id<Stop>>,
+}
+
+impl Resource for LinesResource {
+    const ROUTE: &'static str = "/";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn query_params(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![("stop", self.stop.as_ref().map(|id| id.raw()))]
+    }
+}
 
 pub(crate) fn routes(state: WebState) -> Router {
     Router::new()
         .route("/schema", get(schema::<Line>))
-        .route("/:id", get(get_line))
-        .route("/", get(get_lines))
-        .layer(axum::middleware::from_fn(base_url_middleware))
+        .route(LineResource::ROUTE, get(get_line))
+        .route(LinesResource::ROUTE, get(get_lines))
+        .route(LineShapeResource::ROUTE, get(get_line_shape))
+        .layer(axum::middleware::from_fn_with_state(
+            state.base_url.clone(),
+            base_url_middleware,
+        ))
         .with_state(state)
         .fallback_service(on(METHOD_FILTER_ALL, route_not_found))
 }
@@ -40,6 +107,8 @@ pub(crate) fn routes(state: WebState) -> Router {
 #[derive(Deserialize)]
 struct LinesQuery {
     stop: Option<String>,
+    /// Only lines of this mode. Accepts both `rail` and the finer rail kinds.
+    kind: Option<LineType>,
 }
 
 async fn get_lines(
@@ -47,7 +116,7 @@ async fn get_lines(
     State(WebState { transit_client, .. }): State<WebState>,
     Query(params): Query<LinesQuery>,
     Extension(base_url): Extension<Arc<BaseUrl>>,
-) -> HateoasResult<VecResponse<hateoas::Response<Line>>> {
+) -> HateoasResult<VecResponse<hateoas::Response<LineDto>>> {
     let origins = transit_client.get_origin_ids().await?;
     // get at stop if query stops
     if let Some(stop) = params.stop {
@@ -61,6 +130,11 @@ async fn get_lines(
     .map(|lines| {
         lines
             .into_iter()
+            .filter(|line| {
+                params
+                    .kind
+                    .is_none_or(|kind| line.content.kind.matches(&kind))
+            })
             .map(|line| line_hateoas(line, base_url.clone()))
             .collect::<Vec<_>>()
             .let_owned(|data| VecResponse::non_paginated(data).hateoas().json())
@@ -72,34 +146,266 @@ async fn get_lines(
     })
 }
 
+#[derive(Deserialize)]
+struct LineQuery {
+    /// Service day of the service span. Defaults to today.
+    date: Option<NaiveDate>,
+}
+
+impl Validate for LineQuery {}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LineDetailDto {
+    #[serde(flatten)]
+    line: LineDto,
+    service_span: Option<ServiceSpan>,
+}
+
 async fn get_line(
+    OriginalUri(original_uri): OriginalUri,
+    Path(id): Path<String>,
+    State(WebState {
+        transit_client,
+        clock,
+        ..
+    }): State<WebState>,
+    ValidQuery(params): ValidQuery<LineQuery>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+    headers: HeaderMap,
+) -> RouteResult<Response> {
+    let map_err = |why| {
+        RouteErrorResponse::from(why)
+            .with_method(&Method::GET)
+            .with_uri(original_uri.path())
+    };
+    let origins = transit_client.get_origin_ids().await?;
+    let line = transit_client
+        .get_line(Id::new(id), origins)
+        .await
+        .map_err(map_err)?;
+    let service_day = params.date.unwrap_or_else(|| clock.now().date_naive());
+    let service_span = transit_client
+        .get_line_service_span(&line.id, service_day)
+        .await
+        .map_err(map_err)?;
+    let etag = EntityTag::new(line.content.updated_at, &service_span);
+    let builder = hateoas::Response::builder(
+        LineDetailDto {
+            line: v1_line(line.content.clone()),
+            service_span,
+        },
+        base_url,
+    )
+    .link_to(
+        "self",
+        &LineResource {
+            id: line.id.clone(),
+        },
+    );
+    link_agencies(builder, &line.content)
+        .link_to("shape", &LineShapeResource { id: line.id })
+        .build()
+        .json()
+        .let_owned(|line| Ok(etag.respond(&headers, line)))
+}
+
+/// Shapes change with imports at most.
+const SHAPE_CACHE_CONTROL: &str = "public, max-age=3600";
+
+#[derive(Deserialize)]
+struct LineShapeQuery {
+    /// Points of the line closer than this many meters to the simplified line are
+    /// omitted. Not simplified, if not specified.
+    tolerance: Option<f64>,
+}
+
+/// One feature per direction of the line.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename = "FeatureCollection")]
+struct LineShapeDto {
+    features: Vec<LineShapeFeature>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename = "Feature")]
+struct LineShapeFeature {
+    geometry: Geometry,
+    properties: LineShapeProperties,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LineShapeProperties {
+    /// Not set, if the feed does not separate the trips of the line by direction.
+    direction: Option<TripDirection>,
+    shape_id: i32,
+}
+
+impl LineShapeDto {
+    fn new(shapes: Vec<LineShape>, tolerance_km: f64) -> Self {
+        let features = shapes
+            .into_iter()
+            .map(|line_shape| {
+                let points = line_shape
+                    .shape
+                    .points
+                    .iter()
+                    .map(|point| (point.latitude, point.longitude))
+                    .collect::<Vec<_>>();
+                LineShapeFeature {
+                    geometry: Geometry::LineString {
+                        coordinates: geo::simplify(&points, tolerance_km)
+                            .into_iter()
+                            .map(|(latitude, longitude)| [longitude, latitude])
+                            .collect(),
+                    },
+                    properties: LineShapeProperties {
+                        direction: line_shape.direction,
+                        shape_id: line_shape.shape_id.raw(),
+                    },
+                }
+            })
+            .collect();
+        Self { features }
+    }
+}
+
+/// Responds with `204 No Content`, if no trip of the line has a shape.
+async fn get_line_shape(
     OriginalUri(original_uri): OriginalUri,
     Path(id): Path<String>,
     State(WebState { transit_client, .. }): State<WebState>,
+    Query(params): Query<LineShapeQuery>,
     Extension(base_url): Extension<Arc<BaseUrl>>,
-) -> HateoasResult<Line> {
+) -> RouteResult<Response> {
+    let map_err = |why| {
+        RouteErrorResponse::from(why)
+            .with_method(&Method::GET)
+            .with_uri(original_uri.path())
+    };
     let origins = transit_client.get_origin_ids().await?;
-    transit_client
+    let line = transit_client
         .get_line(Id::new(id), origins)
         .await
-        .map(|line| line_hateoas(line, base_url).json())
-        .map_err(|why| {
-            RouteErrorResponse::from(why)
-                .with_method(&Method::GET)
-                .with_uri(original_uri.path())
+        .map_err(map_err)?;
+    let shapes = transit_client
+        .get_line_shapes(&line.id)
+        .await
+        .map_err(map_err)?;
+    if shapes.is_empty() {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+    let tolerance_km = params.tolerance.unwrap_or_default() / 1000.0;
+    hateoas::Response::builder(LineShapeDto::new(shapes, tolerance_km), base_url)
+        .link_to(
+            "self",
+            &LineShapeResource {
+                id: line.id.clone(),
+            },
+        )
+        .link_to("line", &LineResource { id: line.id })
+        .build()
+        .json()
+        .let_owned(|shape| {
+            Ok(([(CACHE_CONTROL, SHAPE_CACHE_CONTROL)], shape).into_response())
         })
 }
 
+/// A line along with all agencies attributed to it, the primary one first.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LineDto {
+    #[serde(flatten)]
+    pub line: Line,
+    pub agencies: Vec<LineAgency>,
+}
+
+/// v1 predates the finer rail kinds and keeps serializing them as rail.
+fn v1_line(line: Line) -> LineDto {
+    LineDto {
+        agencies: line.agencies(),
+        line: Line {
+            kind: line.kind.legacy(),
+            ..line
+        },
+    }
+}
+
+/// Links the primary agency as `agency` and the others as `secondaryAgency`.
+fn link_agencies<T>(
+    builder: hateoas::ResponseBuilder<T>,
+    line: &Line,
+) -> hateoas::ResponseBuilder<T> {
+    line.secondary_agency_ids.iter().fold(
+        builder.link_to_option(
+            "agency",
+            line.agency_id.clone().map(|id| AgencyResource { id }),
+        ),
+        |builder, id| {
+            builder.link_to("secondaryAgency", &AgencyResource { id: id.clone() })
+        },
+    )
+}
+
 pub(crate) fn line_hateoas(
     line: WithId<Line>,
     base_url: Arc<BaseUrl>,
-) -> hateoas::Response<Line> {
-    let agency_id = line.content.agency_id.clone();
-    hateoas::Response::builder(line.content, base_url)
-        .link("self", resource!("/{}", line.id.raw()))
-        .link_option(
-            "agency",
-            agency_id.map(|id| super::agencies::resource!("/{}", id)),
+) -> hateoas::Response<LineDto> {
+    let builder = hateoas::Response::builder(v1_line(line.content.clone()), base_url)
+        .link_to("self", &LineResource { id: line.id });
+    link_agencies(builder, &line.content).build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(kind: LineType) -> WithId<Line> {
+        WithId::new(
+            Id::new("line".to_owned()),
+            Line {
+                name: Some("RE83".to_owned()),
+                kind,
+                agency_id: None,
+                secondary_agency_ids: vec![],
+                updated_at: None,
+            },
         )
-        .build()
+    }
+
+    #[test]
+    fn finer_rail_kinds_are_served_as_rail() {
+        let base_url = Arc::new(BaseUrl::parse("http://localhost").unwrap());
+        let cases = [
+            (LineType::Rail, "rail"),
+            (LineType::LongDistanceRail, "rail"),
+            (LineType::RegionalRail, "rail"),
+            (LineType::SuburbanRail, "rail"),
+            (LineType::Bus, "bus"),
+        ];
+        for (index, (kind, expected)) in cases.into_iter().enumerate() {
+            let dto =
+                serde_json::to_value(line_hateoas(line(kind), base_url.clone()))
+                    .expect("line is serialized");
+            assert_eq!(dto["kind"], expected, "kind of case {}", index);
+        }
+    }
+
+    #[test]
+    fn kind_filters_accept_both_granularities() {
+        let cases = [
+            ("kind=rail", Some(LineType::Rail)),
+            ("kind=regionalRail", Some(LineType::RegionalRail)),
+            ("kind=suburbanRail", Some(LineType::SuburbanRail)),
+            ("stop=a", None),
+        ];
+        for (index, (query, expected)) in cases.into_iter().enumerate() {
+            let query: LinesQuery =
+                serde_urlencoded::from_str(query).expect("query is valid");
+            assert_eq!(query.kind, expected, "kind of case {}", index);
+        }
+        assert!(serde_urlencoded::from_str::<LinesQuery>("kind=train").is_err());
+    }
 }
@@ -1,36 +1,54 @@
-use agencies::agency_hateoas;
 use itertools::Itertools;
-use lines::line_hateoas;
+use lines::{line_hateoas, LineDto};
 use schemars::JsonSchema;
-use std::sync::Arc;
-use stops::stop_with_distance_hateoas;
+use std::{collections::HashSet, sync::Arc};
+use stops::{served_only_on_request, stop_with_distance_hateoas, ServedStopDto};
 
 use crate::{
     common::{
-        route_not_found, route_not_implemented, schema_no_example, HateoasResult,
-        RouteErrorResponse, METHOD_FILTER_ALL,
+        locale::requested_labels,
+        query::{self, ValidQuery, Validate},
+        route_not_found, route_not_implemented, schema_no_example,
+        single_flight::{SingleFlight, SingleFlightStats},
+        HateoasResult, RouteErrorResponse, RouteResult, METHOD_FILTER_ALL,
     },
     hateoas,
     middleware::base_url::{base_url_middleware, BaseUrl},
-    WebState,
+    WebDatabase, WebState,
 };
 use axum::{
-    extract::{OriginalUri, Query, State},
-    http::Method,
+    extract::{OriginalUri, State},
+    http::{HeaderMap, Method},
     routing::{get, on},
     Extension, Router,
 };
 use model::{
-    line::Line, shared_mobility::SharedMobilityStation, stop::Stop,
-    trip_instance::TripInstance, DateTimeRange, WithDistance,
+    calendar::ServiceTag,
+    line::Line,
+    locale::Locale,
+    origin::Origin,
+    shared_mobility::SharedMobilityStation,
+    stop::Stop,
+    trip_instance::{TripInstance, WindowMode},
+    DateTimeRange, WithDistance, WithId,
 };
+use public_transport::client::{Client, TripInstantiationOptions};
+use realtime::RealtimeNearbyResource;
 use std::time::Instant;
-use trips::{stop_time_hateoas, trip_hateoas, TripInstanceDto};
-use utility::serde::date_time;
+use trips::{trip_hateoas, TripInstanceDto};
+use utility::{
+    id::Id,
+    serde::{comma_separated, date_time},
+};
 
+mod admin;
 mod agencies;
+mod changes;
 mod lines;
 mod realtime;
+mod search;
+mod services;
+mod status;
 mod stops;
 mod trips;
 
@@ -44,16 +62,26 @@ pub(crate) use resource;
 use serde::{Deserialize, Serialize};
 
 pub(crate) fn routes(state: WebState) -> Router {
+    let nearby_flights = Arc::new(NearbyFlights::new(&state.single_flight));
     Router::new()
         .route("/", get(route_not_implemented))
         .route("/nearby", get(nearby))
         .route("/nearby/schema", get(schema_no_example::<NearbyDto>))
+        .nest_service("/admin", admin::routes(state.clone()))
         .nest_service("/agencies", agencies::routes(state.clone()))
+        .nest_service("/changes", changes::routes(state.clone()))
         .nest_service("/lines", lines::routes(state.clone()))
         .nest_service("/trips", trips::routes(state.clone()))
         .nest_service("/stops", stops::routes(state.clone()))
         .nest_service("/realtime", realtime::routes(state.clone()))
-        .layer(axum::middleware::from_fn(base_url_middleware))
+        .nest_service("/search", search::routes(state.clone()))
+        .nest_service("/services", services::routes(state.clone()))
+        .nest_service("/status", status::routes(state.clone()))
+        .layer(Extension(nearby_flights))
+        .layer(axum::middleware::from_fn_with_state(
+            state.base_url.clone(),
+            base_url_middleware,
+        ))
         .with_state(state)
         .fallback_service(on(METHOD_FILTER_ALL, route_not_found))
 }
@@ -66,12 +94,41 @@ struct NearbyDto {
     longitude: f64,
     start: DateTime<Local>,
     end: DateTime<Local>,
-    stops: Vec<hateoas::Response<WithDistance<Stop>>>,
-    lines: Vec<hateoas::Response<Line>>,
+    stops: Vec<hateoas::Response<ServedStopDto<WithDistance<Stop>>>>,
+    lines: Vec<hateoas::Response<LineDto>>,
     trips: Vec<hateoas::Response<TripInstanceDto>>,
-    shared_mobility_stations: Vec<SharedMobilityStation>,
+    shared_mobility_stations: Vec<SharedMobilityStationDto>,
+}
+
+/// Unknown values, e.g. because the status of the station is unknown, are
+/// omitted.
+#[serde_with::skip_serializing_none]
+#[derive(Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct SharedMobilityStationDto {
+    #[serde(flatten)]
+    station: SharedMobilityStation,
+    occupancy_ratio: Option<f64>,
+    is_full: Option<bool>,
+    is_empty: Option<bool>,
+}
+
+impl From<SharedMobilityStation> for SharedMobilityStationDto {
+    fn from(station: SharedMobilityStation) -> Self {
+        Self {
+            occupancy_ratio: station.occupancy_ratio(),
+            is_full: station.is_full(),
+            is_empty: station.is_empty(),
+            station,
+        }
+    }
 }
 
+/// Default number of shared mobility stations nearby.
+const DEFAULT_MAX_SHARED_MOBILITY_STATIONS: usize = 50;
+
+const MAX_SHARED_MOBILITY_STATIONS: usize = 500;
+
 #[derive(Deserialize)]
 pub(crate) struct TripsNearbyQuery {
     latitude: f64,
@@ -85,9 +142,59 @@ pub(crate) struct TripsNearbyQuery {
 
     #[serde(deserialize_with = "date_time::deserialize_local_option", default)]
     end: Option<DateTime<Local>>,
+
+    #[serde(default)]
+    window: WindowMode,
+
+    /// Whether to only include shared mobility stations, vehicles can be rented at.
+    #[serde(default)]
+    rentable_only: bool,
+
+    /// Maximum number of shared mobility stations, the nearest first.
+    max_shared_mobility_stations: Option<usize>,
+
+    /// Comma separated service tags. Trips of services with any of them are
+    /// omitted, e.g., `school`.
+    #[serde(deserialize_with = "comma_separated::deserialize", default)]
+    exclude_tags: Vec<ServiceTag>,
+
+    /// Whether to only include the names of the stops of trips, which are taken
+    /// from their stop times rather than looked up. Locations and platforms are
+    /// omitted, and names may lag behind renamed stops for a few minutes.
+    #[serde(default)]
+    stop_names_only: bool,
+
+    /// Includes localized labels of enum values, e.g. of pickup types.
+    #[serde(default)]
+    include_labels: bool,
+
+    /// Language of the labels. Defaults to the `Accept-Language` header.
+    lang: Option<Locale>,
+
+    /// Whether to include internal timings in the response.
+    #[serde(default)]
+    debug: bool,
+}
+
+impl Validate for TripsNearbyQuery {
+    fn validate(&self) -> Result<(), query::InvalidParameter> {
+        query::latitude("latitude", self.latitude)?;
+        query::longitude("longitude", self.longitude)?;
+        if let Some(radius) = self.radius {
+            query::radius("radius", radius)?;
+        }
+        if let Some(max) = self.max_shared_mobility_stations {
+            query::limit(
+                "max_shared_mobility_stations",
+                max,
+                MAX_SHARED_MOBILITY_STATIONS,
+            )?;
+        }
+        query::ordered(self.start.as_ref(), "end", self.end.as_ref())
+    }
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct NearbyBenchmark {
     fetch_shared_mobility_stations_secs: f64,
@@ -98,15 +205,133 @@ struct NearbyBenchmark {
     num_trips_fetched: usize,
 }
 
+/// Everything nearby, before links are added. Shared by identical requests.
+#[derive(Clone)]
+pub(crate) struct NearbyData {
+    radius: f64,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+    stops: Vec<WithDistance<WithId<Stop>>>,
+    /// Stops of `stops`, which are only served on request.
+    on_request_stops: HashSet<Id<Stop>>,
+    lines: Vec<WithId<Line>>,
+    trips: Vec<TripInstance>,
+    shared_mobility_stations: Vec<SharedMobilityStation>,
+    /// Timings of the request, which computed the data.
+    benchmark: NearbyBenchmark,
+}
+
+/// Collapses identical nearby requests, e.g. of departure boards of the same stop.
+pub(crate) type NearbyFlights = SingleFlight<String, NearbyData, RouteErrorResponse>;
+
 async fn nearby(
     OriginalUri(original_uri): OriginalUri,
-    State(WebState { transit_client, .. }): State<WebState>,
-    Query(params): Query<TripsNearbyQuery>,
+    State(WebState {
+        transit_client,
+        clock,
+        ..
+    }): State<WebState>,
+    ValidQuery(params): ValidQuery<TripsNearbyQuery>,
     Extension(base_url): Extension<Arc<BaseUrl>>,
+    Extension(flights): Extension<Arc<NearbyFlights>>,
+    headers: HeaderMap,
 ) -> HateoasResult<NearbyDto> {
     let origins = transit_client.get_origin_ids().await?;
+    // the time is part of the key only if it is given, so that requests for the
+    // current time are collapsed, too.
+    let key = format!(
+        "/nearby?latitude={}&longitude={}&radius={:?}&start={:?}&end={:?}&window={:?}\
+         &rentable_only={}&max_shared_mobility_stations={:?}&exclude_tags={:?}\
+         &stop_names_only={}&origins={:?}",
+        params.latitude,
+        params.longitude,
+        params.radius,
+        params.start,
+        params.end,
+        params.window,
+        params.rentable_only,
+        params.max_shared_mobility_stations,
+        params.exclude_tags,
+        params.stop_names_only,
+        origins,
+    );
+    let (data, stats) = flights
+        .run(key, || {
+            nearby_data(
+                &transit_client,
+                &params,
+                clock.now(),
+                &origins,
+                original_uri.path(),
+            )
+        })
+        .await;
+    let NearbyData {
+        radius,
+        start,
+        end,
+        stops,
+        on_request_stops,
+        lines,
+        trips,
+        shared_mobility_stations,
+        benchmark,
+    } = data?;
+    // labels are added per request, so they are not part of the key.
+    let labels = requested_labels(params.include_labels, params.lang, &headers);
+
+    let nearby = NearbyDto {
+        radius,
+        latitude: params.latitude,
+        longitude: params.longitude,
+        start,
+        end,
+        stops: stops
+            .into_iter()
+            .map(|stop| {
+                let served_only_on_request =
+                    on_request_stops.contains(&stop.content.id);
+                stop_with_distance_hateoas(
+                    stop,
+                    served_only_on_request,
+                    base_url.clone(),
+                )
+            })
+            .collect(),
+        lines: lines
+            .into_iter()
+            .map(|line| line_hateoas(line, base_url.clone()))
+            .collect(),
+        trips: trips
+            .into_iter()
+            .map(|trip| {
+                trip_hateoas(
+                    TripInstanceDto::new(trip, base_url.clone(), labels),
+                    base_url.clone(),
+                )
+            })
+            .collect::<Vec<_>>(),
+        shared_mobility_stations: shared_mobility_stations
+            .into_iter()
+            .map(SharedMobilityStationDto::from)
+            .collect(),
+    };
+
+    Ok(
+        nearby_hateoas(nearby, base_url, params.debug.then_some((benchmark, stats)))
+            .json(),
+    )
+}
+
+async fn nearby_data(
+    transit_client: &Client<WebDatabase>,
+    params: &TripsNearbyQuery,
+    now: DateTime<Local>,
+    origins: &[Id<Origin>],
+    uri: &str,
+) -> RouteResult<NearbyData> {
     let radius = params.radius.unwrap_or(0.05);
-    let start = params.start.unwrap_or(Local::now());
+    let start = params.start.unwrap_or(now);
     let end = params.end.unwrap_or(start + Duration::hours(1));
 
     // get shared mobility stations
@@ -116,66 +341,79 @@ async fn nearby(
             params.latitude,
             params.longitude,
             radius,
-            &origins,
+            params.rentable_only,
+            params
+                .max_shared_mobility_stations
+                .unwrap_or(DEFAULT_MAX_SHARED_MOBILITY_STATIONS),
+            origins,
         )
         .await
         .map_err(|why| {
             RouteErrorResponse::from(why)
                 .with_method(&Method::GET)
                 .with_message("Could not query nearby shared mobility stations.")
-                .with_uri(original_uri.path())
+                .with_uri(uri)
         })?;
     let fetch_shared_mobility_elapsed = now.elapsed();
 
     // get stops
     let now = Instant::now();
     let stops = transit_client
-        .find_nearby(params.latitude, params.longitude, radius, &origins)
+        .find_nearby(params.latitude, params.longitude, radius, origins)
         .await
         .map_err(|why| {
             RouteErrorResponse::from(why)
                 .with_method(&Method::GET)
                 .with_message("Could not query nearby stops.")
-                .with_uri(original_uri.path())
+                .with_uri(uri)
         })?;
     let fetch_stops_elapsed = now.elapsed();
 
-    // get lines and trips
-    let now = Instant::now();
-    let mut lines = vec![];
-    for stop in stops.iter() {
-        // get lines
-        lines.extend(
-            transit_client
-                .get_lines_at_stop(&stop.content.id, &origins)
-                .await
-                .map_err(|why| {
-                    RouteErrorResponse::from(why)
-                        .with_method(&Method::GET)
-                        .with_message("Could not query lines at nearby stops.")
-                        .with_uri(original_uri.path())
-                })?,
-        );
-    }
-    let fetch_lines_elapsed = now.elapsed();
-
     // stop ids
     let stop_ids = stops
         .iter()
         .map(|stop| &stop.content.id)
         .collect::<Vec<_>>();
 
+    let on_request_stops = served_only_on_request(transit_client, &stop_ids)
+        .await
+        .map_err(|why| {
+            RouteErrorResponse::from(why)
+                .with_method(&Method::GET)
+                .with_message("Could not query how nearby stops are served.")
+                .with_uri(uri)
+        })?;
+
+    // get lines of all stops at once
+    let now = Instant::now();
+    let mut lines_at_stops = transit_client
+        .get_lines_at_stops(&stop_ids, origins)
+        .await
+        .map_err(|why| {
+            RouteErrorResponse::from(why)
+                .with_method(&Method::GET)
+                .with_message("Could not query lines at nearby stops.")
+                .with_uri(uri)
+        })?;
+    // ordered by the distance of the stops.
+    let lines = stop_ids
+        .iter()
+        .flat_map(|id| lines_at_stops.remove(*id).unwrap_or_default())
+        .unique_by(|line| line.id.clone())
+        .collect::<Vec<_>>();
+    let fetch_lines_elapsed = now.elapsed();
+
     // get raw trips
     // TODO: what to do with duplicate trips?
     let now = Instant::now();
     let trips = transit_client
-        .get_all_trips_via_stops(&stop_ids, start, end, &origins)
+        .get_all_trips_via_stops(&stop_ids, start, end, params.window, origins)
         .await
         .map_err(|why| {
             RouteErrorResponse::from(why)
                 .with_method(&Method::GET)
                 .with_message("Could not query trips at nearby stops.")
-                .with_uri(original_uri.path())
+                .with_uri(uri)
         })?;
     let fetch_trips_elapsed = now.elapsed();
     let num_database_trips = trips.len();
@@ -186,30 +424,27 @@ async fn nearby(
         .instanciate_trips_include(
             trips,
             DateTimeRange::new(start, end),
-            Some(&stop_ids),
-            true,
-            true,
-            true,
-            &origins,
+            &TripInstantiationOptions {
+                window_mode: params.window,
+                stop_ids_of_interest: Some(&stop_ids),
+                stop_names_from_stop_times: params.stop_names_only,
+                ..TripInstantiationOptions::all_references()
+            },
+            origins,
         )
         .await
         .map_err(|why| {
             RouteErrorResponse::from(why)
                 .with_method(&Method::GET)
                 .with_message("Could not instanciate trips at nearby stops.")
-                .with_uri(original_uri.path())
+                .with_uri(uri)
         })?;
     let instantiate_trips_elapsed = now.elapsed();
 
     // sort trips
+    instanciated_trips.retain(|trip| !trip.has_any_tag(&params.exclude_tags));
     TripInstance::sort(&mut instanciated_trips);
 
-    // unique lines
-    lines = lines
-        .into_iter()
-        .unique_by(|line| line.id.clone())
-        .collect();
-
     let benchmark = NearbyBenchmark {
         fetch_shared_mobility_stations_secs: fetch_shared_mobility_elapsed
             .as_secs_f64(),
@@ -220,76 +455,147 @@ async fn nearby(
         num_trips_fetched: num_database_trips,
     };
 
-    let nearby = NearbyDto {
+    Ok(NearbyData {
         radius,
-        latitude: params.latitude,
-        longitude: params.longitude,
         start,
         end,
-        stops: stops
-            .into_iter()
-            .map(|stop| stop_with_distance_hateoas(stop, base_url.clone()))
-            .collect(),
-        lines: lines
-            .into_iter()
-            .map(|line| line_hateoas(line, base_url.clone()))
-            .collect(),
-        trips: instanciated_trips
-            .into_iter()
-            .map(|trip| {
-                trip_hateoas(
-                    TripInstanceDto {
-                        info: trip.info,
-                        stops: trip
-                            .stops
-                            .into_iter()
-                            .map(|stop_time| {
-                                stop_time_hateoas(stop_time, base_url.clone())
-                            })
-                            .collect::<Vec<_>>(),
-                        stop_of_interest: trip.stop_of_interest,
-                        line: trip
-                            .line
-                            .map(|line| line_hateoas(line, base_url.clone())),
-                        agency: trip
-                            .agency
-                            .map(|agency| agency_hateoas(agency, base_url.clone())),
-                    },
-                    base_url.clone(),
-                )
-            })
-            .collect::<Vec<_>>(),
+        stops,
+        on_request_stops,
+        lines,
+        trips: instanciated_trips,
         shared_mobility_stations: shared_mobility_stations
             .into_iter()
             .map(|x| x.content.content)
             .collect(),
-    };
-
-    Ok(nearby_hateoas(nearby, base_url, Some(benchmark)).json())
+        benchmark,
+    })
 }
 
 fn nearby_hateoas(
     dto: NearbyDto,
     base_url: Arc<BaseUrl>,
-    benchmark: Option<NearbyBenchmark>,
+    debug: Option<(NearbyBenchmark, SingleFlightStats)>,
 ) -> hateoas::Response<NearbyDto> {
-    let latitude = dto.latitude;
-    let longitude = dto.longitude;
-    let radius = dto.radius;
-    let start = dto.start.format("%Y-%m-%dT%H:%M:%S");
-    let end = dto.end.format("%Y-%m-%dT%H:%M:%S");
+    let realtime = RealtimeNearbyResource {
+        latitude: dto.latitude,
+        longitude: dto.longitude,
+        radius: dto.radius,
+        start: dto.start,
+        end: dto.end,
+    };
     hateoas::Response::builder(dto, base_url)
-        .link(
-            "realtime",
-            realtime::resource!(
-                "/nearby?latitude={}&longitude={}&radius={}&start={}&end={}",
-                latitude,
-                longitude,
-                radius,
-                start,
-                end
-            ),
+        .link_to("realtime", &realtime)
+        .debug_info_option(
+            "benchmark",
+            debug.as_ref().map(|(benchmark, _)| benchmark),
         )
-        .debug_info_option("benchmark", benchmark)
+        .debug_info_option("singleFlight", debug.as_ref().map(|(_, stats)| stats))
         .build()
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        extract::{FromRequestParts, Query},
+        http::{Request, StatusCode, Uri},
+        response::IntoResponse,
+    };
+
+    use super::*;
+
+    fn query(uri: &str) -> TripsNearbyQuery {
+        let uri = uri.parse::<Uri>().unwrap();
+        Query::<TripsNearbyQuery>::try_from_uri(&uri).unwrap().0
+    }
+
+    fn nearby(debug: bool) -> serde_json::Value {
+        let now = Local::now();
+        let dto = NearbyDto {
+            radius: 500.0,
+            latitude: 54.32,
+            longitude: 10.13,
+            start: now,
+            end: now + Duration::hours(1),
+            stops: vec![],
+            lines: vec![],
+            trips: vec![],
+            shared_mobility_stations: vec![],
+        };
+        let benchmark = NearbyBenchmark {
+            fetch_shared_mobility_stations_secs: 0.1,
+            fetch_stops_secs: 0.2,
+            fetch_lines_secs: 0.3,
+            fetch_trips_secs: 0.4,
+            instantiate_trips_secs: 0.5,
+            num_trips_fetched: 6,
+        };
+        let stats = SingleFlightStats {
+            hit: false,
+            hits: 0,
+            misses: 1,
+        };
+        let base_url = Arc::new(BaseUrl::parse("https://example.org").unwrap());
+        let response =
+            nearby_hateoas(dto, base_url, debug.then_some((benchmark, stats)));
+        serde_json::to_value(response).unwrap()
+    }
+
+    #[test]
+    fn debug_info_is_only_included_with_the_flag() {
+        assert!(!query("/v1/nearby?latitude=54.32&longitude=10.13").debug);
+        assert!(query("/v1/nearby?latitude=54.32&longitude=10.13&debug=true").debug);
+
+        let response = nearby(false);
+        assert!(response.get("debugInfo").is_none(), "{}", response);
+        assert!(response.get("links").is_some());
+
+        let response = nearby(true);
+        let debug_info = response.get("debugInfo").expect("debug info is included");
+        assert_eq!(debug_info["benchmark"]["numTripsFetched"], 6);
+        assert_eq!(debug_info["singleFlight"]["misses"], 1);
+    }
+
+    /// Status and body of the rejection of the query, if it is rejected.
+    async fn rejection(uri: &str) -> Option<(StatusCode, serde_json::Value)> {
+        let (mut parts, _) = Request::get(uri).body(()).unwrap().into_parts();
+        let rejection =
+            ValidQuery::<TripsNearbyQuery>::from_request_parts(&mut parts, &())
+                .await
+                .err()?;
+        let response = rejection.clone().into_response();
+        Some((response.status(), serde_json::to_value(rejection).unwrap()))
+    }
+
+    #[tokio::test]
+    async fn invalid_parameters_are_rejected_with_their_name() {
+        assert!(rejection("/v1/nearby?latitude=54.32&longitude=10.13")
+            .await
+            .is_none());
+
+        let cases = [
+            (
+                "/v1/nearby?latitude=91&longitude=10.13",
+                "Invalid query parameter `latitude`.",
+            ),
+            (
+                "/v1/nearby?latitude=54.32&longitude=10.13&start=tomorrow",
+                "Malformed query parameter `start`.",
+            ),
+            (
+                "/v1/nearby?latitude=north&longitude=10.13",
+                "Malformed query parameter `latitude`.",
+            ),
+        ];
+        for (uri, message) in cases {
+            let (status, body) = rejection(uri).await.expect("query is rejected");
+            assert_eq!(status, StatusCode::BAD_REQUEST, "status of {}", uri);
+            assert_eq!(body["message"], message, "message of {}", uri);
+            assert_eq!(body["requestedUri"], "/v1/nearby", "uri of {}", uri);
+            assert!(
+                body["detailedInformation"].is_string(),
+                "details of {}",
+                uri
+            );
+        }
+    }
+}
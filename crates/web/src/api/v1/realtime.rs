@@ -1,35 +1,117 @@
 use axum::{
-    extract::{Query, State},
+    extract::{OriginalUri, Query, State},
+    http::{HeaderMap, Method, StatusCode},
     response::sse::{Event, KeepAlive, Sse},
     routing::{get, on},
-    Router,
+    Extension, Router,
 };
 use axum_extra::TypedHeader;
-use chrono::Local;
-use futures::stream::{self, Stream};
-use model::{trip_update::TripUpdate, DateTimeRange, WithId};
-use serde::Serialize;
-use std::{convert::Infallible, time::Duration};
+use chrono::{DateTime, Local};
+use futures::{
+    future,
+    stream::{self, Stream},
+};
+use model::{
+    locale::Locale,
+    origin::Origin,
+    trip_instance::{TripInstance, TripInstanceId},
+    trip_update::TripUpdate,
+    DateTimeRange, WithId,
+};
+use public_transport::{
+    client::{Client, TripInstantiationOptions},
+    not_found_to_none, RequestResult,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, convert::Infallible, sync::Arc, time::Duration};
 use tokio_stream::StreamExt as _;
 use tower_http::trace::TraceLayer;
+use utility::{id::Id, let_also::LetAlso, serde::comma_separated};
 
 use crate::{
-    common::{route_not_found, METHOD_FILTER_ALL},
-    WebState,
+    common::{
+        locale::requested_labels, query::ValidQuery, route_not_found, HateoasResult,
+        RouteErrorResponse, RouteResult, VecResponse, METHOD_FILTER_ALL,
+    },
+    hateoas::Resource,
+    middleware::base_url::{base_url_middleware, BaseUrl},
+    WebDatabase, WebState,
 };
 
-use super::TripsNearbyQuery;
+use super::{
+    trips::{StatusLabels, TripInstanceDto},
+    TripsNearbyQuery,
+};
 
 macro_rules! resource {
     ($($arg:tt)*) => {
         crate::api::v1::resource!("/realtime{}", format_args!($($arg)*))
     };
 }
-pub(crate) use resource;
+
+/// Stream of realtime updates of trips calling at stops around a location.
+pub(crate) struct RealtimeNearbyResource {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub radius: f64,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+}
+
+impl Resource for RealtimeNearbyResource {
+    const ROUTE: &'static str = "/nearby";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn query_params(&self) -> Vec<(&'static str, Option<String>)> {
+        let format =
+            |time: DateTime<Local>| time.format("%Y-%m-%dT%H:%M:%S").to_string();
+        vec![
+            ("latitude", Some(self.latitude.to_string())),
+            ("longitude", Some(self.longitude.to_string())),
+            ("radius", Some(self.radius.to_string())),
+            ("start", Some(format(self.start))),
+            ("end", Some(format(self.end))),
+        ]
+    }
+}
+
+/// Trip instances refreshed with current realtime data.
+pub(crate) struct RealtimeInstancesResource {
+    pub instances: Vec<TripInstanceId>,
+}
+
+impl Resource for RealtimeInstancesResource {
+    const ROUTE: &'static str = "/instances";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn query_params(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![(
+            "instances",
+            Some(
+                self.instances
+                    .iter()
+                    .map(TripInstanceId::to_string)
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+        )]
+    }
+}
 
 pub(crate) fn routes(state: WebState) -> Router {
     Router::new()
-        .route("/nearby", get(sse_handler))
+        .route(RealtimeNearbyResource::ROUTE, get(sse_handler))
+        .route(RealtimeInstancesResource::ROUTE, get(get_instances))
+        .layer(axum::middleware::from_fn_with_state(
+            state.base_url.clone(),
+            base_url_middleware,
+        ))
         .with_state(state)
         .layer(TraceLayer::new_for_http())
         .fallback_service(on(METHOD_FILTER_ALL, route_not_found))
@@ -43,46 +125,68 @@ struct UpdateEvent {
 }
 
 async fn sse_handler(
+    OriginalUri(original_uri): OriginalUri,
     TypedHeader(user_agent): TypedHeader<headers::UserAgent>,
-    State(WebState { transit_client, .. }): State<WebState>,
-    Query(params): Query<TripsNearbyQuery>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    State(WebState {
+        transit_client,
+        clock,
+        ..
+    }): State<WebState>,
+    ValidQuery(params): ValidQuery<TripsNearbyQuery>,
+) -> RouteResult<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
     println!("`{}` connected", user_agent.as_str());
+    let map_err = |why| {
+        RouteErrorResponse::from(why)
+            .with_method(&Method::GET)
+            .with_uri(original_uri.path())
+    };
 
-    let origins = transit_client.get_origin_ids().await.expect("origins");
+    let origins = transit_client.get_origin_ids().await?;
     let radius = params.radius.unwrap_or(0.05);
-    let start = params.start.unwrap_or(Local::now());
+    let start = params.start.unwrap_or(clock.now());
     let end = params.end.unwrap_or(start + chrono::Duration::hours(1));
 
     let stops = transit_client
         .find_nearby(params.latitude, params.longitude, radius, &origins)
         .await
-        .expect("stops");
+        .map_err(map_err)?;
 
     let stop_ids = stops
         .iter()
         .map(|stop| &stop.content.id)
         .collect::<Vec<_>>();
 
-    let trip_ids = transit_client
-        .get_all_trips_via_stops(&stop_ids, start, end, &origins)
+    let trips = transit_client
+        .get_all_trips_via_stops(&stop_ids, start, end, params.window, &origins)
+        .await
+        .map_err(map_err)?;
+    // updates are looked up by service day, as trip ids may be reused daily.
+    let update_ids = transit_client
+        .instanciate_trips_include(
+            trips,
+            DateTimeRange::new(start, end),
+            &TripInstantiationOptions {
+                window_mode: params.window,
+                stop_ids_of_interest: Some(&stop_ids),
+                ..Default::default()
+            },
+            &origins,
+        )
         .await
-        .expect("trips")
+        .map_err(map_err)?
+        .iter()
+        .filter_map(|trip| trip.info.instance_id.update_id())
+        .collect::<HashSet<_>>()
         .into_iter()
-        .map(|trip| trip.id)
         .collect::<Vec<_>>();
 
     let stream = stream::unfold((), move |()| {
         let client = transit_client.clone();
         let origins = origins.clone();
-        let trip_ids = trip_ids.clone();
+        let update_ids = update_ids.clone();
         async move {
             let updates = client
-                .get_realtime_for_trips_in_range(
-                    &trip_ids,
-                    DateTimeRange::new(start, end),
-                    &origins,
-                )
+                .get_realtime_for_trip_instances(&update_ids, &origins)
                 .await
                 .unwrap_or(vec![]); // TODO: error handling
             let event_data = UpdateEvent {
@@ -97,5 +201,112 @@ async fn sse_handler(
     .map(Ok)
     .throttle(Duration::from_secs(10));
 
-    Sse::new(stream).keep_alive(KeepAlive::default())
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Maximum number of trip instances refreshed at once.
+const MAX_REFRESHED_INSTANCES: usize = 100;
+
+#[derive(Deserialize)]
+struct InstancesQuery {
+    /// Comma separated ids of the trip instances to refresh.
+    #[serde(deserialize_with = "comma_separated::deserialize")]
+    instances: Vec<TripInstanceId>,
+
+    /// Includes localized labels of enum values, e.g. of the realtime status.
+    #[serde(default)]
+    include_labels: bool,
+
+    /// Language of the labels. Defaults to the `Accept-Language` header.
+    lang: Option<Locale>,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RefreshedInstanceDto {
+    #[serde(flatten)]
+    trip: TripInstanceDto,
+    /// Not set, if there is no realtime data for the trip instance (yet).
+    realtime: Option<TripUpdate>,
+    /// Localized label of the realtime status, if requested and known.
+    realtime_labels: Option<StatusLabels>,
+}
+
+/// Refreshes trip instances, which a client already shows, with current realtime
+/// data. Instances, which do not exist (anymore), are omitted.
+async fn get_instances(
+    OriginalUri(original_uri): OriginalUri,
+    State(WebState { transit_client, .. }): State<WebState>,
+    Query(params): Query<InstancesQuery>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+    headers: HeaderMap,
+) -> HateoasResult<VecResponse<RefreshedInstanceDto>> {
+    if params.instances.len() > MAX_REFRESHED_INSTANCES {
+        return Err(RouteErrorResponse::new(StatusCode::BAD_REQUEST)
+            .with_message(format!(
+                "at most {} instances can be refreshed at once.",
+                MAX_REFRESHED_INSTANCES
+            ))
+            .with_method(&Method::GET)
+            .with_uri(original_uri.path()));
+    }
+    let labels = requested_labels(params.include_labels, params.lang, &headers);
+    let origins = transit_client.get_origin_ids().await?;
+    let refreshed = params
+        .instances
+        .iter()
+        .map(|instance_id| refresh_instance(&transit_client, instance_id, &origins));
+    future::try_join_all(refreshed)
+        .await
+        .map(|refreshed| {
+            refreshed
+                .into_iter()
+                .flatten()
+                .map(|(trip, realtime)| RefreshedInstanceDto {
+                    trip: TripInstanceDto::new(trip, base_url.clone(), labels),
+                    realtime_labels: labels.zip(realtime.as_ref()).map(
+                        |(locale, update)| StatusLabels {
+                            status: update.status.label().get(locale),
+                        },
+                    ),
+                    realtime,
+                })
+                .collect::<Vec<_>>()
+                .let_owned(|data| VecResponse::non_paginated(data).hateoas().json())
+        })
+        .map_err(|why| {
+            RouteErrorResponse::from(why)
+                .with_method(&Method::GET)
+                .with_uri(original_uri.path())
+        })
+}
+
+/// The trip instance with its current realtime data. `None`, if the trip instance
+/// does not exist (anymore).
+async fn refresh_instance(
+    transit_client: &Client<WebDatabase>,
+    instance_id: &TripInstanceId,
+    origins: &[Id<Origin>],
+) -> RequestResult<Option<(TripInstance, Option<TripUpdate>)>> {
+    // ids are validated on deserialization.
+    let Some((trip_id, service_day, index)) = instance_id.decode() else {
+        return Ok(None);
+    };
+    let Some(mut trip) = transit_client
+        .get_trip_instance(trip_id.clone(), service_day, index, origins)
+        .await
+        .let_owned(not_found_to_none)?
+    else {
+        return Ok(None);
+    };
+    let realtime = transit_client
+        .get_realtime_for_trip(&trip_id, service_day, index, origins)
+        .await
+        .let_owned(not_found_to_none)?
+        .map(|update| update.content);
+    if let Some(realtime) = &realtime {
+        trip.apply_platform_changes(realtime);
+    }
+    Ok(Some((trip, realtime)))
 }
@@ -0,0 +1,197 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{OriginalUri, Query, State},
+    http::{HeaderMap, Method, StatusCode},
+    routing::{get, on},
+    Extension, Router,
+};
+use model::{line::Line, locale::Locale, stop::StopNameSuggestion, WithId};
+use serde::{Deserialize, Serialize};
+use utility::let_also::LetAlso;
+
+use crate::{
+    common::{
+        locale::requested_labels, route_not_found, HateoasResult, RouteErrorResponse,
+        METHOD_FILTER_ALL,
+    },
+    hateoas::{self, Resource},
+    middleware::base_url::{base_url_middleware, BaseUrl},
+    WebState,
+};
+
+use super::{
+    lines::{line_hateoas, LineDto},
+    stops::stop_suggestion_hateoas,
+    trips::{trip_hateoas, TripInstanceDto},
+};
+
+macro_rules! resource {
+    ($($arg:tt)*) => {
+        crate::api::v1::resource!("/search{}", format_args!($($arg)*))
+    };
+}
+
+/// Stops, lines and trips matching a query, e.g. of a universal search box.
+pub(crate) struct SearchResource {
+    pub q: String,
+    pub limit: Option<usize>,
+    /// Locale of the labels to include.
+    pub labels: Option<Locale>,
+}
+
+impl Resource for SearchResource {
+    const ROUTE: &'static str = "/";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn query_params(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("q", Some(self.q.clone())),
+            ("limit", self.limit.map(|limit| limit.to_string())),
+            ("include_labels", self.labels.map(|_| "true".to_owned())),
+            ("lang", self.labels.map(|locale| locale.as_str().to_owned())),
+        ]
+    }
+}
+
+pub(crate) fn routes(state: WebState) -> Router {
+    Router::new()
+        .route(SearchResource::ROUTE, get(search))
+        .layer(axum::middleware::from_fn_with_state(
+            state.base_url.clone(),
+            base_url_middleware,
+        ))
+        .with_state(state)
+        .fallback_service(on(METHOD_FILTER_ALL, route_not_found))
+}
+
+/// Queries shorter than this are rejected, as they match too much.
+const SEARCH_MIN_LENGTH: usize = 2;
+
+/// Default number of hits per type.
+const SEARCH_DEFAULT_LIMIT: usize = 5;
+
+const SEARCH_MAX_LIMIT: usize = 20;
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    q: String,
+    /// Maximum number of hits per type.
+    limit: Option<usize>,
+    /// Includes localized labels of enum values of the trips.
+    #[serde(default)]
+    include_labels: bool,
+    /// Language of the labels. Defaults to the `Accept-Language` header.
+    lang: Option<Locale>,
+}
+
+/// A hit of the search, tagged with its type.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum SearchHit {
+    Line(hateoas::Response<LineDto>),
+    Stop(hateoas::Response<StopNameSuggestion>),
+    Trip(Box<hateoas::Response<TripInstanceDto>>),
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchDto {
+    query: String,
+    /// Lines named like the query come first, then stops, further lines and trips.
+    hits: Vec<SearchHit>,
+}
+
+/// Whether the name of the line equals the query, regardless of spaces and case,
+/// e.g. `RE7` for the line `RE 7`.
+fn is_exact_line_match(line: &WithId<Line>, query: &str) -> bool {
+    let normalize = |text: &str| {
+        text.chars()
+            .filter(|c| !c.is_whitespace())
+            .flat_map(char::to_lowercase)
+            .collect::<String>()
+    };
+    line.content
+        .name
+        .as_deref()
+        .is_some_and(|name| normalize(name) == normalize(query))
+}
+
+async fn search(
+    OriginalUri(original_uri): OriginalUri,
+    State(WebState {
+        transit_client,
+        clock,
+        ..
+    }): State<WebState>,
+    Query(params): Query<SearchQuery>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+    headers: HeaderMap,
+) -> HateoasResult<SearchDto> {
+    let query = params.q.trim();
+    if query.chars().count() < SEARCH_MIN_LENGTH {
+        return Err(RouteErrorResponse::new(StatusCode::BAD_REQUEST)
+            .with_message(format!(
+                "query must have at least {} characters.",
+                SEARCH_MIN_LENGTH
+            ))
+            .with_method(&Method::GET)
+            .with_uri(original_uri.path()));
+    }
+    let limit = params
+        .limit
+        .unwrap_or(SEARCH_DEFAULT_LIMIT)
+        .clamp(1, SEARCH_MAX_LIMIT);
+    let labels = requested_labels(params.include_labels, params.lang, &headers);
+    let origins = transit_client.get_origin_ids().await?;
+    let (stops, lines, trips) = tokio::try_join!(
+        transit_client.search_stop(query, limit, &origins),
+        transit_client.search_lines(query, limit, &origins),
+        transit_client.search_trips(query, clock.now(), limit, &origins),
+    )
+    .map_err(|why| {
+        RouteErrorResponse::from(why)
+            .with_method(&Method::GET)
+            .with_uri(original_uri.path())
+    })?;
+
+    let (exact_lines, other_lines): (Vec<_>, Vec<_>) = lines
+        .into_iter()
+        .partition(|line| is_exact_line_match(line, query));
+    let line_hit =
+        |line: WithId<Line>| SearchHit::Line(line_hateoas(line, base_url.clone()));
+    let hits = exact_lines
+        .into_iter()
+        .map(line_hit)
+        .chain(stops.into_iter().map(|stop| {
+            SearchHit::Stop(stop_suggestion_hateoas(stop, base_url.clone()))
+        }))
+        .chain(other_lines.into_iter().map(line_hit))
+        .chain(trips.into_iter().take(limit).map(|trip| {
+            SearchHit::Trip(Box::new(trip_hateoas(
+                TripInstanceDto::new(trip, base_url.clone(), labels),
+                base_url.clone(),
+            )))
+        }))
+        .collect::<Vec<_>>();
+
+    let self_resource = SearchResource {
+        q: query.to_owned(),
+        limit: params.limit,
+        labels,
+    };
+    SearchDto {
+        query: query.to_owned(),
+        hits,
+    }
+    .let_owned(|dto| {
+        hateoas::Response::builder(dto, base_url)
+            .link_to("self", &self_resource)
+            .build()
+            .json()
+    })
+    .let_owned(Ok)
+}
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{OriginalUri, Path, State},
+    http::Method,
+    routing::{get, on},
+    Extension, Router,
+};
+use chrono::NaiveDate;
+use model::calendar::ServiceDay;
+use serde::Deserialize;
+use utility::id::Id;
+
+use crate::{
+    common::{
+        query::{self, ValidQuery, Validate},
+        route_not_found, schema_no_example, HateoasResult, RouteErrorResponse,
+        VecResponse, METHOD_FILTER_ALL,
+    },
+    hateoas,
+    middleware::base_url::{base_url_middleware, BaseUrl},
+    WebState,
+};
+
+macro_rules! resource {
+    ($($arg:tt)*) => {
+        crate::api::v1::resource!("/services{}", format_args!($($arg)*))
+    };
+}
+
+pub(crate) fn routes(state: WebState) -> Router {
+    Router::new()
+        .route("/days/schema", get(schema_no_example::<ServiceDay>))
+        .route("/:id/days", get(get_service_days))
+        .layer(axum::middleware::from_fn_with_state(
+            state.base_url.clone(),
+            base_url_middleware,
+        ))
+        .with_state(state)
+        .fallback_service(on(METHOD_FILTER_ALL, route_not_found))
+}
+
+#[derive(Deserialize)]
+struct ServiceDaysQuery {
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+impl Validate for ServiceDaysQuery {
+    fn validate(&self) -> Result<(), query::InvalidParameter> {
+        query::ordered(self.from.as_ref(), "to", self.to.as_ref())
+    }
+}
+
+/// Lists the days a service is available on. Meant for debugging calendars, e.g.,
+/// to find out why a trip is not instantiated on a given day.
+async fn get_service_days(
+    OriginalUri(original_uri): OriginalUri,
+    Path(id): Path<i32>,
+    State(WebState { transit_client, .. }): State<WebState>,
+    ValidQuery(params): ValidQuery<ServiceDaysQuery>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<VecResponse<ServiceDay>> {
+    transit_client
+        .get_service_days(&Id::new(id), params.from, params.to)
+        .await
+        .map(|days| {
+            hateoas::Response::builder(VecResponse::non_paginated(days), base_url)
+                .link("self", resource!("/{}/days", id))
+                .build()
+                .json()
+        })
+        .map_err(|why| {
+            RouteErrorResponse::from(why)
+                .with_method(&Method::GET)
+                .with_uri(original_uri.path())
+        })
+}
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{OriginalUri, State},
+    http::Method,
+    routing::{get, on},
+    Extension, Router,
+};
+use public_transport::{
+    capability::{CapabilityStatus, SearchMode},
+    collector::CollectorHealth,
+    database::Database,
+    instrumented::OperationMetrics,
+};
+use serde::Serialize;
+
+use crate::{
+    common::{route_not_found, HateoasResult, RouteErrorResponse, METHOD_FILTER_ALL},
+    hateoas,
+    middleware::base_url::{base_url_middleware, BaseUrl},
+    WebState,
+};
+
+macro_rules! resource {
+    ($($arg:tt)*) => {
+        crate::api::v1::resource!("/status{}", format_args!($($arg)*))
+    };
+}
+
+pub(crate) fn routes(state: WebState) -> Router {
+    Router::new()
+        .route("/feeds", get(get_feeds))
+        .route("/database", get(get_database))
+        .route("/capabilities", get(get_capabilities))
+        .layer(axum::middleware::from_fn_with_state(
+            state.base_url.clone(),
+            base_url_middleware,
+        ))
+        .with_state(state)
+        .fallback_service(on(METHOD_FILTER_ALL, route_not_found))
+}
+
+/// Health of all collectors, as checked before they were first scheduled.
+async fn get_feeds(
+    OriginalUri(original_uri): OriginalUri,
+    State(WebState { transit_client, .. }): State<WebState>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<Vec<CollectorHealth>> {
+    transit_client
+        .get_collector_health()
+        .await
+        .map(|health| {
+            hateoas::Response::builder(health, base_url)
+                .link("self", resource!("/feeds"))
+                .build()
+                .json()
+        })
+        .map_err(|why| {
+            RouteErrorResponse::from(why)
+                .with_method(&Method::GET)
+                .with_uri(original_uri.path())
+        })
+}
+
+/// Call counts and latencies of all database operations since startup.
+async fn get_database(
+    State(WebState { transit_client, .. }): State<WebState>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<Vec<OperationMetrics>> {
+    let metrics = transit_client.database.metrics().snapshot();
+    Ok(hateoas::Response::builder(metrics, base_url)
+        .link("self", resource!("/database"))
+        .build()
+        .json())
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CapabilitiesDto {
+    capabilities: Vec<CapabilityStatus>,
+    /// How stops are searched with the available capabilities.
+    search_mode: SearchMode,
+}
+
+/// Capabilities of the database and the behavior they enable.
+async fn get_capabilities(
+    State(WebState { transit_client, .. }): State<WebState>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<CapabilitiesDto> {
+    let capabilities = transit_client.database.capabilities();
+    let dto = CapabilitiesDto {
+        capabilities: capabilities.status(),
+        search_mode: capabilities.search_mode(),
+    };
+    Ok(hateoas::Response::builder(dto, base_url)
+        .link("self", resource!("/capabilities"))
+        .build()
+        .json())
+}
@@ -1,43 +1,171 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 
 use axum::{
     extract::{OriginalUri, Path, Query, State},
-    http::Method,
+    http::{HeaderMap, Method, StatusCode},
+    response::Response,
     routing::{get, on},
     Extension, Router,
 };
+use chrono::Local;
 use model::{
+    pathway::PathwayGraph,
     stop::{Stop, StopNameSuggestion},
-    WithDistance, WithId,
+    Provenance, WithDistance, WithId, WithOrigin,
 };
-use serde::Deserialize;
-use utility::{id::Id, let_also::LetAlso};
+use public_transport::{client::Client, RequestResult};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use utility::{id::Id, let_also::LetAlso, normalize};
 
 use crate::{
     common::{
-        route_not_found, schema, HateoasResult, RouteErrorResponse, VecResponse,
-        METHOD_FILTER_ALL,
+        cursor::{Cursor, PageParams},
+        etag::EntityTag,
+        query::{self, ValidQuery, Validate},
+        route_not_found, schema, HateoasResult, RouteErrorResponse, RouteResult,
+        VecResponse, METHOD_FILTER_ALL,
     },
-    hateoas,
+    hateoas::{self, PathParams, Resource},
     middleware::base_url::{base_url_middleware, BaseUrl},
-    WebState,
+    WebDatabase, WebState,
 };
 
+use super::{lines::LinesResource, trips::TripsResource};
+
 macro_rules! resource {
     ($($arg:tt)*) => {
         crate::api::v1::resource!("/stops{}", format_args!($($arg)*))
     };
 }
-pub(crate) use resource;
+
+/// A single stop.
+pub(crate) struct StopResource {
+    pub id: Id<Stop>,
+}
+
+impl Resource for StopResource {
+    const ROUTE: &'static str = "/:id";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn path_params(&self) -> impl PathParams {
+        [self.id.raw()]
+    }
+}
+
+/// Source data of a stop.
+pub(crate) struct StopSourcesResource {
+    pub id: Id<Stop>,
+}
+
+impl Resource for StopSourcesResource {
+    const ROUTE: &'static str = "/:id/sources";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn path_params(&self) -> impl PathParams {
+        [self.id.raw()]
+    }
+}
+
+/// Pathways within a station.
+pub(crate) struct StopPathwaysResource {
+    pub id: Id<Stop>,
+}
+
+impl Resource for StopPathwaysResource {
+    const ROUTE: &'static str = "/:id/pathways";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn path_params(&self) -> impl PathParams {
+        [self.id.raw()]
+    }
+}
+
+/// Platforms of a station in natural order.
+pub(crate) struct StopPlatformsResource {
+    pub id: Id<Stop>,
+}
+
+impl Resource for StopPlatformsResource {
+    const ROUTE: &'static str = "/:id/platforms";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn path_params(&self) -> impl PathParams {
+        [self.id.raw()]
+    }
+}
+
+/// A page of all stops.
+pub(crate) struct StopsResource {
+    pub after: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl Resource for StopsResource {
+    const ROUTE: &'static str = "/";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn query_params(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("after", self.after.clone()),
+            ("limit", self.limit.map(|limit| limit.to_string())),
+        ]
+    }
+}
+
+/// Stops within `radius` km around a location.
+pub(crate) struct NearbyStopsResource {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub radius: Option<f64>,
+}
+
+impl Resource for NearbyStopsResource {
+    const ROUTE: &'static str = "/nearby";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn query_params(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("latitude", Some(self.latitude.to_string())),
+            ("longitude", Some(self.longitude.to_string())),
+            ("radius", self.radius.map(|radius| radius.to_string())),
+        ]
+    }
+}
 
 pub(crate) fn routes(state: WebState) -> Router {
     Router::new()
         .route("/schema", get(schema::<Stop>))
-        .route("/:id", get(get_stop))
-        .route("/", get(get_stops))
+        .route(StopResource::ROUTE, get(get_stop))
+        .route(StopSourcesResource::ROUTE, get(get_stop_sources))
+        .route(StopPathwaysResource::ROUTE, get(get_stop_pathways))
+        .route(StopPlatformsResource::ROUTE, get(get_stop_platforms))
+        .route(StopsResource::ROUTE, get(get_stops))
         .route("/search/:name", get(search_stop))
-        .route("/nearby", get(nearby))
-        .layer(axum::middleware::from_fn(base_url_middleware))
+        .route("/autocomplete", get(autocomplete_stop))
+        .route(NearbyStopsResource::ROUTE, get(nearby))
+        .layer(axum::middleware::from_fn_with_state(
+            state.base_url.clone(),
+            base_url_middleware,
+        ))
         .with_state(state)
         .fallback_service(on(METHOD_FILTER_ALL, route_not_found))
 }
@@ -45,18 +173,171 @@ pub(crate) fn routes(state: WebState) -> Router {
 async fn get_stops(
     OriginalUri(original_uri): OriginalUri,
     State(WebState { transit_client, .. }): State<WebState>,
+    Query(params): Query<PageParams>,
     Extension(base_url): Extension<Arc<BaseUrl>>,
-) -> HateoasResult<VecResponse<hateoas::Response<Stop>>> {
+) -> HateoasResult<VecResponse<hateoas::Response<ServedStopDto<Stop>>>> {
+    let map_err = |why| {
+        RouteErrorResponse::from(why)
+            .with_method(&Method::GET)
+            .with_uri(original_uri.path())
+    };
     let origins = transit_client.get_origin_ids().await?;
+    let after = params
+        .cursor::<Stop>(&Method::GET, original_uri.path())?
+        .map(Cursor::into_inner);
+    let limit = params.limit();
+    let (stops, next) = transit_client
+        .get_stops_page(after, limit, &origins)
+        .await
+        .map_err(map_err)?;
+    let on_request = served_only_on_request(
+        &transit_client,
+        &stops.iter().map(|stop| &stop.id).collect::<Vec<_>>(),
+    )
+    .await
+    .map_err(map_err)?;
+    stops
+        .into_iter()
+        .map(|stop| {
+            let served_only_on_request = on_request.contains(&stop.id);
+            stop_hateoas(stop, base_url.clone()).map(|stop| ServedStopDto {
+                stop,
+                served_only_on_request,
+            })
+        })
+        .collect::<Vec<_>>()
+        .let_owned(|data| {
+            hateoas::Response::builder(
+                VecResponse::non_paginated(data),
+                base_url.clone(),
+            )
+            .link_to_option(
+                "next",
+                next.map(|next| StopsResource {
+                    after: Some(Cursor::from(next).encode()),
+                    limit: Some(limit),
+                }),
+            )
+            .build()
+            .json()
+        })
+        .let_owned(Ok)
+}
+
+/// Stop of a list, along with how it is served.
+#[derive(Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ServedStopDto<T> {
+    #[serde(flatten)]
+    pub stop: T,
+    /// Whether all trips calling at the stop today or later only stop on request.
+    pub served_only_on_request: bool,
+}
+
+/// The given stops, which are only served on request.
+pub(crate) async fn served_only_on_request(
+    transit_client: &Client<WebDatabase>,
+    ids: &[&Id<Stop>],
+) -> RequestResult<HashSet<Id<Stop>>> {
     transit_client
-        .get_stops(origins)
+        .get_service_summaries(ids)
+        .await?
+        .into_iter()
+        .filter(|(_, summary)| summary.served_only_on_request)
+        .map(|(id, _)| id)
+        .collect::<HashSet<_>>()
+        .let_owned(Ok)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StopDetailDto {
+    #[serde(flatten)]
+    stop: Stop,
+    /// Whether any trip calls at the stop today or later.
+    has_future_service: bool,
+    /// Whether all trips calling at the stop today or later only stop on request.
+    served_only_on_request: bool,
+}
+
+async fn get_stop(
+    OriginalUri(original_uri): OriginalUri,
+    Path(id): Path<String>,
+    State(WebState { transit_client, .. }): State<WebState>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+    headers: HeaderMap,
+) -> RouteResult<Response> {
+    let map_err = |why| {
+        RouteErrorResponse::from(why)
+            .with_method(&Method::GET)
+            .with_uri(original_uri.path())
+    };
+    let origins = transit_client.get_origin_ids().await?;
+    let stop = transit_client
+        .get_stop(Id::new(id), origins)
         .await
-        .map(|stops| {
-            stops
-                .into_iter()
-                .map(|stop| stop_hateoas(stop, base_url.clone()))
-                .collect::<Vec<_>>()
-                .let_owned(|data| VecResponse::non_paginated(data).hateoas().json())
+        .map_err(map_err)?;
+    let summary = transit_client
+        .get_service_summaries(&[&stop.id])
+        .await
+        .map_err(map_err)?
+        .remove(&stop.id);
+    let has_future_service = summary
+        .is_none_or(|summary| summary.has_service_since(Local::now().date_naive()));
+    let served_only_on_request =
+        summary.is_some_and(|summary| summary.served_only_on_request);
+    let etag = EntityTag::new(
+        stop.content.updated_at,
+        &(has_future_service, served_only_on_request),
+    );
+    stop_hateoas(stop, base_url)
+        .map(|stop| StopDetailDto {
+            stop,
+            has_future_service,
+            served_only_on_request,
+        })
+        .json()
+        .let_owned(|stop| Ok(etag.respond(&headers, stop)))
+}
+
+/// Source data of a subject, along with the merged value and which origin supplied
+/// each of its fields.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SourcesDto<T: Serialize> {
+    merged: T,
+    provenance: Provenance,
+    sources: Vec<WithOrigin<T>>,
+}
+
+async fn get_stop_sources(
+    OriginalUri(original_uri): OriginalUri,
+    Path(id): Path<String>,
+    State(WebState { transit_client, .. }): State<WebState>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<SourcesDto<Stop>> {
+    let origins = transit_client.get_origin_ids().await?;
+    transit_client
+        .get_stop_sources(Id::new(id), &origins)
+        .await
+        .map(|(merged, provenance, sources)| {
+            hateoas::Response::builder(
+                SourcesDto {
+                    merged: merged.content,
+                    provenance,
+                    sources,
+                },
+                base_url,
+            )
+            .link_to(
+                "self",
+                &StopSourcesResource {
+                    id: merged.id.clone(),
+                },
+            )
+            .link_to("stop", &StopResource { id: merged.id })
+            .build()
+            .json()
         })
         .map_err(|why| {
             RouteErrorResponse::from(why)
@@ -65,17 +346,24 @@ async fn get_stops(
         })
 }
 
-async fn get_stop(
+async fn get_stop_pathways(
     OriginalUri(original_uri): OriginalUri,
     Path(id): Path<String>,
     State(WebState { transit_client, .. }): State<WebState>,
     Extension(base_url): Extension<Arc<BaseUrl>>,
-) -> HateoasResult<Stop> {
+) -> HateoasResult<PathwayGraph> {
     let origins = transit_client.get_origin_ids().await?;
+    let id = Id::new(id);
     transit_client
-        .get_stop(Id::new(id), origins)
+        .get_station_pathway_graph(&id, &origins)
         .await
-        .map(|stop| stop_hateoas(stop, base_url.clone()).json())
+        .map(|graph| {
+            hateoas::Response::builder(graph, base_url)
+                .link_to("self", &StopPathwaysResource { id: id.clone() })
+                .link_to("stop", &StopResource { id })
+                .build()
+                .json()
+        })
         .map_err(|why| {
             RouteErrorResponse::from(why)
                 .with_method(&Method::GET)
@@ -83,15 +371,80 @@ async fn get_stop(
         })
 }
 
+/// A platform along with the key it is ordered by.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PlatformDto {
+    #[serde(flatten)]
+    stop: Stop,
+    /// Orders platform codes naturally when compared bytewise, e.g. `2` before
+    /// `10`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    platform_sort_key: Option<String>,
+}
+
+/// Children of the stop, e.g. the platforms of a station, in natural order of
+/// their platform codes. Children without platform code come last.
+async fn get_stop_platforms(
+    OriginalUri(original_uri): OriginalUri,
+    Path(id): Path<String>,
+    State(WebState { transit_client, .. }): State<WebState>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<VecResponse<hateoas::Response<PlatformDto>>> {
+    let origins = transit_client.get_origin_ids().await?;
+    let id = Id::new(id);
+    transit_client
+        .get_platforms(&id, &origins)
+        .await
+        .map(|platforms| {
+            platforms
+                .into_iter()
+                .map(|platform| {
+                    stop_hateoas(platform, base_url.clone()).map(|stop| PlatformDto {
+                        platform_sort_key: stop
+                            .platform_code
+                            .as_deref()
+                            .map(normalize::platform_sort_key),
+                        stop,
+                    })
+                })
+                .collect::<Vec<_>>()
+                .let_owned(|data| {
+                    hateoas::Response::builder(
+                        VecResponse::non_paginated(data),
+                        base_url,
+                    )
+                    .link_to("self", &StopPlatformsResource { id: id.clone() })
+                    .link_to("stop", &StopResource { id })
+                    .build()
+                    .json()
+                })
+        })
+        .map_err(|why| {
+            RouteErrorResponse::from(why)
+                .with_method(&Method::GET)
+                .with_uri(original_uri.path())
+        })
+}
+
+const SEARCH_DEFAULT_LIMIT: usize = 10;
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    limit: Option<usize>,
+}
+
 async fn search_stop(
     OriginalUri(original_uri): OriginalUri,
     Path(pattern): Path<String>,
     State(WebState { transit_client, .. }): State<WebState>,
+    Query(params): Query<SearchQuery>,
     Extension(base_url): Extension<Arc<BaseUrl>>,
 ) -> HateoasResult<VecResponse<hateoas::Response<StopNameSuggestion>>> {
     let origins = transit_client.get_origin_ids().await?;
+    let limit = params.limit.unwrap_or(SEARCH_DEFAULT_LIMIT);
     transit_client
-        .search_stop(pattern, &origins)
+        .search_stop(pattern, limit, &origins)
         .await
         .map(|stops| {
             stops
@@ -107,32 +460,69 @@ async fn search_stop(
         })
 }
 
+/// Queries shorter than this are rejected, as they match too many stops.
+const AUTOCOMPLETE_MIN_LENGTH: usize = 3;
+
+const AUTOCOMPLETE_DEFAULT_LIMIT: usize = 8;
+
+const AUTOCOMPLETE_MAX_LIMIT: usize = 20;
+
 #[derive(Deserialize)]
-struct NearbyQuery {
+struct AutocompleteQuery {
+    q: String,
+    limit: Option<usize>,
+}
+
+/// Minimal stop suggestion, to keep responses small while typing.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StopCompletion {
+    id: Id<Stop>,
+    name: String,
     latitude: f64,
     longitude: f64,
-    radius: Option<f64>,
 }
 
-async fn nearby(
+impl StopCompletion {
+    /// `None` for stops without name or coordinates, which are not completed.
+    fn from_stop(value: WithId<Stop>) -> Option<Self> {
+        let location = value.content.location?;
+        Some(Self {
+            id: value.id,
+            name: value.content.name?,
+            latitude: location.latitude,
+            longitude: location.longitude,
+        })
+    }
+}
+
+async fn autocomplete_stop(
     OriginalUri(original_uri): OriginalUri,
     State(WebState { transit_client, .. }): State<WebState>,
-    Query(params): Query<NearbyQuery>,
-    Extension(base_url): Extension<Arc<BaseUrl>>,
-) -> HateoasResult<VecResponse<hateoas::Response<WithDistance<Stop>>>> {
+    Query(params): Query<AutocompleteQuery>,
+) -> HateoasResult<VecResponse<StopCompletion>> {
+    let pattern = params.q.trim();
+    if pattern.chars().count() < AUTOCOMPLETE_MIN_LENGTH {
+        return Err(RouteErrorResponse::new(StatusCode::BAD_REQUEST)
+            .with_message(format!(
+                "query must have at least {} characters.",
+                AUTOCOMPLETE_MIN_LENGTH
+            ))
+            .with_method(&Method::GET)
+            .with_uri(original_uri.path()));
+    }
+    let limit = params
+        .limit
+        .unwrap_or(AUTOCOMPLETE_DEFAULT_LIMIT)
+        .clamp(1, AUTOCOMPLETE_MAX_LIMIT);
     let origins = transit_client.get_origin_ids().await?;
     transit_client
-        .find_nearby(
-            params.latitude,
-            params.longitude,
-            params.radius.unwrap_or(0.05),
-            &origins,
-        )
+        .autocomplete_stop(pattern, limit, &origins)
         .await
         .map(|stops| {
             stops
                 .into_iter()
-                .map(|stop| stop_with_distance_hateoas(stop, base_url.clone()))
+                .filter_map(StopCompletion::from_stop)
                 .collect::<Vec<_>>()
                 .let_owned(|data| VecResponse::non_paginated(data).hateoas().json())
         })
@@ -143,22 +533,84 @@ async fn nearby(
         })
 }
 
-fn stop_hateoas(
+#[derive(Deserialize)]
+struct NearbyQuery {
+    latitude: f64,
+    longitude: f64,
+    radius: Option<f64>,
+}
+
+impl Validate for NearbyQuery {
+    fn validate(&self) -> Result<(), query::InvalidParameter> {
+        query::latitude("latitude", self.latitude)?;
+        query::longitude("longitude", self.longitude)?;
+        if let Some(radius) = self.radius {
+            query::radius("radius", radius)?;
+        }
+        Ok(())
+    }
+}
+
+async fn nearby(
+    OriginalUri(original_uri): OriginalUri,
+    State(WebState { transit_client, .. }): State<WebState>,
+    ValidQuery(params): ValidQuery<NearbyQuery>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<VecResponse<hateoas::Response<ServedStopDto<WithDistance<Stop>>>>>
+{
+    let map_err = |why| {
+        RouteErrorResponse::from(why)
+            .with_method(&Method::GET)
+            .with_uri(original_uri.path())
+    };
+    let origins = transit_client.get_origin_ids().await?;
+    let stops = transit_client
+        .find_nearby(
+            params.latitude,
+            params.longitude,
+            params.radius.unwrap_or(0.05),
+            &origins,
+        )
+        .await
+        .map_err(map_err)?;
+    let on_request = served_only_on_request(
+        &transit_client,
+        &stops
+            .iter()
+            .map(|stop| &stop.content.id)
+            .collect::<Vec<_>>(),
+    )
+    .await
+    .map_err(map_err)?;
+    stops
+        .into_iter()
+        .map(|stop| {
+            let served_only_on_request = on_request.contains(&stop.content.id);
+            stop_with_distance_hateoas(stop, served_only_on_request, base_url.clone())
+        })
+        .collect::<Vec<_>>()
+        .let_owned(|data| Ok(VecResponse::non_paginated(data).hateoas().json()))
+}
+
+pub(crate) fn stop_hateoas(
     stop: WithId<Stop>,
     base_url: Arc<BaseUrl>,
 ) -> hateoas::Response<Stop> {
     let location = stop.content.location.clone();
     hateoas::Response::builder(stop.content, base_url)
-        .link("self", resource!("/{}", stop.id.raw()))
-        .link("trips", super::trips::resource!("?stop={}", stop.id.raw()))
-        .link_option(
+        .link_to(
+            "self",
+            &StopResource {
+                id: stop.id.clone(),
+            },
+        )
+        .link_to("trips", &TripsResource::at_stop(stop.id))
+        .link_to_option(
             "nearby",
-            location.map(|location| {
-                resource!(
-                    "/nearby?latitude={}&longitude={}&radius=1",
-                    location.latitude,
-                    location.longitude
-                )
+            location.map(|location| NearbyStopsResource {
+                latitude: location.latitude,
+                longitude: location.longitude,
+                radius: Some(1.0),
             }),
         )
         .build()
@@ -166,36 +618,44 @@ fn stop_hateoas(
 
 pub fn stop_with_distance_hateoas(
     stop: WithDistance<WithId<Stop>>,
+    served_only_on_request: bool,
     base_url: Arc<BaseUrl>,
-) -> hateoas::Response<WithDistance<Stop>> {
+) -> hateoas::Response<ServedStopDto<WithDistance<Stop>>> {
     let id = &stop.content.id;
     hateoas::Response::builder(
-        WithDistance::new(stop.distance_km, stop.content.content),
+        ServedStopDto {
+            stop: WithDistance::new(stop.distance_km, stop.content.content),
+            served_only_on_request,
+        },
         base_url,
     )
-    .link("self", resource!("/{}", id.raw()))
-    .link("trips", super::trips::resource!("?stop={}", id.raw()))
-    .link("lines", super::lines::resource!("?stop={}", id.raw()))
+    .link_to("self", &StopResource { id: id.clone() })
+    .link_to("trips", &TripsResource::at_stop(id.clone()))
+    .link_to(
+        "lines",
+        &LinesResource {
+            stop: Some(id.clone()),
+        },
+    )
     .build()
 }
 
-fn stop_suggestion_hateoas(
+pub(crate) fn stop_suggestion_hateoas(
     stop: StopNameSuggestion,
     base_url: Arc<BaseUrl>,
 ) -> hateoas::Response<StopNameSuggestion> {
     let id = stop.id.clone();
-    let latitude = stop.latitude.clone();
-    let longitude = stop.longitude.clone();
+    let location = stop.location.clone();
     hateoas::Response::builder(stop, base_url)
-        .link("self", resource!("/{}", id.raw()))
-        .link("lines", super::lines::resource!("?stop={}", id.raw()))
-        .link(
+        .link_to("self", &StopResource { id: id.clone() })
+        .link_to("lines", &LinesResource { stop: Some(id) })
+        .link_to_option(
             "nearby",
-            resource!(
-                "/nearby?latitude={}&longitude={}&radius=1",
-                latitude,
-                longitude
-            ),
+            location.map(|location| NearbyStopsResource {
+                latitude: location.latitude,
+                longitude: location.longitude,
+                radius: Some(1.0),
+            }),
         )
         .build()
 }
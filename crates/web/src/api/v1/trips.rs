@@ -1,31 +1,46 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use axum::{
-    extract::{OriginalUri, Query, State},
-    http::{Method, StatusCode},
+    extract::{OriginalUri, Path, Query, State},
+    http::{header::CACHE_CONTROL, HeaderMap, HeaderName, Method, StatusCode},
     routing::{get, on},
     Extension, Json, Router,
 };
-use chrono::{DateTime, Duration, Local};
+use chrono::{DateTime, Duration, Local, NaiveDate};
 use model::{
     agency::Agency,
-    line::Line,
+    calendar::{Service, ServiceTag},
+    locale::Locale,
+    stop::Stop,
     trip::Trip,
-    trip_instance::{StopTimeInstance, TripInstance, TripInstanceInfo},
+    trip_instance::{
+        CoupledTrip, StopTimeInstance, TripInstance, TripInstanceCursor,
+        TripInstanceId, TripInstanceInfo, WindowMode,
+    },
+    trip_update::{HistoricDelay, StopTimeStatus, TripStatus, TripUpdate},
     DateTimeRange, ExampleData, WithId,
 };
+use public_transport::{client::TripInstantiationOptions, not_found_to_none};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use utility::{id::Id, let_also::LetAlso, serde::date_time};
+use utility::{
+    geo,
+    id::Id,
+    let_also::LetAlso,
+    serde::{comma_separated, date_time},
+};
 
 use crate::{
     common::{
-        route_not_found, schema, HateoasResult, RouteErrorResponse, VecResponse,
-        METHOD_FILTER_ALL,
+        cursor::{Cursor, PageParams},
+        locale::requested_labels,
+        query::{self, ValidQuery, Validate},
+        route_not_found, schema, HateoasResult, RouteErrorResponse, RouteResult,
+        VecResponse, METHOD_FILTER_ALL,
     },
-    hateoas,
+    hateoas::{self, PathParams, Resource},
     middleware::base_url::{base_url_middleware, BaseUrl},
-    RouteResult, WebState,
+    WebState,
 };
 
 macro_rules! resource {
@@ -33,16 +48,167 @@ macro_rules! resource {
         crate::api::v1::resource!("/trips{}", format_args!($($arg)*))
     };
 }
-pub(crate) use resource;
 
-use super::{agencies::agency_hateoas, lines::line_hateoas};
+/// Trip instances, e.g., those calling at a stop.
+#[derive(Default)]
+pub(crate) struct TripsResource {
+    pub stop: Option<Id<Stop>>,
+    pub end: Option<DateTime<Local>>,
+    pub window: WindowMode,
+    pub after: Option<TripInstanceCursor>,
+    pub limit: Option<usize>,
+    pub exclude_tags: Vec<ServiceTag>,
+    /// Locale of the labels to include.
+    pub labels: Option<Locale>,
+}
+
+impl TripsResource {
+    pub fn at_stop(stop: Id<Stop>) -> Self {
+        Self {
+            stop: Some(stop),
+            ..Default::default()
+        }
+    }
+}
+
+impl Resource for TripsResource {
+    const ROUTE: &'static str = "/";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn query_params(&self) -> Vec<(&'static str, Option<String>)> {
+        let tags = self.exclude_tags.iter().map(|tag| tag.to_string());
+        vec![
+            ("stop", self.stop.as_ref().map(|id| id.raw())),
+            (
+                "end",
+                self.end
+                    .map(|end| end.format("%Y-%m-%dT%H:%M:%S").to_string()),
+            ),
+            (
+                "window",
+                (self.window == WindowMode::ArriveBetween)
+                    .then(|| "arriveBetween".to_owned()),
+            ),
+            ("after", self.after.as_ref().map(|after| after.to_string())),
+            ("limit", self.limit.map(|limit| limit.to_string())),
+            (
+                "exclude_tags",
+                (!self.exclude_tags.is_empty())
+                    .then(|| tags.collect::<Vec<_>>().join(",")),
+            ),
+            ("include_labels", self.labels.map(|_| "true".to_owned())),
+            ("lang", self.labels.map(|locale| locale.as_str().to_owned())),
+        ]
+    }
+}
+
+/// A single trip with its scheduled stop times, not instantiated for a date.
+pub(crate) struct TripResource {
+    pub id: Id<Trip>,
+}
+
+impl Resource for TripResource {
+    const ROUTE: &'static str = "/:id";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn path_params(&self) -> impl PathParams {
+        [self.id.raw()]
+    }
+}
+
+/// A page of all trips, as stored.
+pub(crate) struct TripsDebugResource {
+    pub after: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl Resource for TripsDebugResource {
+    const ROUTE: &'static str = "/debug";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn query_params(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("after", self.after.clone()),
+            ("limit", self.limit.map(|limit| limit.to_string())),
+        ]
+    }
+}
+
+/// GeoJSON of a trip instance.
+pub(crate) struct TripMapResource {
+    pub id: Id<Trip>,
+    pub date: NaiveDate,
+    pub instance: u32,
+}
+
+impl Resource for TripMapResource {
+    const ROUTE: &'static str = "/:id/instances/:date/map";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn path_params(&self) -> impl PathParams {
+        [self.id.raw(), self.date.to_string()]
+    }
+
+    fn query_params(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![(
+            "instance",
+            (self.instance != 0).then(|| self.instance.to_string()),
+        )]
+    }
+}
+
+/// Delays of a trip instance, as they were announced over time.
+pub(crate) struct TripHistoryResource {
+    pub id: Id<Trip>,
+    pub date: NaiveDate,
+}
+
+impl Resource for TripHistoryResource {
+    const ROUTE: &'static str = "/:id/history";
+
+    fn module() -> String {
+        resource!("")
+    }
+
+    fn path_params(&self) -> impl PathParams {
+        [self.id.raw()]
+    }
+
+    fn query_params(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![("date", Some(self.date.to_string()))]
+    }
+}
+
+use super::{
+    agencies::agency_hateoas,
+    lines::{line_hateoas, LineDto, LineResource},
+    stops::StopResource,
+};
 
 pub(crate) fn routes(state: WebState) -> Router {
     Router::new()
         .route("/schema", get(schema::<TripInstanceDto>))
-        .route("/", get(get_trips))
-        .route("/debug", get(get_trips_debug))
-        .layer(axum::middleware::from_fn(base_url_middleware))
+        .route(TripsResource::ROUTE, get(get_trips))
+        .route(TripsDebugResource::ROUTE, get(get_trips_debug))
+        .route(TripResource::ROUTE, get(get_trip))
+        .route(TripMapResource::ROUTE, get(get_trip_map))
+        .route(TripHistoryResource::ROUTE, get(get_trip_history))
+        .layer(axum::middleware::from_fn_with_state(
+            state.base_url.clone(),
+            base_url_middleware,
+        ))
         .with_state(state)
         .fallback_service(on(METHOD_FILTER_ALL, route_not_found))
 }
@@ -56,112 +222,404 @@ struct TripsQuery {
 
     #[serde(deserialize_with = "date_time::deserialize_local_option", default)]
     end: Option<DateTime<Local>>,
+
+    /// Whether trips have to depart or arrive between `start` and `end`.
+    #[serde(default)]
+    window: WindowMode,
+
+    /// Only return trips after the trip this cursor points to.
+    after: Option<String>,
+
+    /// Maximum number of trips to return.
+    limit: Option<usize>,
+
+    /// Comma separated service tags. Trips of services with any of them are
+    /// omitted, e.g., `school`.
+    #[serde(deserialize_with = "comma_separated::deserialize", default)]
+    exclude_tags: Vec<ServiceTag>,
+
+    /// Includes localized labels of enum values, e.g. of pickup types.
+    #[serde(default)]
+    include_labels: bool,
+
+    /// Language of the labels. Defaults to the `Accept-Language` header.
+    lang: Option<Locale>,
+}
+
+impl Validate for TripsQuery {
+    fn validate(&self) -> Result<(), query::InvalidParameter> {
+        query::ordered(self.start.as_ref(), "end", self.end.as_ref())
+    }
 }
 
 async fn get_trips_debug(
     OriginalUri(original_uri): OriginalUri,
-    State(WebState { transit_client, .. }): State<WebState>,
-    Query(params): Query<TripsQuery>,
-    Extension(_base_url): Extension<Arc<BaseUrl>>,
-) -> RouteResult<Json<VecResponse<WithId<Trip>>>> {
+    State(WebState {
+        transit_client,
+        clock,
+        ..
+    }): State<WebState>,
+    ValidQuery(params): ValidQuery<TripsQuery>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<VecResponse<WithId<Trip>>> {
     let origins = transit_client.get_origin_ids().await?;
-    let start = params.start.unwrap_or(Local::now());
+    let start = params.start.unwrap_or(clock.now());
     let end = params.end.unwrap_or(start + Duration::hours(4));
     // get at stop if query stops
     if let Some(stop) = params.stop {
         let id = Id::new(stop);
         transit_client
-            .get_all_trips_via_stops(&[&id], start, end, &origins)
+            .get_all_trips_via_stops(&[&id], start, end, params.window, &origins)
             .await
             .map_err(|why| {
                 RouteErrorResponse::from(why)
                     .with_method(&Method::GET)
                     .with_uri(original_uri.path())
             })?
-            .let_owned(|trips| Ok(VecResponse::non_paginated(trips).json()))
-    // otherwise get all
+            .let_owned(|trips| Ok(VecResponse::non_paginated(trips).hateoas().json()))
+    // otherwise page through all
     } else {
+        let page = PageParams {
+            after: params.after,
+            limit: params.limit,
+        };
+        let after = page
+            .cursor::<Trip>(&Method::GET, original_uri.path())?
+            .map(Cursor::into_inner);
+        let limit = page.limit();
+        let (trips, next) = transit_client
+            .get_trips_page(after, limit, &origins)
+            .await
+            .map_err(|why| {
+                RouteErrorResponse::from(why)
+                    .with_method(&Method::GET)
+                    .with_uri(original_uri.path())
+            })?;
+        hateoas::Response::builder(VecResponse::non_paginated(trips), base_url)
+            .link_to_option(
+                "next",
+                next.map(|next| TripsDebugResource {
+                    after: Some(Cursor::from(next).encode()),
+                    limit: Some(limit),
+                }),
+            )
+            .build()
+            .json()
+            .let_owned(Ok)
+    }
+}
+
+async fn get_trips(
+    OriginalUri(original_uri): OriginalUri,
+    State(WebState {
+        transit_client,
+        clock,
+        ..
+    }): State<WebState>,
+    ValidQuery(params): ValidQuery<TripsQuery>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+    headers: HeaderMap,
+) -> HateoasResult<VecResponse<hateoas::Response<TripInstanceDto>>> {
+    let origins = transit_client.get_origin_ids().await?;
+    let labels = requested_labels(params.include_labels, params.lang, &headers);
+    let after = params
+        .after
+        .as_deref()
+        .map(str::parse::<TripInstanceCursor>)
+        .transpose()
+        .map_err(|why| {
+            RouteErrorResponse::new(StatusCode::BAD_REQUEST)
+                .with_message(why)
+                .with_method(&Method::GET)
+                .with_uri(original_uri.path())
+        })?;
+    // trips before the cursor do not need to be instantiated at all.
+    let start = match (params.start, after.as_ref()) {
+        (Some(start), Some(after)) => start.max(after.time),
+        (None, Some(after)) => after.time,
+        (start, None) => start.unwrap_or(clock.now()),
+    };
+    let end = params.end.unwrap_or(start + Duration::hours(4));
+    let map_err = |why| {
+        RouteErrorResponse::from(why)
+            .with_method(&Method::GET)
+            .with_uri(original_uri.path())
+    };
+    // get at stop if query stops
+    let Some(stop) = params.stop.clone() else {
         //transit_client.get_trips(origins).await
         return Err(RouteErrorResponse::new(StatusCode::BAD_REQUEST)
             .with_message("please narrow your request.")
             .with_method(&Method::GET)
             .with_uri(original_uri.path()));
+    };
+    let id = Id::new(stop);
+    let trips = transit_client
+        .get_all_trips_via_stops(&[&id], start, end, params.window, &origins)
+        .await
+        .map_err(map_err)?;
+    let mut trip_instances = transit_client
+        .instanciate_trips_include(
+            trips,
+            DateTimeRange::new(start, end),
+            &TripInstantiationOptions {
+                window_mode: params.window,
+                stop_ids_of_interest: Some(&[&id]),
+                ..TripInstantiationOptions::all_references()
+            },
+            &origins,
+        )
+        .await
+        .map_err(map_err)?;
+    trip_instances.retain(|trip| !trip.has_any_tag(&params.exclude_tags));
+    let (mut trip_instances, next) =
+        TripInstance::page(trip_instances, after.as_ref(), params.limit);
+    // platform changes are shown right away, other realtime data is polled.
+    let update_ids = trip_instances
+        .iter()
+        .filter_map(|trip| trip.info.instance_id.update_id())
+        .collect::<Vec<_>>();
+    let updates = transit_client
+        .get_realtime_for_trip_instances(&update_ids, &origins)
+        .await
+        .map_err(map_err)?
+        .into_iter()
+        .map(|update| (update.id, update.content))
+        .collect::<HashMap<_, _>>();
+    for trip in trip_instances.iter_mut() {
+        let Some(update_id) = trip.info.instance_id.update_id() else {
+            continue;
+        };
+        if let Some(update) = updates.get(&update_id) {
+            trip.apply_platform_changes(update);
+        }
     }
+    let next = next.map(|cursor| TripsResource {
+        stop: params.stop.clone().map(Id::new),
+        end: Some(end),
+        window: params.window,
+        after: Some(cursor),
+        limit: params.limit,
+        exclude_tags: params.exclude_tags.clone(),
+        labels,
+    });
+    trip_instances
+        .into_iter()
+        .map(|trip| {
+            trip_hateoas(
+                TripInstanceDto::new(trip, base_url.clone(), labels),
+                base_url.clone(),
+            )
+        })
+        .collect::<Vec<_>>()
+        .let_owned(|data| {
+            hateoas::Response::builder(VecResponse::non_paginated(data), base_url)
+                .link_to_option("next", next)
+                .build()
+                .json()
+        })
+        .let_owned(Ok)
 }
 
-async fn get_trips(
+/// The schedule only variant of a trip map changes with imports at most.
+const SCHEDULE_CACHE_CONTROL: &str = "public, max-age=3600";
+
+/// Realtime data changes all the time.
+const REALTIME_CACHE_CONTROL: &str = "no-cache";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TripMapQuery {
+    /// Index of the departure of a frequency based trip on the day, see
+    /// [`TripInstanceId`]. Other trips only have the instance `0`.
+    #[serde(default)]
+    instance: u32,
+
+    /// Points of the line closer than this many meters to the simplified line are
+    /// omitted. Not simplified, if not specified.
+    tolerance: Option<f64>,
+
+    /// Omits realtime data and the current segment, so that the response only
+    /// depends on the schedule and can be cached.
+    #[serde(default)]
+    schedule_only: bool,
+
+    /// Includes localized labels of the realtime status.
+    #[serde(default)]
+    include_labels: bool,
+
+    /// Language of the labels. Defaults to the `Accept-Language` header.
+    lang: Option<Locale>,
+}
+
+async fn get_trip_map(
     OriginalUri(original_uri): OriginalUri,
-    State(WebState { transit_client, .. }): State<WebState>,
-    Query(params): Query<TripsQuery>,
+    Path((id, date)): Path<(String, NaiveDate)>,
+    State(WebState {
+        transit_client,
+        clock,
+        ..
+    }): State<WebState>,
+    Query(params): Query<TripMapQuery>,
     Extension(base_url): Extension<Arc<BaseUrl>>,
-) -> HateoasResult<VecResponse<hateoas::Response<TripInstanceDto>>> {
+    headers: HeaderMap,
+) -> RouteResult<(
+    [(HeaderName, &'static str); 1],
+    Json<hateoas::Response<TripMapDto>>,
+)> {
     let origins = transit_client.get_origin_ids().await?;
-    let start = params.start.unwrap_or(Local::now());
-    let end = params.end.unwrap_or(start + Duration::hours(4));
-    // get at stop if query stops
-    if let Some(stop) = params.stop {
-        let id = Id::new(stop);
-        let trips = transit_client
-            .get_all_trips_via_stops(&[&id], start, end, &origins)
+    let id = Id::new(id);
+    let trip = transit_client
+        .get_trip_instance(id.clone(), date, params.instance, &origins)
+        .await
+        .map_err(|why| {
+            RouteErrorResponse::from(why)
+                .with_method(&Method::GET)
+                .with_uri(original_uri.path())
+        })?;
+    let update = if params.schedule_only {
+        None
+    } else {
+        transit_client
+            .get_realtime_for_trip(&id, date, params.instance, &origins)
             .await
+            .let_owned(not_found_to_none)
             .map_err(|why| {
                 RouteErrorResponse::from(why)
                     .with_method(&Method::GET)
                     .with_uri(original_uri.path())
-            })?;
-        transit_client
-            .instanciate_trips_include(
-                trips,
-                DateTimeRange::new(start, end),
-                Some(&[&id]),
-                true,
-                true,
-                true,
-                &origins,
-            )
-            .await
-    // otherwise get all
+            })?
+            .map(|update| update.content)
+    };
+    let cache_control = if params.schedule_only {
+        SCHEDULE_CACHE_CONTROL
     } else {
-        //transit_client.get_trips(origins).await
-        return Err(RouteErrorResponse::new(StatusCode::BAD_REQUEST)
-            .with_message("please narrow your request.")
-            .with_method(&Method::GET)
-            .with_uri(original_uri.path()));
-    }
-    .map(|trip_instances| {
-        trip_instances
-            .let_owned(TripInstance::sorted)
-            .into_iter()
-            .map(|trip| {
-                trip_hateoas(
-                    TripInstanceDto {
-                        info: trip.info,
-                        stops: trip
-                            .stops
-                            .into_iter()
-                            .map(|stop_time| {
-                                stop_time_hateoas(stop_time, base_url.clone())
-                            })
-                            .collect::<Vec<_>>(),
-                        stop_of_interest: trip.stop_of_interest,
-                        line: trip
-                            .line
-                            .map(|line| line_hateoas(line, base_url.clone())),
-                        agency: trip
-                            .agency
-                            .map(|agency| agency_hateoas(agency, base_url.clone())),
-                    },
-                    base_url.clone(),
-                )
-            })
-            .collect::<Vec<_>>()
-            .let_owned(|data| VecResponse::non_paginated(data).hateoas().json())
-    })
-    .map_err(|why| {
+        REALTIME_CACHE_CONTROL
+    };
+    let tolerance_km = params.tolerance.unwrap_or_default() / 1000.0;
+    let labels = requested_labels(params.include_labels, params.lang, &headers);
+    let now = (!params.schedule_only).then(|| clock.now());
+    let map = TripMapDto::new(trip, date, update, tolerance_km, now, labels);
+    hateoas::Response::builder(map, base_url)
+        .link_to(
+            "self",
+            &TripMapResource {
+                id,
+                date,
+                instance: params.instance,
+            },
+        )
+        .build()
+        .json()
+        .let_owned(|map| Ok(([(CACHE_CONTROL, cache_control)], map)))
+}
+
+#[derive(Deserialize)]
+struct TripHistoryQuery {
+    /// Start date of the trip instance. Defaults to today.
+    date: Option<NaiveDate>,
+}
+
+impl Validate for TripHistoryQuery {}
+
+async fn get_trip_history(
+    OriginalUri(original_uri): OriginalUri,
+    Path(id): Path<String>,
+    State(WebState {
+        transit_client,
+        clock,
+        ..
+    }): State<WebState>,
+    ValidQuery(params): ValidQuery<TripHistoryQuery>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<VecResponse<HistoricDelay>> {
+    let origins = transit_client.get_origin_ids().await?;
+    let id = Id::new(id);
+    let date = params.date.unwrap_or(clock.now().date_naive());
+    transit_client
+        .get_historic_delays(&id, date, &origins)
+        .await
+        .map(|delays| {
+            hateoas::Response::builder(VecResponse::non_paginated(delays), base_url)
+                .link_to("self", &TripHistoryResource { id, date })
+                .build()
+                .json()
+        })
+        .map_err(|why| {
+            RouteErrorResponse::from(why)
+                .with_method(&Method::GET)
+                .with_uri(original_uri.path())
+        })
+}
+
+/// Summary of the days a service is available on, see `/services/{id}/days` for
+/// all of them.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ServiceSummaryDto {
+    id: Id<Service>,
+    first_day: Option<NaiveDate>,
+    last_day: Option<NaiveDate>,
+    day_count: usize,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TripDetailDto {
+    #[serde(flatten)]
+    trip: WithId<Trip>,
+    /// Not set, if the trip has no service.
+    service: Option<ServiceSummaryDto>,
+}
+
+/// The trip as stored and merged across origins, with its stop times ordered by
+/// stop sequence. Meant for tooling, clients should use trip instances.
+async fn get_trip(
+    OriginalUri(original_uri): OriginalUri,
+    Path(id): Path<String>,
+    State(WebState { transit_client, .. }): State<WebState>,
+    Extension(base_url): Extension<Arc<BaseUrl>>,
+) -> HateoasResult<TripDetailDto> {
+    let map_err = |why| {
         RouteErrorResponse::from(why)
             .with_method(&Method::GET)
             .with_uri(original_uri.path())
-    })
+    };
+    let origins = transit_client.get_origin_ids().await?;
+    let mut trip = transit_client
+        .get_trip(Id::new(id), origins)
+        .await
+        .map_err(map_err)?;
+    trip.content.stops.sort_by_key(|stop| stop.stop_sequence);
+    let service = match trip.content.service_id {
+        Some(service_id) => {
+            let days = transit_client
+                .get_service(&service_id)
+                .await
+                .map_err(map_err)?
+                .available_days(None, None);
+            Some(ServiceSummaryDto {
+                id: service_id,
+                first_day: days.first().copied(),
+                last_day: days.last().copied(),
+                day_count: days.len(),
+            })
+        }
+        None => None,
+    };
+    let trip_resource = TripResource {
+        id: trip.id.clone(),
+    };
+    let line_resource = LineResource {
+        id: trip.content.line_id.clone(),
+    };
+    hateoas::Response::builder(TripDetailDto { trip, service }, base_url)
+        .link_to("self", &trip_resource)
+        .link_to("line", &line_resource)
+        .build()
+        .json()
+        .let_owned(Ok)
 }
 
 pub fn trip_hateoas(
@@ -170,33 +628,111 @@ pub fn trip_hateoas(
 ) -> hateoas::Response<TripInstanceDto> {
     let id = trip.info.trip_id.clone();
     hateoas::Response::builder(trip, base_url)
-        .link("self", resource!("/{}", id.raw()))
+        .link_to("self", &TripResource { id })
         .build()
 }
 
 pub fn stop_time_hateoas(
-    stop_time: StopTimeInstance,
+    stop_time: StopTimeDto,
     base_url: Arc<BaseUrl>,
-) -> hateoas::Response<StopTimeInstance> {
-    let id = stop_time.stop_id.clone();
+) -> hateoas::Response<StopTimeDto> {
+    let id = stop_time.stop_time.stop_id.clone();
     hateoas::Response::builder(stop_time, base_url)
-        .link_option(
-            "stop",
-            id.map(|id| super::stops::resource!("/{}", id.raw())),
-        )
+        .link_to_option("stop", id.map(|id| StopResource { id }))
         .build()
 }
 
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StopTimeDto {
+    #[serde(flatten)]
+    pub stop_time: StopTimeInstance,
+    pub labels: Option<StopTimeLabels>,
+}
+
+/// Localized labels of the enum values of a stop time.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StopTimeLabels {
+    pub pickup_type: &'static str,
+    pub drop_off_type: &'static str,
+}
+
+impl StopTimeDto {
+    pub fn new(stop_time: StopTimeInstance, labels: Option<Locale>) -> Self {
+        let labels = labels.map(|locale| StopTimeLabels {
+            pickup_type: stop_time.pickup_type.pickup_label().get(locale),
+            drop_off_type: stop_time.drop_off_type.drop_off_label().get(locale),
+        });
+        Self { stop_time, labels }
+    }
+}
+
 #[serde_with::skip_serializing_none]
 #[derive(Debug, Clone, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct TripInstanceDto {
     #[serde(flatten)]
     pub info: TripInstanceInfo,
-    pub stops: Vec<hateoas::Response<StopTimeInstance>>,
-    pub stop_of_interest: Option<StopTimeInstance>,
-    pub line: Option<hateoas::Response<Line>>,
+    pub stops: Vec<hateoas::Response<StopTimeDto>>,
+    pub stop_of_interest: Option<StopTimeDto>,
+    pub line: Option<hateoas::Response<LineDto>>,
     pub agency: Option<hateoas::Response<Agency>>,
+    pub secondary_agencies: Vec<hateoas::Response<Agency>>,
+    pub coupled_with: Vec<CoupledTrip>,
+    pub labels: Option<TripInstanceLabels>,
+}
+
+/// Localized labels of the enum values of a trip instance.
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TripInstanceLabels {
+    pub line_kind: Option<&'static str>,
+}
+
+impl TripInstanceDto {
+    /// Includes labels in the locale, if any.
+    pub fn new(
+        trip: TripInstance,
+        base_url: Arc<BaseUrl>,
+        labels: Option<Locale>,
+    ) -> Self {
+        let line = trip.line.map(|line| line_hateoas(line, base_url.clone()));
+        Self {
+            info: trip.info,
+            stops: trip
+                .stops
+                .into_iter()
+                .map(|stop_time| {
+                    stop_time_hateoas(
+                        StopTimeDto::new(stop_time, labels),
+                        base_url.clone(),
+                    )
+                })
+                .collect::<Vec<_>>(),
+            stop_of_interest: trip
+                .stop_of_interest
+                .map(|stop_time| StopTimeDto::new(stop_time, labels)),
+            labels: labels.map(|locale| TripInstanceLabels {
+                // labels the kind as serialized, i.e. after the v1 mapping.
+                line_kind: line
+                    .as_ref()
+                    .map(|line| line.content.line.kind.label().get(locale)),
+            }),
+            line,
+            agency: trip
+                .agency
+                .map(|agency| agency_hateoas(agency, base_url.clone())),
+            secondary_agencies: trip
+                .secondary_agencies
+                .into_iter()
+                .map(|agency| agency_hateoas(agency, base_url.clone()))
+                .collect(),
+            coupled_with: trip.coupled_with,
+        }
+    }
 }
 
 impl ExampleData for TripInstanceDto {
@@ -204,15 +740,308 @@ impl ExampleData for TripInstanceDto {
         TripInstanceDto {
             info: TripInstanceInfo {
                 trip_id: Id::new("eine-id".to_owned()),
+                instance_id: TripInstanceId::new(
+                    &Id::new("eine-id".to_owned()),
+                    NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                    0,
+                ),
                 line_id: Id::new("eine-line".to_owned()),
                 service_id: Some(Id::new(123)),
                 headsign: Some("Moin Moin!".to_owned()),
                 short_name: None,
+                tags: vec![],
             },
             stops: vec![], // TODO!
             stop_of_interest: None,
             line: None,
             agency: None,
+            secondary_agencies: vec![],
+            coupled_with: vec![],
+            labels: None,
+        }
+    }
+}
+
+/// GeoJSON `FeatureCollection` to draw a trip instance on a map. It consists of a
+/// `LineString` connecting the stops and a `Point` per stop.
+///
+/// The geometry only depends on the schedule. Realtime data is confined to the
+/// properties, so that the schedule only variant can be cached.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename = "FeatureCollection")]
+pub struct TripMapDto {
+    features: Vec<Feature>,
+    properties: TripMapProperties,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TripMapProperties {
+    trip_id: Id<Trip>,
+    instance_id: TripInstanceId,
+    date: NaiveDate,
+    headsign: Option<String>,
+    status: Option<TripStatus>,
+    /// Index of the stop the vehicle departed from last. The vehicle is between
+    /// this stop and the following one. Omitted for schedule only maps.
+    current_segment: Option<usize>,
+    labels: Option<StatusLabels>,
+}
+
+/// Localized label of a realtime status.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StatusLabels {
+    pub status: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename = "Feature")]
+struct Feature {
+    geometry: Geometry,
+    properties: FeatureProperties,
+}
+
+/// Coordinates are `[longitude, latitude]`, as required by GeoJSON.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type")]
+pub(crate) enum Geometry {
+    LineString {
+        coordinates: Vec<[f64; 2]>,
+    },
+    Point {
+        coordinates: [f64; 2],
+    },
+    /// Closed rings, the first of which is the boundary.
+    Polygon {
+        coordinates: Vec<Vec<[f64; 2]>>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum FeatureProperties {
+    Route {
+        source: GeometrySource,
+    },
+    #[serde(rename_all = "camelCase")]
+    Stop {
+        /// Position of the stop within the trip, see `currentSegment`.
+        index: usize,
+        stop_sequence: i32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        arrival_time: Option<DateTime<Local>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        departure_time: Option<DateTime<Local>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        realtime: Option<StopRealtime>,
+    },
+}
+
+/// Where the line geometry comes from.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum GeometrySource {
+    /// Straight lines between consecutive stops. Shapes are not linked to trips
+    /// yet.
+    Stops,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StopRealtime {
+    status: StopTimeStatus,
+    arrival_time: Option<DateTime<Local>>,
+    departure_time: Option<DateTime<Local>>,
+    platform: Option<String>,
+    platform_changed: bool,
+    labels: Option<StatusLabels>,
+}
+
+impl TripMapDto {
+    /// Includes labels of the realtime status in the locale, if any. Without
+    /// `now`, the current segment is omitted, e.g. for schedule only maps.
+    fn new(
+        trip: TripInstance,
+        date: NaiveDate,
+        update: Option<TripUpdate>,
+        tolerance_km: f64,
+        now: Option<DateTime<Local>>,
+        labels: Option<Locale>,
+    ) -> Self {
+        let current_segment =
+            now.and_then(|now| trip.current_segment(update.as_ref(), now));
+        let points = trip
+            .stops
+            .iter()
+            .filter_map(|stop| stop.location.as_ref())
+            .map(|location| (location.latitude, location.longitude))
+            .collect::<Vec<_>>();
+        let line = Feature {
+            geometry: Geometry::LineString {
+                coordinates: geo::simplify(&points, tolerance_km)
+                    .into_iter()
+                    .map(|(latitude, longitude)| [longitude, latitude])
+                    .collect(),
+            },
+            properties: FeatureProperties::Route {
+                source: GeometrySource::Stops,
+            },
+        };
+        let stops =
+            trip.stops
+                .into_iter()
+                .enumerate()
+                .filter_map(|(index, mut stop)| {
+                    let location = stop.location.clone()?;
+                    let realtime = update
+                        .as_ref()
+                        .and_then(|update| {
+                            update.stop_time_update(stop.stop_sequence)
+                        })
+                        .map(|realtime| {
+                            stop.apply_platform_change(realtime);
+                            StopRealtime {
+                                status: realtime.status.clone(),
+                                arrival_time: realtime.arrival_time,
+                                departure_time: realtime.departure_time,
+                                platform: realtime.platform.clone(),
+                                platform_changed: stop.platform_changed,
+                                labels: labels.map(|locale| StatusLabels {
+                                    status: realtime.status.label().get(locale),
+                                }),
+                            }
+                        });
+                    Some(Feature {
+                        geometry: Geometry::Point {
+                            coordinates: [location.longitude, location.latitude],
+                        },
+                        properties: FeatureProperties::Stop {
+                            index,
+                            stop_sequence: stop.stop_sequence,
+                            name: stop.stop_name,
+                            arrival_time: stop.arrival_time,
+                            departure_time: stop.departure_time,
+                            realtime,
+                        },
+                    })
+                });
+        Self {
+            features: std::iter::once(line).chain(stops).collect(),
+            properties: TripMapProperties {
+                trip_id: trip.info.trip_id,
+                instance_id: trip.info.instance_id,
+                date,
+                headsign: trip.info.headsign,
+                labels: labels.zip(update.as_ref()).map(|(locale, update)| {
+                    StatusLabels {
+                        status: update.status.label().get(locale),
+                    }
+                }),
+                status: update.map(|update| update.status),
+                current_segment,
+            },
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use model::{
+        stop::Location,
+        trip::{PickupDropOffType, StopTime},
+    };
+    use public_transport::client::instantiate_trip_naive;
+
+    use super::*;
+
+    /// Instance of a trip departing at 08:00, 08:04 and 08:10 on the date, with
+    /// the locations of its stops.
+    fn instance(date: NaiveDate) -> TripInstance {
+        let stops = [0, 4, 10]
+            .into_iter()
+            .enumerate()
+            .map(|(index, minutes)| StopTime {
+                stop_sequence: index as i32 + 1,
+                stop_id: Some(Id::new(format!("stop-{}", index))),
+                arrival_time: Some(Duration::hours(8) + Duration::minutes(minutes)),
+                departure_time: Some(Duration::hours(8) + Duration::minutes(minutes)),
+                stop_headsign: None,
+                pickup_type: Some(PickupDropOffType::Regular),
+                drop_off_type: Some(PickupDropOffType::Regular),
+                area_reference: None,
+                stop_name: None,
+            })
+            .collect();
+        let trip = WithId::new(
+            Id::new("trip".to_owned()),
+            Trip {
+                line_id: Id::new("line".to_owned()),
+                service_id: None,
+                headsign: None,
+                short_name: None,
+                direction: None,
+                shape_id: None,
+                stops,
+                frequencies: vec![],
+                updated_at: None,
+            },
+        );
+        let mut instance =
+            instantiate_trip_naive(&trip, &date, None, None).expect("trip has stops");
+        for (index, stop) in instance.stops.iter_mut().enumerate() {
+            stop.location = Some(Location {
+                latitude: 54.32 + index as f64 / 100.0,
+                longitude: 10.13,
+                address: None,
+            });
+        }
+        instance
+    }
+
+    #[test]
+    fn trip_maps_include_the_current_segment_unless_schedule_only() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 8).unwrap();
+        let now = date
+            .and_hms_opt(8, 5, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .earliest()
+            .unwrap();
+
+        let map = TripMapDto::new(instance(date), date, None, 0.0, Some(now), None);
+        assert_eq!(map.features.len(), 4);
+        assert_eq!(map.properties.current_segment, Some(1));
+
+        let map = TripMapDto::new(instance(date), date, None, 0.0, None, None);
+        assert_eq!(map.features.len(), 4);
+        assert_eq!(map.properties.current_segment, None);
+        let json = serde_json::to_value(&map).unwrap();
+        assert!(json["properties"].get("currentSegment").is_none());
+    }
+
+    #[test]
+    fn trip_instances_include_labels_only_if_requested() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 8).unwrap();
+        let base_url = Arc::new(BaseUrl::parse("http://localhost").unwrap());
+
+        let dto = TripInstanceDto::new(instance(date), base_url.clone(), None);
+        let json = serde_json::to_value(&dto).unwrap();
+        assert!(json.get("labels").is_none());
+        assert!(json["stops"][0].get("labels").is_none());
+
+        let dto = TripInstanceDto::new(instance(date), base_url, Some(Locale::En));
+        let json = serde_json::to_value(&dto).unwrap();
+        assert_eq!(
+            json["stops"][0]["labels"],
+            serde_json::json!({
+                "pickupType": "Regularly scheduled pickup",
+                "dropOffType": "Regularly scheduled drop off",
+            })
+        );
+    }
+}
@@ -0,0 +1,19 @@
+use chrono::{DateTime, Local};
+
+/// Source of the current time of handlers, e.g. of the default start of trip
+/// searches. A fixed clock makes responses reproducible.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Clock {
+    #[default]
+    System,
+    Fixed(DateTime<Local>),
+}
+
+impl Clock {
+    pub fn now(&self) -> DateTime<Local> {
+        match self {
+            Self::System => Local::now(),
+            Self::Fixed(now) => *now,
+        }
+    }
+}
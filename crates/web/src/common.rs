@@ -12,6 +12,12 @@ use serde::{Deserialize, Serialize};
 
 use crate::hateoas;
 
+pub mod cursor;
+pub mod etag;
+pub mod locale;
+pub mod query;
+pub mod single_flight;
+
 pub type RouteResult<O> = Result<O, RouteErrorResponse>;
 pub type HateoasResult<O> = RouteResult<Json<hateoas::Response<O>>>;
 
@@ -193,6 +199,8 @@ impl From<RequestError> for RouteErrorResponse {
         match value {
             RequestError::NotFound => Self::new(StatusCode::BAD_REQUEST)
                 .with_message("The requested item does not exist."),
+            RequestError::Timeout => Self::new(StatusCode::SERVICE_UNAVAILABLE)
+                .with_message("The request took too long, please try again later."),
             RequestError::Other(other) => {
                 Self::new(StatusCode::INTERNAL_SERVER_ERROR)
                     .with_message(format!("{}", other))
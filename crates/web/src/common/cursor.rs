@@ -0,0 +1,96 @@
+use std::any::type_name;
+
+use axum::http::{Method, StatusCode};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::Deserialize;
+use utility::id::{HasId, Id};
+
+use super::RouteErrorResponse;
+
+/// Number of items per page, if not specified otherwise.
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Maximum number of items per page.
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Query parameters of a keyset paginated collection.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PageParams {
+    pub after: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl PageParams {
+    pub fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE)
+    }
+
+    /// Decodes the `after` cursor, if present.
+    pub fn cursor<T>(
+        &self,
+        method: &Method,
+        uri: &str,
+    ) -> Result<Option<Cursor<T>>, RouteErrorResponse>
+    where
+        T: HasId<IdType = String>,
+    {
+        self.after
+            .as_deref()
+            .map(|encoded| {
+                Cursor::decode(encoded).ok_or_else(|| {
+                    RouteErrorResponse::new(StatusCode::BAD_REQUEST)
+                        .with_message("invalid cursor.")
+                        .with_method(method)
+                        .with_uri(uri)
+                })
+            })
+            .transpose()
+    }
+}
+
+/// Position within a collection ordered by id.
+///
+/// Unlike an offset, a cursor stays valid if items are inserted or renamed while
+/// paging through the collection: items that existed before are neither skipped
+/// nor returned twice.
+pub struct Cursor<T: HasId> {
+    pub id: Id<T>,
+}
+
+impl<T> Cursor<T>
+where
+    T: HasId<IdType = String>,
+{
+    pub fn new(id: Id<T>) -> Self {
+        Self { id }
+    }
+
+    /// Encodes the cursor as an opaque, url safe string.
+    /// The string is bound to the type of the collection, so that a cursor of one
+    /// collection can not be used for another.
+    pub fn encode(&self) -> String {
+        let payload = serde_json::json!([type_name::<T>(), self.id]);
+        URL_SAFE_NO_PAD.encode(payload.to_string())
+    }
+
+    pub fn decode(encoded: &str) -> Option<Self> {
+        let bytes = URL_SAFE_NO_PAD.decode(encoded).ok()?;
+        let (kind, id): (String, String) = serde_json::from_slice(&bytes).ok()?;
+        (kind == type_name::<T>()).then(|| Self::new(Id::new(id)))
+    }
+
+    pub fn into_inner(self) -> Id<T> {
+        self.id
+    }
+}
+
+impl<T> From<Id<T>> for Cursor<T>
+where
+    T: HasId<IdType = String>,
+{
+    fn from(id: Id<T>) -> Self {
+        Self::new(id)
+    }
+}
@@ -0,0 +1,68 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use axum::{
+    http::{
+        header::{ETAG, IF_NONE_MATCH},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+
+/// Weak entity tag of a response, which is derived from the last modification of
+/// the entity and from values, which are computed from other data (e.g. whether a
+/// stop is still served), so that it changes whenever the response does.
+#[derive(Debug, Clone)]
+pub struct EntityTag(String);
+
+impl EntityTag {
+    pub fn new(
+        updated_at: Option<DateTime<Local>>,
+        derived: &impl Serialize,
+    ) -> Self {
+        let mut hasher = DefaultHasher::new();
+        updated_at
+            .map(|updated_at| updated_at.timestamp_micros())
+            .hash(&mut hasher);
+        serde_json::to_string(derived)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        Self(format!("W/\"{:016x}\"", hasher.finish()))
+    }
+
+    /// Whether the client already has the current version according to its
+    /// `If-None-Match` header.
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get_all(IF_NONE_MATCH)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            // weak comparison, so the weakness indicator is ignored.
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == self.opaque())
+    }
+
+    fn opaque(&self) -> &str {
+        self.0.trim_start_matches("W/")
+    }
+
+    /// Responds with `304 Not Modified`, if the client has the current version.
+    /// Otherwise with the response. Both carry the entity tag.
+    pub fn respond(
+        self,
+        headers: &HeaderMap,
+        response: impl IntoResponse,
+    ) -> Response {
+        let mut response = if self.matches(headers) {
+            StatusCode::NOT_MODIFIED.into_response()
+        } else {
+            response.into_response()
+        };
+        if let Ok(value) = HeaderValue::from_str(&self.0) {
+            response.headers_mut().insert(ETAG, value);
+        }
+        response
+    }
+}
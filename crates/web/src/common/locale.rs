@@ -0,0 +1,47 @@
+use axum::http::{header::ACCEPT_LANGUAGE, HeaderMap};
+use model::locale::Locale;
+
+/// Locale of the labels to include in a response, if any were requested with
+/// `include_labels=true`. `lang` takes precedence over `Accept-Language`.
+pub fn requested_labels(
+    include_labels: bool,
+    lang: Option<Locale>,
+    headers: &HeaderMap,
+) -> Option<Locale> {
+    include_labels.then(|| {
+        lang.or_else(|| {
+            headers
+                .get(ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(Locale::from_accept_language)
+        })
+        .unwrap_or_default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lang_takes_precedence_over_accept_language() {
+        let mut english = HeaderMap::new();
+        english.insert(ACCEPT_LANGUAGE, "en-US,en;q=0.9".parse().unwrap());
+        let cases = [
+            (false, Some(Locale::En), &english, None),
+            (true, Some(Locale::De), &english, Some(Locale::De)),
+            (true, None, &english, Some(Locale::En)),
+            (true, None, &HeaderMap::new(), Some(Locale::De)),
+        ];
+        for (include_labels, lang, headers, expected) in cases {
+            assert_eq!(
+                requested_labels(include_labels, lang, headers),
+                expected,
+                "labels of include_labels={} lang={:?} headers={:?}",
+                include_labels,
+                lang,
+                headers
+            );
+        }
+    }
+}
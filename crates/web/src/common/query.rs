@@ -0,0 +1,153 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+};
+use serde::de::DeserializeOwned;
+
+use super::RouteErrorResponse;
+
+/// Largest radius in km, which may be searched around a location.
+pub const MAX_RADIUS_KM: f64 = 10.0;
+
+/// A request parameter, which has the right type, but an unacceptable value.
+#[derive(Debug, Clone)]
+pub struct InvalidParameter {
+    pub parameter: &'static str,
+    pub message: String,
+}
+
+impl InvalidParameter {
+    pub fn new(parameter: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            parameter,
+            message: message.into(),
+        }
+    }
+}
+
+/// Checks of query parameters, which go beyond deserializing them.
+pub trait Validate {
+    fn validate(&self) -> Result<(), InvalidParameter> {
+        Ok(())
+    }
+}
+
+pub fn latitude(parameter: &'static str, value: f64) -> Result<(), InvalidParameter> {
+    if (-90.0..=90.0).contains(&value) {
+        Ok(())
+    } else {
+        Err(InvalidParameter::new(
+            parameter,
+            format!("latitude {} is not between -90 and 90", value),
+        ))
+    }
+}
+
+pub fn longitude(
+    parameter: &'static str,
+    value: f64,
+) -> Result<(), InvalidParameter> {
+    if (-180.0..=180.0).contains(&value) {
+        Ok(())
+    } else {
+        Err(InvalidParameter::new(
+            parameter,
+            format!("longitude {} is not between -180 and 180", value),
+        ))
+    }
+}
+
+pub fn radius(parameter: &'static str, value: f64) -> Result<(), InvalidParameter> {
+    if value > 0.0 && value <= MAX_RADIUS_KM {
+        Ok(())
+    } else {
+        Err(InvalidParameter::new(
+            parameter,
+            format!(
+                "radius {} is not greater than 0 and at most {} km",
+                value, MAX_RADIUS_KM
+            ),
+        ))
+    }
+}
+
+/// Accepts limits between 1 and `max`.
+pub fn limit(
+    parameter: &'static str,
+    value: usize,
+    max: usize,
+) -> Result<(), InvalidParameter> {
+    if (1..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(InvalidParameter::new(
+            parameter,
+            format!("limit {} is not between 1 and {}", value, max),
+        ))
+    }
+}
+
+/// Rejects ranges, which end before they start. The end is the rejected parameter.
+pub fn ordered<T: PartialOrd>(
+    start: Option<&T>,
+    parameter: &'static str,
+    end: Option<&T>,
+) -> Result<(), InvalidParameter> {
+    match (start, end) {
+        (Some(start), Some(end)) if end < start => Err(InvalidParameter::new(
+            parameter,
+            "the end of the range is before its start",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Like `Query`, but also validates the parameters. Malformed and invalid
+/// parameters are rejected with `400 Bad Request`, whose message names the
+/// parameter, instead of axum's plain text rejection.
+#[derive(Debug, Clone)]
+pub struct ValidQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ValidQuery<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = RouteErrorResponse;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let bad_request = || {
+            RouteErrorResponse::new(StatusCode::BAD_REQUEST)
+                .with_method(&parts.method)
+                .with_uri(parts.uri.path())
+        };
+        let query = parts.uri.query().unwrap_or_default();
+        let deserializer = serde_urlencoded::Deserializer::new(
+            url::form_urlencoded::parse(query.as_bytes()),
+        );
+        let params: T =
+            serde_path_to_error::deserialize(deserializer).map_err(|why| {
+                // the path is empty for errors of the whole query, e.g. missing fields.
+                let message = match why.path().to_string().as_str() {
+                    "." => "Malformed query parameters.".to_string(),
+                    parameter => {
+                        format!("Malformed query parameter `{}`.", parameter)
+                    }
+                };
+                bad_request()
+                    .with_message(message)
+                    .with_detailed_information(why.inner().to_string())
+            })?;
+        params.validate().map_err(|why| {
+            bad_request()
+                .with_message(format!("Invalid query parameter `{}`.", why.parameter))
+                .with_detailed_information(why.message)
+        })?;
+        Ok(Self(params))
+    }
+}
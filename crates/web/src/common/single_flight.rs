@@ -0,0 +1,193 @@
+use std::{
+    collections::HashMap,
+    env,
+    future::Future,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::Duration,
+};
+
+use serde::Serialize;
+use tokio::{sync::OnceCell, time::Instant};
+
+/// How long the result of a computation is reused, unless configured otherwise.
+const DEFAULT_REUSE_WINDOW: Duration = Duration::from_secs(2);
+
+/// How results of expensive computations are shared between identical requests.
+#[derive(Debug, Clone)]
+pub struct SingleFlightConfig {
+    /// How long a successful result is reused after it was computed. Zero only
+    /// collapses requests, which arrive while the computation is running.
+    pub reuse_window: Duration,
+}
+
+impl Default for SingleFlightConfig {
+    fn default() -> Self {
+        Self {
+            reuse_window: DEFAULT_REUSE_WINDOW,
+        }
+    }
+}
+
+impl SingleFlightConfig {
+    /// Reads `WEBSERVER_REUSE_WINDOW_MILLIS`. Empty variables are treated as unset.
+    /// Fails with the name of the variable, whose value is invalid.
+    pub fn from_env() -> Result<Self, &'static str> {
+        let reuse_window = match env::var("WEBSERVER_REUSE_WINDOW_MILLIS")
+            .ok()
+            .filter(|value| !value.is_empty())
+        {
+            Some(value) => value
+                .parse()
+                .map(Duration::from_millis)
+                .map_err(|_| "WEBSERVER_REUSE_WINDOW_MILLIS")?,
+            None => DEFAULT_REUSE_WINDOW,
+        };
+        Ok(Self { reuse_window })
+    }
+}
+
+/// Whether a request computed its result or awaited the one of another request.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SingleFlightStats {
+    pub hit: bool,
+    /// Number of requests since the start of the server, which reused a result.
+    pub hits: u64,
+    /// Number of requests since the start of the server, which computed a result.
+    pub misses: u64,
+}
+
+struct Flight<T, E> {
+    result: Arc<OnceCell<Result<T, E>>>,
+    /// `None`, while the computation is running.
+    completed_at: Option<Instant>,
+}
+
+/// Collapses concurrent computations with the same key into one. Requests with
+/// the same key await the result of the first one, which is then reused for a
+/// short window. Failures are only shared with requests, which are already
+/// waiting, but never reused afterwards.
+pub struct SingleFlight<K, T, E> {
+    reuse_window: Duration,
+    flights: Mutex<HashMap<K, Flight<T, E>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<K, T, E> SingleFlight<K, T, E>
+where
+    K: Hash + Eq + Clone,
+    T: Clone,
+    E: Clone,
+{
+    pub fn new(config: &SingleFlightConfig) -> Self {
+        Self {
+            reuse_window: config.reuse_window,
+            flights: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Result of the computation of the key. Computed by `compute`, unless an
+    /// identical computation is running or its result may still be reused. If the
+    /// computing request is cancelled, a waiting one takes over.
+    pub async fn run<F, Fut>(
+        &self,
+        key: K,
+        compute: F,
+    ) -> (Result<T, E>, SingleFlightStats)
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let (cell, hit) = {
+            let mut flights =
+                self.flights.lock().unwrap_or_else(PoisonError::into_inner);
+            let now = Instant::now();
+            flights.retain(|_, flight| {
+                flight.completed_at.is_none_or(|completed_at| {
+                    now - completed_at < self.reuse_window
+                })
+            });
+            match flights.get(&key) {
+                Some(flight) => (flight.result.clone(), true),
+                None => {
+                    let result = Arc::new(OnceCell::new());
+                    flights.insert(
+                        key.clone(),
+                        Flight {
+                            result: result.clone(),
+                            completed_at: None,
+                        },
+                    );
+                    (result, false)
+                }
+            }
+        };
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        let result = cell.get_or_init(compute).await.clone();
+
+        {
+            let mut flights =
+                self.flights.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(flight) = flights.get_mut(&key) {
+                if Arc::ptr_eq(&flight.result, &cell) {
+                    if result.is_err() {
+                        flights.remove(&key);
+                    } else if flight.completed_at.is_none() {
+                        flight.completed_at = Some(Instant::now());
+                    }
+                }
+            }
+        }
+
+        let stats = SingleFlightStats {
+            hit,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        };
+        (result, stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn only_successful_results_are_reused_within_the_window() {
+        let cases = [
+            (Duration::from_secs(60), Ok(1), 1),
+            (Duration::from_secs(60), Err("timeout"), 3),
+            (Duration::ZERO, Ok(1), 3),
+        ];
+        for (i, (reuse_window, result, expected)) in cases.into_iter().enumerate() {
+            let flights = SingleFlight::new(&SingleFlightConfig { reuse_window });
+            let computations = AtomicUsize::new(0);
+            for _ in 0..3 {
+                let (actual, _) = flights
+                    .run("nearby", || async {
+                        computations.fetch_add(1, Ordering::Relaxed);
+                        result
+                    })
+                    .await;
+                assert_eq!(actual, result, "result of case {}", i);
+            }
+            assert_eq!(
+                computations.load(Ordering::Relaxed),
+                expected,
+                "computations of case {}",
+                i
+            );
+        }
+    }
+}
@@ -21,6 +21,7 @@ pub struct Link {
 pub struct Response<T> {
     #[serde(flatten)]
     pub content: T,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub debug_info: HashMap<String, Value>,
     pub links: Vec<Link>,
 }
@@ -38,11 +39,139 @@ impl<T> Response<T> {
         ResponseBuilder::new(content, base_url)
     }
 
+    /// Transforms the content, keeping links and debug info.
+    pub fn map<U, F>(self, f: F) -> Response<U>
+    where
+        F: FnOnce(T) -> U,
+    {
+        Response {
+            content: f(self.content),
+            debug_info: self.debug_info,
+            links: self.links,
+        }
+    }
+
     pub fn json(self) -> Json<Self> {
         Json(self)
     }
 }
 
+/// Values of the `:name` segments of a route, in order.
+pub trait PathParams {
+    const COUNT: usize;
+
+    fn into_values(self) -> Vec<String>;
+}
+
+impl<const N: usize> PathParams for [String; N] {
+    const COUNT: usize = N;
+
+    fn into_values(self) -> Vec<String> {
+        self.into()
+    }
+}
+
+/// Number of `:name` segments of a route template.
+const fn route_param_count(route: &str) -> usize {
+    let bytes = route.as_bytes();
+    let mut count = 0;
+    let mut i = 0;
+    while i + 1 < bytes.len() {
+        if bytes[i] == b'/' && bytes[i + 1] == b':' {
+            count += 1;
+        }
+        i += 1;
+    }
+    count
+}
+
+/// Percent-encodes all but the unreserved characters of RFC 3986, so that a
+/// value stays a single path segment. Dot segments are encoded as well, as they
+/// would be removed from the path.
+fn encode_path_segment(value: &str) -> String {
+    if value == "." || value == ".." {
+        return value.replace('.', "%2E");
+    }
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Path of the route of `R` with the given parameters. Fails to compile, if the
+/// number of parameters does not match the route.
+fn route_path<R, P>(params: P) -> String
+where
+    R: Resource + ?Sized,
+    P: PathParams,
+{
+    const {
+        assert!(
+            route_param_count(R::ROUTE) == P::COUNT,
+            "the path parameters do not match the route"
+        )
+    };
+    let mut params = params.into_values().into_iter();
+    R::ROUTE
+        .split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            // the counts match, so that there is a value for each segment.
+            Some(_) => encode_path_segment(&params.next().unwrap_or_default()),
+            None => segment.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// A resource, that can be linked to.
+///
+/// The route template is declared once and used both to register the route and to
+/// build links to it. Parameters are fields of the implementing type, and their
+/// number is checked against the route, so that a link with missing parameters
+/// does not compile.
+pub trait Resource {
+    /// Route template relative to the module, as registered with the router,
+    /// e.g., `/:id`.
+    const ROUTE: &'static str;
+
+    /// Path of the module the route is nested in, e.g., `/api/v1/stops`.
+    fn module() -> String;
+
+    /// Values of the `:name` segments of the route, in order. Percent-encoded
+    /// when building links.
+    fn path_params(&self) -> impl PathParams {
+        [] as [String; 0]
+    }
+
+    /// Query parameters. Those without a value are omitted.
+    fn query_params(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![]
+    }
+
+    fn href(&self) -> String {
+        let path = route_path::<Self, _>(self.path_params());
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        for (key, value) in self.query_params() {
+            if let Some(value) = value {
+                query.append_pair(key, &value);
+            }
+        }
+        let query = query.finish();
+        format!(
+            "{}{}{}{}",
+            Self::module(),
+            path.trim_end_matches('/'),
+            if query.is_empty() { "" } else { "?" },
+            query
+        )
+    }
+}
+
 pub struct ResponseBuilder<T> {
     pub response: Response<T>,
     pub base_url: Arc<BaseUrl>,
@@ -100,6 +229,22 @@ impl<T> ResponseBuilder<T> {
         self.link_extern_option(relation, url)
     }
 
+    pub fn link_to<R, L>(self, relation: R, resource: &L) -> Self
+    where
+        R: Into<String>,
+        L: Resource,
+    {
+        self.link(relation, resource.href())
+    }
+
+    pub fn link_to_option<R, L>(self, relation: R, resource: Option<L>) -> Self
+    where
+        R: Into<String>,
+        L: Resource,
+    {
+        self.link_option(relation, resource.map(|resource| resource.href()))
+    }
+
     pub fn link_extern<R, H>(mut self, relation: R, hypertext_reference: H) -> Self
     where
         R: Into<String>,
@@ -132,3 +277,75 @@ impl<T> ResponseBuilder<T> {
         self.response
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct InstanceResource {
+        id: String,
+        date: String,
+        limit: Option<usize>,
+    }
+
+    impl Resource for InstanceResource {
+        const ROUTE: &'static str = "/:id/instances/:date";
+
+        fn module() -> String {
+            "/api/v1/trips".to_owned()
+        }
+
+        fn path_params(&self) -> impl PathParams {
+            [self.id.clone(), self.date.clone()]
+        }
+
+        fn query_params(&self) -> Vec<(&'static str, Option<String>)> {
+            vec![
+                ("limit", self.limit.map(|limit| limit.to_string())),
+                ("after", None),
+            ]
+        }
+    }
+
+    struct ListResource;
+
+    impl Resource for ListResource {
+        const ROUTE: &'static str = "/";
+
+        fn module() -> String {
+            "/api/v1/trips".to_owned()
+        }
+    }
+
+    #[test]
+    fn counts_route_params() {
+        assert_eq!(route_param_count("/"), 0);
+        assert_eq!(route_param_count("/debug"), 0);
+        assert_eq!(route_param_count("/:id"), 1);
+        assert_eq!(route_param_count("/:id/instances/:date/map"), 2);
+    }
+
+    #[test]
+    fn builds_hrefs_with_encoded_path_params() {
+        let resource = InstanceResource {
+            id: "re-7".to_owned(),
+            date: "2024-03-08".to_owned(),
+            limit: Some(10),
+        };
+        assert_eq!(
+            resource.href(),
+            "/api/v1/trips/re-7/instances/2024-03-08?limit=10"
+        );
+
+        let resource = InstanceResource {
+            id: "RE 7/12?ü".to_owned(),
+            date: "..".to_owned(),
+            limit: None,
+        };
+        assert_eq!(
+            resource.href(),
+            "/api/v1/trips/RE%207%2F12%3F%C3%BC/instances/%2E%2E"
+        );
+        assert_eq!(ListResource.href(), "/api/v1/trips");
+    }
+}
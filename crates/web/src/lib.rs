@@ -1,23 +1,58 @@
 pub use crate::common::RouteResult;
 
-use axum::{extract::FromRef, routing::get_service, Router};
+use axum::{
+    extract::{FromRef, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use clock::Clock;
+use common::single_flight::SingleFlightConfig;
 use database::PgDatabase;
-use public_transport::client::Client;
+use middleware::{admin_auth::AdminAuthConfig, base_url::BaseUrlConfig};
+use public_transport::{
+    capability::{self, Requirement},
+    client::Client,
+    instrumented::InstrumentedDatabase,
+    notification::Notifier,
+};
+use serde_json::json;
+use static_content::static_content_router;
 use tokio::net::TcpListener;
-use tower_http::services::{ServeDir, ServeFile};
 
 pub mod api;
+pub mod clock;
 pub mod common;
 pub mod hateoas;
 pub mod middleware;
+mod static_content;
+
+/// Database of the web server, which records metrics of all operations.
+pub type WebDatabase = InstrumentedDatabase<PgDatabase>;
+
+/// Capabilities of the database the web server needs.
+pub const REQUIREMENTS: &[Requirement] = &[capability::STOP_SEARCH];
 
 #[derive(Clone, FromRef)]
 pub struct WebState {
-    pub transit_client: Client<PgDatabase>,
+    pub transit_client: Client<WebDatabase>,
+    /// Current time of handlers.
+    pub clock: Clock,
+    /// How links in responses are made absolute.
+    pub base_url: BaseUrlConfig,
+    /// Who may use the admin api.
+    pub admin_auth: AdminAuthConfig,
+    /// How identical expensive requests are collapsed.
+    pub single_flight: SingleFlightConfig,
+    /// Where alerts are sent to, `None` if alerting is not configured.
+    pub notifier: Option<Notifier>,
 }
 
 pub async fn start_web_server(state: WebState) -> std::io::Result<()> {
     let routes = Router::new()
+        .route("/readyz", get(readyz))
+        .with_state(state.clone())
         .nest_service("/api", api::routes(state))
         .fallback_service(static_content_router());
 
@@ -27,12 +62,29 @@ pub async fn start_web_server(state: WebState) -> std::io::Result<()> {
     Ok(())
 }
 
-fn static_content_router() -> Router {
-    Router::new().nest_service(
-        "/",
-        get_service(
-            ServeDir::new("./resources/www/")
-                .not_found_service(ServeFile::new("./resources/www/error404.html")),
-        ),
+/// Ready, if the primary database is reachable. Also reports the replication lag
+/// of the read replica, if there is one.
+async fn readyz(
+    State(WebState { transit_client, .. }): State<WebState>,
+) -> impl IntoResponse {
+    let database = transit_client.database.inner();
+    let is_ready = database.is_reachable().await;
+    let replica = database.replica_status().map(|status| {
+        json!({
+            "lagSecs": status.lag.map(|lag| lag.as_secs_f64()),
+            "servesReads": status.serves_reads,
+        })
+    });
+    let status_code = if is_ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status_code,
+        Json(json!({
+            "ready": is_ready,
+            "replica": replica,
+        })),
     )
 }
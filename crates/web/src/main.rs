@@ -1,68 +1,195 @@
+use std::{env, process};
+
 use database::{DatabaseConnectionInfo, PgDatabase};
-use public_transport::server::Server;
-use web::{start_web_server, WebState};
+use public_transport::{
+    capability,
+    database::Database,
+    instrumented::InstrumentedDatabase,
+    notification::{AlertConfig, Notifier},
+    server::Server,
+};
+use web::{
+    clock::Clock,
+    common::single_flight::SingleFlightConfig,
+    middleware::{admin_auth::AdminAuthConfig, base_url::BaseUrlConfig},
+    start_web_server, WebState,
+};
+
+const DEFAULT_CHANGE_LOG_RETENTION_DAYS: i64 = 7;
+
+/// The parsed config, or exits after logging the variable, whose value is
+/// invalid.
+fn from_env<T>(config: Result<T, &'static str>) -> T {
+    config.unwrap_or_else(|name| {
+        log::error!("invalid value of {} in env.", name);
+        process::exit(1)
+    })
+}
 
 #[tokio::main]
 async fn main() {
     env_logger::init();
 
+    // config, which is read up front to fail before connecting anywhere.
+    let base_url = from_env(BaseUrlConfig::from_env());
+    let single_flight = from_env(SingleFlightConfig::from_env());
+    let alert_config = from_env(AlertConfig::from_env());
+    let change_log_retention_days = from_env(
+        env::var("CHANGE_LOG_RETENTION_DAYS")
+            .ok()
+            .filter(|value| !value.is_empty())
+            .map_or(Ok(DEFAULT_CHANGE_LOG_RETENTION_DAYS), |value| value.parse())
+            .map_err(|_| "CHANGE_LOG_RETENTION_DAYS"),
+    );
+
     // database
-    let database_connection_info = DatabaseConnectionInfo::from_env()
-        .expect("expected database connection info in env.");
-    let database = PgDatabase::connect(database_connection_info)
-        .await
-        .expect("could not connect to database.");
+    let Some(database_connection_info) = DatabaseConnectionInfo::from_env() else {
+        log::error!("expected database connection info in env.");
+        process::exit(1)
+    };
+    let database = match PgDatabase::connect(database_connection_info).await {
+        Ok(database) => database,
+        Err(why) => {
+            log::error!("could not connect to database: {}", why);
+            process::exit(1)
+        }
+    };
+
+    // capabilities
+    match capability::check("web server", web::REQUIREMENTS, database.capabilities())
+    {
+        Ok(missing) => {
+            for requirement in missing {
+                log::warn!("web server runs downgraded: {}.", requirement);
+            }
+        }
+        Err(why) => panic!("{}", why),
+    }
 
     // server
-    let server = Server::new(database.clone());
-    server
-        .collectors::<gtfs::collector::ScheduleCollector>()
-        .await
-        .unwrap();
-    server
-        .collectors::<gtfs::collector::RealtimeCollector>()
-        .await
-        .unwrap();
-    server
-        .collectors::<gbfs::collector::StationsCollector>()
-        .await
-        .unwrap();
-    server
-        .collectors::<gbfs::collector::StatusCollector>()
-        .await
-        .unwrap();
-    server
-        .collectors::<deutsche_bahn::collector::DeutscheBahnCollector>()
-        .await
-        .unwrap();
+    let server = Server::new(InstrumentedDatabase::new(database.clone()));
+    let startups = [
+        server
+            .collectors::<gtfs::collector::ScheduleCollector>()
+            .await
+            .unwrap(),
+        server
+            .collectors::<gtfs::collector::MultiScheduleCollector>()
+            .await
+            .unwrap(),
+        server
+            .collectors::<gtfs::collector::RealtimeCollector>()
+            .await
+            .unwrap(),
+        server
+            .collectors::<gbfs::collector::StationsCollector>()
+            .await
+            .unwrap(),
+        server
+            .collectors::<gbfs::collector::StatusCollector>()
+            .await
+            .unwrap(),
+        server
+            .collectors::<deutsche_bahn::collector::DeutscheBahnCollector>()
+            .await
+            .unwrap(),
+    ]
+    .concat();
+    for startup in &startups {
+        match &startup.error {
+            None => log::info!(
+                "started collector '{}' ({}) of origin {}.",
+                startup.kind,
+                startup.id,
+                startup.origin
+            ),
+            Some(why) => log::error!(
+                "could not start collector '{}' ({}) of origin {}: {}",
+                startup.kind,
+                startup.id,
+                startup.origin,
+                why
+            ),
+        }
+    }
+    // by default, the server runs without the failed collectors.
+    let abort_on_failure = env::var("COLLECTORS_ABORT_ON_FAILURE")
+        .is_ok_and(|value| value == "true" || value == "1");
+    let failed = startups
+        .iter()
+        .filter(|startup| !startup.is_started())
+        .count();
+    if abort_on_failure && failed > 0 {
+        panic!(
+            "{} of {} collectors failed to start.",
+            failed,
+            startups.len()
+        );
+    }
+
+    // changes are flushed to the change log, changes older than the retention are
+    // compacted to the latest of each entity, except for deletions.
+    server.change_log_maintenance(chrono::Duration::days(change_log_retention_days));
+    server.stop_name_sync();
+
+    // alerting
+    let notifier = alert_config.map(Notifier::new);
+    match &notifier {
+        Some(notifier) => {
+            log::info!(
+                "sending alerts to {} webhooks.",
+                notifier.config().webhooks.len()
+            );
+            server.alerting(notifier.clone());
+        }
+        None => log::info!("alerting is disabled, as ALERT_WEBHOOK_URLS is empty."),
+    }
 
     /*
+    // origins are registered by name. a renamed origin is still found by its
+    // former name, while one colliding with another origin is not registered,
+    // so that its collectors are skipped.
     // gtfs nah.sh
-    let gtfs_sh_id = server.origin("GTFS NAH.SH", 1).await.unwrap();
-    server.collector(&gtfs_sh_id, || {
-        gtfs::collector::ScheduleCollector::new(
-            "https://www.connect-info.net/opendata/gtfs/nah.sh/rjqfrkqhgu",
-        )
-    });
-    server.collector(&gtfs_sh_id, || {
-        gtfs::collector::RealtimeCollector::new(
-            "https://gtfsr.vbn.de/DluWlw1jMRKr",
-            Duration::from_secs(60),
-        )
-    });
+    match server.origin("GTFS NAH.SH", 1).await {
+        Ok(gtfs_sh_id) => {
+            server.collector(&gtfs_sh_id, || {
+                gtfs::collector::ScheduleCollector::new(
+                    "https://www.connect-info.net/opendata/gtfs/nah.sh/rjqfrkqhgu",
+                )
+            });
+            server.collector(&gtfs_sh_id, || {
+                gtfs::collector::RealtimeCollector::new(
+                    "https://gtfsr.vbn.de/DluWlw1jMRKr",
+                    Duration::from_secs(60),
+                )
+            });
+        }
+        Err(why) => log::error!("could not register origin 'GTFS NAH.SH': {:?}", why),
+    }
 
     // gbfs donkey kiel
-    let gbfs_donkey_kiel_id = server.origin("GBFS Donkey Kiel", 2).await.unwrap();
-    server.collector(&gbfs_donkey_kiel_id, || {
-        gbfs::collector::StatusCollector::new(
-            "https://stables.donkey.bike/api/public/gbfs/2/donkey_kiel/en/station_status.json"
-        )
-    });
+    match server.origin("GBFS Donkey Kiel", 2).await {
+        Ok(gbfs_donkey_kiel_id) => {
+            server.collector(&gbfs_donkey_kiel_id, || {
+                gbfs::collector::StatusCollector::new(
+                    "https://stables.donkey.bike/api/public/gbfs/2/donkey_kiel/en/station_status.json"
+                )
+            });
+        }
+        Err(why) => {
+            log::error!("could not register origin 'GBFS Donkey Kiel': {:?}", why)
+        }
+    }
     */
 
     // web server
     let web_future = start_web_server(WebState {
-        transit_client: server.client("REST API"),
+        transit_client: server.client("REST API").read_from_replica(),
+        clock: Clock::System,
+        base_url,
+        admin_auth: AdminAuthConfig::from_env(),
+        single_flight,
+        notifier,
     });
 
     let _ = web_future.await;
@@ -0,0 +1,146 @@
+use std::{env, sync::Arc};
+
+use axum::{
+    extract::{self, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::common::RouteErrorResponse;
+
+/// Who may use the admin api, which modifies and deletes data.
+#[derive(Debug, Clone, Default)]
+pub struct AdminAuthConfig {
+    /// Bearer token of admin requests. The admin api is disabled, if it is
+    /// `None`.
+    token: Option<Arc<str>>,
+}
+
+impl AdminAuthConfig {
+    pub fn with_token(token: impl Into<Arc<str>>) -> Self {
+        Self {
+            token: Some(token.into()),
+        }
+    }
+
+    /// Reads `WEBSERVER_ADMIN_TOKEN`. An empty or unset variable disables the
+    /// admin api.
+    pub fn from_env() -> Self {
+        Self {
+            token: env::var("WEBSERVER_ADMIN_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty())
+                .map(Into::into),
+        }
+    }
+
+    /// Whether the headers carry the configured token as
+    /// `Authorization: Bearer <token>`.
+    fn authorize(&self, headers: &HeaderMap) -> Result<(), AdminAuthError> {
+        let token = self.token.as_deref().ok_or(AdminAuthError::Disabled)?;
+        let given = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(AdminAuthError::Unauthorized)?;
+        if constant_time_eq(given.trim().as_bytes(), token.as_bytes()) {
+            Ok(())
+        } else {
+            Err(AdminAuthError::Unauthorized)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AdminAuthError {
+    /// No token is configured.
+    Disabled,
+    /// The token is missing or wrong.
+    Unauthorized,
+}
+
+/// Compares in a time, which only depends on the lengths, so that the token
+/// can not be guessed byte by byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Rejects requests without the admin token with `401 Unauthorized`, and all
+/// requests with `403 Forbidden`, if no token is configured.
+pub async fn admin_auth_middleware(
+    State(config): State<AdminAuthConfig>,
+    req: extract::Request,
+    next: Next,
+) -> Response {
+    match config.authorize(req.headers()) {
+        Ok(()) => next.run(req).await,
+        Err(AdminAuthError::Disabled) => {
+            RouteErrorResponse::new(StatusCode::FORBIDDEN)
+                .with_method(req.method())
+                .with_uri(req.uri().path())
+                .with_message("The admin api is disabled.")
+                .into_response()
+        }
+        Err(AdminAuthError::Unauthorized) => {
+            let mut response = RouteErrorResponse::new(StatusCode::UNAUTHORIZED)
+                .with_method(req.method())
+                .with_uri(req.uri().path())
+                .with_message("A valid admin token is required.")
+                .into_response();
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(authorization: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(authorization).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn requires_the_configured_token() {
+        let config = AdminAuthConfig::with_token("secret");
+        assert_eq!(config.authorize(&headers("Bearer secret")), Ok(()));
+        assert_eq!(
+            config.authorize(&headers("Bearer secreT")),
+            Err(AdminAuthError::Unauthorized)
+        );
+        assert_eq!(
+            config.authorize(&headers("Bearer secret2")),
+            Err(AdminAuthError::Unauthorized)
+        );
+        assert_eq!(
+            config.authorize(&headers("Basic secret")),
+            Err(AdminAuthError::Unauthorized)
+        );
+        assert_eq!(
+            config.authorize(&HeaderMap::new()),
+            Err(AdminAuthError::Unauthorized)
+        );
+    }
+
+    #[test]
+    fn is_disabled_without_token() {
+        let config = AdminAuthConfig::default();
+        assert_eq!(
+            config.authorize(&headers("Bearer ")),
+            Err(AdminAuthError::Disabled)
+        );
+        assert_eq!(
+            config.authorize(&HeaderMap::new()),
+            Err(AdminAuthError::Disabled)
+        );
+    }
+}
@@ -1,10 +1,11 @@
 use axum::{
-    extract::{self},
+    extract::{self, State},
     http::HeaderMap,
     middleware::Next,
     response::IntoResponse,
 };
-use std::sync::Arc;
+use std::{env, sync::Arc};
+use url::Url;
 
 #[derive(Debug, Clone)]
 pub struct BaseUrl {
@@ -14,25 +15,31 @@ pub struct BaseUrl {
 }
 
 impl BaseUrl {
-    pub fn from_headers(headers: &HeaderMap) -> Self {
-        let proto = headers
-            .get("x-forwarded-proto")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("http")
-            .to_string();
+    /// Base url of links in responses to a request with the given headers.
+    ///
+    /// The configured public url takes precedence over the request. Otherwise
+    /// `X-Forwarded-Proto`, `X-Forwarded-Host` and `X-Forwarded-Prefix` are used,
+    /// if they are trusted, falling back to the `Host` header.
+    pub fn from_request(headers: &HeaderMap, config: &BaseUrlConfig) -> Self {
+        if let Some(public_url) = &config.public_url {
+            return public_url.clone();
+        }
+        let forwarded = |name: &str| {
+            config
+                .trust_forwarded_headers
+                .then(|| headers.get(name))
+                .flatten()
+                .and_then(|v| v.to_str().ok())
+        };
 
-        let host = headers
-            .get("x-forwarded-host")
-            .and_then(|v| v.to_str().ok())
+        let proto = forwarded("x-forwarded-proto").unwrap_or("http").to_string();
+
+        let host = forwarded("x-forwarded-host")
             .or_else(|| headers.get("host").and_then(|v| v.to_str().ok()))
             .unwrap_or("localhost")
             .to_string();
 
-        let prefix = headers
-            .get("x-forwarded-prefix")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("")
-            .to_string();
+        let prefix = forwarded("x-forwarded-prefix").unwrap_or("").to_string();
 
         BaseUrl {
             proto,
@@ -41,6 +48,21 @@ impl BaseUrl {
         }
     }
 
+    /// Parses an absolute url like `https://example.org/transit`. Its path becomes
+    /// the prefix of all links. `None`, if the url has no host.
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = Url::parse(url).ok()?;
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str()?, port),
+            None => url.host_str()?.to_string(),
+        };
+        Some(BaseUrl {
+            proto: url.scheme().to_string(),
+            host,
+            prefix: url.path().trim_end_matches('/').to_string(),
+        })
+    }
+
     pub fn full_url<S: Into<String>>(&self, path: S) -> String {
         format!(
             "{}://{}{}{}",
@@ -52,12 +74,160 @@ impl BaseUrl {
     }
 }
 
-pub async fn base_url_middleware(req: extract::Request, next: Next) -> impl IntoResponse {
+/// How the base url of links in responses is determined, e.g. behind a reverse
+/// proxy, which terminates TLS.
+#[derive(Debug, Clone, Default)]
+pub struct BaseUrlConfig {
+    /// Overrides the base url derived from requests.
+    pub public_url: Option<BaseUrl>,
+    /// Whether the `X-Forwarded-*` headers are set by a trusted reverse proxy.
+    /// Otherwise clients could make the server link to arbitrary hosts.
+    pub trust_forwarded_headers: bool,
+}
+
+impl BaseUrlConfig {
+    /// Reads `WEBSERVER_PUBLIC_URL` and `WEBSERVER_TRUST_FORWARDED_HEADERS`
+    /// (`false` by default). Empty variables are treated as unset. Fails with the
+    /// name of the variable, whose value is invalid.
+    pub fn from_env() -> Result<Self, &'static str> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, &'static str> {
+        let var = |name| var(name).filter(|value| !value.is_empty());
+        let public_url = match var("WEBSERVER_PUBLIC_URL") {
+            Some(url) => Some(BaseUrl::parse(&url).ok_or("WEBSERVER_PUBLIC_URL")?),
+            None => None,
+        };
+        let trust_forwarded_headers = match var("WEBSERVER_TRUST_FORWARDED_HEADERS") {
+            Some(value) => value
+                .parse()
+                .map_err(|_| "WEBSERVER_TRUST_FORWARDED_HEADERS")?,
+            None => false,
+        };
+        Ok(Self {
+            public_url,
+            trust_forwarded_headers,
+        })
+    }
+}
+
+pub async fn base_url_middleware(
+    State(config): State<BaseUrlConfig>,
+    req: extract::Request,
+    next: Next,
+) -> impl IntoResponse {
     let headers = req.headers().clone();
-    let base_url = BaseUrl::from_headers(&headers);
+    let base_url = BaseUrl::from_request(&headers, &config);
 
     let mut req = req;
     req.extensions_mut().insert(Arc::new(base_url));
 
     next.run(req).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn config(vars: &[(&str, &str)]) -> Result<BaseUrlConfig, &'static str> {
+        BaseUrlConfig::from_vars(|name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn ignores_forwarded_headers_by_default() {
+        let headers = headers(&[
+            ("host", "localhost:8080"),
+            ("x-forwarded-proto", "https"),
+            ("x-forwarded-host", "evil.example"),
+            ("x-forwarded-prefix", "/evil"),
+        ]);
+        let cases = [
+            (BaseUrlConfig::default(), "http://localhost:8080/stops"),
+            (config(&[]).unwrap(), "http://localhost:8080/stops"),
+            (
+                BaseUrlConfig {
+                    public_url: None,
+                    trust_forwarded_headers: true,
+                },
+                "https://evil.example/evil/stops",
+            ),
+            (
+                BaseUrlConfig {
+                    public_url: BaseUrl::parse("https://example.org:8443/transit/"),
+                    trust_forwarded_headers: true,
+                },
+                "https://example.org:8443/transit/stops",
+            ),
+        ];
+        for (config, expected) in cases {
+            assert_eq!(
+                BaseUrl::from_request(&headers, &config).full_url("/stops"),
+                expected,
+                "config `{:?}`",
+                config
+            );
+        }
+    }
+
+    #[test]
+    fn falls_back_to_localhost_without_host() {
+        let url = BaseUrl::from_request(&HeaderMap::new(), &BaseUrlConfig::default());
+        assert_eq!(url.full_url("/"), "http://localhost/");
+    }
+
+    #[test]
+    fn reads_the_config_from_vars() {
+        let parsed = config(&[
+            ("WEBSERVER_PUBLIC_URL", "https://example.org"),
+            ("WEBSERVER_TRUST_FORWARDED_HEADERS", "true"),
+        ])
+        .unwrap();
+        assert_eq!(
+            parsed.public_url.map(|url| url.full_url("/")),
+            Some("https://example.org/".to_owned())
+        );
+        assert!(parsed.trust_forwarded_headers);
+
+        let parsed = config(&[
+            ("WEBSERVER_PUBLIC_URL", ""),
+            ("WEBSERVER_TRUST_FORWARDED_HEADERS", ""),
+        ])
+        .unwrap();
+        assert!(parsed.public_url.is_none());
+        assert!(!parsed.trust_forwarded_headers);
+    }
+
+    #[test]
+    fn names_the_invalid_var() {
+        let cases = [
+            (
+                ("WEBSERVER_PUBLIC_URL", "example.org"),
+                "WEBSERVER_PUBLIC_URL",
+            ),
+            (
+                ("WEBSERVER_PUBLIC_URL", "data:text"),
+                "WEBSERVER_PUBLIC_URL",
+            ),
+            (
+                ("WEBSERVER_TRUST_FORWARDED_HEADERS", "yes"),
+                "WEBSERVER_TRUST_FORWARDED_HEADERS",
+            ),
+        ];
+        for (var, expected) in cases {
+            assert_eq!(config(&[var]).err(), Some(expected), "var `{:?}`", var);
+        }
+    }
+}
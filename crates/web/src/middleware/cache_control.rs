@@ -0,0 +1,43 @@
+use axum::{
+    extract,
+    http::{header::CACHE_CONTROL, HeaderValue},
+    middleware::Next,
+    response::IntoResponse,
+};
+
+/// Assets with a content hash in their file name never change.
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+/// Everything else, especially `index.html`, changes with every deployment and has
+/// to be revalidated.
+const REVALIDATE: &str = "no-cache";
+
+/// Minimum length of a content hash within a file name.
+const MIN_HASH_LENGTH: usize = 8;
+
+/// Sets the `Cache-Control` header of successful static content responses.
+pub async fn cache_control_middleware(
+    req: extract::Request,
+    next: Next,
+) -> impl IntoResponse {
+    let is_hashed = is_hashed_asset(req.uri().path());
+    let mut response = next.run(req).await;
+    if response.status().is_success() {
+        let value = if is_hashed { IMMUTABLE } else { REVALIDATE };
+        response
+            .headers_mut()
+            .insert(CACHE_CONTROL, HeaderValue::from_static(value));
+    }
+    response
+}
+
+/// Whether the file name contains a content hash, e.g., `index.3f9a1c2b.js`.
+fn is_hashed_asset(path: &str) -> bool {
+    let file_name = path.rsplit('/').next().unwrap_or_default();
+    let Some((stem, _extension)) = file_name.rsplit_once('.') else {
+        return false;
+    };
+    stem.split(['.', '-', '_']).skip(1).any(|part| {
+        part.len() >= MIN_HASH_LENGTH && part.chars().all(|c| c.is_ascii_hexdigit())
+    })
+}
@@ -0,0 +1,367 @@
+use axum::{
+    body::{to_bytes, Body, HttpBody},
+    extract,
+    http::{
+        header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE, VARY},
+        HeaderMap, HeaderValue, Method, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{
+    ser::{SerializeMap, SerializeStruct},
+    Serialize, Serializer,
+};
+use serde_json::Value;
+
+use crate::common::RouteErrorResponse;
+
+const APPLICATION_JSON: &str = "application/json";
+const APPLICATION_XML: &str = "application/xml";
+
+/// Name of the root element of XML documents.
+const ROOT_ELEMENT: &str = "response";
+
+/// Name of the elements of arrays.
+const ITEM_ELEMENT: &str = "item";
+
+/// Name of the elements of object keys, that are no valid element names, e.g.
+/// ids. The key is kept as attribute.
+const ENTRY_ELEMENT: &str = "entry";
+
+/// Responses larger than this are not rendered as XML, as they are converted in
+/// memory. Large lists are paged well below this.
+const MAX_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Json,
+    Xml,
+}
+
+/// Renders JSON responses as XML, if the `Accept` header prefers it. Handlers
+/// only produce JSON, so both formats have the same content, including links.
+pub async fn content_negotiation_middleware(
+    req: extract::Request,
+    next: Next,
+) -> Response {
+    let format = preferred_format(req.headers());
+    let method = req.method().clone();
+    let uri = req.uri().path().to_string();
+    let mut response = next.run(req).await;
+    response
+        .headers_mut()
+        .append(VARY, HeaderValue::from_static("accept"));
+    if format != Format::Xml || !is_json(response.headers()) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    if body.size_hint().lower() > MAX_BODY_SIZE as u64 {
+        return too_large(&method, uri);
+    }
+    let bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+        Ok(bytes) => bytes,
+        Err(_) => return too_large(&method, uri),
+    };
+    let xml = serde_json::from_slice::<Value>(&bytes)
+        .map_err(|why| why.to_string())
+        .and_then(|value| to_xml(&value).map_err(|why| why.to_string()));
+    match xml {
+        Ok(xml) => {
+            parts.headers.remove(CONTENT_LENGTH);
+            parts
+                .headers
+                .insert(CONTENT_TYPE, HeaderValue::from_static(APPLICATION_XML));
+            Response::from_parts(parts, Body::from(xml))
+        }
+        Err(why) => {
+            tracing::error!(%method, uri, why, "could not render response as xml");
+            RouteErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
+                .with_method(&method)
+                .with_uri(uri)
+                .with_message("The response could not be rendered as XML.")
+                .into_response()
+        }
+    }
+}
+
+fn too_large(method: &Method, uri: String) -> Response {
+    RouteErrorResponse::new(StatusCode::NOT_ACCEPTABLE)
+        .with_method(method)
+        .with_uri(uri)
+        .with_message("The response is too large to be rendered as XML.")
+        .into_response()
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with(APPLICATION_JSON))
+}
+
+/// The format with the highest quality in the `Accept` header. JSON, unless XML
+/// is preferred.
+fn preferred_format(headers: &HeaderMap) -> Format {
+    let mut json = 0.0;
+    let mut xml = 0.0;
+    let accepted = headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+    for media_range in accepted {
+        let mut params = media_range.split(';').map(str::trim);
+        let media_type = params.next().unwrap_or_default().to_ascii_lowercase();
+        let quality = params
+            .filter_map(|param| param.strip_prefix("q="))
+            .find_map(|quality| quality.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        match media_type.as_str() {
+            APPLICATION_XML | "text/xml" => xml = f32::max(xml, quality),
+            APPLICATION_JSON | "application/*" | "*/*" => {
+                json = f32::max(json, quality)
+            }
+            _ => {}
+        }
+    }
+    if xml > json {
+        Format::Xml
+    } else {
+        Format::Json
+    }
+}
+
+/// Renders the value as XML document. Object keys become elements, array items
+/// become `item` elements and null values are omitted.
+fn to_xml(value: &Value) -> Result<Vec<u8>, serde_xml_rs::Error> {
+    let mut xml = Vec::new();
+    serde_xml_rs::to_writer(&mut xml, &Document(value))?;
+    Ok(xml)
+}
+
+/// The root element, which the serializer only writes for structs.
+struct Document<'a>(&'a Value);
+
+impl Serialize for Document<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut document = serializer.serialize_struct(ROOT_ELEMENT, 1)?;
+        document.serialize_field("$value", &Content(self.0))?;
+        document.end()
+    }
+}
+
+/// The content of an element. The serializer names the elements of map values by
+/// their key.
+struct Content<'a>(&'a Value);
+
+impl Serialize for Content<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Value::Null => serializer.serialize_unit(),
+            Value::Bool(value) => serializer.serialize_str(&value.to_string()),
+            Value::Number(value) => serializer.serialize_str(&value.to_string()),
+            Value::String(value) => serializer.serialize_str(value),
+            Value::Array(items) => {
+                // entries instead of a sequence, which would name all items after
+                // the last key of an object item.
+                let mut map = serializer.serialize_map(Some(items.len()))?;
+                for item in items.iter().filter(|item| !item.is_null()) {
+                    map.serialize_entry(ITEM_ELEMENT, &Content(item))?;
+                }
+                map.end()
+            }
+            Value::Object(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in
+                    entries.iter().filter(|(_, value)| !value.is_null())
+                {
+                    if is_element_name(key) {
+                        map.serialize_entry(key, &Content(value))?;
+                    } else {
+                        map.serialize_entry(ENTRY_ELEMENT, &Entry { key, value })?;
+                    }
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+/// Element of an object key, that is no valid element name.
+struct Entry<'a> {
+    key: &'a str,
+    value: &'a Value,
+}
+
+impl Serialize for Entry<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entry = serializer.serialize_struct(ENTRY_ELEMENT, 2)?;
+        entry.serialize_field("@key", self.key)?;
+        entry.serialize_field("$value", &Content(self.value))?;
+        entry.end()
+    }
+}
+
+/// Whether the name is a valid XML element name, restricted to ASCII.
+fn is_element_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        && !name.to_ascii_lowercase().starts_with("xml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hateoas::{self, Link};
+    use model::{
+        stop::{Location, Stop},
+        WithId,
+    };
+    use serde_json::json;
+    use serde_xml_rs::EventReader;
+    use utility::id::Id;
+
+    struct Element {
+        name: String,
+        children: Vec<(String, Value)>,
+        text: String,
+    }
+
+    /// Reads the XML rendering back, so that it can be compared to the JSON.
+    /// Scalars are read as strings and elements without content as `""`.
+    fn from_xml(xml: &[u8]) -> Value {
+        use xml::reader::XmlEvent;
+
+        let mut stack: Vec<Element> = vec![];
+        let mut root = None;
+        for event in EventReader::new(xml) {
+            match event.expect("xml is well-formed") {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => {
+                    let name = attributes
+                        .into_iter()
+                        .find(|attribute| attribute.name.local_name == "key")
+                        .map(|attribute| attribute.value)
+                        .unwrap_or(name.local_name);
+                    stack.push(Element {
+                        name,
+                        children: vec![],
+                        text: String::new(),
+                    });
+                }
+                XmlEvent::Characters(text) => stack.last_mut().unwrap().text += &text,
+                XmlEvent::EndElement { .. } => {
+                    let Element {
+                        name,
+                        children,
+                        text,
+                    } = stack.pop().unwrap();
+                    let value = if children.is_empty() {
+                        Value::String(text)
+                    } else if children.iter().all(|(name, _)| name == ITEM_ELEMENT) {
+                        Value::Array(children.into_iter().map(|(_, c)| c).collect())
+                    } else {
+                        Value::Object(children.into_iter().collect())
+                    };
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push((name, value)),
+                        None => root = Some((name, value)),
+                    }
+                }
+                _ => {}
+            }
+        }
+        let (name, value) = root.expect("xml has a root element");
+        assert_eq!(name, ROOT_ELEMENT);
+        value
+    }
+
+    /// The JSON as read back from XML.
+    fn as_read_from_xml(value: Value) -> Value {
+        match value {
+            Value::Bool(value) => Value::String(value.to_string()),
+            Value::Number(value) => Value::String(value.to_string()),
+            Value::Array(items) if items.iter().all(Value::is_null) => {
+                Value::String(String::new())
+            }
+            Value::Array(items) => Value::Array(
+                items
+                    .into_iter()
+                    .filter(|item| !item.is_null())
+                    .map(as_read_from_xml)
+                    .collect(),
+            ),
+            Value::Object(entries) if entries.values().all(Value::is_null) => {
+                Value::String(String::new())
+            }
+            Value::Object(entries) => Value::Object(
+                entries
+                    .into_iter()
+                    .filter(|(_, value)| !value.is_null())
+                    .map(|(key, value)| (key, as_read_from_xml(value)))
+                    .collect(),
+            ),
+            value => value,
+        }
+    }
+
+    #[test]
+    fn xml_has_the_data_of_the_json() {
+        let stop = WithId::new(
+            Id::new("stop-1".to_owned()),
+            Stop {
+                name: Some("Kiel Hbf <Gleis 1 & 2>".to_owned()),
+                description: None,
+                parent_id: None,
+                location: Some(Location {
+                    latitude: 54.3149,
+                    longitude: 10.1317,
+                    address: None,
+                }),
+                platform_code: Some("1".to_owned()),
+                amenities: vec![],
+                updated_at: None,
+            },
+        );
+        let mut response = hateoas::Response::new(stop);
+        response.links = ["self", "departures"]
+            .into_iter()
+            .map(|relation| Link {
+                relation: relation.to_owned(),
+                hypertext_reference: format!("/api/v1/stops/stop-1/{}", relation),
+            })
+            .collect();
+        response
+            .debug_info
+            .insert("42".to_owned(), json!({ "merged": [true, null, [1, 2.5]] }));
+        let json = serde_json::to_value(&response).unwrap();
+
+        let xml = to_xml(&json).expect("json is rendered as xml");
+
+        assert_eq!(from_xml(&xml), as_read_from_xml(json));
+    }
+
+    #[test]
+    fn prefers_json_unless_xml_is_preferred() {
+        let cases = [
+            ("application/xml", Format::Xml),
+            ("text/xml", Format::Xml),
+            ("application/json", Format::Json),
+            ("*/*", Format::Json),
+            ("application/xml;q=0.5, application/json", Format::Json),
+            ("application/json;q=0.5, application/xml", Format::Xml),
+            ("text/html", Format::Json),
+        ];
+        for (accept, expected) in cases {
+            let mut headers = HeaderMap::new();
+            headers.insert(ACCEPT, HeaderValue::from_static(accept));
+            assert_eq!(preferred_format(&headers), expected, "accept `{}`", accept);
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use axum::{
+    extract,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use public_transport::deadline;
+use tokio::time::{self, Instant};
+
+use crate::common::RouteErrorResponse;
+
+/// Time, in which a request has to be answered. Database operations of the
+/// request are cancelled by the database, once it is exceeded.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Answers with `503 Service Unavailable`, if the request is not answered in
+/// time. Database operations get the remaining time as their deadline.
+pub async fn deadline_middleware(req: extract::Request, next: Next) -> Response {
+    let method = req.method().clone();
+    let uri = req.uri().path().to_string();
+    let deadline = Instant::now() + REQUEST_TIMEOUT;
+    match time::timeout_at(deadline, deadline::scope(deadline, next.run(req))).await {
+        Ok(response) => response,
+        Err(_) => {
+            tracing::warn!(%method, uri, "request timed out");
+            RouteErrorResponse::new(StatusCode::SERVICE_UNAVAILABLE)
+                .with_method(&method)
+                .with_uri(uri)
+                .with_message("The request took too long, please try again later.")
+                .into_response()
+        }
+    }
+}
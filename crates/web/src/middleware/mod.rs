@@ -1 +1,5 @@
+pub mod admin_auth;
 pub mod base_url;
+pub mod cache_control;
+pub mod content_negotiation;
+pub mod deadline;
@@ -0,0 +1,210 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use axum::{
+    http::StatusCode,
+    response::Html,
+    routing::{get, get_service},
+    Router,
+};
+use tower_http::services::{ServeDir, ServeFile};
+
+use crate::middleware::cache_control::cache_control_middleware;
+
+/// Directory of the frontend, if not configured otherwise by `WEB_STATIC_ROOT`.
+const DEFAULT_STATIC_ROOT: &str = "./resources/www/";
+
+/// Minimal frontend, served if the static root does not exist.
+const FALLBACK_INDEX: &str = include_str!("../resources/fallback/index.html");
+const FALLBACK_NOT_FOUND: &str = include_str!("../resources/fallback/error404.html");
+
+/// Serves the frontend from the static root, or the embedded fallback frontend if
+/// the static root does not exist.
+pub(crate) fn static_content_router() -> Router {
+    let root = env::var("WEB_STATIC_ROOT")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_STATIC_ROOT));
+    root_router(&root)
+}
+
+/// Serves the frontend from the given static root, see `static_content_router`.
+fn root_router(root: &Path) -> Router {
+    let router = if root.is_dir() {
+        println!("Serving frontend from '{}'.", root.display());
+        directory_router(root)
+    } else {
+        println!(
+            "Static root '{}' does not exist. Serving embedded fallback frontend.",
+            root.display()
+        );
+        fallback_router()
+    };
+    router.layer(axum::middleware::from_fn(cache_control_middleware))
+}
+
+fn directory_router(root: &Path) -> Router {
+    let not_found = root.join("error404.html");
+    let serve_dir = ServeDir::new(root);
+    let service = if not_found.is_file() {
+        get_service(serve_dir.not_found_service(ServeFile::new(not_found)))
+    } else {
+        get_service(serve_dir.not_found_service(get(fallback_not_found)))
+    };
+    Router::new().nest_service("/", service)
+}
+
+fn fallback_router() -> Router {
+    Router::new()
+        .route("/", get(fallback_index))
+        .route("/index.html", get(fallback_index))
+        .fallback(fallback_not_found)
+}
+
+async fn fallback_index() -> Html<&'static str> {
+    Html(FALLBACK_INDEX)
+}
+
+async fn fallback_not_found() -> (StatusCode, Html<&'static str>) {
+    (StatusCode::NOT_FOUND, Html(FALLBACK_NOT_FOUND))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process};
+
+    use axum::{
+        body::{to_bytes, Body},
+        http::{header::CACHE_CONTROL, Request},
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+
+    /// Static root of a test, removed when dropped.
+    struct StaticRoot {
+        path: PathBuf,
+    }
+
+    impl StaticRoot {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let path = env::temp_dir().join(format!(
+                "web-static-{}-{}",
+                process::id(),
+                name
+            ));
+            fs::create_dir_all(&path).expect("static root is created");
+            for (file, content) in files {
+                fs::write(path.join(file), content).expect("file is written");
+            }
+            Self { path }
+        }
+    }
+
+    impl Drop for StaticRoot {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+
+    /// Status, cache control header and body of the response.
+    async fn get(router: &Router, uri: &str) -> (StatusCode, Option<String>, String) {
+        let response = router
+            .clone()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let cache_control = response
+            .headers()
+            .get(CACHE_CONTROL)
+            .map(|value| value.to_str().unwrap().to_owned());
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (
+            status,
+            cache_control,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    fn expected(
+        status: StatusCode,
+        cache_control: Option<&str>,
+        body: &str,
+    ) -> (StatusCode, Option<String>, String) {
+        (status, cache_control.map(str::to_owned), body.to_owned())
+    }
+
+    #[tokio::test]
+    async fn frontend_is_served_from_the_directory() {
+        let root = StaticRoot::new(
+            "directory",
+            &[
+                ("index.html", "<p>index</p>"),
+                ("error404.html", "<p>not found</p>"),
+                ("app.3f9a1c2b.js", "app();"),
+            ],
+        );
+        let router = root_router(&root.path);
+        let cases = [
+            (
+                "/",
+                expected(StatusCode::OK, Some("no-cache"), "<p>index</p>"),
+            ),
+            (
+                "/index.html",
+                expected(StatusCode::OK, Some("no-cache"), "<p>index</p>"),
+            ),
+            (
+                "/app.3f9a1c2b.js",
+                expected(
+                    StatusCode::OK,
+                    Some("public, max-age=31536000, immutable"),
+                    "app();",
+                ),
+            ),
+            (
+                "/missing.html",
+                expected(StatusCode::NOT_FOUND, None, "<p>not found</p>"),
+            ),
+        ];
+        for (uri, expected) in cases {
+            assert_eq!(get(&router, uri).await, expected, "response of {}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_error_page_of_the_directory_is_embedded() {
+        let root = StaticRoot::new("without-error-page", &[("index.html", "index")]);
+        let router = root_router(&root.path);
+        assert_eq!(
+            get(&router, "/missing.html").await,
+            expected(StatusCode::NOT_FOUND, None, FALLBACK_NOT_FOUND)
+        );
+    }
+
+    #[tokio::test]
+    async fn embedded_frontend_is_served_without_the_directory() {
+        let router = root_router(
+            &env::temp_dir().join(format!("web-static-{}-missing", process::id())),
+        );
+        let cases = [
+            (
+                "/",
+                expected(StatusCode::OK, Some("no-cache"), FALLBACK_INDEX),
+            ),
+            (
+                "/index.html",
+                expected(StatusCode::OK, Some("no-cache"), FALLBACK_INDEX),
+            ),
+            (
+                "/app.3f9a1c2b.js",
+                expected(StatusCode::NOT_FOUND, None, FALLBACK_NOT_FOUND),
+            ),
+        ];
+        for (uri, expected) in cases {
+            assert_eq!(get(&router, uri).await, expected, "response of {}", uri);
+        }
+    }
+}